@@ -1,11 +1,81 @@
 //! A macro for making bit sets (see [`bitset!`]).
+//!
+//! With the `serde` feature, the types it makes also implement `Serialize` and `Deserialize`.
 
 #![no_std]
 
+use core::ops::{BitAnd, BitOr, BitXor, Not};
+
 /// A macro for making bitsets.
+///
+/// The bits are stored in a primitive integer type, or in an array of `u64`s (like `[u64; 16]`)
+/// for sets with more bits than that. Array-backed sets don't implement [`BitSet`], and their
+/// formatting as numbers ignores width and fill.
+///
+/// Attributes on the set are passed through, so extra traits can be derived with `#[derive]` as
+/// usual. Options for the macro itself go in a `#[bitset(...)]` attribute before any others:
+///
+/// - `ord` orders sets by their raw bits as a number, and implements [`Ord`]. Otherwise, sets are
+///   only partially ordered, with one greater than another if it has every bit the other does.
+/// - `no_bytemuck` leaves out the [`bytemuck`] implementations, for when they don't apply to the
+///   representation.
+///
+/// ```
+/// bitset::bitset!(
+///     #[bitset(ord)]
+///     #[derive(Hash)]
+///     /// Some flags.
+///     pub Flags(u8) {
+///         /// The first flag.
+///         A,
+///         /// The second flag.
+///         B,
+///     }
+/// );
+/// assert!(Flags::A < Flags::B);
+/// ```
 #[macro_export]
 macro_rules! bitset {
     (
+        #[bitset( $( $opt:ident ),* $(,)? )]
+        $( $rest:tt )*
+    ) => {
+        $crate::__bitset_parse! { [$( $opt )*] $( $rest )* }
+    };
+    ( $( $rest:tt )* ) => {
+        $crate::__bitset_parse! { [] $( $rest )* }
+    };
+}
+
+/// Parse the body of [`bitset!`] once its options have been taken off, and work out whether the
+/// bits are stored in an integer or an array of words.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bitset_parse {
+    (
+        [$( $opt:ident )*]
+        $( #[$set_meta:meta] )*
+        $pub:vis $name:ident([u64; $words:expr]) {
+            $(
+                $( #[$bit_meta:meta] )*
+                $bit:ident $( = $disc:expr)? ),*
+            $(,)?
+        }
+    ) => {
+        $crate::__bitset_impl! {
+            words;
+            [$( $opt )*];
+            $( #[$set_meta] )*
+            $pub $name([u64; $words]) {
+                $(
+                    $( #[$bit_meta] )*
+                    $bit $( = $disc )?
+                ),*
+            }
+        }
+    };
+    (
+        [$( $opt:ident )*]
         $( #[$set_meta:meta] )*
         $pub:vis $name:ident($repr:ty) {
             $(
@@ -13,46 +83,146 @@ macro_rules! bitset {
                 $bit:ident $( = $disc:expr)? ),*
             $(,)?
         }
+    ) => {
+        $crate::__bitset_impl! {
+            int;
+            [$( $opt )*];
+            $( #[$set_meta] )*
+            $pub $name($repr) {
+                $(
+                    $( #[$bit_meta] )*
+                    $bit $( = $disc )?
+                ),*
+            }
+        }
+    };
+}
+
+/// The implementation of [`bitset!`], once it's worked out whether the bits are stored in an
+/// integer (`int`) or an array of words (`words`).
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bitset_impl {
+    (
+        $kind:ident;
+        [$( $opt:ident )*];
+        $( #[$set_meta:meta] )*
+        $pub:vis $name:ident($repr:ty) {
+            $(
+                $( #[$bit_meta:meta] )*
+                $bit:ident $( = $disc:expr)? ),*
+        }
     ) => {$crate::__macro_export::paste! {
             $( #[$set_meta] )*
             #[derive(Clone, Copy, Debug, PartialEq, Eq)]
             #[repr(transparent)]
             $pub struct $name($repr);
+
+            #[doc = ::core::concat!("A single bit of [`", ::core::stringify!($name), "`].")]
+            // No `repr`, so the compiler sizes the discriminant to fit however many bits there are.
+            #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            $pub enum [< $name Flag >] {
+                $(
+                    $( #[$bit_meta] )*
+                    $bit,
+                )*
+            }
+
             const _: () = {
-                use ::core::ops::BitOr;
+                use ::core::ops::{BitAnd, BitOr, BitXor, Not, Sub};
+
+                $( $crate::__bitset_check_option!($opt); )*
 
                 /// Constructors
                 impl $name {
                     $(
                         $( #[$bit_meta] )*
-                        pub const [< $bit:snake:upper >]: Self = Self(1 << (Offsets::$bit as usize));
+                        pub const [< $bit:snake:upper >]: Self = Self(raw_bit(Offsets::$bit as usize));
                     )*
 
                     /// Make a value with no bits set.
-                    pub const fn empty() -> Self { Self(0) }
+                    pub const fn empty() -> Self { Self(ZERO) }
 
                     /// Make a value with every bit set.
-                    pub const fn all() -> Self { Self(const { $( Self::[< $bit:snake:upper >].0 |)* 0 }) }
+                    pub const fn all() -> Self {
+                        let mut all = ZERO;
+                        $( all = raw_or(all, Self::[< $bit:snake:upper >].0); )*
+                        Self(all)
+                    }
+
+                    /// The set of every defined bit, the same as [`Self::all`].
+                    pub const ALL_BITS: Self = Self::all();
+
+                    /// The number of defined bits.
+                    // Counted from the declarations, so that the type of `ALL_FLAGS` doesn't
+                    // depend on evaluating every bit.
+                    pub const NUM_BITS: u32 = [$( ::core::stringify!($bit) ),*].len() as u32;
+
+                    /// Every bit, one at a time, in the order they're declared.
+                    pub const ALL_FLAGS: [[< $name Flag >]; Self::NUM_BITS as usize] =
+                        [$( [< $name Flag >]::$bit ),*];
 
                     /// The raw bits set in [`Self::all`].
                     const MASK: $repr = Self::all().0;
+
+                    /// Make a value from its raw bits, failing if any bits which aren't defined
+                    /// are set.
+                    ///
+                    /// Converting with [`From`] drops unknown bits instead.
+                    pub const fn try_from_repr(
+                        repr: $repr,
+                    ) -> ::core::result::Result<Self, $crate::UnknownBits<$repr>> {
+                        let unknown = raw_and(repr, raw_not(Self::MASK));
+                        if raw_is_zero(unknown) {
+                            Ok(Self(repr))
+                        } else {
+                            Err($crate::UnknownBits(unknown))
+                        }
+                    }
+
+                    /// Make a value from its raw bits, keeping any bits which aren't defined.
+                    ///
+                    /// Converting with [`From`] drops unknown bits instead.
+                    pub const fn from_repr_retain(repr: $repr) -> Self {
+                        Self(repr)
+                    }
                 }
 
                 /// Functions for manipulating values.
                 impl $name {
                     /// Get all bits set in either input.
                     pub const fn bit_or(self, other: Self) -> Self {
-                        Self(self.0 | other.0)
+                        Self(raw_or(self.0, other.0))
+                    }
+
+                    /// Get the bits set in both inputs.
+                    pub const fn bit_and(self, other: Self) -> Self {
+                        Self(raw_and(self.0, other.0))
+                    }
+
+                    /// Get the bits set in exactly one of the inputs.
+                    pub const fn bit_xor(self, other: Self) -> Self {
+                        Self(raw_xor(self.0, other.0))
+                    }
+
+                    /// Get the bits set in `self` but not in `other`.
+                    pub const fn difference(self, other: Self) -> Self {
+                        Self(raw_and(self.0, raw_not(other.0)))
+                    }
+
+                    /// Get the defined bits which aren't set in `self`.
+                    pub const fn complement(self) -> Self {
+                        Self(raw_and(raw_not(self.0), Self::MASK))
                     }
 
                     /// Get whether we contain every bit set in `other`.
                     pub const fn contains(self, other: Self) -> bool {
-                        (self.0 & other.0) == other.0
+                        raw_eq(raw_and(self.0, other.0), other.0)
                     }
 
                     /// Get whether we contain any bit set in `other`.
                     pub const fn contains_any(self, other: Self) -> bool {
-                        (self.0 | other.0) != 0
+                        !raw_is_zero(raw_and(self.0, other.0))
                     }
 
                     $(
@@ -62,10 +232,61 @@ macro_rules! bitset {
                         }
                     )*
 
+                    /// Iterate over the bits which are set, in the order they're declared.
+                    pub fn flags(self) -> impl Iterator<Item = [< $name Flag >]> {
+                        Self::ALL_FLAGS
+                            .into_iter()
+                            .filter(move |flag| self.contains(flag.mask()))
+                    }
+
                     /// Get whether this set is empty.
                     pub const fn is_empty(&self) -> bool {
-                        self.0 == 0
+                        raw_is_zero(self.0)
+                    }
+
+                    /// Get whether every defined bit is set.
+                    pub const fn is_all(&self) -> bool {
+                        raw_eq(raw_and(self.0, Self::MASK), Self::MASK)
+                    }
+
+                    /// Get how many defined bits are set.
+                    pub const fn count(&self) -> u32 {
+                        raw_count_ones(raw_and(self.0, Self::MASK))
+                    }
+                }
+
+                /// Functions for modifying values in place.
+                impl $name {
+                    /// Set every bit set in `other`.
+                    pub const fn insert(&mut self, other: Self) {
+                        self.0 = raw_or(self.0, other.0);
                     }
+
+                    /// Clear every bit set in `other`.
+                    pub const fn remove(&mut self, other: Self) {
+                        self.0 = raw_and(self.0, raw_not(other.0));
+                    }
+
+                    /// Flip every bit set in `other`.
+                    pub const fn toggle(&mut self, other: Self) {
+                        self.0 = raw_xor(self.0, other.0);
+                    }
+
+                    /// Set or clear every bit set in `other`, depending on `value`.
+                    pub const fn set(&mut self, other: Self, value: bool) {
+                        if value {
+                            self.insert(other);
+                        } else {
+                            self.remove(other);
+                        }
+                    }
+
+                    $(
+                        $( #[$bit_meta] )*
+                        pub const fn [< set_ $bit:snake:lower >](&mut self, value: bool) {
+                            self.set(Self::[< $bit:snake:upper >], value);
+                        }
+                    )*
                 }
                 /// Combine the bits from each.
                 ///
@@ -76,10 +297,99 @@ macro_rules! bitset {
                         self.bit_or(rhs)
                     }
                 }
+                /// Keep the bits set in both.
+                ///
+                /// See [`Self::bit_and`] for a const-time implementation.
+                impl BitAnd for $name {
+                    type Output = Self;
+                    fn bitand(self, rhs: Self) -> Self::Output {
+                        self.bit_and(rhs)
+                    }
+                }
+                /// Keep the bits set in exactly one.
+                ///
+                /// See [`Self::bit_xor`] for a const-time implementation.
+                impl BitXor for $name {
+                    type Output = Self;
+                    fn bitxor(self, rhs: Self) -> Self::Output {
+                        self.bit_xor(rhs)
+                    }
+                }
+                /// Remove the bits set on the right from the left.
+                ///
+                /// See [`Self::difference`] for a const-time implementation.
+                impl Sub for $name {
+                    type Output = Self;
+                    fn sub(self, rhs: Self) -> Self::Output {
+                        self.difference(rhs)
+                    }
+                }
+                /// Flip every defined bit.
+                ///
+                /// See [`Self::complement`] for a const-time implementation.
+                impl Not for $name {
+                    type Output = Self;
+                    fn not(self) -> Self::Output {
+                        self.complement()
+                    }
+                }
+
+                impl [< $name Flag >] {
+                    /// Get the set with only this bit set.
+                    pub const fn mask(self) -> $name {
+                        match self {
+                            $( Self::$bit => $name::[< $bit:snake:upper >], )*
+                        }
+                    }
+
+                    /// Get the name this bit was declared with.
+                    pub const fn name(self) -> &'static str {
+                        match self {
+                            $( Self::$bit => ::core::stringify!($bit), )*
+                        }
+                    }
+                }
+                /// Parse the name a bit was declared with.
+                impl ::core::str::FromStr for [< $name Flag >] {
+                    type Err = $crate::UnknownFlag;
+                    fn from_str(name: &str) -> ::core::result::Result<Self, Self::Err> {
+                        match name {
+                            $( ::core::stringify!($bit) => Ok(Self::$bit), )*
+                            _ => Err($crate::UnknownFlag),
+                        }
+                    }
+                }
+                impl ::core::fmt::Display for [< $name Flag >] {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        f.write_str(self.name())
+                    }
+                }
+                impl From<[< $name Flag >]> for $name {
+                    fn from(flag: [< $name Flag >]) -> Self {
+                        flag.mask()
+                    }
+                }
+
+                /// Combine the bits from every item.
+                impl ::core::iter::FromIterator<Self> for $name {
+                    fn from_iter<I: IntoIterator<Item = Self>>(iter: I) -> Self {
+                        let mut set = Self::empty();
+                        set.extend(iter);
+                        set
+                    }
+                }
+                /// Set the bits from every item.
+                impl ::core::iter::Extend<Self> for $name {
+                    fn extend<I: IntoIterator<Item = Self>>(&mut self, iter: I) {
+                        for item in iter {
+                            self.insert(item);
+                        }
+                    }
+                }
 
                 impl From<$repr> for $name {
                     fn from(repr: $repr) -> Self {
-                        Self(repr & Self::MASK)
+                        Self(raw_and(repr, Self::MASK))
                     }
                 }
                 impl From<$name> for $repr {
@@ -88,25 +398,40 @@ macro_rules! bitset {
                     }
                 }
 
-                /// Partial ordering by each bit, `a > b` implies every bit set in `b` is also set
-                /// in `a`.
-                ///
-                /// See [`Self::contains`] for a const-time implementation.
-                impl PartialOrd for $name {
-                    fn partial_cmp(&self, rhs: &Self) -> Option<core::cmp::Ordering> {
-                        if self == rhs {
-                            Some(core::cmp::Ordering::Equal)
-                        } else
-                        if self.contains(*rhs) {
-                            Some(core::cmp::Ordering::Greater)
-                        } else
-                        if rhs.contains(*self) {
-                            Some(core::cmp::Ordering::Less)
-                        } else {
-                            None
+                $crate::__bitset_option!(ord [$( $opt )*] {
+                    /// Order by the raw bits as a number.
+                    impl Ord for $name {
+                        fn cmp(&self, rhs: &Self) -> core::cmp::Ordering {
+                            raw_cmp(&self.0, &rhs.0)
                         }
                     }
-                }
+                    /// Order by the raw bits as a number, the same as [`Ord`].
+                    impl PartialOrd for $name {
+                        fn partial_cmp(&self, rhs: &Self) -> Option<core::cmp::Ordering> {
+                            Some(self.cmp(rhs))
+                        }
+                    }
+                } {
+                    /// Partial ordering by each bit, `a > b` implies every bit set in `b` is also
+                    /// set in `a`.
+                    ///
+                    /// See [`Self::contains`] for a const-time implementation.
+                    impl PartialOrd for $name {
+                        fn partial_cmp(&self, rhs: &Self) -> Option<core::cmp::Ordering> {
+                            if self == rhs {
+                                Some(core::cmp::Ordering::Equal)
+                            } else
+                            if self.contains(*rhs) {
+                                Some(core::cmp::Ordering::Greater)
+                            } else
+                            if rhs.contains(*self) {
+                                Some(core::cmp::Ordering::Less)
+                            } else {
+                                None
+                            }
+                        }
+                    }
+                });
 
                 /// Default to an empty set of values.
                 impl ::core::default::Default for $name {
@@ -115,53 +440,494 @@ macro_rules! bitset {
                     }
                 }
 
+                /// List the bits which are set.
+                ///
+                /// The alternate form (`{:#}`) is more compact, like `Name(A|B|0x40)`, and includes
+                /// the value of any unknown bits.
                 impl ::core::fmt::Display for $name {
                     fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        if f.alternate() {
+                            f.write_str(::core::concat!(::core::stringify!($name), "("))?;
+                            let mut first = true;
+                            $(
+                                if self.[< $bit:snake:lower >]() {
+                                    if !first {
+                                        f.write_str("|")?;
+                                    }
+                                    first = false;
+                                    f.write_str(::core::stringify!($bit))?;
+                                }
+                            )*
+                            let unknown = raw_and(self.0, raw_not(Self::MASK));
+                            if !raw_is_zero(unknown) {
+                                if !first {
+                                    f.write_str("|")?;
+                                }
+                                // We're formatting in the alternate form, so this gets a `0x`.
+                                raw_fmt(&unknown, f, $crate::__macro_export::Radix::LowerHex)?;
+                            }
+                            return f.write_str(")");
+                        }
                         f.write_str(::core::concat!(::core::stringify!($name), " { "))?;
                         $(
                             if self.[< $bit:snake:lower >]() {
                                 f.write_str(::core::concat!(::core::stringify!($bit), " "))?;
                             }
                         )*
-                        if self.0 & !Self::MASK != 0 {
+                        if !raw_is_zero(raw_and(self.0, raw_not(Self::MASK))) {
                             f.write_str("<unknown bits> ")?;
                         }
                         f.write_str("}")
                     }
                 }
 
-                impl $crate::BitSet for $name {
-                    type Repr = $repr;
-
-                    fn as_inner(&self) -> &Self::Repr { &self.0 }
-                    fn as_inner_mut(&mut self) -> &mut Self::Repr { &mut self.0 }
+                /// Format the raw bits, including any unknown ones.
+                impl ::core::fmt::LowerHex for $name {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        raw_fmt(&self.0, f, $crate::__macro_export::Radix::LowerHex)
+                    }
                 }
+                /// Format the raw bits, including any unknown ones.
+                impl ::core::fmt::UpperHex for $name {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        raw_fmt(&self.0, f, $crate::__macro_export::Radix::UpperHex)
+                    }
+                }
+                /// Format the raw bits, including any unknown ones.
+                impl ::core::fmt::Binary for $name {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        raw_fmt(&self.0, f, $crate::__macro_export::Radix::Binary)
+                    }
+                }
+
+                $crate::__raw_ops!($kind $name($repr) [< $name Flag >]);
+
+                $crate::__serde_impls!(
+                    $kind $name($repr) { $( $bit => [< $bit:snake:upper >] ),* }
+                );
 
                 /// Use an enum to generate offsets if not provided.
+                ///
+                /// This also makes declaring two bits with the same offset a compile error.
                 enum Offsets {
                     $( $bit $( = $disc )? ),*
                 }
 
+                // Check every bit fits, so a mistake here is a clear compile error rather than an
+                // overflow wherever the bit is first used.
+                const _: () = {
+                    $(
+                        ::core::assert!(
+                            (Offsets::$bit as usize) < ::core::mem::size_of::<$repr>() * 8,
+                            ::core::concat!(
+                                "bit `",
+                                ::core::stringify!($bit),
+                                "` of `",
+                                ::core::stringify!($name),
+                                "` doesn't fit in its representation",
+                            ),
+                        );
+                    )*
+                };
+
                 // A note about bytemuck impls:
                 // Using `bytemuck` functions to set bits not defined may result in weird behavior,
                 // but the behavior will always be sound.
-
-                // SAFETY:
-                // `#[repr(transparent)]` around plain old data is plain old data.
-                unsafe impl $crate::__macro_export::Pod for $name where $repr: $crate::__macro_export::Pod {}
-                // SAFETY: All zeros is the empty value.
-                unsafe impl $crate::__macro_export::Zeroable for $name where $repr: $crate::__macro_export::Zeroable  {}
+                $crate::__bitset_option!(no_bytemuck [$( $opt )*] {} {
+                    // SAFETY:
+                    // `#[repr(transparent)]` around plain old data is plain old data.
+                    unsafe impl $crate::__macro_export::Pod for $name
+                    where $repr: $crate::__macro_export::Pod {}
+                    // SAFETY: All zeros is the empty value.
+                    unsafe impl $crate::__macro_export::Zeroable for $name
+                    where $repr: $crate::__macro_export::Zeroable {}
+                });
             };
         }};
 }
 
+/// Expand to the first group of tokens if `$want` is one of the options given to [`bitset!`], or
+/// the second if not.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bitset_option {
+    (ord [ord $( $rest:ident )*] { $( $then:tt )* } $else:tt) => { $( $then )* };
+    (no_bytemuck [no_bytemuck $( $rest:ident )*] { $( $then:tt )* } $else:tt) => { $( $then )* };
+    ($want:ident [$other:ident $( $rest:ident )*] $then:tt $else:tt) => {
+        $crate::__bitset_option!($want [$( $rest )*] $then $else)
+    };
+    ($want:ident [] $then:tt { $( $else:tt )* }) => { $( $else )* };
+}
+
+/// Fail to compile if `$opt` isn't an option [`bitset!`] understands.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bitset_check_option {
+    (ord) => {};
+    (no_bytemuck) => {};
+    ($opt:ident) => {
+        ::core::compile_error!(::core::concat!(
+            "unknown bitset option `",
+            ::core::stringify!($opt),
+            "`",
+        ));
+    };
+}
+
+/// Define the operations on the raw bits of a type from [`bitset!`], which [`__bitset_impl!`]
+/// implements everything else with.
+///
+/// These are functions rather than operators so that they can be `const` for arrays too.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __raw_ops {
+    (int $name:ident($repr:ty) $flag:ident) => {
+        /// The raw value with no bits set.
+        const ZERO: $repr = 0;
+
+        /// Get the raw value with only the bit at `offset` set.
+        const fn raw_bit(offset: usize) -> $repr {
+            1 << offset
+        }
+
+        /// Get the bits set in either value.
+        const fn raw_or(a: $repr, b: $repr) -> $repr {
+            a | b
+        }
+
+        /// Get the bits set in both values.
+        const fn raw_and(a: $repr, b: $repr) -> $repr {
+            a & b
+        }
+
+        /// Get the bits set in exactly one of the values.
+        const fn raw_xor(a: $repr, b: $repr) -> $repr {
+            a ^ b
+        }
+
+        /// Get the bits which aren't set in the value.
+        const fn raw_not(a: $repr) -> $repr {
+            !a
+        }
+
+        /// Get whether no bits are set in the value.
+        const fn raw_is_zero(a: $repr) -> bool {
+            a == 0
+        }
+
+        /// Get whether the values have the same bits set.
+        const fn raw_eq(a: $repr, b: $repr) -> bool {
+            a == b
+        }
+
+        /// Get how many bits are set in the value.
+        const fn raw_count_ones(a: $repr) -> u32 {
+            a.count_ones()
+        }
+
+        /// Compare the values as numbers.
+        fn raw_cmp(a: &$repr, b: &$repr) -> ::core::cmp::Ordering {
+            a.cmp(b)
+        }
+
+        /// Format the value as a number.
+        fn raw_fmt(
+            repr: &$repr,
+            f: &mut ::core::fmt::Formatter<'_>,
+            radix: $crate::__macro_export::Radix,
+        ) -> ::core::fmt::Result {
+            match radix {
+                $crate::__macro_export::Radix::LowerHex => ::core::fmt::LowerHex::fmt(repr, f),
+                $crate::__macro_export::Radix::UpperHex => ::core::fmt::UpperHex::fmt(repr, f),
+                $crate::__macro_export::Radix::Binary => ::core::fmt::Binary::fmt(repr, f),
+            }
+        }
+
+        impl $crate::BitSet for $name {
+            type Repr = $repr;
+            type Flag = $flag;
+
+            fn as_inner(&self) -> &Self::Repr { &self.0 }
+            fn as_inner_mut(&mut self) -> &mut Self::Repr { &mut self.0 }
+            fn flags(self) -> impl Iterator<Item = Self::Flag> { $name::flags(self) }
+        }
+    };
+    (words $name:ident($repr:ty) $flag:ident) => {
+        /// The raw value with no bits set.
+        const ZERO: $repr = $crate::__words::zero();
+
+        /// Get the raw value with only the bit at `offset` set.
+        const fn raw_bit(offset: usize) -> $repr {
+            $crate::__words::bit(offset)
+        }
+
+        /// Get the bits set in either value.
+        const fn raw_or(a: $repr, b: $repr) -> $repr {
+            $crate::__words::or(a, b)
+        }
+
+        /// Get the bits set in both values.
+        const fn raw_and(a: $repr, b: $repr) -> $repr {
+            $crate::__words::and(a, b)
+        }
+
+        /// Get the bits set in exactly one of the values.
+        const fn raw_xor(a: $repr, b: $repr) -> $repr {
+            $crate::__words::xor(a, b)
+        }
+
+        /// Get the bits which aren't set in the value.
+        const fn raw_not(a: $repr) -> $repr {
+            $crate::__words::not(a)
+        }
+
+        /// Get whether no bits are set in the value.
+        const fn raw_is_zero(a: $repr) -> bool {
+            $crate::__words::is_zero(a)
+        }
+
+        /// Get whether the values have the same bits set.
+        const fn raw_eq(a: $repr, b: $repr) -> bool {
+            $crate::__words::eq(a, b)
+        }
+
+        /// Get how many bits are set in the value.
+        const fn raw_count_ones(a: $repr) -> u32 {
+            $crate::__words::count_ones(a)
+        }
+
+        /// Compare the values as numbers.
+        fn raw_cmp(a: &$repr, b: &$repr) -> ::core::cmp::Ordering {
+            $crate::__words::cmp(a, b)
+        }
+
+        /// Format the value as a number.
+        fn raw_fmt(
+            repr: &$repr,
+            f: &mut ::core::fmt::Formatter<'_>,
+            radix: $crate::__macro_export::Radix,
+        ) -> ::core::fmt::Result {
+            $crate::__words::fmt(repr, f, radix)
+        }
+    };
+}
+
+/// Operations on the raw bits of types from [`bitset!`] which are stored in arrays of words.
+///
+/// Bit `n` of a set is bit `n % 64` of word `n / 64`.
+#[doc(hidden)]
+pub mod __words {
+    use core::fmt;
+
+    use crate::__macro_export::Radix;
+
+    /// Get the value with no bits set.
+    #[must_use]
+    pub const fn zero<const N: usize>() -> [u64; N] {
+        [0; N]
+    }
+
+    /// Get the value with only the bit at `offset` set.
+    ///
+    /// # Panics
+    /// Panics if `offset` doesn't fit in `N` words.
+    #[must_use]
+    pub const fn bit<const N: usize>(offset: usize) -> [u64; N] {
+        let mut words = [0; N];
+        words[offset / 64] = 1 << (offset % 64);
+        words
+    }
+
+    /// Combine each pair of words from `a` and `b` with `op`.
+    macro_rules! zip_words {
+        ($a:ident, $b:ident, |$x:ident, $y:ident| $op:expr) => {{
+            let mut words = [0; N];
+            let mut i = 0;
+            while i < N {
+                let ($x, $y) = ($a[i], $b[i]);
+                words[i] = $op;
+                i += 1;
+            }
+            words
+        }};
+    }
+
+    /// Get the bits set in either value.
+    #[must_use]
+    pub const fn or<const N: usize>(a: [u64; N], b: [u64; N]) -> [u64; N] {
+        zip_words!(a, b, |x, y| x | y)
+    }
+
+    /// Get the bits set in both values.
+    #[must_use]
+    pub const fn and<const N: usize>(a: [u64; N], b: [u64; N]) -> [u64; N] {
+        zip_words!(a, b, |x, y| x & y)
+    }
+
+    /// Get the bits set in exactly one of the values.
+    #[must_use]
+    pub const fn xor<const N: usize>(a: [u64; N], b: [u64; N]) -> [u64; N] {
+        zip_words!(a, b, |x, y| x ^ y)
+    }
+
+    /// Get the bits which aren't set in the value.
+    #[must_use]
+    pub const fn not<const N: usize>(a: [u64; N]) -> [u64; N] {
+        zip_words!(a, a, |x, _y| !x)
+    }
+
+    /// Get whether no bits are set in the value.
+    #[must_use]
+    pub const fn is_zero<const N: usize>(a: [u64; N]) -> bool {
+        eq(a, [0; N])
+    }
+
+    /// Get whether the values have the same bits set.
+    #[must_use]
+    pub const fn eq<const N: usize>(a: [u64; N], b: [u64; N]) -> bool {
+        let mut i = 0;
+        while i < N {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// Get how many bits are set in the value.
+    #[must_use]
+    pub const fn count_ones<const N: usize>(a: [u64; N]) -> u32 {
+        let mut count = 0;
+        let mut i = 0;
+        while i < N {
+            count += a[i].count_ones();
+            i += 1;
+        }
+        count
+    }
+
+    /// Serialize the words as a tuple, the same way `serde` does for short arrays.
+    #[cfg(feature = "serde")]
+    pub fn serialize<const N: usize, S: serde::Serializer>(
+        words: &[u64; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeTuple as _;
+
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for word in words {
+            tuple.serialize_element(word)?;
+        }
+        tuple.end()
+    }
+
+    /// Deserialize words which were serialized with [`serialize`].
+    #[cfg(feature = "serde")]
+    pub fn deserialize<'de, const N: usize, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[u64; N], D::Error> {
+        /// Visits a tuple of `N` words.
+        struct WordsVisitor<const N: usize>;
+        impl<'de, const N: usize> serde::de::Visitor<'de> for WordsVisitor<N> {
+            type Value = [u64; N];
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(formatter, "{N} words")
+            }
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<[u64; N], A::Error> {
+                let mut words = [0; N];
+                for (i, word) in words.iter_mut().enumerate() {
+                    *word = seq
+                        .next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                }
+                Ok(words)
+            }
+        }
+        deserializer.deserialize_tuple(N, WordsVisitor)
+    }
+
+    /// Compare the values as numbers.
+    #[must_use]
+    pub fn cmp<const N: usize>(a: &[u64; N], b: &[u64; N]) -> core::cmp::Ordering {
+        a.iter().rev().cmp(b.iter().rev())
+    }
+
+    /// Format the value as one big number, most significant word first.
+    ///
+    /// This adds a prefix for the alternate form, but ignores width and fill.
+    pub fn fmt<const N: usize>(
+        words: &[u64; N],
+        f: &mut fmt::Formatter<'_>,
+        radix: Radix,
+    ) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(match radix {
+                Radix::LowerHex | Radix::UpperHex => "0x",
+                Radix::Binary => "0b",
+            })?;
+        }
+        let Some(top) = words.iter().rposition(|&word| word != 0) else {
+            return f.write_str("0");
+        };
+        for (i, &word) in words[..=top].iter().enumerate().rev() {
+            // Only the most significant word can leave out its leading zeros.
+            match (radix, i == top) {
+                (Radix::LowerHex, true) => write!(f, "{word:x}"),
+                (Radix::LowerHex, false) => write!(f, "{word:016x}"),
+                (Radix::UpperHex, true) => write!(f, "{word:X}"),
+                (Radix::UpperHex, false) => write!(f, "{word:016X}"),
+                (Radix::Binary, true) => write!(f, "{word:b}"),
+                (Radix::Binary, false) => write!(f, "{word:064b}"),
+            }?;
+        }
+        Ok(())
+    }
+}
+
+/// The error when making a bit set from raw bits with bits set which it doesn't define.
+///
+/// This holds the bits which weren't recognized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownBits<Repr>(pub Repr);
+impl<Repr: core::fmt::LowerHex> core::fmt::Display for UnknownBits<Repr> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "unknown bits {:#x} set", self.0)
+    }
+}
+impl<Repr: core::fmt::Debug + core::fmt::LowerHex> core::error::Error for UnknownBits<Repr> {}
+
+/// The error when parsing the name of a bit which isn't defined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownFlag;
+impl core::fmt::Display for UnknownFlag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("unknown flag name")
+    }
+}
+impl core::error::Error for UnknownFlag {}
+
 /// A trait for types from [`bitset!`].
 ///
-/// TODO All functionality should be duplicated between the trait (allowing for generic code) and
-/// inherent methods (so you don't have to import the trait).
-pub trait BitSet: From<Self::Repr> + Into<Self::Repr> {
+/// The provided methods do the same as the inherent methods of the same names, so generic code can
+/// use them. The inherent `bit_or`, `bit_and` and `bit_xor` are [`Self::union`],
+/// [`Self::intersection`] and [`Self::symmetric_difference`] here. Only the methods for individual
+/// bits, which differ between types, are left out.
+pub trait BitSet: Copy + From<Self::Repr> + Into<Self::Repr> {
     /// The underlying representation for this value.
-    type Repr;
+    type Repr: Copy
+        + Eq
+        + Default
+        + BitAnd<Output = Self::Repr>
+        + BitOr<Output = Self::Repr>
+        + BitXor<Output = Self::Repr>
+        + Not<Output = Self::Repr>;
+
+    /// The type of a single bit of this set.
+    type Flag: Copy;
 
     /// Get a reference to the inner value.
     fn as_inner(&self) -> &Self::Repr;
@@ -171,6 +937,237 @@ pub trait BitSet: From<Self::Repr> + Into<Self::Repr> {
     /// You may experience unexpected behavior if you set bits on the inner value which don't match
     /// bits in the bit set, but the behavior will still be sound.
     fn as_inner_mut(&mut self) -> &mut Self::Repr;
+
+    /// Iterate over the bits which are set, in the order they're declared.
+    fn flags(self) -> impl Iterator<Item = Self::Flag>;
+
+    /// Make a value with no bits set.
+    #[must_use]
+    fn empty() -> Self {
+        Self::from(Self::Repr::default())
+    }
+
+    /// Make a value with every bit set.
+    #[must_use]
+    fn all() -> Self {
+        // Converting from the raw bits drops the ones which aren't defined.
+        Self::from(!Self::Repr::default())
+    }
+
+    /// Get whether we contain every bit set in `other`.
+    fn contains(self, other: Self) -> bool {
+        (*self.as_inner() & *other.as_inner()) == *other.as_inner()
+    }
+
+    /// Get whether we contain any bit set in `other`.
+    fn contains_any(self, other: Self) -> bool {
+        (*self.as_inner() & *other.as_inner()) != Self::Repr::default()
+    }
+
+    /// Get all bits set in either input.
+    #[must_use]
+    fn union(self, other: Self) -> Self {
+        Self::from(*self.as_inner() | *other.as_inner())
+    }
+
+    /// Get the bits set in both inputs.
+    #[must_use]
+    fn intersection(self, other: Self) -> Self {
+        Self::from(*self.as_inner() & *other.as_inner())
+    }
+
+    /// Get the bits set in exactly one of the inputs.
+    #[must_use]
+    fn symmetric_difference(self, other: Self) -> Self {
+        Self::from(*self.as_inner() ^ *other.as_inner())
+    }
+
+    /// Get the bits set in `self` but not in `other`.
+    #[must_use]
+    fn difference(self, other: Self) -> Self {
+        Self::from(*self.as_inner() & !*other.as_inner())
+    }
+
+    /// Get the defined bits which aren't set in `self`.
+    #[must_use]
+    fn complement(self) -> Self {
+        Self::from(!*self.as_inner())
+    }
+
+    /// Get whether this set is empty.
+    fn is_empty(&self) -> bool {
+        *self.as_inner() == Self::Repr::default()
+    }
+
+    /// Get whether every defined bit is set.
+    fn is_all(&self) -> bool {
+        self.complement().is_empty()
+    }
+
+    /// Get how many defined bits are set.
+    fn count(&self) -> u32 {
+        self.flags().count() as u32
+    }
+
+    /// Set every bit set in `other`.
+    fn insert(&mut self, other: Self) {
+        *self.as_inner_mut() = *self.as_inner() | *other.as_inner();
+    }
+
+    /// Clear every bit set in `other`.
+    fn remove(&mut self, other: Self) {
+        *self.as_inner_mut() = *self.as_inner() & !*other.as_inner();
+    }
+
+    /// Flip every bit set in `other`.
+    fn toggle(&mut self, other: Self) {
+        *self.as_inner_mut() = *self.as_inner() ^ *other.as_inner();
+    }
+
+    /// Set or clear every bit set in `other`, depending on `value`.
+    fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+/// Implement `Serialize` and `Deserialize` for a type from [`bitset!`].
+///
+/// Human-readable formats get a list of the names of the bits which are set, and others get the
+/// raw integer. Either way, bits which aren't defined are dropped.
+///
+/// This is a separate macro so that whether it does anything depends on this crate's `serde`
+/// feature, not on the features of the crate using [`bitset!`].
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_impls {
+    ($kind:ident $name:ident($repr:ty) { $( $bit:ident => $constant:ident ),* }) => {
+        impl $crate::__macro_export::serde::Serialize for $name {
+            fn serialize<S: $crate::__macro_export::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                use $crate::__macro_export::serde::ser::SerializeSeq as _;
+
+                if !serializer.is_human_readable() {
+                    return $crate::__serde_raw!(ser $kind &self.0, serializer);
+                }
+                let mut seq = serializer.serialize_seq(Some(self.count() as usize))?;
+                $(
+                    if self.contains(Self::$constant) {
+                        seq.serialize_element(::core::stringify!($bit))?;
+                    }
+                )*
+                seq.end()
+            }
+        }
+
+        impl<'de> $crate::__macro_export::serde::Deserialize<'de> for $name {
+            fn deserialize<D: $crate::__macro_export::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                use $crate::__macro_export::serde::de;
+
+                /// The names of the bits, for error messages.
+                const NAMES: &[&str] = &[$( ::core::stringify!($bit) ),*];
+
+                /// A single bit, deserialized from its name.
+                struct Bit($name);
+                impl<'de> de::Deserialize<'de> for Bit {
+                    fn deserialize<D: de::Deserializer<'de>>(
+                        deserializer: D,
+                    ) -> ::core::result::Result<Self, D::Error> {
+                        deserializer.deserialize_str(BitVisitor)
+                    }
+                }
+                struct BitVisitor;
+                impl de::Visitor<'_> for BitVisitor {
+                    type Value = Bit;
+                    fn expecting(
+                        &self,
+                        f: &mut ::core::fmt::Formatter<'_>,
+                    ) -> ::core::fmt::Result {
+                        f.write_str(::core::concat!(
+                            "the name of a bit in ",
+                            ::core::stringify!($name),
+                        ))
+                    }
+                    fn visit_str<E: de::Error>(self, name: &str) -> ::core::result::Result<Bit, E> {
+                        match name {
+                            $( ::core::stringify!($bit) => Ok(Bit($name::$constant)), )*
+                            _ => Err(E::unknown_variant(name, NAMES)),
+                        }
+                    }
+                }
+
+                struct SetVisitor;
+                impl<'de> de::Visitor<'de> for SetVisitor {
+                    type Value = $name;
+                    fn expecting(
+                        &self,
+                        f: &mut ::core::fmt::Formatter<'_>,
+                    ) -> ::core::fmt::Result {
+                        f.write_str(::core::concat!(
+                            "a list of bits in ",
+                            ::core::stringify!($name),
+                        ))
+                    }
+                    fn visit_seq<A: de::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> ::core::result::Result<$name, A::Error> {
+                        let mut set = $name::empty();
+                        while let Some(Bit(bit)) = seq.next_element()? {
+                            set.insert(bit);
+                        }
+                        Ok(set)
+                    }
+                }
+
+                if deserializer.is_human_readable() {
+                    deserializer.deserialize_seq(SetVisitor)
+                } else {
+                    $crate::__serde_raw!(de $kind $repr, deserializer).map(Self::from)
+                }
+            }
+        }
+    };
+}
+
+/// Serialize (`ser`) or deserialize (`de`) the raw bits of a type from [`bitset!`].
+///
+/// Arrays of words have their own implementation, since `serde` only implements its traits for
+/// arrays of up to 32 elements.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_raw {
+    (ser int $value:expr, $serializer:expr) => {
+        $crate::__macro_export::serde::Serialize::serialize($value, $serializer)
+    };
+    (ser words $value:expr, $serializer:expr) => {
+        $crate::__words::serialize($value, $serializer)
+    };
+    (de int $repr:ty, $deserializer:expr) => {
+        <$repr as $crate::__macro_export::serde::Deserialize>::deserialize($deserializer)
+    };
+    (de words $repr:ty, $deserializer:expr) => {
+        $crate::__words::deserialize($deserializer)
+    };
+}
+
+/// Implement `Serialize` and `Deserialize` for a type from [`bitset!`].
+///
+/// This does nothing since the `serde` feature is disabled.
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_impls {
+    ($kind:ident $name:ident($repr:ty) { $( $bit:ident => $constant:ident ),* }) => {};
 }
 
 #[doc(hidden)]
@@ -178,4 +1175,17 @@ pub mod __macro_export {
     pub use paste::paste;
 
     pub use bytemuck::{Pod, Zeroable};
+    #[cfg(feature = "serde")]
+    pub use serde;
+
+    /// Which way to format the raw bits of a bit set as a number.
+    #[derive(Clone, Copy)]
+    pub enum Radix {
+        /// Like [`core::fmt::LowerHex`].
+        LowerHex,
+        /// Like [`core::fmt::UpperHex`].
+        UpperHex,
+        /// Like [`core::fmt::Binary`].
+        Binary,
+    }
 }
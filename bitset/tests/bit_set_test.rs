@@ -0,0 +1,64 @@
+//! Testing of the provided methods of [`BitSet`], through generic code.
+
+use bitset::BitSet;
+
+bitset::bitset!(
+    /// A set to test [`BitSet`] with.
+    pub Perms(u8) {
+        /// Readable.
+        Read,
+        /// Writable.
+        Write,
+        /// Executable.
+        Exec = 4,
+    }
+);
+
+/// Check that every provided method of [`BitSet`] agrees with what the inherent methods return
+/// for `a` and `b`.
+fn check_ops<T: BitSet + PartialEq + core::fmt::Debug>(a: T, b: T) -> Vec<T::Flag> {
+    assert!(T::empty().is_empty());
+    assert!(T::all().is_all());
+    assert_eq!(a.union(b).intersection(a), a);
+    assert_eq!(a.symmetric_difference(a), T::empty());
+    assert_eq!(a.difference(b).union(a.intersection(b)), a);
+    assert_eq!(a.complement().union(a), T::all());
+    assert!(a.union(b).contains(b));
+    assert_eq!(a.contains_any(b), !a.intersection(b).is_empty());
+    assert_eq!(a.complement().count() + a.count(), T::all().count());
+
+    let mut set = a;
+    set.insert(b);
+    assert_eq!(set, a.union(b));
+    set.remove(b);
+    assert_eq!(set, a.difference(b));
+    set.toggle(b);
+    assert_eq!(set, a.union(b));
+    set.set(a, false);
+    assert_eq!(set, b.difference(a));
+    set.set(a, true);
+    assert_eq!(set, a.union(b));
+    set.flags().collect()
+}
+
+#[test]
+fn test_bit_set_ops() {
+    let (a, b) = (Perms::READ | Perms::WRITE, Perms::WRITE | Perms::EXEC);
+    assert_eq!(
+        check_ops(a, b),
+        [PermsFlag::Read, PermsFlag::Write, PermsFlag::Exec]
+    );
+    assert_eq!(check_ops(Perms::READ, Perms::EXEC).len(), 2);
+    assert_eq!(check_ops(Perms::empty(), Perms::all()).len(), 3);
+
+    assert_eq!(<Perms as BitSet>::all(), Perms::all());
+    assert_eq!(
+        <Perms as BitSet>::complement(Perms::READ),
+        Perms::WRITE | Perms::EXEC
+    );
+    assert!(BitSet::contains_any(a, b));
+    assert!(!BitSet::contains_any(Perms::READ, Perms::EXEC));
+    assert_eq!(BitSet::count(&Perms::from_repr_retain(0xff)), 3);
+    assert!(BitSet::is_all(&Perms::all()));
+    assert!(!BitSet::is_all(&a));
+}
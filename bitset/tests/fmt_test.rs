@@ -0,0 +1,85 @@
+//! Testing of the formatting implementations from [`bitset::bitset!`].
+
+bitset::bitset!(
+    /// A set stored in an integer.
+    pub Perms(u8) {
+        /// Readable.
+        Read,
+        /// Writable.
+        Write,
+        /// Executable.
+        Exec,
+    }
+);
+
+bitset::bitset!(
+    /// A set stored in more than one word.
+    pub Wide([u64; 2]) {
+        /// Bit 0.
+        First,
+        /// Bit 127.
+        Last = 127,
+    }
+);
+
+#[test]
+fn test_display() {
+    let perms = Perms::READ | Perms::EXEC;
+    assert_eq!(perms.to_string(), "Perms { Read Exec }");
+    assert_eq!(Perms::empty().to_string(), "Perms { }");
+    assert_eq!(
+        Perms::from_repr_retain(0x41).to_string(),
+        "Perms { Read <unknown bits> }"
+    );
+    assert_eq!(format!("{perms:#}"), "Perms(Read|Exec)");
+    assert_eq!(format!("{:#}", Perms::empty()), "Perms()");
+    assert_eq!(
+        format!("{:#}", Perms::from_repr_retain(0x41)),
+        "Perms(Read|0x40)"
+    );
+    assert_eq!(
+        format!("{:#}", Perms::from_repr_retain(0x40)),
+        "Perms(0x40)"
+    );
+
+    let wide = Wide::FIRST | Wide::LAST;
+    assert_eq!(wide.to_string(), "Wide { First Last }");
+    assert_eq!(format!("{wide:#}"), "Wide(First|Last)");
+    assert_eq!(
+        format!("{:#}", Wide::from_repr_retain([0, 0x10])),
+        "Wide(0x100000000000000000)"
+    );
+}
+
+#[test]
+fn test_int_radixes() {
+    let perms = Perms::READ | Perms::EXEC;
+    assert_eq!(format!("{perms:x}"), "5");
+    assert_eq!(format!("{:X}", Perms::from_repr_retain(0xa5)), "A5");
+    assert_eq!(format!("{:#x}", Perms::from_repr_retain(0xa5)), "0xa5");
+    assert_eq!(format!("{perms:b}"), "101");
+    assert_eq!(format!("{perms:#010b}"), "0b00000101");
+    assert_eq!(format!("{perms:04x}"), "0005");
+}
+
+#[test]
+fn test_word_radixes() {
+    let wide = Wide::FIRST | Wide::LAST;
+    assert_eq!(format!("{wide:x}"), "80000000000000000000000000000001");
+    assert_eq!(format!("{wide:#X}"), "0x80000000000000000000000000000001");
+    assert_eq!(format!("{:x}", Wide::FIRST), "1");
+    assert_eq!(format!("{:#x}", Wide::empty()), "0x0");
+    assert_eq!(
+        format!("{:X}", Wide::from_repr_retain([0xab, 0xcd])),
+        "CD00000000000000AB"
+    );
+    assert_eq!(
+        format!("{:#b}", Wide::from_repr_retain([0b10, 0b1])),
+        format!("0b1{:064b}", 0b10)
+    );
+    assert_eq!(
+        format!("{:8x}", Wide::FIRST),
+        "1",
+        "Width is ignored for sets of words"
+    );
+}
@@ -0,0 +1,208 @@
+//! Testing of the operations [`bitset::bitset!`] generates, for sets stored in integers and in
+//! arrays of words.
+
+use core::cmp::Ordering;
+
+bitset::bitset!(
+    /// A set stored in an integer, with implicit offsets.
+    pub Perms(u8) {
+        /// Readable.
+        Read,
+        /// Writable.
+        Write,
+        /// Executable.
+        Exec,
+    }
+);
+
+bitset::bitset!(
+    /// A set stored in an integer, with explicit offsets leaving gaps.
+    pub Sparse(u16) {
+        /// Bit 1.
+        Low = 1,
+        /// Bit 9.
+        High = 9,
+    }
+);
+
+bitset::bitset!(
+    /// A set stored in more than one word, with bits at either end of each word.
+    pub Wide([u64; 2]) {
+        /// Bit 0.
+        First,
+        /// Bit 63.
+        EndOfWord = 63,
+        /// Bit 64.
+        StartOfWord = 64,
+        /// Bit 127.
+        Last = 127,
+    }
+);
+
+#[test]
+fn test_constants() {
+    assert_eq!(u8::from(Perms::READ), 0b001);
+    assert_eq!(u8::from(Perms::EXEC), 0b100);
+    assert_eq!(u16::from(Sparse::HIGH), 1 << 9);
+    assert_eq!(<[u64; 2]>::from(Wide::END_OF_WORD), [1 << 63, 0]);
+    assert_eq!(<[u64; 2]>::from(Wide::START_OF_WORD), [0, 1]);
+    assert_eq!(<[u64; 2]>::from(Wide::LAST), [0, 1 << 63]);
+
+    assert_eq!(u8::from(Perms::empty()), 0);
+    assert_eq!(u8::from(Perms::all()), 0b111);
+    assert_eq!(Perms::ALL_BITS, Perms::all());
+    assert_eq!(u16::from(Sparse::all()), 0b10_0000_0010);
+    assert_eq!(<[u64; 2]>::from(Wide::all()), [1 | 1 << 63, 1 | 1 << 63]);
+
+    assert_eq!(Perms::NUM_BITS, 3);
+    assert_eq!(Wide::NUM_BITS, 4);
+    assert_eq!(
+        Perms::ALL_FLAGS,
+        [PermsFlag::Read, PermsFlag::Write, PermsFlag::Exec]
+    );
+    assert_eq!(Perms::default(), Perms::empty());
+    assert_eq!(Wide::default(), Wide::empty());
+}
+
+#[test]
+fn test_combining() {
+    let rw = Perms::READ.bit_or(Perms::WRITE);
+    let wx = Perms::WRITE | Perms::EXEC;
+    assert_eq!(rw.bit_and(wx), Perms::WRITE);
+    assert_eq!(rw & wx, Perms::WRITE);
+    assert_eq!(rw.bit_xor(wx), Perms::READ | Perms::EXEC);
+    assert_eq!(rw ^ wx, Perms::READ | Perms::EXEC);
+    assert_eq!(rw.difference(wx), Perms::READ);
+    assert_eq!(rw - wx, Perms::READ);
+    assert_eq!(rw.complement(), Perms::EXEC);
+    assert_eq!(!rw, Perms::EXEC);
+    assert_eq!(
+        (!Sparse::LOW).complement(),
+        Sparse::LOW,
+        "Complementing shouldn't set undefined bits"
+    );
+
+    let ends = Wide::FIRST | Wide::LAST;
+    let middle = Wide::END_OF_WORD | Wide::START_OF_WORD;
+    assert_eq!(ends | middle, Wide::all());
+    assert_eq!(ends & middle, Wide::empty());
+    assert_eq!((ends | Wide::END_OF_WORD) & middle, Wide::END_OF_WORD);
+    assert_eq!(Wide::all() ^ ends, middle);
+    assert_eq!(Wide::all() - middle, ends);
+    assert_eq!(!ends, middle);
+}
+
+#[test]
+fn test_queries() {
+    let rw = Perms::READ | Perms::WRITE;
+    assert!(rw.contains(Perms::READ));
+    assert!(rw.contains(rw));
+    assert!(!rw.contains(Perms::READ | Perms::EXEC));
+    assert!(rw.contains(Perms::empty()));
+    assert!(rw.contains_any(Perms::READ | Perms::EXEC));
+    assert!(!rw.contains_any(Perms::EXEC));
+    assert!(!rw.contains_any(Perms::empty()));
+    assert!(rw.read() && rw.write() && !rw.exec());
+    assert!(!rw.is_empty());
+    assert!(Perms::empty().is_empty());
+    assert!(!rw.is_all());
+    assert!(Perms::all().is_all());
+    assert_eq!(rw.count(), 2);
+    assert_eq!(
+        Sparse::from_repr_retain(0xffff).count(),
+        2,
+        "Only defined bits should be counted"
+    );
+
+    let ends = Wide::FIRST | Wide::LAST;
+    assert!(ends.contains(Wide::LAST));
+    assert!(!ends.contains(Wide::LAST | Wide::START_OF_WORD));
+    assert!(ends.contains_any(Wide::LAST | Wide::START_OF_WORD));
+    assert!(!ends.contains_any(Wide::END_OF_WORD | Wide::START_OF_WORD));
+    assert!(ends.first() && ends.last() && !ends.start_of_word());
+    assert!(Wide::empty().is_empty());
+    assert!(!ends.is_empty());
+    assert!(Wide::all().is_all());
+    assert_eq!(ends.count(), 2);
+    assert_eq!(Wide::all().count(), 4);
+}
+
+#[test]
+fn test_modifying() {
+    let mut perms = Perms::empty();
+    perms.insert(Perms::READ | Perms::EXEC);
+    assert_eq!(perms, Perms::READ | Perms::EXEC);
+    perms.remove(Perms::READ | Perms::WRITE);
+    assert_eq!(perms, Perms::EXEC);
+    perms.toggle(Perms::READ | Perms::EXEC);
+    assert_eq!(perms, Perms::READ);
+    perms.set(Perms::WRITE, true);
+    assert_eq!(perms, Perms::READ | Perms::WRITE);
+    perms.set(Perms::READ, false);
+    assert_eq!(perms, Perms::WRITE);
+    perms.set_exec(true);
+    perms.set_write(false);
+    assert_eq!(perms, Perms::EXEC);
+
+    let mut wide = Wide::empty();
+    wide.insert(Wide::FIRST | Wide::START_OF_WORD);
+    wide.set_last(true);
+    assert_eq!(<[u64; 2]>::from(wide), [1, 1 | 1 << 63]);
+    wide.remove(Wide::START_OF_WORD);
+    wide.toggle(Wide::END_OF_WORD | Wide::LAST);
+    assert_eq!(wide, Wide::FIRST | Wide::END_OF_WORD);
+    wide.set(Wide::FIRST, false);
+    assert_eq!(wide, Wide::END_OF_WORD);
+}
+
+#[test]
+fn test_flags() {
+    let perms = Perms::EXEC | Perms::READ;
+    assert_eq!(
+        perms.flags().collect::<Vec<_>>(),
+        [PermsFlag::Read, PermsFlag::Exec]
+    );
+    assert_eq!(
+        (Wide::LAST | Wide::END_OF_WORD).flags().collect::<Vec<_>>(),
+        [WideFlag::EndOfWord, WideFlag::Last]
+    );
+
+    assert_eq!(PermsFlag::Write.mask(), Perms::WRITE);
+    assert_eq!(Perms::from(PermsFlag::Write), Perms::WRITE);
+    assert_eq!(WideFlag::StartOfWord.mask(), Wide::START_OF_WORD);
+    assert_eq!(WideFlag::StartOfWord.name(), "StartOfWord");
+    assert_eq!(WideFlag::StartOfWord.to_string(), "StartOfWord");
+    assert_eq!("Exec".parse(), Ok(PermsFlag::Exec));
+    assert_eq!("exec".parse::<PermsFlag>(), Err(bitset::UnknownFlag));
+    assert!(PermsFlag::Read < PermsFlag::Exec);
+}
+
+#[test]
+fn test_collecting() {
+    let perms = [Perms::READ, Perms::EXEC, Perms::READ]
+        .into_iter()
+        .collect::<Perms>();
+    assert_eq!(perms, Perms::READ | Perms::EXEC);
+    let mut wide = Wide::FIRST;
+    wide.extend([Wide::LAST, Wide::START_OF_WORD]);
+    assert_eq!(wide, Wide::FIRST | Wide::START_OF_WORD | Wide::LAST);
+    assert_eq!(
+        WideFlag::StartOfWord
+            .mask()
+            .flags()
+            .map(WideFlag::mask)
+            .collect::<Wide>(),
+        Wide::START_OF_WORD
+    );
+}
+
+#[test]
+fn test_partial_order() {
+    let rw = Perms::READ | Perms::WRITE;
+    assert_eq!(rw.partial_cmp(&rw), Some(Ordering::Equal));
+    assert!(rw > Perms::READ);
+    assert!(Perms::WRITE < rw);
+    assert_eq!(rw.partial_cmp(&Perms::EXEC), None);
+    assert!(Wide::FIRST | Wide::LAST > Wide::LAST);
+    assert_eq!(Wide::FIRST.partial_cmp(&Wide::LAST), None);
+}
@@ -0,0 +1,93 @@
+//! Testing of the options and attributes which [`bitset::bitset!`] accepts.
+
+use std::{
+    cmp::Ordering,
+    hash::{BuildHasher as _, RandomState},
+};
+
+bitset::bitset!(
+    #[bitset(ord)]
+    #[derive(Hash)]
+    /// A set ordered by its raw bits.
+    pub Ordered(u8) {
+        /// Bit 0.
+        Low,
+        /// Bit 1.
+        High,
+    }
+);
+
+bitset::bitset!(
+    #[bitset(ord)]
+    /// A set of words ordered by its raw bits.
+    pub OrderedWide([u64; 2]) {
+        /// Bit 0.
+        Low,
+        /// Bit 64.
+        High = 64,
+    }
+);
+
+bitset::bitset!(
+    #[bitset(no_bytemuck, ord)]
+    /// A set which implements the `bytemuck` traits itself.
+    pub Manual(u16) {
+        /// Bit 0.
+        Low,
+        /// Bit 15.
+        High = 15,
+    }
+);
+// SAFETY: `Manual` is `#[repr(transparent)]` around a `u16`.
+unsafe impl bytemuck::Zeroable for Manual {}
+// SAFETY: `Manual` is `#[repr(transparent)]` around a `u16`.
+unsafe impl bytemuck::Pod for Manual {}
+
+#[test]
+fn test_ord() {
+    assert!(Ordered::LOW < Ordered::HIGH);
+    assert!(Ordered::HIGH < (Ordered::LOW | Ordered::HIGH));
+    assert_eq!(Ordered::LOW.cmp(&Ordered::HIGH), Ordering::Less);
+    assert_eq!(
+        Ordered::LOW.partial_cmp(&Ordered::HIGH),
+        Some(Ordering::Less)
+    );
+    assert_eq!(
+        [
+            Ordered::all(),
+            Ordered::HIGH,
+            Ordered::empty(),
+            Ordered::LOW
+        ]
+        .iter()
+        .max(),
+        Some(&Ordered::all())
+    );
+
+    assert!(OrderedWide::LOW < OrderedWide::HIGH);
+    assert!(
+        OrderedWide::from_repr_retain([u64::MAX, 0]) < OrderedWide::HIGH,
+        "Higher words should be more significant"
+    );
+    assert!(OrderedWide::HIGH < OrderedWide::all());
+
+    assert!(Manual::LOW < Manual::HIGH);
+}
+
+#[test]
+fn test_derive_passthrough() {
+    let state = RandomState::new();
+    assert_eq!(
+        state.hash_one(Ordered::LOW | Ordered::HIGH),
+        state.hash_one(Ordered::all())
+    );
+}
+
+#[test]
+fn test_bytemuck() {
+    assert_eq!(bytemuck::cast::<_, u8>(Ordered::HIGH), 0b10);
+    assert_eq!(bytemuck::cast::<u8, Ordered>(0b01), Ordered::LOW);
+    assert_eq!(bytemuck::cast::<_, [u64; 2]>(OrderedWide::HIGH), [0, 1]);
+    assert_eq!(bytemuck::cast::<_, u16>(Manual::HIGH), 0x8000);
+    assert_eq!(<Manual as bytemuck::Zeroable>::zeroed(), Manual::empty());
+}
@@ -0,0 +1,66 @@
+//! Testing of converting between types from [`bitset::bitset!`] and their raw bits.
+
+use bitset::UnknownBits;
+
+bitset::bitset!(
+    /// A set stored in an integer.
+    pub Perms(u8) {
+        /// Readable.
+        Read,
+        /// Writable.
+        Write,
+        /// Executable.
+        Exec,
+    }
+);
+
+bitset::bitset!(
+    /// A set stored in more than one word.
+    pub Wide([u64; 2]) {
+        /// Bit 0.
+        First,
+        /// Bit 127.
+        Last = 127,
+    }
+);
+
+#[test]
+fn test_int_conversions() {
+    assert_eq!(Perms::try_from_repr(0b101), Ok(Perms::READ | Perms::EXEC));
+    assert_eq!(
+        Perms::try_from_repr(0b1010_0010),
+        Err(UnknownBits(0b1010_0000))
+    );
+    assert_eq!(Perms::from(0b1010_0010), Perms::WRITE);
+
+    let retained = Perms::from_repr_retain(0b1010_0010);
+    assert_eq!(u8::from(retained), 0b1010_0010);
+    assert!(retained.write());
+    assert_ne!(retained, Perms::WRITE);
+    assert_eq!(
+        retained.complement(),
+        Perms::READ | Perms::EXEC,
+        "Unknown bits are dropped by operations which make new bits"
+    );
+    assert_eq!(u8::from(retained | Perms::READ), 0b1010_0011);
+}
+
+#[test]
+fn test_word_conversions() {
+    assert_eq!(
+        Wide::try_from_repr([1, 1 << 63]),
+        Ok(Wide::FIRST | Wide::LAST)
+    );
+    assert_eq!(Wide::try_from_repr([0b11, 1]), Err(UnknownBits([0b10, 1])));
+    assert_eq!(Wide::from([0b11, 1]), Wide::FIRST);
+
+    let retained = Wide::from_repr_retain([0b11, 1]);
+    assert_eq!(<[u64; 2]>::from(retained), [0b11, 1]);
+    assert!(retained.first());
+    assert_eq!(retained.count(), 1);
+}
+
+#[test]
+fn test_unknown_bits_display() {
+    assert_eq!(UnknownBits(0xa0_u8).to_string(), "unknown bits 0xa0 set");
+}
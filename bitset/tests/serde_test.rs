@@ -0,0 +1,82 @@
+//! Testing of the `Serialize` and `Deserialize` implementations from [`bitset::bitset!`], with the
+//! `serde` feature.
+
+#![cfg(feature = "serde")]
+
+use serde_test::{Configure as _, Token, assert_de_tokens, assert_de_tokens_error, assert_tokens};
+
+bitset::bitset!(
+    /// A set stored in an integer.
+    pub Perms(u8) {
+        /// Readable.
+        Read,
+        /// Writable.
+        Write,
+        /// Executable.
+        Exec,
+    }
+);
+
+bitset::bitset!(
+    /// A set stored in more than one word.
+    pub Wide([u64; 2]) {
+        /// Bit 0.
+        First,
+        /// Bit 127.
+        Last = 127,
+    }
+);
+
+#[test]
+fn test_serde_readable() {
+    assert_tokens(
+        &(Perms::READ | Perms::EXEC).readable(),
+        &[
+            Token::Seq { len: Some(2) },
+            Token::Str("Read"),
+            Token::Str("Exec"),
+            Token::SeqEnd,
+        ],
+    );
+    assert_tokens(
+        &Perms::empty().readable(),
+        &[Token::Seq { len: Some(0) }, Token::SeqEnd],
+    );
+    assert_tokens(
+        &(Wide::FIRST | Wide::LAST).readable(),
+        &[
+            Token::Seq { len: Some(2) },
+            Token::Str("First"),
+            Token::Str("Last"),
+            Token::SeqEnd,
+        ],
+    );
+    assert_de_tokens_error::<serde_test::Readable<Perms>>(
+        &[Token::Seq { len: Some(1) }, Token::Str("Delete")],
+        "unknown variant `Delete`, expected one of `Read`, `Write`, `Exec`",
+    );
+}
+
+#[test]
+fn test_serde_compact() {
+    assert_tokens(&(Perms::READ | Perms::EXEC).compact(), &[Token::U8(0b101)]);
+    assert_de_tokens(&Perms::WRITE.compact(), &[Token::U8(0b1111_1010)]);
+    assert_tokens(
+        &(Wide::FIRST | Wide::LAST).compact(),
+        &[
+            Token::Tuple { len: 2 },
+            Token::U64(1),
+            Token::U64(1 << 63),
+            Token::TupleEnd,
+        ],
+    );
+    assert_de_tokens(
+        &Wide::LAST.compact(),
+        &[
+            Token::Tuple { len: 2 },
+            Token::U64(0b10),
+            Token::U64(u64::MAX),
+            Token::TupleEnd,
+        ],
+    );
+}
@@ -4,10 +4,27 @@ use std::{env, fs, path::PathBuf};
 
 const LINKER_SCRIPT: &str = include_str!("./kernel.ld");
 
+/// The environment variable naming a disk image to embed in the kernel as a RAM disk.
+const RAM_DISK_IMAGE_VAR: &str = "RAM_DISK_IMAGE";
+
 fn main() {
-    let linker_script_path =
+    let out_dir =
         PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR env var not specified by cargo"));
-    fs::write(linker_script_path.join("linker.ld"), LINKER_SCRIPT)
+    fs::write(out_dir.join("linker.ld"), LINKER_SCRIPT)
         .expect("Failed to copy linker script to output directory");
-    println!("cargo:rustc-link-search={}", linker_script_path.display());
+    println!("cargo:rustc-link-search={}", out_dir.display());
+
+    // The kernel always embeds `ram_disk.img`, which is left empty if no image was given.
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=kernel.ld");
+    println!("cargo:rerun-if-env-changed={RAM_DISK_IMAGE_VAR}");
+    let image = match env::var_os(RAM_DISK_IMAGE_VAR) {
+        Some(path) => {
+            println!("cargo:rerun-if-changed={}", path.display());
+            fs::read(&path).expect("Failed to read RAM disk image")
+        }
+        None => Vec::new(),
+    };
+    fs::write(out_dir.join("ram_disk.img"), image)
+        .expect("Failed to write RAM disk image to output directory");
 }
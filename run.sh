@@ -14,8 +14,21 @@ cargo build --release -p shell --bin shell --target riscv32imac-unknown-none-elf
 # Convert it to raw binary data for including in the build
 $OBJCOPY --set-section-flags .bss=alloc,contents -O binary target/riscv32imac-unknown-none-elf/release/shell target/riscv32imac-unknown-none-elf/release/shell.bin
 
+# Set TESTS=1 to run userlib's tests instead of the shell, with QEMU exiting with a nonzero status
+# if any fail.
+if [ "${TESTS:-0}" = 1 ]; then
+    cargo build --release -p tests --bin tests --target riscv32imac-unknown-none-elf
+    $OBJCOPY --set-section-flags .bss=alloc,contents -O binary target/riscv32imac-unknown-none-elf/release/tests target/riscv32imac-unknown-none-elf/release/tests.bin
+fi
+
+# Set SELF_TEST=1 to have the kernel run its own tests against the filesystem when mounting it.
+KERNEL_FEATURES=""
+if [ "${SELF_TEST:-0}" = 1 ]; then
+    KERNEL_FEATURES="--features self-test"
+fi
+
 # Build the kernel
-cargo build --release --bin rust-os --target riscv32imac-unknown-none-elf
+cargo build --release --bin rust-os --target riscv32imac-unknown-none-elf $KERNEL_FEATURES
 
 FS_PATH="$SCRATCH_DIR/fs.bin"
 # FS size: 1MB
@@ -28,12 +41,46 @@ echo "Mounting FS at $FS_MOUNT..."
 mkdir "$FS_MOUNT"
 fuse2fs -o rw,uid=$(id -u),gid=$(id -g),allow_other "$FS_PATH" "$FS_MOUNT"
 echo "Lorem ipsum dolor sit amet, consectetur adipiscing elit. In ut magna consequat, cursus velit aliquam, scelerisque odio. Ut lorem eros, feugiat quis bibendum vitae, malesuada ac orci. Praesent eget quam non nunc fringilla cursus imperdiet non tellus. Aenean dictum lobortis turpis, non interdum leo rhoncus sed. Cras in tellus auctor, faucibus tortor ut, maximus metus. Praesent placerat ut magna non tristique. Pellentesque at nunc quis dui tempor vulputate. Vestibulum vitae massa orci. Mauris et tellus quis risus sagittis placerat. Integer lorem leo, feugiat sed molestie non, viverra a tellus." > "$FS_MOUNT/lorem-ipsum.txt"
+# Put user programs on the filesystem, so they can be spawned.
+mkdir "$FS_MOUNT/bin"
+cp target/riscv32imac-unknown-none-elf/release/shell.bin "$FS_MOUNT/bin/shell"
+if [ "${TESTS:-0}" = 1 ]; then
+    cp target/riscv32imac-unknown-none-elf/release/tests.bin "$FS_MOUNT/bin/tests"
+fi
 fusermount -u "$FS_MOUNT" 
 
+# Set RAM_DISK=1 to build the filesystem into the kernel as a RAM disk, instead of attaching it as
+# a virtio block device.
+DISK_ARGS="-drive id=drive0,file=$FS_PATH,format=raw,if=none -device virtio-blk-device,drive=drive0,bus=virtio-mmio-bus.0"
+if [ "${RAM_DISK:-0}" = 1 ]; then
+    RAM_DISK_IMAGE="$FS_PATH" cargo build --release --bin rust-os --target riscv32imac-unknown-none-elf $KERNEL_FEATURES
+    DISK_ARGS=""
+fi
+
+# Set VIRTIO_MODERN=1 to have QEMU provide modern (version 2) virtio-mmio devices instead of legacy
+# ones.
+VIRTIO_ARGS=""
+if [ "${VIRTIO_MODERN:-0}" = 1 ]; then
+    VIRTIO_ARGS="-global virtio-mmio.force-legacy=false"
+fi
+
+# Set BOOTARGS to pass a kernel command line (e.g. `BOOTARGS=loglevel=debug`).
+BOOTARGS="${BOOTARGS:-}"
+if [ "${TESTS:-0}" = 1 ]; then
+    BOOTARGS="$BOOTARGS init=/bin/tests"
+fi
+
 # Start QEMU
-$QEMU -machine virt -bios default -nographic -serial mon:stdio --no-reboot \
-    -drive id=drive0,file="$FS_PATH",format=raw,if=none \
-    -device virtio-blk-device,drive=drive0,bus=virtio-mmio-bus.0 \
+# The UART (which the SBI console also uses), the virtio console, and the QEMU monitor all share
+# stdio.
+$QEMU -machine virt -bios default -nographic --no-reboot $VIRTIO_ARGS $DISK_ARGS \
+    -chardev stdio,id=char0,mux=on \
+    -serial chardev:char0 -mon chardev=char0 \
     -device virtio-rng-device,bus=virtio-mmio-bus.1 \
     -device virtio-serial-device,bus=virtio-mmio-bus.2 \
-    -kernel target/riscv32imac-unknown-none-elf/release/rust-os
+    -device virtconsole,chardev=char0 \
+    -netdev user,id=net0,hostfwd=udp::5555-:7 \
+    -device virtio-net-device,netdev=net0,bus=virtio-mmio-bus.3 \
+    -device virtio-gpu-device,bus=virtio-mmio-bus.4 \
+    -device virtio-keyboard-device,bus=virtio-mmio-bus.5 \
+    -kernel target/riscv32imac-unknown-none-elf/release/rust-os -append "$BOOTARGS"
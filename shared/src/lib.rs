@@ -25,6 +25,63 @@ pub enum Syscall {
     Mmap = 11,
     /// Unmap a memory region.
     Munmap = 12,
+    /// Create a new hard link to an existing file.
+    Link = 13,
+    /// Remove a link to a file.
+    Unlink = 14,
+    /// Get usage information about the filesystem containing a path.
+    Statfs = 15,
+    /// Open a new network socket.
+    Socket = 16,
+    /// Bind a socket to a local address.
+    Bind = 17,
+    /// Send a datagram from a socket to an address.
+    SendTo = 18,
+    /// Receive a datagram on a socket, along with the address it came from.
+    RecvFrom = 19,
+    /// Map the memory backing a resource (such as a framebuffer) into the process.
+    MapResource = 20,
+    /// Push any pending changes to a resource out to the underlying device.
+    Sync = 21,
+    /// Get how long it's been since the machine started.
+    ClockGetTime = 22,
+    /// Block the current process for a duration.
+    Sleep = 23,
+    /// Bring the system down, resetting every device first.
+    ///
+    /// When powering off, a nonzero status tells the firmware that something went wrong.
+    Shutdown = 24,
+    /// Move the offset in a resource descriptor that reads and writes start from.
+    Seek = 25,
+    /// Start a new process running a program from the filesystem, as a child of this one.
+    Spawn = 26,
+    /// Wait for a child process to exit, and get its exit status.
+    Wait = 27,
+    /// Get information about the file at a path.
+    Stat = 28,
+    /// Get the name of an entry in a directory, by its index.
+    ReadDir = 29,
+    /// Create a new, empty directory.
+    CreateDir = 30,
+    /// Block until woken, if a word in memory still holds an expected value.
+    FutexWait = 31,
+    /// Wake processes blocked waiting on a word in memory.
+    FutexWake = 32,
+    /// Start a new thread in the current process.
+    SpawnThread = 33,
+    /// Exit the current thread, leaving the rest of the process running.
+    ExitThread = 34,
+    /// Create a new resource descriptor pointing at the same resource description as another.
+    Dup = 35,
+    /// Read from a resource, giving up if nothing arrives within a timeout.
+    ReadTimeout = 36,
+    /// Make one resource descriptor point at the same resource description as another, closing
+    /// whatever it pointed at before.
+    Dup2 = 37,
+    /// Create a pipe, giving a resource descriptor for each of its ends.
+    Pipe = 38,
+    /// Remove an empty directory.
+    RemoveDir = 39,
 }
 
 bitset::bitset!(
@@ -36,6 +93,10 @@ bitset::bitset!(
         WriteOnly,
         /// If writing a file, append to the end.
         Append,
+        /// Create the file if it doesn't exist.
+        Create,
+        /// If writing a file, discard its existing contents.
+        Truncate,
     }
 );
 impl FileOpenFlags {
@@ -43,6 +104,307 @@ impl FileOpenFlags {
     pub const READWRITE: Self = Self::READ_ONLY.bit_or(Self::WRITE_ONLY);
 }
 
+/// Usage information about a filesystem, as reported by [`Syscall::Statfs`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilesystemStats {
+    /// The type of the filesystem, given by the magic number identifying it on disk.
+    ///
+    /// See the associated constants for known values.
+    pub fs_type: u32,
+    /// The size of a block on the filesystem, in bytes.
+    pub block_size: u32,
+    /// The total number of blocks on the filesystem.
+    pub total_blocks: u64,
+    /// The number of blocks that aren't in use.
+    pub free_blocks: u64,
+    /// The total number of inodes on the filesystem.
+    pub total_inodes: u64,
+    /// The number of inodes that aren't in use.
+    pub free_inodes: u64,
+}
+impl FilesystemStats {
+    /// The [`Self::fs_type`] for an ext2 filesystem.
+    pub const EXT2_FS_TYPE: u32 = 0xEF53;
+}
+
+/// Information about a file, as reported by [`Syscall::Stat`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileMetadata {
+    /// What kind of file this is, as a [`FileKind`].
+    pub kind: u32,
+    /// The number of hard links to the file.
+    pub hard_links: u32,
+    /// The size of the file, in bytes.
+    pub size: u64,
+    /// The file's permission bits, as in a Unix mode (like `0o644`).
+    pub permissions: u32,
+}
+
+/// The kinds of file in [`FileMetadata::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FileKind {
+    /// A regular file, holding data.
+    RegularFile = 0,
+    /// A directory, holding other files.
+    Directory = 1,
+    /// Something else, such as a symbolic link or a device.
+    Other = 2,
+}
+impl FileKind {
+    /// Get the file kind from a number.
+    #[must_use]
+    pub fn from_num(num: u32) -> Option<Self> {
+        Some(match num {
+            0 => Self::RegularFile,
+            1 => Self::Directory,
+            2 => Self::Other,
+            _ => return None,
+        })
+    }
+}
+
+/// The path at which the framebuffer of the display can be opened.
+pub const FRAMEBUFFER_PATH: &str = "/dev/fb0";
+
+/// The layout of a framebuffer, as read from its resource descriptor.
+///
+/// Each pixel is a `u32` in the form `0x00RRGGBB`, and pixels are laid out in rows from the top
+/// left corner. Changes written to the mapped framebuffer show up on the display after a
+/// [`Syscall::Sync`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FramebufferInfo {
+    /// The width of the display, in pixels.
+    pub width: u32,
+    /// The height of the display, in pixels.
+    pub height: u32,
+    /// The number of bytes from the start of one row to the start of the next.
+    pub stride: u32,
+}
+
+/// The path at which keyboard events can be opened.
+pub const KEYBOARD_PATH: &str = "/dev/input/event0";
+
+/// An input event, as read from an input resource descriptor.
+///
+/// These follow the Linux evdev event codes. Reads give whole events, so the buffer should be a
+/// multiple of [`Self::LEN`] bytes long.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputEvent {
+    /// The type of event, such as [`Self::TYPE_KEY`].
+    pub ty: u16,
+    /// Which key (or other control) the event is for.
+    pub code: u16,
+    /// The new value, such as [`Self::KEY_PRESSED`].
+    pub value: u32,
+}
+impl InputEvent {
+    /// The length of an event in bytes.
+    pub const LEN: usize = 8;
+
+    /// Marks the end of a group of events which happened together.
+    pub const TYPE_SYN: u16 = 0;
+    /// A key was pressed or released.
+    pub const TYPE_KEY: u16 = 1;
+
+    /// The [`Self::value`] of a key event when the key is released.
+    pub const KEY_RELEASED: u32 = 0;
+    /// The [`Self::value`] of a key event when the key is pressed.
+    pub const KEY_PRESSED: u32 = 1;
+    /// The [`Self::value`] of a key event when a held key repeats.
+    pub const KEY_REPEATED: u32 = 2;
+
+    /// The [`Self::code`] for the escape key.
+    pub const KEY_ESC: u16 = 1;
+    /// The [`Self::code`] for the left control key.
+    pub const KEY_LEFT_CTRL: u16 = 29;
+    /// The [`Self::code`] for the right control key.
+    pub const KEY_RIGHT_CTRL: u16 = 97;
+    /// The [`Self::code`] for the up arrow key.
+    pub const KEY_UP: u16 = 103;
+    /// The [`Self::code`] for the left arrow key.
+    pub const KEY_LEFT: u16 = 105;
+    /// The [`Self::code`] for the right arrow key.
+    pub const KEY_RIGHT: u16 = 106;
+    /// The [`Self::code`] for the down arrow key.
+    pub const KEY_DOWN: u16 = 108;
+
+    /// Parse an event from its little-endian byte representation.
+    #[must_use]
+    pub fn from_bytes(bytes: [u8; Self::LEN]) -> Self {
+        Self {
+            ty: u16::from_le_bytes([bytes[0], bytes[1]]),
+            code: u16::from_le_bytes([bytes[2], bytes[3]]),
+            value: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        }
+    }
+
+    /// Get the little-endian byte representation of this event.
+    #[must_use]
+    pub fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[0..2].copy_from_slice(&self.ty.to_le_bytes());
+        bytes[2..4].copy_from_slice(&self.code.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.value.to_le_bytes());
+        bytes
+    }
+}
+
+/// The kinds of socket that [`Syscall::Socket`] can open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SocketKind {
+    /// A UDP socket, sending and receiving datagrams.
+    Udp = 0,
+    /// An ICMP echo socket, sending echo requests and receiving the replies.
+    ///
+    /// Messages sent and received include the ICMP header. The kernel fills in the identifier and
+    /// checksum of sent messages, and only delivers replies matching this socket's identifier.
+    IcmpEcho = 1,
+}
+impl SocketKind {
+    /// Get the socket kind from a number.
+    #[must_use]
+    pub fn from_num(num: u32) -> Option<Self> {
+        Some(match num {
+            0 => Self::Udp,
+            1 => Self::IcmpEcho,
+            _ => return None,
+        })
+    }
+}
+
+/// The address where the kernel maps each process's arguments and environment variables.
+///
+/// The block starts with three little-endian `u32`s: the number of arguments, the number of
+/// environment variables, and the length in bytes of the strings which follow. The strings are the
+/// arguments and then the environment variables (each as `NAME=value`), each followed by a NUL
+/// byte. The block is read-only.
+pub const PROCESS_ARGS_ADDR: usize = 0x0180_0000;
+
+/// Where [`Syscall::Seek`] measures the new offset from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SeekWhence {
+    /// The start of the resource.
+    Start = 0,
+    /// The current offset.
+    Current = 1,
+    /// The end of the resource.
+    End = 2,
+}
+impl SeekWhence {
+    /// Get the seek origin from a number.
+    #[must_use]
+    pub fn from_num(num: u32) -> Option<Self> {
+        Some(match num {
+            0 => Self::Start,
+            1 => Self::Current,
+            2 => Self::End,
+            _ => return None,
+        })
+    }
+}
+
+/// What [`Syscall::Shutdown`] does once every device has been reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ShutdownKind {
+    /// Turn the machine off.
+    PowerOff = 0,
+    /// Restart the machine.
+    Reboot = 1,
+}
+impl ShutdownKind {
+    /// Get the shutdown kind from a number.
+    #[must_use]
+    pub fn from_num(num: u32) -> Option<Self> {
+        Some(match num {
+            0 => Self::PowerOff,
+            1 => Self::Reboot,
+            _ => return None,
+        })
+    }
+}
+
+/// An IPv4 address.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ipv4Addr(pub [u8; 4]);
+impl Ipv4Addr {
+    /// The address used to mean "any address".
+    pub const UNSPECIFIED: Self = Self([0; 4]);
+    /// The address for broadcasting to the local network.
+    pub const BROADCAST: Self = Self([255; 4]);
+
+    /// Create an address from its four octets.
+    #[must_use]
+    pub const fn new(a: u8, b: u8, c: u8, d: u8) -> Self {
+        Self([a, b, c, d])
+    }
+}
+impl core::fmt::Display for Ipv4Addr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let [first, second, third, fourth] = self.0;
+        write!(f, "{first}.{second}.{third}.{fourth}")
+    }
+}
+impl core::str::FromStr for Ipv4Addr {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut octets = [0; 4];
+        let mut parts = s.split('.');
+        for octet in &mut octets {
+            *octet = parts
+                .next()
+                .and_then(|part| part.parse().ok())
+                .ok_or(ErrorKind::InvalidFormat)?;
+        }
+        if parts.next().is_some() {
+            return Err(ErrorKind::InvalidFormat);
+        }
+        Ok(Self(octets))
+    }
+}
+
+/// An IPv4 address and port, as passed to the socket syscalls.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketAddrV4 {
+    /// The IP address.
+    pub ip: Ipv4Addr,
+    /// The port, or `0` if unused.
+    pub port: u16,
+}
+impl SocketAddrV4 {
+    /// Create a socket address from an IP address and port.
+    #[must_use]
+    pub const fn new(ip: Ipv4Addr, port: u16) -> Self {
+        Self { ip, port }
+    }
+}
+impl core::fmt::Display for SocketAddrV4 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.ip, self.port)
+    }
+}
+impl core::str::FromStr for SocketAddrV4 {
+    type Err = ErrorKind;
+
+    /// Parse an address in the form `a.b.c.d:port`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, port) = s.split_once(':').ok_or(ErrorKind::InvalidFormat)?;
+        Ok(Self {
+            ip: ip.parse()?,
+            port: port.parse().map_err(|_| ErrorKind::InvalidFormat)?,
+        })
+    }
+}
+
 /// Possible kinds of errors from kernel syscalls.
 #[derive(Debug, Clone, Copy)]
 #[repr(u32)]
@@ -67,6 +429,14 @@ pub enum ErrorKind {
     /// process doesn't have permission to act upon (such as calling `read` on memory the process
     /// can't write to).
     NotPermitted = 7,
+    /// The operation would create something which already exists.
+    AlreadyExists = 8,
+    /// The operation didn't finish in time.
+    TimedOut = 9,
+    /// The operation wrote to a pipe whose read end is closed.
+    BrokenPipe = 10,
+    /// The operation needed an empty directory, but the directory had entries in it.
+    DirectoryNotEmpty = 11,
     /// Some other error happened.
     Other = u32::MAX,
 }
@@ -82,6 +452,10 @@ impl ErrorKind {
             5 => Self::InvalidFormat,
             6 => Self::LimitReached,
             7 => Self::NotPermitted,
+            8 => Self::AlreadyExists,
+            9 => Self::TimedOut,
+            10 => Self::BrokenPipe,
+            11 => Self::DirectoryNotEmpty,
             u32::MAX => Self::Other,
             _ => return None,
         })
@@ -97,6 +471,10 @@ impl core::fmt::Display for ErrorKind {
             Self::InvalidFormat => "Supplied data did not match expected format",
             Self::LimitReached => "Process reached resource limit",
             Self::NotPermitted => "Operation not permitted",
+            Self::AlreadyExists => "Entity already exists",
+            Self::TimedOut => "Operation timed out",
+            Self::BrokenPipe => "Pipe was closed by the reader",
+            Self::DirectoryNotEmpty => "Directory not empty",
             Self::Other => "Some other error",
         })
     }
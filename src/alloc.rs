@@ -1,12 +1,14 @@
 //! Memory allocator for the kernel.
 
 mod bytebuf;
+mod dma;
 mod page;
 mod raw;
 mod rc;
 
 pub use bytebuf::KByteBuf;
-pub use page::{alloc_pages, alloc_pages_zeroed, free_pages};
+pub use dma::DmaBuffer;
+pub use page::{alloc_pages, alloc_pages_zeroed, free_pages, free_ram_end};
 pub use rc::KrcBox;
 
 /// The size of a single page in memory.
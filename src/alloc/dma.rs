@@ -0,0 +1,69 @@
+use core::{ops::Deref, ptr::NonNull};
+
+use super::PAGE_SIZE;
+use crate::error::{OutOfMemory, Result};
+
+/// A buffer which devices can read and write directly.
+///
+/// The buffer is made of whole pages straight from the page allocator, so it's contiguous in
+/// physical memory however long it is. Kernel memory is identity-mapped, so its physical address
+/// is the same as its address in the kernel.
+pub struct DmaBuffer {
+    /// The start of the allocated pages.
+    base: NonNull<u8>,
+    /// The number of bytes in the buffer, which may be less than the pages allocated.
+    len: usize,
+}
+impl DmaBuffer {
+    /// Allocate a buffer of `len` bytes, all zero.
+    pub fn new_zeroed(len: usize) -> Result<Self, OutOfMemory> {
+        let base = super::alloc_pages_zeroed(Self::num_pages(len))?;
+        Ok(Self {
+            base: NonNull::new(base.cast()).ok_or(OutOfMemory)?,
+            len,
+        })
+    }
+
+    /// Get the physical address of the start of the buffer, to hand to a device.
+    pub fn phys_addr(&self) -> usize {
+        self.base.addr().get()
+    }
+
+    /// Get a pointer to the start of the buffer.
+    ///
+    /// Use this rather than going through a reference for memory the device might write to while
+    /// we hold the pointer.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.base.as_ptr()
+    }
+
+    /// Get the number of pages to allocate for a buffer of `len` bytes.
+    fn num_pages(len: usize) -> usize {
+        len.div_ceil(PAGE_SIZE).max(1)
+    }
+}
+impl Deref for DmaBuffer {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // This memory is initialized in the constructor, so we can read it.
+        unsafe { NonNull::slice_from_raw_parts(self.base, self.len).as_ref() }
+    }
+}
+impl core::ops::DerefMut for DmaBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // This memory is initialized in the constructor, so we can read it.
+        unsafe { NonNull::slice_from_raw_parts(self.base, self.len).as_mut() }
+    }
+}
+impl Drop for DmaBuffer {
+    fn drop(&mut self) {
+        // SAFETY: We allocated these pages in the constructor, and nothing else has them.
+        unsafe { super::free_pages(self.base.as_ptr().cast(), Self::num_pages(self.len)) };
+    }
+}
+// SAFETY: Raw bytes are always sendable.
+unsafe impl Send for DmaBuffer {}
+// SAFETY: Raw bytes are always shareable.
+unsafe impl Sync for DmaBuffer {}
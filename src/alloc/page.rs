@@ -4,10 +4,12 @@ use core::{
     sync::atomic::{AtomicPtr, Ordering},
 };
 
+use util::cell::LazyLock;
+
 use crate::{
     alloc::PAGE_SIZE,
     error::{OutOfMemory, Result},
-    sync::{KSpinLock, LazyLock},
+    sync::KSpinLock,
 };
 
 #[expect(
@@ -16,12 +18,21 @@ use crate::{
 )]
 unsafe extern "C" {
     safe static mut __free_ram: ();
-    safe static mut __free_ram_end: ();
 }
 
 static NEXT_PTR: LazyLock<AtomicPtr<()>> =
     LazyLock::new(|| AtomicPtr::new(core::ptr::addr_of_mut!(__free_ram)));
 
+/// The end of the memory we allocate pages from.
+static FREE_RAM_END: LazyLock<usize> = LazyLock::new(|| {
+    crate::fdt::BOOT_INFO.usable_memory_end(core::ptr::addr_of_mut!(__free_ram).addr())
+});
+
+/// Get the end of the memory we allocate pages from, which runs from the end of the kernel.
+pub fn free_ram_end() -> usize {
+    *FREE_RAM_END
+}
+
 static FREED_PAGES: FreePageList = FreePageList::new();
 
 /// Allocate some pages, and erase the memory.
@@ -43,7 +54,7 @@ pub fn alloc_pages(num_pages: usize) -> Result<*mut (), OutOfMemory> {
         log::debug!("Trying to allocate {num_pages} pages at {:X}", head.addr());
         let new_next =
             head.wrapping_byte_add(PAGE_SIZE.checked_mul(num_pages).expect("alloc too big"));
-        if new_next > core::ptr::addr_of_mut!(__free_ram_end) {
+        if new_next.addr() > free_ram_end() {
             return Err(OutOfMemory);
         }
         if NEXT_PTR
@@ -85,17 +96,27 @@ impl FreePageList {
         *head = Some(page_addr);
     }
 
+    /// Take `num_pages` freed pages from the list, if there's a run of them.
+    ///
+    /// A run of exactly the right size is unlinked, and a bigger run gives up its last pages.
     fn try_pop(&self, num_pages: usize) -> Option<NonNull<()>> {
         let mut head = self.head.try_lock()?;
         let mut head = &mut *head;
         loop {
             let mut page = (*head)?;
-            // SAFETY: Entries are valid for reading.
-            if unsafe { page.read() }.num_pages == num_pages {
-                todo!("Return these pages");
+            // SAFETY: Entries are valid for reading and writing, and we hold the lock.
+            let node = unsafe { page.as_mut() };
+            if node.num_pages == num_pages {
+                *head = node.next;
+                return Some(page.cast());
+            }
+            if node.num_pages > num_pages {
+                node.num_pages -= num_pages;
+                // SAFETY: The run is `node.num_pages + num_pages` pages long, so the pages we take
+                // are inside it.
+                return Some(unsafe { page.cast::<()>().byte_add(node.num_pages * PAGE_SIZE) });
             }
-            // SAFETY: Entries are valid for reading.
-            head = &mut unsafe { page.as_mut() }.next;
+            head = &mut node.next;
         }
     }
 }
@@ -0,0 +1,132 @@
+//! Devices which store data in fixed-size sectors, for filesystems to sit on top of.
+
+use crate::{error::Result, ram_disk::RamDisk, virtio::VirtioBlock};
+
+/// The size of one sector on disk.
+pub const BLOCK_SECTOR_LEN: usize = 512;
+
+/// A device holding an array of sectors, which can be read and written.
+pub trait BlockDevice {
+    /// Read consecutive sectors, starting at `start_sector`, to fill `buf`.
+    ///
+    /// `buf` must be a whole number of sectors long.
+    fn read_sectors(&mut self, buf: &mut [u8], start_sector: u64) -> Result<()>;
+
+    /// Write `data` to consecutive sectors, starting at `start_sector`.
+    ///
+    /// `data` must be a whole number of sectors long.
+    fn write_sectors(&mut self, data: &[u8], start_sector: u64) -> Result<()>;
+
+    /// Make sure every finished write has reached stable storage.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Get the capacity in number of sectors.
+    fn capacity(&self) -> u64;
+
+    /// Get the size of each sector in bytes.
+    fn sector_size(&self) -> usize {
+        BLOCK_SECTOR_LEN
+    }
+
+    /// Read a sector from the device into the buffer.
+    fn read_sector(&mut self, buf: &mut [u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
+        self.read_sectors(buf, sector)
+    }
+
+    /// Write a sector to the device.
+    fn write_sector(&mut self, data: &[u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
+        self.write_sectors(data, sector)
+    }
+
+    /// Tell the device that the given sectors no longer hold anything useful.
+    ///
+    /// This is only a hint, so by default it does nothing, and the sectors may read as anything
+    /// afterwards.
+    fn discard(&mut self, start_sector: u64, num_sectors: u64) -> Result<()> {
+        _ = (start_sector, num_sectors);
+        Ok(())
+    }
+
+    /// Fill the given sectors with zeroes.
+    ///
+    /// By default, this writes the zeroes one sector at a time.
+    fn write_zeroes(&mut self, start_sector: u64, num_sectors: u64) -> Result<()> {
+        let zeroes = [0; BLOCK_SECTOR_LEN];
+        for sector in start_sector..start_sector + num_sectors {
+            self.write_sector(&zeroes, sector)?;
+        }
+        Ok(())
+    }
+}
+
+/// Any of the block devices the kernel can put a filesystem on.
+pub enum Disk {
+    /// A virtio block device.
+    Virtio(VirtioBlock<'static>),
+    /// A disk in memory.
+    Ram(RamDisk),
+}
+impl BlockDevice for Disk {
+    fn read_sectors(&mut self, buf: &mut [u8], start_sector: u64) -> Result<()> {
+        match self {
+            Self::Virtio(disk) => disk.read_sectors(buf, start_sector),
+            Self::Ram(disk) => disk.read_sectors(buf, start_sector),
+        }
+    }
+
+    fn write_sectors(&mut self, data: &[u8], start_sector: u64) -> Result<()> {
+        match self {
+            Self::Virtio(disk) => disk.write_sectors(data, start_sector),
+            Self::Ram(disk) => disk.write_sectors(data, start_sector),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Virtio(disk) => disk.flush(),
+            Self::Ram(disk) => disk.flush(),
+        }
+    }
+
+    fn capacity(&self) -> u64 {
+        match self {
+            Self::Virtio(disk) => disk.capacity(),
+            Self::Ram(disk) => disk.capacity(),
+        }
+    }
+
+    fn sector_size(&self) -> usize {
+        match self {
+            Self::Virtio(disk) => disk.sector_size(),
+            Self::Ram(disk) => disk.sector_size(),
+        }
+    }
+
+    fn read_sector(&mut self, buf: &mut [u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
+        match self {
+            Self::Virtio(disk) => disk.read_sector(buf, sector),
+            Self::Ram(disk) => disk.read_sector(buf, sector),
+        }
+    }
+
+    fn write_sector(&mut self, data: &[u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
+        match self {
+            Self::Virtio(disk) => disk.write_sector(data, sector),
+            Self::Ram(disk) => disk.write_sector(data, sector),
+        }
+    }
+
+    fn discard(&mut self, start_sector: u64, num_sectors: u64) -> Result<()> {
+        match self {
+            Self::Virtio(disk) => disk.discard(start_sector, num_sectors),
+            Self::Ram(disk) => disk.discard(start_sector, num_sectors),
+        }
+    }
+
+    fn write_zeroes(&mut self, start_sector: u64, num_sectors: u64) -> Result<()> {
+        match self {
+            Self::Virtio(disk) => disk.write_zeroes(start_sector, num_sectors),
+            Self::Ram(disk) => disk.write_zeroes(start_sector, num_sectors),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! The kernel console.
+//!
+//! This uses the virtio console device if there is one, then the UART, and falls back to SBI calls
+//! if neither is available.
+
+/// A [`core::fmt::Write`] implementation for writing to the console.
+pub struct ConsoleWriter;
+impl core::fmt::Write for ConsoleWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        write(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
+
+/// Write all of `data` to the console.
+pub fn write(data: &[u8]) -> crate::error::Result<()> {
+    // If the console is locked, we might be logging from inside the driver, so we fall back to SBI
+    // rather than deadlocking.
+    if let Some(mut console) = crate::DEVICE_TREE.console.try_lock()
+        && let Some(console) = console.as_mut()
+    {
+        console.write(data);
+        return Ok(());
+    }
+    if let Some(mut uart) = crate::DEVICE_TREE.uart.try_lock()
+        && let Some(uart) = uart.as_mut()
+    {
+        uart.write(data);
+        return Ok(());
+    }
+    for &byte in data {
+        crate::sbi::putchar(char::from(byte))?;
+    }
+    Ok(())
+}
+
+/// Read from the console into `buf`, waiting until at least one byte arrives.
+///
+/// Returns the number of bytes read, or fails with [`shared::ErrorKind::TimedOut`] if nothing
+/// arrived before `timeout` expired.
+pub fn read(buf: &mut [u8], timeout: &crate::timer::Timeout) -> crate::error::Result<usize> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    loop {
+        let mut sleep_channel = None;
+        // Don't hold the lock while waiting, so others can write to the console meanwhile.
+        if let Some(console) = crate::DEVICE_TREE.console.lock().as_mut() {
+            let len = console.read(buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        } else if let Some(uart) = crate::DEVICE_TREE.uart.lock().as_mut() {
+            let len = uart.read(buf);
+            if len > 0 {
+                return Ok(len);
+            }
+            if uart.uses_interrupts() {
+                sleep_channel = Some(uart.wait_channel());
+            }
+        } else if let Some(c) = crate::sbi::getchar()? {
+            // The SBI call gives one byte at a time.
+            buf[0] = c.get() as u8;
+            return Ok(1);
+        }
+        if timeout.expired() {
+            return Err(shared::ErrorKind::TimedOut.into());
+        }
+        if !timeout.is_never() {
+            // Nothing wakes a sleeping process once its timeout passes, so keep checking instead.
+            crate::proc::sched_yield();
+        } else if let Some(channel) = sleep_channel {
+            // Interrupts aren't taken while we're in the kernel, so data can't arrive between our
+            // check and going to sleep without waking us.
+            crate::proc::sleep(channel);
+        } else {
+            core::hint::spin_loop();
+        }
+    }
+}
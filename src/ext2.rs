@@ -1,38 +1,69 @@
 //! An implementation of ext2
 
+mod fsck;
+mod inode_cache;
+mod self_test;
+
+use util::bitmap::Bitmap;
+
 use crate::{
     alloc::KByteBuf,
+    block::{BLOCK_SECTOR_LEN, BlockDevice},
     error::{Error, ErrorKind, Result},
-    virtio::VirtioBlock,
 };
 
-pub struct Ext2<'a> {
-    fs: VirtioBlock<'a>,
+/// The number of the root directory's inode.
+const ROOT_INODE: u32 = 2;
+
+pub struct Ext2<D> {
+    fs: D,
     /// The contents of the superblock.
     ///
     /// We reference this memory often, so we keep it cached instead of requiring a new disk read
     /// each time we're interested in any of it.
     superblock: KByteBuf,
+    /// Parsed inodes which we've recently used or which are held open.
+    inode_cache: inode_cache::InodeCache,
 }
-impl<'a> Ext2<'a> {
-    pub fn new(fs: VirtioBlock<'a>) -> Result<Self> {
+impl<D: BlockDevice> Ext2<D> {
+    pub fn new(fs: D) -> Result<Self> {
+        if fs.sector_size() != BLOCK_SECTOR_LEN {
+            log::error!("ext2 only supports {BLOCK_SECTOR_LEN} byte sectors");
+            return Err(ErrorKind::Unsupported.into());
+        }
         let mut this = Self {
             fs,
             superblock: KByteBuf::new_zeroed(1024)?,
+            inode_cache: inode_cache::InodeCache::new(),
         };
-        for (sector_in_block, buf) in this
-            .superblock
-            .as_chunks_mut::<512>()
-            .0
-            .iter_mut()
-            .enumerate()
-        {
-            this.fs.read_sector(buf, sector_in_block as u64 + 2)?;
+        this.fs.read_sectors(&mut this.superblock, 2)?;
+        let superblock = this.superblock();
+        superblock.check_validity()?;
+        let fs_sectors =
+            u64::from(superblock.block_count) * u64::from(superblock.sectors_per_block());
+        if fs_sectors > this.fs.capacity() {
+            log::error!(
+                "Filesystem needs {fs_sectors} sectors, but the disk only has {}",
+                this.fs.capacity()
+            );
+            return Err(ErrorKind::InvalidFormat.into());
         }
-        this.superblock().check_validity()?;
         Ok(this)
     }
 
+    /// Get usage information about this filesystem.
+    pub fn stats(&self) -> shared::FilesystemStats {
+        let superblock = self.superblock();
+        shared::FilesystemStats {
+            fs_type: shared::FilesystemStats::EXT2_FS_TYPE,
+            block_size: superblock.block_size() as u32,
+            total_blocks: superblock.block_count.into(),
+            free_blocks: superblock.free_blocks.into(),
+            total_inodes: superblock.inode_count.into(),
+            free_inodes: superblock.free_inodes.into(),
+        }
+    }
+
     fn superblock(&self) -> Superblock {
         #![expect(
             clippy::cast_ptr_alignment,
@@ -45,12 +76,130 @@ impl<'a> Ext2<'a> {
     }
 
     fn inode(&mut self, inode_num: u32) -> Inode {
+        if let Some(inode) = self.inode_cache.get(inode_num) {
+            return inode;
+        }
+        let inode = self.read_inode(inode_num);
+        // If every cache entry is held open, we just won't cache this inode.
+        _ = self.inode_cache.insert(inode_num, inode);
+        inode
+    }
+
+    /// Update the given inode.
+    ///
+    /// If the inode is held open, the change is kept in the cache until it's released. Otherwise,
+    /// it's written straight to disk.
+    fn write_inode(&mut self, inode_num: u32, inode: Inode) -> Result<()> {
+        if self.inode_cache.update(inode_num, inode) {
+            return Ok(());
+        }
+        self.write_inode_to_disk(inode_num, inode)
+    }
+
+    /// Hold the given inode open, keeping it cached until a matching call to
+    /// [`Self::release_inode`].
+    ///
+    /// While an inode is held, changes to it are shared by everything holding it, and it won't be
+    /// freed even if its last link is removed.
+    pub fn acquire_inode(&mut self, inode_num: u32) -> Result<()> {
+        if self.inode_cache.acquire(inode_num) {
+            return Ok(());
+        }
+        let inode = self.read_inode(inode_num);
+        if !self.inode_cache.insert(inode_num, inode) {
+            log::error!("Inode cache full, can't hold open inode {inode_num}");
+            return Err(ErrorKind::LimitReached.into());
+        }
+        let acquired = self.inode_cache.acquire(inode_num);
+        debug_assert!(acquired, "Inode should have just been cached");
+        Ok(())
+    }
+
+    /// Release an inode held by [`Self::acquire_inode`].
+    ///
+    /// Once nothing holds the inode, any cached changes are written back to disk, and the inode is
+    /// freed if it has no links left.
+    pub fn release_inode(&mut self, inode_num: u32) -> Result<()> {
+        let Some(released) = self.inode_cache.release(inode_num) else {
+            return Ok(());
+        };
+        if released.inode.hard_link_count == 0 {
+            self.free_inode(inode_num, released.inode)
+        } else if released.dirty {
+            self.write_inode_to_disk(inode_num, released.inode)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Make sure all changes to the given inode, and everything written before, are on disk.
+    pub fn sync_inode(&mut self, inode_num: u32) -> Result<()> {
+        if let Some(inode) = self.inode_cache.take_dirty(inode_num)
+            // An unlinked inode gets freed once it's released, so there's nothing to keep.
+            && inode.hard_link_count > 0
+        {
+            self.write_inode_to_disk(inode_num, inode)?;
+        }
+        self.fs.flush()
+    }
+
+    /// Make sure every change to the filesystem is on disk.
+    pub fn sync(&mut self) -> Result<()> {
+        while let Some((inode_num, inode)) = self.inode_cache.take_any_dirty() {
+            // An unlinked inode gets freed once it's released, so there's nothing to keep.
+            if inode.hard_link_count > 0 {
+                self.write_inode_to_disk(inode_num, inode)?;
+            }
+        }
+        self.fs.flush()
+    }
+
+    /// Read the given inode from disk, bypassing the cache.
+    fn read_inode(&mut self, inode_num: u32) -> Inode {
+        // TODO Check that the inode is used.
+        let (inode_sector, inode_index_in_sector) = self.inode_location(inode_num);
+
+        let mut buf = [0; 512];
+        self.fs
+            .read_sector(&mut buf, inode_sector)
+            .expect("Failed to read inode");
+
+        #[expect(clippy::cast_ptr_alignment, reason = "We only do an unaligned read")]
+        let inode_ptr = core::ptr::from_ref(&buf)
+            .cast::<Inode>()
+            .wrapping_byte_add(inode_index_in_sector);
+
+        // SAFETY: We just wrote an Inode there from the disk.
+        unsafe { core::ptr::read_unaligned(inode_ptr) }
+    }
+
+    /// Write the given inode back to disk, bypassing the cache.
+    fn write_inode_to_disk(&mut self, inode_num: u32, inode: Inode) -> Result<()> {
+        let (inode_sector, inode_index_in_sector) = self.inode_location(inode_num);
+
+        let buf = &mut [0; 512];
+        self.fs.read_sector(buf, inode_sector)?;
+
+        #[expect(clippy::cast_ptr_alignment, reason = "Following write is unaligned")]
+        let inode_ptr = core::ptr::from_mut(buf)
+            .cast::<Inode>()
+            .wrapping_byte_add(inode_index_in_sector);
+
+        // SAFETY: `inode_ptr` points into a buffer we just read from, so we can write to it.
+        unsafe { inode_ptr.write_unaligned(inode) };
+        self.fs.write_sector(buf, inode_sector)?;
+        Ok(())
+    }
+
+    /// Find where the given inode lives on disk.
+    ///
+    /// Returns the sector number containing the inode, and the byte offset of the inode within
+    /// that sector.
+    fn inode_location(&mut self, inode_num: u32) -> (u64, usize) {
         let superblock = self.superblock();
         let group_num = inode_num.saturating_sub(1) / superblock.inodes_per_group;
         let group = self.block_group_descriptor(group_num);
 
-        // TODO Check that the inode is used.
-
         let inode_block = group.inode_table_addr
             + (inode_num.saturating_sub(1) % superblock.inodes_per_group)
                 / superblock.inodes_per_block();
@@ -63,46 +212,28 @@ impl<'a> Ext2<'a> {
                     / u32::from(inodes_per_sector),
             );
 
-        let mut buf = [0; 512];
-        self.fs
-            .read_sector(&mut buf, inode_sector)
-            .expect("Failed to read inode");
-
         let inode_index_in_sector = (inode_num.saturating_sub(1) as usize
             % inodes_per_sector as usize)
             * superblock.inode_size as usize;
 
-        #[expect(clippy::cast_ptr_alignment, reason = "We only do an unaligned read")]
-        let inode_ptr = core::ptr::from_ref(&buf)
-            .cast::<Inode>()
-            .wrapping_byte_add(inode_index_in_sector);
-
-        // SAFETY: We just wrote an Inode there from the disk.
-        unsafe { core::ptr::read_unaligned(inode_ptr) }
-    }
-
-    fn read_dir(&mut self, dir_inode_num: u32) -> DirectoryEntryIter {
-        let inode = self.inode(dir_inode_num);
-        // TODO Check that it is a directory
-        if inode.size_lower != 1024 {
-            todo!("Support big directories");
-        }
-        DirectoryEntryIter {
-            buf: self.read_block(inode.direct_block_pointers[0]),
-            idx: 0,
-        }
+        (inode_sector, inode_index_in_sector)
     }
 
-    /// Get the inode number for a specific path, if present.
+    /// Get the inode number for a specific path.
     pub fn lookup_path<'path>(
         &mut self,
         path_parts: impl IntoIterator<Item = &'path str>,
-    ) -> Option<u32> {
+    ) -> Result<u32> {
         let mut inode_num = 2;
         for part in path_parts {
-            inode_num = self.read_dir(inode_num).find_for_name(part)?.inode_num;
+            let (_, block) = self.directory_block(inode_num)?;
+            inode_num = DirectoryEntryIter::new(&block)
+                .find_for_name(part)?
+                .ok_or(ErrorKind::NotFound)?
+                .header
+                .inode_num;
         }
-        Some(inode_num)
+        Ok(inode_num)
     }
 
     pub fn read_file_from_offset(
@@ -112,24 +243,99 @@ impl<'a> Ext2<'a> {
         mut buf: &mut [u8],
     ) -> Result<usize> {
         let inode = self.inode(inode_num);
-        if buf.len() as u64 > inode.file_size() - offset {
-            buf = &mut buf[..(inode.file_size() - offset) as usize];
+        let remaining_len = inode.file_size().saturating_sub(offset);
+        if buf.len() as u64 > remaining_len {
+            buf = &mut buf[..remaining_len as usize];
         }
         let sector_buf = &mut [0; 512];
-        let mut sector_num = (offset / 512) as u32;
         let mut write_len = 0;
-        loop {
-            if offset >= inode.file_size() {
-                return Ok(write_len);
-            }
-            self.read_inode_sector(inode_num, sector_num, sector_buf)?;
-            let this_write_len = buf.len().min(512);
-            buf[..this_write_len].copy_from_slice(&sector_buf[..this_write_len]);
+        while !buf.is_empty() {
+            // Only the first sector can start partway through.
+            let offset_in_sector = (offset % 512) as usize;
+            self.read_inode_sector(inode_num, (offset / 512) as u32, sector_buf)?;
+            let this_write_len = buf.len().min(512 - offset_in_sector);
+            buf[..this_write_len]
+                .copy_from_slice(&sector_buf[offset_in_sector..][..this_write_len]);
             buf = &mut buf[this_write_len..];
             write_len += this_write_len;
             offset += this_write_len as u64;
-            sector_num += 1;
         }
+        Ok(write_len)
+    }
+
+    /// Get the current size of a file, in bytes.
+    pub fn file_size(&mut self, inode_num: u32) -> u64 {
+        self.inode(inode_num).file_size()
+    }
+
+    /// Get information about the given inode.
+    pub fn metadata(&mut self, inode_num: u32) -> Result<shared::FileMetadata> {
+        let inode = self.inode(inode_num);
+        let kind = match inode.inode_type()? {
+            InodeType::RegularFile => shared::FileKind::RegularFile,
+            InodeType::Directory => shared::FileKind::Directory,
+            _ => shared::FileKind::Other,
+        };
+        Ok(shared::FileMetadata {
+            kind: kind as u32,
+            hard_links: inode.hard_link_count.into(),
+            size: inode.file_size(),
+            permissions: u32::from(inode.type_and_permissions & 0o7777),
+        })
+    }
+
+    /// Copy the name of the entry at `index` in the given directory into `name_buf`.
+    ///
+    /// Returns the length of the name, or `None` if the directory doesn't have that many entries.
+    pub fn read_directory_entry(
+        &mut self,
+        dir_inode_num: u32,
+        index: usize,
+        name_buf: &mut [u8],
+    ) -> Result<Option<usize>> {
+        let (_, block) = self.directory_block(dir_inode_num)?;
+        let Some(entry) = DirectoryEntryIter::new(&block)
+            .filter(|entry| !matches!(entry, Ok(entry) if entry.header.inode_num == 0))
+            .nth(index)
+            .transpose()?
+        else {
+            return Ok(None);
+        };
+        name_buf
+            .get_mut(..entry.name.len())
+            .ok_or(ErrorKind::InvalidFormat)?
+            .copy_from_slice(entry.name);
+        Ok(Some(entry.name.len()))
+    }
+
+    /// Discard the contents of a regular file, leaving it empty.
+    pub fn truncate(&mut self, inode_num: u32) -> Result<()> {
+        let inode = self.inode(inode_num);
+        if inode.inode_type()? != InodeType::RegularFile {
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        if inode.singly_indirect_block_pointer != 0
+            || inode.doubly_indirect_block_pointer != 0
+            || inode.triply_indirect_block_pointer != 0
+        {
+            log::error!("TODO Support indirect block pointers");
+            return Err(ErrorKind::Unsupported.into());
+        }
+        for &block_num in &inode.direct_block_pointers {
+            if block_num != 0 {
+                self.free_block(block_num)?;
+            }
+        }
+        self.write_inode(
+            inode_num,
+            Inode {
+                size_lower: 0,
+                size_upper_or_directory_acl: 0,
+                disk_sectors_used: 0,
+                direct_block_pointers: [0; 12],
+                ..inode
+            },
+        )
     }
 
     pub fn write_file_from_offset(
@@ -149,10 +355,13 @@ impl<'a> Ext2<'a> {
                 self.write_inode_sector(inode_num, sector_num as u32, sector_buf)?;
                 offset += write_len as u64;
             } else {
+                // The whole write fits in this sector, but it may still extend the file, so we
+                // fall through to update the length with nothing left to write.
                 let write_start = 512 - write_len;
                 sector_buf[write_start..][..buf.len()].copy_from_slice(buf);
                 self.write_inode_sector(inode_num, sector_num as u32, sector_buf)?;
-                return Ok(buf.len());
+                offset += buf.len() as u64;
+                buf = &[];
             }
         }
         let (full_sectors, remainder) = buf.as_chunks::<512>();
@@ -181,7 +390,7 @@ impl<'a> Ext2<'a> {
     ) -> Result<()> {
         let superblock = self.superblock();
         let inode = self.inode(inode_num);
-        if inode.inode_type() != InodeType::RegularFile {
+        if inode.inode_type()? != InodeType::RegularFile {
             return Err(ErrorKind::InvalidFormat.into());
         }
         let block_idx = sector_num / superblock.sectors_per_block();
@@ -192,6 +401,11 @@ impl<'a> Ext2<'a> {
                 log::error!("TODO Support indirect block pointers");
                 Error::from(ErrorKind::Unsupported)
             })?;
+        if block_num == 0 {
+            // A null block pointer is a hole in a sparse file, which reads as all zeros.
+            buf.fill(0);
+            return Ok(());
+        }
         self.fs.read_sector(
             buf,
             u64::from(block_num) * u64::from(superblock.sectors_per_block())
@@ -207,10 +421,12 @@ impl<'a> Ext2<'a> {
         contents: &[u8; 512],
     ) -> Result<()> {
         let superblock = self.superblock();
-        let inode = self.inode(inode_num);
-        assert_eq!(inode.inode_type(), InodeType::RegularFile);
+        let mut inode = self.inode(inode_num);
+        if inode.inode_type()? != InodeType::RegularFile {
+            return Err(ErrorKind::InvalidFormat.into());
+        }
         let block_idx = sector_num / superblock.sectors_per_block();
-        let block_num = *inode
+        let mut block_num = *inode
             .direct_block_pointers
             .get(block_idx as usize)
             .ok_or_else(|| {
@@ -218,7 +434,15 @@ impl<'a> Ext2<'a> {
                 Error::from(ErrorKind::Unsupported)
             })?;
         if block_num == 0 {
-            todo!("Allocate additional blocks for inode");
+            if contents.iter().all(|&byte| byte == 0) {
+                // Holes already read as zeros, so we can leave this one in place rather than
+                // allocating a block to store nothing.
+                return Ok(());
+            }
+            block_num = self.allocate_block()?;
+            inode.direct_block_pointers[block_idx as usize] = block_num;
+            inode.disk_sectors_used += superblock.sectors_per_block();
+            self.write_inode(inode_num, inode)?;
         }
         self.fs.write_sector(
             contents,
@@ -229,125 +453,440 @@ impl<'a> Ext2<'a> {
     }
 
     fn block_group_descriptor(&mut self, group_num: u32) -> BlockGroupDescriptor {
-        const DESCS_PER_SECTOR: usize = 512 / size_of::<BlockGroupDescriptor>();
-        let superblock = self.superblock();
-        assert!(group_num < superblock.num_block_groups());
-        let table_start_sector = 2 + superblock.block_size() / 512;
+        let (sector_num, desc_idx) = self.block_group_descriptor_location(group_num);
         let mut buf = [0; 512];
         self.fs
-            .read_sector(
-                &mut buf,
-                table_start_sector + (u64::from(group_num) * DESCS_PER_SECTOR as u64) / 512,
-            )
+            .read_sector(&mut buf, sector_num)
             .expect("Failed to read block descriptor table");
         #[expect(clippy::cast_ptr_alignment, reason = "Read is unaligned")]
         let desc_ptr = core::ptr::from_ref(&buf)
             .cast::<BlockGroupDescriptor>()
-            .wrapping_add(group_num as usize % DESCS_PER_SECTOR);
+            .wrapping_add(desc_idx);
         // SAFETY:
         // We just read the value from disk to this memory, so we can read it.
         unsafe { desc_ptr.read_unaligned() }
     }
 
+    /// Find where the given block group descriptor lives on disk.
+    ///
+    /// Returns the sector number containing the descriptor, and the index of the descriptor
+    /// within that sector.
+    fn block_group_descriptor_location(&self, group_num: u32) -> (u64, usize) {
+        const DESCS_PER_SECTOR: usize = 512 / size_of::<BlockGroupDescriptor>();
+        let superblock = self.superblock();
+        assert!(group_num < superblock.num_block_groups());
+        let table_start_sector = 2 + superblock.block_size() / 512;
+        (
+            table_start_sector + u64::from(group_num) / DESCS_PER_SECTOR as u64,
+            group_num as usize % DESCS_PER_SECTOR,
+        )
+    }
+
     /// Read the given block number.
     ///
     /// This takes extra time to read the whole block, so only use this method if you actually need
     /// to get the whole block.
-    fn read_block(&mut self, block_num: u32) -> KByteBuf {
-        let mut buf =
-            KByteBuf::new_zeroed(self.superblock().block_size() as usize).expect("Out of memory");
+    fn read_block(&mut self, block_num: u32) -> Result<KByteBuf> {
+        let mut buf = KByteBuf::new_zeroed(self.superblock().block_size() as usize)?;
         let start_sector = u64::from(block_num) * u64::from(self.superblock().sectors_per_block());
-        for (sector_in_block, buf) in buf.as_chunks_mut().0.iter_mut().enumerate() {
-            self.fs
-                .read_sector(buf, start_sector + sector_in_block as u64)
-                .expect("Failed to read sector of block");
-        }
-        buf
+        self.fs.read_sectors(&mut buf, start_sector)?;
+        Ok(buf)
     }
 
     fn set_inode_length_at_least(&mut self, inode_num: u32, min_length: u64) -> Result<()> {
-        let superblock = self.superblock();
-        let group_num = inode_num.saturating_sub(1) / superblock.inodes_per_group;
-        let group = self.block_group_descriptor(group_num);
+        let mut inode = self.inode(inode_num);
+        let old_size = inode.file_size();
+        if min_length > old_size {
+            log::info!("increasing file length from {old_size} to {min_length}");
+            inode.size_lower = min_length as u32;
+            inode.size_upper_or_directory_acl = (min_length >> 32) as u32;
+            self.write_inode(inode_num, inode)?;
+        } else {
+            log::info!("Not increasing file length from {old_size} to {min_length}");
+        }
+        Ok(())
+    }
 
-        // TODO Check that the inode is used.
+    /// Add a new directory entry named `name` in the given directory, pointing at `inode_num`.
+    ///
+    /// This increments the hard link count on the target inode.
+    pub fn link(&mut self, inode_num: u32, dir_inode_num: u32, name: &str) -> Result<()> {
+        let mut inode = self.inode(inode_num);
+        let inode_type = inode.inode_type()?;
+        if inode_type == InodeType::Directory {
+            // Hard links to directories would let the directory tree contain cycles.
+            return Err(ErrorKind::NotPermitted.into());
+        }
+        check_name(name)?;
+        inode.hard_link_count = inode
+            .hard_link_count
+            .checked_add(1)
+            .ok_or(ErrorKind::LimitReached)?;
 
-        let inode_block = group.inode_table_addr
-            + (inode_num.saturating_sub(1) % superblock.inodes_per_group)
-                / superblock.inodes_per_block();
+        let (block_num, mut block) = self.directory_block(dir_inode_num)?;
+        if directory_block_find(&block, name)?.is_some() {
+            return Err(ErrorKind::AlreadyExists.into());
+        }
+        if !directory_block_insert(&mut block, inode_num, inode_type, name)? {
+            log::error!("TODO Support big directories");
+            return Err(ErrorKind::LimitReached.into());
+        }
+        self.write_block(block_num, &block)?;
+        self.write_inode(inode_num, inode)
+    }
 
-        let inodes_per_sector = 512 / superblock.inode_size;
+    /// Create a new, empty regular file named `name` in the given directory.
+    ///
+    /// Returns the inode number of the new file.
+    pub fn create_file(&mut self, dir_inode_num: u32, name: &str) -> Result<u32> {
+        let inode_num = self.allocate_inode(InodeType::RegularFile)?;
+        if let Err(e) = self.link(inode_num, dir_inode_num, name) {
+            let inode = self.inode(inode_num);
+            self.free_inode(inode_num, inode)?;
+            return Err(e);
+        }
+        Ok(inode_num)
+    }
 
-        let inode_sector = u64::from(inode_block) * (2 << superblock.block_size_raw)
-            + u64::from(
-                (inode_num.saturating_sub(1) % superblock.inodes_per_block())
-                    / u32::from(inodes_per_sector),
-            );
+    /// Create a new, empty directory named `name` in the given directory.
+    ///
+    /// Returns the inode number of the new directory.
+    pub fn create_dir(&mut self, dir_inode_num: u32, name: &str) -> Result<u32> {
+        check_name(name)?;
+        let (parent_block_num, mut parent_block) = self.directory_block(dir_inode_num)?;
+        if directory_block_find(&parent_block, name)?.is_some() {
+            return Err(ErrorKind::AlreadyExists.into());
+        }
 
-        let buf = &mut [0; 512];
-        self.fs.read_sector(buf, inode_sector)?;
+        let inode_num = self.allocate_inode(InodeType::Directory)?;
+        let mut inode = self.inode(inode_num);
+        let block_num = match self.allocate_block() {
+            Ok(block_num) => block_num,
+            Err(e) => {
+                self.free_inode(inode_num, inode)?;
+                return Err(e);
+            }
+        };
+        let block_size = self.superblock().block_size();
+        inode.size_lower = block_size as u32;
+        inode.direct_block_pointers[0] = block_num;
+        inode.disk_sectors_used = self.superblock().sectors_per_block();
+        self.write_inode(inode_num, inode)?;
 
-        let inode_index_in_sector = (inode_num.saturating_sub(1) as usize
-            % inodes_per_sector as usize)
-            * superblock.inode_size as usize;
+        // Start with one unused entry covering the whole block, and fill in `.` and `..`.
+        let mut block = KByteBuf::new_zeroed(block_size as usize)?;
+        DirectoryEntryHeader {
+            inode_num: 0,
+            entry_size: block_size as u16,
+            name_len: 0,
+            entry_type: 0,
+        }
+        .write_to(&mut block);
+        let inserted = directory_block_insert(&mut block, inode_num, InodeType::Directory, ".")?
+            && directory_block_insert(&mut block, dir_inode_num, InodeType::Directory, "..")?;
+        debug_assert!(inserted, "Empty directory block should fit `.` and `..`");
+        self.write_block(block_num, &block)?;
 
-        #[expect(clippy::cast_ptr_alignment, reason = "Following read is unaligned")]
-        let inode_ptr = core::ptr::from_mut(buf)
-            .cast::<Inode>()
-            .wrapping_byte_add(inode_index_in_sector);
+        if !directory_block_insert(&mut parent_block, inode_num, InodeType::Directory, name)? {
+            log::error!("TODO Support big directories");
+            // This also frees the block we gave it.
+            self.free_inode(inode_num, inode)?;
+            return Err(ErrorKind::LimitReached.into());
+        }
+        self.write_block(parent_block_num, &parent_block)?;
 
-        // SAFETY: We just read the inode value here from disk.
-        let mut inode = unsafe { core::ptr::read_unaligned(inode_ptr) };
-        let old_size =
-            u64::from(inode.size_lower) | (u64::from(inode.size_upper_or_directory_acl) << 32);
-        if min_length > old_size {
-            log::info!("increasing file length from {old_size} to {min_length}");
-            inode.size_lower = min_length as u32;
-            inode.size_upper_or_directory_acl = (min_length >> 32) as u32;
-            // SAFETY: `inode_ptr` points into a buffer we just read from, so we can write to it.
-            unsafe { inode_ptr.write_unaligned(inode) };
-            self.fs.write_sector(buf, inode_sector)?;
-        } else {
-            log::info!("Not increasing file length from {old_size} to {min_length}");
+        // The new directory is linked from its parent and its own `.`, and its `..` links the
+        // parent.
+        inode.hard_link_count = 2;
+        self.write_inode(inode_num, inode)?;
+        let mut parent = self.inode(dir_inode_num);
+        parent.hard_link_count = parent.hard_link_count.saturating_add(1);
+        self.write_inode(dir_inode_num, parent)?;
+        Ok(inode_num)
+    }
+
+    /// Remove the directory entry named `name` from the given directory.
+    ///
+    /// This decrements the hard link count on the inode the entry pointed at, and frees the inode
+    /// if that was its last link.
+    pub fn unlink(&mut self, dir_inode_num: u32, name: &str) -> Result<()> {
+        let (block_num, mut block) = self.directory_block(dir_inode_num)?;
+        let (_, header) = directory_block_find(&block, name)?.ok_or(ErrorKind::NotFound)?;
+        let mut inode = self.inode(header.inode_num);
+        if inode.inode_type()? == InodeType::Directory {
+            // Removing directories also requires removing their `.` and `..` entries.
+            return Err(ErrorKind::NotPermitted.into());
+        }
+        directory_block_remove(&mut block, name)?;
+        self.write_block(block_num, &block)?;
+
+        inode.hard_link_count = inode.hard_link_count.saturating_sub(1);
+        if inode.hard_link_count > 0 || self.inode_cache.refcount(header.inode_num) > 0 {
+            // If the inode is held open, it gets freed once it's released.
+            return self.write_inode(header.inode_num, inode);
+        }
+        self.free_inode(header.inode_num, inode)
+    }
+
+    /// Remove the empty directory named `name` from the given directory.
+    ///
+    /// Fails with [`ErrorKind::DirectoryNotEmpty`] if it has entries other than `.` and `..`.
+    pub fn remove_dir(&mut self, dir_inode_num: u32, name: &str) -> Result<()> {
+        if name == "." || name == ".." {
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        let (block_num, mut block) = self.directory_block(dir_inode_num)?;
+        let (_, header) = directory_block_find(&block, name)?.ok_or(ErrorKind::NotFound)?;
+        // This also checks that it's a directory.
+        let (_, dir_block) = self.directory_block(header.inode_num)?;
+        for entry in DirectoryEntryIter::new(&dir_block) {
+            let entry = entry?;
+            if entry.header.inode_num != 0 && entry.name != b"." && entry.name != b".." {
+                return Err(ErrorKind::DirectoryNotEmpty.into());
+            }
+        }
+        directory_block_remove(&mut block, name)?;
+        self.write_block(block_num, &block)?;
+
+        // The directory's `..` no longer links the parent.
+        let mut parent = self.inode(dir_inode_num);
+        parent.hard_link_count = parent.hard_link_count.saturating_sub(1);
+        self.write_inode(dir_inode_num, parent)?;
+        let inode = Inode {
+            hard_link_count: 0,
+            ..self.inode(header.inode_num)
+        };
+        if self.inode_cache.refcount(header.inode_num) > 0 {
+            // If the inode is held open, it gets freed once it's released.
+            return self.write_inode(header.inode_num, inode);
+        }
+        self.free_inode(header.inode_num, inode)
+    }
+
+    /// Release an inode with no remaining links, along with the blocks it owns.
+    fn free_inode(&mut self, inode_num: u32, inode: Inode) -> Result<()> {
+        for &block_num in &inode.direct_block_pointers {
+            if block_num != 0 {
+                self.free_block(block_num)?;
+            }
+        }
+        if inode.singly_indirect_block_pointer != 0
+            || inode.doubly_indirect_block_pointer != 0
+            || inode.triply_indirect_block_pointer != 0
+        {
+            log::error!(
+                "TODO Support indirect block pointers, leaking blocks of inode {inode_num}"
+            );
+        }
+        let is_directory = inode.inode_type()? == InodeType::Directory;
+        self.write_inode(
+            inode_num,
+            Inode {
+                hard_link_count: 0,
+                disk_sectors_used: 0,
+                direct_block_pointers: [0; 12],
+                ..inode
+            },
+        )?;
+
+        let superblock = self.superblock();
+        let group_num = inode_num.saturating_sub(1) / superblock.inodes_per_group;
+        let mut group = self.block_group_descriptor(group_num);
+        self.set_bitmap_bit(
+            group.inode_usage_bitmap_addr,
+            inode_num.saturating_sub(1) % superblock.inodes_per_group,
+            false,
+        )?;
+        group.free_inodes = group.free_inodes.saturating_add(1);
+        if is_directory {
+            group.num_directories = group.num_directories.saturating_sub(1);
+        }
+        self.write_block_group_descriptor(group_num, group)?;
+        self.update_superblock(|superblock| {
+            superblock.free_inodes = superblock.free_inodes.saturating_add(1);
+        })
+    }
+
+    /// Mark the given block as unused.
+    fn free_block(&mut self, block_num: u32) -> Result<()> {
+        let superblock = self.superblock();
+        let block_idx = block_num
+            .checked_sub(superblock.superblock_block_number)
+            .ok_or(ErrorKind::InvalidFormat)?;
+        let group_num = block_idx / superblock.blocks_per_group;
+        let mut group = self.block_group_descriptor(group_num);
+        self.set_bitmap_bit(
+            group.block_usage_bitmap_addr,
+            block_idx % superblock.blocks_per_group,
+            false,
+        )?;
+        group.free_blocks = group.free_blocks.saturating_add(1);
+        self.write_block_group_descriptor(group_num, group)?;
+        self.update_superblock(|superblock| {
+            superblock.free_blocks = superblock.free_blocks.saturating_add(1);
+        })?;
+        // Let the device know it doesn't need to keep the old contents around. The block is
+        // already free on disk, so failing this hint doesn't fail the free.
+        let sectors_per_block = u64::from(superblock.sectors_per_block());
+        if let Err(err) = self
+            .fs
+            .discard(u64::from(block_num) * sectors_per_block, sectors_per_block)
+        {
+            log::warn!("Failed to discard freed block {block_num}: {err}");
         }
         Ok(())
     }
-}
 
-struct DirectoryEntryIter {
-    buf: KByteBuf,
-    idx: usize,
-}
-impl DirectoryEntryIter {
-    fn next(&mut self) -> Option<&DirectoryEntry> {
-        if self.idx >= self.buf.len() {
-            return None;
+    /// Find an unused inode and mark it as used, initializing it with no links.
+    fn allocate_inode(&mut self, inode_type: InodeType) -> Result<u32> {
+        let superblock = self.superblock();
+        for group_num in 0..superblock.num_block_groups() {
+            let mut group = self.block_group_descriptor(group_num);
+            if group.free_inodes == 0 {
+                continue;
+            }
+            let Some(idx) =
+                self.find_clear_bit(group.inode_usage_bitmap_addr, superblock.inodes_per_group)?
+            else {
+                continue;
+            };
+            self.set_bitmap_bit(group.inode_usage_bitmap_addr, idx, true)?;
+            group.free_inodes -= 1;
+            if inode_type == InodeType::Directory {
+                group.num_directories = group.num_directories.saturating_add(1);
+            }
+            self.write_block_group_descriptor(group_num, group)?;
+            self.update_superblock(|superblock| {
+                superblock.free_inodes = superblock.free_inodes.saturating_sub(1);
+            })?;
+
+            let inode_num = group_num * superblock.inodes_per_group + idx + 1;
+            self.write_inode(inode_num, Inode::new(inode_type))?;
+            return Ok(inode_num);
         }
-        #[expect(clippy::cast_ptr_alignment, reason = "KByteBuf has higher alignment")]
-        let entry_ptr = self
-            .buf
-            .as_ptr()
-            .wrapping_byte_add(self.idx)
-            .cast::<DirectoryEntryHeader>();
-        // SAFETY:
-        // If the filesystem is valid, then the memory if correct for this.
-        self.idx += unsafe { &*entry_ptr }.entry_size as usize;
-        // SAFETY:
-        // If the filesystem is valid, then the memory is correct for this. And the return lifetime is tied to `self`, so it is valid for that long.
-        Some(unsafe { DirectoryEntry::for_header(entry_ptr) })
+        log::error!("No free inodes left");
+        Err(ErrorKind::LimitReached.into())
     }
 
-    /// Find the entry with this name, if one exists.
-    ///
-    /// After completion, this iterator is immediately after the found entry, or at the end if it
-    /// couldn't be found.
-    fn find_for_name(&mut self, name: &str) -> Option<DirectoryEntryHeader> {
-        loop {
-            let next_entry = self.next()?;
-            if &next_entry.name == name {
-                return Some(next_entry.header);
+    /// Find an unused block and mark it as used, zeroing its contents.
+    fn allocate_block(&mut self) -> Result<u32> {
+        let superblock = self.superblock();
+        for group_num in 0..superblock.num_block_groups() {
+            let mut group = self.block_group_descriptor(group_num);
+            if group.free_blocks == 0 {
+                continue;
             }
+            let group_start =
+                superblock.superblock_block_number + group_num * superblock.blocks_per_group;
+            // The last group may be cut short.
+            let blocks_in_group = superblock
+                .blocks_per_group
+                .min(superblock.block_count.saturating_sub(group_start));
+            let Some(idx) = self.find_clear_bit(group.block_usage_bitmap_addr, blocks_in_group)?
+            else {
+                continue;
+            };
+            self.set_bitmap_bit(group.block_usage_bitmap_addr, idx, true)?;
+            group.free_blocks -= 1;
+            self.write_block_group_descriptor(group_num, group)?;
+            self.update_superblock(|superblock| {
+                superblock.free_blocks = superblock.free_blocks.saturating_sub(1);
+            })?;
+
+            let block_num = group_start + idx;
+            // Blocks may have been discarded when they were freed, so their contents are
+            // unspecified.
+            let sectors_per_block = u64::from(superblock.sectors_per_block());
+            self.fs
+                .write_zeroes(u64::from(block_num) * sectors_per_block, sectors_per_block)?;
+            return Ok(block_num);
         }
+        log::error!("No free blocks left");
+        Err(ErrorKind::LimitReached.into())
+    }
+
+    /// Find the first clear bit among the first `num_bits` of an on-disk bitmap which starts at
+    /// the given block.
+    fn find_clear_bit(&mut self, bitmap_block: u32, num_bits: u32) -> Result<Option<u32>> {
+        let first_sector =
+            u64::from(bitmap_block) * u64::from(self.superblock().sectors_per_block());
+        let buf = &mut [0; 512];
+        for sector_idx in 0..num_bits.div_ceil(8 * 512) {
+            self.fs
+                .read_sector(buf, first_sector + u64::from(sector_idx))?;
+            if let Some(idx) = Bitmap::from_slice(buf).find_first_zero() {
+                let bit = sector_idx * 512 * 8 + idx as u32;
+                return Ok((bit < num_bits).then_some(bit));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Set the given bit in an on-disk bitmap which starts at the given block.
+    ///
+    /// Returns the previous value of the bit.
+    fn set_bitmap_bit(&mut self, bitmap_block: u32, bit: u32, value: bool) -> Result<bool> {
+        let sector_num = u64::from(bitmap_block) * u64::from(self.superblock().sectors_per_block())
+            + u64::from(bit / 8 / 512);
+        let buf = &mut [0; 512];
+        self.fs.read_sector(buf, sector_num)?;
+        let old_value = Bitmap::from_mut(buf).set((bit % (512 * 8)) as usize, value);
+        self.fs.write_sector(buf, sector_num)?;
+        Ok(old_value)
+    }
+
+    /// Get the block number and contents of a directory's entries.
+    fn directory_block(&mut self, dir_inode_num: u32) -> Result<(u32, KByteBuf)> {
+        let inode = self.inode(dir_inode_num);
+        if inode.inode_type()? != InodeType::Directory {
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        if inode.file_size() != self.superblock().block_size() {
+            log::error!("TODO Support big directories");
+            return Err(ErrorKind::Unsupported.into());
+        }
+        let block_num = inode.direct_block_pointers[0];
+        Ok((block_num, self.read_block(block_num)?))
+    }
+
+    /// Write the given block number.
+    fn write_block(&mut self, block_num: u32, buf: &[u8]) -> Result<()> {
+        let start_sector = u64::from(block_num) * u64::from(self.superblock().sectors_per_block());
+        self.fs.write_sectors(buf, start_sector)
+    }
+
+    /// Write the given block group descriptor back to disk.
+    fn write_block_group_descriptor(
+        &mut self,
+        group_num: u32,
+        desc: BlockGroupDescriptor,
+    ) -> Result<()> {
+        let (sector_num, desc_idx) = self.block_group_descriptor_location(group_num);
+        let buf = &mut [0; 512];
+        self.fs.read_sector(buf, sector_num)?;
+        #[expect(clippy::cast_ptr_alignment, reason = "Write is unaligned")]
+        let desc_ptr = core::ptr::from_mut(buf)
+            .cast::<BlockGroupDescriptor>()
+            .wrapping_add(desc_idx);
+        // SAFETY:
+        // We just read the sector from disk to this memory, so we can write to it.
+        unsafe { desc_ptr.write_unaligned(desc) };
+        self.fs.write_sector(buf, sector_num)?;
+        Ok(())
+    }
+
+    /// Modify the superblock, writing the changes back to disk.
+    fn update_superblock(&mut self, update: impl FnOnce(&mut Superblock)) -> Result<()> {
+        #![expect(
+            clippy::cast_ptr_alignment,
+            reason = "Byte buffer comes from a more-aligned allocation, so it will be aligned"
+        )]
+        let mut superblock = self.superblock();
+        update(&mut superblock);
+        let superblock_ptr = core::ptr::from_mut(self.superblock.as_mut()).cast::<Superblock>();
+        // SAFETY: The buffer is big enough and aligned for a superblock, and we own it.
+        unsafe { superblock_ptr.write(superblock) };
+        self.fs.write_sectors(&self.superblock, 2)
     }
 }
 
@@ -396,6 +935,32 @@ impl Superblock {
         if self.inodes_per_group == 0 || self.blocks_per_group == 0 {
             return Err(ErrorKind::Io.into());
         }
+        // ext2 blocks are at most 64 KiB.
+        if self.block_size_raw > 6 {
+            log::error!("Invalid block size 1024 << {}", self.block_size_raw);
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        // Each group's bitmaps are one block long, so the groups can't have more than that many
+        // blocks or inodes.
+        let bits_per_block = self.block_size() * 8;
+        if u64::from(self.blocks_per_group) > bits_per_block
+            || u64::from(self.inodes_per_group) > bits_per_block
+        {
+            log::error!(
+                "Groups of {} blocks and {} inodes don't fit in {}-bit bitmaps",
+                self.blocks_per_group,
+                self.inodes_per_group,
+                bits_per_block
+            );
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        if self.superblock_block_number >= self.block_count {
+            log::error!(
+                "First data block {} is past the end of the filesystem",
+                self.superblock_block_number
+            );
+            return Err(ErrorKind::InvalidFormat.into());
+        }
         if self.inode_count.div_ceil(self.inodes_per_group)
             != self.block_count.div_ceil(self.blocks_per_group)
         {
@@ -450,7 +1015,7 @@ struct BlockGroupDescriptor {
 }
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 struct Inode {
     /// The file type and the permissions.
     ///
@@ -478,12 +1043,53 @@ struct Inode {
     operating_system_specific_2: [u8; 12],
 }
 impl Inode {
+    /// Make a new, empty inode of the given type, with no links.
+    fn new(inode_type: InodeType) -> Self {
+        let permissions = if inode_type == InodeType::Directory {
+            Permissions::USER_READ
+                | Permissions::USER_WRITE
+                | Permissions::USER_EXECUTE
+                | Permissions::GROUP_READ
+                | Permissions::GROUP_EXECUTE
+                | Permissions::OTHER_READ
+                | Permissions::OTHER_EXECUTE
+        } else {
+            Permissions::USER_READ
+                | Permissions::USER_WRITE
+                | Permissions::GROUP_READ
+                | Permissions::OTHER_READ
+        };
+        Self {
+            type_and_permissions: ((inode_type as u16) << 12) | u16::from(permissions),
+            user_id: 0,
+            size_lower: 0,
+            last_access_time: 0,
+            creation_time: 0,
+            modification_time: 0,
+            deletion_time: 0,
+            group_id: 0,
+            hard_link_count: 0,
+            disk_sectors_used: 0,
+            flags: InodeFlags::empty(),
+            operating_system_specific_1: [0; 4],
+            direct_block_pointers: [0; 12],
+            singly_indirect_block_pointer: 0,
+            doubly_indirect_block_pointer: 0,
+            triply_indirect_block_pointer: 0,
+            generation_number: 0,
+            extended_attributes: 0,
+            size_upper_or_directory_acl: 0,
+            fragment_block_address: 0,
+            operating_system_specific_2: [0; 12],
+        }
+    }
+
     fn file_size(&self) -> u64 {
         u64::from(self.size_lower) | (u64::from(self.size_upper_or_directory_acl) << 32)
     }
 
-    fn inode_type(&self) -> InodeType {
-        match (self.type_and_permissions >> 12) & 0xF {
+    fn inode_type(&self) -> Result<InodeType> {
+        Ok(match (self.type_and_permissions >> 12) & 0xF {
             1 => InodeType::Fifo,
             2 => InodeType::CharacterDevice,
             4 => InodeType::Directory,
@@ -491,8 +1097,11 @@ impl Inode {
             8 => InodeType::RegularFile,
             10 => InodeType::SymbolicLink,
             12 => InodeType::UnixSocket,
-            ty => unreachable!("Invalid inode type {ty}"),
-        }
+            ty => {
+                log::error!("Invalid inode type {ty}");
+                return Err(ErrorKind::InvalidFormat.into());
+            }
+        })
     }
 }
 
@@ -538,6 +1147,20 @@ pub enum InodeType {
     SymbolicLink = 10,
     UnixSocket = 12,
 }
+impl InodeType {
+    /// The type indicator stored in directory entries pointing at inodes of this type.
+    fn directory_entry_type(self) -> u8 {
+        match self {
+            Self::RegularFile => 1,
+            Self::Directory => 2,
+            Self::CharacterDevice => 3,
+            Self::BlockDevice => 4,
+            Self::Fifo => 5,
+            Self::UnixSocket => 6,
+            Self::SymbolicLink => 7,
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
@@ -547,29 +1170,195 @@ struct DirectoryEntryHeader {
     name_len: u8,
     entry_type: u8,
 }
+impl DirectoryEntryHeader {
+    /// The size of the header on disk.
+    const SIZE: usize = size_of::<Self>();
 
-#[repr(C)]
-#[derive(Debug)]
-struct DirectoryEntry {
+    /// Parse the header at the start of `bytes`, if there's enough room for one.
+    fn read_from(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.first_chunk::<{ Self::SIZE }>()?;
+        Some(Self {
+            inode_num: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            entry_size: u16::from_le_bytes([bytes[4], bytes[5]]),
+            name_len: bytes[6],
+            entry_type: bytes[7],
+        })
+    }
+
+    /// Write this header to the start of `bytes`.
+    fn write_to(self, bytes: &mut [u8]) {
+        let bytes = bytes
+            .first_chunk_mut::<{ Self::SIZE }>()
+            .expect("No room for directory entry header");
+        bytes[..4].copy_from_slice(&self.inode_num.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.entry_size.to_le_bytes());
+        bytes[6] = self.name_len;
+        bytes[7] = self.entry_type;
+    }
+
+    /// The number of bytes an entry needs to hold a name of the given length.
+    fn size_for_name_len(name_len: usize) -> usize {
+        (Self::SIZE + name_len).next_multiple_of(4)
+    }
+}
+
+/// Check that `name` can be the name of a directory entry.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > usize::from(u8::MAX) || name.contains('/') {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    Ok(())
+}
+
+/// Find the entry named `name` in a directory block, returning its offset and header.
+fn directory_block_find(block: &[u8], name: &str) -> Result<Option<(usize, DirectoryEntryHeader)>> {
+    Ok(DirectoryEntryIter::new(block)
+        .find_for_name(name)?
+        .map(|entry| (entry.offset, entry.header)))
+}
+
+/// Insert a new entry into a directory block, if there's room for it.
+///
+/// Returns whether the entry was inserted.
+fn directory_block_insert(
+    block: &mut [u8],
+    inode_num: u32,
+    inode_type: InodeType,
+    name: &str,
+) -> Result<bool> {
+    let needed_size = DirectoryEntryHeader::size_for_name_len(name.len());
+    // Find an entry with enough slack space after it to fit the new entry.
+    let mut found = None;
+    for entry in DirectoryEntryIter::new(block) {
+        let DirectoryEntry { offset, header, .. } = entry?;
+        let used_size = if header.inode_num == 0 {
+            0
+        } else {
+            DirectoryEntryHeader::size_for_name_len(header.name_len.into())
+        };
+        if usize::from(header.entry_size) - used_size >= needed_size {
+            found = Some((offset, header, used_size));
+            break;
+        }
+    }
+    let Some((idx, header, used_size)) = found else {
+        return Ok(false);
+    };
+    if used_size > 0 {
+        DirectoryEntryHeader {
+            entry_size: used_size as u16,
+            ..header
+        }
+        .write_to(&mut block[idx..]);
+    }
+    let new_idx = idx + used_size;
+    DirectoryEntryHeader {
+        inode_num,
+        entry_size: header.entry_size - used_size as u16,
+        name_len: name.len() as u8,
+        entry_type: inode_type.directory_entry_type(),
+    }
+    .write_to(&mut block[new_idx..]);
+    block[new_idx + DirectoryEntryHeader::SIZE..][..name.len()].copy_from_slice(name.as_bytes());
+    Ok(true)
+}
+
+/// Remove the entry named `name` from a directory block.
+fn directory_block_remove(block: &mut [u8], name: &str) -> Result<()> {
+    let (idx, header) = directory_block_find(block, name)?.ok_or(ErrorKind::NotFound)?;
+    let mut previous = None;
+    for entry in DirectoryEntryIter::new(block) {
+        let entry = entry?;
+        if entry.offset + usize::from(entry.header.entry_size) == idx {
+            previous = Some((entry.offset, entry.header));
+            break;
+        }
+    }
+    if let Some((prev_idx, prev_header)) = previous {
+        // Fold the removed entry into the space of the one before it.
+        DirectoryEntryHeader {
+            entry_size: prev_header.entry_size + header.entry_size,
+            ..prev_header
+        }
+        .write_to(&mut block[prev_idx..]);
+    } else {
+        // The first entry in a block has nothing to fold into, so it's marked unused instead.
+        DirectoryEntryHeader {
+            inode_num: 0,
+            ..header
+        }
+        .write_to(&mut block[idx..]);
+    }
+    Ok(())
+}
+
+/// An entry in a directory block, borrowed from the block's contents.
+#[derive(Debug, Clone, Copy)]
+struct DirectoryEntry<'block> {
+    /// The offset of this entry within the block.
+    offset: usize,
     header: DirectoryEntryHeader,
-    // TODO I don't think names have to be utf-8
-    name: str,
+    // TODO I don't think names have to be utf-8, so we leave them as bytes.
+    name: &'block [u8],
+}
+
+/// An iterator over the entries in a directory block.
+///
+/// Each entry is checked to fit within the block before it's returned. If an entry is corrupt, the
+/// iterator yields an error and then stops.
+struct DirectoryEntryIter<'block> {
+    block: &'block [u8],
+    /// The offset of the next entry, or `None` once we've finished.
+    idx: Option<usize>,
 }
-impl DirectoryEntry {
-    /// Create a [`DirectoryEntry`] for the given header.
+impl<'block> DirectoryEntryIter<'block> {
+    fn new(block: &'block [u8]) -> Self {
+        Self {
+            block,
+            idx: Some(0),
+        }
+    }
+
+    /// Find the in-use entry with this name, if one exists.
     ///
-    /// # Safety
-    /// `header_ptr` must be valid for reading for `'a`, and also have provenance for the name
-    /// section that follows.
-    unsafe fn for_header<'a>(header_ptr: *const DirectoryEntryHeader) -> &'a Self {
-        // SAFETY: By precondition, this is a valid header.
-        let len = unsafe { &*header_ptr }.name_len as usize;
-        // We make a pointer to the value by first artificially constructing a pointer to a slice
-        // with the right length. The slice pointer has the same format, so we can transmute.
-        let similar_ptr = core::ptr::slice_from_raw_parts(header_ptr, len);
-        let entry_ptr: *const Self = similar_ptr as *const Self;
-        // SAFETY: By precondition, this is a valid header.
-        unsafe { &*entry_ptr }
+    /// After completion, this iterator is immediately after the found entry, or at the end if it
+    /// couldn't be found.
+    fn find_for_name(&mut self, name: &str) -> Result<Option<DirectoryEntry<'block>>> {
+        for entry in self {
+            let entry = entry?;
+            if entry.header.inode_num != 0 && entry.name == name.as_bytes() {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+}
+impl<'block> Iterator for DirectoryEntryIter<'block> {
+    type Item = Result<DirectoryEntry<'block>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.idx.take()?;
+        if idx >= self.block.len() {
+            return None;
+        }
+        let entry = &self.block[idx..];
+        let Some(header) = DirectoryEntryHeader::read_from(entry) else {
+            log::error!("Truncated directory entry at offset {idx}");
+            return Some(Err(ErrorKind::InvalidFormat.into()));
+        };
+        let entry_size = usize::from(header.entry_size);
+        if entry_size < DirectoryEntryHeader::size_for_name_len(header.name_len.into())
+            || entry_size > entry.len()
+        {
+            log::error!("Corrupt directory entry at offset {idx}");
+            return Some(Err(ErrorKind::InvalidFormat.into()));
+        }
+        self.idx = Some(idx + entry_size);
+        Some(Ok(DirectoryEntry {
+            offset: idx,
+            header,
+            name: &entry[DirectoryEntryHeader::SIZE..][..header.name_len.into()],
+        }))
     }
 }
 
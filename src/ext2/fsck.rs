@@ -0,0 +1,274 @@
+//! A lightweight consistency check for ext2 filesystems.
+//!
+//! See [`Ext2::check_consistency`].
+
+use util::bitmap::Bitmap;
+
+use super::{DirectoryEntryIter, Ext2, InodeType, ROOT_INODE};
+use crate::{
+    alloc::KByteBuf,
+    block::BlockDevice,
+    error::{ErrorKind, Result},
+};
+
+impl<D: BlockDevice> Ext2<D> {
+    /// Check that the on-disk structures of this filesystem agree with each other.
+    ///
+    /// This checks that:
+    /// - The free counts in the superblock and block group descriptors match the bitmaps.
+    /// - Every in-use inode has a valid type and at least one link, and every block it points at
+    ///   is in range and marked as used.
+    /// - Every directory entry points at an in-use inode of the type it claims.
+    /// - Every in-use inode is reachable from the root directory.
+    ///
+    /// Each problem found is logged, and an error is returned if there were any. Nothing is
+    /// repaired.
+    pub fn check_consistency(&mut self) -> Result<()> {
+        let mut report = Report { problems: 0 };
+        let reachable = self.check_directory_tree(&mut report)?;
+        self.check_block_groups(Bitmap::from_slice(&reachable), &mut report)?;
+        if report.problems > 0 {
+            log::error!(
+                "Filesystem consistency check found {} problems",
+                report.problems
+            );
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        log::info!("Filesystem consistency check passed");
+        Ok(())
+    }
+
+    /// Walk the directory tree from the root, checking each entry.
+    ///
+    /// Returns a bitmap of which inodes are reachable.
+    fn check_directory_tree(&mut self, report: &mut Report) -> Result<KByteBuf> {
+        let superblock = self.superblock();
+        let bitmap_len = superblock.inode_count.div_ceil(8) as usize;
+        let mut reachable = KByteBuf::new_zeroed(bitmap_len)?;
+        // Directories we've reached but haven't yet looked inside of. Tracking these in a bitmap
+        // instead of a stack means we can't run out of room no matter the shape of the tree.
+        let mut pending = KByteBuf::new_zeroed(bitmap_len)?;
+        let reachable_bits = Bitmap::from_mut(&mut reachable);
+        let pending = Bitmap::from_mut(&mut pending);
+        reachable_bits.set(inode_idx(ROOT_INODE), true);
+        pending.set(inode_idx(ROOT_INODE), true);
+        while let Some(idx) = pending.find_first_one() {
+            pending.set(idx, false);
+            let dir_inode_num = idx as u32 + 1;
+            let block = match self.directory_block(dir_inode_num) {
+                Ok((_, block)) => block,
+                Err(e) if matches!(e.kind, ErrorKind::Unsupported) => {
+                    log::warn!("Skipping unsupported directory {dir_inode_num}");
+                    continue;
+                }
+                Err(e) => {
+                    report.problem(format_args!("Can't read directory {dir_inode_num}: {e}"));
+                    continue;
+                }
+            };
+            for entry in DirectoryEntryIter::new(&block) {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        report.problem(format_args!("Corrupt directory {dir_inode_num}: {e}"));
+                        break;
+                    }
+                };
+                let inode_num = entry.header.inode_num;
+                if inode_num == 0 || entry.name == b"." || entry.name == b".." {
+                    continue;
+                }
+                if inode_num > superblock.inode_count || !self.inode_in_use(inode_num)? {
+                    report.problem(format_args!(
+                        "Directory {dir_inode_num} has entry for unused inode {inode_num}"
+                    ));
+                    continue;
+                }
+                let Ok(inode_type) = self.read_inode(inode_num).inode_type() else {
+                    // This gets reported when checking the inode itself.
+                    continue;
+                };
+                if entry.header.entry_type != 0
+                    && entry.header.entry_type != inode_type.directory_entry_type()
+                {
+                    report.problem(format_args!(
+                        "Directory {dir_inode_num} has wrong type for inode {inode_num}"
+                    ));
+                }
+                if reachable_bits.set(inode_idx(inode_num), true) {
+                    if inode_type == InodeType::Directory {
+                        report.problem(format_args!("Directory {inode_num} has multiple links"));
+                    }
+                    continue;
+                }
+                if inode_type == InodeType::Directory {
+                    pending.set(inode_idx(inode_num), true);
+                }
+            }
+        }
+        Ok(reachable)
+    }
+
+    /// Check each block group's bitmaps, counts, and in-use inodes.
+    fn check_block_groups(&mut self, reachable: &Bitmap<u8>, report: &mut Report) -> Result<()> {
+        let superblock = self.superblock();
+        let first_data_block = superblock.superblock_block_number;
+        let (mut total_free_blocks, mut total_free_inodes) = (0, 0);
+        for group_num in 0..superblock.num_block_groups() {
+            let group = self.block_group_descriptor(group_num);
+            let group_first_block = first_data_block + group_num * superblock.blocks_per_group;
+            let blocks_in_group = superblock
+                .blocks_per_group
+                .min(superblock.block_count - group_first_block);
+            let group_first_inode = group_num * superblock.inodes_per_group + 1;
+            let inodes_in_group = superblock
+                .inodes_per_group
+                .min(superblock.inode_count + 1 - group_first_inode);
+
+            let block_bitmap = self.read_block(group.block_usage_bitmap_addr)?;
+            let free_blocks = count_clear_bits(Bitmap::from_slice(&block_bitmap), blocks_in_group);
+            if free_blocks != u32::from(group.free_blocks) {
+                report.problem(format_args!(
+                    "Group {group_num} claims {} free blocks, but has {free_blocks}",
+                    group.free_blocks
+                ));
+            }
+            total_free_blocks += free_blocks;
+
+            let inode_bitmap = self.read_block(group.inode_usage_bitmap_addr)?;
+            let inode_bitmap = Bitmap::from_slice(&inode_bitmap);
+            let free_inodes = count_clear_bits(inode_bitmap, inodes_in_group);
+            if free_inodes != u32::from(group.free_inodes) {
+                report.problem(format_args!(
+                    "Group {group_num} claims {} free inodes, but has {free_inodes}",
+                    group.free_inodes
+                ));
+            }
+            total_free_inodes += free_inodes;
+
+            let mut num_directories = 0;
+            for idx in 0..inodes_in_group {
+                let inode_num = group_first_inode + idx;
+                if !inode_bitmap.get(idx as usize) {
+                    continue;
+                }
+                // Reserved inodes have special meanings, so we only check the root directory.
+                if inode_num < superblock.first_non_reserved_inode && inode_num != ROOT_INODE {
+                    continue;
+                }
+                if self.check_inode(inode_num, report)? == Some(InodeType::Directory) {
+                    num_directories += 1;
+                }
+                if !reachable.get(inode_idx(inode_num)) {
+                    report.problem(format_args!("Inode {inode_num} is not in any directory"));
+                }
+            }
+            if num_directories != u32::from(group.num_directories) {
+                report.problem(format_args!(
+                    "Group {group_num} claims {} directories, but has {num_directories}",
+                    group.num_directories
+                ));
+            }
+        }
+        if total_free_blocks != superblock.free_blocks {
+            report.problem(format_args!(
+                "Superblock claims {} free blocks, but groups have {total_free_blocks}",
+                superblock.free_blocks
+            ));
+        }
+        if total_free_inodes != superblock.free_inodes {
+            report.problem(format_args!(
+                "Superblock claims {} free inodes, but groups have {total_free_inodes}",
+                superblock.free_inodes
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a single in-use inode, returning its type if it's valid.
+    fn check_inode(&mut self, inode_num: u32, report: &mut Report) -> Result<Option<InodeType>> {
+        let inode = self.read_inode(inode_num);
+        let Ok(inode_type) = inode.inode_type() else {
+            report.problem(format_args!("Inode {inode_num} has an invalid type"));
+            return Ok(None);
+        };
+        if inode.hard_link_count == 0 {
+            report.problem(format_args!("In-use inode {inode_num} has no links"));
+        }
+        // We don't follow indirect blocks yet, but we can still check the blocks of pointers.
+        let block_pointers = inode.direct_block_pointers.into_iter().chain([
+            inode.singly_indirect_block_pointer,
+            inode.doubly_indirect_block_pointer,
+            inode.triply_indirect_block_pointer,
+        ]);
+        for block_num in block_pointers {
+            if block_num == 0 {
+                continue;
+            }
+            if !self.block_in_use(block_num)? {
+                report.problem(format_args!(
+                    "Inode {inode_num} uses block {block_num}, which isn't allocated"
+                ));
+            }
+        }
+        Ok(Some(inode_type))
+    }
+
+    /// Check whether the given inode is marked as used in its bitmap.
+    fn inode_in_use(&mut self, inode_num: u32) -> Result<bool> {
+        let superblock = self.superblock();
+        let inode_idx = inode_num.saturating_sub(1);
+        let group = self.block_group_descriptor(inode_idx / superblock.inodes_per_group);
+        self.bitmap_bit(
+            group.inode_usage_bitmap_addr,
+            inode_idx % superblock.inodes_per_group,
+        )
+    }
+
+    /// Check whether the given block is in range and marked as used in its bitmap.
+    fn block_in_use(&mut self, block_num: u32) -> Result<bool> {
+        let superblock = self.superblock();
+        if block_num >= superblock.block_count {
+            return Ok(false);
+        }
+        let Some(block_idx) = block_num.checked_sub(superblock.superblock_block_number) else {
+            return Ok(false);
+        };
+        let group = self.block_group_descriptor(block_idx / superblock.blocks_per_group);
+        self.bitmap_bit(
+            group.block_usage_bitmap_addr,
+            block_idx % superblock.blocks_per_group,
+        )
+    }
+
+    /// Read the given bit in an on-disk bitmap which starts at the given block.
+    fn bitmap_bit(&mut self, bitmap_block: u32, bit: u32) -> Result<bool> {
+        let sector_num = u64::from(bitmap_block) * u64::from(self.superblock().sectors_per_block())
+            + u64::from(bit / 8 / 512);
+        let buf = &mut [0; 512];
+        self.fs.read_sector(buf, sector_num)?;
+        Ok(Bitmap::from_slice(buf).get((bit % (512 * 8)) as usize))
+    }
+}
+
+/// A tally of the problems found by a consistency check.
+struct Report {
+    problems: usize,
+}
+impl Report {
+    /// Log a problem with the filesystem.
+    fn problem(&mut self, message: core::fmt::Arguments<'_>) {
+        log::error!("fsck: {message}");
+        self.problems += 1;
+    }
+}
+
+/// Get the index of an inode in bitmaps of inodes, which start from inode 1.
+fn inode_idx(inode_num: u32) -> usize {
+    inode_num as usize - 1
+}
+
+/// Count the clear bits among the first `len` bits of a bitmap.
+fn count_clear_bits(bitmap: &Bitmap<u8>, len: u32) -> u32 {
+    (0..len as usize).filter(|&bit| !bitmap.get(bit)).count() as u32
+}
@@ -0,0 +1,177 @@
+//! A cache of parsed inodes.
+//!
+//! See [`InodeCache`].
+
+use super::Inode;
+
+/// The number of inodes to keep cached for each filesystem.
+const INODE_CACHE_SIZE: usize = 32;
+
+/// A cache of parsed inodes for one filesystem.
+///
+/// Each filesystem keeps its own cache, so entries are effectively keyed by the device and the
+/// inode number.
+///
+/// Entries can be pinned by acquiring a reference to them (e.g. while a file is open), which keeps
+/// them from being evicted. Changes to pinned entries are held in the cache and marked dirty until
+/// the last reference is released, while changes to unpinned entries should be written straight
+/// through to disk.
+pub(super) struct InodeCache {
+    /// The cached entries.
+    entries: [Option<CachedInode>; INODE_CACHE_SIZE],
+    /// A counter incremented on every access, used to find the least-recently used entry.
+    clock: u64,
+}
+impl InodeCache {
+    /// Create an empty cache.
+    pub(super) const fn new() -> Self {
+        Self {
+            entries: [const { None }; INODE_CACHE_SIZE],
+            clock: 0,
+        }
+    }
+
+    /// Get the cached copy of an inode, if present.
+    pub(super) fn get(&mut self, inode_num: u32) -> Option<Inode> {
+        self.entry_mut(inode_num).map(|entry| entry.inode)
+    }
+
+    /// Insert a freshly-read inode into the cache.
+    ///
+    /// This may evict the least-recently used unpinned entry. Returns `false` if every entry is
+    /// pinned, so there's no room for the new inode.
+    pub(super) fn insert(&mut self, inode_num: u32, inode: Inode) -> bool {
+        debug_assert!(
+            self.entry_mut(inode_num).is_none(),
+            "Inserting inode {inode_num} twice"
+        );
+        let Some(slot) = self
+            .entries
+            .iter_mut()
+            .filter(|slot| slot.as_ref().is_none_or(|entry| entry.refcount == 0))
+            .min_by_key(|slot| slot.as_ref().map_or(0, |entry| entry.last_used))
+        else {
+            return false;
+        };
+        self.clock += 1;
+        *slot = Some(CachedInode {
+            inode_num,
+            inode,
+            refcount: 0,
+            dirty: false,
+            last_used: self.clock,
+        });
+        true
+    }
+
+    /// Update the cached copy of an inode.
+    ///
+    /// Returns `true` if the entry is pinned, in which case the change is held in the cache until
+    /// the entry is released. Otherwise, the caller is responsible for writing the change to disk.
+    pub(super) fn update(&mut self, inode_num: u32, inode: Inode) -> bool {
+        let Some(entry) = self.entry_mut(inode_num) else {
+            return false;
+        };
+        entry.inode = inode;
+        if entry.refcount > 0 {
+            entry.dirty = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Take a reference to a cached inode, pinning it in the cache.
+    ///
+    /// Returns `false` if the inode isn't cached.
+    pub(super) fn acquire(&mut self, inode_num: u32) -> bool {
+        let Some(entry) = self.entry_mut(inode_num) else {
+            return false;
+        };
+        entry.refcount += 1;
+        true
+    }
+
+    /// Release a reference taken by [`Self::acquire`].
+    ///
+    /// If this was the last reference, the inode is returned so the caller can write it back (if
+    /// it was dirty) or free it (if it has no links left).
+    pub(super) fn release(&mut self, inode_num: u32) -> Option<ReleasedInode> {
+        let entry = self.entry_mut(inode_num)?;
+        debug_assert!(entry.refcount > 0, "Releasing unreferenced inode");
+        entry.refcount = entry.refcount.saturating_sub(1);
+        if entry.refcount > 0 {
+            return None;
+        }
+        let dirty = core::mem::replace(&mut entry.dirty, false);
+        Some(ReleasedInode {
+            inode: entry.inode,
+            dirty,
+        })
+    }
+
+    /// Take the cached copy of an inode if it has changes not yet written to disk, marking it clean.
+    ///
+    /// The caller is responsible for writing the returned inode to disk.
+    pub(super) fn take_dirty(&mut self, inode_num: u32) -> Option<Inode> {
+        let entry = self.entry_mut(inode_num)?;
+        core::mem::replace(&mut entry.dirty, false).then_some(entry.inode)
+    }
+
+    /// Take the cached copy of any inode with changes not yet written to disk, marking it clean.
+    ///
+    /// Returns the inode's number along with it. The caller is responsible for writing the
+    /// returned inode to disk.
+    pub(super) fn take_any_dirty(&mut self) -> Option<(u32, Inode)> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .flatten()
+            .find(|entry| entry.dirty)?;
+        entry.dirty = false;
+        Some((entry.inode_num, entry.inode))
+    }
+
+    /// Get the number of references held to an inode.
+    pub(super) fn refcount(&self, inode_num: u32) -> u32 {
+        self.entries
+            .iter()
+            .flatten()
+            .find(|entry| entry.inode_num == inode_num)
+            .map_or(0, |entry| entry.refcount)
+    }
+
+    /// Find the entry for an inode, marking it as recently used.
+    fn entry_mut(&mut self, inode_num: u32) -> Option<&mut CachedInode> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .flatten()
+            .find(|entry| entry.inode_num == inode_num)?;
+        self.clock += 1;
+        entry.last_used = self.clock;
+        Some(entry)
+    }
+}
+
+/// An inode whose last reference was released from the [`InodeCache`].
+pub(super) struct ReleasedInode {
+    /// The latest version of the inode.
+    pub(super) inode: Inode,
+    /// Whether the inode was changed without being written back to disk.
+    pub(super) dirty: bool,
+}
+
+/// An entry in the [`InodeCache`].
+struct CachedInode {
+    /// The number of the cached inode.
+    inode_num: u32,
+    /// The parsed inode.
+    inode: Inode,
+    /// The number of references pinning this entry.
+    refcount: u32,
+    /// Whether `inode` has changes that haven't been written to disk.
+    dirty: bool,
+    /// The value of [`InodeCache::clock`] when this entry was last accessed.
+    last_used: u64,
+}
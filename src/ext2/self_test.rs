@@ -0,0 +1,59 @@
+//! Tests of the ext2 implementation, run against a mounted filesystem.
+//!
+//! See [`Ext2::self_test`].
+
+use super::{Ext2, ROOT_INODE};
+use crate::{
+    block::BlockDevice,
+    error::{ErrorKind, Result},
+};
+
+/// The name of the scratch file the tests use, in the root directory.
+const SCRATCH_FILE: &str = "ext2-self-test.tmp";
+
+impl<D: BlockDevice> Ext2<D> {
+    /// Run tests of this implementation, using a scratch file in the root directory.
+    ///
+    /// Each failure is logged, and an error is returned if there were any. The scratch file is
+    /// removed afterwards.
+    pub fn self_test(&mut self) -> Result<()> {
+        let inode_num = self.create_file(ROOT_INODE, SCRATCH_FILE)?;
+        let result = self.test_appends(inode_num);
+        self.unlink(ROOT_INODE, SCRATCH_FILE)?;
+        result?;
+        log::info!("Filesystem self-test passed");
+        Ok(())
+    }
+
+    /// Check that writes at the end of a file grow it, including ones which start and end partway
+    /// through a sector, like appends do.
+    fn test_appends(&mut self, inode_num: u32) -> Result<()> {
+        let mut expected = [0; 1100];
+        let mut len = 0;
+        // Short writes within one sector, then ones which cross sectors, then whole sectors.
+        for write_len in [6, 7, 1, 500, 10, 512, 64] {
+            let data = &mut expected[len..][..write_len];
+            for (idx, byte) in data.iter_mut().enumerate() {
+                *byte = b'a' + ((len + idx) % 26) as u8;
+            }
+            let offset = self.file_size(inode_num);
+            let written = self.write_file_from_offset(inode_num, offset, data)?;
+            len += write_len;
+            if written != write_len || self.file_size(inode_num) != len as u64 {
+                log::error!(
+                    "Writing {write_len} bytes at {offset} wrote {written} and left the file {} \
+                     bytes long, expected {len}",
+                    self.file_size(inode_num),
+                );
+                return Err(ErrorKind::InvalidFormat.into());
+            }
+        }
+        let mut contents = [0; 1100];
+        let read = self.read_file_from_offset(inode_num, 0, &mut contents)?;
+        if contents[..read] != expected[..len] {
+            log::error!("File contents don't match what was appended");
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,506 @@
+//! Reading the flattened device tree (FDT) the firmware hands us at boot.
+//!
+//! The device tree describes the machine we're running on, so we don't need to hardcode the layout
+//! of QEMU's `virt` machine. We copy out everything we need while booting, so the memory holding
+//! the tree can be reused afterwards.
+//!
+//! Designed according to the spec from
+//! <https://github.com/devicetree-org/devicetree-specification/releases/tag/v0.4>.
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use util::cell::LazyLock;
+
+use crate::error::{ErrorKind, Result};
+
+/// The magic number at the start of every device tree blob.
+const FDT_MAGIC: u32 = 0xD00D_FEED;
+/// The oldest version of the format whose layout we understand.
+const FDT_COMPATIBLE_VERSION: u32 = 16;
+/// The token starting a node, followed by its name.
+const FDT_BEGIN_NODE: u32 = 1;
+/// The token ending a node.
+const FDT_END_NODE: u32 = 2;
+/// The token starting a property, followed by its length, name, and value.
+const FDT_PROP: u32 = 3;
+/// A token which should be ignored.
+const FDT_NOP: u32 = 4;
+/// The token ending the structure block.
+const FDT_END: u32 = 9;
+
+/// The deepest nesting of nodes we can parse.
+const MAX_DEPTH: usize = 16;
+/// The most virtio-mmio devices we keep track of.
+const MAX_VIRTIO_DEVICES: usize = 16;
+/// The most reserved memory regions we keep track of.
+const MAX_RESERVED_REGIONS: usize = 8;
+/// The longest command line we keep.
+const MAX_BOOTARGS_LEN: usize = 256;
+
+/// The address of the device tree blob, which [`init`] sets before anything reads [`BOOT_INFO`].
+static DTB_ADDRESS: AtomicUsize = AtomicUsize::new(0);
+
+/// What the device tree told us about the machine.
+///
+/// # Panics
+/// Reading this before [`init`] is called panics.
+pub static BOOT_INFO: LazyLock<BootInfo> = LazyLock::new(|| {
+    let address = DTB_ADDRESS.load(Ordering::Acquire);
+    assert!(
+        address != 0,
+        "Boot info read before the device tree was found"
+    );
+    // SAFETY: `init` checked that there's a device tree at this address.
+    let blob = unsafe { blob_at_address(address) }.expect("Device tree went missing");
+    BootInfo::parse(blob).expect("Failed to parse the device tree")
+});
+
+/// Read the device tree blob at `address` into [`BOOT_INFO`].
+///
+/// # Panics
+/// Panics if there isn't a valid device tree there, or it doesn't describe the hardware we need.
+///
+/// # Safety
+/// `address` must be the device tree blob the firmware gave us, and nothing can write to it until
+/// this returns.
+pub unsafe fn init(address: usize) {
+    // SAFETY: By method precondition, this is a device tree.
+    if let Err(e) = unsafe { blob_at_address(address) } {
+        panic!("No device tree at {address:#X}: {e}");
+    }
+    DTB_ADDRESS.store(address, Ordering::Release);
+    BOOT_INFO.log_summary();
+}
+
+/// Get the device tree blob starting at `address`, checking its header.
+///
+/// # Safety
+/// If `address` points to a device tree header, the whole blob it describes must be readable and
+/// unchanging for the rest of the kernel's life.
+unsafe fn blob_at_address(address: usize) -> Result<&'static [u8]> {
+    if address == 0 || !address.is_multiple_of(8) {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    let header = core::ptr::with_exposed_provenance::<[u8; 8]>(address);
+    // SAFETY: By method precondition, a device tree is at least long enough to hold its header.
+    let header = unsafe { header.read() };
+    if read_be_u32(&header, 0) != Some(FDT_MAGIC) {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    let total_size = read_be_u32(&header, 4).ok_or(ErrorKind::InvalidFormat)?;
+    // SAFETY: By method precondition, the blob is as long as its header says and lasts forever.
+    Ok(unsafe {
+        core::slice::from_raw_parts(
+            core::ptr::with_exposed_provenance(address),
+            total_size as usize,
+        )
+    })
+}
+
+/// Read the big-endian `u32` at `offset` in `bytes`, if it's in range.
+fn read_be_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..)?.first_chunk::<4>()?;
+    Some(u32::from_be_bytes(*bytes))
+}
+
+/// Read the big-endian `u64` at `offset` in `bytes`, if it's in range.
+fn read_be_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let bytes = bytes.get(offset..)?.first_chunk::<8>()?;
+    Some(u64::from_be_bytes(*bytes))
+}
+
+/// Read a number made of `num_cells` big-endian cells from the start of `bytes`.
+///
+/// Returns the number and the rest of the bytes.
+fn read_cells(bytes: &[u8], num_cells: u32) -> Option<(u64, &[u8])> {
+    let len = num_cells as usize * 4;
+    let (cells, rest) = bytes.split_at_checked(len)?;
+    let value = cells
+        .as_chunks::<4>()
+        .0
+        .iter()
+        .try_fold(0_u64, |value, cell| {
+            value
+                .checked_shl(32)
+                .map(|value| value | u64::from(u32::from_be_bytes(*cell)))
+        })?;
+    Some((value, rest))
+}
+
+/// A virtio-mmio device described by the device tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmioDevice {
+    /// The address of the device's registers.
+    pub address: usize,
+    /// The PLIC interrupt source the device is wired to.
+    pub irq: u32,
+}
+
+/// The parts of the device tree the kernel cares about.
+#[derive(Debug)]
+pub struct BootInfo {
+    /// The range of physical memory.
+    memory: Range<usize>,
+    /// Memory the firmware set aside, which we must leave alone.
+    reserved: [Option<Range<usize>>; MAX_RESERVED_REGIONS],
+    /// The number of harts (CPU cores) in the machine.
+    num_harts: usize,
+    /// The virtio-mmio devices, sorted by address.
+    virtio: [Option<MmioDevice>; MAX_VIRTIO_DEVICES],
+    /// The address of the PLIC's registers.
+    plic: usize,
+    /// The address of the CLINT's registers, if there is one.
+    clint: Option<usize>,
+    /// The address of the test finisher's register, if there is one.
+    test_finisher: Option<usize>,
+    /// The kernel command line, which is the first `bootargs_len` bytes.
+    bootargs: [u8; MAX_BOOTARGS_LEN],
+    /// The length of the kernel command line.
+    bootargs_len: usize,
+}
+impl BootInfo {
+    /// Parse the device tree in `blob`.
+    fn parse(blob: &[u8]) -> Result<Self> {
+        let header_field = |offset| read_be_u32(blob, offset).ok_or(ErrorKind::InvalidFormat);
+        if header_field(24)? > FDT_COMPATIBLE_VERSION {
+            return Err(ErrorKind::Unsupported.into());
+        }
+        let section = |offset_field, size_field| {
+            let start = header_field(offset_field)? as usize;
+            let len = header_field(size_field)? as usize;
+            blob.get(start..)
+                .and_then(|section| section.get(..len))
+                .ok_or(ErrorKind::InvalidFormat)
+        };
+        let structure = section(8, 36)?;
+        let strings = section(12, 32)?;
+        // The reserved memory map has no size field, and runs until an empty entry.
+        let reserved_map = blob
+            .get(header_field(16)? as usize..)
+            .ok_or(ErrorKind::InvalidFormat)?;
+
+        let mut this = Self {
+            memory: 0..0,
+            reserved: [const { None }; MAX_RESERVED_REGIONS],
+            num_harts: 0,
+            virtio: [None; MAX_VIRTIO_DEVICES],
+            plic: 0,
+            clint: None,
+            test_finisher: None,
+            bootargs: [0; MAX_BOOTARGS_LEN],
+            bootargs_len: 0,
+        };
+        for entry in reserved_map.chunks_exact(16) {
+            let address = read_be_u64(entry, 0).ok_or(ErrorKind::InvalidFormat)?;
+            let size = read_be_u64(entry, 8).ok_or(ErrorKind::InvalidFormat)?;
+            if size == 0 {
+                break;
+            }
+            this.add_reserved(address, size);
+        }
+        this.parse_structure(structure, strings)?;
+
+        if this.memory.is_empty() {
+            log::error!("Device tree has no memory");
+            return Err(ErrorKind::NotFound.into());
+        }
+        if this.plic == 0 {
+            log::error!("Device tree has no PLIC");
+            return Err(ErrorKind::NotFound.into());
+        }
+        // Keep the devices in slot order, like the addresses they're at.
+        this.virtio
+            .sort_unstable_by_key(|device| device.map_or(usize::MAX, |device| device.address));
+        Ok(this)
+    }
+
+    /// Walk the structure block, recording each node we care about.
+    fn parse_structure(&mut self, structure: &[u8], strings: &[u8]) -> Result<()> {
+        // The nodes we're inside of, outermost first.
+        let mut stack = [Node::default(); MAX_DEPTH];
+        let mut depth = 0;
+        let mut offset = 0;
+        loop {
+            let token = read_be_u32(structure, offset).ok_or(ErrorKind::InvalidFormat)?;
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = structure
+                        .get(offset..)
+                        .and_then(|rest| core::ffi::CStr::from_bytes_until_nul(rest).ok())
+                        .ok_or(ErrorKind::InvalidFormat)?
+                        .to_bytes();
+                    offset += (name.len() + 1).next_multiple_of(4);
+                    *stack.get_mut(depth).ok_or(ErrorKind::LimitReached)? = Node {
+                        name,
+                        ..Node::default()
+                    };
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    depth = depth.checked_sub(1).ok_or(ErrorKind::InvalidFormat)?;
+                    // The root node's cells are the defaults, since it has no parent.
+                    let parent = depth
+                        .checked_sub(1)
+                        .map_or_else(Node::default, |parent| stack[parent]);
+                    self.add_node(&stack[depth], &parent, depth);
+                }
+                FDT_PROP => {
+                    let len = read_be_u32(structure, offset).ok_or(ErrorKind::InvalidFormat)?;
+                    let name_offset =
+                        read_be_u32(structure, offset + 4).ok_or(ErrorKind::InvalidFormat)?;
+                    offset += 8;
+                    let value = structure
+                        .get(offset..)
+                        .and_then(|rest| rest.get(..len as usize))
+                        .ok_or(ErrorKind::InvalidFormat)?;
+                    offset += (len as usize).next_multiple_of(4);
+                    let name = strings
+                        .get(name_offset as usize..)
+                        .and_then(|rest| core::ffi::CStr::from_bytes_until_nul(rest).ok())
+                        .ok_or(ErrorKind::InvalidFormat)?
+                        .to_bytes();
+                    let node = depth
+                        .checked_sub(1)
+                        .and_then(|node| stack.get_mut(node))
+                        .ok_or(ErrorKind::InvalidFormat)?;
+                    node.add_property(name, value)?;
+                }
+                FDT_NOP => {}
+                FDT_END => return Ok(()),
+                _ => return Err(ErrorKind::InvalidFormat.into()),
+            }
+        }
+    }
+
+    /// Record what we need from a node, once we've seen all of its properties.
+    ///
+    /// `depth` is the number of nodes containing it.
+    fn add_node(&mut self, node: &Node<'_>, parent: &Node<'_>, depth: usize) {
+        let mut regs = node.regs(parent);
+        if node.device_type == b"memory" {
+            match regs.next() {
+                Some((address, size)) if self.memory.is_empty() => {
+                    self.memory = clamp_range(address, size);
+                }
+                _ => log::warn!("Ignoring extra memory in the device tree"),
+            }
+        } else if node.device_type == b"cpu" {
+            self.num_harts += 1;
+        } else if depth == 1 && node.name == b"chosen" {
+            match core::str::from_utf8(node.bootargs.strip_suffix(b"\0").unwrap_or(node.bootargs)) {
+                Ok(bootargs) if bootargs.len() <= MAX_BOOTARGS_LEN => {
+                    self.bootargs[..bootargs.len()].copy_from_slice(bootargs.as_bytes());
+                    self.bootargs_len = bootargs.len();
+                }
+                _ => log::warn!("Ignoring invalid kernel command line"),
+            }
+        } else if depth == 2 && parent.name == b"reserved-memory" {
+            for (address, size) in regs {
+                self.add_reserved(address, size);
+            }
+        } else if node.is_compatible(b"virtio,mmio") {
+            let (Some((address, _)), Some(irq)) = (regs.next(), node.irq()) else {
+                log::warn!("Ignoring virtio-mmio device without an address and interrupt");
+                return;
+            };
+            let device = MmioDevice {
+                address: clamp_address(address),
+                irq,
+            };
+            if !push(&mut self.virtio, device) {
+                log::warn!("Ignoring virtio-mmio device at {:#X}", device.address);
+            }
+        } else if node.is_compatible(b"riscv,plic0") || node.is_compatible(b"sifive,plic-1.0.0") {
+            if let Some((address, _)) = regs.next() {
+                self.plic = clamp_address(address);
+            }
+        } else if node.is_compatible(b"riscv,clint0") || node.is_compatible(b"sifive,clint0") {
+            self.clint = regs.next().map(|(address, _)| clamp_address(address));
+        } else if node.is_compatible(b"sifive,test0") {
+            self.test_finisher = regs.next().map(|(address, _)| clamp_address(address));
+        }
+    }
+
+    /// Record that the firmware set aside the given memory.
+    fn add_reserved(&mut self, address: u64, size: u64) {
+        if !push(&mut self.reserved, clamp_range(address, size)) {
+            log::warn!("Too many reserved memory regions, ignoring {address:#X}");
+        }
+    }
+
+    /// Log what we found, to help with debugging.
+    fn log_summary(&self) {
+        log::info!(
+            "Found {} harts and {} KiB of memory at {:#X}",
+            self.num_harts,
+            self.memory.len() / 1024,
+            self.memory.start,
+        );
+        log::info!("Found PLIC at {:#X}", self.plic);
+        match self.clint {
+            Some(clint) => log::info!("Found CLINT at {clint:#X}"),
+            None => log::info!("No CLINT found"),
+        }
+        if self.num_harts > 1 {
+            log::info!("Only running on one hart");
+        }
+    }
+
+    /// Get the end of the memory which can be used, starting at `start`.
+    ///
+    /// This stops at the end of memory or the first reserved region, and is rounded down to a
+    /// whole page.
+    ///
+    /// # Panics
+    /// Panics if `start` isn't in memory or is reserved.
+    pub fn usable_memory_end(&self, start: usize) -> usize {
+        assert!(
+            self.memory.contains(&start),
+            "{start:#X} isn't in memory ({:#X?})",
+            self.memory,
+        );
+        let end = self
+            .reserved
+            .iter()
+            .flatten()
+            .filter(|region| region.end > start)
+            .map(|region| region.start)
+            .fold(self.memory.end, usize::min);
+        assert!(end >= start, "{start:#X} is in reserved memory");
+        end & !(crate::page_table::PAGE_SIZE - 1)
+    }
+
+    /// Get the virtio-mmio devices, in order of address.
+    pub fn virtio_devices(&self) -> impl Iterator<Item = MmioDevice> + '_ {
+        self.virtio.iter().flatten().copied()
+    }
+
+    /// Get the interrupt source for the virtio-mmio device at `address`.
+    pub fn virtio_irq(&self, address: usize) -> Option<u32> {
+        self.virtio_devices()
+            .find(|device| device.address == address)
+            .map(|device| device.irq)
+    }
+
+    /// Get the address of the PLIC's registers.
+    pub fn plic_address(&self) -> usize {
+        self.plic
+    }
+
+    /// Get the address of the test finisher's register, if there is one.
+    pub fn test_finisher_address(&self) -> Option<usize> {
+        self.test_finisher
+    }
+
+    /// Get the kernel command line.
+    pub fn bootargs(&self) -> &str {
+        // We checked it was UTF-8 when we copied it in.
+        core::str::from_utf8(&self.bootargs[..self.bootargs_len]).unwrap_or_default()
+    }
+}
+
+/// Put `value` in the first empty slot, returning whether there was one.
+fn push<T>(slots: &mut [Option<T>], value: T) -> bool {
+    match slots.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(value);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Convert an address from the device tree to one we can use, saturating if it's out of range.
+fn clamp_address(address: u64) -> usize {
+    usize::try_from(address).unwrap_or(usize::MAX)
+}
+
+/// Convert a range from the device tree to addresses, cutting off any part we can't address.
+fn clamp_range(address: u64, size: u64) -> Range<usize> {
+    clamp_address(address)..clamp_address(address.saturating_add(size))
+}
+
+/// The properties of a node which we care about.
+///
+/// Missing properties are empty.
+#[derive(Debug, Clone, Copy)]
+struct Node<'a> {
+    /// The node's name, including its unit address.
+    name: &'a [u8],
+    /// The `compatible` property, a list of NUL-terminated strings.
+    compatible: &'a [u8],
+    /// The `device_type` property, without its NUL terminator.
+    device_type: &'a [u8],
+    /// The `reg` property.
+    reg: &'a [u8],
+    /// The `interrupts` property.
+    interrupts: &'a [u8],
+    /// The `bootargs` property.
+    bootargs: &'a [u8],
+    /// The number of cells in the addresses of this node's children.
+    address_cells: u32,
+    /// The number of cells in the sizes of this node's children.
+    size_cells: u32,
+}
+impl Default for Node<'_> {
+    fn default() -> Self {
+        Self {
+            name: &[],
+            compatible: &[],
+            device_type: &[],
+            reg: &[],
+            interrupts: &[],
+            bootargs: &[],
+            // The defaults from the spec.
+            address_cells: 2,
+            size_cells: 1,
+        }
+    }
+}
+impl<'a> Node<'a> {
+    /// Record a property of this node, if it's one we care about.
+    fn add_property(&mut self, name: &[u8], value: &'a [u8]) -> Result<()> {
+        let cells = || read_be_u32(value, 0).ok_or(ErrorKind::InvalidFormat);
+        match name {
+            b"compatible" => self.compatible = value,
+            b"device_type" => self.device_type = value.strip_suffix(b"\0").unwrap_or(value),
+            b"reg" => self.reg = value,
+            b"interrupts" => self.interrupts = value,
+            b"bootargs" => self.bootargs = value,
+            b"#address-cells" => self.address_cells = cells()?,
+            b"#size-cells" => self.size_cells = cells()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Check whether this node is compatible with the given device.
+    fn is_compatible(&self, device: &[u8]) -> bool {
+        self.compatible
+            .split(|&byte| byte == 0)
+            .any(|c| c == device)
+    }
+
+    /// Get the address and size of each region in the `reg` property.
+    ///
+    /// `parent` is the node containing this one, which says how the regions are laid out.
+    fn regs(&self, parent: &Node<'_>) -> impl Iterator<Item = (u64, u64)> + use<'a> {
+        let (address_cells, size_cells) = (parent.address_cells, parent.size_cells);
+        let mut reg = self.reg;
+        core::iter::from_fn(move || {
+            let (address, rest) = read_cells(reg, address_cells)?;
+            let (size, rest) = read_cells(rest, size_cells)?;
+            reg = rest;
+            Some((address, size))
+        })
+    }
+
+    /// Get the first interrupt source in the `interrupts` property.
+    fn irq(&self) -> Option<u32> {
+        read_be_u32(self.interrupts, 0)
+    }
+}
@@ -0,0 +1,26 @@
+//! A driver for the test finisher (`sifive,test0`), which lets the kernel exit QEMU with a status.
+//!
+//! QEMU's `virt` machine has one of these, which is how automated runs (such as the user-space
+//! tests) report whether they passed.
+
+/// The value which exits successfully.
+const FINISHER_PASS: u32 = 0x5555;
+/// The value which exits unsuccessfully, with the status code in the upper 16 bits.
+const FINISHER_FAIL: u32 = 0x3333;
+
+/// Exit QEMU with the given status code.
+///
+/// This only returns if there's no test finisher to exit with.
+pub fn exit(status: u16) {
+    let Some(address) = crate::fdt::BOOT_INFO.test_finisher_address() else {
+        return;
+    };
+    let value = match status {
+        0 => FINISHER_PASS,
+        status => FINISHER_FAIL | (u32::from(status) << 16),
+    };
+    // SAFETY:
+    // The device tree says there's a test finisher here, which we mapped with the rest of the
+    // kernel's memory.
+    unsafe { core::ptr::with_exposed_provenance_mut::<u32>(address).write_volatile(value) };
+}
@@ -26,7 +26,7 @@ impl log::Log for Logger {
         use core::fmt::Write as _;
 
         _ = writeln!(
-            crate::sbi::SbiPutcharWriter,
+            crate::console::ConsoleWriter,
             // TODO I'd like to color these logs
             "{level:>8 } - {source} - {args}",
             level = record.level(),
@@ -4,17 +4,28 @@
 #![no_main]
 
 mod alloc;
+mod block;
+mod console;
 mod csr;
 mod error;
 mod ext2;
+mod fdt;
+mod finisher;
 mod logger;
+mod net;
 mod page_table;
+mod pipe;
+mod plic;
 mod proc;
+mod ram_disk;
+mod random;
 mod resource_desc;
 mod sbi;
 mod sync;
 mod syscall;
+mod timer;
 mod trap;
+mod uart;
 mod virtio;
 
 unsafe extern "C" {
@@ -23,13 +34,25 @@ unsafe extern "C" {
     safe static __stack_top: *mut ();
 }
 
+/// How the UART should receive data.
+const UART_MODE: uart::Mode = uart::Mode::Interrupt;
+
 const USER_PROC: &[u8] = include_bytes!("../target/riscv32imac-unknown-none-elf/release/shell.bin");
 
+/// The ID of the hart we're running on, which [`kernel_trap_entry`] puts back in `tp` after user
+/// code may have changed it.
+///
+/// We only run on one hart, so one value is enough. Running on more will need per-hart storage,
+/// e.g. reached through `sscratch`.
+static HART_ID: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
 /// The main kernel function.
 ///
 /// This function is called by [`boot`] as soon as we can leave assembly and enter pure Rust code.
+/// The firmware passes the ID of the hart we're on and the address of the device tree, which
+/// [`boot`] leaves in place for us.
 #[unsafe(no_mangle)]
-extern "C" fn kernel_main() -> ! {
+extern "C" fn kernel_main(hart_id: usize, device_tree: usize) -> ! {
     // Zero-initialize the BSS section.
     //
     // This needs to run before any code that references a zero-initialized static, in case the
@@ -43,6 +66,7 @@ extern "C" fn kernel_main() -> ! {
         )
     };
     bss.fill(0);
+    HART_ID.store(hart_id, core::sync::atomic::Ordering::Relaxed);
 
     // SAFETY:
     // `kernel_trap_entry` is a good function for writing here.
@@ -50,27 +74,77 @@ extern "C" fn kernel_main() -> ! {
 
     // Keep only logs at `Info` level or above.
     logger::init_logger(log::LevelFilter::Info);
+    log::info!("Booting on hart {hart_id}");
 
-    // SAFETY: We take ownership over this device.
-    let console = unsafe { virtio::VirtioConsole::init_kernel_address() }
-        .expect("Failed to create console driver");
-    *DEVICE_TREE.console.lock() = Some(console);
+    // SAFETY:
+    // The firmware gave us this address, and nothing will write over it until we've read it.
+    unsafe { fdt::init(device_tree) };
+    // Let the command line pick a different log level, with e.g. `loglevel=debug`.
+    let log_level = fdt::BOOT_INFO
+        .bootargs()
+        .split_whitespace()
+        .find_map(|arg| arg.strip_prefix("loglevel="));
+    if let Some(level) = log_level {
+        match level.parse() {
+            Ok(level) => log::set_max_level(level),
+            Err(e) => log::warn!("Ignoring log level {level:?}: {e}"),
+        }
+    }
 
-    // SAFETY: We take ownership over this device.
-    let storage = unsafe { virtio::VirtioBlock::init_kernel_address() }
-        .expect("Failed to create storage driver");
-    let fs = ext2::Ext2::new(storage).expect("Failed to initialize filesystem");
-    *DEVICE_TREE.storage.lock() = Some(fs);
+    plic::init();
+    timer::init();
 
-    // SAFETY: We take ownership over this device.
-    let rng = unsafe { virtio::VirtioRandom::init_kernel_address() }
-        .expect("Failed to create RNG driver");
-    *DEVICE_TREE.random.lock() = Some(rng);
+    // SAFETY: Nothing else has touched the UART yet.
+    if let Some(uart) = unsafe { uart::Ns16550::init(UART_MODE) } {
+        *DEVICE_TREE.uart.lock() = Some(uart);
+    } else {
+        log::warn!("No UART found, the console will fall back to SBI calls");
+    }
 
-    let mut user_proc =
-        proc::Process::create_process(USER_PROC).expect("Failed to init user process");
+    // SAFETY: Nothing else has touched the virtio devices yet.
+    let virtio_devices = unsafe { virtio::scan_bus() };
+    for (device, kind) in virtio_devices {
+        // SAFETY: The scan gives each device once, so we can take ownership of it.
+        unsafe { init_virtio_device(device, kind) };
+    }
+    {
+        let mut storage = DEVICE_TREE.storage.lock();
+        if storage.is_none() {
+            // Without a disk, we can still run from an image built into the kernel.
+            match ram_disk::RamDisk::from_embedded_image()
+                .and_then(|disk| mount(block::Disk::Ram(disk)))
+            {
+                Ok(fs) => *storage = Some(fs),
+                Err(e) => log::warn!("Failed to set up RAM disk: {e}"),
+            }
+        }
+        assert!(storage.is_some(), "No storage device found");
+    }
+    random::init().expect("Failed to seed the random number generator");
+
+    // Let the command line run a program from the filesystem first, with e.g. `init=/bin/tests`,
+    // instead of the built-in shell.
+    let init_path = fdt::BOOT_INFO
+        .bootargs()
+        .split_whitespace()
+        .find_map(|arg| arg.strip_prefix("init="));
+    let init_image;
+    let (image, name) = match init_path {
+        Some(path) => {
+            init_image = path
+                .strip_prefix('/')
+                .ok_or(shared::ErrorKind::InvalidFormat.into())
+                .and_then(proc::load_image)
+                .expect("Failed to load init program");
+            (&*init_image, path)
+        }
+        None => (USER_PROC, "shell"),
+    };
+    let mut user_proc = proc::Process::create_process(image, &[name], &["HOME=/", "PWD=/"])
+        .expect("Failed to init user process");
 
-    let mut idle_proc = proc::Process::create_process(&[]).expect("Failed to init user process");
+    let mut idle_proc =
+        proc::Process::create_process(&[], &[], &[]).expect("Failed to init user process");
     idle_proc.set_idle();
 
     // SAFETY:
@@ -80,17 +154,102 @@ extern "C" fn kernel_main() -> ! {
     };
 
     loop {
-        log::info!("Reached idle loop");
         // SAFETY: "wait for interrupt" is safe.
         unsafe { core::arch::asm!("wfi", options(nomem, preserves_flags, nostack)) };
+        // Interrupts aren't taken in the kernel, so we handle whatever woke us up here.
+        plic::handle_interrupts();
+        timer::handle_interrupt();
         proc::sched_yield();
     }
 }
 
+/// Initialize the driver for a virtio device found on the bus, adding it to the [`DEVICE_TREE`].
+///
+/// Only the first device of each kind gets used. Failures are logged, since most devices are
+/// optional.
+///
+/// # Safety
+/// This takes ownership over the device at the given address, so requires nothing else access
+/// this memory.
+unsafe fn init_virtio_device(device: fdt::MmioDevice, kind: virtio::DeviceKind) {
+    /// Put the device made by `init` into `slot`, unless the slot is already taken.
+    fn install<T>(
+        slot: &sync::KSpinLock<Option<T>>,
+        kind: virtio::DeviceKind,
+        init: impl FnOnce() -> error::Result<T>,
+    ) {
+        let mut slot = slot.lock();
+        if slot.is_some() {
+            log::warn!("Ignoring extra virtio {kind:?} device");
+            return;
+        }
+        match init() {
+            Ok(device) => *slot = Some(device),
+            Err(e) => log::warn!("Failed to initialize virtio {kind:?} device: {e}"),
+        }
+    }
+
+    // Requests to these devices sleep until the device interrupts, instead of spinning.
+    if matches!(
+        kind,
+        virtio::DeviceKind::Block | virtio::DeviceKind::Entropy
+    ) {
+        plic::enable(device.irq, virtio::handle_interrupt, device.address);
+    }
+    let address = device.address;
+    match kind {
+        virtio::DeviceKind::Block => install(&DEVICE_TREE.storage, kind, || {
+            // SAFETY: By method precondition, we can take ownership of this device.
+            let storage = unsafe { virtio::VirtioBlock::init_at_address(address) }?;
+            mount(block::Disk::Virtio(storage))
+        }),
+        virtio::DeviceKind::Entropy => install(&DEVICE_TREE.random, kind, || {
+            // SAFETY: By method precondition, we can take ownership of this device.
+            unsafe { virtio::VirtioRandom::init_at_address(address) }
+        }),
+        // Without this, the console falls back to SBI calls.
+        virtio::DeviceKind::Console => install(&DEVICE_TREE.console, kind, || {
+            // SAFETY: By method precondition, we can take ownership of this device.
+            unsafe { virtio::VirtioConsole::init_at_address(address) }
+        }),
+        virtio::DeviceKind::Network => install(&DEVICE_TREE.network, kind, || {
+            // SAFETY: By method precondition, we can take ownership of this device.
+            unsafe { virtio::VirtioNet::init_at_address(address) }.and_then(net::NetStack::new)
+        }),
+        virtio::DeviceKind::Gpu => install(&DEVICE_TREE.gpu, kind, || {
+            // SAFETY: By method precondition, we can take ownership of this device.
+            unsafe { virtio::VirtioGpu::init_at_address(address) }
+        }),
+        virtio::DeviceKind::Input => install(&DEVICE_TREE.input, kind, || {
+            // SAFETY: By method precondition, we can take ownership of this device.
+            unsafe { virtio::VirtioInput::init_at_address(address) }
+        }),
+    }
+}
+
+/// Mount the filesystem on the given disk, checking it first if the `fsck` feature is enabled and
+/// testing it if the `self-test` feature is.
+fn mount(disk: block::Disk) -> error::Result<ext2::Ext2<block::Disk>> {
+    let mut fs = ext2::Ext2::new(disk)?;
+    if cfg!(feature = "fsck") {
+        fs.check_consistency()
+            .inspect_err(|e| log::error!("Filesystem failed consistency check: {e}"))?;
+    }
+    if cfg!(feature = "self-test") {
+        fs.self_test()
+            .inspect_err(|e| log::error!("Filesystem failed self-test: {e}"))?;
+    }
+    Ok(fs)
+}
+
 struct DeviceTree {
     random: sync::KSpinLock<Option<virtio::VirtioRandom<'static>>>,
-    storage: sync::KSpinLock<Option<ext2::Ext2<'static>>>,
+    storage: sync::KSpinLock<Option<ext2::Ext2<block::Disk>>>,
     console: sync::KSpinLock<Option<virtio::VirtioConsole<'static>>>,
+    network: sync::KSpinLock<Option<net::NetStack<'static>>>,
+    gpu: sync::KSpinLock<Option<virtio::VirtioGpu<'static>>>,
+    input: sync::KSpinLock<Option<virtio::VirtioInput<'static>>>,
+    uart: sync::KSpinLock<Option<uart::Ns16550>>,
 }
 impl DeviceTree {
     pub const fn new() -> Self {
@@ -98,15 +257,80 @@ impl DeviceTree {
             random: sync::KSpinLock::new(None),
             storage: sync::KSpinLock::new(None),
             console: sync::KSpinLock::new(None),
+            network: sync::KSpinLock::new(None),
+            gpu: sync::KSpinLock::new(None),
+            input: sync::KSpinLock::new(None),
+            uart: sync::KSpinLock::new(None),
         }
     }
+
+    /// Reset every virtio device, dropping its driver.
+    ///
+    /// Devices someone is in the middle of using are skipped, since this runs while panicking and
+    /// whatever panicked might have been using one.
+    fn teardown(&self) {
+        /// Drop the driver in `slot`, unless it's in use.
+        fn teardown_slot<T>(slot: &sync::KSpinLock<Option<T>>, name: &str) {
+            match slot.try_lock() {
+                Some(mut slot) => drop(slot.take()),
+                None => log::warn!("Not resetting the {name} device, since it's in use"),
+            }
+        }
+
+        // The disk goes first, so its writes finish as soon as possible.
+        teardown_slot(&self.storage, "storage");
+        teardown_slot(&self.random, "entropy");
+        teardown_slot(&self.network, "network");
+        teardown_slot(&self.gpu, "GPU");
+        teardown_slot(&self.input, "input");
+        // The console goes last, so logs go to it for as long as possible.
+        teardown_slot(&self.console, "console");
+    }
 }
 
 static DEVICE_TREE: DeviceTree = DeviceTree::new();
 
+/// Write out the filesystem, reset every device, and then power off or reboot the machine.
+///
+/// When powering off, a nonzero `status` tells the firmware (and QEMU, if we're running in it)
+/// that something went wrong.
+fn shutdown(kind: shared::ShutdownKind, status: u16) -> ! {
+    log::info!("Shutting down ({kind:?}, status {status})");
+    // Unlike the other devices, we wait for the disk if it's in use, so we don't lose writes.
+    if let Some(mut fs) = DEVICE_TREE.storage.lock().take()
+        && let Err(e) = fs.sync()
+    {
+        log::error!("Failed to write out the filesystem: {e}");
+    }
+    DEVICE_TREE.teardown();
+
+    let reset_type = match kind {
+        shared::ShutdownKind::PowerOff => {
+            // The SBI can't pass on the status, so we try this first.
+            finisher::exit(status);
+            sbi::ResetType::Shutdown
+        }
+        shared::ShutdownKind::Reboot => sbi::ResetType::ColdReboot,
+    };
+    let reason = match status {
+        0 => sbi::ResetReason::NoReason,
+        _ => sbi::ResetReason::SystemFailure,
+    };
+    if let Err(e) = sbi::system_reset(reset_type, reason) {
+        log::error!("Failed to reset the system: {e:?}");
+    }
+    loop {
+        // SAFETY: "wait for interrupt" is safe.
+        unsafe { core::arch::asm!("wfi", options(nomem, preserves_flags, nostack)) };
+        core::hint::spin_loop();
+    }
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn handle_trap(frame: &mut trap::TrapFrame) {
     const SCAUSE_ECALL: u32 = 8;
+    const SCAUSE_SUPERVISOR_TIMER_INTERRUPT: u32 = (1 << 31) | 5;
+    const SCAUSE_SUPERVISOR_EXTERNAL_INTERRUPT: u32 = (1 << 31) | 9;
 
     let scause = csr::read_csr!(scause);
     let stval = csr::read_csr!(stval);
@@ -117,6 +341,15 @@ extern "C" fn handle_trap(frame: &mut trap::TrapFrame) {
             syscall::handle_syscall(frame);
             user_pc += 4;
         }
+        SCAUSE_SUPERVISOR_TIMER_INTERRUPT => {
+            // Give other processes a turn once the current one has used up its time slice.
+            if timer::handle_interrupt() {
+                proc::sched_yield();
+            }
+        }
+        SCAUSE_SUPERVISOR_EXTERNAL_INTERRUPT => {
+            plic::handle_interrupts();
+        }
         _ => {
             panic!("Unexpected trap scause={scause:X}, stval={stval:X}, user_pc={user_pc:X}, ");
         }
@@ -136,6 +369,9 @@ extern "C" fn kernel_trap_entry() -> ! {
         "sw ra,  4 * 0(sp)\n",
         "sw gp,  4 * 1(sp)\n",
         "sw tp,  4 * 2(sp)\n",
+        // Put the hart ID back in `tp`, for `util::sync::current_hart_id`.
+        "lui tp, %hi({hart_id})\n",
+        "lw tp, %lo({hart_id})(tp)\n",
         "sw t0,  4 * 3(sp)\n",
         "sw t1,  4 * 4(sp)\n",
         "sw t2,  4 * 5(sp)\n",
@@ -202,7 +438,9 @@ extern "C" fn kernel_trap_entry() -> ! {
         "lw s10, 4 * 28(sp)\n",
         "lw s11, 4 * 29(sp)\n",
         "lw sp,  4 * 30(sp)\n",
-        "sret\n"
+        "sret\n",
+
+        hart_id = sym HART_ID,
     );
 }
 
@@ -217,6 +455,8 @@ extern "C" fn boot() -> ! {
         // Set up the stack pointer
         "lui sp, %hi({stack_top})",
         "addi sp, sp, %lo({stack_top})",
+        // Keep the hart ID in `tp`, for `util::sync::current_hart_id`.
+        "mv tp, a0",
         // Jump to the main function
         "j kernel_main",
 
@@ -232,6 +472,14 @@ fn panic(info: &core::panic::PanicInfo) -> ! {
     _ = writeln!(sbi::SbiPutcharWriter, "===== KERNEL PANIC! =====");
     _ = writeln!(sbi::SbiPutcharWriter, "{info}");
 
+    // Reset the devices, so the disk isn't left part way through writing. If that panics too, we
+    // don't try again.
+    static TEARING_DOWN: core::sync::atomic::AtomicBool =
+        core::sync::atomic::AtomicBool::new(false);
+    if !TEARING_DOWN.swap(true, core::sync::atomic::Ordering::Relaxed) {
+        DEVICE_TREE.teardown();
+    }
+
     loop {
         // SAFETY: "wait for interrupt" is safe.
         unsafe { core::arch::asm!("wfi", options(nomem, preserves_flags, nostack)) };
@@ -0,0 +1,557 @@
+//! A minimal network stack, handling ARP, IPv4, ICMP echo, and UDP over a virtio network device.
+//!
+//! There are no interrupts yet, so the stack is polled: incoming frames are only processed while
+//! something is sending or waiting to receive.
+
+mod arp;
+mod icmp;
+mod ipv4;
+mod udp;
+
+use shared::{Ipv4Addr, SocketAddrV4, SocketKind};
+
+use crate::{
+    alloc::KByteBuf,
+    error::{ErrorKind, Result},
+    timer::Timeout,
+    virtio::{MAX_FRAME_LEN, VirtioNet},
+};
+
+/// Our address on the network.
+///
+/// This is the address QEMU's user-mode networking gives its guest.
+const LOCAL_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+/// The router to send packets for other networks through.
+const GATEWAY_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+/// The mask of the address bits shared by everything on our network.
+const NETMASK: Ipv4Addr = Ipv4Addr::new(255, 255, 255, 0);
+
+/// The Ethernet MAC address for broadcasting to everything on the network.
+const BROADCAST_MAC: [u8; 6] = [0xFF; 6];
+/// The length of an Ethernet header.
+const ETHERNET_HEADER_LEN: usize = 14;
+/// The Ethernet frame type for IPv4 packets.
+const ETHERTYPE_IPV4: u16 = 0x0800;
+/// The Ethernet frame type for ARP packets.
+const ETHERTYPE_ARP: u16 = 0x0806;
+
+/// The largest payload that fits in an IPv4 packet in one Ethernet frame.
+const MAX_IP_PAYLOAD_LEN: usize = MAX_FRAME_LEN - ETHERNET_HEADER_LEN - ipv4::HEADER_LEN;
+
+/// The number of IP to MAC address mappings we remember.
+const ARP_CACHE_LEN: usize = 8;
+/// How long to wait for a reply to an ARP request.
+const ARP_TIMEOUT_MS: u32 = 1000;
+
+/// The most sockets which can be open at once.
+const MAX_SOCKETS: usize = 16;
+/// The number of received datagrams each socket can hold before dropping more.
+const SOCKET_QUEUE_LEN: usize = 4;
+/// The first port handed out to sockets which don't bind to a specific one.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// The state of the network stack.
+pub struct NetStack<'a> {
+    /// The device to send and receive frames with.
+    device: VirtioNet<'a>,
+    /// The MAC address of `device`.
+    mac: [u8; 6],
+    /// The most recently received frame.
+    receive_frame: KByteBuf,
+    /// The frame being built to transmit.
+    transmit_frame: KByteBuf,
+    /// Recently-learned MAC addresses of IP addresses on our network.
+    arp_cache: [Option<(Ipv4Addr, [u8; 6])>; ARP_CACHE_LEN],
+    /// The slot in `arp_cache` to replace next.
+    next_arp_slot: usize,
+    /// The open sockets.
+    sockets: [Option<Socket>; MAX_SOCKETS],
+    /// The next ephemeral port to try handing out.
+    next_ephemeral_port: u16,
+    /// The identification to give the next IPv4 packet we send.
+    next_ip_id: u16,
+}
+impl<'a> NetStack<'a> {
+    pub fn new(device: VirtioNet<'a>) -> Result<Self> {
+        let mac = device.mac();
+        log::info!(
+            "Network device has MAC address {}, using IP {LOCAL_IP}",
+            MacDisplay(mac)
+        );
+        Ok(Self {
+            device,
+            mac,
+            receive_frame: KByteBuf::new_zeroed(MAX_FRAME_LEN)?,
+            transmit_frame: KByteBuf::new_zeroed(MAX_FRAME_LEN)?,
+            arp_cache: [None; ARP_CACHE_LEN],
+            next_arp_slot: 0,
+            sockets: [const { None }; MAX_SOCKETS],
+            next_ephemeral_port: FIRST_EPHEMERAL_PORT,
+            next_ip_id: 0,
+        })
+    }
+
+    /// Open a new socket, returning its handle.
+    ///
+    /// A `read_timeout_ms` of zero means receiving waits forever.
+    pub fn open_socket(&mut self, kind: SocketKind, read_timeout_ms: u32) -> Result<usize> {
+        let (handle, slot) = self
+            .sockets
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| slot.is_none())
+            .ok_or(ErrorKind::LimitReached)?;
+        *slot = Some(Socket {
+            kind,
+            port: None,
+            read_timeout_ms,
+            queue: KByteBuf::new_zeroed(SOCKET_QUEUE_LEN * MAX_IP_PAYLOAD_LEN)?,
+            queue_entries: [(SocketAddrV4::default(), 0); SOCKET_QUEUE_LEN],
+            queue_start: 0,
+            queue_len: 0,
+        });
+        Ok(handle)
+    }
+
+    /// Close a socket, dropping any datagrams it hasn't received.
+    pub fn close_socket(&mut self, handle: usize) {
+        self.sockets[handle] = None;
+    }
+
+    /// Get how long receiving on a socket waits before timing out, or zero if it waits forever.
+    pub fn read_timeout_ms(&self, handle: usize) -> Result<u32> {
+        Ok(self.socket(handle)?.read_timeout_ms)
+    }
+
+    /// Bind a socket to a local address.
+    ///
+    /// For ICMP echo sockets, the port is used as the echo identifier. A port of zero picks an
+    /// unused one.
+    pub fn bind(&mut self, handle: usize, addr: SocketAddrV4) -> Result<()> {
+        if addr.ip != Ipv4Addr::UNSPECIFIED && addr.ip != LOCAL_IP {
+            return Err(ErrorKind::NotFound.into());
+        }
+        let kind = self.socket(handle)?.kind;
+        if self.socket(handle)?.port.is_some() {
+            return Err(ErrorKind::AlreadyExists.into());
+        }
+        let port = if addr.port == 0 {
+            self.ephemeral_port(kind)?
+        } else if self.port_in_use(kind, addr.port) {
+            return Err(ErrorKind::AlreadyExists.into());
+        } else {
+            addr.port
+        };
+        self.socket_mut(handle)?.port = Some(port);
+        Ok(())
+    }
+
+    /// Send a datagram from a socket.
+    ///
+    /// For ICMP echo sockets, `data` is an echo request message including its header, and the
+    /// port of `to` is ignored. Sockets which aren't bound are bound to an unused port first.
+    pub fn send_to(&mut self, handle: usize, data: &[u8], to: SocketAddrV4) -> Result<usize> {
+        let kind = self.socket(handle)?.kind;
+        match kind {
+            SocketKind::Udp => {
+                if data.len() > MAX_IP_PAYLOAD_LEN - udp::HEADER_LEN || to.port == 0 {
+                    return Err(ErrorKind::InvalidFormat.into());
+                }
+            }
+            SocketKind::IcmpEcho => {
+                if data.len() < icmp::HEADER_LEN
+                    || data.len() > MAX_IP_PAYLOAD_LEN
+                    || icmp::message_type(data) != icmp::TYPE_ECHO_REQUEST
+                {
+                    return Err(ErrorKind::InvalidFormat.into());
+                }
+            }
+        }
+        if self.socket(handle)?.port.is_none() {
+            self.bind(handle, SocketAddrV4::default())?;
+        }
+        let port = self.socket(handle)?.port.ok_or(ErrorKind::Other)?;
+        // Resolving the address may receive frames, so we do it before building ours.
+        let dst_mac = self.resolve(to.ip)?;
+        match kind {
+            SocketKind::Udp => {
+                self.transmit_ipv4(dst_mac, to.ip, ipv4::PROTOCOL_UDP, |payload| {
+                    payload[udp::HEADER_LEN..][..data.len()].copy_from_slice(data);
+                    udp::write_header(payload, LOCAL_IP, to.ip, port, to.port, data.len());
+                    udp::HEADER_LEN + data.len()
+                })?;
+            }
+            SocketKind::IcmpEcho => {
+                self.transmit_ipv4(dst_mac, to.ip, ipv4::PROTOCOL_ICMP, |payload| {
+                    let message = &mut payload[..data.len()];
+                    message.copy_from_slice(data);
+                    icmp::finish_echo(message, icmp::TYPE_ECHO_REQUEST, port);
+                    data.len()
+                })?;
+            }
+        }
+        Ok(data.len())
+    }
+
+    /// Receive a datagram on a socket, if one has arrived.
+    ///
+    /// Returns the length received and the address it came from. Datagrams longer than `buf` are
+    /// truncated.
+    pub fn try_recv_from(
+        &mut self,
+        handle: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<(usize, SocketAddrV4)>> {
+        self.poll();
+        Ok(self.socket_mut(handle)?.pop(buf))
+    }
+
+    /// Handle every frame which has arrived since we last checked.
+    fn poll(&mut self) {
+        // Take the buffer so we can handle the frame while using the rest of `self`.
+        let mut frame = core::mem::take(&mut self.receive_frame);
+        while let Some(len) = self.device.receive(&mut frame) {
+            self.handle_frame(&frame[..len]);
+        }
+        self.receive_frame = frame;
+    }
+
+    /// Handle a received Ethernet frame.
+    fn handle_frame(&mut self, frame: &[u8]) {
+        let Some(header) = frame.first_chunk::<ETHERNET_HEADER_LEN>() else {
+            return;
+        };
+        let dst_mac: [u8; 6] = header[0..6].try_into().expect("Slice is 6 bytes");
+        let src_mac: [u8; 6] = header[6..12].try_into().expect("Slice is 6 bytes");
+        if dst_mac != self.mac && dst_mac != BROADCAST_MAC {
+            return;
+        }
+        let payload = &frame[ETHERNET_HEADER_LEN..];
+        let result = match u16::from_be_bytes([header[12], header[13]]) {
+            ETHERTYPE_ARP => self.handle_arp(payload),
+            ETHERTYPE_IPV4 => self.handle_ipv4(src_mac, payload),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("Error handling received frame: {e}");
+        }
+    }
+
+    /// Handle a received ARP packet.
+    fn handle_arp(&mut self, payload: &[u8]) -> Result<()> {
+        let Some(packet) = arp::ArpPacket::parse(payload) else {
+            return Ok(());
+        };
+        if packet.target_ip != LOCAL_IP {
+            return Ok(());
+        }
+        self.learn_mac(packet.sender_ip, packet.sender_mac);
+        if packet.operation == arp::Operation::Request {
+            let reply = arp::ArpPacket {
+                operation: arp::Operation::Reply,
+                sender_mac: self.mac,
+                sender_ip: LOCAL_IP,
+                target_mac: packet.sender_mac,
+                target_ip: packet.sender_ip,
+            };
+            self.transmit(packet.sender_mac, ETHERTYPE_ARP, |payload| {
+                reply.write_to(payload)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Handle a received IPv4 packet.
+    fn handle_ipv4(&mut self, src_mac: [u8; 6], payload: &[u8]) -> Result<()> {
+        let Some(packet) = ipv4::parse(payload) else {
+            return Ok(());
+        };
+        if packet.dst != LOCAL_IP && packet.dst != Ipv4Addr::BROADCAST {
+            return Ok(());
+        }
+        match packet.protocol {
+            ipv4::PROTOCOL_ICMP => self.handle_icmp(src_mac, packet),
+            ipv4::PROTOCOL_UDP => {
+                if let Some(datagram) = udp::parse(packet.src, packet.dst, packet.payload) {
+                    let from = SocketAddrV4 {
+                        ip: packet.src,
+                        port: datagram.src_port,
+                    };
+                    self.deliver(SocketKind::Udp, datagram.dst_port, from, datagram.payload);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handle a received ICMP message.
+    fn handle_icmp(&mut self, src_mac: [u8; 6], packet: ipv4::Ipv4Packet<'_>) -> Result<()> {
+        let message = packet.payload;
+        if !icmp::is_valid(message) {
+            return Ok(());
+        }
+        match icmp::message_type(message) {
+            icmp::TYPE_ECHO_REQUEST if packet.dst == LOCAL_IP => {
+                // Reply straight back to whoever sent it, so we don't need to resolve their address.
+                self.transmit_ipv4(src_mac, packet.src, ipv4::PROTOCOL_ICMP, |payload| {
+                    let reply = &mut payload[..message.len()];
+                    reply.copy_from_slice(message);
+                    icmp::finish_echo(reply, icmp::TYPE_ECHO_REPLY, icmp::identifier(message));
+                    message.len()
+                })
+            }
+            icmp::TYPE_ECHO_REPLY => {
+                let from = SocketAddrV4 {
+                    ip: packet.src,
+                    port: 0,
+                };
+                self.deliver(
+                    SocketKind::IcmpEcho,
+                    icmp::identifier(message),
+                    from,
+                    message,
+                );
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Queue a received datagram on the socket bound to `port`, if there is one.
+    fn deliver(&mut self, kind: SocketKind, port: u16, from: SocketAddrV4, data: &[u8]) {
+        let Some(socket) = self
+            .sockets
+            .iter_mut()
+            .flatten()
+            .find(|socket| socket.kind == kind && socket.port == Some(port))
+        else {
+            log::debug!("Dropping datagram for unbound port {port}");
+            return;
+        };
+        if !socket.push(from, data) {
+            log::debug!("Dropping datagram for port {port} with full queue");
+        }
+    }
+
+    /// Find the MAC address to send packets for `ip` to, asking the network if we don't know it.
+    fn resolve(&mut self, ip: Ipv4Addr) -> Result<[u8; 6]> {
+        if ip == Ipv4Addr::BROADCAST {
+            return Ok(BROADCAST_MAC);
+        }
+        let same_network =
+            ip.0.iter()
+                .zip(LOCAL_IP.0)
+                .zip(NETMASK.0)
+                .all(|((a, b), mask)| a & mask == b & mask);
+        let next_hop = if same_network { ip } else { GATEWAY_IP };
+        if let Some(mac) = self.cached_mac(next_hop) {
+            return Ok(mac);
+        }
+
+        let request = arp::ArpPacket {
+            operation: arp::Operation::Request,
+            sender_mac: self.mac,
+            sender_ip: LOCAL_IP,
+            target_mac: [0; 6],
+            target_ip: next_hop,
+        };
+        self.transmit(BROADCAST_MAC, ETHERTYPE_ARP, |payload| {
+            request.write_to(payload)
+        })?;
+        let timeout = Timeout::after_ms(ARP_TIMEOUT_MS);
+        loop {
+            self.poll();
+            if let Some(mac) = self.cached_mac(next_hop) {
+                return Ok(mac);
+            }
+            if timeout.expired() {
+                log::warn!("No ARP reply for {next_hop}");
+                return Err(ErrorKind::TimedOut.into());
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Look up the MAC address for `ip` in the cache.
+    fn cached_mac(&self, ip: Ipv4Addr) -> Option<[u8; 6]> {
+        self.arp_cache
+            .iter()
+            .flatten()
+            .find(|(cached_ip, _)| *cached_ip == ip)
+            .map(|&(_, mac)| mac)
+    }
+
+    /// Remember the MAC address for `ip`.
+    fn learn_mac(&mut self, ip: Ipv4Addr, mac: [u8; 6]) {
+        if let Some(entry) = self
+            .arp_cache
+            .iter_mut()
+            .flatten()
+            .find(|(cached_ip, _)| *cached_ip == ip)
+        {
+            entry.1 = mac;
+            return;
+        }
+        self.arp_cache[self.next_arp_slot] = Some((ip, mac));
+        self.next_arp_slot = (self.next_arp_slot + 1) % ARP_CACHE_LEN;
+    }
+
+    /// Transmit an IPv4 packet.
+    ///
+    /// `write_payload` writes the payload into the given buffer and returns its length.
+    fn transmit_ipv4(
+        &mut self,
+        dst_mac: [u8; 6],
+        dst: Ipv4Addr,
+        protocol: u8,
+        write_payload: impl FnOnce(&mut [u8]) -> usize,
+    ) -> Result<()> {
+        let identification = self.next_ip_id;
+        self.next_ip_id = self.next_ip_id.wrapping_add(1);
+        self.transmit(dst_mac, ETHERTYPE_IPV4, |packet| {
+            let payload_len = write_payload(&mut packet[ipv4::HEADER_LEN..]);
+            ipv4::write_header(packet, LOCAL_IP, dst, protocol, identification, payload_len);
+            ipv4::HEADER_LEN + payload_len
+        })
+    }
+
+    /// Transmit an Ethernet frame.
+    ///
+    /// `write_payload` writes the payload into the given buffer and returns its length.
+    fn transmit(
+        &mut self,
+        dst_mac: [u8; 6],
+        ethertype: u16,
+        write_payload: impl FnOnce(&mut [u8]) -> usize,
+    ) -> Result<()> {
+        let frame = &mut self.transmit_frame;
+        frame[0..6].copy_from_slice(&dst_mac);
+        frame[6..12].copy_from_slice(&self.mac);
+        frame[12..14].copy_from_slice(&ethertype.to_be_bytes());
+        let payload_len = write_payload(&mut frame[ETHERNET_HEADER_LEN..]);
+        self.device
+            .transmit(&self.transmit_frame[..ETHERNET_HEADER_LEN + payload_len])
+    }
+
+    /// Pick an unused port for a socket of the given kind.
+    fn ephemeral_port(&mut self, kind: SocketKind) -> Result<u16> {
+        for _ in FIRST_EPHEMERAL_PORT..=u16::MAX {
+            let port = self.next_ephemeral_port;
+            self.next_ephemeral_port = port.checked_add(1).unwrap_or(FIRST_EPHEMERAL_PORT);
+            if !self.port_in_use(kind, port) {
+                return Ok(port);
+            }
+        }
+        Err(ErrorKind::LimitReached.into())
+    }
+
+    /// Check whether a socket of the given kind is bound to `port`.
+    fn port_in_use(&self, kind: SocketKind, port: u16) -> bool {
+        self.sockets
+            .iter()
+            .flatten()
+            .any(|socket| socket.kind == kind && socket.port == Some(port))
+    }
+
+    fn socket(&self, handle: usize) -> Result<&Socket> {
+        Ok(self
+            .sockets
+            .get(handle)
+            .and_then(Option::as_ref)
+            .ok_or(ErrorKind::NotFound)?)
+    }
+
+    fn socket_mut(&mut self, handle: usize) -> Result<&mut Socket> {
+        Ok(self
+            .sockets
+            .get_mut(handle)
+            .and_then(Option::as_mut)
+            .ok_or(ErrorKind::NotFound)?)
+    }
+}
+
+/// An open socket.
+struct Socket {
+    kind: SocketKind,
+    /// The local port (or echo identifier) this socket is bound to, if any.
+    port: Option<u16>,
+    /// How long receiving waits before timing out, or zero to wait forever.
+    read_timeout_ms: u32,
+    /// Storage for received datagrams, with room for [`MAX_IP_PAYLOAD_LEN`] bytes for each entry.
+    queue: KByteBuf,
+    /// The sender and length of each entry in `queue`.
+    queue_entries: [(SocketAddrV4, usize); SOCKET_QUEUE_LEN],
+    /// The index of the oldest entry in `queue`.
+    queue_start: usize,
+    /// The number of entries in `queue`.
+    queue_len: usize,
+}
+impl Socket {
+    /// Add a received datagram to the queue, returning `false` if the queue is full.
+    fn push(&mut self, from: SocketAddrV4, data: &[u8]) -> bool {
+        if self.queue_len == SOCKET_QUEUE_LEN {
+            return false;
+        }
+        let idx = (self.queue_start + self.queue_len) % SOCKET_QUEUE_LEN;
+        let len = data.len().min(MAX_IP_PAYLOAD_LEN);
+        self.queue[idx * MAX_IP_PAYLOAD_LEN..][..len].copy_from_slice(&data[..len]);
+        self.queue_entries[idx] = (from, len);
+        self.queue_len += 1;
+        true
+    }
+
+    /// Take the oldest datagram from the queue, copying as much as fits into `buf`.
+    fn pop(&mut self, buf: &mut [u8]) -> Option<(usize, SocketAddrV4)> {
+        if self.queue_len == 0 {
+            return None;
+        }
+        let idx = self.queue_start;
+        let (from, len) = self.queue_entries[idx];
+        let len = len.min(buf.len());
+        buf[..len].copy_from_slice(&self.queue[idx * MAX_IP_PAYLOAD_LEN..][..len]);
+        self.queue_start = (self.queue_start + 1) % SOCKET_QUEUE_LEN;
+        self.queue_len -= 1;
+        Some((len, from))
+    }
+}
+
+/// Add the 16-bit words of `data` to a running internet checksum.
+///
+/// If `data` has an odd length, it's padded with a zero byte, so only the last part of a message
+/// may have an odd length.
+fn checksum_sum(mut sum: u32, data: &[u8]) -> u32 {
+    let (words, rest) = data.as_chunks::<2>();
+    for word in words {
+        sum += u32::from(u16::from_be_bytes(*word));
+    }
+    if let [byte] = rest {
+        sum += u32::from(*byte) << 8;
+    }
+    sum
+}
+
+/// Compute the internet checksum (see RFC 1071) of `data`, continuing from `sum`.
+///
+/// Computing this over data which includes a correct checksum gives zero.
+fn checksum(sum: u32, data: &[u8]) -> u16 {
+    let mut sum = checksum_sum(sum, data);
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// A wrapper to display a MAC address in the usual format.
+struct MacDisplay([u8; 6]);
+impl core::fmt::Display for MacDisplay {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (idx, byte) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
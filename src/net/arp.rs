@@ -0,0 +1,75 @@
+//! The Address Resolution Protocol, for finding the MAC address belonging to an IPv4 address.
+//!
+//! See RFC 826.
+
+use shared::Ipv4Addr;
+
+/// The length of an ARP packet for IPv4 over Ethernet.
+pub(super) const PACKET_LEN: usize = 28;
+
+/// The hardware type for Ethernet.
+const HARDWARE_TYPE_ETHERNET: u16 = 1;
+
+/// What an ARP packet is asking for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub(super) enum Operation {
+    /// Asking who has the target IP address.
+    Request = 1,
+    /// Answering a request with the sender's MAC address.
+    Reply = 2,
+}
+
+/// An ARP packet for IPv4 over Ethernet.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ArpPacket {
+    pub(super) operation: Operation,
+    pub(super) sender_mac: [u8; 6],
+    pub(super) sender_ip: Ipv4Addr,
+    pub(super) target_mac: [u8; 6],
+    pub(super) target_ip: Ipv4Addr,
+}
+impl ArpPacket {
+    /// Parse an ARP packet, returning `None` if it isn't one we understand.
+    pub(super) fn parse(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.first_chunk::<PACKET_LEN>()?;
+        let hardware_type = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let protocol_type = u16::from_be_bytes([bytes[2], bytes[3]]);
+        if hardware_type != HARDWARE_TYPE_ETHERNET
+            || protocol_type != super::ETHERTYPE_IPV4
+            || bytes[4] != 6
+            || bytes[5] != 4
+        {
+            return None;
+        }
+        let operation = match u16::from_be_bytes([bytes[6], bytes[7]]) {
+            1 => Operation::Request,
+            2 => Operation::Reply,
+            _ => return None,
+        };
+        Some(Self {
+            operation,
+            sender_mac: bytes[8..14].try_into().ok()?,
+            sender_ip: Ipv4Addr(bytes[14..18].try_into().ok()?),
+            target_mac: bytes[18..24].try_into().ok()?,
+            target_ip: Ipv4Addr(bytes[24..28].try_into().ok()?),
+        })
+    }
+
+    /// Write this packet to the start of `bytes`, returning the length written.
+    pub(super) fn write_to(&self, bytes: &mut [u8]) -> usize {
+        let bytes = bytes
+            .first_chunk_mut::<PACKET_LEN>()
+            .expect("No room for ARP packet");
+        bytes[0..2].copy_from_slice(&HARDWARE_TYPE_ETHERNET.to_be_bytes());
+        bytes[2..4].copy_from_slice(&super::ETHERTYPE_IPV4.to_be_bytes());
+        bytes[4] = 6;
+        bytes[5] = 4;
+        bytes[6..8].copy_from_slice(&(self.operation as u16).to_be_bytes());
+        bytes[8..14].copy_from_slice(&self.sender_mac);
+        bytes[14..18].copy_from_slice(&self.sender_ip.0);
+        bytes[18..24].copy_from_slice(&self.target_mac);
+        bytes[24..28].copy_from_slice(&self.target_ip.0);
+        PACKET_LEN
+    }
+}
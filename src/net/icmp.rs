@@ -0,0 +1,38 @@
+//! ICMP echo messages.
+//!
+//! See RFC 792.
+
+/// The length of the header of an echo message.
+pub(super) const HEADER_LEN: usize = 8;
+
+/// The type of an echo reply message.
+pub(super) const TYPE_ECHO_REPLY: u8 = 0;
+/// The type of an echo request message.
+pub(super) const TYPE_ECHO_REQUEST: u8 = 8;
+
+/// Check that a received message is long enough and has a valid checksum.
+pub(super) fn is_valid(message: &[u8]) -> bool {
+    message.len() >= HEADER_LEN && super::checksum(0, message) == 0
+}
+
+/// Get the type of a message.
+pub(super) fn message_type(message: &[u8]) -> u8 {
+    message[0]
+}
+
+/// Get the identifier of an echo message.
+pub(super) fn identifier(message: &[u8]) -> u16 {
+    u16::from_be_bytes([message[4], message[5]])
+}
+
+/// Fill in the type, code, and identifier of an echo message, then compute its checksum.
+///
+/// The sequence number and data are left as they are.
+pub(super) fn finish_echo(message: &mut [u8], message_type: u8, identifier: u16) {
+    message[0] = message_type;
+    message[1] = 0;
+    message[2..4].fill(0);
+    message[4..6].copy_from_slice(&identifier.to_be_bytes());
+    let checksum = super::checksum(0, message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+}
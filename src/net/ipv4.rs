@@ -0,0 +1,84 @@
+//! IPv4 packet headers.
+//!
+//! See RFC 791.
+
+use shared::Ipv4Addr;
+
+/// The length of the headers we send, which have no options.
+pub(super) const HEADER_LEN: usize = 20;
+
+/// The protocol number for ICMP.
+pub(super) const PROTOCOL_ICMP: u8 = 1;
+/// The protocol number for UDP.
+pub(super) const PROTOCOL_UDP: u8 = 17;
+
+/// The time-to-live we give packets we send.
+const DEFAULT_TTL: u8 = 64;
+
+/// A received IPv4 packet.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Ipv4Packet<'a> {
+    pub(super) src: Ipv4Addr,
+    pub(super) dst: Ipv4Addr,
+    pub(super) protocol: u8,
+    pub(super) payload: &'a [u8],
+}
+
+/// Parse an IPv4 packet, returning `None` if it's malformed or one we can't handle.
+///
+/// We don't reassemble fragmented packets, so fragments are rejected.
+pub(super) fn parse(bytes: &[u8]) -> Option<Ipv4Packet<'_>> {
+    let header = bytes.first_chunk::<HEADER_LEN>()?;
+    if header[0] >> 4 != 4 {
+        return None;
+    }
+    let header_len = usize::from(header[0] & 0xF) * 4;
+    let total_len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+    if header_len < HEADER_LEN || total_len < header_len || total_len > bytes.len() {
+        return None;
+    }
+    let flags_and_fragment_offset = u16::from_be_bytes([header[6], header[7]]);
+    let more_fragments = flags_and_fragment_offset & 0x2000 != 0;
+    if more_fragments || flags_and_fragment_offset & 0x1FFF != 0 {
+        log::debug!("Dropping fragmented IPv4 packet");
+        return None;
+    }
+    if super::checksum(0, &bytes[..header_len]) != 0 {
+        log::debug!("Dropping IPv4 packet with bad checksum");
+        return None;
+    }
+    Some(Ipv4Packet {
+        src: Ipv4Addr(header[12..16].try_into().ok()?),
+        dst: Ipv4Addr(header[16..20].try_into().ok()?),
+        protocol: header[9],
+        payload: &bytes[header_len..total_len],
+    })
+}
+
+/// Write an IPv4 header to the start of `bytes`, for a payload of the given length.
+pub(super) fn write_header(
+    bytes: &mut [u8],
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    protocol: u8,
+    identification: u16,
+    payload_len: usize,
+) {
+    let header = bytes
+        .first_chunk_mut::<HEADER_LEN>()
+        .expect("No room for IPv4 header");
+    // Version 4, with a header of 5 32-bit words.
+    header[0] = 0x45;
+    header[1] = 0;
+    header[2..4].copy_from_slice(&((HEADER_LEN + payload_len) as u16).to_be_bytes());
+    header[4..6].copy_from_slice(&identification.to_be_bytes());
+    // Don't fragment, since we can't reassemble fragments either.
+    header[6..8].copy_from_slice(&0x4000_u16.to_be_bytes());
+    header[8] = DEFAULT_TTL;
+    header[9] = protocol;
+    header[10..12].fill(0);
+    header[12..16].copy_from_slice(&src.0);
+    header[16..20].copy_from_slice(&dst.0);
+    let checksum = super::checksum(0, header);
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+}
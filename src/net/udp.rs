@@ -0,0 +1,72 @@
+//! UDP datagrams.
+//!
+//! See RFC 768.
+
+use shared::Ipv4Addr;
+
+/// The length of a UDP header.
+pub(super) const HEADER_LEN: usize = 8;
+
+/// A received UDP datagram.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct UdpDatagram<'a> {
+    pub(super) src_port: u16,
+    pub(super) dst_port: u16,
+    pub(super) payload: &'a [u8],
+}
+
+/// Parse a UDP datagram carried in an IPv4 packet between the given addresses.
+///
+/// Returns `None` if it's malformed or its checksum doesn't match.
+pub(super) fn parse(src: Ipv4Addr, dst: Ipv4Addr, bytes: &[u8]) -> Option<UdpDatagram<'_>> {
+    let header = bytes.first_chunk::<HEADER_LEN>()?;
+    let len = usize::from(u16::from_be_bytes([header[4], header[5]]));
+    if len < HEADER_LEN || len > bytes.len() {
+        return None;
+    }
+    let bytes = &bytes[..len];
+    // A checksum of zero means the sender didn't compute one.
+    if u16::from_be_bytes([header[6], header[7]]) != 0 && checksum(src, dst, bytes) != 0 {
+        log::debug!("Dropping UDP datagram with bad checksum");
+        return None;
+    }
+    Some(UdpDatagram {
+        src_port: u16::from_be_bytes([header[0], header[1]]),
+        dst_port: u16::from_be_bytes([header[2], header[3]]),
+        payload: &bytes[HEADER_LEN..],
+    })
+}
+
+/// Write a UDP header to the start of `bytes`, whose payload of `payload_len` bytes follows the
+/// header.
+pub(super) fn write_header(
+    bytes: &mut [u8],
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    src_port: u16,
+    dst_port: u16,
+    payload_len: usize,
+) {
+    let len = HEADER_LEN + payload_len;
+    let bytes = &mut bytes[..len];
+    bytes[0..2].copy_from_slice(&src_port.to_be_bytes());
+    bytes[2..4].copy_from_slice(&dst_port.to_be_bytes());
+    bytes[4..6].copy_from_slice(&(len as u16).to_be_bytes());
+    bytes[6..8].fill(0);
+    // A computed checksum of zero is sent as all ones, since zero means there's no checksum.
+    let checksum = match checksum(src, dst, bytes) {
+        0 => 0xFFFF,
+        checksum => checksum,
+    };
+    bytes[6..8].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// Compute the checksum of a datagram, including the IPv4 pseudo-header.
+fn checksum(src: Ipv4Addr, dst: Ipv4Addr, datagram: &[u8]) -> u16 {
+    let mut pseudo_header = [0; 12];
+    pseudo_header[0..4].copy_from_slice(&src.0);
+    pseudo_header[4..8].copy_from_slice(&dst.0);
+    pseudo_header[9] = super::ipv4::PROTOCOL_UDP;
+    pseudo_header[10..12].copy_from_slice(&(datagram.len() as u16).to_be_bytes());
+    super::checksum(super::checksum_sum(0, &pseudo_header), datagram)
+}
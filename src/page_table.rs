@@ -13,7 +13,6 @@ pub const PAGE_SIZE: usize = 4096;
 )]
 unsafe extern "C" {
     safe static mut __kernel_base: ();
-    safe static mut __free_ram_end: ();
 }
 
 /// The number of entries in a page table.
@@ -75,10 +74,6 @@ impl PhysicalAddress {
     pub const fn null() -> Self {
         Self(0)
     }
-
-    pub const fn byte_add(self, offset: usize) -> Self {
-        Self(self.0 + offset)
-    }
 }
 
 bitset::bitset!(
@@ -116,8 +111,7 @@ pub unsafe fn map_kernel_memory(table: NonNull<PageTable>) -> Result<(), OutOfMe
         .bit_or(PageTableFlags::WRITABLE)
         .bit_or(PageTableFlags::EXECUTABLE);
 
-    for paddr in (core::ptr::addr_of_mut!(__kernel_base).addr()
-        ..core::ptr::addr_of_mut!(__free_ram_end).addr())
+    for paddr in (core::ptr::addr_of_mut!(__kernel_base).addr()..crate::alloc::free_ram_end())
         .step_by(PAGE_SIZE)
     {
         // SAFETY: Outer method preconditions match inner method's.
@@ -130,36 +124,56 @@ pub unsafe fn map_kernel_memory(table: NonNull<PageTable>) -> Result<(), OutOfMe
             )
         }?;
     }
-    // Map the virtio block device
-    // SAFETY: Outer method preconditions match inner method's.
-    unsafe {
-        map_page(
-            table,
-            core::ptr::with_exposed_provenance_mut(crate::virtio::BLOCK_DEVICE_ADDRESS),
-            PhysicalAddress(crate::virtio::BLOCK_DEVICE_ADDRESS),
-            PageTableFlags::READABLE.bit_or(PageTableFlags::WRITABLE),
-        )
-    }?;
-    // Map the virtio entropy device
-    // SAFETY: Outer method preconditions match inner method's.
-    unsafe {
-        map_page(
-            table,
-            core::ptr::with_exposed_provenance_mut(crate::virtio::RNG_DEVICE_ADDRESS),
-            PhysicalAddress(crate::virtio::RNG_DEVICE_ADDRESS),
-            PageTableFlags::READABLE.bit_or(PageTableFlags::WRITABLE),
-        )
-    }?;
-    // Map the virtio network device
+    // Map the PLIC, so we can handle interrupts.
+    for paddr in crate::plic::mmio_pages() {
+        // SAFETY: Outer method preconditions match inner method's.
+        unsafe {
+            map_page(
+                table,
+                core::ptr::with_exposed_provenance_mut(paddr),
+                PhysicalAddress(paddr),
+                PageTableFlags::READABLE.bit_or(PageTableFlags::WRITABLE),
+            )
+        }?;
+    }
+    // Map the UART, so the console works without SBI calls.
     // SAFETY: Outer method preconditions match inner method's.
     unsafe {
         map_page(
             table,
-            core::ptr::with_exposed_provenance_mut(crate::virtio::CONSOLE_DEVICE_ADDRESS),
-            PhysicalAddress(crate::virtio::CONSOLE_DEVICE_ADDRESS),
+            core::ptr::with_exposed_provenance_mut(crate::uart::UART_ADDRESS),
+            PhysicalAddress(crate::uart::UART_ADDRESS),
             PageTableFlags::READABLE.bit_or(PageTableFlags::WRITABLE),
         )
     }?;
+    // Map the test finisher, so shutting down can give QEMU an exit status.
+    if let Some(address) = crate::fdt::BOOT_INFO.test_finisher_address() {
+        let paddr = address & !(PAGE_SIZE - 1);
+        // SAFETY: Outer method preconditions match inner method's.
+        unsafe {
+            map_page(
+                table,
+                core::ptr::with_exposed_provenance_mut(paddr),
+                PhysicalAddress(paddr),
+                PageTableFlags::READABLE.bit_or(PageTableFlags::WRITABLE),
+            )
+        }?;
+    }
+    // Map the virtio-mmio slots, so drivers can reach devices in any of them.
+    for paddr in crate::fdt::BOOT_INFO
+        .virtio_devices()
+        .map(|device| device.address & !(PAGE_SIZE - 1))
+    {
+        // SAFETY: Outer method preconditions match inner method's.
+        unsafe {
+            map_page(
+                table,
+                core::ptr::with_exposed_provenance_mut(paddr),
+                PhysicalAddress(paddr),
+                PageTableFlags::READABLE.bit_or(PageTableFlags::WRITABLE),
+            )
+        }?;
+    }
     Ok(())
 }
 
@@ -228,18 +242,6 @@ fn entry_for_vaddr(vaddr: *const ()) -> Option<PageTableEntry> {
     }
 }
 
-/// Get the physical address for a given virtual address.
-#[inline(never)]
-pub fn paddr_for_vaddr<T: ?Sized>(vaddr: *mut T) -> Option<PhysicalAddress> {
-    if crate::csr::current_page_table().is_some() {
-        let page_table_entry = entry_for_vaddr(vaddr.cast())?;
-        let offset_in_page = vaddr.addr() & 0xfff;
-        Some(page_table_entry.physical_addr().byte_add(offset_in_page))
-    } else {
-        Some(PhysicalAddress(vaddr.addr()))
-    }
-}
-
 /// Check that the given range of virtual addresses has the given flags set for all of its memory.
 pub fn check_range_has_flags(vaddr_range: *const [u8], flags: PageTableFlags) -> bool {
     let start_vaddr = vaddr_range.addr() & !0xfff;
@@ -255,6 +257,17 @@ pub fn check_range_has_flags(vaddr_range: *const [u8], flags: PageTableFlags) ->
     true
 }
 
+/// Get the physical address that the given virtual address is mapped to, if it's mapped.
+pub fn translate(vaddr: *const ()) -> Option<PhysicalAddress> {
+    let entry = entry_for_vaddr(vaddr)?;
+    if !entry.flags().valid() {
+        return None;
+    }
+    Some(PhysicalAddress(
+        entry.physical_addr().0 + (vaddr.addr() & (PAGE_SIZE - 1)),
+    ))
+}
+
 /// A read-only reference to a region of user-space memory.
 #[derive(Copy, Clone)]
 pub struct UserMemRef<'a>(&'a [u8]);
@@ -349,34 +362,6 @@ impl UserMemMutOpaque {
         }
         Some(Self(memory))
     }
-
-    /// Get the raw pointer to the memory.
-    ///
-    /// This method is always safe, but the pointer is not guaranteed to be writable. In fact,
-    /// unless something else forces user-space memory to be writable, writes to this memory from
-    /// the kernel will hit a page fault.
-    pub fn as_ptr(&self) -> *mut [u8] {
-        self.0
-    }
-
-    /// Get the length of this memory region.
-    pub fn len(&self) -> usize {
-        self.0.len()
-    }
-
-    /// Get a subslice starting at the given index.
-    ///
-    /// # Panics
-    /// Panics if the range start is out-of-bounds for `self`.
-    pub fn range_from(&self, range: core::ops::RangeFrom<usize>) -> Self {
-        Self(core::ptr::slice_from_raw_parts_mut(
-            self.0.cast::<u8>().wrapping_add(range.start),
-            self.0
-                .len()
-                .checked_sub(range.start)
-                .expect("Index out of bounds"),
-        ))
-    }
 }
 
 /// Map the given page into the given page table at the given virtual address.
@@ -432,3 +417,77 @@ pub unsafe fn map_page(
     table0.entries[vpn0] = PageTableEntry::from_addr_flags(paddr, flags | PageTableFlags::VALID);
     Ok(())
 }
+
+/// Remove the mapping at the given virtual address from the given page table.
+///
+/// Returns the physical address the page was mapped to and the flags it had, or `None` if nothing
+/// was mapped there. The caller must flush the TLB afterwards, and is responsible for the physical
+/// page.
+///
+/// # Safety
+/// We must have exclusive access to the given table, which must be initialized as a valid page
+/// table structure. Also, nothing may still be using the memory that was mapped.
+pub unsafe fn unmap_page(
+    mut table: NonNull<PageTable>,
+    vaddr: *mut (),
+) -> Option<(PhysicalAddress, PageTableFlags)> {
+    assert!(
+        vaddr.addr().is_multiple_of(PAGE_SIZE),
+        "Unaligned virtual address 0x{:X}",
+        vaddr.addr(),
+    );
+
+    let vpn1 = (vaddr.addr() >> 22) & 0x3ff;
+
+    // SAFETY: Method precondition ensures valid access.
+    let table = unsafe { table.as_mut() };
+    if !table.entries[vpn1].flags().valid() {
+        return None;
+    }
+    // SAFETY: Method precondition ensures valid access.
+    let table0 = unsafe {
+        &mut *core::ptr::with_exposed_provenance_mut::<PageTable>(
+            table.entries[vpn1].physical_addr().0,
+        )
+    };
+
+    let vpn0 = (vaddr.addr() >> 12) & 0x3ff;
+    let entry = table0.entries[vpn0];
+    if !entry.flags().valid() {
+        return None;
+    }
+    table0.entries[vpn0] = PageTableEntry::EMPTY;
+    Some((entry.physical_addr(), entry.flags()))
+}
+
+/// Free the given page table, along with its lower-level tables and every page mapped in it for
+/// which `owned` returns `true`.
+///
+/// # Safety
+/// The table must be a valid page table structure which isn't active, and nothing may use it or
+/// the pages it owns afterwards.
+pub unsafe fn free_page_table(table: NonNull<PageTable>, owned: impl Fn(PageTableFlags) -> bool) {
+    // SAFETY: Method precondition ensures valid access.
+    let root = unsafe { table.as_ref() };
+    for entry in root.entries.iter().filter(|entry| entry.flags().valid()) {
+        let table0 = core::ptr::with_exposed_provenance_mut::<PageTable>(entry.physical_addr().0);
+        // SAFETY: Valid entries in the top level point at tables that `map_page` allocated.
+        let leaves = unsafe { &(*table0).entries };
+        for leaf in leaves
+            .iter()
+            .filter(|leaf| leaf.flags().valid() && owned(leaf.flags()))
+        {
+            // SAFETY: The caller promises nothing uses the pages it owns anymore.
+            unsafe {
+                crate::alloc::free_pages(
+                    core::ptr::with_exposed_provenance_mut(leaf.physical_addr().0),
+                    1,
+                );
+            }
+        }
+        // SAFETY: We're done reading this table, and nothing else uses it.
+        unsafe { crate::alloc::free_pages(table0.cast(), 1) };
+    }
+    // SAFETY: We're done reading this table, and nothing else uses it.
+    unsafe { crate::alloc::free_pages(table.as_ptr().cast(), 1) };
+}
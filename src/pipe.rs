@@ -0,0 +1,130 @@
+//! Pipes, which carry the bytes written to one resource to whoever reads from another.
+
+use crate::{
+    alloc::{KByteBuf, KrcBox},
+    error::{ErrorKind, Result},
+    sync::KSpinLock,
+    timer::Timeout,
+};
+
+/// The most bytes a pipe holds before writes have to wait for reads to catch up.
+const PIPE_CAPACITY: usize = 4096;
+
+/// A pipe, shared between the resource descriptions for its two ends.
+pub type SharedPipe = KrcBox<KSpinLock<Pipe>>;
+
+/// The state of a pipe.
+///
+/// The bytes in the pipe are kept in a ring buffer, starting at `start` and wrapping around the
+/// end of `buf`.
+pub struct Pipe {
+    /// The memory holding the bytes in the pipe.
+    buf: KByteBuf,
+    /// The index in `buf` of the first byte to be read.
+    start: usize,
+    /// The number of bytes waiting to be read.
+    len: usize,
+    /// Whether the read end is still open.
+    reader_open: bool,
+    /// Whether the write end is still open.
+    writer_open: bool,
+}
+
+/// Create a new, empty pipe with both ends open.
+pub fn new() -> Result<SharedPipe> {
+    Ok(KrcBox::new(KSpinLock::new(Pipe {
+        buf: KByteBuf::new_zeroed(PIPE_CAPACITY)?,
+        start: 0,
+        len: 0,
+        reader_open: true,
+        writer_open: true,
+    }))?)
+}
+
+/// Get the channel that processes waiting on `pipe` sleep on.
+///
+/// Readers and writers share a channel, since a pipe is rarely waited on from both ends at once.
+fn channel(pipe: &KSpinLock<Pipe>) -> usize {
+    core::ptr::from_ref(pipe).addr()
+}
+
+/// Read from the pipe, waiting for something to be written if it's empty.
+///
+/// Returns 0 once the pipe is empty and the write end is closed. Fails with
+/// [`ErrorKind::TimedOut`] if nothing is written before `timeout` expires.
+pub fn read(pipe: &KSpinLock<Pipe>, buf: &mut [u8], timeout: &Timeout) -> Result<usize> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    loop {
+        {
+            let mut pipe_guard = pipe.lock();
+            let state = &mut *pipe_guard;
+            if state.len > 0 {
+                let len = buf.len().min(state.len);
+                for (i, byte) in buf[..len].iter_mut().enumerate() {
+                    *byte = state.buf[(state.start + i) % PIPE_CAPACITY];
+                }
+                state.start = (state.start + len) % PIPE_CAPACITY;
+                state.len -= len;
+                crate::proc::wake_all(channel(pipe));
+                return Ok(len);
+            }
+            if !state.writer_open {
+                return Ok(0);
+            }
+        }
+        if timeout.expired() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        if timeout.is_never() {
+            // Interrupts aren't taken while we're in the kernel, so nothing can write between our
+            // check and going to sleep without waking us.
+            crate::proc::sleep(channel(pipe));
+        } else {
+            // Nothing wakes a sleeping process once its timeout passes, so keep checking instead.
+            crate::proc::sched_yield();
+        }
+    }
+}
+
+/// Write to the pipe, waiting for room if it's full.
+///
+/// Fails with [`ErrorKind::BrokenPipe`] if the read end is closed, since nothing could ever read
+/// what's written.
+pub fn write(pipe: &KSpinLock<Pipe>, buf: &[u8]) -> Result<usize> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    loop {
+        {
+            let mut pipe_guard = pipe.lock();
+            let state = &mut *pipe_guard;
+            if !state.reader_open {
+                return Err(ErrorKind::BrokenPipe.into());
+            }
+            let len = buf.len().min(PIPE_CAPACITY - state.len);
+            if len > 0 {
+                for (i, &byte) in buf[..len].iter().enumerate() {
+                    state.buf[(state.start + state.len + i) % PIPE_CAPACITY] = byte;
+                }
+                state.len += len;
+                crate::proc::wake_all(channel(pipe));
+                return Ok(len);
+            }
+        }
+        crate::proc::sleep(channel(pipe));
+    }
+}
+
+/// Close the read end of the pipe, so writes fail from now on.
+pub fn close_reader(pipe: &KSpinLock<Pipe>) {
+    pipe.lock().reader_open = false;
+    crate::proc::wake_all(channel(pipe));
+}
+
+/// Close the write end of the pipe, so reads give 0 once it's empty.
+pub fn close_writer(pipe: &KSpinLock<Pipe>) {
+    pipe.lock().writer_open = false;
+    crate::proc::wake_all(channel(pipe));
+}
@@ -0,0 +1,115 @@
+//! A driver for the platform-level interrupt controller (PLIC), which routes interrupts from
+//! devices to us.
+//!
+//! Designed according to the spec from
+//! <https://github.com/riscv/riscv-plic-spec/blob/master/riscv-plic.adoc>.
+
+use crate::sync::KSpinLock;
+
+/// The PLIC context for supervisor mode on hart 0, which is the only hart we run on.
+const CONTEXT: usize = 1;
+/// The offset of the priority registers, one word per interrupt source.
+const PRIORITY_OFFSET: usize = 0;
+/// The offset of the enable bits for our context, one bit per interrupt source.
+const ENABLE_OFFSET: usize = 0x2000 + 0x80 * CONTEXT;
+/// The offset of the priority threshold register for our context.
+const THRESHOLD_OFFSET: usize = 0x20_0000 + 0x1000 * CONTEXT;
+/// The offset of the claim/complete register for our context.
+const CLAIM_OFFSET: usize = THRESHOLD_OFFSET + 4;
+/// The number of interrupt sources we support handlers for.
+///
+/// This covers the first word of enable bits, which includes all the virtio-mmio devices.
+const NUM_IRQS: usize = 32;
+
+/// The bit in `sie` which enables supervisor external interrupts.
+const SIE_SEIE: u32 = 1 << 9;
+
+/// A function to handle an interrupt, along with a value to pass it.
+type Handler = (fn(usize), usize);
+
+/// The handler for each interrupt source.
+static HANDLERS: KSpinLock<[Option<Handler>; NUM_IRQS]> = KSpinLock::new([None; NUM_IRQS]);
+
+/// The pages the PLIC registers we use are on, which need mapping in every page table.
+pub(crate) fn mmio_pages() -> [usize; 3] {
+    let address = crate::fdt::BOOT_INFO.plic_address();
+    [
+        address + PRIORITY_OFFSET,
+        address + ENABLE_OFFSET,
+        address + THRESHOLD_OFFSET,
+    ]
+    .map(|addr| addr & !(crate::page_table::PAGE_SIZE - 1))
+}
+
+/// Get a pointer to the PLIC register at the given offset.
+fn register(offset: usize) -> *mut u32 {
+    core::ptr::with_exposed_provenance_mut(crate::fdt::BOOT_INFO.plic_address() + offset)
+}
+
+/// Set up the PLIC and enable external interrupts.
+///
+/// This should only be called once, before any calls to [`enable`].
+pub fn init() {
+    // Accept interrupts of any nonzero priority.
+    // SAFETY: This register is valid, and we're the only ones who access the PLIC.
+    unsafe { register(THRESHOLD_OFFSET).write_volatile(0) };
+    let sie = crate::csr::read_csr!(sie);
+    // SAFETY: Enabling external interrupts is fine, since we have handlers for them.
+    unsafe { crate::csr::write_csr!(sie = sie | SIE_SEIE) };
+}
+
+/// Enable the interrupt source `irq`, calling `handler(context)` whenever it interrupts.
+///
+/// # Panics
+/// Panics if `irq` is out of range or already has a handler.
+pub fn enable(irq: u32, handler: fn(usize), context: usize) {
+    let irq = irq as usize;
+    assert!(
+        irq != 0 && irq < NUM_IRQS,
+        "Interrupt source {irq} out of range"
+    );
+    {
+        let mut handlers = HANDLERS.lock();
+        assert!(
+            handlers[irq].is_none(),
+            "Interrupt source {irq} already has a handler"
+        );
+        handlers[irq] = Some((handler, context));
+    }
+    // SAFETY: These registers are valid, and we're the only ones who access the PLIC.
+    unsafe {
+        register(PRIORITY_OFFSET + 4 * irq).write_volatile(1);
+        let enable = register(ENABLE_OFFSET);
+        enable.write_volatile(enable.read_volatile() | 1 << irq);
+    }
+    log::info!("Enabled interrupt source {irq}");
+}
+
+/// Check whether the interrupt source `irq` has a handler.
+pub fn is_enabled(irq: u32) -> bool {
+    HANDLERS
+        .lock()
+        .get(irq as usize)
+        .is_some_and(Option::is_some)
+}
+
+/// Handle every pending interrupt.
+///
+/// This works whether or not interrupts are currently enabled, so the idle loop can use it after
+/// waking up.
+pub fn handle_interrupts() {
+    loop {
+        // SAFETY: This register is valid, and we're the only ones who access the PLIC.
+        let irq = unsafe { register(CLAIM_OFFSET).read_volatile() };
+        if irq == 0 {
+            break;
+        }
+        let handler = HANDLERS.lock().get(irq as usize).copied().flatten();
+        match handler {
+            Some((handler, context)) => handler(context),
+            None => log::warn!("Interrupt from source {irq} without a handler"),
+        }
+        // SAFETY: This register is valid, and we're done with this interrupt.
+        unsafe { register(CLAIM_OFFSET).write_volatile(irq) };
+    }
+}
@@ -4,12 +4,12 @@ use core::{
 };
 
 use shared::ErrorKind;
-use util::cell::SyncUnsafeCell;
+use util::cell::{CheckedCell, CheckedRef, CheckedRefMut};
 
 use crate::{
-    alloc::KrcBox,
+    alloc::{KByteBuf, KrcBox},
     error::{OutOfMemory, Result},
-    page_table::{PageTableFlags, PhysicalAddress, PAGE_SIZE},
+    page_table::{PAGE_SIZE, PageTableFlags, PhysicalAddress},
     resource_desc::ResourceDescription,
     sync::KSpinLock,
 };
@@ -25,8 +25,8 @@ pub struct Process {
     buf_idx: usize,
 }
 
-static PROCS_BUF: [SyncUnsafeCell<ProcessInner>; MAX_PROCS] = [const {
-    SyncUnsafeCell::new(ProcessInner {
+static PROCS_BUF: [CheckedCell<ProcessInner>; MAX_PROCS] = [const {
+    CheckedCell::new(ProcessInner {
         pid: 0,
         state: ProcessState::Unused,
         sp: core::ptr::dangling_mut(),
@@ -34,22 +34,65 @@ static PROCS_BUF: [SyncUnsafeCell<ProcessInner>; MAX_PROCS] = [const {
         kernel_stack: core::ptr::dangling_mut(),
         resource_descriptors: core::ptr::dangling_mut(),
         mmap_head: 0,
+        parent_pid: 0,
+        thread_group: 0,
+        exit_status: 0,
     })
 }; MAX_PROCS];
 
 impl Process {
-    pub fn create_process(image: &[u8]) -> Result<Self> {
-        let (buf_idx, slot) = PROCS_BUF
-            .iter()
-            .enumerate()
-            .find(|(_, slot)| {
-                // SAFETY: TODO make this thread-safe
-                let slot = unsafe { &*slot.get() };
-                slot.state == ProcessState::Unused
-            })
-            .ok_or(ErrorKind::LimitReached)?;
-        // SAFETY: We picked a slot that isn't in use (TODO make this thread-safe).
-        unsafe { slot.get().write(ProcessInner::create_process(image)?) };
+    /// Create a process running `image`, with the given arguments and environment variables
+    /// (each as `NAME=value`), attached to the console.
+    pub fn create_process(image: &[u8], args: &[&str], env: &[&str]) -> Result<Self> {
+        let args_block = encode_process_args(args, env)?;
+        let stdio = [
+            ResourceDescriptor::new(ResourceDescription::for_console_in())?,
+            ResourceDescriptor::new(ResourceDescription::for_console_out())?,
+            // Stderr gets its own description, so it still reaches the console if stdout is
+            // redirected.
+            ResourceDescriptor::new(ResourceDescription::for_console_out())?,
+        ];
+        Self::create(image, &args_block, stdio, 0)
+    }
+
+    /// Create a process running `image`, as a child of the current process.
+    ///
+    /// `args_block` holds its arguments and environment variables, laid out as described at
+    /// [`shared::PROCESS_ARGS_ADDR`], and `stdio` are its stdin, stdout, and stderr. Returns the
+    /// new process's PID.
+    pub fn spawn(image: &[u8], args_block: &[u8], stdio: [ResourceDescriptor; 3]) -> Result<u32> {
+        validate_process_args(args_block)?;
+        // The image has to fit below the argument block.
+        if image.len() > shared::PROCESS_ARGS_ADDR - USER_BASE as usize {
+            return Err(ErrorKind::LimitReached.into());
+        }
+        let child = Self::create(image, args_block, stdio, current_pid())?;
+        Ok(child.inner().pid)
+    }
+
+    /// Start a new thread in the current process, returning its thread ID.
+    ///
+    /// The thread starts running user code at `entry`, with its stack pointer set to `stack_top`
+    /// and `arg` as its first argument. It shares everything else with the rest of the process.
+    pub fn spawn_thread(entry: usize, stack_top: usize, arg: usize) -> Result<u32> {
+        let buf_idx = free_slot()?;
+        let inner = ProcessInner::create_thread(&current_leader(), entry, stack_top, arg)?;
+        // TODO Reserve the slot, so nothing else can pick it while we set up the thread.
+        *PROCS_BUF[buf_idx].borrow_mut() = inner;
+        Ok(Process { buf_idx }.inner().pid)
+    }
+
+    /// Create a process in an unused slot.
+    fn create(
+        image: &[u8],
+        args_block: &[u8],
+        stdio: [ResourceDescriptor; 3],
+        parent_pid: u32,
+    ) -> Result<Self> {
+        let buf_idx = free_slot()?;
+        let inner = ProcessInner::create_process(image, args_block, stdio, parent_pid)?;
+        // TODO Reserve the slot, so nothing else can pick it while we set up the process.
+        *PROCS_BUF[buf_idx].borrow_mut() = inner;
         Ok(Process { buf_idx })
     }
 
@@ -58,14 +101,12 @@ impl Process {
         self.inner_mut().state = ProcessState::Idle;
     }
 
-    fn inner(&self) -> &ProcessInner {
-        // SAFETY: We effectively own the inner data.
-        unsafe { &*PROCS_BUF[self.buf_idx].get() }
+    fn inner(&self) -> CheckedRef<'static, ProcessInner> {
+        PROCS_BUF[self.buf_idx].borrow()
     }
 
-    fn inner_mut(&mut self) -> &mut ProcessInner {
-        // SAFETY: We effectively own the inner data.
-        unsafe { &mut *PROCS_BUF[self.buf_idx].get() }
+    fn inner_mut(&mut self) -> CheckedRefMut<'static, ProcessInner> {
+        PROCS_BUF[self.buf_idx].borrow_mut()
     }
 }
 
@@ -76,32 +117,27 @@ pub(crate) struct ProcessInner {
     pub page_table: PhysicalAddress,
     pub kernel_stack: *mut [u8; KERNEL_STACK_SIZE],
     pub resource_descriptors: *mut [Option<ResourceDescriptor>; MAX_NUM_RESOURCE_DESCRIPTORS],
+    /// Where the next `mmap` goes.
+    ///
+    /// Only the main thread's value is used, and its other threads share it (see
+    /// [`current_leader`]).
     pub mmap_head: usize,
+    /// The PID of the process which spawned this one, or 0 if the kernel started it.
+    pub parent_pid: u32,
+    /// The PID of the process this is a thread of, which is its own PID for the main thread.
+    pub thread_group: u32,
+    /// The status the process exited with, once it's [`ProcessState::Exited`].
+    pub exit_status: i32,
 }
 
 impl ProcessInner {
-    fn create_process(image: &[u8]) -> Result<Self> {
-        /// Counter for incrementing process IDs.
-        static PID_COUNTER: AtomicU32 = AtomicU32::new(1);
-
-        let kernel_stack = crate::alloc::alloc_pages(KERNEL_STACK_SIZE.div_ceil(4096))?
-            .cast::<[u8; KERNEL_STACK_SIZE]>();
-        let sp = kernel_stack
-            .wrapping_byte_add(KERNEL_STACK_SIZE)
-            .wrapping_byte_sub(52)
-            .cast::<()>();
-        {
-            let pc_ptr = sp.cast::<usize>();
-            debug_assert!(pc_ptr.is_aligned(), "Stack misaligned");
-            #[allow(
-                clippy::fn_to_numeric_cast_any,
-                reason = "I really want the function address"
-            )]
-            // SAFETY: We allocated this stack, so we can write to it.
-            unsafe {
-                pc_ptr.write(user_entry as usize);
-            }
-        }
+    fn create_process(
+        image: &[u8],
+        args_block: &[u8],
+        stdio: [ResourceDescriptor; 3],
+        parent_pid: u32,
+    ) -> Result<Self> {
+        let (kernel_stack, sp) = new_kernel_stack(user_entry, [0; 3])?;
         let page_table =
             // SAFETY: Pages will never be null.
             unsafe { core::ptr::NonNull::new_unchecked(crate::alloc::alloc_pages_zeroed(1)?) };
@@ -124,6 +160,19 @@ impl ProcessInner {
             )
         }?;
         // SAFETY:
+        // The page table for this process is valid, and the block goes after the space for user
+        // memory.
+        unsafe {
+            crate::page_table::alloc_and_map_slice(
+                page_table.cast(),
+                PhysicalAddress(shared::PROCESS_ARGS_ADDR),
+                args_block,
+                PageTableFlags::VALID
+                    .bit_or(PageTableFlags::READABLE)
+                    .bit_or(PageTableFlags::USER_ACCESSIBLE),
+            )
+        }?;
+        // SAFETY:
         // We just allocated the memory, so we can write to it (though it might not yet be
         // initialied).
         let resource_descriptors = unsafe {
@@ -136,28 +185,109 @@ impl ProcessInner {
         let resource_descriptors =
             resource_descriptors.write([const { None }; MAX_NUM_RESOURCE_DESCRIPTORS]);
         // Give the process stdin, stdout, and stderr
-        let [stdin, stdout, stderr] =
-        // SAFETY: These indices are disjoint.
-            unsafe { resource_descriptors.get_disjoint_unchecked_mut([0, 1, 2]) };
-        *stdin = Some(ResourceDescriptor::new(
-            ResourceDescription::for_console_in(),
-        )?);
-        *stdout = Some(ResourceDescriptor::new(
-            ResourceDescription::for_console_out(),
-        )?);
-        stderr.clone_from(stdout);
+        for (slot, descriptor) in resource_descriptors.iter_mut().zip(stdio) {
+            *slot = Some(descriptor);
+        }
+        let pid = next_pid();
         Ok(Self {
-            // TODO Don't collide with pre-existing processes if it wraps.
-            pid: PID_COUNTER.fetch_add(1, core::sync::atomic::Ordering::Relaxed),
+            pid,
             state: ProcessState::Runnable,
             sp,
             // Page table has same physical and virtual address.
             page_table: PhysicalAddress(page_table.addr().into()),
             kernel_stack,
             resource_descriptors,
-            mmap_head: 0x0200_0000,
+            mmap_head: MMAP_BASE,
+            parent_pid,
+            thread_group: pid,
+            exit_status: 0,
         })
     }
+
+    fn create_thread(leader: &Self, entry: usize, stack_top: usize, arg: usize) -> Result<Self> {
+        let (kernel_stack, sp) = new_kernel_stack(thread_entry, [entry, stack_top, arg])?;
+        Ok(Self {
+            pid: next_pid(),
+            state: ProcessState::Runnable,
+            sp,
+            page_table: leader.page_table,
+            kernel_stack,
+            resource_descriptors: leader.resource_descriptors,
+            mmap_head: 0,
+            // Threads can't be waited for like child processes.
+            parent_pid: 0,
+            thread_group: leader.pid,
+            exit_status: 0,
+        })
+    }
+
+    /// Check whether this is a thread other than its process's main thread.
+    fn is_secondary_thread(&self) -> bool {
+        self.pid != self.thread_group
+    }
+}
+
+/// Get a fresh PID (which threads also use as their thread ID).
+fn next_pid() -> u32 {
+    /// Counter for incrementing process IDs.
+    static PID_COUNTER: AtomicU32 = AtomicU32::new(1);
+
+    // TODO Don't collide with pre-existing processes if it wraps.
+    PID_COUNTER.fetch_add(1, core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Allocate a kernel stack, set up so that switching to it starts running `entry` with `s0`, `s1`,
+/// and `s2` set to `saved_regs`.
+///
+/// Returns the stack, and the stack pointer to switch to.
+fn new_kernel_stack(
+    entry: unsafe extern "C" fn(),
+    saved_regs: [usize; 3],
+) -> Result<(*mut [u8; KERNEL_STACK_SIZE], *mut ())> {
+    let kernel_stack = crate::alloc::alloc_pages(KERNEL_STACK_SIZE.div_ceil(4096))?
+        .cast::<[u8; KERNEL_STACK_SIZE]>();
+    // Leave space for the 13 registers `switch_context_inner` restores.
+    let sp = kernel_stack
+        .wrapping_byte_add(KERNEL_STACK_SIZE)
+        .wrapping_byte_sub(52)
+        .cast::<()>();
+    let regs_ptr = sp.cast::<usize>();
+    debug_assert!(regs_ptr.is_aligned(), "Stack misaligned");
+    #[allow(
+        clippy::fn_to_numeric_cast_any,
+        reason = "I really want the function address"
+    )]
+    let entry = entry as usize;
+    for (idx, value) in [entry].into_iter().chain(saved_regs).enumerate() {
+        // SAFETY: We allocated this stack, so we can write to it.
+        unsafe { regs_ptr.add(idx).write(value) };
+    }
+    Ok((kernel_stack, sp))
+}
+
+/// Find a slot to put a new process or thread in, returning its index.
+///
+/// Slots of threads which have exited are cleaned up and reused.
+fn free_slot() -> Result<usize> {
+    PROCS_BUF
+        .iter()
+        .position(|slot| {
+            let mut slot = slot.borrow_mut();
+            if slot.state == ProcessState::Exited && slot.is_secondary_thread() {
+                // SAFETY:
+                // The thread has exited, so nothing runs on its kernel stack anymore, and nobody
+                // waits for threads, so nothing else will free it.
+                unsafe {
+                    crate::alloc::free_pages(
+                        slot.kernel_stack.cast(),
+                        KERNEL_STACK_SIZE.div_ceil(PAGE_SIZE),
+                    );
+                }
+                slot.state = ProcessState::Unused;
+            }
+            slot.state == ProcessState::Unused
+        })
+        .ok_or_else(|| ErrorKind::LimitReached.into())
 }
 // SAFETY: Processes can move between threads.
 unsafe impl Send for ProcessInner {}
@@ -166,6 +296,9 @@ unsafe impl Sync for ProcessInner {}
 
 pub(crate) const MAX_NUM_RESOURCE_DESCRIPTORS: usize = 1024;
 
+/// The user address where memory from `mmap` (and mapped resources) starts.
+pub(crate) const MMAP_BASE: usize = 0x0200_0000;
+
 /// A resource descriptor that a process might have.
 ///
 /// Note that a [`ResourceDescriptor`] is a reference-counted shared pointer to a
@@ -194,6 +327,10 @@ impl ResourceDescriptor {
 pub enum ProcessState {
     Unused,
     Runnable,
+    /// Waiting for [`wake_all`] to be called with the given channel.
+    Blocked {
+        channel: usize,
+    },
     Idle,
     Exited,
 }
@@ -205,12 +342,7 @@ fn next_proc_to_run(current_proc: &Process) -> usize {
         if slot == current_proc.buf_idx {
             return false;
         }
-        // SAFETY: Changing the active process can invalidate this whole buffer.
-        let proc = unsafe { &*proc.get() };
-        if proc.state != ProcessState::Runnable {
-            return false;
-        }
-        true
+        proc.borrow().state == ProcessState::Runnable
     }) {
         return next_proc_slot;
     }
@@ -220,11 +352,10 @@ fn next_proc_to_run(current_proc: &Process) -> usize {
     // If no processes are runnable, run the idle process.
     //
     // TODO We should cache this result, since it won't change.
-    if let Some((next_proc_slot, _)) = PROCS_BUF.iter().enumerate().find(|&(_, proc)| {
-        // SAFETY: Changing the active process can invalidate this whole buffer.
-        let proc = unsafe { &*proc.get() };
-        proc.state == ProcessState::Idle
-    }) {
+    if let Some(next_proc_slot) = PROCS_BUF
+        .iter()
+        .position(|proc| proc.borrow().state == ProcessState::Idle)
+    {
         return next_proc_slot;
     }
     unreachable!("Nothing runnable");
@@ -245,25 +376,218 @@ pub fn sched_yield() {
     }
 }
 
+/// Block until the child `pid` of the current process exits, then clean it up and return its exit
+/// status.
+pub fn wait(pid: u32) -> Result<i32> {
+    let parent_pid = current_pid();
+    loop {
+        let slot = PROCS_BUF
+            .iter()
+            .find(|slot| {
+                let proc = slot.borrow();
+                proc.state != ProcessState::Unused
+                    && proc.pid == pid
+                    && proc.parent_pid == parent_pid
+            })
+            .ok_or(ErrorKind::NotFound)?;
+        let mut child = slot.borrow_mut();
+        if child.state == ProcessState::Exited {
+            // SAFETY: The child has exited, so nothing runs on its kernel stack anymore.
+            unsafe {
+                crate::alloc::free_pages(
+                    child.kernel_stack.cast(),
+                    KERNEL_STACK_SIZE.div_ceil(PAGE_SIZE),
+                );
+            }
+            // SAFETY: The child has exited, so nothing uses its address space anymore.
+            unsafe {
+                crate::page_table::free_page_table(
+                    core::ptr::NonNull::new(core::ptr::with_exposed_provenance_mut(
+                        child.page_table.0,
+                    ))
+                    .unwrap(),
+                    owns_user_page,
+                );
+            }
+            child.state = ProcessState::Unused;
+            return Ok(child.exit_status);
+        }
+        let channel = exit_channel(&child);
+        // Other processes need the slot while we sleep.
+        drop(child);
+        sleep(channel);
+    }
+}
+
+/// Check whether a page with the given flags in a process's page table belongs to the process.
+///
+/// Processes own their image, their arguments, and the memory from `mmap`, but not kernel memory
+/// or mapped resources (which are the only writable user pages that aren't executable).
+fn owns_user_page(flags: PageTableFlags) -> bool {
+    flags.user_accessible() && (flags.executable() || !flags.writable())
+}
+
+/// Exit the current process with the given status, stopping all of its threads.
+pub fn exit_process(status: i32) {
+    let thread_group = current_proc().thread_group;
+    for proc in &PROCS_BUF {
+        let mut proc = proc.borrow_mut();
+        if proc.state != ProcessState::Unused
+            && proc.thread_group == thread_group
+            && proc.is_secondary_thread()
+        {
+            proc.state = ProcessState::Exited;
+        }
+    }
+    let (channel, parent_pid, resource_descriptors) = {
+        let mut leader = current_leader();
+        log::info!("Process {} exited", leader.pid);
+        leader.exit_status = status;
+        leader.state = ProcessState::Exited;
+        (
+            exit_channel(&leader),
+            leader.parent_pid,
+            leader.resource_descriptors,
+        )
+    };
+    if parent_pid == 0 {
+        // Nothing is left to wait for the first process, so the machine is done once it exits.
+        let status = u16::try_from(status).unwrap_or(u16::MAX);
+        crate::shutdown(shared::ShutdownKind::PowerOff, status);
+    }
+    wake_all(channel);
+    // SAFETY: The process exited, so we can drop the resource descriptors (possibly running
+    // cleanup on the resource descriptions they point at).
+    unsafe { resource_descriptors.drop_in_place() };
+    // SAFETY: The process exited, so we can free these pages.
+    unsafe {
+        crate::alloc::free_pages(
+            resource_descriptors.cast(),
+            (MAX_NUM_RESOURCE_DESCRIPTORS * size_of::<Option<ResourceDescriptor>>())
+                .div_ceil(PAGE_SIZE),
+        );
+    }
+    sched_yield();
+}
+
+/// Exit the current thread, leaving the rest of its process running.
+///
+/// If this is the process's main thread, then the whole process exits with status 0.
+pub fn exit_thread() {
+    {
+        let mut current_proc = current_proc();
+        if current_proc.is_secondary_thread() {
+            // The slot gets cleaned up when it's next needed (see `free_slot`).
+            current_proc.state = ProcessState::Exited;
+        } else {
+            drop(current_proc);
+            exit_process(0);
+            return;
+        }
+    }
+    sched_yield();
+}
+
+/// Read the program at `path_name` (which is relative to the root directory) into memory.
+pub fn load_image(path_name: &str) -> Result<KByteBuf> {
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().ok_or(ErrorKind::NotFound)?;
+    let inode_num = fs.lookup_path(path_name.split('/'))?;
+    let len = usize::try_from(fs.file_size(inode_num)).map_err(|_| ErrorKind::LimitReached)?;
+    let mut image = KByteBuf::new_zeroed(len)?;
+    let mut read = 0;
+    while read < len {
+        match fs.read_file_from_offset(inode_num, read as u64, &mut image[read..])? {
+            0 => return Err(ErrorKind::Io.into()),
+            n => read += n,
+        }
+    }
+    Ok(image)
+}
+
+/// Get the channel which is woken when `proc` exits.
+pub(crate) fn exit_channel(proc: &ProcessInner) -> usize {
+    core::ptr::from_ref(proc).addr()
+}
+
+/// Block the current process until [`wake_all`] is called with the same `channel`.
+///
+/// Processes can wake up for other reasons too, so callers should check what they're waiting for
+/// again after this returns.
+pub fn sleep(channel: usize) {
+    current_proc().state = ProcessState::Blocked { channel };
+    sched_yield();
+}
+
+/// Make every process blocked on `channel` runnable again.
+pub fn wake_all(channel: usize) {
+    for proc in &PROCS_BUF {
+        let mut proc = proc.borrow_mut();
+        if proc.state == (ProcessState::Blocked { channel }) {
+            proc.state = ProcessState::Runnable;
+        }
+    }
+}
+
+/// Make up to `max` processes blocked on `channel` runnable again, returning how many were woken.
+pub fn wake(channel: usize, max: u32) -> u32 {
+    let mut woken = 0;
+    for proc in &PROCS_BUF {
+        if woken == max {
+            break;
+        }
+        let mut proc = proc.borrow_mut();
+        if proc.state == (ProcessState::Blocked { channel }) {
+            proc.state = ProcessState::Runnable;
+            woken += 1;
+        }
+    }
+    woken
+}
+
+/// Check whether we're running on behalf of a process, as opposed to still booting.
+///
+/// Only processes can [`sleep`].
+pub fn in_process() -> bool {
+    CURRENT_PROC_SLOT.load(core::sync::atomic::Ordering::Relaxed) < MAX_PROCS
+}
+
 /// Get the PID of the currently-active process.
 ///
-/// Note that this invalidates any references to [`current_proc()`].
+/// All of a process's threads share its PID.
 pub fn current_pid() -> u32 {
-    // SAFETY:
-    // Reference is valid and method precondition allows invalidating any other references.
-    unsafe { current_proc() }.pid
+    current_proc().thread_group
 }
 
-/// Get a reference to the current process.
+/// Borrow the current process.
 ///
-/// # Safety
-/// This reference is only valid until some other function references this slot.
+/// The borrow mustn't be held across anything which might switch processes (like sleeping or
+/// locking a [`KSpinLock`]), since the scheduler needs every slot.
 ///
-/// TODO Support thread-safety in multithreading.
-pub(crate) unsafe fn current_proc<'a>() -> &'a mut ProcessInner {
-    // SAFETY:
-    // Method precondition indicates how long the reference can be made to exist.
-    unsafe { &mut *PROCS_BUF[CURRENT_PROC_SLOT.load(core::sync::atomic::Ordering::Relaxed)].get() }
+/// # Panics
+/// Panics if the current process is already borrowed.
+#[track_caller]
+pub(crate) fn current_proc() -> CheckedRefMut<'static, ProcessInner> {
+    PROCS_BUF[CURRENT_PROC_SLOT.load(core::sync::atomic::Ordering::Relaxed)].borrow_mut()
+}
+
+/// Borrow the main thread of the current process.
+///
+/// This is the same as [`current_proc`] for the main thread, and the same restrictions apply.
+///
+/// # Panics
+/// Panics if any process is already mutably borrowed, or the main thread is borrowed at all.
+#[track_caller]
+pub(crate) fn current_leader() -> CheckedRefMut<'static, ProcessInner> {
+    let thread_group = current_proc().thread_group;
+    PROCS_BUF
+        .iter()
+        .find(|slot| {
+            let proc = slot.borrow();
+            proc.state != ProcessState::Unused && proc.pid == thread_group
+        })
+        .expect("A process's threads exit along with its main thread")
+        .borrow_mut()
 }
 
 /// Do a context switch.
@@ -288,19 +612,23 @@ pub unsafe fn switch_context(old_proc: &mut Process, new_proc: &mut Process) {
         core::arch::asm!("sfence.vma");
     };
     CURRENT_PROC_SLOT.store(new_proc.buf_idx, core::sync::atomic::Ordering::Relaxed);
-    let old_sp = &mut old_proc.inner_mut().sp;
-    let new_sp = &mut new_proc.inner_mut().sp;
+    // Borrowing checks that nothing else is using either slot, but the borrows can't last through
+    // the switch, since other processes need the slots while this one is switched out.
+    let old_sp = &raw mut old_proc.inner_mut().sp;
+    let new_sp = &raw mut new_proc.inner_mut().sp;
     // SAFETY:
-    // We've parsed the stack pointers from the two processes correctly.
+    // We've parsed the stack pointers from the two processes correctly, and nothing else runs on
+    // this hart to use the slots until the switch is done.
     unsafe { switch_context_inner(old_sp, new_sp) };
 }
 
 /// Actually do the inner context switch
 ///
 /// # Safety
-/// `old_sp` and `new_sp` must be references to [`ProcessInner::sp`] fields which are properly set up.
+/// `old_sp` and `new_sp` must point to [`ProcessInner::sp`] fields which are properly set up, and
+/// which nothing else is using.
 #[unsafe(naked)]
-unsafe extern "C" fn switch_context_inner(old_sp: &mut *mut (), new_sp: &mut *mut ()) {
+unsafe extern "C" fn switch_context_inner(old_sp: *mut *mut (), new_sp: *mut *mut ()) {
     core::arch::naked_asm!(
         // Save callee-saved registers onto the current process's stack.
         "addi sp, sp, -13 * 4", // Allocate stack space for 13 4-byte registers
@@ -339,6 +667,56 @@ unsafe extern "C" fn switch_context_inner(old_sp: &mut *mut (), new_sp: &mut *mu
     )
 }
 
+/// The length of the header of a process's argument block, before the strings.
+const ARGS_HEADER_LEN: usize = 3 * size_of::<u32>();
+
+/// Lay out a process's arguments and environment variables as described at
+/// [`shared::PROCESS_ARGS_ADDR`].
+fn encode_process_args(args: &[&str], env: &[&str]) -> Result<KByteBuf> {
+    let strings_len: usize = args.iter().chain(env).map(|s| s.len() + 1).sum();
+    let mut block = KByteBuf::new_zeroed(ARGS_HEADER_LEN + strings_len)?;
+    for (field, value) in block[..ARGS_HEADER_LEN]
+        .as_chunks_mut::<4>()
+        .0
+        .iter_mut()
+        .zip([args.len(), env.len(), strings_len])
+    {
+        *field = u32::try_from(value)
+            .map_err(|_| ErrorKind::LimitReached)?
+            .to_le_bytes();
+    }
+    let mut rest = &mut block[ARGS_HEADER_LEN..];
+    for s in args.iter().chain(env) {
+        if s.contains('\0') {
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        rest[..s.len()].copy_from_slice(s.as_bytes());
+        // The byte after is already NUL.
+        rest = &mut rest[s.len() + 1..];
+    }
+    Ok(block)
+}
+
+/// Check that a process's argument block from user space is laid out as described at
+/// [`shared::PROCESS_ARGS_ADDR`].
+fn validate_process_args(block: &[u8]) -> Result<()> {
+    let (header, strings) = block
+        .split_first_chunk::<ARGS_HEADER_LEN>()
+        .ok_or(ErrorKind::InvalidFormat)?;
+    let [num_args, num_vars, strings_len] = {
+        let (fields, _) = header.as_chunks::<4>();
+        [0, 1, 2].map(|idx| u32::from_le_bytes(fields[idx]) as usize)
+    };
+    let strings = str::from_utf8(strings).map_err(|_| ErrorKind::InvalidFormat)?;
+    if strings_len != strings.len()
+        || !(strings.is_empty() || strings.ends_with('\0'))
+        || strings.matches('\0').count() != num_args + num_vars
+    {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    Ok(())
+}
+
 #[unsafe(naked)]
 unsafe extern "C" fn user_entry() {
     core::arch::naked_asm!(
@@ -348,8 +726,32 @@ unsafe extern "C" fn user_entry() {
         "lui t0, %hi({sstatus})",
         "addi t0, t0, %lo({sstatus})",
         "csrw sstatus, t0",
+        // Start user code with `tp` cleared, since userlib uses it as the thread pointer.
+        "li tp, 0",
         "sret",
         sepc = const USER_BASE,
         sstatus =  const 1 << 5,
     );
 }
+
+/// Start running a new thread in user mode.
+///
+/// [`new_kernel_stack`] sets `s0` to the entry point, `s1` to the stack pointer, and `s2` to the
+/// argument to pass. The thread starts with `s0` cleared, since user code uses it as the frame
+/// pointer.
+#[unsafe(naked)]
+unsafe extern "C" fn thread_entry() {
+    core::arch::naked_asm!(
+        "csrw sepc, s0",
+        // Clear the frame pointer, which marks the end of the chain for backtraces.
+        "li s0, 0",
+        "mv sp, s1",
+        "mv a0, s2",
+        "li t0, {sstatus}",
+        "csrw sstatus, t0",
+        // Start with `tp` cleared, as for new processes (see `user_entry`).
+        "li tp, 0",
+        "sret",
+        sstatus = const 1 << 5,
+    );
+}
@@ -0,0 +1,97 @@
+//! A block device backed by kernel memory.
+//!
+//! This is a [`BlockDevice`] like any other, so the filesystem can run without a disk attached.
+//! Set the `RAM_DISK_IMAGE` environment variable to a disk image's path when building to embed it
+//! in the kernel.
+
+use crate::{
+    alloc::KByteBuf,
+    block::{BLOCK_SECTOR_LEN, BlockDevice},
+    error::{ErrorKind, Result},
+};
+
+/// The disk image embedded in the kernel at build time, which is empty if there isn't one.
+const EMBEDDED_IMAGE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ram_disk.img"));
+
+/// A disk whose sectors are held in memory.
+///
+/// Writes only last until the disk is dropped.
+pub struct RamDisk {
+    /// The contents of the disk.
+    data: KByteBuf,
+}
+impl RamDisk {
+    /// Create a disk holding a copy of `image`.
+    ///
+    /// `image` must be a whole number of sectors long.
+    pub fn new(image: &[u8]) -> Result<Self> {
+        if !image.len().is_multiple_of(BLOCK_SECTOR_LEN) {
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        let mut data = KByteBuf::new_zeroed(image.len())?;
+        data.copy_from_slice(image);
+        Ok(Self { data })
+    }
+
+    /// Create a disk holding a copy of the image embedded at build time.
+    ///
+    /// Fails if no image was embedded.
+    pub fn from_embedded_image() -> Result<Self> {
+        if EMBEDDED_IMAGE.is_empty() {
+            return Err(ErrorKind::NotFound.into());
+        }
+        log::info!(
+            "Initializing RAM disk from {} byte embedded image",
+            EMBEDDED_IMAGE.len()
+        );
+        Self::new(EMBEDDED_IMAGE)
+    }
+
+    /// Get the range of bytes in `data` for a transfer of `len` bytes, starting at `start_sector`.
+    ///
+    /// Fails if `len` isn't a whole number of sectors.
+    fn byte_range(&self, start_sector: u64, len: usize) -> Result<core::ops::Range<usize>> {
+        if !len.is_multiple_of(BLOCK_SECTOR_LEN) {
+            return Err(ErrorKind::InvalidFormat.into());
+        }
+        self.sector_range(start_sector, (len / BLOCK_SECTOR_LEN) as u64)
+    }
+
+    /// Get the range of bytes in `data` holding the given sectors.
+    ///
+    /// Fails if any of the sectors are past the end of the disk, like a real disk would.
+    fn sector_range(&self, start_sector: u64, num_sectors: u64) -> Result<core::ops::Range<usize>> {
+        let end_sector = start_sector
+            .checked_add(num_sectors)
+            .filter(|&end| end <= self.capacity())
+            .ok_or(ErrorKind::Io)?;
+        Ok(start_sector as usize * BLOCK_SECTOR_LEN..end_sector as usize * BLOCK_SECTOR_LEN)
+    }
+}
+impl BlockDevice for RamDisk {
+    fn read_sectors(&mut self, buf: &mut [u8], start_sector: u64) -> Result<()> {
+        let range = self.byte_range(start_sector, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, data: &[u8], start_sector: u64) -> Result<()> {
+        let range = self.byte_range(start_sector, data.len())?;
+        self.data[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // Writes go straight to memory, so there's nothing to do.
+        Ok(())
+    }
+
+    fn capacity(&self) -> u64 {
+        (self.data.len() / BLOCK_SECTOR_LEN) as u64
+    }
+
+    fn discard(&mut self, start_sector: u64, num_sectors: u64) -> Result<()> {
+        // We can't give memory back for part of the disk, so we only check the range.
+        self.sector_range(start_sector, num_sectors).map(|_| ())
+    }
+}
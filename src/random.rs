@@ -0,0 +1,195 @@
+//! The kernel's source of random numbers.
+//!
+//! Going to the entropy device for every request would be slow, and requests would fail whenever
+//! the device can't keep up. Instead, we use its data to seed a CSPRNG built on the `ChaCha20` block
+//! function, which we reseed from the device every so often.
+//!
+//! After each request, the generator replaces its key with fresh output ("fast key erasure"), so
+//! its state can't be used to recover anything it already handed out.
+
+use core::time::Duration;
+
+use crate::{
+    error::{ErrorKind, Result},
+    sync::KSpinLock,
+    virtio::VirtioRandom,
+};
+
+/// The number of bytes of entropy we take from the device for each (re)seed.
+const SEED_LEN: usize = 32;
+/// How long we go between reseeds.
+const RESEED_INTERVAL: Duration = Duration::from_mins(1);
+/// How many bytes we hand out between reseeds.
+const RESEED_BYTES: u64 = 1 << 20;
+
+/// The generator, which is `None` until it's seeded.
+static POOL: KSpinLock<Option<ChaCha20Rng>> = KSpinLock::new(None);
+
+/// Seed the generator from the entropy device, which must be in the
+/// [`DEVICE_TREE`](crate::DEVICE_TREE).
+pub fn init() -> Result<()> {
+    let seed = {
+        let mut device = crate::DEVICE_TREE.random.lock();
+        read_seed(device.as_mut().ok_or(ErrorKind::NotFound)?)?
+    };
+    *POOL.lock() = Some(ChaCha20Rng::new(seed));
+    Ok(())
+}
+
+/// Fill `buf` with random bytes.
+///
+/// This only fails if the generator hasn't been seeded. If it's due to be reseeded but the
+/// entropy device is busy or fails, we keep using the current seed and try again next time.
+pub fn fill(buf: &mut [u8]) -> Result<()> {
+    let needs_reseed = POOL
+        .lock()
+        .as_ref()
+        .ok_or(ErrorKind::NotFound)?
+        .needs_reseed();
+    // We don't hold the lock on the generator while we wait for the device, so other requests
+    // aren't held up behind us.
+    let seed = if needs_reseed {
+        try_read_seed()
+            .inspect_err(|e| log::warn!("Failed to reseed random number generator: {e}"))
+            .ok()
+    } else {
+        None
+    };
+
+    let mut pool = POOL.lock();
+    let pool = pool.as_mut().ok_or(ErrorKind::NotFound)?;
+    if let Some(seed) = seed {
+        pool.reseed(seed);
+    }
+    pool.fill(buf);
+    Ok(())
+}
+
+/// Read a seed from the entropy device, if nothing else is using it.
+fn try_read_seed() -> Result<[u8; SEED_LEN]> {
+    let mut device = crate::DEVICE_TREE
+        .random
+        .try_lock()
+        .ok_or(ErrorKind::LimitReached)?;
+    read_seed(device.as_mut().ok_or(ErrorKind::NotFound)?)
+}
+
+/// Read a seed from the given entropy device.
+fn read_seed(device: &mut VirtioRandom<'_>) -> Result<[u8; SEED_LEN]> {
+    let mut seed = [0; SEED_LEN];
+    device.read_random(&mut seed)?;
+    Ok(seed)
+}
+
+/// A CSPRNG which outputs the `ChaCha20` keystream.
+struct ChaCha20Rng {
+    /// The key for the keystream.
+    key: [u32; 8],
+    /// The number of the next block of keystream.
+    counter: u64,
+    /// The `time` CSR value after which we should reseed.
+    reseed_deadline: u64,
+    /// The number of bytes handed out since we last reseeded.
+    bytes_since_reseed: u64,
+}
+impl ChaCha20Rng {
+    /// The constant which fills the first row of the `ChaCha` state ("expand 32-byte k").
+    const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+    /// Create a generator with the given seed as its key.
+    fn new(seed: [u8; SEED_LEN]) -> Self {
+        let mut this = Self {
+            key: [0; 8],
+            counter: 0,
+            reseed_deadline: 0,
+            bytes_since_reseed: 0,
+        };
+        this.reseed(seed);
+        this
+    }
+
+    /// Get whether it's time to mix in a new seed.
+    fn needs_reseed(&self) -> bool {
+        self.bytes_since_reseed >= RESEED_BYTES || crate::timer::now() >= self.reseed_deadline
+    }
+
+    /// Mix a new seed into the key.
+    fn reseed(&mut self, seed: [u8; SEED_LEN]) {
+        for (key, seed) in self.key.iter_mut().zip(seed.as_chunks::<4>().0) {
+            *key ^= u32::from_le_bytes(*seed);
+        }
+        // Run the combined key through the block function, so the new key doesn't depend on the
+        // old one and the seed in any simple way.
+        self.rekey();
+        self.reseed_deadline = crate::timer::deadline_after(RESEED_INTERVAL);
+        self.bytes_since_reseed = 0;
+    }
+
+    /// Fill `buf` with the keystream, then replace the key.
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(64) {
+            let block = self.next_block();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        self.rekey();
+        self.bytes_since_reseed = self.bytes_since_reseed.saturating_add(buf.len() as u64);
+    }
+
+    /// Replace the key with the next block of keystream.
+    fn rekey(&mut self) {
+        let block = self.next_block();
+        for (key, bytes) in self.key.iter_mut().zip(block.as_chunks::<4>().0) {
+            *key = u32::from_le_bytes(*bytes);
+        }
+    }
+
+    /// Generate the next block of keystream.
+    fn next_block(&mut self) -> [u8; 64] {
+        let mut state = [0; 16];
+        state[..4].copy_from_slice(&Self::CONSTANTS);
+        state[4..12].copy_from_slice(&self.key);
+        state[12] = self.counter as u32;
+        state[13] = (self.counter >> 32) as u32;
+        // The nonce (the last two words) is always zero, since each key is only used for a few
+        // blocks.
+        self.counter = self.counter.wrapping_add(1);
+
+        let mut working = state;
+        for _ in 0..10 {
+            // Column rounds
+            quarter_round(&mut working, 0, 4, 8, 12);
+            quarter_round(&mut working, 1, 5, 9, 13);
+            quarter_round(&mut working, 2, 6, 10, 14);
+            quarter_round(&mut working, 3, 7, 11, 15);
+            // Diagonal rounds
+            quarter_round(&mut working, 0, 5, 10, 15);
+            quarter_round(&mut working, 1, 6, 11, 12);
+            quarter_round(&mut working, 2, 7, 8, 13);
+            quarter_round(&mut working, 3, 4, 9, 14);
+        }
+
+        let mut block = [0; 64];
+        for ((out, word), initial) in block
+            .as_chunks_mut::<4>()
+            .0
+            .iter_mut()
+            .zip(working)
+            .zip(state)
+        {
+            *out = word.wrapping_add(initial).to_le_bytes();
+        }
+        block
+    }
+}
+
+/// The `ChaCha` quarter round, on the given words of the state.
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
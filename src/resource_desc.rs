@@ -1,6 +1,8 @@
 //! Code for handling open resource descriptions.
 
-use crate::error::Result;
+use core::mem::ManuallyDrop;
+
+use crate::{error::Result, page_table::PhysicalAddress, pipe::SharedPipe, timer::Timeout};
 
 /// The state of an open resource.
 pub struct ResourceDescription {
@@ -19,6 +21,52 @@ impl ResourceDescription {
         }
     }
 
+    /// Create a new descriptor for the socket with the given handle in the network stack.
+    pub const fn for_socket(socket_handle: usize) -> Self {
+        Self {
+            vtable: RawResourceDescriptionVTable::SOCKET_VTABLE,
+            data: ResourceDescriptionData {
+                socket: socket_handle,
+            },
+        }
+    }
+
+    /// Create a new descriptor for the display's framebuffer.
+    pub const fn for_framebuffer() -> Self {
+        Self {
+            vtable: RawResourceDescriptionVTable::FRAMEBUFFER_VTABLE,
+            data: ResourceDescriptionData { null: () },
+        }
+    }
+
+    /// Create a new descriptor for keyboard events.
+    pub const fn for_keyboard() -> Self {
+        Self {
+            vtable: RawResourceDescriptionVTable::KEYBOARD_VTABLE,
+            data: ResourceDescriptionData { null: () },
+        }
+    }
+
+    /// Create a new descriptor for the read end of a pipe.
+    pub const fn for_pipe_reader(pipe: SharedPipe) -> Self {
+        Self {
+            vtable: RawResourceDescriptionVTable::PIPE_READER_VTABLE,
+            data: ResourceDescriptionData {
+                pipe: ManuallyDrop::new(pipe),
+            },
+        }
+    }
+
+    /// Create a new descriptor for the write end of a pipe.
+    pub const fn for_pipe_writer(pipe: SharedPipe) -> Self {
+        Self {
+            vtable: RawResourceDescriptionVTable::PIPE_WRITER_VTABLE,
+            data: ResourceDescriptionData {
+                pipe: ManuallyDrop::new(pipe),
+            },
+        }
+    }
+
     pub const fn for_console_in() -> Self {
         Self {
             vtable: RawResourceDescriptionVTable::CONSOLE_IN_VTABLE,
@@ -33,10 +81,13 @@ impl ResourceDescription {
         }
     }
 
-    /// Read from the given resource.
-    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    /// Read from the given resource, failing with [`shared::ErrorKind::TimedOut`] if it would
+    /// still be waiting for something to arrive once `timeout` expires.
+    ///
+    /// Resources which never wait to read ignore the timeout.
+    pub fn read(&mut self, buf: &mut [u8], timeout: &Timeout) -> Result<usize> {
         // SAFETY: We keep the vtable and the value together to meet the precondition.
-        unsafe { (self.vtable.read)(&mut self.data, buf) }
+        unsafe { (self.vtable.read)(&mut self.data, buf, timeout) }
     }
 
     /// Write to the given resource.
@@ -45,6 +96,30 @@ impl ResourceDescription {
         unsafe { (self.vtable.write)(&mut self.data, buf) }
     }
 
+    /// Get the handle of the socket in the network stack, if this resource is a socket.
+    pub fn socket(&self) -> Option<usize> {
+        // SAFETY: We keep the vtable and the value together to meet the precondition.
+        unsafe { (self.vtable.socket)(&self.data) }
+    }
+
+    /// Get the physical memory backing this resource and its length, for mapping into a process.
+    pub fn memory(&self) -> Result<(PhysicalAddress, usize)> {
+        // SAFETY: We keep the vtable and the value together to meet the precondition.
+        unsafe { (self.vtable.memory)(&self.data) }
+    }
+
+    /// Move the offset that reads and writes start from, returning the new offset.
+    pub fn seek(&mut self, whence: shared::SeekWhence, offset: i64) -> Result<u64> {
+        // SAFETY: We keep the vtable and the value together to meet the precondition.
+        unsafe { (self.vtable.seek)(&mut self.data, whence, offset) }
+    }
+
+    /// Push any pending changes to the resource out to the underlying device.
+    pub fn sync(&mut self) -> Result<()> {
+        // SAFETY: We keep the vtable and the value together to meet the precondition.
+        unsafe { (self.vtable.sync)(&mut self.data) }
+    }
+
     /// Close the given resource.
     pub fn close(&mut self) {
         // SAFETY: We keep the vtable and the value together to meet the precondition.
@@ -62,6 +137,8 @@ bitset::bitset!(
         Present,
         Readable,
         Writable,
+        /// Writes always go to the end of the file.
+        Append,
     }
 );
 impl FileFlags {
@@ -76,40 +153,80 @@ impl FileFlags {
 /// [`ResourceDescriptionData`] associated with the same resource descriptor as contains this
 /// vtable.
 struct RawResourceDescriptionVTable {
-    read: unsafe fn(&mut ResourceDescriptionData, &mut [u8]) -> Result<usize>,
+    read: unsafe fn(&mut ResourceDescriptionData, &mut [u8], &Timeout) -> Result<usize>,
     write: unsafe fn(&mut ResourceDescriptionData, &[u8]) -> Result<usize>,
     close: unsafe fn(&mut ResourceDescriptionData),
+    socket: unsafe fn(&ResourceDescriptionData) -> Option<usize>,
+    memory: unsafe fn(&ResourceDescriptionData) -> Result<(PhysicalAddress, usize)>,
+    sync: unsafe fn(&mut ResourceDescriptionData) -> Result<()>,
+    seek: unsafe fn(&mut ResourceDescriptionData, shared::SeekWhence, i64) -> Result<u64>,
 }
 impl RawResourceDescriptionVTable {
     /// The [`RawResourceDescriptionVTable`] for file operations.
     const FILE_VTABLE: Self = {
         fn file_read(file_data: &mut FileResourceDescriptionData, buf: &mut [u8]) -> Result<usize> {
             assert!(file_data.flags.present() && file_data.flags.readable());
-            crate::DEVICE_TREE
+            let len = crate::DEVICE_TREE
                 .storage
                 .lock()
                 .as_mut()
                 .unwrap()
-                .read_file_from_offset(file_data.inode_num, file_data.offset, buf)
+                .read_file_from_offset(file_data.inode_num, file_data.offset, buf)?;
+            file_data.offset += len as u64;
+            Ok(len)
         }
         fn file_write(file_data: &mut FileResourceDescriptionData, buf: &[u8]) -> Result<usize> {
             assert!(file_data.flags.present() && file_data.flags.writable());
-            let len = crate::DEVICE_TREE
+            let mut fs = crate::DEVICE_TREE.storage.lock();
+            let fs = fs.as_mut().unwrap();
+            if file_data.flags.append() {
+                // Other descriptions may have written to the file since we last did, so we find
+                // the end while holding the lock.
+                file_data.offset = fs.file_size(file_data.inode_num);
+            }
+            let len = fs.write_file_from_offset(file_data.inode_num, file_data.offset, buf)?;
+            file_data.offset += len as u64;
+            Ok(len)
+        }
+        fn file_seek(
+            file_data: &mut FileResourceDescriptionData,
+            whence: shared::SeekWhence,
+            offset: i64,
+        ) -> Result<u64> {
+            let file_size = crate::DEVICE_TREE
                 .storage
                 .lock()
                 .as_mut()
                 .unwrap()
-                .write_file_from_offset(file_data.inode_num, file_data.offset, buf)?;
-            file_data.offset += len as u64;
-            Ok(len)
+                .file_size(file_data.inode_num);
+            let base = match whence {
+                shared::SeekWhence::Start => 0,
+                shared::SeekWhence::Current => file_data.offset,
+                shared::SeekWhence::End => file_size,
+            };
+            // We can't leave gaps in files, so the offset has to stay within the file.
+            file_data.offset = base
+                .checked_add_signed(offset)
+                .filter(|&offset| offset <= file_size)
+                .ok_or(shared::ErrorKind::InvalidFormat)?;
+            Ok(file_data.offset)
         }
         fn file_close(file_data: &mut FileResourceDescriptionData) {
+            if let Err(e) = crate::DEVICE_TREE
+                .storage
+                .lock()
+                .as_mut()
+                .unwrap()
+                .release_inode(file_data.inode_num)
+            {
+                log::error!("Error releasing inode {}: {e}", file_data.inode_num);
+            }
             file_data.flags = FileFlags::empty();
             file_data.offset = 0;
             file_data.inode_num = 0;
         }
         Self {
-            read: |data, buf| {
+            read: |data, buf, _| {
                 // SAFETY: This can only be called if the data is a file.
                 let data = unsafe { &mut data.file };
                 file_read(data, buf)
@@ -124,42 +241,209 @@ impl RawResourceDescriptionVTable {
                 let data = unsafe { &mut data.file };
                 file_close(data);
             },
+            socket: |_| None,
+            memory: |_| Err(shared::ErrorKind::Unsupported.into()),
+            sync: |data| {
+                // SAFETY: This can only be called if the data is a file.
+                let data = unsafe { &mut data.file };
+                crate::DEVICE_TREE
+                    .storage
+                    .lock()
+                    .as_mut()
+                    .unwrap()
+                    .sync_inode(data.inode_num)
+            },
+            seek: |data, whence, offset| {
+                // SAFETY: This can only be called if the data is a file.
+                let data = unsafe { &mut data.file };
+                file_seek(data, whence, offset)
+            },
         }
     };
 
     const CONSOLE_IN_VTABLE: Self = {
         Self {
-            read: |_, buf| {
-                let c = loop {
-                    if let Ok(Some(c)) = crate::sbi::getchar() {
-                        // TODO log the error
-                        break c;
-                    }
-                };
-                let c_ser = c.get().encode_utf8(buf);
-                Ok(c_ser.len())
-            },
+            read: |_, buf, timeout| crate::console::read(buf, timeout),
             write: |_, _| {
                 panic!("Write to console in not permitted");
             },
             close: |_| {},
+            socket: |_| None,
+            memory: |_| Err(shared::ErrorKind::Unsupported.into()),
+            // Writes go straight to the device, so there's nothing to do.
+            sync: |_| Ok(()),
+            seek: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
         }
     };
 
     const CONSOLE_OUT_VTABLE: Self = {
         Self {
-            read: |_, _| {
+            read: |_, _, _| {
                 panic!("Read from console out not permitted");
             },
             write: |_, buf| {
-                use core::fmt::Write as _;
-                let s = str::from_utf8(buf).expect("TODO Write non-utf8");
-                crate::sbi::SbiPutcharWriter
-                    .write_str(s)
-                    .map_err(|core::fmt::Error| shared::ErrorKind::Io)?;
-                Ok(s.len())
+                crate::console::write(buf)?;
+                Ok(buf.len())
+            },
+            close: |_| {},
+            socket: |_| None,
+            memory: |_| Err(shared::ErrorKind::Unsupported.into()),
+            // Writes go straight to the device, so there's nothing to do.
+            sync: |_| Ok(()),
+            seek: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
+        }
+    };
+
+    /// The [`RawResourceDescriptionVTable`] for the read end of a pipe.
+    ///
+    /// Reads wait for something to be written, until the write end is closed.
+    const PIPE_READER_VTABLE: Self = {
+        Self {
+            read: |data, buf, timeout| {
+                // SAFETY: This can only be called if the data is a pipe.
+                let pipe = unsafe { &data.pipe };
+                crate::pipe::read(pipe, buf, timeout)
+            },
+            write: |_, _| Err(shared::ErrorKind::Unsupported.into()),
+            close: |data| {
+                // SAFETY:
+                // This can only be called if the data is a pipe, and it's only called once, when
+                // the description is dropped, so nothing uses the pipe after we take it.
+                let pipe = unsafe { ManuallyDrop::take(&mut data.pipe) };
+                crate::pipe::close_reader(&pipe);
+            },
+            socket: |_| None,
+            memory: |_| Err(shared::ErrorKind::Unsupported.into()),
+            sync: |_| Ok(()),
+            seek: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
+        }
+    };
+
+    /// The [`RawResourceDescriptionVTable`] for the write end of a pipe.
+    ///
+    /// Writes wait for room in the pipe, and fail once the read end is closed.
+    const PIPE_WRITER_VTABLE: Self = {
+        Self {
+            read: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
+            write: |data, buf| {
+                // SAFETY: This can only be called if the data is a pipe.
+                let pipe = unsafe { &data.pipe };
+                crate::pipe::write(pipe, buf)
+            },
+            close: |data| {
+                // SAFETY:
+                // This can only be called if the data is a pipe, and it's only called once, when
+                // the description is dropped, so nothing uses the pipe after we take it.
+                let pipe = unsafe { ManuallyDrop::take(&mut data.pipe) };
+                crate::pipe::close_writer(&pipe);
+            },
+            socket: |_| None,
+            memory: |_| Err(shared::ErrorKind::Unsupported.into()),
+            sync: |_| Ok(()),
+            seek: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
+        }
+    };
+
+    /// The [`RawResourceDescriptionVTable`] for network sockets.
+    ///
+    /// Sockets send and receive through their own syscalls, since each datagram has an address.
+    const SOCKET_VTABLE: Self = {
+        Self {
+            read: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
+            write: |_, _| Err(shared::ErrorKind::Unsupported.into()),
+            close: |data| {
+                // SAFETY: This can only be called if the data is a socket.
+                let socket_handle = unsafe { data.socket };
+                if let Some(net) = crate::DEVICE_TREE.network.lock().as_mut() {
+                    net.close_socket(socket_handle);
+                }
+            },
+            socket: |data| {
+                // SAFETY: This can only be called if the data is a socket.
+                Some(unsafe { data.socket })
+            },
+            memory: |_| Err(shared::ErrorKind::Unsupported.into()),
+            sync: |_| Ok(()),
+            seek: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
+        }
+    };
+
+    /// The [`RawResourceDescriptionVTable`] for the display's framebuffer.
+    ///
+    /// Processes draw by mapping the framebuffer's memory, so reading gives its layout instead of
+    /// its contents.
+    const FRAMEBUFFER_VTABLE: Self = {
+        Self {
+            read: |_, buf, _| {
+                let gpu = crate::DEVICE_TREE.gpu.lock();
+                let info = gpu.as_ref().ok_or(shared::ErrorKind::NotFound)?.info();
+                if buf.len() != size_of::<shared::FramebufferInfo>() {
+                    return Err(shared::ErrorKind::InvalidFormat.into());
+                }
+                #[expect(clippy::cast_ptr_alignment, reason = "Following write is unaligned")]
+                let info_ptr = buf.as_mut_ptr().cast::<shared::FramebufferInfo>();
+                // SAFETY: We checked that the buffer is the right size, and the write is unaligned.
+                unsafe { info_ptr.write_unaligned(info) };
+                Ok(buf.len())
+            },
+            write: |_, _| Err(shared::ErrorKind::Unsupported.into()),
+            close: |_| {},
+            socket: |_| None,
+            memory: |_| {
+                let gpu = crate::DEVICE_TREE.gpu.lock();
+                let gpu = gpu.as_ref().ok_or(shared::ErrorKind::NotFound)?;
+                Ok((gpu.framebuffer(), gpu.framebuffer_len()))
+            },
+            sync: |_| {
+                crate::DEVICE_TREE
+                    .gpu
+                    .lock()
+                    .as_mut()
+                    .ok_or(shared::ErrorKind::NotFound)?
+                    .flush()
+            },
+            seek: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
+        }
+    };
+
+    /// The [`RawResourceDescriptionVTable`] for keyboard events.
+    ///
+    /// Reads wait for at least one event, then give as many whole events as are waiting and fit.
+    const KEYBOARD_VTABLE: Self = {
+        Self {
+            read: |_, buf, timeout| {
+                if buf.len() < shared::InputEvent::LEN {
+                    return Err(shared::ErrorKind::InvalidFormat.into());
+                }
+                loop {
+                    let mut len = 0;
+                    {
+                        let mut input = crate::DEVICE_TREE.input.lock();
+                        let input = input.as_mut().ok_or(shared::ErrorKind::NotFound)?;
+                        for event_buf in buf.chunks_exact_mut(shared::InputEvent::LEN) {
+                            let Some(event) = input.next_event() else {
+                                break;
+                            };
+                            event_buf.copy_from_slice(&event.to_bytes());
+                            len += shared::InputEvent::LEN;
+                        }
+                    }
+                    if len > 0 {
+                        return Ok(len);
+                    }
+                    if timeout.expired() {
+                        return Err(shared::ErrorKind::TimedOut.into());
+                    }
+                    // Let other processes run while we wait for a key.
+                    crate::proc::sched_yield();
+                }
             },
+            write: |_, _| Err(shared::ErrorKind::Unsupported.into()),
             close: |_| {},
+            socket: |_| None,
+            memory: |_| Err(shared::ErrorKind::Unsupported.into()),
+            sync: |_| Ok(()),
+            seek: |_, _, _| Err(shared::ErrorKind::Unsupported.into()),
         }
     };
 }
@@ -168,6 +452,10 @@ impl RawResourceDescriptionVTable {
 pub(crate) union ResourceDescriptionData {
     /// State information for anything resembling a file.
     file: FileResourceDescriptionData,
+    /// The handle of a socket in the network stack.
+    socket: usize,
+    /// The pipe that this is one end of.
+    pipe: ManuallyDrop<SharedPipe>,
     /// Some descriptors don't need anything more.
     null: (),
 }
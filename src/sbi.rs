@@ -43,6 +43,63 @@ pub fn getchar() -> Result<Option<core::num::NonZero<char>>> {
     Ok(char::from_u32(c).and_then(core::num::NonZero::new))
 }
 
+/// The ID of the SBI timer extension ("TIME").
+const TIME_EXTENSION_ID: u32 = 0x5449_4D45;
+
+/// Schedule a timer interrupt for when the `time` counter reaches `stime_value`.
+///
+/// This also clears any pending timer interrupt. To stop timer interrupts instead, pass
+/// `u64::MAX`.
+pub fn set_timer(stime_value: u64) -> Result<()> {
+    // SAFETY: These args are for `SetTimer`, which is valid to call here.
+    unsafe {
+        call(
+            [stime_value as u32, (stime_value >> 32) as u32, 0, 0, 0, 0],
+            0,
+            TIME_EXTENSION_ID,
+        )
+    }?;
+    Ok(())
+}
+
+/// The ID of the SBI system reset extension ("SRST").
+const SYSTEM_RESET_EXTENSION_ID: u32 = 0x5352_5354;
+
+/// The kinds of reset [`system_reset`] can do.
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum ResetType {
+    /// Turn the machine off.
+    Shutdown = 0,
+    /// Restart the machine from scratch.
+    ColdReboot = 1,
+}
+
+/// Why we're asking for a reset, in [`system_reset`].
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum ResetReason {
+    /// Nothing went wrong.
+    NoReason = 0,
+    /// Something went wrong that we couldn't recover from.
+    SystemFailure = 1,
+}
+
+/// Shut down or reboot the machine.
+///
+/// This only returns if the SBI couldn't do the reset.
+pub fn system_reset(reset_type: ResetType, reason: ResetReason) -> Result<()> {
+    // SAFETY: These args are for `SystemReset`, which is valid to call here.
+    unsafe {
+        call(
+            [reset_type as u32, reason as u32, 0, 0, 0, 0],
+            0,
+            SYSTEM_RESET_EXTENSION_ID,
+        )
+    }?;
+    Ok(())
+}
+
 /// A [`core::fmt::Write`] implementation for the SBI writing interface.
 pub struct SbiPutcharWriter;
 impl core::fmt::Write for SbiPutcharWriter {
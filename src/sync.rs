@@ -2,7 +2,6 @@
 
 use core::{
     cell::UnsafeCell,
-    mem::MaybeUninit,
     ops::Deref,
     sync::atomic::{AtomicBool, Ordering},
 };
@@ -107,92 +106,3 @@ impl<T: ?Sized> Drop for KSpinLockGuard<'_, T> {
         self.flag.store(false, Ordering::Release);
     }
 }
-
-/// A lazy-initialized value.
-///
-/// Note that the default value for `F` is a function pointer, which takes a word of space and
-/// which requires the function to not be a closure that captures values. You can use other types
-/// to address either shortcoming, but most other meaningful types aren't nameable, so they can't
-/// be used in a `static` variable.
-pub struct LazyLock<T, F = fn() -> T> {
-    value: UnsafeCell<MaybeUninit<T>>,
-    init_func: UnsafeCell<MaybeUninit<F>>,
-    started: AtomicBool,
-    finished: AtomicBool,
-}
-impl<T, F> LazyLock<T, F> {
-    /// Construct a new [`LazyLock`] that will call the given function to initialize.
-    pub const fn new(f: F) -> Self {
-        Self {
-            value: UnsafeCell::new(MaybeUninit::uninit()),
-            init_func: UnsafeCell::new(MaybeUninit::new(f)),
-            started: AtomicBool::new(false),
-            finished: AtomicBool::new(false),
-        }
-    }
-
-    /// Force the value to be initialized.
-    pub fn force(&self) -> &T
-    where
-        F: FnOnce() -> T,
-        T: core::fmt::Debug,
-    {
-        if self.finished.load(Ordering::Acquire) {
-            // SAFETY: The value has been initialized, so we can read.
-            let value = unsafe { &*self.value.get() };
-            // SAFETY: The value has been initialized, so we can read.
-            unsafe { value.assume_init_ref() }
-        } else {
-            #[expect(clippy::manual_assert, reason = "TODO Remove panic")]
-            if self.started.swap(true, Ordering::AcqRel) {
-                panic!("TODO Deconflict concurrent initialization attempts");
-            }
-            // SAFETY: We're about to initialize, so we can move out of the pointer.
-            let init_func = unsafe { self.init_func.get().read() };
-            let value =
-                // SAFETY: We have exclusive access, so we can write.
-                unsafe { &mut *self.value.get() }.write(unsafe { init_func.assume_init() }());
-            self.finished.store(true, Ordering::Release);
-            value
-        }
-    }
-}
-impl<T, F> Deref for LazyLock<T, F>
-where
-    F: FnOnce() -> T,
-    T: core::fmt::Debug,
-{
-    type Target = T;
-
-    fn deref(&self) -> &Self::Target {
-        self.force()
-    }
-}
-impl<T, F> Drop for LazyLock<T, F> {
-    fn drop(&mut self) {
-        let started = self.started.load(Ordering::Acquire);
-        let finished = self.started.load(Ordering::Acquire);
-        match (started, finished) {
-            (false, false) => {
-                // SAFETY: We have exclusive access.
-                let init_func = unsafe { &mut *self.init_func.get() };
-                // SAFETY: Function hasn't been consumed, but won't be, so we can drop.
-                unsafe { init_func.assume_init_drop() };
-            }
-            (true, true) => {
-                // SAFETY: We have exclusive access.
-                let value = unsafe { &mut *self.value.get() };
-                // SAFETY: Value is initialized, but won't be used anymore, so we can drop.
-                unsafe { value.assume_init_drop() };
-            }
-            _ => {
-                unreachable!("dropping lazy lock but started != finished");
-            }
-        }
-    }
-}
-
-// SAFETY: It contains a `T` or an `F`.
-unsafe impl<T: Sync, F: Send> Sync for LazyLock<T, F> {}
-// SAFETY: It contains a `T` or an `F`.
-unsafe impl<T: Send, F: Send> Send for LazyLock<T, F> {}
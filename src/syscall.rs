@@ -2,7 +2,7 @@ use shared::ErrorKind;
 
 use crate::{
     error::Result,
-    page_table::{UserMemMut, UserMemMutOpaque, UserMemRef, PAGE_SIZE},
+    page_table::{PAGE_SIZE, UserMemMut, UserMemRef},
     proc::ResourceDescriptor,
     resource_desc::{FileFlags, ResourceDescription},
 };
@@ -17,6 +17,33 @@ const READ_NUM: u32 = shared::Syscall::Read as u32;
 const WRITE_NUM: u32 = shared::Syscall::Write as u32;
 const MMAP_NUM: u32 = shared::Syscall::Mmap as u32;
 const MUNMAP_NUM: u32 = shared::Syscall::Munmap as u32;
+const LINK_NUM: u32 = shared::Syscall::Link as u32;
+const UNLINK_NUM: u32 = shared::Syscall::Unlink as u32;
+const STATFS_NUM: u32 = shared::Syscall::Statfs as u32;
+const SOCKET_NUM: u32 = shared::Syscall::Socket as u32;
+const BIND_NUM: u32 = shared::Syscall::Bind as u32;
+const SEND_TO_NUM: u32 = shared::Syscall::SendTo as u32;
+const RECV_FROM_NUM: u32 = shared::Syscall::RecvFrom as u32;
+const MAP_RESOURCE_NUM: u32 = shared::Syscall::MapResource as u32;
+const SYNC_NUM: u32 = shared::Syscall::Sync as u32;
+const CLOCK_GET_TIME_NUM: u32 = shared::Syscall::ClockGetTime as u32;
+const SLEEP_NUM: u32 = shared::Syscall::Sleep as u32;
+const SHUTDOWN_NUM: u32 = shared::Syscall::Shutdown as u32;
+const SEEK_NUM: u32 = shared::Syscall::Seek as u32;
+const SPAWN_NUM: u32 = shared::Syscall::Spawn as u32;
+const WAIT_NUM: u32 = shared::Syscall::Wait as u32;
+const STAT_NUM: u32 = shared::Syscall::Stat as u32;
+const READ_DIR_NUM: u32 = shared::Syscall::ReadDir as u32;
+const CREATE_DIR_NUM: u32 = shared::Syscall::CreateDir as u32;
+const FUTEX_WAIT_NUM: u32 = shared::Syscall::FutexWait as u32;
+const FUTEX_WAKE_NUM: u32 = shared::Syscall::FutexWake as u32;
+const SPAWN_THREAD_NUM: u32 = shared::Syscall::SpawnThread as u32;
+const EXIT_THREAD_NUM: u32 = shared::Syscall::ExitThread as u32;
+const DUP_NUM: u32 = shared::Syscall::Dup as u32;
+const READ_TIMEOUT_NUM: u32 = shared::Syscall::ReadTimeout as u32;
+const DUP2_NUM: u32 = shared::Syscall::Dup2 as u32;
+const PIPE_NUM: u32 = shared::Syscall::Pipe as u32;
+const REMOVE_DIR_NUM: u32 = shared::Syscall::RemoveDir as u32;
 
 pub fn handle_syscall(frame: &mut crate::trap::TrapFrame) {
     #![allow(
@@ -31,47 +58,28 @@ pub fn handle_syscall(frame: &mut crate::trap::TrapFrame) {
             crate::proc::sched_yield();
         }
         EXIT_NUM => {
-            // TODO record the exit status somewhere.
-            // let _exit_status = frame.a1 as i32;
-
-            // SAFETY: We have exclusive access to this thread's running process.
-            let current_proc = unsafe { crate::proc::current_proc() };
-            log::info!("Process {} exited", current_proc.pid);
-            current_proc.state = crate::proc::ProcessState::Exited;
-            // SAFETY: The process exited, so we can drop the resource descriptors (possibly
-            // running cleanup on the resource descriptions they point at).
-            unsafe { current_proc.resource_descriptors.drop_in_place() };
-            // SAFETY: The process exited, so we can free these pages.
-            unsafe {
-                crate::alloc::free_pages(
-                    current_proc.resource_descriptors.cast(),
-                    (crate::proc::MAX_NUM_RESOURCE_DESCRIPTORS
-                        * size_of::<Option<ResourceDescriptor>>())
-                    .div_ceil(PAGE_SIZE),
-                );
-            }
-            crate::proc::sched_yield();
+            crate::proc::exit_process(frame.a1.cast_signed());
         }
         GET_RANDOM_NUM => {
             let buf_start = core::ptr::with_exposed_provenance_mut(frame.a1 as usize);
             let buf_len = frame.a2 as usize;
             let user_buf = core::ptr::slice_from_raw_parts_mut(buf_start, buf_len);
+            let allow = crate::csr::AllowUserModeMemory::allow();
             // SAFETY:
-            // The buffer is in user-space, so it can't alias anything, we drop it when we return
-            // from the syscall, so the lifetime isn't too long.
-            let Some(user_buf) = (unsafe { UserMemMutOpaque::for_region(user_buf) }) else {
+            // The buffer is in user-space, so it can't alias anything, and `allow` is dropped when
+            // we return from the syscall, so the lifetime isn't too long.
+            let Some(mut user_buf) = (unsafe { UserMemMut::for_region(user_buf, &allow) }) else {
                 frame.a1 = -1_i32 as u32;
                 frame.a2 = ErrorKind::NotPermitted as u32;
                 return;
             };
-            crate::DEVICE_TREE
-                .random
-                .lock()
-                .as_mut()
-                .unwrap()
-                .read_random(user_buf)
-                .unwrap();
-            frame.a1 = 0;
+            match crate::random::fill(&mut user_buf) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
         }
         OPEN_NUM => {
             let allow = crate::csr::AllowUserModeMemory::allow();
@@ -99,17 +107,50 @@ pub fn handle_syscall(frame: &mut crate::trap::TrapFrame) {
         CLOSE_NUM => {
             let desc_num = frame.a1;
             assert!(desc_num < crate::proc::MAX_NUM_RESOURCE_DESCRIPTORS as u32);
-            // SAFETY: We have exclusive access to this thread's running process.
-            let proc = unsafe { crate::proc::current_proc() };
+            let descriptors = crate::proc::current_proc().resource_descriptors;
             // SAFETY: We can get exclusive access to the resource descriptor set.
-            let desc = &mut unsafe { &mut *proc.resource_descriptors }[desc_num as usize];
+            let desc = &mut unsafe { &mut *descriptors }[desc_num as usize];
             if desc.take().is_none() {
                 frame.a1 = -1_i32 as u32;
                 frame.a2 = ErrorKind::NotFound as u32;
             }
         }
-        READ_NUM => {
+        DUP_NUM => match syscall_dup(frame.a1) {
+            Ok(new_desc_num) => frame.a1 = new_desc_num as u32,
+            Err(e) => {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = e.kind as u32;
+            }
+        },
+        DUP2_NUM => match syscall_dup2(frame.a1, frame.a2) {
+            Ok(()) => frame.a1 = 0,
+            Err(e) => {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = e.kind as u32;
+            }
+        },
+        PIPE_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let Some(mut descs_buf) = user_mem_mut(frame.a1, frame.a2, &allow) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_pipe(&mut descs_buf) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        READ_NUM | READ_TIMEOUT_NUM => {
             let desc_num = frame.a1;
+            let timeout = if frame.a0 == READ_TIMEOUT_NUM {
+                crate::timer::Timeout::after(core::time::Duration::from_millis(frame.a4.into()))
+            } else {
+                crate::timer::Timeout::NEVER
+            };
             let allow = crate::csr::AllowUserModeMemory::allow();
             let buf_start = core::ptr::with_exposed_provenance_mut(frame.a2 as usize);
             let buf_len = frame.a3 as usize;
@@ -122,7 +163,7 @@ pub fn handle_syscall(frame: &mut crate::trap::TrapFrame) {
                 frame.a2 = ErrorKind::NotPermitted as u32;
                 return;
             };
-            match syscall_read(desc_num, &mut user_buf) {
+            match syscall_read(desc_num, &mut user_buf, &timeout) {
                 Ok(read_len) => frame.a1 = read_len as u32,
                 Err(e) => {
                     frame.a1 = -1_i32 as u32;
@@ -164,43 +205,456 @@ pub fn handle_syscall(frame: &mut crate::trap::TrapFrame) {
             }
         }
         MUNMAP_NUM => {
-            #[expect(unused, reason = "Will be used once TODO is done")]
             let alloc_addr = frame.a1;
-            #[expect(unused, reason = "Will be used once TODO is done")]
             let alloc_size = frame.a2;
-            // TODO Unmap and free the pages
-            //
-            // This is technically okay but wasteful because we could reuse these pages but we
-            // won't.
-            frame.a1 = 0;
+            match syscall_munmap(alloc_addr, alloc_size) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        LINK_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let (Some(existing_path), Some(new_path)) = (
+                user_mem_ref(frame.a1, frame.a2, &allow),
+                user_mem_ref(frame.a3, frame.a4, &allow),
+            ) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_link(&existing_path, &new_path) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        UNLINK_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let Some(path) = user_mem_ref(frame.a1, frame.a2, &allow) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_unlink(&path) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        STATFS_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let Some(path) = user_mem_ref(frame.a1, frame.a2, &allow) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            let stats_buf = core::ptr::slice_from_raw_parts_mut(
+                core::ptr::with_exposed_provenance_mut::<u8>(frame.a3 as usize),
+                frame.a4 as usize,
+            );
+            // SAFETY:
+            // The buffer is in user-space, so it can't alias anything, and `allow` is
+            // dropped when we return from the syscall, so the lifetime isn't too long.
+            let Some(mut stats_buf) = (unsafe { UserMemMut::for_region(stats_buf, &allow) }) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_statfs(&path, &mut stats_buf) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        STAT_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let (Some(path), Some(mut metadata_buf)) = (
+                user_mem_ref(frame.a1, frame.a2, &allow),
+                user_mem_mut(frame.a3, frame.a4, &allow),
+            ) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_stat(&path, &mut metadata_buf) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        READ_DIR_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let index = frame.a3;
+            let (Some(path), Some(mut name_buf)) = (
+                user_mem_ref(frame.a1, frame.a2, &allow),
+                user_mem_mut(frame.a4, frame.a5, &allow),
+            ) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_read_dir(&path, index as usize, &mut name_buf) {
+                Ok(name_len) => frame.a1 = name_len as u32,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        CREATE_DIR_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let Some(path) = user_mem_ref(frame.a1, frame.a2, &allow) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_create_dir(&path) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        REMOVE_DIR_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let Some(path) = user_mem_ref(frame.a1, frame.a2, &allow) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_remove_dir(&path) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        FUTEX_WAIT_NUM => match syscall_futex_wait(frame.a1, frame.a2) {
+            Ok(()) => frame.a1 = 0,
+            Err(e) => {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = e.kind as u32;
+            }
+        },
+        FUTEX_WAKE_NUM => match syscall_futex_wake(frame.a1, frame.a2) {
+            Ok(woken) => frame.a1 = woken,
+            Err(e) => {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = e.kind as u32;
+            }
+        },
+        SPAWN_THREAD_NUM => match crate::proc::Process::spawn_thread(
+            frame.a1 as usize,
+            frame.a2 as usize,
+            frame.a3 as usize,
+        ) {
+            Ok(tid) => frame.a1 = tid,
+            Err(e) => {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = e.kind as u32;
+            }
+        },
+        EXIT_THREAD_NUM => {
+            // The thread is exiting either way, so there's nobody to report a failure to.
+            _ = syscall_exit_thread(frame.a1);
+            crate::proc::exit_thread();
+        }
+        SOCKET_NUM => {
+            let Some(kind) = shared::SocketKind::from_num(frame.a1) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::InvalidFormat as u32;
+                return;
+            };
+            let read_timeout_ms = frame.a2;
+            match syscall_socket(kind, read_timeout_ms) {
+                Ok(desc) => frame.a1 = desc as u32,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        BIND_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let desc_num = frame.a1;
+            let Some(addr) = user_mem_ref(frame.a2, frame.a3, &allow) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_bind(desc_num, &addr) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        SEND_TO_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let desc_num = frame.a1;
+            let (Some(buf), Some(addr)) = (
+                user_mem_ref(frame.a2, frame.a3, &allow),
+                user_mem_ref(frame.a4, frame.a5, &allow),
+            ) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_send_to(desc_num, &buf, &addr) {
+                Ok(len) => frame.a1 = len as u32,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        RECV_FROM_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let desc_num = frame.a1;
+            let (Some(mut buf), Some(mut addr)) = (
+                user_mem_mut(frame.a2, frame.a3, &allow),
+                user_mem_mut(frame.a4, frame.a5, &allow),
+            ) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_recv_from(desc_num, &mut buf, &mut addr) {
+                Ok(len) => frame.a1 = len as u32,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        MAP_RESOURCE_NUM => {
+            let desc_num = frame.a1;
+            match syscall_map_resource(desc_num) {
+                Ok(start_user_vaddr) => frame.a1 = start_user_vaddr as u32,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        SYNC_NUM => {
+            let desc_num = frame.a1;
+            match syscall_sync(desc_num) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        CLOCK_GET_TIME_NUM => {
+            let uptime = crate::timer::uptime();
+            frame.a1 = uptime.as_secs() as u32;
+            frame.a2 = uptime.subsec_nanos();
+        }
+        SLEEP_NUM => {
+            let duration = core::time::Duration::from_secs(frame.a1.into())
+                + core::time::Duration::from_nanos(frame.a2.into());
+            match crate::timer::sleep(duration) {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        SEEK_NUM => {
+            let desc_num = frame.a1;
+            let Some(whence) = shared::SeekWhence::from_num(frame.a2) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::InvalidFormat as u32;
+                return;
+            };
+            let offset = (i64::from(frame.a4.cast_signed()) << 32) | i64::from(frame.a3);
+            // The new offset has to fit in the return register, without looking like an error.
+            match syscall_seek(desc_num, whence, offset).and_then(|offset| {
+                u32::try_from(offset)
+                    .ok()
+                    .filter(|&offset| offset != u32::MAX)
+                    .ok_or(ErrorKind::LimitReached.into())
+            }) {
+                Ok(offset) => frame.a1 = offset,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        SPAWN_NUM => {
+            let allow = crate::csr::AllowUserModeMemory::allow();
+            let (Some(path_buf), Some(args_block), Some(stdio)) = (
+                user_mem_ref(frame.a1, frame.a2, &allow),
+                user_mem_ref(frame.a3, frame.a4, &allow),
+                user_mem_ref(frame.a5, 3 * size_of::<u32>() as u32, &allow),
+            ) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::NotPermitted as u32;
+                return;
+            };
+            match syscall_spawn(&path_buf, &args_block, &stdio) {
+                Ok(pid) => frame.a1 = pid,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        WAIT_NUM => {
+            let pid = frame.a1;
+            // Don't allow access to user memory while we might be asleep.
+            let status = crate::proc::wait(pid).and_then(|status| {
+                let allow = crate::csr::AllowUserModeMemory::allow();
+                let mut status_buf = user_mem_mut(frame.a2, size_of::<i32>() as u32, &allow)
+                    .ok_or(ErrorKind::NotPermitted)?;
+                status_buf.copy_from_slice(&status.to_le_bytes());
+                Ok(())
+            });
+            match status {
+                Ok(()) => frame.a1 = 0,
+                Err(e) => {
+                    frame.a1 = -1_i32 as u32;
+                    frame.a2 = e.kind as u32;
+                }
+            }
+        }
+        SHUTDOWN_NUM => {
+            let Some(kind) = shared::ShutdownKind::from_num(frame.a1) else {
+                frame.a1 = -1_i32 as u32;
+                frame.a2 = ErrorKind::InvalidFormat as u32;
+                return;
+            };
+            crate::shutdown(kind, u16::try_from(frame.a2).unwrap_or(u16::MAX));
         }
         number => panic!("Unrecognized syscall {number}"), // TODO don't panic here
     }
 }
 
-fn syscall_open(path_name: &[u8], open_flags: shared::FileOpenFlags) -> Result<usize> {
+/// Get a reference to a buffer in user memory, given the registers holding its address and length.
+///
+/// Returns `None` if the process isn't allowed to read that memory.
+fn user_mem_ref(
+    addr: u32,
+    len: u32,
+    allow: &crate::csr::AllowUserModeMemory,
+) -> Option<UserMemRef<'_>> {
+    let buf = core::ptr::slice_from_raw_parts(
+        core::ptr::with_exposed_provenance::<u8>(addr as usize),
+        len as usize,
+    );
+    // SAFETY:
+    // The buffer is in user-space, so it can't alias anything, and the result borrows from
+    // `allow`, which is dropped when we return from the syscall, so the lifetime isn't too long.
+    unsafe { UserMemRef::for_region(buf, allow) }
+}
+
+/// Get a mutable reference to a buffer in user memory, given the registers holding its address and
+/// length.
+///
+/// Returns `None` if the process isn't allowed to write that memory.
+fn user_mem_mut(
+    addr: u32,
+    len: u32,
+    allow: &crate::csr::AllowUserModeMemory,
+) -> Option<UserMemMut<'_>> {
+    let buf = core::ptr::slice_from_raw_parts_mut(
+        core::ptr::with_exposed_provenance_mut::<u8>(addr as usize),
+        len as usize,
+    );
+    // SAFETY:
+    // The buffer is in user-space, so it can't alias anything, and the result borrows from
+    // `allow`, which is dropped when we return from the syscall, so the lifetime isn't too long.
+    unsafe { UserMemMut::for_region(buf, allow) }
+}
+
+/// Parse a path given by a process, returning it relative to the root directory.
+fn parse_path(path_name: &[u8]) -> Result<&str> {
     let path_name = str::from_utf8(path_name).map_err(|_| ErrorKind::InvalidFormat)?;
     // TODO Support relative paths.
-    let path_name = path_name
+    Ok(path_name
         .strip_prefix('/')
-        .ok_or(ErrorKind::InvalidFormat)?;
+        .ok_or(ErrorKind::InvalidFormat)?)
+}
+
+/// Split a path (as returned by [`parse_path`]) into the inode of its parent directory and the
+/// name of its final component.
+fn lookup_parent<'path>(
+    fs: &mut crate::ext2::Ext2<impl crate::block::BlockDevice>,
+    path_name: &'path str,
+) -> Result<(u32, &'path str)> {
+    let (parent, name) = path_name.rsplit_once('/').unwrap_or(("", path_name));
+    let parent_inode_num = fs.lookup_path(parent.split('/').filter(|part| !part.is_empty()))?;
+    Ok((parent_inode_num, name))
+}
+
+fn syscall_open(path_name: &[u8], open_flags: shared::FileOpenFlags) -> Result<usize> {
+    let path_name = parse_path(path_name)?;
 
-    // SAFETY: We have exclusive access to this thread's running process.
-    let proc = unsafe { crate::proc::current_proc() };
+    let descriptors = crate::proc::current_proc().resource_descriptors;
     // SAFETY: We can get exclusive access to the resource descriptor set.
-    let (desc_num, slot) = unsafe { &mut *proc.resource_descriptors }
+    let (desc_num, slot) = unsafe { &mut *descriptors }
         .iter_mut()
         .enumerate()
         .find(|(_, slot)| slot.is_none())
         .ok_or(ErrorKind::LimitReached)?;
+    if path_name == shared::FRAMEBUFFER_PATH {
+        if crate::DEVICE_TREE.gpu.lock().is_none() {
+            return Err(ErrorKind::NotFound.into());
+        }
+        *slot = Some(ResourceDescriptor::new(
+            ResourceDescription::for_framebuffer(),
+        )?);
+        return Ok(desc_num);
+    }
+    if path_name == shared::KEYBOARD_PATH {
+        if crate::DEVICE_TREE.input.lock().is_none() {
+            return Err(ErrorKind::NotFound.into());
+        }
+        *slot = Some(ResourceDescriptor::new(ResourceDescription::for_keyboard())?);
+        return Ok(desc_num);
+    }
     // Initialize the slot
-    let inode_num = crate::DEVICE_TREE
-        .storage
-        .lock()
-        .as_mut()
-        .unwrap()
-        .lookup_path(path_name.split('/'))
-        .ok_or(ErrorKind::NotFound)?;
+    let (inode_num, offset) = {
+        let mut fs = crate::DEVICE_TREE.storage.lock();
+        let fs = fs.as_mut().unwrap();
+        let inode_num = match fs.lookup_path(path_name.split('/')) {
+            Err(e) if open_flags.create() && matches!(e.kind, ErrorKind::NotFound) => {
+                let (dir_inode_num, name) = lookup_parent(fs, path_name)?;
+                fs.create_file(dir_inode_num, name)?
+            }
+            result => result?,
+        };
+        if open_flags.truncate() && open_flags.write_only() {
+            fs.truncate(inode_num)?;
+        }
+        // Hold the inode open for as long as the resource description exists. It gets released
+        // when the description closes.
+        fs.acquire_inode(inode_num)?;
+        let offset = if open_flags.append() {
+            fs.file_size(inode_num)
+        } else {
+            0
+        };
+        (inode_num, offset)
+    };
     let mut flags = FileFlags::PRESENT;
     if open_flags.read_only() {
         flags = flags.bit_or(FileFlags::READABLE);
@@ -208,35 +662,168 @@ fn syscall_open(path_name: &[u8], open_flags: shared::FileOpenFlags) -> Result<u
     if open_flags.write_only() {
         flags = flags.bit_or(FileFlags::WRITABLE);
     }
+    if open_flags.append() {
+        flags = flags.bit_or(FileFlags::APPEND);
+    }
     *slot = Some(ResourceDescriptor::new(ResourceDescription::for_file(
         crate::resource_desc::FileResourceDescriptionData {
             flags,
-            offset: if open_flags.append() {
-                todo!("Set offset to end of file")
-            } else {
-                0
-            },
             inode_num,
+            offset,
         },
     ))?);
     Ok(desc_num)
 }
 
-fn syscall_read(desc_num: u32, user_buf: &mut [u8]) -> Result<usize> {
-    // SAFETY: We have exclusive access to this thread's running process.
-    let proc = unsafe { crate::proc::current_proc() };
+fn syscall_link(existing_path: &[u8], new_path: &[u8]) -> Result<()> {
+    let existing_path = parse_path(existing_path)?;
+    let new_path = parse_path(new_path)?;
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().unwrap();
+    let inode_num = fs.lookup_path(existing_path.split('/').filter(|part| !part.is_empty()))?;
+    let (dir_inode_num, name) = lookup_parent(fs, new_path.trim_end_matches('/'))?;
+    fs.link(inode_num, dir_inode_num, name)
+}
+
+fn syscall_unlink(path: &[u8]) -> Result<()> {
+    let path = parse_path(path)?;
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().unwrap();
+    let (dir_inode_num, name) = lookup_parent(fs, path)?;
+    fs.unlink(dir_inode_num, name)
+}
+
+fn syscall_statfs(path: &[u8], stats_buf: &mut [u8]) -> Result<()> {
+    if stats_buf.len() != size_of::<shared::FilesystemStats>() {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    let path = parse_path(path)?;
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().unwrap();
+    // Only one filesystem can be mounted, but we still check that the path exists on it.
+    fs.lookup_path(path.split('/').filter(|part| !part.is_empty()))?;
+    #[expect(clippy::cast_ptr_alignment, reason = "Following write is unaligned")]
+    let stats_ptr = stats_buf.as_mut_ptr().cast::<shared::FilesystemStats>();
+    // SAFETY: We checked that the buffer is the right size, and the write is unaligned.
+    unsafe { stats_ptr.write_unaligned(fs.stats()) };
+    Ok(())
+}
+
+fn syscall_stat(path: &[u8], metadata_buf: &mut [u8]) -> Result<()> {
+    if metadata_buf.len() != size_of::<shared::FileMetadata>() {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    let path = parse_path(path)?;
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().unwrap();
+    let inode_num = fs.lookup_path(path.split('/').filter(|part| !part.is_empty()))?;
+    let metadata = fs.metadata(inode_num)?;
+    #[expect(clippy::cast_ptr_alignment, reason = "Following write is unaligned")]
+    let metadata_ptr = metadata_buf.as_mut_ptr().cast::<shared::FileMetadata>();
+    // SAFETY: We checked that the buffer is the right size, and the write is unaligned.
+    unsafe { metadata_ptr.write_unaligned(metadata) };
+    Ok(())
+}
+
+/// Copy the name of the directory entry at `index` into `name_buf`, returning its length.
+///
+/// Names are never empty, so a length of 0 means there are no more entries.
+fn syscall_read_dir(path: &[u8], index: usize, name_buf: &mut [u8]) -> Result<usize> {
+    let path = parse_path(path)?;
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().unwrap();
+    let dir_inode_num = fs.lookup_path(path.split('/').filter(|part| !part.is_empty()))?;
+    Ok(fs
+        .read_directory_entry(dir_inode_num, index, name_buf)?
+        .unwrap_or(0))
+}
+
+fn syscall_create_dir(path: &[u8]) -> Result<()> {
+    let path = parse_path(path)?;
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().unwrap();
+    let (dir_inode_num, name) = lookup_parent(fs, path.trim_end_matches('/'))?;
+    fs.create_dir(dir_inode_num, name)?;
+    Ok(())
+}
+
+fn syscall_remove_dir(path: &[u8]) -> Result<()> {
+    let path = parse_path(path)?;
+    let mut fs = crate::DEVICE_TREE.storage.lock();
+    let fs = fs.as_mut().unwrap();
+    let (dir_inode_num, name) = lookup_parent(fs, path.trim_end_matches('/'))?;
+    fs.remove_dir(dir_inode_num, name)
+}
+
+/// Get the channel that processes waiting on the futex word at `addr` sleep on.
+///
+/// We key futexes on the physical address of the word, so the channel can't collide with the
+/// kernel addresses used by other sleepers.
+fn futex_channel(addr: u32) -> Result<usize> {
+    if !addr.is_multiple_of(4) {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    let physical_addr = crate::page_table::translate(core::ptr::without_provenance(addr as usize))
+        .ok_or(ErrorKind::NotPermitted)?;
+    Ok(physical_addr.0)
+}
+
+fn syscall_futex_wait(addr: u32, expected: u32) -> Result<()> {
+    let channel = futex_channel(addr)?;
+    {
+        // Don't allow access to user memory while we might be asleep.
+        let allow = crate::csr::AllowUserModeMemory::allow();
+        let word =
+            user_mem_ref(addr, size_of::<u32>() as u32, &allow).ok_or(ErrorKind::NotPermitted)?;
+        let mut bytes = [0; size_of::<u32>()];
+        bytes.copy_from_slice(&word);
+        if u32::from_le_bytes(bytes) != expected {
+            // Someone changed the word already, so whatever we'd wait for might have happened.
+            return Ok(());
+        }
+    }
+    crate::proc::sleep(channel);
+    Ok(())
+}
+
+fn syscall_futex_wake(addr: u32, max: u32) -> Result<u32> {
+    Ok(crate::proc::wake(futex_channel(addr)?, max))
+}
+
+/// Clear the word at `exited_addr` (unless it's 0) and wake anyone waiting on it, so they can tell
+/// that the exiting thread is done with its stack.
+fn syscall_exit_thread(exited_addr: u32) -> Result<()> {
+    if exited_addr == 0 {
+        return Ok(());
+    }
+    let channel = futex_channel(exited_addr)?;
+    {
+        let allow = crate::csr::AllowUserModeMemory::allow();
+        let mut word = user_mem_mut(exited_addr, size_of::<u32>() as u32, &allow)
+            .ok_or(ErrorKind::NotPermitted)?;
+        word.copy_from_slice(&0_u32.to_le_bytes());
+    }
+    crate::proc::wake(channel, u32::MAX);
+    Ok(())
+}
+
+fn syscall_read(
+    desc_num: u32,
+    user_buf: &mut [u8],
+    timeout: &crate::timer::Timeout,
+) -> Result<usize> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
     // SAFETY: We can get exclusive access to the resource descriptor set.
-    let desc = unsafe { &mut *proc.resource_descriptors }[desc_num as usize]
+    let desc = unsafe { &mut *descriptors }[desc_num as usize]
         .as_ref()
         .ok_or(ErrorKind::NotFound)?;
-    desc.description().read(user_buf)
+    desc.description().read(user_buf, timeout)
 }
 
 fn syscall_write(desc_num: u32, user_buf: UserMemRef) -> Result<usize> {
-    // SAFETY: We have exclusive access to this thread's running process.
-    let proc = unsafe { crate::proc::current_proc() };
+    let descriptors = crate::proc::current_proc().resource_descriptors;
     // SAFETY: We can get exclusive access to the resource descriptor set.
-    let desc = unsafe { &mut *proc.resource_descriptors }[desc_num as usize]
+    let desc = unsafe { &mut *descriptors }[desc_num as usize]
         .as_ref()
         .ok_or(ErrorKind::NotFound)?;
     desc.description().write(&user_buf)
@@ -245,18 +832,106 @@ fn syscall_write(desc_num: u32, user_buf: UserMemRef) -> Result<usize> {
 fn syscall_mmap(alloc_size: u32) -> Result<usize> {
     let alloc_num_pages = (alloc_size as usize).div_ceil(PAGE_SIZE);
     let current_table = crate::csr::current_page_table().unwrap();
-    let alloc_first_page = crate::alloc::alloc_pages_zeroed(alloc_num_pages).unwrap();
-    // SAFETY: We have exclusive access to this thread's running process.
-    let proc = unsafe { crate::proc::current_proc() };
-    let start_user_vaddr = proc.mmap_head;
-    // Leave a 1-page gap to help user programs avoid overruns.
-    proc.mmap_head += PAGE_SIZE * (alloc_num_pages + 1);
+    let alloc_first_page = crate::alloc::alloc_pages_zeroed(alloc_num_pages)?;
+    let start_user_vaddr = reserve_mmap_range(alloc_num_pages);
     for (paddr, user_vaddr) in (alloc_first_page.addr()..)
         .step_by(PAGE_SIZE)
         .take(alloc_num_pages)
         .zip((start_user_vaddr..).step_by(PAGE_SIZE))
     {
         // SAFETY: We're mapping fresh pages into unused memory in userspace.
+        unsafe {
+            crate::page_table::map_page(
+                current_table,
+                core::ptr::without_provenance_mut(user_vaddr),
+                crate::page_table::PhysicalAddress(paddr),
+                MMAP_PAGE_FLAGS,
+            )
+        }?;
+    }
+    Ok(start_user_vaddr)
+}
+
+/// Reserve the next `num_pages` pages of the current process's address space for `mmap`,
+/// returning the address of the first.
+fn reserve_mmap_range(num_pages: usize) -> usize {
+    // The main thread holds the `mmap_head` shared by all of a process's threads.
+    let mut proc = crate::proc::current_leader();
+    let start_user_vaddr = proc.mmap_head;
+    // Leave a 1-page gap to help user programs avoid overruns.
+    proc.mmap_head += PAGE_SIZE * (num_pages + 1);
+    start_user_vaddr
+}
+
+/// The flags on pages allocated by `mmap`.
+///
+/// Mapped resources don't get [`PageTableFlags::EXECUTABLE`](crate::page_table::PageTableFlags),
+/// which is how `munmap` and `wait` tell that the process owns these pages but not those.
+const MMAP_PAGE_FLAGS: crate::page_table::PageTableFlags =
+    crate::page_table::PageTableFlags::READABLE
+        .bit_or(crate::page_table::PageTableFlags::WRITABLE)
+        .bit_or(crate::page_table::PageTableFlags::EXECUTABLE)
+        .bit_or(crate::page_table::PageTableFlags::USER_ACCESSIBLE);
+
+fn syscall_munmap(alloc_addr: u32, alloc_size: u32) -> Result<()> {
+    let start_user_vaddr = alloc_addr as usize;
+    if !start_user_vaddr.is_multiple_of(PAGE_SIZE) {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    let end_user_vaddr = start_user_vaddr
+        .checked_add((alloc_size as usize).div_ceil(PAGE_SIZE) * PAGE_SIZE)
+        .ok_or(ErrorKind::NotPermitted)?;
+    // The main thread holds the `mmap_head` shared by all of a process's threads.
+    let mmap_head = crate::proc::current_leader().mmap_head;
+    if start_user_vaddr < crate::proc::MMAP_BASE || end_user_vaddr > mmap_head {
+        return Err(ErrorKind::NotPermitted.into());
+    }
+    let current_table = crate::csr::current_page_table().unwrap();
+    for user_vaddr in (start_user_vaddr..end_user_vaddr).step_by(PAGE_SIZE) {
+        // SAFETY:
+        // This is in the process's mapped region, so it's not kernel memory, and the process
+        // promises it's done with it.
+        let unmapped = unsafe {
+            crate::page_table::unmap_page(
+                current_table,
+                core::ptr::without_provenance_mut(user_vaddr),
+            )
+        };
+        if let Some((paddr, flags)) = unmapped
+            && flags == MMAP_PAGE_FLAGS | crate::page_table::PageTableFlags::VALID
+        {
+            // SAFETY:
+            // `mmap` allocated this page for this process alone, and it's no longer mapped.
+            unsafe {
+                crate::alloc::free_pages(core::ptr::with_exposed_provenance_mut(paddr.0), 1);
+            }
+        }
+    }
+    // SAFETY: Flushing the TLB so the unmapped pages can't be used anymore.
+    unsafe { core::arch::asm!("sfence.vma") };
+    Ok(())
+}
+
+fn syscall_map_resource(desc_num: u32) -> Result<usize> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    let (memory, memory_len) = unsafe { &*descriptors }
+        .get(desc_num as usize)
+        .and_then(Option::as_ref)
+        .ok_or(ErrorKind::NotFound)?
+        .description()
+        .memory()?;
+    let num_pages = memory_len.div_ceil(PAGE_SIZE);
+    let current_table = crate::csr::current_page_table().unwrap();
+    let start_user_vaddr = reserve_mmap_range(num_pages);
+    for (paddr, user_vaddr) in (memory.0..)
+        .step_by(PAGE_SIZE)
+        .take(num_pages)
+        .zip((start_user_vaddr..).step_by(PAGE_SIZE))
+    {
+        // SAFETY:
+        // We're mapping memory the resource shares with processes into unused memory in
+        // userspace.
         unsafe {
             crate::page_table::map_page(
                 current_table,
@@ -264,11 +939,214 @@ fn syscall_mmap(alloc_size: u32) -> Result<usize> {
                 crate::page_table::PhysicalAddress(paddr),
                 crate::page_table::PageTableFlags::READABLE
                     | crate::page_table::PageTableFlags::WRITABLE
-                    | crate::page_table::PageTableFlags::EXECUTABLE
                     | crate::page_table::PageTableFlags::USER_ACCESSIBLE,
             )
         }?;
-        // NOTE: This memory gets leaked, we should track the maps somewhere to clean them up.
     }
     Ok(start_user_vaddr)
 }
+
+fn syscall_sync(desc_num: u32) -> Result<()> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    unsafe { &*descriptors }
+        .get(desc_num as usize)
+        .and_then(Option::as_ref)
+        .ok_or(ErrorKind::NotFound)?
+        .description()
+        .sync()
+}
+
+fn syscall_spawn(path_name: &[u8], args_block: &[u8], stdio_nums: &[u8]) -> Result<u32> {
+    let path_name = parse_path(path_name)?;
+
+    // The child gets its own copies of the given descriptors.
+    let stdio = {
+        let descriptors = crate::proc::current_proc().resource_descriptors;
+        // SAFETY: We can get exclusive access to the resource descriptor set.
+        let descriptors = unsafe { &*descriptors };
+        let mut copies = [const { None }; 3];
+        for (copy, num) in copies.iter_mut().zip(stdio_nums.as_chunks::<4>().0) {
+            *copy = descriptors
+                .get(u32::from_le_bytes(*num) as usize)
+                .and_then(Option::clone);
+        }
+        let [Some(stdin), Some(stdout), Some(stderr)] = copies else {
+            return Err(ErrorKind::NotFound.into());
+        };
+        [stdin, stdout, stderr]
+    };
+
+    let image = crate::proc::load_image(path_name)?;
+    crate::proc::Process::spawn(&image, args_block, stdio)
+}
+
+fn syscall_seek(desc_num: u32, whence: shared::SeekWhence, offset: i64) -> Result<u64> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    unsafe { &*descriptors }
+        .get(desc_num as usize)
+        .and_then(Option::as_ref)
+        .ok_or(ErrorKind::NotFound)?
+        .description()
+        .seek(whence, offset)
+}
+
+fn syscall_dup(desc_num: u32) -> Result<usize> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    let descriptors = unsafe { &mut *descriptors };
+    let desc = descriptors
+        .get(desc_num as usize)
+        .and_then(Option::as_ref)
+        .ok_or(ErrorKind::NotFound)?
+        .clone();
+    let (new_desc_num, slot) = descriptors
+        .iter_mut()
+        .enumerate()
+        .find(|(_, slot)| slot.is_none())
+        .ok_or(ErrorKind::LimitReached)?;
+    *slot = Some(desc);
+    Ok(new_desc_num)
+}
+
+fn syscall_dup2(desc_num: u32, new_desc_num: u32) -> Result<()> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    let descriptors = unsafe { &mut *descriptors };
+    let desc = descriptors
+        .get(desc_num as usize)
+        .and_then(Option::as_ref)
+        .ok_or(ErrorKind::NotFound)?
+        .clone();
+    let slot = descriptors
+        .get_mut(new_desc_num as usize)
+        .ok_or(ErrorKind::LimitReached)?;
+    // Dropping the old descriptor closes it, if it was open.
+    *slot = Some(desc);
+    Ok(())
+}
+
+/// Create a pipe, writing the numbers of the descriptors for its read and write ends to
+/// `descs_buf` as two little-endian `u32`s.
+fn syscall_pipe(descs_buf: &mut [u8]) -> Result<()> {
+    let descs_buf: &mut [u8; 8] = descs_buf.try_into().map_err(|_| ErrorKind::InvalidFormat)?;
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    let descriptors = unsafe { &mut *descriptors };
+    let mut free_slots = descriptors
+        .iter()
+        .enumerate()
+        .filter(|(_, slot)| slot.is_none())
+        .map(|(desc_num, _)| desc_num);
+    let (Some(reader_num), Some(writer_num)) = (free_slots.next(), free_slots.next()) else {
+        return Err(ErrorKind::LimitReached.into());
+    };
+    let pipe = crate::pipe::new()?;
+    let writer = ResourceDescriptor::new(ResourceDescription::for_pipe_writer(pipe.clone()))?;
+    let reader = ResourceDescriptor::new(ResourceDescription::for_pipe_reader(pipe))?;
+    descriptors[reader_num] = Some(reader);
+    descriptors[writer_num] = Some(writer);
+    descs_buf[..4].copy_from_slice(&(reader_num as u32).to_le_bytes());
+    descs_buf[4..].copy_from_slice(&(writer_num as u32).to_le_bytes());
+    Ok(())
+}
+
+fn syscall_socket(kind: shared::SocketKind, read_timeout_ms: u32) -> Result<usize> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    let (desc_num, slot) = unsafe { &mut *descriptors }
+        .iter_mut()
+        .enumerate()
+        .find(|(_, slot)| slot.is_none())
+        .ok_or(ErrorKind::LimitReached)?;
+    let mut net = crate::DEVICE_TREE.network.lock();
+    let net = net.as_mut().ok_or(ErrorKind::Unsupported)?;
+    let socket_handle = net.open_socket(kind, read_timeout_ms)?;
+    match ResourceDescriptor::new(ResourceDescription::for_socket(socket_handle)) {
+        Ok(desc) => *slot = Some(desc),
+        Err(e) => {
+            net.close_socket(socket_handle);
+            return Err(e.into());
+        }
+    }
+    Ok(desc_num)
+}
+
+fn syscall_bind(desc_num: u32, addr: &[u8]) -> Result<()> {
+    let socket_handle = socket_for_descriptor(desc_num)?;
+    let addr = parse_socket_addr(addr)?;
+    crate::DEVICE_TREE
+        .network
+        .lock()
+        .as_mut()
+        .ok_or(ErrorKind::Unsupported)?
+        .bind(socket_handle, addr)
+}
+
+fn syscall_send_to(desc_num: u32, buf: &[u8], addr: &[u8]) -> Result<usize> {
+    let socket_handle = socket_for_descriptor(desc_num)?;
+    let addr = parse_socket_addr(addr)?;
+    crate::DEVICE_TREE
+        .network
+        .lock()
+        .as_mut()
+        .ok_or(ErrorKind::Unsupported)?
+        .send_to(socket_handle, buf, addr)
+}
+
+fn syscall_recv_from(desc_num: u32, buf: &mut [u8], addr_buf: &mut [u8]) -> Result<usize> {
+    if addr_buf.len() != size_of::<shared::SocketAddrV4>() {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    let socket_handle = socket_for_descriptor(desc_num)?;
+    let mut timeout = None;
+    loop {
+        {
+            let mut net = crate::DEVICE_TREE.network.lock();
+            let net = net.as_mut().ok_or(ErrorKind::Unsupported)?;
+            if let Some((len, from)) = net.try_recv_from(socket_handle, buf)? {
+                #[expect(clippy::cast_ptr_alignment, reason = "Following write is unaligned")]
+                let addr_ptr = addr_buf.as_mut_ptr().cast::<shared::SocketAddrV4>();
+                // SAFETY: We checked that the buffer is the right size, and the write is unaligned.
+                unsafe { addr_ptr.write_unaligned(from) };
+                return Ok(len);
+            }
+            let timeout = match &timeout {
+                Some(timeout) => timeout,
+                None => timeout.insert(crate::timer::Timeout::after_ms(
+                    net.read_timeout_ms(socket_handle)?,
+                )),
+            };
+            if timeout.expired() {
+                return Err(ErrorKind::TimedOut.into());
+            }
+        }
+        // Let other processes run while we wait for something to arrive.
+        crate::proc::sched_yield();
+    }
+}
+
+/// Get the handle of the socket a resource descriptor points at.
+fn socket_for_descriptor(desc_num: u32) -> Result<usize> {
+    let descriptors = crate::proc::current_proc().resource_descriptors;
+    // SAFETY: We can get exclusive access to the resource descriptor set.
+    let desc = unsafe { &*descriptors }
+        .get(desc_num as usize)
+        .and_then(Option::as_ref)
+        .ok_or(ErrorKind::NotFound)?;
+    Ok(desc.description().socket().ok_or(ErrorKind::Unsupported)?)
+}
+
+/// Parse a socket address given by a process.
+fn parse_socket_addr(addr: &[u8]) -> Result<shared::SocketAddrV4> {
+    if addr.len() != size_of::<shared::SocketAddrV4>() {
+        return Err(ErrorKind::InvalidFormat.into());
+    }
+    #[expect(clippy::cast_ptr_alignment, reason = "Following read is unaligned")]
+    let addr_ptr = addr.as_ptr().cast::<shared::SocketAddrV4>();
+    // SAFETY:
+    // We checked that the buffer is the right size, every bit pattern is a valid address, and the
+    // read is unaligned.
+    Ok(unsafe { addr_ptr.read_unaligned() })
+}
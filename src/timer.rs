@@ -0,0 +1,212 @@
+//! Keeping track of time.
+//!
+//! This drives the scheduling tick, which fires at a fixed interval, and runs software timers once
+//! their deadlines pass. Time is measured in the units of the `time` CSR.
+
+use core::time::Duration;
+
+use shared::ErrorKind;
+
+use crate::{error::Result, sync::KSpinLock};
+
+/// The rate the `time` CSR counts at, per second.
+///
+/// This is the timebase of QEMU's `virt` machine.
+pub const TICKS_PER_SECOND: u64 = 10_000_000;
+
+/// How often the scheduling tick fires.
+const TICK_INTERVAL: u64 = TICKS_PER_SECOND / 100;
+
+/// The most software timers which can be pending at once.
+const MAX_TIMERS: usize = 16;
+
+/// The bit in `sie` which enables supervisor timer interrupts.
+const SIE_STIE: u32 = 1 << 5;
+
+/// The state of the timers.
+static TIMERS: KSpinLock<Timers> = KSpinLock::new(Timers {
+    next_tick: 0,
+    pending: [None; MAX_TIMERS],
+});
+
+/// The state of the timers.
+struct Timers {
+    /// When the next scheduling tick is due.
+    next_tick: u64,
+    /// The pending software timers, sorted by deadline with the soonest first.
+    ///
+    /// All the `Some` entries come before all the `None` entries.
+    pending: [Option<SoftwareTimer>; MAX_TIMERS],
+}
+impl Timers {
+    /// Tell the SBI to interrupt us at the next tick or timer deadline, whichever is sooner.
+    fn schedule_interrupt(&self) {
+        let deadline = match self.pending[0] {
+            Some(timer) => timer.deadline.min(self.next_tick),
+            None => self.next_tick,
+        };
+        if let Err(e) = crate::sbi::set_timer(deadline) {
+            log::error!("Failed to set timer: {e:?}");
+        }
+    }
+}
+
+/// A callback to run once a deadline passes.
+#[derive(Clone, Copy)]
+struct SoftwareTimer {
+    /// The value of the `time` CSR after which to run the callback.
+    deadline: u64,
+    /// The function to call.
+    callback: fn(usize),
+    /// The value to pass to `callback`.
+    context: usize,
+}
+
+/// Start the scheduling tick and enable timer interrupts.
+///
+/// This should only be called once.
+pub fn init() {
+    {
+        let mut timers = TIMERS.lock();
+        timers.next_tick = now() + TICK_INTERVAL;
+        timers.schedule_interrupt();
+    }
+    let sie = crate::csr::read_csr!(sie);
+    // SAFETY: Enabling timer interrupts is fine, since we handle them.
+    unsafe { crate::csr::write_csr!(sie = sie | SIE_STIE) };
+}
+
+/// Get the current value of the `time` CSR.
+pub fn now() -> u64 {
+    // The counter is split across two CSRs, so we retry if the upper half changes while we read.
+    loop {
+        let high = crate::csr::read_csr!(timeh);
+        let low = crate::csr::read_csr!(time);
+        if crate::csr::read_csr!(timeh) == high {
+            return (u64::from(high) << 32) | u64::from(low);
+        }
+    }
+}
+
+/// Get how long it's been since the machine started.
+pub fn uptime() -> Duration {
+    let now = now();
+    Duration::new(
+        now / TICKS_PER_SECOND,
+        ((now % TICKS_PER_SECOND) * 1_000_000_000 / TICKS_PER_SECOND) as u32,
+    )
+}
+
+/// Convert a duration to a number of `time` CSR units, saturating if it's too long.
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos() * u128::from(TICKS_PER_SECOND) / 1_000_000_000)
+        .unwrap_or(u64::MAX)
+}
+
+/// Get the value of the `time` CSR once `duration` has passed.
+pub fn deadline_after(duration: Duration) -> u64 {
+    now().saturating_add(duration_to_ticks(duration))
+}
+
+/// Call `callback(context)` once the `time` CSR reaches `deadline`.
+///
+/// The callback runs while handling an interrupt, so it shouldn't block.
+pub fn add_timer(deadline: u64, callback: fn(usize), context: usize) -> Result<()> {
+    let mut timers = TIMERS.lock();
+    let idx = timers
+        .pending
+        .iter()
+        .position(|timer| timer.is_none_or(|timer| timer.deadline > deadline))
+        .unwrap_or(MAX_TIMERS);
+    if timers.pending[MAX_TIMERS - 1].is_some() {
+        return Err(ErrorKind::LimitReached.into());
+    }
+    // Shift the later timers back to keep the list sorted.
+    timers.pending[idx..].rotate_right(1);
+    timers.pending[idx] = Some(SoftwareTimer {
+        deadline,
+        callback,
+        context,
+    });
+    if idx == 0 {
+        timers.schedule_interrupt();
+    }
+    Ok(())
+}
+
+/// Block the current process until the `time` CSR reaches `deadline`.
+pub fn sleep_until(deadline: u64) -> Result<()> {
+    // The address of a local variable is unique among sleeping processes, so it makes a good
+    // channel.
+    let channel = core::ptr::from_ref(&deadline).addr();
+    add_timer(deadline, crate::proc::wake_all, channel)?;
+    while now() < deadline {
+        crate::proc::sleep(channel);
+    }
+    Ok(())
+}
+
+/// Block the current process until `duration` has passed.
+pub fn sleep(duration: Duration) -> Result<()> {
+    sleep_until(deadline_after(duration))
+}
+
+/// Handle a timer interrupt, running any timers whose deadlines have passed.
+///
+/// Returns whether a scheduling tick passed, in which case the current process should yield. This
+/// works even if no interrupt is pending, so the idle loop can use it after waking up.
+pub fn handle_interrupt() -> bool {
+    let now = now();
+    let mut timers = TIMERS.lock();
+    let ticked = now >= timers.next_tick;
+    if ticked {
+        // If we fell behind, skip the ticks we missed rather than firing them all at once.
+        let missed = (now - timers.next_tick) / TICK_INTERVAL;
+        timers.next_tick += (missed + 1) * TICK_INTERVAL;
+    }
+    while let Some(timer) = timers.pending[0].filter(|timer| timer.deadline <= now) {
+        timers.pending[0] = None;
+        timers.pending.rotate_left(1);
+        (timer.callback)(timer.context);
+    }
+    timers.schedule_interrupt();
+    ticked
+}
+
+/// A point in time after which an operation should give up.
+pub struct Timeout {
+    /// The value of the `time` CSR at which we give up, or `None` to wait forever.
+    deadline: Option<u64>,
+}
+impl Timeout {
+    /// A timeout which never expires.
+    pub const NEVER: Self = Self { deadline: None };
+
+    /// Create a timeout which expires after `duration`.
+    ///
+    /// Unlike [`Self::after_ms`], a duration of zero means the timeout has already expired.
+    pub fn after(duration: Duration) -> Self {
+        Self {
+            deadline: Some(deadline_after(duration)),
+        }
+    }
+
+    /// Create a timeout which expires after the given number of milliseconds.
+    ///
+    /// A duration of zero means the timeout never expires.
+    pub fn after_ms(ms: u32) -> Self {
+        Self {
+            deadline: (ms != 0).then(|| deadline_after(Duration::from_millis(ms.into()))),
+        }
+    }
+
+    /// Check whether the timeout has passed.
+    pub fn expired(&self) -> bool {
+        self.deadline.is_some_and(|deadline| now() >= deadline)
+    }
+
+    /// Check whether the timeout ever expires.
+    pub fn is_never(&self) -> bool {
+        self.deadline.is_none()
+    }
+}
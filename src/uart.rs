@@ -0,0 +1,189 @@
+//! A driver for the NS16550-compatible UART on QEMU's `virt` machine.
+//!
+//! This lets the console work without relying on the SBI's legacy console calls. Output is always
+//! polled, while input can either be polled or buffered by an interrupt handler.
+//!
+//! Designed according to the datasheet at <https://www.ti.com/lit/ds/symlink/pc16550d.pdf>.
+
+/// The address of the UART's registers.
+pub const UART_ADDRESS: usize = 0x1000_0000;
+/// The PLIC interrupt source the UART is wired to.
+const UART_IRQ: u32 = 10;
+
+/// The offset of the receive buffer register (when reading) and transmit holding register (when
+/// writing).
+const DATA_OFFSET: usize = 0;
+/// The offset of the interrupt enable register.
+const INTERRUPT_ENABLE_OFFSET: usize = 1;
+/// The offset of the FIFO control register.
+const FIFO_CONTROL_OFFSET: usize = 2;
+/// The offset of the line control register.
+const LINE_CONTROL_OFFSET: usize = 3;
+/// The offset of the line status register.
+const LINE_STATUS_OFFSET: usize = 5;
+/// The offset of the scratch register, which does nothing but hold a value.
+const SCRATCH_OFFSET: usize = 7;
+
+/// The interrupt enable bit for "received data available".
+const INTERRUPT_ENABLE_RECEIVED: u8 = 1 << 0;
+/// The FIFO control bits to enable and clear both FIFOs.
+const FIFO_ENABLE_AND_CLEAR: u8 = 0b111;
+/// The line control setting for 8 data bits, no parity, and one stop bit.
+const LINE_CONTROL_8N1: u8 = 0b11;
+/// The line status bit which is set while there's received data to read.
+const LINE_STATUS_DATA_READY: u8 = 1 << 0;
+/// The line status bit which is set while the transmitter can accept another byte.
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 1 << 5;
+
+/// How many received bytes we buffer in interrupt-driven mode.
+const RECEIVE_BUFFER_LEN: usize = 256;
+
+/// How the UART notices received data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Check the line status whenever someone reads.
+    #[expect(dead_code, reason = "Only used if selected with `UART_MODE`")]
+    Polled,
+    /// Buffer data as the UART interrupts, and sleep while waiting for more.
+    Interrupt,
+}
+
+/// A driver for an NS16550 UART.
+pub struct Ns16550 {
+    /// The address of the UART's registers.
+    address: usize,
+    /// How we receive data.
+    mode: Mode,
+    /// Data received by the interrupt handler which hasn't been read yet, as a ring buffer.
+    received: [u8; RECEIVE_BUFFER_LEN],
+    /// The index in `received` of the oldest unread byte.
+    received_start: usize,
+    /// The number of unread bytes in `received`.
+    received_len: usize,
+}
+impl Ns16550 {
+    /// Initialize the UART at [`UART_ADDRESS`], if there is one.
+    ///
+    /// In [`Mode::Interrupt`], this also registers the interrupt handler, which reads the driver
+    /// out of the [`DEVICE_TREE`](crate::DEVICE_TREE), so the driver should be put there.
+    ///
+    /// # Safety
+    /// Nothing else may be using the UART.
+    pub unsafe fn init(mode: Mode) -> Option<Self> {
+        let mut this = Self {
+            address: UART_ADDRESS,
+            mode,
+            received: [0; RECEIVE_BUFFER_LEN],
+            received_start: 0,
+            received_len: 0,
+        };
+        // Check a UART is there, by seeing whether the scratch register remembers what we write.
+        for probe in [0x5A, 0xA5] {
+            this.write_register(SCRATCH_OFFSET, probe);
+            if this.read_register(SCRATCH_OFFSET) != probe {
+                return None;
+            }
+        }
+        // QEMU ignores the baud rate, so we leave the divisor alone.
+        this.write_register(LINE_CONTROL_OFFSET, LINE_CONTROL_8N1);
+        this.write_register(FIFO_CONTROL_OFFSET, FIFO_ENABLE_AND_CLEAR);
+        match mode {
+            Mode::Polled => this.write_register(INTERRUPT_ENABLE_OFFSET, 0),
+            Mode::Interrupt => {
+                crate::plic::enable(UART_IRQ, handle_interrupt, UART_ADDRESS);
+                this.write_register(INTERRUPT_ENABLE_OFFSET, INTERRUPT_ENABLE_RECEIVED);
+            }
+        }
+        Some(this)
+    }
+
+    /// Write all of `data`, waiting for the transmitter as needed.
+    pub fn write(&mut self, data: &[u8]) {
+        for &byte in data {
+            while self.read_register(LINE_STATUS_OFFSET) & LINE_STATUS_TRANSMIT_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            self.write_register(DATA_OFFSET, byte);
+        }
+    }
+
+    /// Read whatever data is available into `buf`, without waiting.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        match self.mode {
+            Mode::Polled => {
+                let mut len = 0;
+                while len < buf.len()
+                    && let Some(byte) = self.receive_byte()
+                {
+                    buf[len] = byte;
+                    len += 1;
+                }
+                len
+            }
+            Mode::Interrupt => {
+                let len = buf.len().min(self.received_len);
+                for (i, slot) in buf[..len].iter_mut().enumerate() {
+                    *slot = self.received[(self.received_start + i) % RECEIVE_BUFFER_LEN];
+                }
+                self.received_start = (self.received_start + len) % RECEIVE_BUFFER_LEN;
+                self.received_len -= len;
+                len
+            }
+        }
+    }
+
+    /// Get whether reads should sleep until the UART interrupts, rather than spinning.
+    pub fn uses_interrupts(&self) -> bool {
+        self.mode == Mode::Interrupt && crate::proc::in_process()
+    }
+
+    /// The address processes waiting for input sleep on.
+    pub fn wait_channel(&self) -> usize {
+        self.address
+    }
+
+    /// Take a byte out of the UART's receive FIFO, if there is one.
+    fn receive_byte(&mut self) -> Option<u8> {
+        (self.read_register(LINE_STATUS_OFFSET) & LINE_STATUS_DATA_READY != 0)
+            .then(|| self.read_register(DATA_OFFSET))
+    }
+
+    /// Move everything in the UART's receive FIFO into our buffer.
+    ///
+    /// If our buffer is full, the oldest data gets dropped.
+    fn drain_receive_fifo(&mut self) {
+        while let Some(byte) = self.receive_byte() {
+            if self.received_len == RECEIVE_BUFFER_LEN {
+                self.received_start = (self.received_start + 1) % RECEIVE_BUFFER_LEN;
+                self.received_len -= 1;
+            }
+            self.received[(self.received_start + self.received_len) % RECEIVE_BUFFER_LEN] = byte;
+            self.received_len += 1;
+        }
+    }
+
+    /// Read the register at the given offset.
+    fn read_register(&self, offset: usize) -> u8 {
+        // SAFETY: The UART's registers are valid, and we own the UART.
+        unsafe { core::ptr::with_exposed_provenance::<u8>(self.address + offset).read_volatile() }
+    }
+
+    /// Write the register at the given offset.
+    fn write_register(&mut self, offset: usize, value: u8) {
+        // SAFETY: The UART's registers are valid, and we own the UART.
+        unsafe {
+            core::ptr::with_exposed_provenance_mut::<u8>(self.address + offset)
+                .write_volatile(value);
+        }
+    }
+}
+
+/// Handle an interrupt from the UART at `address`, buffering received data and waking readers.
+fn handle_interrupt(address: usize) {
+    if let Some(uart) = crate::DEVICE_TREE.uart.lock().as_mut() {
+        uart.drain_receive_fifo();
+    }
+    crate::proc::wake_all(address);
+}
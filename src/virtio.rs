@@ -1,267 +1,1167 @@
-//! A virtio block device driver for handling storage.
+//! Drivers for virtio devices.
 //!
 //! Designed according to the spec from
 //! <https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.pdf>.
 
+mod block_queue;
+mod chain;
 mod reg;
 
-use core::{marker::PhantomData, mem::MaybeUninit, ptr::NonNull};
+use core::{marker::PhantomData, ptr::NonNull};
 
-use crate::error::{ErrorKind, Result};
+pub use block_queue::BlockQueue;
+use chain::DescriptorChain;
+use util::mmio::{self, Readable, Writable};
 
-/// The address for the block device.
-pub(crate) const BLOCK_DEVICE_ADDRESS: usize = 0x1000_1000;
+use crate::{
+    alloc::DmaBuffer,
+    block::{BLOCK_SECTOR_LEN, BlockDevice},
+    error::{ErrorKind, Result},
+};
 
-/// The address for the block device.
-pub(crate) const RNG_DEVICE_ADDRESS: usize = 0x1000_2000;
+/// Handle an interrupt from the virtio device at `address`.
+///
+/// This acknowledges the interrupt and wakes any processes waiting on the device. It doesn't touch
+/// the queues, so it's fine to call while a driver is in use.
+pub fn handle_interrupt(address: usize) {
+    let regs = core::ptr::with_exposed_provenance_mut(address);
+    // SAFETY:
+    // The interrupt came from a device at this address. Drivers don't use these registers
+    // otherwise, so we don't interfere with them.
+    unsafe {
+        let status = read_register_at(regs, reg::InterruptStatus);
+        write_register_at(regs, reg::InterruptAck, status);
+    }
+    crate::proc::wake_all(address);
+}
+
+/// The types of virtio device we have drivers for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Network,
+    Block,
+    Console,
+    Entropy,
+    Gpu,
+    Input,
+}
+impl DeviceKind {
+    /// Get the kind of device with the given virtio device ID, if we support it.
+    fn from_id(device_id: u32) -> Option<Self> {
+        Some(match device_id {
+            1 => Self::Network,
+            2 => Self::Block,
+            3 => Self::Console,
+            4 => Self::Entropy,
+            16 => Self::Gpu,
+            18 => Self::Input,
+            _ => return None,
+        })
+    }
+
+    /// Get the virtio device ID for this kind of device.
+    const fn id(self) -> u32 {
+        match self {
+            Self::Network => 1,
+            Self::Block => 2,
+            Self::Console => 3,
+            Self::Entropy => 4,
+            Self::Gpu => 16,
+            Self::Input => 18,
+        }
+    }
+}
 
-/// The address for the block device.
-pub(crate) const CONSOLE_DEVICE_ADDRESS: usize = 0x1000_3000;
+/// Find the supported devices among the virtio-mmio slots in the device tree.
+///
+/// Returns each device and its kind, in slot order. Empty slots and devices we don't have drivers
+/// for are skipped.
+///
+/// # Safety
+/// This reads the registers of every slot, so requires nothing else is using them.
+pub unsafe fn scan_bus() -> impl Iterator<Item = (crate::fdt::MmioDevice, DeviceKind)> {
+    crate::fdt::BOOT_INFO.virtio_devices().filter_map(|device| {
+        let address = device.address;
+        let regs = core::ptr::with_exposed_provenance_mut(address);
+        // SAFETY: By method precondition, we can read these registers.
+        let (magic, version, device_id) = unsafe {
+            (
+                read_register_at(regs, reg::Magic),
+                read_register_at(regs, reg::Version),
+                read_register_at(regs, reg::DeviceId),
+            )
+        };
+        if magic != 0x7472_6976 || !matches!(version, 1 | 2) {
+            log::warn!("Slot at {address:#X} isn't a virtio-mmio device we support");
+            return None;
+        }
+        // Empty slots have a device ID of zero.
+        if device_id == 0 {
+            return None;
+        }
+        let kind = DeviceKind::from_id(device_id);
+        match kind {
+            Some(kind) => log::info!("Found virtio {kind:?} device at {address:#X}"),
+            None => log::info!("Ignoring unsupported virtio device {device_id} at {address:#X}"),
+        }
+        Some((device, kind?))
+    })
+}
 
 /// A driver controlling a virtio block device.
+///
+/// Several requests can be in flight at once. Each one is started with a method like
+/// [`Self::start_read`], which returns a [`BlockToken`] to wait for it to finish with.
 pub struct VirtioBlock<'a> {
     /// The underlying virtio implementation.
     virtio: Virtio<'a, 1>,
+    /// The memory for the requests which can be in flight, one per slot.
+    ///
+    /// The device can modify this memory, so we only access it through [`Self::request`].
+    requests: DmaBuffer,
+    /// The state of the request in each slot.
+    slots: [BlockSlot; MAX_BLOCK_REQUESTS],
+    /// The number of slots we use, which is limited by the size of the queue.
+    num_slots: usize,
+    /// The number of entries in the request queue.
+    queue_size: u16,
 }
 impl VirtioBlock<'_> {
-    /// Initialize at the address the device appears at in kernel memory.
+    /// The number of descriptors each request uses.
+    const DESCRIPTORS_PER_REQUEST: u16 = 3;
+    /// The optional features we know how to use, if the device offers them.
+    ///
+    /// We don't support read-only devices, but accept the feature so we can tell when a device is
+    /// one.
+    const WANTED_FEATURES: reg::DeviceFeatureFlags = reg::DeviceFeatureFlags::READ_ONLY
+        .bit_or(reg::DeviceFeatureFlags::BLOCK_SIZE)
+        .bit_or(reg::DeviceFeatureFlags::FLUSH)
+        .bit_or(reg::DeviceFeatureFlags::DISCARD)
+        .bit_or(reg::DeviceFeatureFlags::WRITE_ZEROS);
+
+    /// Initialize the device at the given address in kernel memory.
     ///
     /// # Safety
     /// This takes ownership over a device at the given address, so requires nothing else access
     /// this memory.
-    pub unsafe fn init_kernel_address() -> Result<Self> {
+    pub unsafe fn init_at_address(address: usize) -> Result<Self> {
         log::info!("Initializing virtio block device");
         // SAFETY: By method precondition, we can take ownership of this memory.
         let mut virtio = unsafe {
-            Virtio::init_for_pointers(core::ptr::with_exposed_provenance_mut(BLOCK_DEVICE_ADDRESS))
-        };
-        if virtio.read_register(reg::DeviceId) != 2 {
-            // It wasn't a block device we know about.
+            Virtio::init_for_pointers(
+                core::ptr::with_exposed_provenance_mut(address),
+                DeviceKind::Block,
+                Self::WANTED_FEATURES,
+            )
+        }?;
+        if virtio.features().read_only() {
+            log::error!("Read-only block devices aren't supported");
             return Err(ErrorKind::Unsupported.into());
         }
-        // SAFETY: Newly-allocated memory can get exclusive access.
-        let queue = unsafe {
-            &mut *crate::alloc::alloc_pages_zeroed(
-                size_of::<VirtQueue>().div_ceil(crate::page_table::PAGE_SIZE),
-            )?
-            .cast::<MaybeUninit<VirtQueue>>()
+        let queue_size = virtio.initialize_queue(0, Self::DESCRIPTORS_PER_REQUEST)?;
+        let num_slots =
+            usize::from(queue_size / Self::DESCRIPTORS_PER_REQUEST).min(MAX_BLOCK_REQUESTS);
+        let this = Self {
+            virtio,
+            requests: DmaBuffer::new_zeroed(MAX_BLOCK_REQUESTS * size_of::<BlockRequest>())?,
+            slots: [BlockSlot::Free; MAX_BLOCK_REQUESTS],
+            num_slots,
+            queue_size,
         };
-        virtio.initialize_queue(0, queue);
-        Ok(Self { virtio })
+        log::info!(
+            "virtio block device has {} sectors, in blocks of {} bytes",
+            this.capacity(),
+            this.block_size(),
+        );
+        Ok(this)
     }
 
-    /// Send the request to the disk and wait for a response.
-    fn do_request(&mut self, request: &mut BlockRequest) {
-        // Each descriptor can only be read-only or write-only, so we need to split into multiple
-        // parts.
-        let desc = self.virtio.queues[0]
-            .unwrap()
-            .as_ptr()
-            .wrapping_byte_add(core::mem::offset_of!(VirtQueue, descriptor))
-            .cast::<VirtQueueDescriptor>();
-        // Descriptor 0: Device-read-only header
-        // SAFETY: We have exclusive access to the queue, so we can write to it.
-        unsafe {
-            desc.write_volatile(VirtQueueDescriptor {
-                address: core::ptr::from_mut(request).addr() as u64,
-                length: core::mem::offset_of!(BlockRequest, data) as u32,
-                flags: DescriptorFlags::NEXT,
-                next: 1,
-            });
+    /// Get the size of the device's blocks, which accesses should be aligned to for performance.
+    ///
+    /// This doesn't change the sector size requests use, which is always [`BLOCK_SECTOR_LEN`].
+    pub fn block_size(&self) -> u32 {
+        if self.virtio.features().block_size() {
+            self.virtio.read_register(reg::BlockSize)
+        } else {
+            BLOCK_SECTOR_LEN as u32
+        }
+    }
+
+    /// Start reading a sector, without waiting for it to finish.
+    ///
+    /// Fails if too many requests are already in flight. Pass the returned token to
+    /// [`Self::wait_read`] to get the data.
+    pub fn start_read(&mut self, sector: u64) -> Result<BlockToken> {
+        log::trace!("Reading sector {sector} from virtio block device");
+        self.start_request(BlockRequestType::Read, sector, None)
+    }
+
+    /// Start writing a sector, without waiting for it to finish.
+    ///
+    /// Fails if too many requests are already in flight. Pass the returned token to
+    /// [`Self::wait_write`] to check that it succeeded.
+    pub fn start_write(
+        &mut self,
+        data: &[u8; BLOCK_SECTOR_LEN],
+        sector: u64,
+    ) -> Result<BlockToken> {
+        log::trace!("Writing sector {sector} to virtio block device");
+        self.start_request(BlockRequestType::Write, sector, Some(data))
+    }
+
+    /// Wait for the read for `token` to finish, and copy the sector into `buf`.
+    pub fn wait_read(&mut self, token: BlockToken, buf: &mut [u8; BLOCK_SECTOR_LEN]) -> Result<()> {
+        let request = self.wait_for(token);
+        request.status.success()?;
+        buf.copy_from_slice(&request.data);
+        Ok(())
+    }
+
+    /// Wait for the write for `token` to finish.
+    pub fn wait_write(&mut self, token: BlockToken) -> Result<()> {
+        self.wait_for(token).status.success()
+    }
+
+    /// Run a discard or write-zeroes request over the given sectors, split into requests of at
+    /// most `max_sectors` each.
+    fn run_segments(
+        &mut self,
+        ty: BlockRequestType,
+        start_sector: u64,
+        num_sectors: u64,
+        max_sectors: u32,
+    ) -> Result<()> {
+        let end_sector = start_sector + num_sectors;
+        let mut sector = start_sector;
+        while sector < end_sector {
+            let num_sectors = (end_sector - sector).min(u64::from(max_sectors)) as u32;
+            self.run_command(
+                ty,
+                Some(BlockSegment {
+                    sector,
+                    num_sectors,
+                    flags: 0,
+                }),
+            )?;
+            sector += u64::from(num_sectors);
+        }
+        Ok(())
+    }
+
+    /// Run a request which doesn't transfer any sectors, waiting for it to finish.
+    ///
+    /// The device reads `segment`, if there is one, as the request's data.
+    fn run_command(&mut self, ty: BlockRequestType, segment: Option<BlockSegment>) -> Result<()> {
+        let mut data = [0; BLOCK_SECTOR_LEN];
+        if let Some(segment) = segment {
+            data[..BlockSegment::LEN].copy_from_slice(&segment.to_bytes());
         }
-        // Descriptor 1: The data (may be read or written)
-        // SAFETY: We have exclusive access to the queue, so we can write to it.
+        let slot = self.claim_slot(ty, 0, Some(&data))?;
+        let data_addr = self.request_addr(slot) + core::mem::offset_of!(BlockRequest, data);
+        let data_buf = segment.map(|_| (data_addr, BlockSegment::LEN));
+        // SAFETY:
+        // The segment is in the request in this slot, which we don't touch again until the device
+        // finishes with it.
+        let token = unsafe { self.submit_slot(slot, data_buf.into_iter(), true) }?;
+        self.wait_for(token).status.success()
+    }
+
+    /// Fill in a free slot with a single-sector request and hand it to the device.
+    fn start_request(
+        &mut self,
+        ty: BlockRequestType,
+        sector: u64,
+        data: Option<&[u8; BLOCK_SECTOR_LEN]>,
+    ) -> Result<BlockToken> {
+        let slot = self.claim_slot(ty, sector, data)?;
+        let data_addr = self.request_addr(slot) + core::mem::offset_of!(BlockRequest, data);
+        // SAFETY:
+        // The data is in the request in this slot, which we don't touch again until the device
+        // finishes with it.
+        unsafe { self.submit_slot(slot, core::iter::once((data_addr, BLOCK_SECTOR_LEN)), true) }
+    }
+
+    /// Fill in the request in a free slot.
+    ///
+    /// The slot stays free until it's passed to [`Self::submit_slot`].
+    fn claim_slot(
+        &mut self,
+        ty: BlockRequestType,
+        sector: u64,
+        data: Option<&[u8; BLOCK_SECTOR_LEN]>,
+    ) -> Result<usize> {
+        let slot = self.slots[..self.num_slots]
+            .iter()
+            .position(|slot| *slot == BlockSlot::Free)
+            .ok_or(ErrorKind::LimitReached)?;
+        // SAFETY: The slot is free, so the device isn't using this request.
         unsafe {
-            desc.wrapping_add(1).write_volatile(VirtQueueDescriptor {
-                address: core::ptr::from_mut(request).addr() as u64
-                    + core::mem::offset_of!(BlockRequest, data) as u64,
-                length: BLOCK_SECTOR_LEN as u32,
-                flags: match request.ty {
-                    BlockRequestType::Read => DescriptorFlags::NEXT | DescriptorFlags::WRITE,
-                    BlockRequestType::Write => DescriptorFlags::NEXT,
-                    _ => {
-                        // We (the driver) don't yet support the other types.
-                        request.status = BlockRequestStatus::UNSUPPORTED;
-                        return;
-                    }
-                },
-                next: 2,
+            self.request(slot).write(BlockRequest {
+                ty,
+                reserved: 0,
+                sector,
+                data: data.copied().unwrap_or([0; BLOCK_SECTOR_LEN]),
+                status: BlockRequestStatus::empty(),
             });
         }
-        // Descriptor 2: The status byte (device-written)
-        // SAFETY: We have exclusive access to the queue, so we can write to it.
-        unsafe {
-            desc.wrapping_add(2).write_volatile(VirtQueueDescriptor {
-                address: core::ptr::from_mut(request).addr() as u64
-                    + core::mem::offset_of!(BlockRequest, status) as u64,
-                length: 1,
-                flags: DescriptorFlags::WRITE,
+        Ok(slot)
+    }
 
-                next: 0,
-            });
+    /// Hand the request in `slot` to the device, with its data in the buffers at the given physical
+    /// addresses and lengths.
+    ///
+    /// If `notify` is false, the device might not notice the request until the queue is notified.
+    ///
+    /// # Safety
+    /// The device will read or write the data buffers. The caller is responsible for ensuring that
+    /// these reads and writes do not violate Rust's memory model until the request is waited for.
+    unsafe fn submit_slot(
+        &mut self,
+        slot: usize,
+        data_bufs: impl Iterator<Item = (usize, usize)>,
+        notify: bool,
+    ) -> Result<BlockToken> {
+        let request = self.request(slot);
+        // Only reads give the device anything to write. The other types only send it data.
+        // SAFETY: The slot is free, so the device isn't using this request.
+        let device_writes_data = matches!(unsafe { &(*request).ty }, BlockRequestType::Read);
+
+        // Each descriptor can only be read-only or write-only, so we need to split into multiple
+        // parts.
+        let request_addr = self.request_addr(slot);
+        let status_addr = request_addr + core::mem::offset_of!(BlockRequest, status);
+        let mut chain = DescriptorChain::new(&mut self.virtio, 0);
+        // The header, which the device only reads.
+        chain.push_readable(request_addr, core::mem::offset_of!(BlockRequest, data))?;
+        // The data, which may be read or written.
+        for (data_addr, data_len) in data_bufs {
+            if device_writes_data {
+                chain.push_writable(data_addr, data_len)?;
+            } else {
+                chain.push_readable(data_addr, data_len)?;
+            }
         }
+        // The status byte, which the device writes.
+        chain.push_writable(status_addr, 1)?;
 
         // SAFETY:
-        // The descriptors point to non-overlapping sections of `request`, which we have an
-        // exclusive reference to.
-        unsafe { self.virtio.run_descriptor(0, 0) };
+        // The header and status are in the request in this slot, which we don't touch again until
+        // the device finishes with it, and our caller upholds the requirements for the data.
+        let head = unsafe {
+            if notify {
+                chain.submit()
+            } else {
+                chain.submit_without_notify()
+            }
+        };
+        self.slots[slot] = BlockSlot::InFlight { head };
+        Ok(BlockToken { slot })
     }
 
-    /// Read a sector from the device into the buffer.
-    pub fn read_sector(&mut self, buf: &mut [u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
-        log::trace!("Reading sector {sector} from virtio block device");
-        let mut request = BlockRequest {
-            ty: BlockRequestType::Read,
-            reserved: 0,
-            sector,
-            data: [0; 512],
-            status: BlockRequestStatus::empty(),
+    /// Wait for the request for `token` to finish, and free its slot.
+    ///
+    /// Returns the finished request.
+    #[expect(
+        clippy::needless_pass_by_value,
+        reason = "Waiting uses up the token, since the slot gets freed"
+    )]
+    fn wait_for(&mut self, token: BlockToken) -> BlockRequest {
+        self.reap_finished();
+        let BlockToken { slot } = token;
+        while self.slots[slot] != BlockSlot::Finished {
+            let used = self.virtio.wait_for_used(0);
+            self.finish_used(&used);
+        }
+        self.slots[slot] = BlockSlot::Free;
+        // SAFETY: The device has finished with this request, so we can read it.
+        unsafe { self.request(slot).read() }
+    }
+
+    /// Get a pointer to the request in `slot`.
+    fn request(&self, slot: usize) -> *mut BlockRequest {
+        #[expect(
+            clippy::cast_ptr_alignment,
+            reason = "The buffer starts on a page boundary, so requests are aligned"
+        )]
+        let requests = self.requests.as_ptr().cast::<BlockRequest>();
+        requests.wrapping_add(slot)
+    }
+
+    /// Get the physical address of the request in `slot`.
+    fn request_addr(&self, slot: usize) -> usize {
+        self.requests.phys_addr() + slot * size_of::<BlockRequest>()
+    }
+
+    /// Wait for every request in flight to finish, so the device isn't reset part way through one.
+    ///
+    /// This spins rather than sleeping, so it works while panicking. If the device takes too long,
+    /// we give up on it.
+    fn drain(&mut self) {
+        /// How long we wait for requests to finish.
+        const DRAIN_TIMEOUT: core::time::Duration = core::time::Duration::from_secs(1);
+
+        let deadline = crate::timer::deadline_after(DRAIN_TIMEOUT);
+        loop {
+            self.reap_finished();
+            let in_flight = self
+                .slots
+                .iter()
+                .filter(|slot| matches!(slot, BlockSlot::InFlight { .. }))
+                .count();
+            if in_flight == 0 {
+                return;
+            }
+            if crate::timer::now() >= deadline {
+                log::warn!("Gave up waiting for {in_flight} virtio block requests to finish");
+                return;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Mark the requests the device has finished with as finished.
+    fn reap_finished(&mut self) {
+        while let Some(used) = self.virtio.pop_used(0) {
+            self.finish_used(&used);
+        }
+    }
+
+    /// Mark the request for a used element as finished, freeing its descriptors.
+    fn finish_used(&mut self, used: &VirtQueueUsedElement) {
+        let head = used.index as u16;
+        let Some(slot) = self
+            .slots
+            .iter_mut()
+            .find(|slot| **slot == BlockSlot::InFlight { head })
+        else {
+            log::error!("virtio block device finished unknown request {head}");
+            return;
         };
-        self.do_request(&mut request);
-        request.status.success()?;
-        buf.copy_from_slice(&request.data);
+        *slot = BlockSlot::Finished;
+        self.virtio.free_chain(0, head);
+    }
+}
+impl Drop for VirtioBlock<'_> {
+    fn drop(&mut self) {
+        self.drain();
+    }
+}
+impl BlockDevice for VirtioBlock<'_> {
+    /// A single sector doesn't need a [`BlockQueue`], so this skips it.
+    fn read_sector(&mut self, buf: &mut [u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
+        let token = self.start_read(sector)?;
+        self.wait_read(token, buf)
+    }
+
+    /// A single sector doesn't need a [`BlockQueue`], so this skips it.
+    fn write_sector(&mut self, data: &[u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
+        let token = self.start_write(data, sector)?;
+        self.wait_write(token)
+    }
+
+    /// Read consecutive sectors, starting at `start_sector`, to fill `buf`.
+    ///
+    /// The sectors are read with as few requests as possible, so it's faster than reading each
+    /// sector in turn. `buf` must be a whole number of sectors long.
+    fn read_sectors(&mut self, buf: &mut [u8], start_sector: u64) -> Result<()> {
+        let (sectors, []) = buf.as_chunks_mut::<BLOCK_SECTOR_LEN>() else {
+            return Err(ErrorKind::InvalidFormat.into());
+        };
+        let mut sector_num = start_sector;
+        for batch in sectors.chunks_mut(block_queue::MAX_QUEUED_OPS) {
+            let batch_len = batch.len() as u64;
+            let mut queue = BlockQueue::new();
+            for (buf, sector) in batch.iter_mut().zip(sector_num..) {
+                queue.read(sector, buf)?;
+            }
+            self.run_queue(&mut queue)?;
+            sector_num += batch_len;
+        }
         Ok(())
     }
 
-    /// Write a sector to the buffer.
-    pub fn write_sector(&mut self, data: &[u8; BLOCK_SECTOR_LEN], sector: u64) -> Result<()> {
-        log::trace!("Writing sector {sector} to virtio block device");
-        let mut request = BlockRequest {
-            ty: BlockRequestType::Write,
-            reserved: 0,
-            sector,
-            data: *data,
-            status: BlockRequestStatus::empty(),
+    /// Write `data` to consecutive sectors, starting at `start_sector`.
+    ///
+    /// The sectors are written with as few requests as possible, so it's faster than writing each
+    /// sector in turn. `data` must be a whole number of sectors long.
+    fn write_sectors(&mut self, data: &[u8], start_sector: u64) -> Result<()> {
+        let (sectors, []) = data.as_chunks::<BLOCK_SECTOR_LEN>() else {
+            return Err(ErrorKind::InvalidFormat.into());
         };
-        self.do_request(&mut request);
-        request.status.success()?;
+        let mut sector_num = start_sector;
+        for batch in sectors.chunks(block_queue::MAX_QUEUED_OPS) {
+            let mut queue = BlockQueue::new();
+            for (data, sector) in batch.iter().zip(sector_num..) {
+                queue.write(sector, data)?;
+            }
+            self.run_queue(&mut queue)?;
+            sector_num += batch.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Make sure every finished write has reached stable storage.
+    ///
+    /// If the device doesn't support flushing, it has no write cache, so this does nothing.
+    fn flush(&mut self) -> Result<()> {
+        if !self.virtio.features().flush() {
+            return Ok(());
+        }
+        log::trace!("Flushing virtio block device");
+        self.run_command(BlockRequestType::Flush, None)
+    }
+
+    /// Tell the device that the given sectors no longer hold anything useful.
+    ///
+    /// This is only a hint, so it does nothing if the device doesn't support discarding, and the
+    /// sectors may read as anything afterwards.
+    fn discard(&mut self, start_sector: u64, num_sectors: u64) -> Result<()> {
+        if !self.virtio.features().discard() {
+            return Ok(());
+        }
+        log::trace!("Discarding {num_sectors} sectors from {start_sector} on virtio block device");
+        let max_sectors = self.virtio.read_register(reg::MaxDiscardSectors).max(1);
+        self.run_segments(
+            BlockRequestType::Discard,
+            start_sector,
+            num_sectors,
+            max_sectors,
+        )
+    }
+
+    /// Fill the given sectors with zeroes.
+    ///
+    /// If the device doesn't support this directly, we write the zeroes ourselves.
+    fn write_zeroes(&mut self, start_sector: u64, num_sectors: u64) -> Result<()> {
+        log::trace!("Zeroing {num_sectors} sectors from {start_sector} on virtio block device");
+        if self.virtio.features().write_zeros() {
+            let max_sectors = self.virtio.read_register(reg::MaxWriteZeroesSectors).max(1);
+            return self.run_segments(
+                BlockRequestType::WriteZeros,
+                start_sector,
+                num_sectors,
+                max_sectors,
+            );
+        }
+        let zeroes = [0; BLOCK_SECTOR_LEN];
+        let end_sector = start_sector + num_sectors;
+        let mut sector_num = start_sector;
+        while sector_num < end_sector {
+            let mut queue = BlockQueue::new();
+            while sector_num < end_sector && queue.write(sector_num, &zeroes).is_ok() {
+                sector_num += 1;
+            }
+            self.run_queue(&mut queue)?;
+        }
         Ok(())
     }
 
     /// Get the capacity in number of 512-byte sectors.
-    pub fn capacity(&self) -> u64 {
+    fn capacity(&self) -> u64 {
         self.virtio.read_register(reg::Capacity)
     }
 }
 
 pub struct VirtioRandom<'a> {
     virtio: Virtio<'a, 1>,
+    /// The buffer the device writes random bytes into.
+    buf: DmaBuffer,
 }
 impl VirtioRandom<'_> {
-    /// Initialize at the address the device appears at in kernel memory.
+    /// The most random bytes we ask the device for at once.
+    const BUF_LEN: usize = 64;
+
+    /// Initialize the device at the given address in kernel memory.
     ///
     /// # Safety
     /// This takes ownership over a device at the given address, so requires nothing else access
     /// this memory.
-    pub unsafe fn init_kernel_address() -> Result<Self> {
+    pub unsafe fn init_at_address(address: usize) -> Result<Self> {
         log::info!("Initializing virtio random device");
         // SAFETY: By method precondition, we can take ownership of this memory.
         let mut virtio = unsafe {
-            Virtio::init_for_pointers(core::ptr::with_exposed_provenance_mut(RNG_DEVICE_ADDRESS))
-        };
-        if virtio.read_register(reg::DeviceId) != 4 {
-            // It wasn't a random device we know about.
-            return Err(ErrorKind::Unsupported.into());
-        }
-        // SAFETY: Newly-allocated memory can get exclusive access.
-        let queue = unsafe {
-            &mut *crate::alloc::alloc_pages_zeroed(
-                size_of::<VirtQueue>().div_ceil(crate::page_table::PAGE_SIZE),
-            )?
-            .cast::<MaybeUninit<VirtQueue>>()
-        };
-        virtio.initialize_queue(0, queue);
-        Ok(Self { virtio })
+            Virtio::init_for_pointers(
+                core::ptr::with_exposed_provenance_mut(address),
+                DeviceKind::Entropy,
+                reg::DeviceFeatureFlags::empty(),
+            )
+        }?;
+        virtio.initialize_queue(0, 1)?;
+        Ok(Self {
+            virtio,
+            buf: DmaBuffer::new_zeroed(Self::BUF_LEN)?,
+        })
     }
 
     /// Fill this buffer with random bytes.
-    ///
-    /// This function assumes the buffer is in kernel memory (i.e. the physical and virtual
-    /// addresses are the same).
-    pub fn read_random(&mut self, mut buf: crate::page_table::UserMemMutOpaque) -> Result<()> {
-        #![expect(
-            clippy::unwrap_in_result,
-            reason = "should be initialized in constructor"
-        )]
+    pub fn read_random(&mut self, buf: &mut [u8]) -> Result<()> {
         const MAX_NUM_ITERS: u8 = 128;
         let mut num_iters = 0;
-        loop {
+        let mut filled = 0;
+        while filled < buf.len() {
             num_iters += 1;
             if num_iters > MAX_NUM_ITERS {
                 log::error!("Entropy device didn't make random data on time");
                 return Err(ErrorKind::Io.into());
             }
-            let desc = self.virtio.queues[0]
-                .unwrap()
-                .as_ptr()
-                .wrapping_byte_add(core::mem::offset_of!(VirtQueue, descriptor))
-                .cast::<VirtQueueDescriptor>();
-            // SAFETY: We have exclusive access, so we can write to the queue.
-            unsafe {
-                desc.write_volatile(VirtQueueDescriptor {
-                    // `UserMemMutOpaque` already checked that the memory is allocated
-                    address: crate::page_table::paddr_for_vaddr(buf.as_ptr()).unwrap().0 as u64,
-                    // TODO check if allocation is split among multiple pages.
-                    length: buf.len() as u32,
-                    flags: DescriptorFlags::WRITE,
-                    next: 0,
-                });
-            }
+            let len = (buf.len() - filled).min(self.buf.len());
+            let buf_addr = self.buf.phys_addr();
+            let mut chain = DescriptorChain::new(&mut self.virtio, 0);
+            chain.push_writable(buf_addr, len)?;
             // SAFETY:
-            // The descriptors point to non-overlapping sections of `request`, which we have an
-            // exclusive reference to.
-            let used = unsafe { self.virtio.run_descriptor(0, 0) };
-            if used.length as usize >= buf.len() {
-                if used.length as usize > buf.len() {
-                    // NOTE: I'm not sure why it would return a length greater than the original
-                    // buffer, I should figure this out.
-                    log::error!(
-                        "entropy device wrote longer than expected: {} bytes written out of {}",
-                        used.length,
-                        buf.len()
-                    );
-                }
-                return Ok(());
+            // The descriptor points to our buffer, which we don't touch until the device is done
+            // with it.
+            let used = unsafe { chain.run() };
+            if used.length as usize > len {
+                // NOTE: I'm not sure why it would return a length greater than the original
+                // buffer, I should figure this out.
+                log::error!(
+                    "entropy device wrote longer than expected: {} bytes written out of {len}",
+                    used.length,
+                );
+            }
+            let written = (used.length as usize).min(len);
+            buf[filled..][..written].copy_from_slice(&self.buf[..written]);
+            filled += written;
+            if written < len {
+                crate::proc::sched_yield();
             }
-            buf = buf.range_from(used.length as usize..);
-            crate::proc::sched_yield();
         }
+        Ok(())
     }
 }
 
+/// A driver controlling a virtio console device.
+///
+/// We don't negotiate multiple ports, so this only talks to the first port, using one receive
+/// buffer and one transmit buffer.
 pub struct VirtioConsole<'a> {
-    virtio: Virtio<'a, 4>,
+    virtio: Virtio<'a, 2>,
+    /// The buffer the device writes received bytes into.
+    receive_buf: DmaBuffer,
+    /// The range of `receive_buf` holding received bytes that haven't been read yet.
+    ///
+    /// The buffer is only handed back to the device once this is empty.
+    unread: core::ops::Range<usize>,
+    /// The buffer we write bytes to transmit into.
+    transmit_buf: DmaBuffer,
 }
 impl VirtioConsole<'_> {
-    /// Initialize at the address the device appears at in kernel memory.
+    /// The queue the device puts received bytes in.
+    const RECEIVE_QUEUE: u32 = 0;
+    /// The queue we put bytes to transmit in.
+    const TRANSMIT_QUEUE: u32 = 1;
+    /// The size of each of the receive and transmit buffers.
+    const BUF_LEN: usize = 256;
+
+    /// Initialize the device at the given address in kernel memory.
     ///
     /// # Safety
     /// This takes ownership over a device at the given address, so requires nothing else access
     /// this memory.
-    pub unsafe fn init_kernel_address() -> Result<Self> {
+    pub unsafe fn init_at_address(address: usize) -> Result<Self> {
         log::info!("Initializing virtio console device");
         // SAFETY: By method precondition, we can take ownership of this memory.
         let mut virtio = unsafe {
-            Virtio::init_for_pointers(core::ptr::with_exposed_provenance_mut(
-                CONSOLE_DEVICE_ADDRESS,
-            ))
+            Virtio::init_for_pointers(
+                core::ptr::with_exposed_provenance_mut(address),
+                DeviceKind::Console,
+                reg::DeviceFeatureFlags::empty(),
+            )
+        }?;
+        for queue_idx in [Self::RECEIVE_QUEUE, Self::TRANSMIT_QUEUE] {
+            virtio.initialize_queue(queue_idx, 1)?;
+        }
+        let mut this = Self {
+            virtio,
+            receive_buf: DmaBuffer::new_zeroed(Self::BUF_LEN)?,
+            unread: 0..0,
+            transmit_buf: DmaBuffer::new_zeroed(Self::BUF_LEN)?,
         };
-        if virtio.read_register(reg::DeviceId) != 3 {
-            // It wasn't a console device we know about.
-            return Err(ErrorKind::Unsupported.into());
+        this.submit_receive_buf();
+        Ok(this)
+    }
+
+    /// Hand the receive buffer to the device to fill with the next input.
+    fn submit_receive_buf(&mut self) {
+        // SAFETY: We own the receive buffer, and don't touch it until the device hands it back.
+        unsafe {
+            self.virtio.write_descriptor(
+                Self::RECEIVE_QUEUE,
+                0,
+                VirtQueueDescriptor {
+                    address: self.receive_buf.phys_addr() as u64,
+                    length: self.receive_buf.len() as u32,
+                    flags: DescriptorFlags::WRITE,
+                    next: 0,
+                },
+            );
+            self.virtio.submit_descriptor(Self::RECEIVE_QUEUE, 0);
         }
-        // We need 4 different queues.
-        for queue_idx in 0..4 {
-            // SAFETY: Newly-allocated memory can get exclusive access.
-            let queue = unsafe {
-                &mut *crate::alloc::alloc_pages_zeroed(
-                    size_of::<VirtQueue>().div_ceil(crate::page_table::PAGE_SIZE),
-                )?
-                .cast::<MaybeUninit<VirtQueue>>()
+    }
+
+    /// Copy any received bytes into `buf`, without waiting for more to arrive.
+    ///
+    /// Returns the number of bytes copied.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        if self.unread.is_empty() {
+            let Some(used) = self.virtio.pop_used(Self::RECEIVE_QUEUE) else {
+                return 0;
             };
-            virtio.initialize_queue(queue_idx, queue);
+            self.unread = 0..(used.length as usize).min(self.receive_buf.len());
+        }
+        let len = buf.len().min(self.unread.len());
+        let start = self.unread.start;
+        buf[..len].copy_from_slice(&self.receive_buf[start..start + len]);
+        self.unread.start += len;
+        if self.unread.is_empty() {
+            self.submit_receive_buf();
+        }
+        len
+    }
+
+    /// Write all of `data` to the console, waiting until the device has taken it.
+    pub fn write(&mut self, data: &[u8]) {
+        for chunk in data.chunks(self.transmit_buf.len()) {
+            self.transmit_buf[..chunk.len()].copy_from_slice(chunk);
+            // SAFETY:
+            // The descriptor points at the transmit buffer, which we own and don't touch until the
+            // device is done with it.
+            unsafe {
+                self.virtio.write_descriptor(
+                    Self::TRANSMIT_QUEUE,
+                    0,
+                    VirtQueueDescriptor {
+                        address: self.transmit_buf.phys_addr() as u64,
+                        length: chunk.len() as u32,
+                        flags: DescriptorFlags::empty(),
+                        next: 0,
+                    },
+                );
+                self.virtio.run_descriptor(Self::TRANSMIT_QUEUE, 0);
+            }
+        }
+    }
+}
+
+/// A driver controlling a virtio network device.
+///
+/// The device has one receive buffer and one transmit buffer, so it handles one frame at a time in
+/// each direction.
+pub struct VirtioNet<'a> {
+    virtio: Virtio<'a, 2>,
+    /// The MAC address of the device.
+    mac: [u8; 6],
+    /// The size of the header preceding each frame, which depends on the device's version.
+    header_len: usize,
+    /// The buffer the device writes received frames into.
+    receive_buf: DmaBuffer,
+    /// The buffer we write frames to transmit into.
+    transmit_buf: DmaBuffer,
+}
+impl VirtioNet<'_> {
+    /// The queue the device puts received frames in.
+    const RECEIVE_QUEUE: u32 = 0;
+    /// The queue we put frames to transmit in.
+    const TRANSMIT_QUEUE: u32 = 1;
+    /// The largest size of the header preceding each frame.
+    const MAX_HEADER_LEN: usize = size_of::<NetHeader>() + size_of::<u16>();
+
+    /// Initialize the device at the given address in kernel memory.
+    ///
+    /// # Safety
+    /// This takes ownership over a device at the given address, so requires nothing else access
+    /// this memory.
+    pub unsafe fn init_at_address(address: usize) -> Result<Self> {
+        log::info!("Initializing virtio network device");
+        // SAFETY: By method precondition, we can take ownership of this memory.
+        let mut virtio = unsafe {
+            Virtio::init_for_pointers(
+                core::ptr::with_exposed_provenance_mut(address),
+                DeviceKind::Network,
+                reg::DeviceFeatureFlags::empty(),
+            )
+        }?;
+        for queue_idx in [Self::RECEIVE_QUEUE, Self::TRANSMIT_QUEUE] {
+            virtio.initialize_queue(queue_idx, 1)?;
+        }
+        // Modern devices always include the buffer count, since we accept `VIRTIO_F_VERSION_1`.
+        let header_len = if virtio.modern {
+            Self::MAX_HEADER_LEN
+        } else {
+            size_of::<NetHeader>()
+        };
+        let mut this = Self {
+            mac: virtio.read_register(reg::NetMac),
+            header_len,
+            virtio,
+            receive_buf: DmaBuffer::new_zeroed(Self::MAX_HEADER_LEN + MAX_FRAME_LEN)?,
+            transmit_buf: DmaBuffer::new_zeroed(Self::MAX_HEADER_LEN + MAX_FRAME_LEN)?,
+        };
+        this.submit_receive_buf();
+        Ok(this)
+    }
+
+    /// Get the MAC address of this device.
+    pub fn mac(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    /// Hand the receive buffer to the device to fill with the next frame.
+    fn submit_receive_buf(&mut self) {
+        // SAFETY: We own the receive buffer, and don't touch it until the device hands it back.
+        unsafe {
+            self.virtio.write_descriptor(
+                Self::RECEIVE_QUEUE,
+                0,
+                VirtQueueDescriptor {
+                    address: self.receive_buf.phys_addr() as u64,
+                    length: self.receive_buf.len() as u32,
+                    flags: DescriptorFlags::WRITE,
+                    next: 0,
+                },
+            );
+            self.virtio.submit_descriptor(Self::RECEIVE_QUEUE, 0);
+        }
+    }
+
+    /// Copy the next received frame into `buf`, if one has arrived.
+    ///
+    /// Returns the length of the frame, or `None` if no frame is waiting. Frames longer than
+    /// `buf` are truncated.
+    pub fn receive(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let used = self.virtio.pop_used(Self::RECEIVE_QUEUE)?;
+        let frame_len = (used.length as usize)
+            .saturating_sub(self.header_len)
+            .min(MAX_FRAME_LEN)
+            .min(buf.len());
+        buf[..frame_len].copy_from_slice(&self.receive_buf[self.header_len..][..frame_len]);
+        self.submit_receive_buf();
+        Some(frame_len)
+    }
+
+    /// Transmit a frame, waiting until the device has sent it.
+    pub fn transmit(&mut self, frame: &[u8]) -> Result<()> {
+        if frame.len() > MAX_FRAME_LEN {
+            return Err(ErrorKind::InvalidFormat.into());
         }
-        Ok(Self { virtio })
+        // We don't use any offloading features, so the header is all zeroes.
+        self.transmit_buf[..self.header_len].fill(0);
+        self.transmit_buf[self.header_len..][..frame.len()].copy_from_slice(frame);
+        // SAFETY:
+        // The descriptor points at the transmit buffer, which we own and don't touch until the
+        // device is done with it.
+        unsafe {
+            self.virtio.write_descriptor(
+                Self::TRANSMIT_QUEUE,
+                0,
+                VirtQueueDescriptor {
+                    address: self.transmit_buf.phys_addr() as u64,
+                    length: (self.header_len + frame.len()) as u32,
+                    flags: DescriptorFlags::empty(),
+                    next: 0,
+                },
+            );
+            self.virtio.run_descriptor(Self::TRANSMIT_QUEUE, 0);
+        }
+        Ok(())
+    }
+}
+
+/// A driver controlling a virtio GPU device.
+///
+/// This sets up a single 2D framebuffer covering the first display, which gets copied to the
+/// display when [`Self::flush`] is called.
+pub struct VirtioGpu<'a> {
+    virtio: Virtio<'a, 1>,
+    /// The size of the display, in pixels.
+    width: u32,
+    height: u32,
+    /// The start of the framebuffer in memory.
+    ///
+    /// This is kernel memory, so the physical and virtual addresses are the same.
+    framebuffer: crate::page_table::PhysicalAddress,
+    /// The buffer holding each command we send, followed by the device's response.
+    command_buf: DmaBuffer,
+}
+impl VirtioGpu<'_> {
+    /// The offset in `command_buf` where the device writes its response.
+    const RESPONSE_OFFSET: usize = 1024;
+    /// The queue we send control commands on.
+    const CONTROL_QUEUE: u32 = 0;
+    /// The ID of the resource we use as the framebuffer.
+    const RESOURCE_ID: u32 = 1;
+    /// The number of bytes in each pixel.
+    const BYTES_PER_PIXEL: u32 = 4;
+
+    /// Initialize the device at the given address in kernel memory.
+    ///
+    /// # Safety
+    /// This takes ownership over a device at the given address, so requires nothing else access
+    /// this memory.
+    pub unsafe fn init_at_address(address: usize) -> Result<Self> {
+        log::info!("Initializing virtio GPU device");
+        // SAFETY: By method precondition, we can take ownership of this memory.
+        let mut virtio = unsafe {
+            Virtio::init_for_pointers(
+                core::ptr::with_exposed_provenance_mut(address),
+                DeviceKind::Gpu,
+                reg::DeviceFeatureFlags::empty(),
+            )
+        }?;
+        // We don't use the cursor queue, so we only set up the control queue. Each command uses a
+        // chain of two descriptors.
+        virtio.initialize_queue(Self::CONTROL_QUEUE, 2)?;
+        let mut this = Self {
+            virtio,
+            width: 0,
+            height: 0,
+            framebuffer: crate::page_table::PhysicalAddress(0),
+            command_buf: DmaBuffer::new_zeroed(crate::page_table::PAGE_SIZE)?,
+        };
+
+        let display_info: GpuDisplayInfo = this.command(
+            &GpuCtrlHeader::for_type(GpuCtrlHeader::CMD_GET_DISPLAY_INFO),
+            GpuCtrlHeader::RESP_OK_DISPLAY_INFO,
+        )?;
+        let display = display_info
+            .displays
+            .iter()
+            .find(|display| display.enabled != 0)
+            .ok_or_else(|| {
+                log::error!("virtio GPU device has no enabled displays");
+                ErrorKind::NotFound
+            })?;
+        this.width = display.rect.width;
+        this.height = display.rect.height;
+        log::info!("Using {}x{} display", this.width, this.height);
+
+        let framebuffer_len = this.framebuffer_len();
+        this.framebuffer = crate::page_table::PhysicalAddress(
+            crate::alloc::alloc_pages_zeroed(
+                framebuffer_len.div_ceil(crate::page_table::PAGE_SIZE),
+            )?
+            .addr(),
+        );
+        this.command_no_data(&GpuResourceCreate2d {
+            header: GpuCtrlHeader::for_type(GpuCtrlHeader::CMD_RESOURCE_CREATE_2D),
+            resource_id: Self::RESOURCE_ID,
+            format: GpuResourceCreate2d::FORMAT_B8G8R8X8_UNORM,
+            width: this.width,
+            height: this.height,
+        })?;
+        this.command_no_data(&GpuResourceAttachBacking {
+            header: GpuCtrlHeader::for_type(GpuCtrlHeader::CMD_RESOURCE_ATTACH_BACKING),
+            resource_id: Self::RESOURCE_ID,
+            num_entries: 1,
+            address: this.framebuffer.0 as u64,
+            length: framebuffer_len as u32,
+            padding: 0,
+        })?;
+        this.command_no_data(&GpuSetScanout {
+            header: GpuCtrlHeader::for_type(GpuCtrlHeader::CMD_SET_SCANOUT),
+            rect: this.display_rect(),
+            scanout_id: 0,
+            resource_id: Self::RESOURCE_ID,
+        })?;
+        this.flush()?;
+        Ok(this)
+    }
+
+    /// Get the layout of the framebuffer.
+    pub fn info(&self) -> shared::FramebufferInfo {
+        shared::FramebufferInfo {
+            width: self.width,
+            height: self.height,
+            stride: self.width * Self::BYTES_PER_PIXEL,
+        }
+    }
+
+    /// Get the start of the framebuffer in memory.
+    pub fn framebuffer(&self) -> crate::page_table::PhysicalAddress {
+        self.framebuffer
+    }
+
+    /// Get the length of the framebuffer in bytes.
+    pub fn framebuffer_len(&self) -> usize {
+        (self.width * self.height * Self::BYTES_PER_PIXEL) as usize
+    }
+
+    /// Copy the contents of the framebuffer to the display.
+    pub fn flush(&mut self) -> Result<()> {
+        let rect = self.display_rect();
+        self.command_no_data(&GpuTransferToHost2d {
+            header: GpuCtrlHeader::for_type(GpuCtrlHeader::CMD_TRANSFER_TO_HOST_2D),
+            rect,
+            offset: 0,
+            resource_id: Self::RESOURCE_ID,
+            padding: 0,
+        })?;
+        self.command_no_data(&GpuResourceFlush {
+            header: GpuCtrlHeader::for_type(GpuCtrlHeader::CMD_RESOURCE_FLUSH),
+            rect,
+            resource_id: Self::RESOURCE_ID,
+            padding: 0,
+        })
+    }
+
+    /// The rectangle covering the whole display.
+    fn display_rect(&self) -> GpuRect {
+        GpuRect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Send a command which the device answers with just a header.
+    fn command_no_data<Request>(&mut self, request: &Request) -> Result<()> {
+        let _: GpuCtrlHeader = self.command(request, GpuCtrlHeader::RESP_OK_NODATA)?;
+        Ok(())
+    }
+
+    /// Send a command to the device and wait for its response.
+    ///
+    /// Returns an error if the response isn't of the `expected_type`.
+    fn command<Request, Response: GpuResponse>(
+        &mut self,
+        request: &Request,
+        expected_type: u32,
+    ) -> Result<Response> {
+        const {
+            assert!(size_of::<Request>() <= Self::RESPONSE_OFFSET);
+            assert!(size_of::<Response>() <= crate::page_table::PAGE_SIZE - Self::RESPONSE_OFFSET);
+        };
+        let request_ptr = self.command_buf.as_ptr();
+        let response_ptr = request_ptr.wrapping_add(Self::RESPONSE_OFFSET);
+        // SAFETY:
+        // The device isn't using the buffer, and we checked above that both parts fit in it.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                core::ptr::from_ref(request).cast::<u8>(),
+                request_ptr,
+                size_of::<Request>(),
+            );
+            response_ptr
+                .cast::<Response>()
+                .write_unaligned(Response::default());
+        }
+        let request_addr = self.command_buf.phys_addr();
+        // SAFETY:
+        // The descriptors point at the command buffer, which we don't touch until the device is
+        // done with it.
+        unsafe {
+            self.virtio.write_descriptor(
+                Self::CONTROL_QUEUE,
+                0,
+                VirtQueueDescriptor {
+                    address: request_addr as u64,
+                    length: size_of::<Request>() as u32,
+                    flags: DescriptorFlags::NEXT,
+                    next: 1,
+                },
+            );
+            self.virtio.write_descriptor(
+                Self::CONTROL_QUEUE,
+                1,
+                VirtQueueDescriptor {
+                    address: (request_addr + Self::RESPONSE_OFFSET) as u64,
+                    length: size_of::<Response>() as u32,
+                    flags: DescriptorFlags::WRITE,
+                    next: 0,
+                },
+            );
+            self.virtio.run_descriptor(Self::CONTROL_QUEUE, 0);
+        }
+        // SAFETY: The device is done with the buffer, and we wrote a valid response there first.
+        let response = unsafe { response_ptr.cast::<Response>().read_unaligned() };
+        let response_type = response.header().ty;
+        if response_type != expected_type {
+            log::error!(
+                "virtio GPU device responded with {response_type:#X}, expected {expected_type:#X}"
+            );
+            return Err(ErrorKind::Io.into());
+        }
+        Ok(response)
+    }
+}
+
+/// A driver controlling a virtio input device, such as a keyboard.
+pub struct VirtioInput<'a> {
+    virtio: Virtio<'a, 1>,
+    /// The buffers the device writes events into, one event per descriptor.
+    events: DmaBuffer,
+    /// The number of event buffers, which is limited by the size of the event queue.
+    num_event_bufs: u16,
+}
+impl VirtioInput<'_> {
+    /// The queue the device puts events in.
+    const EVENT_QUEUE: u32 = 0;
+    /// The most events the device can hold for us before we read them.
+    const MAX_EVENT_BUFS: u16 = 8;
+
+    /// Initialize the device at the given address in kernel memory.
+    ///
+    /// # Safety
+    /// This takes ownership over a device at the given address, so requires nothing else access
+    /// this memory.
+    pub unsafe fn init_at_address(address: usize) -> Result<Self> {
+        log::info!("Initializing virtio input device");
+        // SAFETY: By method precondition, we can take ownership of this memory.
+        let mut virtio = unsafe {
+            Virtio::init_for_pointers(
+                core::ptr::with_exposed_provenance_mut(address),
+                DeviceKind::Input,
+                reg::DeviceFeatureFlags::empty(),
+            )
+        }?;
+        // We don't send status updates (e.g. keyboard LEDs), so we only set up the event queue.
+        // We give the device as many event buffers as its queue can hold, up to our maximum.
+        let num_event_bufs = virtio
+            .initialize_queue(Self::EVENT_QUEUE, 1)?
+            .min(Self::MAX_EVENT_BUFS);
+        let mut this = Self {
+            virtio,
+            events: DmaBuffer::new_zeroed(usize::from(num_event_bufs) * shared::InputEvent::LEN)?,
+            num_event_bufs,
+        };
+        for descriptor_idx in 0..num_event_bufs {
+            this.submit_event_buf(descriptor_idx);
+        }
+        Ok(this)
+    }
+
+    /// Hand an event buffer to the device to fill with the next event.
+    fn submit_event_buf(&mut self, descriptor_idx: u16) {
+        let offset = usize::from(descriptor_idx) * shared::InputEvent::LEN;
+        // SAFETY: We own the event buffer, and don't touch it until the device hands it back.
+        unsafe {
+            self.virtio.write_descriptor(
+                Self::EVENT_QUEUE,
+                descriptor_idx,
+                VirtQueueDescriptor {
+                    address: (self.events.phys_addr() + offset) as u64,
+                    length: shared::InputEvent::LEN as u32,
+                    flags: DescriptorFlags::WRITE,
+                    next: 0,
+                },
+            );
+            self.virtio
+                .submit_descriptor(Self::EVENT_QUEUE, descriptor_idx);
+        }
+    }
+
+    /// Take the next event from the device, if one has arrived.
+    pub fn next_event(&mut self) -> Option<shared::InputEvent> {
+        let used = self.virtio.pop_used(Self::EVENT_QUEUE)?;
+        let descriptor_idx = (used.index % u32::from(self.num_event_bufs)) as u16;
+        let offset = usize::from(descriptor_idx) * shared::InputEvent::LEN;
+        let mut event_bytes = [0; shared::InputEvent::LEN];
+        event_bytes.copy_from_slice(&self.events[offset..][..shared::InputEvent::LEN]);
+        let event = shared::InputEvent::from_bytes(event_bytes);
+        self.submit_event_buf(descriptor_idx);
+        Some(event)
     }
 }
 
@@ -275,6 +1175,8 @@ struct Virtio<'a, const NUM_QUEUES: usize> {
     /// This isn't a reference because the underlying hardware can modify the pointed-to data, so
     /// the aliasing rules for exclusive references are violated.
     regs: *mut (),
+    /// Whether the device uses the modern (version 2) register layout, rather than the legacy one.
+    modern: bool,
     /// A pointer to the queue buffer.
     ///
     /// This isn't a reference because the underlying hardware can modify the pointed-to data, so
@@ -282,71 +1184,152 @@ struct Virtio<'a, const NUM_QUEUES: usize> {
     ///
     /// The driver presently only supports having exactly one queue. TODO Add support for
     /// initializing and destroying queues.
-    queues: [Option<NonNull<VirtQueue>>; NUM_QUEUES],
+    queues: [Option<VirtQueue>; NUM_QUEUES],
+    /// For each queue, the index of the next used element we haven't yet looked at.
+    last_used: [u16; NUM_QUEUES],
+    /// For each queue, a bitmask of the descriptors which aren't part of a [`DescriptorChain`].
+    ///
+    /// Drivers which manage descriptors themselves don't use this.
+    free_descriptors: [u32; NUM_QUEUES],
+    /// The device-specific features we and the device agreed on.
+    features: reg::DeviceFeatureFlags,
     /// Phantom to track the lifetime.
     phantom: PhantomData<&'a mut ()>,
 }
 
-impl<'a, const NUM_QUEUES: usize> Virtio<'a, NUM_QUEUES> {
-    unsafe fn init_for_pointers(regs: *mut ()) -> Self {
+impl<const NUM_QUEUES: usize> Drop for Virtio<'_, NUM_QUEUES> {
+    fn drop(&mut self) {
+        self.reset();
+    }
+}
+impl<const NUM_QUEUES: usize> Virtio<'_, NUM_QUEUES> {
+    /// Initialize the device at the given registers, if it has the given device ID.
+    ///
+    /// Of the device-specific features in `wanted_features`, we accept whichever the device offers.
+    ///
+    /// # Safety
+    /// This takes ownership over a device at the given address, so requires nothing else access
+    /// this memory.
+    unsafe fn init_for_pointers(
+        regs: *mut (),
+        kind: DeviceKind,
+        wanted_features: reg::DeviceFeatureFlags,
+    ) -> Result<Self> {
+        // Check the device before we touch it, since there might be nothing attached here.
+        // SAFETY: By method precondition, we can read these registers.
+        let found_id = unsafe { read_register_at(regs, reg::DeviceId) };
+        if found_id != kind.id() {
+            log::error!("Expected virtio {kind:?} device, found device {found_id}");
+            return Err(ErrorKind::NotFound.into());
+        }
         let mut this = Self {
             regs,
+            modern: false,
             queues: [None; NUM_QUEUES],
+            last_used: [0; NUM_QUEUES],
+            free_descriptors: [0; NUM_QUEUES],
+            features: reg::DeviceFeatureFlags::empty(),
             phantom: PhantomData,
         };
-        this.initialize();
-        this
+        this.initialize(wanted_features);
+        Ok(this)
     }
 
-    fn initialize_queue(&mut self, queue_num: u32, queue: &'a mut MaybeUninit<VirtQueue>) {
+    /// Set up the given queue, using as many entries as both we and the device support.
+    ///
+    /// Fails if the device doesn't have the queue, or can't fit at least `min_size` entries in it.
+    /// Returns the number of entries in the queue.
+    fn initialize_queue(&mut self, queue_num: u32, min_size: u16) -> Result<u16> {
         self.write_register(reg::QueueSelect, queue_num);
 
         // Check that the selected queue isn't active.
         assert_eq!(self.read_register(reg::QueueReady), 0);
 
+        // Use the largest power of two that we and the device both support, since split queues
+        // must be a power of two in size.
+        let device_max = self.read_register(reg::QueueSizeMax);
+        if device_max == 0 {
+            log::error!("virtio device doesn't have queue {queue_num}");
+            return Err(ErrorKind::NotFound.into());
+        }
+        let size = 1 << device_max.min(u32::from(MAX_QUEUE_SIZE)).ilog2();
+        if size < min_size {
+            log::error!(
+                "virtio queue {queue_num} only fits {size} entries, but we need {min_size}"
+            );
+            return Err(ErrorKind::Unsupported.into());
+        }
+
         // Initialize the queue
-        self.write_register(
-            reg::QueueSize,
-            const {
-                assert!(QUEUE_SIZE <= u32::MAX as usize);
-                QUEUE_SIZE as u32
-            },
-        );
-        let queue = queue.write(VirtQueue::default());
-        self.queues[queue_num as usize] = NonNull::new(queue);
+        self.write_register(reg::QueueSize, u32::from(size));
+        let queue = VirtQueue::alloc(size)?;
+        self.queues[queue_num as usize] = Some(queue);
+        self.free_descriptors[queue_num as usize] = (1 << size) - 1;
 
-        self.write_register(reg::QueuePfn, core::ptr::from_mut(queue).addr() as u32);
+        if self.modern {
+            // Modern devices take the address of each part of the queue separately. Our addresses
+            // fit in 32 bits, so the high halves are always zero.
+            self.write_register(
+                reg::QueueDescriptorLow,
+                queue.descriptor_table_addr() as u32,
+            );
+            self.write_register(reg::QueueDescriptorHigh, 0);
+            self.write_register(reg::QueueAvailableLow, queue.available_ring_addr() as u32);
+            self.write_register(reg::QueueAvailableHigh, 0);
+            self.write_register(reg::QueueUsedLow, queue.used_ring_addr() as u32);
+            self.write_register(reg::QueueUsedHigh, 0);
+        } else {
+            self.write_register(reg::QueuePfn, queue.descriptor_table_addr() as u32);
+        }
 
         // Mark the queue as ready for operation.
         self.write_register(reg::QueueReady, 1);
+        Ok(size)
+    }
+
+    /// Reset the device and free its queues.
+    ///
+    /// Once reset, the device stops touching memory, so any requests it hasn't finished never will
+    /// be.
+    fn reset(&mut self) {
+        self.write_register(reg::DeviceStatus, reg::DeviceStatusFlags::empty());
+        // The reset is done once the status reads back as zero.
+        while u32::from(self.read_register(reg::DeviceStatus)) != 0 {
+            core::hint::spin_loop();
+        }
+        for queue in self.queues.iter_mut().filter_map(Option::take) {
+            // SAFETY: The device was reset, so it's done with the queue.
+            unsafe { queue.free() };
+        }
+        log::info!("virtio device at {:#X} reset", self.regs.addr());
+    }
+
+    /// Get the device-specific features we and the device agreed on.
+    fn features(&self) -> reg::DeviceFeatureFlags {
+        self.features
     }
 
-    fn read_register<Register: VirtioBlockRegister>(&self, _register: Register) -> Register::RegTy {
-        const { assert!(Register::READABLE) };
-        let reg_ptr = self
-            .regs
-            .wrapping_byte_add(Register::OFFSET)
-            .cast::<Register::RegTy>();
+    fn read_register<Register: VirtioBlockRegister>(&self, register: Register) -> Register::RegTy
+    where
+        Register::Access: Readable,
+    {
         // SAFETY: We have shared access to the memory, so we can read.
-        unsafe { reg_ptr.read_volatile() }
+        unsafe { read_register_at(self.regs, register) }
     }
 
     fn write_register<Register: VirtioBlockRegister>(
         &mut self,
-        _register: Register,
+        register: Register,
         value: Register::RegTy,
-    ) {
-        const { assert!(Register::WRITABLE) };
-        let reg_ptr = self
-            .regs
-            .wrapping_byte_add(Register::OFFSET)
-            .cast::<Register::RegTy>();
+    ) where
+        Register::Access: Writable,
+    {
         // SAFETY: We have exclusive access to the memory, so we can write.
-        unsafe { reg_ptr.write_volatile(value) }
+        unsafe { write_register_at(self.regs, register, value) }
     }
 
-    /// Initialize the device.
-    fn initialize(&mut self) {
+    /// Initialize the device, accepting whichever of `wanted_features` it offers.
+    fn initialize(&mut self, wanted_features: reg::DeviceFeatureFlags) {
         log::info!("Initializing virtio device");
         // Initialize device per section 3.1
         // 1. Reset the device.
@@ -363,13 +1346,34 @@ impl<'a, const NUM_QUEUES: usize> Virtio<'a, NUM_QUEUES> {
 
         // First check that the device is what we expect.
         assert_eq!(self.read_register(reg::Magic), 0x7472_6976);
-        assert_eq!(self.read_register(reg::Version), 1);
+        let version = self.read_register(reg::Version);
+        assert!(
+            matches!(version, 1 | 2),
+            "Unsupported virtio-mmio version {version}"
+        );
+        self.modern = version == 2;
 
-        // Then read the features, check that we support them, and write them back.
-        let features = self.read_register(reg::DeviceFeatures);
-        log::info!("virtio device advertizes features {features}");
-        assert!(!features.read_only());
-        // NOTE We currently don't use any features
+        // Then read the features, and write back the ones we want.
+        self.write_register(reg::DeviceFeaturesSelect, 0);
+        let offered = self.read_register(reg::DeviceFeatures);
+        self.features = reg::DeviceFeatureFlags::from(offered & u32::from(wanted_features));
+        log::info!(
+            "virtio device offers features {offered:#X}, accepting {}",
+            self.features,
+        );
+        self.write_register(reg::DriverFeaturesSelect, 0);
+        self.write_register(reg::DriverFeatures, u32::from(self.features));
+        if self.modern {
+            // Modern devices only work if we accept that they're modern.
+            self.write_register(reg::DeviceFeaturesSelect, 1);
+            assert!(
+                self.read_register(reg::DeviceFeatures) & reg::FEATURE_VERSION_1_HIGH != 0,
+                "Modern virtio device doesn't offer VIRTIO_F_VERSION_1"
+            );
+            self.write_register(reg::DriverFeaturesSelect, 1);
+            self.write_register(reg::DriverFeatures, reg::FEATURE_VERSION_1_HIGH);
+            self.write_register(reg::DeviceFeaturesSelect, 0);
+        }
 
         // 5. Set the status bit to indicate we've accepted the features.
         self.write_register(
@@ -400,92 +1404,220 @@ impl<'a, const NUM_QUEUES: usize> Virtio<'a, NUM_QUEUES> {
         log::info!("virtio device initialized!");
     }
 
-    /// Run the request indicated by `descriptor_idx` (and any descriptors chained).
-    ///
-    /// This method will block until the read succeeds.
+    /// Write a descriptor into the given queue.
     ///
     /// # Safety
-    /// The device will read and/or write the contents the descriptors point at. The caller is
-    /// responsible for ensuring that these reads and writes do not violate Rust's memory model.
-    unsafe fn run_descriptor(
+    /// The descriptor must not currently be in use by the device.
+    unsafe fn write_descriptor(
         &mut self,
         queue_num: u32,
         descriptor_idx: u16,
-    ) -> VirtQueueUsedElement {
-        let queue = self.queues[queue_num as usize].unwrap().as_ptr();
-        // Reference the descriptors in the queue.
-        //
-        // SAFETY: We have exclusive access, so we can write to the queue.
-        let available = unsafe {
-            &mut *queue
-                .wrapping_byte_add(core::mem::offset_of!(VirtQueue, available))
-                .cast::<VirtQueueAvailableRing>()
-        };
-        available.ring[available.index as usize % QUEUE_SIZE] = descriptor_idx;
-        let available_idx = queue
-            .wrapping_byte_add(core::mem::offset_of!(VirtQueue, available.index))
-            .cast::<u16>();
+        descriptor: VirtQueueDescriptor,
+    ) {
+        let desc = self.queues[queue_num as usize]
+            .unwrap()
+            .descriptor(descriptor_idx);
+        // SAFETY: We have exclusive access, and the device isn't using this descriptor.
+        unsafe { desc.write_volatile(descriptor) };
+    }
+
+    /// Make the request indicated by `descriptor_idx` (and any descriptors chained) available to
+    /// the device, without waiting for it to finish.
+    ///
+    /// # Safety
+    /// The device will read and/or write the contents the descriptors point at. The caller is
+    /// responsible for ensuring that these reads and writes do not violate Rust's memory model
+    /// until the request shows up in [`Self::pop_used`].
+    unsafe fn submit_descriptor(&mut self, queue_num: u32, descriptor_idx: u16) {
+        // SAFETY: Our caller upholds the same requirements.
+        unsafe { self.make_available(queue_num, descriptor_idx) };
+        self.notify(queue_num);
+    }
+
+    /// Put the request indicated by `descriptor_idx` in the available ring, without notifying the
+    /// device.
+    ///
+    /// The device might not notice the request until [`Self::notify`] is called, so this lets
+    /// several requests be submitted with one notification.
+    ///
+    /// # Safety
+    /// The same as for [`Self::submit_descriptor`].
+    unsafe fn make_available(&mut self, queue_num: u32, descriptor_idx: u16) {
+        let queue = self.queues[queue_num as usize].unwrap();
+        let available_idx = queue.available_index();
         // SAFETY: We have exclusive access, so we can write to the queue.
         let idx = unsafe { available_idx.read_volatile() };
-        let available_slot = queue
-            .wrapping_byte_add(core::mem::offset_of!(VirtQueue, available.ring))
-            .cast::<u16>()
-            .wrapping_add(idx as usize % QUEUE_SIZE);
+        let available_slot = queue.available_slot(idx);
         // SAFETY: We have exclusive access, so we can write to the queue.
-        unsafe { available_slot.write_volatile(0) };
+        unsafe { available_slot.write_volatile(descriptor_idx) };
+        // Use a fence to ensure the slot is written before the device sees the new index.
+        core::sync::atomic::fence(core::sync::atomic::Ordering::AcqRel);
         // SAFETY: We have exclusive access, so we can write to the queue.
         unsafe { available_idx.write_volatile(idx.wrapping_add(1)) };
+    }
 
+    /// Tell the device there are new requests in the available ring of the given queue.
+    fn notify(&mut self, queue_num: u32) {
         // Use a fence to ensure we set up the queue before sending the notification
         core::sync::atomic::fence(core::sync::atomic::Ordering::AcqRel);
         // Notify the device that a new operation is available.
-        self.write_register(reg::QueueNotify, 0);
+        self.write_register(reg::QueueNotify, queue_num);
+        log::debug!("Submitted requests to device");
+    }
 
-        // Wait for the device to finish
-        log::debug!("Submitted request to device");
-        while self.queue_busy(queue_num) {
-            core::hint::spin_loop();
+    /// Take a free descriptor from the given queue.
+    ///
+    /// Fails if every descriptor is in use.
+    fn alloc_descriptor(&mut self, queue_num: u32) -> Result<u16> {
+        let free = &mut self.free_descriptors[queue_num as usize];
+        if *free == 0 {
+            return Err(ErrorKind::LimitReached.into());
         }
-        // SAFETY: We have exclusive access over the queue.
-        let used_idx = unsafe {
-            queue
-                .wrapping_byte_add(core::mem::offset_of!(VirtQueue, used.index))
-                .cast::<u16>()
-                .read_volatile()
-        } as usize
-            % QUEUE_SIZE;
-        let queue_elem = queue
-            .wrapping_byte_add(core::mem::offset_of!(VirtQueue, used.ring))
-            .cast::<VirtQueueUsedElement>()
-            .wrapping_add(used_idx);
-        // SAFETY:
-        // This queue element was just written, and we have exclusive access over the queue.
-        unsafe { queue_elem.read_volatile() }
+        let idx = free.trailing_zeros();
+        *free &= !(1 << idx);
+        Ok(idx as u16)
     }
 
-    /// Returns `true` if the device is processing elements in the queue.
-    fn queue_busy(&self, queue_num: u32) -> bool {
-        let queue = self.queues[queue_num as usize].unwrap().as_ptr();
-        // SAFETY: Shared access lets us read the queue.
-        let available_idx = unsafe {
-            queue
-                .wrapping_byte_add(core::mem::offset_of!(VirtQueue, available.index))
-                .cast::<u16>()
-                .read_volatile()
-        };
+    /// Return a descriptor taken with [`Self::alloc_descriptor`] to the given queue.
+    fn free_descriptor(&mut self, queue_num: u32, descriptor_idx: u16) {
+        let free = &mut self.free_descriptors[queue_num as usize];
+        debug_assert_eq!(
+            *free & (1 << descriptor_idx),
+            0,
+            "Double free of descriptor"
+        );
+        *free |= 1 << descriptor_idx;
+    }
+
+    /// Free every descriptor in the chain starting at `head`, once the device is done with it.
+    fn free_chain(&mut self, queue_num: u32, head: u16) {
+        let queue = self.queues[queue_num as usize].unwrap();
+        let mut idx = head;
+        loop {
+            // SAFETY: The device is done with this chain, so we can read its descriptors.
+            let descriptor = unsafe { queue.descriptor(idx).read_volatile() };
+            self.free_descriptor(queue_num, idx);
+            if !descriptor.flags.next() {
+                break;
+            }
+            idx = descriptor.next;
+        }
+    }
+
+    /// Take the next request the device has finished with, if there is one.
+    fn pop_used(&mut self, queue_num: u32) -> Option<VirtQueueUsedElement> {
+        #![expect(
+            clippy::unwrap_in_result,
+            reason = "Queues are initialized in constructors"
+        )]
+        let queue = self.queues[queue_num as usize].unwrap();
         // SAFETY: Shared access lets us read the queue.
-        let used_idx = unsafe {
-            queue
-                .wrapping_byte_add(core::mem::offset_of!(VirtQueue, used.index))
-                .cast::<u16>()
-                .read_volatile()
-        };
-        available_idx != used_idx
+        let used_idx = unsafe { queue.used_index().read_volatile() };
+        let last_used = &mut self.last_used[queue_num as usize];
+        if used_idx == *last_used {
+            return None;
+        }
+        // Make sure we read the element after the device finished writing it.
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        let queue_elem = queue.used_element(*last_used);
+        *last_used = last_used.wrapping_add(1);
+        // SAFETY:
+        // The device is done writing this element, and we have exclusive access over the queue.
+        Some(unsafe { queue_elem.read_volatile() })
+    }
+
+    /// Run the request indicated by `descriptor_idx` (and any descriptors chained).
+    ///
+    /// This method will block until the read succeeds.
+    ///
+    /// # Safety
+    /// The device will read and/or write the contents the descriptors point at. The caller is
+    /// responsible for ensuring that these reads and writes do not violate Rust's memory model.
+    unsafe fn run_descriptor(
+        &mut self,
+        queue_num: u32,
+        descriptor_idx: u16,
+    ) -> VirtQueueUsedElement {
+        // SAFETY: We wait for the device to finish before returning, per our precondition.
+        unsafe { self.submit_descriptor(queue_num, descriptor_idx) };
+        self.wait_for_used(queue_num)
     }
+
+    /// Wait for the device to finish a request on the given queue, and take it.
+    ///
+    /// If the device's interrupts are enabled, the current process sleeps until the device
+    /// interrupts. Otherwise (e.g. while booting), this spins.
+    fn wait_for_used(&mut self, queue_num: u32) -> VirtQueueUsedElement {
+        let address = self.regs.addr();
+        let use_interrupts = crate::proc::in_process()
+            && crate::fdt::BOOT_INFO
+                .virtio_irq(address)
+                .is_some_and(crate::plic::is_enabled);
+        loop {
+            if let Some(used) = self.pop_used(queue_num) {
+                return used;
+            }
+            if use_interrupts {
+                // Interrupts aren't taken while we're in the kernel, so the device can't finish
+                // between our check and going to sleep without waking us.
+                crate::proc::sleep(address);
+            } else {
+                core::hint::spin_loop();
+            }
+        }
+    }
+}
+
+/// Read a register of the device whose registers start at `regs`.
+///
+/// # Safety
+/// There must be a virtio-mmio device at `regs` which nothing else is writing to.
+unsafe fn read_register_at<Register: VirtioBlockRegister>(
+    regs: *mut (),
+    _register: Register,
+) -> Register::RegTy
+where
+    Register::Access: Readable,
+{
+    // SAFETY: By precondition, we can read this register.
+    unsafe { register_at::<Register>(regs) }.read()
+}
+
+/// Write a register of the device whose registers start at `regs`.
+///
+/// # Safety
+/// There must be a virtio-mmio device at `regs`, and writing this register mustn't interfere with
+/// anything else using the device.
+unsafe fn write_register_at<Register: VirtioBlockRegister>(
+    regs: *mut (),
+    _register: Register,
+    value: Register::RegTy,
+) where
+    Register::Access: Writable,
+{
+    // SAFETY: By precondition, we can write this register.
+    unsafe { register_at::<Register>(regs) }.write(value);
+}
+
+/// Get a register of the device whose registers start at `regs`.
+///
+/// # Safety
+/// There must be a virtio-mmio device at `regs`, and accessing this register as its access marker
+/// allows mustn't interfere with anything else using the device.
+unsafe fn register_at<Register: VirtioBlockRegister>(
+    regs: *mut (),
+) -> mmio::Register<Register::RegTy, Register::Access> {
+    let reg_ptr = regs
+        .wrapping_byte_add(Register::OFFSET)
+        .cast::<Register::RegTy>();
+    // SAFETY: By precondition, the register is there.
+    unsafe { mmio::Register::new(reg_ptr) }
 }
 
 // SAFETY: The device uses shared/exclusive references to force synchronization.
 unsafe impl<const NUM_QUEUES: usize> Send for Virtio<'_, NUM_QUEUES> {}
+// SAFETY: Only the driver accesses the requests, and the device doesn't care which hart we're on.
+unsafe impl Send for VirtioBlock<'_> {}
 // SAFETY: The device uses shared/exclusive references to force synchronization.
 unsafe impl<const NUM_QUEUES: usize> Sync for Virtio<'_, NUM_QUEUES> {}
 
@@ -497,20 +1629,133 @@ unsafe trait VirtioBlockRegister {
     /// The offset of this register from the base in memory.
     const OFFSET: usize;
     /// The data type for this field.
-    type RegTy;
-
-    /// Whether this register is readable.
-    const READABLE: bool;
-    /// Whether this register is writable.
-    const WRITABLE: bool;
+    type RegTy: Copy;
+    /// Which accesses this register allows.
+    type Access: mmio::Access;
 }
 
-#[derive(Default, Debug)]
-#[repr(C, align(4096))]
+/// The memory for a virtqueue, laid out as a split virtqueue.
+///
+/// Where each part goes depends on the number of entries the device agrees to, so this works out
+/// the offsets at runtime. Legacy devices require this exact layout, while modern devices are told
+/// where each part is, so this layout works for them too.
+#[derive(Clone, Copy, Debug)]
 struct VirtQueue {
-    descriptor: [VirtQueueDescriptor; QUEUE_SIZE],
-    available: VirtQueueAvailableRing,
-    used: VirtQueueUsedRing,
+    /// The start of the queue, which is where the descriptor table goes.
+    ///
+    /// This isn't a reference because the underlying hardware can modify the pointed-to data, so
+    /// the aliasing rules for exclusive references are violated.
+    base: NonNull<VirtQueueDescriptor>,
+    /// The number of entries in the queue.
+    size: u16,
+}
+impl VirtQueue {
+    /// The alignment legacy devices require of the used ring.
+    const USED_RING_ALIGN: usize = 4096;
+    /// The length of the flags and index fields at the start of the available and used rings.
+    const RING_HEADER_LEN: usize = 2 * size_of::<u16>();
+    /// The length of the event index field at the end of the available and used rings.
+    const RING_FOOTER_LEN: usize = size_of::<u16>();
+
+    /// Allocate zeroed memory for a queue with `size` entries.
+    ///
+    /// Give the memory back with [`Self::free`] once the device is done with the queue.
+    fn alloc(size: u16) -> Result<Self> {
+        let base = crate::alloc::alloc_pages_zeroed(Self::num_pages(size))?;
+        Ok(Self {
+            base: NonNull::new(base.cast()).ok_or(ErrorKind::OutOfMemory)?,
+            size,
+        })
+    }
+
+    /// Free the memory for the queue.
+    ///
+    /// # Safety
+    /// The device must be done with the queue, so it won't touch this memory again, and nothing
+    /// else can use the queue afterwards.
+    unsafe fn free(self) {
+        // SAFETY: We allocated these pages in `alloc`, and by precondition nothing uses them now.
+        unsafe { crate::alloc::free_pages(self.base.as_ptr().cast(), Self::num_pages(self.size)) };
+    }
+
+    /// The number of pages for a queue with `size` entries.
+    fn num_pages(size: u16) -> usize {
+        let used_ring_len = Self::RING_HEADER_LEN
+            + usize::from(size) * size_of::<VirtQueueUsedElement>()
+            + Self::RING_FOOTER_LEN;
+        let len = Self::used_ring_offset(size) + used_ring_len;
+        len.div_ceil(crate::page_table::PAGE_SIZE)
+    }
+
+    /// The offset of the available ring from the start of the queue.
+    fn available_ring_offset(size: u16) -> usize {
+        usize::from(size) * size_of::<VirtQueueDescriptor>()
+    }
+
+    /// The offset of the used ring from the start of the queue.
+    fn used_ring_offset(size: u16) -> usize {
+        (Self::available_ring_offset(size)
+            + Self::RING_HEADER_LEN
+            + usize::from(size) * size_of::<u16>()
+            + Self::RING_FOOTER_LEN)
+            .next_multiple_of(Self::USED_RING_ALIGN)
+    }
+
+    /// The address of the descriptor table.
+    fn descriptor_table_addr(self) -> usize {
+        self.base.addr().get()
+    }
+
+    /// The address of the available ring.
+    fn available_ring_addr(self) -> usize {
+        self.descriptor_table_addr() + Self::available_ring_offset(self.size)
+    }
+
+    /// The address of the used ring.
+    fn used_ring_addr(self) -> usize {
+        self.descriptor_table_addr() + Self::used_ring_offset(self.size)
+    }
+
+    /// A pointer to the descriptor at `idx` (wrapping around the table).
+    fn descriptor(self, idx: u16) -> *mut VirtQueueDescriptor {
+        self.base
+            .as_ptr()
+            .wrapping_add(usize::from(idx % self.size))
+    }
+
+    /// A pointer to the index of the next slot we'll fill in the available ring.
+    fn available_index(self) -> *mut u16 {
+        self.base
+            .as_ptr()
+            .wrapping_byte_add(Self::available_ring_offset(self.size) + size_of::<u16>())
+            .cast()
+    }
+
+    /// A pointer to the slot at `idx` (wrapping around the ring) in the available ring.
+    fn available_slot(self, idx: u16) -> *mut u16 {
+        self.base
+            .as_ptr()
+            .wrapping_byte_add(Self::available_ring_offset(self.size) + Self::RING_HEADER_LEN)
+            .cast::<u16>()
+            .wrapping_add(usize::from(idx % self.size))
+    }
+
+    /// A pointer to the index of the next slot the device will fill in the used ring.
+    fn used_index(self) -> *mut u16 {
+        self.base
+            .as_ptr()
+            .wrapping_byte_add(Self::used_ring_offset(self.size) + size_of::<u16>())
+            .cast()
+    }
+
+    /// A pointer to the element at `idx` (wrapping around the ring) in the used ring.
+    fn used_element(self, idx: u16) -> *mut VirtQueueUsedElement {
+        self.base
+            .as_ptr()
+            .wrapping_byte_add(Self::used_ring_offset(self.size) + Self::RING_HEADER_LEN)
+            .cast::<VirtQueueUsedElement>()
+            .wrapping_add(usize::from(idx % self.size))
+    }
 }
 
 #[repr(C, align(16))]
@@ -532,25 +1777,30 @@ bitset::bitset!(
 
 #[repr(C)]
 #[derive(Default, Debug)]
-struct VirtQueueAvailableRing {
-    flags: u16,
-    index: u16,
-    ring: [u16; QUEUE_SIZE],
+struct VirtQueueUsedElement {
+    index: u32,
+    length: u32,
 }
 
-#[repr(C, align(4096))]
-#[derive(Default, Debug)]
-struct VirtQueueUsedRing {
-    flags: u16,
-    index: u16,
-    ring: [VirtQueueUsedElement; 16],
+/// A block request which has been started, and needs to be waited for.
+///
+/// Each token takes up one of the driver's request slots until it's waited for.
+#[must_use = "The request's slot is only freed once it's waited for"]
+#[derive(Debug)]
+pub struct BlockToken {
+    /// The slot holding the request.
+    slot: usize,
 }
 
-#[repr(C)]
-#[derive(Default, Debug)]
-struct VirtQueueUsedElement {
-    index: u32,
-    length: u32,
+/// The state of a slot for a block request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockSlot {
+    /// There's no request in the slot.
+    Free,
+    /// The device is working on the request, which starts at the given descriptor.
+    InFlight { head: u16 },
+    /// The device has finished the request, but it hasn't been waited for yet.
+    Finished,
 }
 
 #[derive(Debug)]
@@ -563,9 +1813,8 @@ struct BlockRequest {
     status: BlockRequestStatus,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[repr(u32)]
-#[expect(unused, reason = "todo")]
 enum BlockRequestType {
     Read = 0,
     Write = 1,
@@ -574,6 +1823,30 @@ enum BlockRequestType {
     WriteZeros = 13,
 }
 
+/// A range of sectors for a discard or write-zeroes request.
+#[derive(Debug, Clone, Copy)]
+struct BlockSegment {
+    /// The first sector in the range.
+    sector: u64,
+    /// The number of sectors in the range.
+    num_sectors: u32,
+    /// Flags for the request, which we leave clear.
+    flags: u32,
+}
+impl BlockSegment {
+    /// The length of a segment as the device sees it.
+    const LEN: usize = 16;
+
+    /// Get the bytes the device expects for this segment.
+    fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[..8].copy_from_slice(&self.sector.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.num_sectors.to_le_bytes());
+        bytes[12..].copy_from_slice(&self.flags.to_le_bytes());
+        bytes
+    }
+}
+
 bitset::bitset!(
     BlockRequestStatus(u8) {
         IoError = 0,
@@ -593,7 +1866,152 @@ impl BlockRequestStatus {
     }
 }
 
-const QUEUE_SIZE: usize = 16;
+/// The header preceding each frame sent to or received from a network device.
+///
+/// We don't negotiate any device features, so for legacy devices this is the whole header. Modern
+/// devices follow it with a 16-bit buffer count.
+///
+/// We don't use any offloading features, so we only need its size and always send it zeroed.
+#[repr(C)]
+struct NetHeader {
+    flags: u8,
+    gso_type: u8,
+    header_len: u16,
+    gso_size: u16,
+    checksum_start: u16,
+    checksum_offset: u16,
+}
+
+/// The header at the start of every GPU command and response.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct GpuCtrlHeader {
+    ty: u32,
+    flags: u32,
+    fence_id: u64,
+    context_id: u32,
+    padding: u32,
+}
+impl GpuCtrlHeader {
+    const CMD_GET_DISPLAY_INFO: u32 = 0x0100;
+    const CMD_RESOURCE_CREATE_2D: u32 = 0x0101;
+    const CMD_SET_SCANOUT: u32 = 0x0103;
+    const CMD_RESOURCE_FLUSH: u32 = 0x0104;
+    const CMD_TRANSFER_TO_HOST_2D: u32 = 0x0105;
+    const CMD_RESOURCE_ATTACH_BACKING: u32 = 0x0106;
+
+    const RESP_OK_NODATA: u32 = 0x1100;
+    const RESP_OK_DISPLAY_INFO: u32 = 0x1101;
+
+    /// Make a header for a command of the given type.
+    fn for_type(ty: u32) -> Self {
+        Self {
+            ty,
+            ..Self::default()
+        }
+    }
+}
+
+/// A response from the GPU device, which starts with a header.
+trait GpuResponse: Default {
+    fn header(&self) -> &GpuCtrlHeader;
+}
+impl GpuResponse for GpuCtrlHeader {
+    fn header(&self) -> &GpuCtrlHeader {
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct GpuRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// The response to [`GpuCtrlHeader::CMD_GET_DISPLAY_INFO`].
+#[repr(C)]
+#[derive(Debug, Default)]
+struct GpuDisplayInfo {
+    header: GpuCtrlHeader,
+    displays: [GpuDisplay; 16],
+}
+impl GpuResponse for GpuDisplayInfo {
+    fn header(&self) -> &GpuCtrlHeader {
+        &self.header
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct GpuDisplay {
+    rect: GpuRect,
+    enabled: u32,
+    flags: u32,
+}
+
+#[repr(C)]
+struct GpuResourceCreate2d {
+    header: GpuCtrlHeader,
+    resource_id: u32,
+    format: u32,
+    width: u32,
+    height: u32,
+}
+impl GpuResourceCreate2d {
+    /// Each pixel is blue, green, and red bytes, followed by an unused byte.
+    const FORMAT_B8G8R8X8_UNORM: u32 = 2;
+}
+
+/// Attach memory to a resource.
+///
+/// The device accepts any number of memory entries following the command, but our framebuffer is
+/// contiguous so we always send exactly one.
+#[repr(C)]
+struct GpuResourceAttachBacking {
+    header: GpuCtrlHeader,
+    resource_id: u32,
+    num_entries: u32,
+    address: u64,
+    length: u32,
+    padding: u32,
+}
+
+#[repr(C)]
+struct GpuSetScanout {
+    header: GpuCtrlHeader,
+    rect: GpuRect,
+    scanout_id: u32,
+    resource_id: u32,
+}
+
+#[repr(C)]
+struct GpuTransferToHost2d {
+    header: GpuCtrlHeader,
+    rect: GpuRect,
+    offset: u64,
+    resource_id: u32,
+    padding: u32,
+}
+
+#[repr(C)]
+struct GpuResourceFlush {
+    header: GpuCtrlHeader,
+    rect: GpuRect,
+    resource_id: u32,
+    padding: u32,
+}
+
+/// The largest Ethernet frame (without the frame check sequence) we send or receive.
+pub const MAX_FRAME_LEN: usize = 1514;
+
+/// The most entries we use in each virtqueue.
+///
+/// This can't be more than 32, so each queue's free descriptors fit in a `u32` bitmask.
+const MAX_QUEUE_SIZE: u16 = 16;
+const _: () = assert!(MAX_QUEUE_SIZE <= 32);
 
-/// The size of one sector on disk.
-pub const BLOCK_SECTOR_LEN: usize = 512;
+/// The most block requests we have in flight at once.
+const MAX_BLOCK_REQUESTS: usize = MAX_QUEUE_SIZE as usize / 3;
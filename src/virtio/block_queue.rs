@@ -0,0 +1,205 @@
+//! Queueing up block operations, so they can be merged into fewer requests to the device.
+
+use super::{BLOCK_SECTOR_LEN, BlockRequestType, BlockToken, MAX_BLOCK_REQUESTS, VirtioBlock};
+use crate::error::{ErrorKind, Result};
+
+/// The most operations a [`BlockQueue`] can hold.
+pub const MAX_QUEUED_OPS: usize = 32;
+
+/// A queue of sector reads and writes to run on a [`VirtioBlock`] together.
+///
+/// Operations are kept sorted by sector, like an elevator sweeping across the disk. When the queue
+/// is run, operations of the same kind on consecutive sectors are merged into one request, and as
+/// many requests as fit are submitted with a single notification to the device.
+///
+/// Since the device may run requests in any order, each sector can only appear once in a queue.
+pub struct BlockQueue<'b> {
+    /// The queued operations, sorted by sector.
+    ///
+    /// The first `len` entries are `Some`, and the rest are `None`.
+    ops: [Option<BlockOp<'b>>; MAX_QUEUED_OPS],
+    /// The number of queued operations.
+    len: usize,
+}
+impl<'b> BlockQueue<'b> {
+    /// Create an empty queue.
+    pub const fn new() -> Self {
+        Self {
+            ops: [const { None }; MAX_QUEUED_OPS],
+            len: 0,
+        }
+    }
+
+    /// Queue a read of `sector` into `buf`.
+    ///
+    /// Fails if the queue is full or already has an operation on `sector`.
+    pub fn read(&mut self, sector: u64, buf: &'b mut [u8; BLOCK_SECTOR_LEN]) -> Result<()> {
+        self.push(BlockOp::Read { sector, buf })
+    }
+
+    /// Queue a write of `data` to `sector`.
+    ///
+    /// Fails if the queue is full or already has an operation on `sector`.
+    pub fn write(&mut self, sector: u64, data: &'b [u8; BLOCK_SECTOR_LEN]) -> Result<()> {
+        self.push(BlockOp::Write { sector, data })
+    }
+
+    /// Add an operation to the queue, keeping it sorted.
+    fn push(&mut self, op: BlockOp<'b>) -> Result<()> {
+        if self.len == MAX_QUEUED_OPS {
+            return Err(ErrorKind::LimitReached.into());
+        }
+        let sector = op.sector();
+        let idx = self.queued().position(|queued| queued.sector() >= sector);
+        let idx = match idx {
+            Some(idx)
+                if self.ops[idx]
+                    .as_ref()
+                    .is_some_and(|op| op.sector() == sector) =>
+            {
+                return Err(ErrorKind::AlreadyExists.into());
+            }
+            Some(idx) => idx,
+            None => self.len,
+        };
+        // Shift the later operations back to make room.
+        self.ops[idx..=self.len].rotate_right(1);
+        self.ops[idx] = Some(op);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Iterate over the queued operations, in order.
+    fn queued(&self) -> impl Iterator<Item = &BlockOp<'b>> {
+        self.ops[..self.len].iter().flatten()
+    }
+
+    /// Get the number of operations, starting at `start`, which can be merged into one request of
+    /// at most `max_len` sectors.
+    fn run_len(&self, start: usize, max_len: usize) -> usize {
+        let Some(first) = self.ops[start].as_ref() else {
+            return 0;
+        };
+        self.queued()
+            .skip(start)
+            .take(max_len)
+            .zip(first.sector()..)
+            .take_while(|(op, sector)| op.is_write() == first.is_write() && op.sector() == *sector)
+            .count()
+    }
+}
+impl Default for BlockQueue<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An operation on one sector.
+enum BlockOp<'b> {
+    /// Read the sector into the buffer.
+    Read {
+        sector: u64,
+        buf: &'b mut [u8; BLOCK_SECTOR_LEN],
+    },
+    /// Write the data to the sector.
+    Write {
+        sector: u64,
+        data: &'b [u8; BLOCK_SECTOR_LEN],
+    },
+}
+impl BlockOp<'_> {
+    /// Get the sector this operates on.
+    fn sector(&self) -> u64 {
+        match self {
+            Self::Read { sector, .. } | Self::Write { sector, .. } => *sector,
+        }
+    }
+
+    /// Get whether this writes to the disk.
+    fn is_write(&self) -> bool {
+        matches!(self, Self::Write { .. })
+    }
+
+    /// Get the address of the buffer the device reads or writes.
+    fn buf_addr(&self) -> usize {
+        match self {
+            Self::Read { buf, .. } => buf.as_ptr().addr(),
+            Self::Write { data, .. } => data.as_ptr().addr(),
+        }
+    }
+}
+
+impl VirtioBlock<'_> {
+    /// Run all the operations in `queue`, waiting for them to finish and leaving it empty.
+    ///
+    /// The buffers must be in kernel memory (i.e. the physical and virtual addresses are the same).
+    /// If any operation fails, this still waits for the rest before returning the first error.
+    pub fn run_queue(&mut self, queue: &mut BlockQueue<'_>) -> Result<()> {
+        // Each request needs a descriptor for its header and status, and the rest can hold data.
+        let max_run_len = usize::from(self.queue_size) - 2;
+        let mut tokens = [const { None }; MAX_BLOCK_REQUESTS];
+        let mut num_in_flight = 0;
+        let mut result = Ok(());
+        let mut start = 0;
+        while start < queue.len {
+            let run_len = queue.run_len(start, max_run_len);
+            match self.start_run(queue, start, run_len) {
+                Ok(token) => {
+                    tokens[num_in_flight] = Some(token);
+                    num_in_flight += 1;
+                    start += run_len;
+                }
+                // We've run out of room on the device, so wait for what we've submitted so far.
+                Err(e) if matches!(e.kind, ErrorKind::LimitReached) && num_in_flight > 0 => {
+                    result = result.and(self.finish_all(&mut tokens));
+                    num_in_flight = 0;
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        result = result.and(self.finish_all(&mut tokens));
+        *queue = BlockQueue::new();
+        result
+    }
+
+    /// Start a request for the `run_len` operations in `queue` starting at `start`, without
+    /// notifying the device.
+    fn start_run(
+        &mut self,
+        queue: &BlockQueue<'_>,
+        start: usize,
+        run_len: usize,
+    ) -> Result<BlockToken> {
+        let first = queue.queued().nth(start).ok_or(ErrorKind::InvalidFormat)?;
+        let ty = if first.is_write() {
+            BlockRequestType::Write
+        } else {
+            BlockRequestType::Read
+        };
+        let slot = self.claim_slot(ty, first.sector(), None)?;
+        let data_addrs = queue
+            .queued()
+            .skip(start)
+            .take(run_len)
+            .map(|op| (op.buf_addr(), BLOCK_SECTOR_LEN));
+        // SAFETY:
+        // The buffers are borrowed by the queue, which our caller doesn't let go of until the
+        // request has been waited for.
+        unsafe { self.submit_slot(slot, data_addrs, false) }
+    }
+
+    /// Notify the device of the submitted requests and wait for them all to finish.
+    ///
+    /// Returns the first error from any of them.
+    fn finish_all(&mut self, tokens: &mut [Option<BlockToken>]) -> Result<()> {
+        self.virtio.notify(0);
+        let mut result = Ok(());
+        for token in tokens.iter_mut().filter_map(Option::take) {
+            result = result.and(self.wait_for(token).status.success());
+        }
+        result
+    }
+}
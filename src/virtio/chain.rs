@@ -0,0 +1,155 @@
+//! Building chains of descriptors for requests to a device.
+
+use super::{DescriptorFlags, VirtQueueDescriptor, VirtQueueUsedElement, Virtio};
+use crate::error::Result;
+
+/// A chain of descriptors being built up for one request on a queue.
+///
+/// Each buffer pushed takes a free descriptor from the queue, which gets linked after the previous
+/// one. If the chain is dropped without being submitted, its descriptors are freed again.
+pub(super) struct DescriptorChain<'v, 'a, const NUM_QUEUES: usize> {
+    /// The device the request is for.
+    virtio: &'v mut Virtio<'a, NUM_QUEUES>,
+    /// The queue the request goes in.
+    queue_num: u32,
+    /// The index of the first descriptor in the chain.
+    head: Option<u16>,
+    /// The index and contents of the last descriptor in the chain.
+    ///
+    /// This isn't written to the queue until we know whether another descriptor follows it.
+    tail: Option<(u16, VirtQueueDescriptor)>,
+    /// A bitmask of the descriptors in the chain, which we free if the chain is dropped.
+    allocated: u32,
+}
+impl<'v, 'a, const NUM_QUEUES: usize> DescriptorChain<'v, 'a, NUM_QUEUES> {
+    /// Start building an empty chain for the given queue.
+    pub(super) fn new(virtio: &'v mut Virtio<'a, NUM_QUEUES>, queue_num: u32) -> Self {
+        Self {
+            virtio,
+            queue_num,
+            head: None,
+            tail: None,
+            allocated: 0,
+        }
+    }
+
+    /// Add a buffer the device reads from, at the given physical address.
+    ///
+    /// Fails if the queue has no free descriptors left.
+    pub(super) fn push_readable(&mut self, address: usize, len: usize) -> Result<()> {
+        self.push(address, len, DescriptorFlags::empty())
+    }
+
+    /// Add a buffer the device writes to, at the given physical address.
+    ///
+    /// Fails if the queue has no free descriptors left.
+    pub(super) fn push_writable(&mut self, address: usize, len: usize) -> Result<()> {
+        self.push(address, len, DescriptorFlags::WRITE)
+    }
+
+    /// Add a buffer with the given flags to the end of the chain.
+    fn push(&mut self, address: usize, len: usize, flags: DescriptorFlags) -> Result<()> {
+        let idx = self.virtio.alloc_descriptor(self.queue_num)?;
+        self.allocated |= 1 << idx;
+        if let Some((prev_idx, mut prev)) = self.tail.take() {
+            prev.flags = prev.flags | DescriptorFlags::NEXT;
+            prev.next = idx;
+            // SAFETY: The descriptor is part of this chain, which the device doesn't have yet.
+            unsafe { self.virtio.write_descriptor(self.queue_num, prev_idx, prev) };
+        } else {
+            self.head = Some(idx);
+        }
+        self.tail = Some((
+            idx,
+            VirtQueueDescriptor {
+                address: address as u64,
+                length: len as u32,
+                flags,
+                next: 0,
+            },
+        ));
+        Ok(())
+    }
+
+    /// Make the request available to the device, without waiting for it to finish.
+    ///
+    /// Returns the index of the chain's first descriptor, which identifies the request when it
+    /// shows up in [`Virtio::pop_used`]. Once it does, the caller should free the chain with
+    /// [`Virtio::free_chain`].
+    ///
+    /// # Panics
+    /// Panics if the chain is empty.
+    ///
+    /// # Safety
+    /// The device will read and/or write the buffers in the chain. The caller is responsible for
+    /// ensuring that these reads and writes do not violate Rust's memory model until the request
+    /// shows up in [`Virtio::pop_used`].
+    pub(super) unsafe fn submit(mut self) -> u16 {
+        // SAFETY: Our caller upholds the requirements on the buffers.
+        let head = unsafe { self.make_available() };
+        self.virtio.notify(self.queue_num);
+        head
+    }
+
+    /// Like [`Self::submit`], but without notifying the device.
+    ///
+    /// This lets several requests be submitted with one call to [`Virtio::notify`] afterwards.
+    ///
+    /// # Panics
+    /// Panics if the chain is empty.
+    ///
+    /// # Safety
+    /// The same as for [`Self::submit`].
+    pub(super) unsafe fn submit_without_notify(mut self) -> u16 {
+        // SAFETY: Our caller upholds the requirements on the buffers.
+        unsafe { self.make_available() }
+    }
+
+    /// Run the request, waiting until the device finishes it and freeing the chain afterwards.
+    ///
+    /// This expects to be the only request in flight on its queue.
+    ///
+    /// # Panics
+    /// Panics if the chain is empty.
+    ///
+    /// # Safety
+    /// The device will read and/or write the buffers in the chain. The caller is responsible for
+    /// ensuring that these reads and writes do not violate Rust's memory model.
+    pub(super) unsafe fn run(mut self) -> VirtQueueUsedElement {
+        // SAFETY: We wait for the device to finish before returning, per our precondition.
+        let head = unsafe { self.make_available() };
+        self.virtio.notify(self.queue_num);
+        let used = self.virtio.wait_for_used(self.queue_num);
+        debug_assert_eq!(used.index, u32::from(head));
+        self.virtio.free_chain(self.queue_num, head);
+        used
+    }
+
+    /// Put the chain in the available ring, leaving `self` empty.
+    ///
+    /// # Safety
+    /// The same as for [`Self::submit`].
+    unsafe fn make_available(&mut self) -> u16 {
+        let (head, (tail_idx, tail)) = self
+            .head
+            .take()
+            .zip(self.tail.take())
+            .expect("Can't submit an empty descriptor chain");
+        // SAFETY: The descriptor is part of this chain, which the device doesn't have yet.
+        unsafe { self.virtio.write_descriptor(self.queue_num, tail_idx, tail) };
+        // The device owns the descriptors now, so we mustn't free them when we're dropped.
+        self.allocated = 0;
+        // SAFETY: Our caller upholds the requirements on the buffers.
+        unsafe { self.virtio.make_available(self.queue_num, head) };
+        head
+    }
+}
+impl<const NUM_QUEUES: usize> Drop for DescriptorChain<'_, '_, NUM_QUEUES> {
+    fn drop(&mut self) {
+        for idx in 0..u32::BITS as u16 {
+            if self.allocated & (1 << idx) != 0 {
+                self.virtio.free_descriptor(self.queue_num, idx);
+            }
+        }
+    }
+}
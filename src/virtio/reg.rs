@@ -6,23 +6,31 @@ register!(
     Magic(u32, 0x000, R),
     Version(u32, 0x004, R),
     DeviceId(u32, 0x008, R),
-    DeviceFeatures(DeviceFeatureFlags, 0x010, R),
-    DeviceFeaturesSelect(DeviceFeatureFlags, 0x014, W),
+    DeviceFeatures(u32, 0x010, R),
+    DeviceFeaturesSelect(u32, 0x014, W),
+    DriverFeatures(u32, 0x020, W),
+    DriverFeaturesSelect(u32, 0x024, W),
     QueueSelect(u32, 0x030, W),
+    QueueSizeMax(u32, 0x034, R),
     QueueSize(u32, 0x038, W),
     QueuePfn(u32, 0x040, RW),
     QueueReady(u32, 0x044, RW),
     QueueNotify(u32, 0x050, W),
+    InterruptStatus(u32, 0x060, R),
+    InterruptAck(u32, 0x064, W),
     DeviceStatus(DeviceStatusFlags, 0x070, RW),
-    /* These aren't available for legacy devices
+    // These are only available for modern (version 2) devices.
     QueueDescriptorLow(u32, 0x080, W),
     QueueDescriptorHigh(u32, 0x084, W),
     QueueAvailableLow(u32, 0x090, W),
     QueueAvailableHigh(u32, 0x094, W),
     QueueUsedLow(u32, 0x0A0, W),
     QueueUsedHigh(u32, 0x0A4, W),
-    */
     Capacity(u64, 0x100, R),
+    NetMac([u8; 6], 0x100, R),
+    BlockSize(u32, 0x114, R),
+    MaxDiscardSectors(u32, 0x124, R),
+    MaxWriteZeroesSectors(u32, 0x130, R),
 );
 
 bitset::bitset!(
@@ -36,6 +44,11 @@ bitset::bitset!(
     }
 );
 
+/// The `VIRTIO_F_VERSION_1` feature, which modern devices require us to accept.
+///
+/// This is feature bit 32, so it's the lowest bit of the second word of features.
+pub(super) const FEATURE_VERSION_1_HIGH: u32 = 1;
+
 bitset::bitset!(
     pub(super) DeviceFeatureFlags(u32) {
         SizeMax = 1,
@@ -61,35 +74,21 @@ macro_rules! register {
         unsafe impl VirtioBlockRegister for $regname {
             const OFFSET: usize = $regoffset;
             type RegTy = $regty;
-
-            const READABLE: bool = readable!($rw);
-            const WRITABLE: bool = writable!($rw);
+            type Access = access!($rw);
         }
     )*};
 }
 
-macro_rules! readable {
-    (R) => {
-        true
-    };
-    (W) => {
-        false
-    };
-    (RW) => {
-        true
-    };
-}
-
-macro_rules! writable {
+macro_rules! access {
     (R) => {
-        false
+        util::mmio::ReadOnly
     };
     (W) => {
-        true
+        util::mmio::WriteOnly
     };
     (RW) => {
-        true
+        util::mmio::ReadWrite
     };
 }
 
-use {readable, register, writable};
+use {access, register};
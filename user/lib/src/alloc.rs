@@ -1,8 +1,17 @@
 //! An allocator implementation.
 //!
 //! See [`ALLOCATOR`] for details on the global allocator.
+//!
+//! To track down leaks or heap growth, [`Allocator::stats`] reports how much memory each size class
+//! has handed out. [`Allocator::set_poisoning`] fills memory with [`ALLOC_POISON`] when it's
+//! allocated and [`FREE_POISON`] when it's freed, so reading uninitialized or freed memory shows up
+//! as a recognizable pattern rather than whatever was there before.
 
-use core::{alloc::GlobalAlloc, ptr::NonNull};
+use core::{
+    alloc::GlobalAlloc,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use crate::sync::SpinLock;
 
@@ -12,15 +21,24 @@ pub static ALLOCATOR: Allocator = Allocator::new();
 
 /// An implementation of an allocator.
 ///
-/// This allocator has specific size classes for powers of two up to a page size, beyond which the
-/// backing memory is `mmap`ed. This comes with a relatively high potential for overhead, since
+/// This allocator has specific size classes for powers of two up to half a page size, beyond which
+/// the backing memory is `mmap`ed. This comes with a relatively high potential for overhead, since
 /// almost half of the assigned memory can go unused if allocations at the wrong size are chosen.
 ///
-/// This allocator is thread-safe, but may have poor performance if several threads attempt to use
-/// it to allocate memory at the same time.
+/// Each size class hands out blocks from slabs of memory it `mmap`s. Once everything in a slab has
+/// been freed, the slab is given back to the kernel, unless it's the only one in its size class
+/// with space left (so allocating and freeing in a loop doesn't map and unmap memory every time).
+///
+/// This allocator is thread-safe. For [`ALLOCATOR`], each thread also keeps a small magazine of
+/// blocks for each size class (see [`with_thread_cache`]), which is refilled from and flushed to
+/// the size class in batches, so most allocations don't need to take a lock.
 pub struct Allocator {
     /// Each size class gets its own separate logic.
     classes: [SpinLock<FixedSizeAllocator>; NUM_SIZE_CLASSES],
+    /// Statistics for allocations too large for any size class, which are `mmap`ed directly.
+    large: SpinLock<ClassStats>,
+    /// Whether to poison memory when it's allocated and freed.
+    poisoning: AtomicBool,
 }
 impl Allocator {
     /// Create a new allocator.
@@ -28,9 +46,42 @@ impl Allocator {
     pub const fn new() -> Self {
         Self {
             classes: [const { SpinLock::new(FixedSizeAllocator::new()) }; NUM_SIZE_CLASSES],
+            large: SpinLock::new(ClassStats::new()),
+            poisoning: AtomicBool::new(false),
         }
     }
 
+    /// Get a snapshot of how much memory this allocator has handed out.
+    ///
+    /// Each size class is locked in turn, so allocations made by other threads while this runs may
+    /// be counted in some size classes and not others. Other threads' allocations from their
+    /// magazines aren't counted until they next refill or flush them.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        let mut cache = self.thread_cache();
+        Stats {
+            classes: core::array::from_fn(|class| {
+                let mut allocator = self.classes[class].lock();
+                if let Some(cache) = &mut cache {
+                    cache.magazines[class]
+                        .sync_stats(&mut allocator.stats, MIN_SIZE_CLASS << class);
+                }
+                allocator.stats
+            }),
+            large: *self.large.lock(),
+        }
+    }
+
+    /// Set whether to poison memory when it's allocated and freed.
+    ///
+    /// While this is enabled, every allocation is filled with [`ALLOC_POISON`] before it's handed
+    /// out, and every block in a size class is filled with [`FREE_POISON`] when it's freed (except
+    /// for the first word, which the allocator uses to track free blocks). This slows down every
+    /// allocation, so it's off by default.
+    pub fn set_poisoning(&self, enabled: bool) {
+        self.poisoning.store(enabled, Ordering::Relaxed);
+    }
+
     /// Request to allocate for a given layout.
     ///
     /// The given allocation (which may be larger than requested) is returned as a slice.
@@ -50,19 +101,21 @@ impl Allocator {
             ));
         }
         let size = layout.size().max(layout.align());
-        let Some((size_class, raw_size)) = class_for_size(size) else {
-            if layout.align() > 4096 {
-                todo!("greater than page alignment");
-            }
-            return crate::sys::mmap(size)
-                .map(|head_ptr| NonNull::slice_from_raw_parts(head_ptr.cast::<u8>(), size))
-                .ok();
+        let (head_ptr, raw_size) = if let Some((size_class, raw_size)) = class_for_size(size) {
+            (self.allocate_block(size_class, raw_size)?, raw_size)
+        } else {
+            let raw_size = size.checked_next_multiple_of(PAGE_SIZE)?;
+            let head_ptr = map_aligned(raw_size, layout.align())?;
+            let mut stats = self.large.lock();
+            stats.record_allocation(raw_size);
+            stats.pages_mapped += raw_size / PAGE_SIZE;
+            (head_ptr, raw_size)
         };
-        // SAFETY:
-        // `class_for_size` always returns the same size for a given size class, so we meet the
-        // precondition.
-        let head_ptr = unsafe { self.classes[size_class].lock().allocate(raw_size) };
-        Some(NonNull::slice_from_raw_parts(head_ptr?.cast(), raw_size))
+        if self.poisoning.load(Ordering::Relaxed) {
+            // SAFETY: We just allocated this many bytes here, and nothing else is using them yet.
+            unsafe { head_ptr.cast::<u8>().write_bytes(ALLOC_POISON, raw_size) };
+        }
+        Some(NonNull::slice_from_raw_parts(head_ptr.cast(), raw_size))
     }
 
     /// Deallocate a given allocation.
@@ -74,15 +127,109 @@ impl Allocator {
             return;
         }
         let size = layout.size().max(layout.align());
-        let Some((size_class, _raw_size)) = class_for_size(size) else {
+        let Some((size_class, raw_size)) = class_for_size(size) else {
             // SAFETY:
             // For this layout, we called `mmap` to allocate, so we can call `munmap` to free.
             _ = unsafe { crate::sys::munmap(ptr, size) };
+            // This can't overflow, since it didn't when we allocated.
+            let raw_size = size.next_multiple_of(PAGE_SIZE);
+            let mut stats = self.large.lock();
+            stats.record_free(raw_size);
+            stats.pages_mapped -= raw_size / PAGE_SIZE;
             return;
         };
+        if self.poisoning.load(Ordering::Relaxed) {
+            // SAFETY:
+            // The caller is giving the block back to us, so nothing else uses it anymore.
+            unsafe { ptr.cast::<u8>().write_bytes(FREE_POISON, raw_size) };
+        }
         // SAFETY:
         // We allocated from the same size class originally.
-        unsafe { self.classes[size_class].lock().deallocate(ptr) };
+        unsafe { self.deallocate_block(ptr, size_class, raw_size) };
+    }
+
+    /// Get a block from the given size class, preferring the current thread's magazine.
+    ///
+    /// `raw_size` must be the size [`class_for_size`] gives for `size_class`.
+    fn allocate_block(&self, size_class: usize, raw_size: usize) -> Option<NonNull<()>> {
+        let Some(cache) = self.thread_cache() else {
+            let mut allocator = self.classes[size_class].lock();
+            // SAFETY:
+            // `class_for_size` always returns the same size for a given size class, so we meet the
+            // precondition.
+            let block = unsafe { allocator.allocate(raw_size) }?;
+            allocator.stats.record_blocks(raw_size, 1, 0);
+            return Some(block);
+        };
+        let magazine = &mut cache.magazines[size_class];
+        if magazine.len == 0 {
+            let mut allocator = self.classes[size_class].lock();
+            magazine.sync_stats(&mut allocator.stats, raw_size);
+            // Only fill half of the magazine, so there's room for frees without flushing.
+            while magazine.len < MAGAZINE_SIZE / 2 {
+                // SAFETY: As above.
+                let Some(block) = (unsafe { allocator.allocate(raw_size) }) else {
+                    break;
+                };
+                magazine.blocks[magazine.len] = Some(block);
+                magazine.len += 1;
+            }
+        }
+        magazine.len = magazine.len.checked_sub(1)?;
+        magazine.allocations += 1;
+        magazine.blocks[magazine.len].take()
+    }
+
+    /// Give back a block to the given size class, preferring the current thread's magazine.
+    ///
+    /// # Safety
+    /// `ptr` must have been returned from [`Self::allocate_block`] with the same size class and
+    /// `raw_size`, and not be used again.
+    unsafe fn deallocate_block(&self, ptr: NonNull<()>, size_class: usize, raw_size: usize) {
+        let Some(cache) = self.thread_cache() else {
+            let mut allocator = self.classes[size_class].lock();
+            // SAFETY: By method precondition, the block came from this size class.
+            unsafe { allocator.deallocate(ptr, raw_size) };
+            allocator.stats.record_blocks(raw_size, 0, 1);
+            return;
+        };
+        let magazine = &mut cache.magazines[size_class];
+        if magazine.len == MAGAZINE_SIZE {
+            let mut allocator = self.classes[size_class].lock();
+            magazine.sync_stats(&mut allocator.stats, raw_size);
+            // SAFETY: Blocks in magazines come from their size class.
+            unsafe { magazine.flush(&mut allocator, raw_size, MAGAZINE_SIZE / 2) };
+        }
+        magazine.blocks[magazine.len] = Some(ptr);
+        magazine.len += 1;
+        magazine.frees += 1;
+    }
+
+    /// Get the current thread's cache, if it has one.
+    ///
+    /// Only [`ALLOCATOR`] uses the caches, since they hold its blocks.
+    #[expect(
+        clippy::mut_from_ref,
+        reason = "Each thread's cache is only used by that thread, and not across calls"
+    )]
+    fn thread_cache(&self) -> Option<&mut ThreadCache> {
+        if !core::ptr::eq(self, &raw const ALLOCATOR) {
+            return None;
+        }
+        let cache: usize;
+        // SAFETY: This only copies a register.
+        unsafe {
+            core::arch::asm!(
+                "mv {cache}, tp",
+                cache = out(reg) cache,
+                options(nomem, nostack, preserves_flags),
+            );
+        }
+        let mut cache = NonNull::new(core::ptr::with_exposed_provenance_mut::<ThreadCache>(cache))?;
+        // SAFETY:
+        // `with_thread_cache` only puts a cache in `tp` while it lives, and each thread's cache is
+        // only used by that thread, within one allocator call at a time.
+        Some(unsafe { cache.as_mut() })
     }
 }
 
@@ -110,6 +257,194 @@ unsafe impl GlobalAlloc for Allocator {
     }
 }
 
+/// Run `f` with a magazine of blocks for each size class of [`ALLOCATOR`], which only this thread
+/// uses.
+///
+/// Each thread's entry point runs the rest of the thread inside this. The thread pointer register
+/// (`tp`) points at the cache while `f` runs, and the cached blocks are given back to their size
+/// classes once it returns. A thread which exits from within `f` never gives them back.
+pub(crate) fn with_thread_cache<T>(f: impl FnOnce() -> T) -> T {
+    let mut cache = ThreadCache {
+        magazines: [const { Magazine::new() }; NUM_SIZE_CLASSES],
+    };
+    set_thread_pointer(core::ptr::from_mut(&mut cache).expose_provenance());
+    let result = f();
+    set_thread_pointer(0);
+    for (class, magazine) in cache.magazines.iter_mut().enumerate() {
+        let mut allocator = ALLOCATOR.classes[class].lock();
+        let size = MIN_SIZE_CLASS << class;
+        magazine.sync_stats(&mut allocator.stats, size);
+        // SAFETY: Blocks in magazines come from their size class.
+        unsafe { magazine.flush(&mut allocator, size, magazine.len) };
+    }
+    result
+}
+
+/// Set the thread pointer register (`tp`), which points at the current thread's [`ThreadCache`].
+fn set_thread_pointer(value: usize) {
+    // SAFETY:
+    // Nothing else in user code uses `tp`, and the kernel saves and restores it for each thread.
+    unsafe {
+        core::arch::asm!(
+            "mv tp, {value}",
+            value = in(reg) value,
+            options(nostack, preserves_flags),
+        );
+    }
+}
+
+/// The most blocks a thread keeps in its magazine for each size class.
+const MAGAZINE_SIZE: usize = 16;
+
+/// A thread's magazines, one for each size class.
+struct ThreadCache {
+    /// The magazine for each size class, smallest first.
+    magazines: [Magazine; NUM_SIZE_CLASSES],
+}
+
+/// Blocks of one size class which a thread keeps on hand, so it can allocate and free them without
+/// locking the size class.
+struct Magazine {
+    /// The blocks on hand, which are the first `len` entries.
+    blocks: [Option<NonNull<()>>; MAGAZINE_SIZE],
+    /// The number of blocks on hand.
+    len: usize,
+    /// The number of allocations from this magazine which the size class hasn't counted yet.
+    allocations: u64,
+    /// The number of frees into this magazine which the size class hasn't counted yet.
+    frees: u64,
+}
+impl Magazine {
+    /// Create an empty magazine.
+    const fn new() -> Self {
+        Self {
+            blocks: [None; MAGAZINE_SIZE],
+            len: 0,
+            allocations: 0,
+            frees: 0,
+        }
+    }
+
+    /// Add the allocations and frees this magazine has made to the size class's statistics.
+    fn sync_stats(&mut self, stats: &mut ClassStats, size: usize) {
+        stats.record_blocks(size, self.allocations, self.frees);
+        self.allocations = 0;
+        self.frees = 0;
+    }
+
+    /// Give `count` blocks back to the size class, from the top of the magazine.
+    ///
+    /// # Safety
+    /// The blocks must have come from `allocator`, which allocates blocks of `size` bytes.
+    unsafe fn flush(&mut self, allocator: &mut FixedSizeAllocator, size: usize, count: usize) {
+        for _ in 0..count {
+            self.len -= 1;
+            let block = self.blocks[self.len]
+                .take()
+                .expect("Blocks below `len` are on hand");
+            // SAFETY: By method precondition, the block came from this allocator.
+            unsafe { allocator.deallocate(block, size) };
+        }
+    }
+}
+
+/// The byte that fills newly-allocated memory while [`Allocator::set_poisoning`] is enabled.
+pub const ALLOC_POISON: u8 = 0xaa;
+
+/// The byte that fills freed memory while [`Allocator::set_poisoning`] is enabled.
+pub const FREE_POISON: u8 = 0xdd;
+
+/// A snapshot of an [`Allocator`]'s statistics, from [`Allocator::stats`].
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    /// The statistics for each size class, smallest first.
+    ///
+    /// Use [`Stats::size_classes`] to get the block size for each one.
+    pub classes: [ClassStats; NUM_SIZE_CLASSES],
+    /// The statistics for allocations too large for any size class, which are `mmap`ed directly.
+    pub large: ClassStats,
+}
+impl Stats {
+    /// Iterate over the block size of each size class, along with its statistics.
+    pub fn size_classes(&self) -> impl Iterator<Item = (usize, &ClassStats)> {
+        self.classes
+            .iter()
+            .enumerate()
+            .map(|(class, stats)| (MIN_SIZE_CLASS << class, stats))
+    }
+
+    /// Add up the statistics for every size class, including large allocations.
+    #[must_use]
+    pub fn total(&self) -> ClassStats {
+        self.classes
+            .iter()
+            .fold(self.large, |total, stats| ClassStats {
+                allocations: total.allocations + stats.allocations,
+                frees: total.frees + stats.frees,
+                bytes_live: total.bytes_live + stats.bytes_live,
+                pages_mapped: total.pages_mapped + stats.pages_mapped,
+            })
+    }
+}
+
+/// Statistics for one size class of an [`Allocator`].
+///
+/// Sizes count the memory handed out, which includes the rounding up to the size class (or to a
+/// whole page, for large allocations).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassStats {
+    /// The number of allocations made.
+    pub allocations: u64,
+    /// The number of allocations freed.
+    pub frees: u64,
+    /// The number of bytes currently allocated.
+    pub bytes_live: usize,
+    /// The number of pages currently mapped to hold these allocations.
+    pub pages_mapped: usize,
+}
+impl ClassStats {
+    /// Create statistics for a size class which hasn't allocated anything yet.
+    const fn new() -> Self {
+        Self {
+            allocations: 0,
+            frees: 0,
+            bytes_live: 0,
+            pages_mapped: 0,
+        }
+    }
+
+    /// Get the number of allocations which haven't been freed yet.
+    #[must_use]
+    pub const fn live(&self) -> u64 {
+        // Threads' counts catch up separately, so one thread's free can briefly be counted before
+        // another thread's allocation of the same block.
+        self.allocations.saturating_sub(self.frees)
+    }
+
+    /// Count an allocation of `size` bytes.
+    fn record_allocation(&mut self, size: usize) {
+        self.allocations += 1;
+        self.bytes_live += size;
+    }
+
+    /// Count freeing an allocation of `size` bytes.
+    fn record_free(&mut self, size: usize) {
+        self.frees += 1;
+        self.bytes_live -= size;
+    }
+
+    /// Count some allocations and frees of blocks in a size class of `size` bytes.
+    fn record_blocks(&mut self, size: usize, allocations: u64, frees: u64) {
+        self.allocations += allocations;
+        self.frees += frees;
+        // Every block in a size class is the same size, and the live ones all fit in memory.
+        self.bytes_live = usize::try_from(self.live()).map_or(usize::MAX, |live| live * size);
+    }
+}
+
+/// The size of a page of memory, which is what `mmap` hands out.
+const PAGE_SIZE: usize = 4096;
+
 /// The smallest size class we make a separate allocation for.
 ///
 /// Allocations smaller than this limit get rounded up to this value.
@@ -127,6 +462,9 @@ const NUM_SIZE_CLASSES: usize = {
     num
 };
 
+/// The fewest blocks a slab holds, so that the header doesn't waste too much of it.
+const MIN_BLOCKS_PER_SLAB: usize = 8;
+
 /// Get the size class and raw allocation size for this pointer.
 ///
 /// The first element is the size class index and the second number is the raw allocation size.
@@ -141,19 +479,54 @@ fn class_for_size(size: usize) -> Option<(usize, usize)> {
     ))
 }
 
+/// Get the size of the slabs to use for blocks of the given size.
+///
+/// This is always a power of two, and a multiple of the page size.
+fn slab_size(size: usize) -> usize {
+    (size * MIN_BLOCKS_PER_SLAB).max(PAGE_SIZE)
+}
+
+/// `mmap` `size` bytes (which must be a multiple of the page size), aligned to `align`.
+///
+/// For alignments greater than a page, we map extra and then unmap the parts on either side.
+fn map_aligned(size: usize, align: usize) -> Option<NonNull<()>> {
+    if align <= PAGE_SIZE {
+        return crate::sys::mmap(size).ok();
+    }
+    let mapped_size = size.checked_add(align - PAGE_SIZE)?;
+    let mapped_ptr = crate::sys::mmap(mapped_size).ok()?;
+    let head_len = mapped_ptr.addr().get().next_multiple_of(align) - mapped_ptr.addr().get();
+    let tail_len = mapped_size - head_len - size;
+    // SAFETY: This is within the mapping.
+    let aligned_ptr = unsafe { mapped_ptr.byte_add(head_len) };
+    if head_len > 0 {
+        // SAFETY: We just mapped this, and don't use it.
+        _ = unsafe { crate::sys::munmap(mapped_ptr, head_len) };
+    }
+    if tail_len > 0 {
+        // SAFETY: We just mapped this, and don't use it.
+        _ = unsafe { crate::sys::munmap(aligned_ptr.byte_add(size), tail_len) };
+    }
+    Some(aligned_ptr)
+}
+
 /// An allocator which only ever allocates blocks of a given size.
 struct FixedSizeAllocator {
-    /// A pointer to a list of "freed" blocks which we can reuse.
-    free_list: Option<NonNull<FreeListNode>>,
-    /// A pointer to the next "fresh" address to allocate from.
-    fresh_head: *mut (),
+    /// The slabs which have blocks free, linked through their headers.
+    ///
+    /// Slabs whose blocks are all allocated aren't tracked until one is freed.
+    partial_slabs: Option<NonNull<SlabHeader>>,
+    /// Statistics about the allocations made from this size class.
+    ///
+    /// [`Allocator`] counts allocations and frees here, since blocks can sit in threads' magazines.
+    stats: ClassStats,
 }
 impl FixedSizeAllocator {
     /// Create a new fixed-size allocator with no backing memory yet.
     const fn new() -> Self {
         Self {
-            free_list: None,
-            fresh_head: core::ptr::null_mut(),
+            partial_slabs: None,
+            stats: ClassStats::new(),
         }
     }
 
@@ -167,44 +540,157 @@ impl FixedSizeAllocator {
     /// [`FixedSizeAllocator`].
     unsafe fn allocate(&mut self, size: usize) -> Option<NonNull<()>> {
         assert!(size >= size_of::<FreeListNode>());
-        if let Some(free_head) = self.free_list {
+        let slab = if let Some(slab) = self.partial_slabs {
+            slab
+        } else {
+            let slab = SlabHeader::create(size)?;
+            self.stats.pages_mapped += slab_size(size) / PAGE_SIZE;
+            // SAFETY: The slab was just created, so it isn't in the list.
+            unsafe { self.push_slab(slab) };
+            slab
+        };
+        // SAFETY:
+        // Slabs in our list are valid, and nothing else references their headers.
+        let header = unsafe { &mut *slab.as_ptr() };
+        let block = if let Some(free_head) = header.free_list {
             // SAFETY:
             // The free list contains valid values, so we can read them.
-            self.free_list = unsafe { free_head.as_ref() }.next;
-            return Some(free_head.cast());
-        }
-        if self.fresh_head.addr().is_multiple_of(4096) {
-            self.fresh_head = crate::sys::mmap(4096).ok()?.as_ptr();
+            header.free_list = unsafe { free_head.as_ref() }.next;
+            free_head.cast()
+        } else {
+            // SAFETY:
+            // The slab isn't full, so the next fresh block is within it.
+            let block = unsafe { slab.cast::<()>().byte_add(usize::from(header.fresh) * size) };
+            header.fresh += 1;
+            block
+        };
+        header.live += 1;
+        if header.is_full(size) {
+            // SAFETY: The slab was in the list, since it had space.
+            unsafe { self.unlink_slab(slab) };
         }
-        // SAFETY:
-        // Null pointers are a multiple of 4096, so we'd hit the above branch and grab a new
-        // page to use.
-        let ret_ptr = unsafe { NonNull::new_unchecked(self.fresh_head) };
-        self.fresh_head = self.fresh_head.wrapping_byte_add(size);
-        Some(ret_ptr)
+        Some(block)
     }
 
     /// Free the given pointer.
     ///
     /// # Safety
-    /// This pointer must have been returned by [`Self::allocate`] called on this object. This
-    /// function takes ownership over the allocation, so the pointer must not be used again except
-    /// through this allocator returning it again from [`Self::allocate`].
-    unsafe fn deallocate(&mut self, ptr: NonNull<()>) {
+    /// This pointer must have been returned by [`Self::allocate`] called on this object with the
+    /// same `size`. This function takes ownership over the allocation, so the pointer must not be
+    /// used again except through this allocator returning it again from [`Self::allocate`].
+    unsafe fn deallocate(&mut self, ptr: NonNull<()>, size: usize) {
+        // SAFETY:
+        // Slabs are aligned to their size, so this is the start of the slab the block is in.
+        let slab = unsafe { ptr.byte_sub(ptr.addr().get() % slab_size(size)) }.cast::<SlabHeader>();
+        // SAFETY:
+        // The slab is valid since it has an allocation in it, and nothing else references its
+        // header.
+        let header = unsafe { &mut *slab.as_ptr() };
+        let was_full = header.is_full(size);
         let ptr = ptr.cast::<FreeListNode>();
         // SAFETY:
         // Our allocations are large enough to store this (and aligned for it).
         unsafe {
             ptr.write(FreeListNode {
-                next: self.free_list,
+                next: header.free_list,
             });
         }
-        self.free_list = Some(ptr);
+        header.free_list = Some(ptr);
+        header.live -= 1;
+        let is_only_partial = self.partial_slabs == Some(slab) && header.next.is_none();
+        if was_full {
+            // SAFETY: Full slabs aren't in the list.
+            unsafe { self.push_slab(slab) };
+        } else if header.live == 0 && !is_only_partial {
+            // SAFETY: The slab had space, so it's in the list.
+            unsafe { self.unlink_slab(slab) };
+            // SAFETY:
+            // We mapped the slab with this size, and nothing is allocated from it anymore.
+            _ = unsafe { crate::sys::munmap(slab.cast(), slab_size(size)) };
+            self.stats.pages_mapped -= slab_size(size) / PAGE_SIZE;
+        }
+    }
+
+    /// Add a slab to the front of the list of slabs with free blocks.
+    ///
+    /// # Safety
+    /// `slab` must be a valid slab which isn't in the list.
+    unsafe fn push_slab(&mut self, slab: NonNull<SlabHeader>) {
+        // SAFETY: By method precondition, the slab is valid.
+        let header = unsafe { &mut *slab.as_ptr() };
+        header.prev = None;
+        header.next = self.partial_slabs;
+        if let Some(mut old_head) = self.partial_slabs {
+            // SAFETY: Slabs in the list are valid.
+            unsafe { old_head.as_mut() }.prev = Some(slab);
+        }
+        self.partial_slabs = Some(slab);
+    }
+
+    /// Remove a slab from the list of slabs with free blocks.
+    ///
+    /// # Safety
+    /// `slab` must be in the list.
+    unsafe fn unlink_slab(&mut self, slab: NonNull<SlabHeader>) {
+        // SAFETY: By method precondition, the slab is valid.
+        let header = unsafe { &mut *slab.as_ptr() };
+        match header.prev {
+            // SAFETY: Slabs in the list are valid.
+            Some(mut prev) => unsafe { prev.as_mut() }.next = header.next,
+            None => self.partial_slabs = header.next,
+        }
+        if let Some(mut next) = header.next {
+            // SAFETY: Slabs in the list are valid.
+            unsafe { next.as_mut() }.prev = header.prev;
+        }
+        header.prev = None;
+        header.next = None;
     }
 }
 // SAFETY: Nothing is tied to a specific thread.
 unsafe impl Send for FixedSizeAllocator {}
 
+/// The bookkeeping at the start of each slab.
+///
+/// A slab is a region of memory, aligned to its size, which is split into blocks of one size. The
+/// header takes up the first block (or blocks, if it doesn't fit in one).
+struct SlabHeader {
+    /// A list of "freed" blocks which we can reuse.
+    free_list: Option<NonNull<FreeListNode>>,
+    /// The index of the first block which has never been allocated.
+    fresh: u16,
+    /// The number of blocks currently allocated.
+    live: u16,
+    /// The previous slab in the list of slabs with free blocks.
+    prev: Option<NonNull<Self>>,
+    /// The next slab in the list of slabs with free blocks.
+    next: Option<NonNull<Self>>,
+}
+impl SlabHeader {
+    /// Map a new slab for blocks of the given size.
+    fn create(size: usize) -> Option<NonNull<Self>> {
+        let slab_size = slab_size(size);
+        let slab = map_aligned(slab_size, slab_size)?.cast::<Self>();
+        // SAFETY:
+        // We just mapped this memory, and it's large and aligned enough for the header.
+        unsafe {
+            slab.write(Self {
+                free_list: None,
+                fresh: u16::try_from(size_of::<Self>().div_ceil(size)).ok()?,
+                live: 0,
+                prev: None,
+                next: None,
+            });
+        }
+        Some(slab)
+    }
+
+    /// Check whether every block in the slab is allocated.
+    fn is_full(&self, size: usize) -> bool {
+        self.free_list.is_none() && usize::from(self.fresh) == slab_size(size) / size
+    }
+}
+
 struct FreeListNode {
     next: Option<NonNull<Self>>,
 }
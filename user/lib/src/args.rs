@@ -0,0 +1,231 @@
+//! A small getopt-style parser for command-line arguments.
+//!
+//! [`Parser`] splits arguments into short options (`-v`, or several at once as in `-lah`), long
+//! options (`--verbose`), and positional arguments. Whether an option takes a value is up to the
+//! caller: after getting an option back from [`Parser::next`], call [`Parser::value`] to take its
+//! value, which can be attached to it (`-n5`, `--count=5`) or be the next argument (`-n 5`,
+//! `--count 5`). Everything after a `--` argument is positional, and so is a lone `-`.
+//!
+//! ```ignore
+//! let mut verbose = false;
+//! let mut count = 4;
+//! let mut paths = Vec::new();
+//! let mut parser = userlib::args::Parser::from_env();
+//! while let Some(arg) = parser.next()? {
+//!     match arg {
+//!         Arg::Short('v') | Arg::Long("verbose") => verbose = true,
+//!         Arg::Short('n') | Arg::Long("count") => count = parser.parsed_value()?,
+//!         Arg::Value(path) => paths.push(path),
+//!         _ => return Err(arg.unexpected()),
+//!     }
+//! }
+//! ```
+
+use core::{fmt, str::FromStr};
+
+/// An argument returned by [`Parser::next`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg<'a> {
+    /// A short option, like `-v`.
+    Short(char),
+    /// A long option, like `--verbose`, without the leading dashes.
+    Long(&'a str),
+    /// A positional argument.
+    Value(&'a str),
+}
+impl<'a> Arg<'a> {
+    /// Make the error for when the program doesn't accept this argument.
+    #[must_use]
+    pub fn unexpected(self) -> Error<'a> {
+        match self {
+            Self::Short(_) | Self::Long(_) => Error::UnexpectedOption(self),
+            Self::Value(value) => Error::UnexpectedArgument(value),
+        }
+    }
+}
+impl fmt::Display for Arg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Short(option) => write!(f, "-{option}"),
+            Self::Long(option) => write!(f, "--{option}"),
+            Self::Value(value) => f.write_str(value),
+        }
+    }
+}
+
+/// An error from parsing arguments.
+///
+/// The [`Display`](fmt::Display) implementation gives a message suitable for showing to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<'a> {
+    /// An option needed a value, but there were no arguments left.
+    MissingValue(Arg<'a>),
+    /// An option's value couldn't be parsed.
+    InvalidValue {
+        /// The option the value was for.
+        option: Arg<'a>,
+        /// The value which couldn't be parsed.
+        value: &'a str,
+    },
+    /// A long option was given a value with `=`, but the program didn't take one.
+    UnexpectedValue {
+        /// The option the value was attached to.
+        option: Arg<'a>,
+        /// The value attached to it.
+        value: &'a str,
+    },
+    /// The program doesn't accept this option.
+    UnexpectedOption(Arg<'a>),
+    /// The program doesn't accept this positional argument.
+    UnexpectedArgument(&'a str),
+}
+impl fmt::Display for Error<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingValue(option) => write!(f, "missing value for option {option}"),
+            Self::InvalidValue { option, value } => {
+                write!(f, "invalid value {value:?} for option {option}")
+            }
+            Self::UnexpectedValue { option, value } => {
+                write!(f, "option {option} doesn't take a value (got {value:?})")
+            }
+            Self::UnexpectedOption(option) => write!(f, "unknown option {option}"),
+            Self::UnexpectedArgument(value) => write!(f, "unexpected argument {value:?}"),
+        }
+    }
+}
+
+/// A parser for command-line arguments.
+///
+/// See the [module documentation](self) for how to use it.
+#[derive(Debug, Clone)]
+pub struct Parser<'a, I> {
+    /// The arguments which haven't been looked at yet.
+    args: I,
+    /// The rest of a group of short options (like `ah` after returning `l` from `-lah`).
+    shorts: &'a str,
+    /// A value attached to the last long option with `=`, which hasn't been taken yet.
+    attached: Option<&'a str>,
+    /// The last option returned, for error messages.
+    last_option: Option<Arg<'a>>,
+    /// Whether we've seen `--`, after which everything is positional.
+    options_done: bool,
+}
+impl Parser<'static, crate::env::Args> {
+    /// Create a parser for the arguments this process was started with, skipping the program
+    /// name.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let mut args = crate::env::args();
+        args.next();
+        Self::new(args)
+    }
+}
+impl<'a, I: Iterator<Item = &'a str>> Parser<'a, I> {
+    /// Create a parser for the given arguments.
+    ///
+    /// The arguments shouldn't include the program name.
+    pub fn new(args: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            args: args.into_iter(),
+            shorts: "",
+            attached: None,
+            last_option: None,
+            options_done: false,
+        }
+    }
+
+    /// Get the next argument, or `None` once they've all been parsed.
+    ///
+    /// Fails if the last option returned had a value attached with `=` which wasn't taken with
+    /// [`Self::value`].
+    #[expect(
+        clippy::should_implement_trait,
+        reason = "This can fail, so it doesn't fit `Iterator`"
+    )]
+    pub fn next(&mut self) -> Result<Option<Arg<'a>>, Error<'a>> {
+        if let Some(value) = self.attached.take() {
+            return Err(Error::UnexpectedValue {
+                option: self
+                    .last_option
+                    .expect("Values are only attached to options"),
+                value,
+            });
+        }
+        let mut chars = self.shorts.chars();
+        if let Some(option) = chars.next() {
+            self.shorts = chars.as_str();
+            return Ok(Some(self.option(Arg::Short(option))));
+        }
+
+        let Some(arg) = self.args.next() else {
+            return Ok(None);
+        };
+        if self.options_done || arg == "-" {
+            return Ok(Some(Arg::Value(arg)));
+        }
+        if arg == "--" {
+            self.options_done = true;
+            return self.next();
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = match long.split_once('=') {
+                Some((name, value)) => {
+                    self.attached = Some(value);
+                    name
+                }
+                None => long,
+            };
+            return Ok(Some(self.option(Arg::Long(name))));
+        }
+        if let Some(shorts) = arg.strip_prefix('-') {
+            self.shorts = shorts;
+            return self.next();
+        }
+        Ok(Some(Arg::Value(arg)))
+    }
+
+    /// Take the value for the option [`Self::next`] just returned.
+    ///
+    /// This is the rest of the argument the option was in if there's anything left of it (the `5`
+    /// in `-n5` or `--count=5`), or the whole next argument otherwise, even if it starts with `-`.
+    ///
+    /// # Panics
+    /// Panics if [`Self::next`] hasn't returned an option yet and there are no arguments left.
+    pub fn value(&mut self) -> Result<&'a str, Error<'a>> {
+        if let Some(value) = self.attached.take() {
+            return Ok(value);
+        }
+        if !self.shorts.is_empty() {
+            return Ok(core::mem::take(&mut self.shorts));
+        }
+        self.args.next().ok_or_else(|| {
+            Error::MissingValue(
+                self.last_option
+                    .expect("`value` is only called after getting an option"),
+            )
+        })
+    }
+
+    /// Take the value for the option [`Self::next`] just returned, and parse it.
+    ///
+    /// See [`Self::value`] for where the value comes from.
+    ///
+    /// # Panics
+    /// Panics if [`Self::next`] hasn't returned an option yet.
+    pub fn parsed_value<T: FromStr>(&mut self) -> Result<T, Error<'a>> {
+        let value = self.value()?;
+        value.parse().map_err(|_| Error::InvalidValue {
+            option: self
+                .last_option
+                .expect("`parsed_value` is only called after getting an option"),
+            value,
+        })
+    }
+
+    /// Record that `option` is being returned, so later errors can mention it.
+    fn option(&mut self, option: Arg<'a>) -> Arg<'a> {
+        self.last_option = Some(option);
+        option
+    }
+}
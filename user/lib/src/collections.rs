@@ -0,0 +1,88 @@
+//! Collection types.
+//!
+//! This has everything from `alloc::collections`, along with [`HashMap`] and [`HashSet`] (from
+//! the `hashbrown` crate) which default to a randomly-keyed hasher.
+//!
+//! Unlike the standard library, `HashMap::new()` and `HashSet::new()` don't exist for the default
+//! hasher, so use `HashMap::default()` and `HashSet::default()` instead. The hasher can't be
+//! inferred from those alone, so name the type somewhere, as in
+//! `let map: HashMap<_, _> = HashMap::default();`.
+
+use core::hash::{BuildHasher, Hasher};
+
+pub use hashbrown::{hash_map, hash_set};
+
+pub use crate::rust_alloc::collections::*;
+use crate::sync::OnceLock;
+
+/// A hash map, which uses [`RandomState`] to hash keys by default.
+pub type HashMap<K, V, S = RandomState> = hashbrown::HashMap<K, V, S>;
+
+/// A hash set, which uses [`RandomState`] to hash values by default.
+pub type HashSet<T, S = RandomState> = hashbrown::HashSet<T, S>;
+
+/// The keys for [`RandomState`], which are generated once per process.
+static KEYS: OnceLock<(u64, u64)> = OnceLock::new();
+
+/// The default hasher for [`HashMap`] and [`HashSet`], which is keyed with random bytes from the
+/// kernel so that other programs can't predict which keys collide.
+#[derive(Debug, Clone)]
+pub struct RandomState {
+    /// The first key for the hash function.
+    k0: u64,
+    /// The second key for the hash function.
+    k1: u64,
+}
+impl RandomState {
+    /// Construct a new random state.
+    ///
+    /// The keys are generated the first time this is called. If the kernel has no source of
+    /// randomness, then they're derived from the current time instead.
+    #[must_use]
+    pub fn new() -> Self {
+        let &(k0, k1) = KEYS.get_or_init(|| {
+            let mut bytes = [0; 16];
+            if crate::sys::get_random(&mut bytes).is_err() {
+                let now = crate::sys::clock_get_time().as_nanos();
+                bytes = now.to_le_bytes();
+            }
+            let (k0, k1) = bytes.split_at(8);
+            (
+                u64::from_le_bytes(k0.try_into().expect("Split in half")),
+                u64::from_le_bytes(k1.try_into().expect("Split in half")),
+            )
+        });
+        Self { k0, k1 }
+    }
+}
+impl Default for RandomState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl BuildHasher for RandomState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        #[expect(
+            deprecated,
+            reason = "`core` has no other SipHash, and it's fine for hash maps"
+        )]
+        DefaultHasher(core::hash::SipHasher::new_with_keys(self.k0, self.k1))
+    }
+}
+
+/// The hasher built by [`RandomState`].
+#[derive(Debug, Clone)]
+pub struct DefaultHasher(
+    #[expect(deprecated, reason = "See `RandomState::build_hasher`")] core::hash::SipHasher,
+);
+impl Hasher for DefaultHasher {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+}
@@ -0,0 +1,190 @@
+//! The process's environment: its arguments, environment variables, and current directory.
+//!
+//! The kernel hands each process its arguments and initial environment variables in a read-only
+//! block at [`shared::PROCESS_ARGS_ADDR`]. Changes to environment variables are only seen by this
+//! process.
+//!
+//! The kernel only understands absolute paths, so the current directory is kept here, in the
+//! `PWD` environment variable, and the [`fs`](crate::fs) functions resolve relative paths against
+//! it before they reach the kernel.
+
+use crate::{
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    rust_alloc::{collections::BTreeMap, string::String, vec::Vec},
+    sync::Mutex,
+};
+
+/// The environment variable holding the current directory.
+const CURRENT_DIR_VAR: &str = "PWD";
+
+/// The environment variables, which are read from the kernel's block when first used.
+static VARS: Mutex<Option<BTreeMap<String, String>>> = Mutex::new(None);
+
+/// Get the arguments this process was started with.
+///
+/// By convention, the first argument is the name of the program.
+#[must_use]
+pub fn args() -> Args {
+    let block = ArgsBlock::get();
+    Args {
+        strings: block.strings,
+        remaining: block.num_args,
+    }
+}
+
+/// Get the value of the environment variable `key`.
+///
+/// Fails with [`ErrorKind::NotFound`] if it isn't set.
+pub fn var(key: &str) -> Result<String> {
+    with_vars(|vars| vars.get(key).cloned()).ok_or(Error::new(
+        ErrorKind::NotFound,
+        "environment variable not set",
+    ))
+}
+
+/// Get all the environment variables, as `(key, value)` pairs.
+#[must_use]
+pub fn vars() -> Vec<(String, String)> {
+    with_vars(|vars| {
+        vars.iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    })
+}
+
+/// Set the environment variable `key` to `value`.
+///
+/// # Panics
+/// Panics if `key` is empty or contains `=` or a NUL byte, or if `value` contains a NUL byte,
+/// since those couldn't be passed on to other processes.
+pub fn set_var(key: &str, value: &str) {
+    assert!(
+        !key.is_empty() && !key.contains(['=', '\0']),
+        "Invalid environment variable name {key:?}"
+    );
+    assert!(
+        !value.contains('\0'),
+        "Invalid value for environment variable {key:?}"
+    );
+    with_vars(|vars| vars.insert(key.into(), value.into()));
+}
+
+/// Unset the environment variable `key`.
+pub fn remove_var(key: &str) {
+    with_vars(|vars| vars.remove(key));
+}
+
+/// Get the current directory, as an absolute path.
+#[must_use]
+pub fn current_dir() -> PathBuf {
+    var(CURRENT_DIR_VAR)
+        .ok()
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(|| Path::ROOT.to_path_buf())
+}
+
+/// Change the current directory to `path`, which may be relative to the current one.
+///
+/// Fails if there's nothing at `path`, or with [`ErrorKind::InvalidFormat`] if it isn't a
+/// directory.
+pub fn set_current_dir(path: impl AsRef<Path>) -> Result<()> {
+    let path = absolute(path);
+    if !crate::fs::metadata(&path)?.is_dir() {
+        return Err(Error::new(ErrorKind::InvalidFormat, "not a directory"));
+    }
+    set_var(CURRENT_DIR_VAR, path.as_str());
+    Ok(())
+}
+
+/// Turn `path` into a normalized absolute path, resolving it against the current directory if
+/// it's relative.
+///
+/// `.` and `..` components are resolved without looking at the filesystem.
+pub(crate) fn absolute(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_absolute() {
+        path.normalize()
+    } else {
+        current_dir().join(path).normalize()
+    }
+}
+
+/// Run `f` on the environment variables, reading them from the kernel's block first if needed.
+fn with_vars<T>(f: impl FnOnce(&mut BTreeMap<String, String>) -> T) -> T {
+    let mut vars = VARS.lock();
+    let vars = vars.get_or_insert_with(|| {
+        let block = ArgsBlock::get();
+        Args {
+            strings: block.strings,
+            remaining: block.num_args + block.num_vars,
+        }
+        .skip(block.num_args)
+        .filter_map(|var| var.split_once('='))
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect()
+    });
+    f(vars)
+}
+
+/// An iterator over the arguments a process was started with.
+///
+/// See [`args`].
+pub struct Args {
+    /// The strings which haven't been returned yet, each followed by a NUL byte.
+    strings: &'static [u8],
+    /// The number of arguments left in `strings`.
+    remaining: usize,
+}
+impl Iterator for Args {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.remaining = self.remaining.checked_sub(1)?;
+        let len = self
+            .strings
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.strings.len());
+        let (arg, rest) = self.strings.split_at(len);
+        self.strings = rest.get(1..).unwrap_or_default();
+        // The kernel's block is made from `&str`s, so this is always valid.
+        str::from_utf8(arg).ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl ExactSizeIterator for Args {}
+
+/// The block of arguments and environment variables from the kernel.
+struct ArgsBlock {
+    /// The number of arguments.
+    num_args: usize,
+    /// The number of environment variables.
+    num_vars: usize,
+    /// The arguments followed by the environment variables, each followed by a NUL byte.
+    strings: &'static [u8],
+}
+impl ArgsBlock {
+    /// Find the block the kernel mapped for this process.
+    fn get() -> Self {
+        let header_ptr = core::ptr::with_exposed_provenance::<[u32; 3]>(shared::PROCESS_ARGS_ADDR);
+        // SAFETY:
+        // The kernel maps the block at this address for every process, and never unmaps or
+        // changes it.
+        let [num_args, num_vars, strings_len] = unsafe { header_ptr.read() }.map(u32::from_le);
+        // SAFETY:
+        // The strings follow the header, in the same block.
+        let strings = unsafe {
+            core::slice::from_raw_parts(header_ptr.add(1).cast::<u8>(), strings_len as usize)
+        };
+        Self {
+            num_args: num_args as usize,
+            num_vars: num_vars as usize,
+            strings,
+        }
+    }
+}
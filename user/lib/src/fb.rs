@@ -0,0 +1,76 @@
+//! Drawing to the display's framebuffer.
+
+pub use shared::FramebufferInfo;
+
+use crate::{io::Result, rd::OwnedResourceDescriptor};
+
+/// Access to the framebuffer of the display.
+pub struct Framebuffer {
+    /// The underlying resource descriptor.
+    descriptor: OwnedResourceDescriptor,
+    /// The layout of the framebuffer.
+    info: FramebufferInfo,
+    /// The pixels of the framebuffer, mapped into our memory.
+    pixels: &'static mut [u32],
+}
+
+impl Framebuffer {
+    /// Open the framebuffer and map it into memory.
+    pub fn open() -> Result<Self> {
+        let descriptor =
+            crate::sys::open(shared::FRAMEBUFFER_PATH, shared::FileOpenFlags::READWRITE)?;
+        // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+        let descriptor = unsafe { OwnedResourceDescriptor::from_raw(descriptor) };
+        let mut info = FramebufferInfo::default();
+        // SAFETY: Every bit pattern is a valid `FramebufferInfo`, so we can write any bytes to it.
+        let info_bytes = unsafe {
+            core::slice::from_raw_parts_mut(
+                core::ptr::from_mut(&mut info).cast::<u8>(),
+                size_of::<FramebufferInfo>(),
+            )
+        };
+        crate::sys::read(descriptor.raw(), info_bytes)?;
+        let pixels_start = crate::sys::map_resource(descriptor.raw())?;
+        // SAFETY:
+        // The kernel mapped the framebuffer here for us, and nothing else in this process refers to
+        // it. It stays mapped for as long as the process runs.
+        let pixels = unsafe {
+            core::slice::from_raw_parts_mut(
+                pixels_start.cast::<u32>().as_ptr(),
+                (info.stride / 4 * info.height) as usize,
+            )
+        };
+        Ok(Self {
+            descriptor,
+            info,
+            pixels,
+        })
+    }
+
+    /// Get the layout of the framebuffer.
+    #[must_use]
+    pub fn info(&self) -> FramebufferInfo {
+        self.info
+    }
+
+    /// Get the pixels of the framebuffer, in rows from the top left corner.
+    ///
+    /// Each pixel is in the form `0x00RRGGBB`.
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        self.pixels
+    }
+
+    /// Set the pixel at the given coordinates to a color in the form `0x00RRGGBB`.
+    ///
+    /// # Panics
+    /// Panics if the coordinates are outside the framebuffer.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: u32) {
+        assert!(x < self.info.width && y < self.info.height);
+        self.pixels[(y * self.info.stride / 4 + x) as usize] = color;
+    }
+
+    /// Show the current contents of the framebuffer on the display.
+    pub fn flush(&self) -> Result<()> {
+        Ok(crate::sys::sync(self.descriptor.raw())?)
+    }
+}
@@ -1,6 +1,16 @@
 //! Filesystem access.
+//!
+//! Relative paths are resolved against the [current directory](crate::env::current_dir).
 
-use crate::rd::OwnedResourceDescriptor;
+pub use shared::{FileKind, FilesystemStats};
+
+use crate::{
+    env::absolute,
+    io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    rd::{BorrowedResourceDescriptor, OwnedResourceDescriptor},
+    rust_alloc::{string::String, vec::Vec},
+};
 
 /// Owned access to a file.
 pub struct File {
@@ -10,44 +20,303 @@ pub struct File {
 
 impl File {
     /// Open an existing file for reading.
-    pub fn open(path: &str) -> Result<Self, shared::ErrorKind> {
-        let descriptor = crate::sys::open(path, shared::FileOpenFlags::READ_ONLY)?;
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let descriptor =
+            crate::sys::open(absolute(path).as_str(), shared::FileOpenFlags::READ_ONLY)?;
         Ok(Self {
-            descriptor: OwnedResourceDescriptor::from_raw(descriptor),
+            // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+            descriptor: unsafe { OwnedResourceDescriptor::from_raw(descriptor) },
         })
     }
 
     /// Open an existing file to overwrite from the beginnin.
-    pub fn overwrite(path: &str) -> Result<Self, shared::ErrorKind> {
-        let descriptor = crate::sys::open(path, shared::FileOpenFlags::WRITE_ONLY)?;
+    pub fn overwrite(path: impl AsRef<Path>) -> Result<Self> {
+        let descriptor =
+            crate::sys::open(absolute(path).as_str(), shared::FileOpenFlags::WRITE_ONLY)?;
+        Ok(Self {
+            // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+            descriptor: unsafe { OwnedResourceDescriptor::from_raw(descriptor) },
+        })
+    }
+
+    /// Open a file for writing, creating it if it doesn't exist and discarding its contents if it
+    /// does.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let descriptor = crate::sys::open(
+            absolute(path).as_str(),
+            shared::FileOpenFlags::WRITE_ONLY
+                | shared::FileOpenFlags::CREATE
+                | shared::FileOpenFlags::TRUNCATE,
+        )?;
+        Ok(Self {
+            // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+            descriptor: unsafe { OwnedResourceDescriptor::from_raw(descriptor) },
+        })
+    }
+
+    /// Open a file for writing at its end, creating it if it doesn't exist.
+    pub fn append(path: impl AsRef<Path>) -> Result<Self> {
+        let descriptor = crate::sys::open(
+            absolute(path).as_str(),
+            shared::FileOpenFlags::WRITE_ONLY
+                | shared::FileOpenFlags::CREATE
+                | shared::FileOpenFlags::APPEND,
+        )?;
         Ok(Self {
-            descriptor: OwnedResourceDescriptor::from_raw(descriptor),
+            // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+            descriptor: unsafe { OwnedResourceDescriptor::from_raw(descriptor) },
         })
     }
 
-    /// Read from this file into a buffer.
-    ///
-    /// Returns the written memory, which will be at the start of [`buf`].
-    pub fn read<'a>(&self, buf: &'a mut [u8]) -> Result<&'a mut [u8], shared::ErrorKind> {
-        let read_length = crate::sys::read(self.descriptor.raw(), buf)?;
-        Ok(&mut buf[..read_length])
+    /// Borrow the file's resource descriptor.
+    #[must_use]
+    pub fn as_descriptor(&self) -> BorrowedResourceDescriptor<'_> {
+        self.descriptor.borrow()
+    }
+}
+impl From<File> for OwnedResourceDescriptor {
+    fn from(file: File) -> Self {
+        file.descriptor
+    }
+}
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.descriptor.read(buf)
+    }
+}
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.descriptor.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.descriptor.flush()
+    }
+}
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.descriptor.seek(pos)
+    }
+}
+
+/// Read the whole contents of the file at `path`.
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    File::open(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Read the whole contents of the file at `path` as a string.
+///
+/// Fails with [`ErrorKind::InvalidFormat`] if the contents aren't valid UTF-8.
+pub fn read_to_string(path: impl AsRef<Path>) -> Result<String> {
+    String::from_utf8(read(path)?)
+        .map_err(|_| Error::new(ErrorKind::InvalidFormat, "file contents aren't valid UTF-8"))
+}
+
+/// Write `contents` to the file at `path`, replacing whatever was there.
+///
+/// The file is created if it doesn't exist.
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<()> {
+    File::create(path)?.write_all(contents.as_ref())
+}
+
+/// Create a new hard link at `link` pointing to the same file as `original`.
+pub fn hard_link(original: impl AsRef<Path>, link: impl AsRef<Path>) -> Result<()> {
+    Ok(crate::sys::link(
+        absolute(original).as_str(),
+        absolute(link).as_str(),
+    )?)
+}
+
+/// Remove the file at `path`.
+///
+/// The file's contents are only freed once no other hard links to it remain.
+pub fn remove_file(path: impl AsRef<Path>) -> Result<()> {
+    Ok(crate::sys::unlink(absolute(path).as_str())?)
+}
+
+/// Create a new, empty directory at `path`.
+///
+/// The parent directory must already exist.
+pub fn create_dir(path: impl AsRef<Path>) -> Result<()> {
+    Ok(crate::sys::create_dir(absolute(path).as_str())?)
+}
+
+/// Create a new, empty directory at `path`, along with any of its parents which don't exist yet.
+///
+/// This succeeds without doing anything if there's already a directory at `path`.
+pub fn create_dir_all(path: impl AsRef<Path>) -> Result<()> {
+    let path = absolute(path);
+    if metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    create_dir(&path)
+}
+
+/// Remove the empty directory at `path`.
+///
+/// Fails with [`ErrorKind::DirectoryNotEmpty`] if there's anything in the directory.
+pub fn remove_dir(path: impl AsRef<Path>) -> Result<()> {
+    Ok(crate::sys::remove_dir(absolute(path).as_str())?)
+}
+
+/// Remove the directory at `path`, after removing everything in it.
+pub fn remove_dir_all(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    // Removing entries moves the ones after them up, so get them all before removing any.
+    let entries = read_dir(path)?.collect::<Result<Vec<_>>>()?;
+    for entry in entries {
+        if entry.metadata()?.is_dir() {
+            remove_dir_all(entry.path())?;
+        } else {
+            remove_file(entry.path())?;
+        }
+    }
+    remove_dir(path)
+}
+
+/// Get information about the file at `path`.
+pub fn metadata(path: impl AsRef<Path>) -> Result<Metadata> {
+    Ok(Metadata(crate::sys::stat(absolute(path).as_str())?))
+}
+
+/// Iterate over the entries in the directory at `path`.
+///
+/// The `.` and `..` entries are skipped. Fails with [`ErrorKind::InvalidFormat`] if `path` isn't
+/// a directory.
+pub fn read_dir(path: impl AsRef<Path>) -> Result<ReadDir> {
+    let path = absolute(path);
+    if !metadata(&path)?.is_dir() {
+        return Err(Error::new(ErrorKind::InvalidFormat, "not a directory"));
+    }
+    Ok(ReadDir {
+        path,
+        index: 0,
+        done: false,
+    })
+}
+
+/// Get usage information about the filesystem containing `path`.
+pub fn filesystem_stats(path: impl AsRef<Path>) -> Result<FilesystemStats> {
+    Ok(crate::sys::statfs(absolute(path).as_str())?)
+}
+
+/// Information about a file, from [`metadata`].
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata(shared::FileMetadata);
+impl Metadata {
+    /// Get what kind of file this is.
+    #[must_use]
+    pub fn kind(&self) -> FileKind {
+        FileKind::from_num(self.0.kind).unwrap_or(FileKind::Other)
+    }
+
+    /// Get whether this is a directory.
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        self.kind() == FileKind::Directory
+    }
+
+    /// Get whether this is a regular file.
+    #[must_use]
+    pub fn is_file(&self) -> bool {
+        self.kind() == FileKind::RegularFile
+    }
+
+    /// Get the size of the file, in bytes.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.0.size
+    }
+
+    /// Get whether the file is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.size == 0
     }
 
-    /// Write from a buffer into this file.
-    ///
-    /// Returns the number of bytes writen, which will be at the start of [`buf`].
-    pub fn write(&self, buf: &[u8]) -> Result<usize, shared::ErrorKind> {
-        crate::sys::write(self.descriptor.raw(), buf)
+    /// Get the number of hard links to the file.
+    #[must_use]
+    pub fn hard_links(&self) -> u32 {
+        self.0.hard_links
     }
 
-    /// Write the entire buffer into this file.
-    pub fn write_all(&self, mut buf: &[u8]) -> Result<(), shared::ErrorKind> {
-        loop {
-            let len = self.write(buf)?;
-            if len == buf.len() {
-                return Ok(());
+    /// Get the file's permission bits, as in a Unix mode (like `0o644`).
+    #[must_use]
+    pub fn permissions(&self) -> u32 {
+        self.0.permissions
+    }
+}
+
+/// An iterator over the entries in a directory, from [`read_dir`].
+pub struct ReadDir {
+    /// The absolute path of the directory.
+    path: PathBuf,
+    /// The index of the next entry to ask the kernel for.
+    index: usize,
+    /// Whether we've run out of entries (or hit an error).
+    done: bool,
+}
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Directory entry names are at most 255 bytes long.
+        let mut name_buf = [0; 255];
+        while !self.done {
+            let name_len = match crate::sys::read_dir(self.path.as_str(), self.index, &mut name_buf)
+            {
+                Ok(0) => break,
+                Ok(name_len) => name_len,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            };
+            self.index += 1;
+            let Ok(name) = str::from_utf8(&name_buf[..name_len]) else {
+                return Some(Err(Error::new(
+                    ErrorKind::InvalidFormat,
+                    "directory entry name isn't valid UTF-8",
+                )));
+            };
+            if name == "." || name == ".." {
+                continue;
             }
-            buf = &buf[len..];
+            return Some(Ok(DirEntry {
+                path: self.path.join(name),
+            }));
         }
+        self.done = true;
+        None
+    }
+}
+
+/// An entry in a directory, from [`ReadDir`].
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    /// The absolute path of the entry.
+    path: PathBuf,
+}
+impl DirEntry {
+    /// Get the absolute path of the entry.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the name of the entry, without the directory it's in.
+    #[must_use]
+    pub fn file_name(&self) -> &str {
+        self.path.file_name().unwrap_or_default()
+    }
+
+    /// Get information about the entry.
+    pub fn metadata(&self) -> Result<Metadata> {
+        metadata(&self.path)
     }
 }
@@ -23,16 +23,23 @@ extern "C" fn start() -> ! {
     core::arch::naked_asm!(
         "lui sp, %hi({stack_top})",
         "addi sp, sp, %lo({stack_top})",
+        // Clear the frame pointer, which marks the end of the chain for backtraces.
+        "li s0, 0",
 
         "call {main}",
         "call {exit}",
 
         stack_top = sym __stack_top,
         exit = sym __exit,
-        main = sym main,
+        main = sym run_main,
     )
 }
 
+/// Run the user binary's `main` function, with a cache for the allocator.
+fn run_main() {
+    crate::alloc::with_thread_cache(main);
+}
+
 unsafe extern "Rust" {
     /// The `main` function provided by the user binary.
     safe fn main();
@@ -40,21 +47,15 @@ unsafe extern "Rust" {
 
 /// The panic handler for user-space code.
 ///
-/// This handler just displays the panic information and exits with a non-zero status.
+/// This runs the panic hook (see [`crate::panic`]) and exits with a non-zero status.
 #[cfg_attr(target_os = "none", panic_handler)]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    use core::fmt::Write as _;
-    // SAFETY:
-    // This panic handler will never return to outside code, so it is safe to take ownership over
-    // the stderr stream.
-    let mut stderr = unsafe { crate::io::Stderr::force_lock() };
-    _ = writeln!(stderr, "\n{info}");
-    crate::sys::exit(1);
+    crate::panic::handle(info)
 }
 
-/// Exit the process.
+/// Exit the process, running the hooks registered with [`crate::process::at_exit`].
 ///
 /// This function exists for [`start`] to exit without returning, which would be problematic.
 fn __exit() -> ! {
-    crate::sys::exit(0)
+    crate::process::exit(0)
 }
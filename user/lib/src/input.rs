@@ -0,0 +1,45 @@
+//! Raw input events, such as key presses.
+
+use core::time::Duration;
+
+use shared::ErrorKind;
+pub use shared::InputEvent;
+
+use crate::{io::Result, rd::OwnedResourceDescriptor};
+
+/// Access to the events from the keyboard.
+pub struct Keyboard {
+    /// The underlying resource descriptor.
+    descriptor: OwnedResourceDescriptor,
+}
+
+impl Keyboard {
+    /// Open the keyboard for reading events.
+    pub fn open() -> Result<Self> {
+        let descriptor = crate::sys::open(shared::KEYBOARD_PATH, shared::FileOpenFlags::READ_ONLY)?;
+        Ok(Self {
+            // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+            descriptor: unsafe { OwnedResourceDescriptor::from_raw(descriptor) },
+        })
+    }
+
+    /// Wait for the next event.
+    pub fn next_event(&self) -> Result<InputEvent> {
+        let mut buf = [0; InputEvent::LEN];
+        crate::sys::read(self.descriptor.raw(), &mut buf)?;
+        Ok(InputEvent::from_bytes(buf))
+    }
+
+    /// Wait for the next event, giving up after `timeout`.
+    ///
+    /// Returns `None` if no event arrived in time. A timeout of zero only checks for events that
+    /// have already arrived, without waiting.
+    pub fn next_event_timeout(&self, timeout: Duration) -> Result<Option<InputEvent>> {
+        let mut buf = [0; InputEvent::LEN];
+        match crate::sys::read_timeout(self.descriptor.raw(), &mut buf, timeout) {
+            Ok(_) => Ok(Some(InputEvent::from_bytes(buf))),
+            Err(ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -1,8 +1,304 @@
 //! Utilities for input/output
 
-use core::{fmt, sync::atomic::AtomicBool};
+use core::{fmt, sync::atomic::AtomicBool, time::Duration};
 
-use crate::rd::BorrowedResourceDescriptor;
+mod buffered;
+mod error;
+mod pipe;
+
+pub use buffered::{BufRead, BufReader, BufWriter};
+pub use error::{Error, Result};
+pub use pipe::{PipeReader, PipeWriter, pipe};
+pub use shared::ErrorKind;
+
+use crate::{
+    rd::{BorrowedResourceDescriptor, OwnedResourceDescriptor},
+    rust_alloc::{string::String, vec::Vec},
+};
+
+/// The error for reading text that isn't valid UTF-8.
+const INVALID_UTF8: Error = Error::new(
+    ErrorKind::InvalidFormat,
+    "stream did not contain valid UTF-8",
+);
+
+/// A source of bytes, such as a file or the console.
+pub trait Read {
+    /// Read some bytes into `buf`, returning how many were read.
+    ///
+    /// Reading 0 bytes means there's nothing left to read (unless `buf` is empty).
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Read exactly enough bytes to fill `buf`.
+    ///
+    /// If there isn't enough left to read, this fails with [`ErrorKind::Io`], and the contents of
+    /// `buf` are unspecified.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(Error::new(ErrorKind::Io, "failed to fill whole buffer")),
+                len => buf = &mut buf[len..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Read everything that's left, appending it to `buf`.
+    ///
+    /// Returns the number of bytes read.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        /// How much more room we make in `buf` for each read.
+        const CHUNK_LEN: usize = 512;
+
+        let start_len = buf.len();
+        loop {
+            let len = buf.len();
+            buf.resize(len + CHUNK_LEN, 0);
+            let result = self.read(&mut buf[len..]);
+            buf.truncate(len + *result.as_ref().unwrap_or(&0));
+            if result? == 0 {
+                return Ok(buf.len() - start_len);
+            }
+        }
+    }
+
+    /// Read everything that's left, appending it to `buf`.
+    ///
+    /// Returns the number of bytes read. Fails with [`ErrorKind::InvalidFormat`] if what was read
+    /// isn't valid UTF-8, in which case `buf` is left unchanged.
+    fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
+        let mut bytes = Vec::new();
+        let len = self.read_to_end(&mut bytes)?;
+        buf.push_str(str::from_utf8(&bytes).map_err(|_| INVALID_UTF8)?);
+        Ok(len)
+    }
+}
+
+/// A destination for bytes, such as a file or the console.
+pub trait Write {
+    /// Write some of `buf`, returning how many bytes were written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    /// Make sure everything written so far has reached its destination.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Write all of `buf`.
+    ///
+    /// If writing stops part way through, this fails with [`ErrorKind::Io`].
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(Error::new(ErrorKind::Io, "failed to write whole buffer")),
+                len => buf = &buf[len..],
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copy everything that's left in `reader` into `writer`.
+///
+/// Returns the number of bytes copied.
+pub fn copy<R: Read + ?Sized, W: Write + ?Sized>(reader: &mut R, writer: &mut W) -> Result<u64> {
+    let mut buf = [0; 512];
+    let mut copied = 0;
+    loop {
+        let len = reader.read(&mut buf)?;
+        if len == 0 {
+            return Ok(copied);
+        }
+        writer.write_all(&buf[..len])?;
+        copied += len as u64;
+    }
+}
+
+/// Where to move to with [`Seek::seek`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// This many bytes from the start.
+    Start(u64),
+    /// This many bytes from the end.
+    End(i64),
+    /// This many bytes from the current position.
+    Current(i64),
+}
+
+/// Something with a position that reads and writes happen at, which can be moved.
+pub trait Seek {
+    /// Move to the given position, returning the new position from the start.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+    /// Move back to the start.
+    fn rewind(&mut self) -> Result<()> {
+        self.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+
+    /// Get the current position from the start.
+    fn stream_position(&mut self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
+
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+impl<S: Seek + ?Sized> Seek for &mut S {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        (**self).seek(pos)
+    }
+}
+
+/// Temporary ownership over the standard input stream.
+#[must_use = "`Stdin` objects are only useful for reading from"]
+pub struct Stdin<'a> {
+    rd: BorrowedResourceDescriptor<'a>,
+}
+impl Stdin<'_> {
+    /// Lock the standard input stream so reading can happen.
+    ///
+    /// If another copy of `Self` exists anywhere, this method will panic. See [`Self::try_lock`]
+    /// for a panic-free alternative.
+    pub fn lock() -> Self {
+        Self::try_lock().expect("Failed to lock stdin - is there another instance?")
+    }
+
+    /// Attempt to lock the standard input stream.
+    ///
+    /// This method returns `None` if the input stream is already locked. See [`Self::lock`] for
+    /// an alternative that panics.
+    pub fn try_lock() -> Option<Self> {
+        if STDIN_LOCK.swap(true, core::sync::atomic::Ordering::Acquire) {
+            None
+        } else {
+            Some(Self {
+                rd: BorrowedResourceDescriptor::from_raw(0),
+            })
+        }
+    }
+
+    /// Read a line typed at the console, appending it to `buf`.
+    ///
+    /// The console doesn't echo what's typed or handle editing, so this echoes each character to
+    /// standard output and handles backspace. Either of `\r` or `\n` ends the line, and is
+    /// appended as `\n`. Returns the number of bytes appended.
+    pub fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let start_len = buf.len();
+        for c in self.chars() {
+            match c? {
+                '\r' | '\n' => {
+                    crate::println!();
+                    buf.push('\n');
+                    break;
+                }
+                '\x7f' => {
+                    if buf.len() > start_len && buf.pop().is_some() {
+                        crate::print!("\x08 \x08");
+                    }
+                }
+                c => {
+                    crate::print!("{c}");
+                    buf.push(c);
+                }
+            }
+        }
+        Ok(buf.len() - start_len)
+    }
+
+    /// Iterate over the bytes of the stream.
+    pub fn bytes(&mut self) -> Bytes<&mut Self> {
+        Bytes { reader: self }
+    }
+
+    /// Iterate over the characters of the stream, decoded as UTF-8.
+    pub fn chars(&mut self) -> Chars<&mut Self> {
+        Chars { reader: self }
+    }
+
+    /// Read some bytes into `buf` like [`Read::read`], but give up if nothing has been typed
+    /// within `timeout`.
+    ///
+    /// Giving up fails with [`ErrorKind::TimedOut`]. A timeout of zero only takes what has
+    /// already been typed, without waiting.
+    pub fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        Ok(crate::sys::read_timeout(self.rd.raw(), buf, timeout)?)
+    }
+}
+impl Drop for Stdin<'_> {
+    fn drop(&mut self) {
+        STDIN_LOCK.store(false, core::sync::atomic::Ordering::Release);
+    }
+}
+impl Read for Stdin<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.rd.read(buf)
+    }
+}
+
+/// A lock for [`Stdin`], to ensure there aren't conflicting claims.
+static STDIN_LOCK: AtomicBool = AtomicBool::new(false);
+
+/// An iterator over the bytes of a reader, which stops once there's nothing left to read.
+///
+/// Each byte takes a separate read, so wrap slow readers in a [`BufReader`] first.
+pub struct Bytes<R> {
+    reader: R,
+}
+impl<R: Read> Iterator for Bytes<R> {
+    type Item = Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut byte = 0;
+        match self.reader.read(core::slice::from_mut(&mut byte)) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(byte)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// An iterator over the characters of a reader, decoded as UTF-8, which stops once there's
+/// nothing left to read.
+///
+/// Invalid UTF-8 gives [`ErrorKind::InvalidFormat`].
+pub struct Chars<R> {
+    reader: R,
+}
+impl<R: Read> Iterator for Chars<R> {
+    type Item = Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = [0; 4];
+        match self.reader.read(&mut bytes[..1]) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        // The leading byte says how many continuation bytes follow.
+        let len = match bytes[0].leading_ones() {
+            0 => 1,
+            n @ 2..=4 => n as usize,
+            _ => return Some(Err(INVALID_UTF8)),
+        };
+        Some(
+            self.reader
+                .read_exact(&mut bytes[1..len])
+                .and_then(|()| str::from_utf8(&bytes[..len]).map_err(|_| INVALID_UTF8))
+                .map(|s| s.chars().next().unwrap_or_default()),
+        )
+    }
+}
 
 /// Write to standard output.
 #[macro_export]
@@ -26,7 +322,7 @@ macro_rules! println {
     }};
 }
 
-/// Write to standard output.
+/// Write to standard error.
 #[macro_export]
 macro_rules! eprint {
     ($( $args:tt )*) => {{
@@ -37,7 +333,7 @@ macro_rules! eprint {
     }};
 }
 
-/// Write to standard output.
+/// Write to standard error.
 #[macro_export]
 macro_rules! eprintln {
     ($( $args:tt )*) => {{
@@ -94,58 +390,62 @@ impl Drop for Stdout<'_> {
         STDOUT_LOCK.store(false, core::sync::atomic::Ordering::Release);
     }
 }
+impl Write for Stdout<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.rd.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.rd.flush()
+    }
+}
 impl fmt::Write for Stdout<'_> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        let mut s = s.as_bytes();
-        while !s.is_empty() {
-            let len = crate::sys::write(self.rd.raw(), s).map_err(|_| fmt::Error)?;
-            s = &s[len..];
-        }
-        Ok(())
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
     }
 }
 
 /// A lock for [`Stdout`], to ensure there aren't conflicting claims.
 static STDOUT_LOCK: AtomicBool = AtomicBool::new(false);
 
-/// Temporary ownership over the standard output stream.
+/// Temporary ownership over the standard error stream.
 #[must_use = "`Stderr` objects are only useful for writing to"]
 pub struct Stderr<'a> {
     rd: BorrowedResourceDescriptor<'a>,
 }
 impl Stderr<'_> {
-    /// Lock the standard output stream so writing can happen.
+    /// Lock the standard error stream so writing can happen.
     ///
     /// If another copy of `Self` exists anywhere, this method will panic. See [`Self::try_lock`]
     /// for a panic-free alternative.
     pub fn lock() -> Self {
-        Self::try_lock().expect("Failed to lock stdout - is there another instance?")
+        Self::try_lock().expect("Failed to lock stderr - is there another instance?")
     }
 
-    /// Attempt to lock the standard output stream.
+    /// Attempt to lock the standard error stream.
     ///
-    /// This method returns `None` if the output stream is already locked. See [`Self::lock`] for
+    /// This method returns `None` if the error stream is already locked. See [`Self::lock`] for
     /// an alternative that panics.
     pub fn try_lock() -> Option<Self> {
         if STDERR_LOCK.swap(true, core::sync::atomic::Ordering::Acquire) {
             None
         } else {
             Some(Self {
-                rd: BorrowedResourceDescriptor::from_raw(1),
+                rd: BorrowedResourceDescriptor::from_raw(2),
             })
         }
     }
 
-    /// Forcibly lock the standard output stream.
+    /// Forcibly lock the standard error stream.
     ///
     /// # Safety
-    /// Calling this method when other instances of [`Stdout`] exist may lead to undefined behavior
+    /// Calling this method when other instances of [`Stderr`] exist may lead to undefined behavior
     /// if those other instances will have any methods called on them in the future (including the
     /// [`Drop::drop`] destructor).
     pub unsafe fn force_lock() -> Self {
         STDERR_LOCK.store(true, core::sync::atomic::Ordering::Relaxed);
         Self {
-            rd: BorrowedResourceDescriptor::from_raw(1),
+            rd: BorrowedResourceDescriptor::from_raw(2),
         }
     }
 }
@@ -154,16 +454,51 @@ impl Drop for Stderr<'_> {
         STDERR_LOCK.store(false, core::sync::atomic::Ordering::Release);
     }
 }
+impl Write for Stderr<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.rd.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.rd.flush()
+    }
+}
 impl fmt::Write for Stderr<'_> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        let mut s = s.as_bytes();
-        while !s.is_empty() {
-            let len = crate::sys::write(self.rd.raw(), s).map_err(|_| fmt::Error)?;
-            s = &s[len..];
-        }
-        Ok(())
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
     }
 }
 
 /// A lock for [`Stderr`], to ensure there aren't conflicting claims.
 static STDERR_LOCK: AtomicBool = AtomicBool::new(false);
+
+/// Make standard input read from `rd` instead, returning a descriptor for what it read from before.
+///
+/// Pass the returned descriptor to this function again to put standard input back the way it was.
+pub fn replace_stdin(rd: BorrowedResourceDescriptor<'_>) -> Result<OwnedResourceDescriptor> {
+    replace_stream(0, rd)
+}
+
+/// Make standard output write to `rd` instead, returning a descriptor for what it wrote to before.
+///
+/// Pass the returned descriptor to this function again to put standard output back the way it
+/// was.
+pub fn replace_stdout(rd: BorrowedResourceDescriptor<'_>) -> Result<OwnedResourceDescriptor> {
+    replace_stream(1, rd)
+}
+
+/// Make standard error write to `rd` instead, returning a descriptor for what it wrote to before.
+///
+/// Pass the returned descriptor to this function again to put standard error back the way it was.
+pub fn replace_stderr(rd: BorrowedResourceDescriptor<'_>) -> Result<OwnedResourceDescriptor> {
+    replace_stream(2, rd)
+}
+
+/// Point the standard stream with the raw descriptor `raw` at `rd`, returning a descriptor for
+/// what it pointed at before.
+fn replace_stream(raw: i32, rd: BorrowedResourceDescriptor<'_>) -> Result<OwnedResourceDescriptor> {
+    // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+    let old = unsafe { OwnedResourceDescriptor::from_raw(crate::sys::dup(raw)?) };
+    crate::sys::dup2(rd.raw(), raw)?;
+    Ok(old)
+}
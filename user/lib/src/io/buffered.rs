@@ -0,0 +1,243 @@
+//! Buffering for readers and writers, so small reads and writes don't each need a syscall.
+
+use core::fmt;
+
+use super::{Error, ErrorKind, Read, Result, Write};
+use crate::rust_alloc::{boxed::Box, string::String, vec, vec::Vec};
+
+/// The capacity of buffers made with `new`, in bytes.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// A reader with an internal buffer, which can be looked at without consuming it.
+pub trait BufRead: Read {
+    /// Get the contents of the internal buffer, filling it from the underlying reader if it's
+    /// empty.
+    ///
+    /// An empty slice means there's nothing left to read.
+    fn fill_buf(&mut self) -> Result<&[u8]>;
+
+    /// Mark the first `amt` bytes of the internal buffer as read, so they won't be returned again.
+    fn consume(&mut self, amt: usize);
+
+    /// Read bytes up to and including `byte`, appending them to `buf`.
+    ///
+    /// This stops early if there's nothing left to read. Returns the number of bytes read.
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut read = 0;
+        loop {
+            let (done, used) = {
+                let available = self.fill_buf()?;
+                if let Some(idx) = available.iter().position(|&b| b == byte) {
+                    buf.extend_from_slice(&available[..=idx]);
+                    (true, idx + 1)
+                } else {
+                    buf.extend_from_slice(available);
+                    (available.is_empty(), available.len())
+                }
+            };
+            self.consume(used);
+            read += used;
+            if done {
+                return Ok(read);
+            }
+        }
+    }
+
+    /// Read a line, including the trailing newline if there is one, appending it to `buf`.
+    ///
+    /// Returns the number of bytes read, which is 0 if there's nothing left to read. Fails with
+    /// [`ErrorKind::InvalidFormat`] if the line isn't valid UTF-8, in which case `buf` is left
+    /// unchanged.
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let mut bytes = Vec::new();
+        let len = self.read_until(b'\n', &mut bytes)?;
+        buf.push_str(str::from_utf8(&bytes).map_err(|_| ErrorKind::InvalidFormat)?);
+        Ok(len)
+    }
+}
+impl<B: BufRead + ?Sized> BufRead for &mut B {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        (**self).fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        (**self).consume(amt);
+    }
+}
+
+/// Wraps a reader, reading from it in large chunks.
+pub struct BufReader<R> {
+    /// The reader we're buffering.
+    inner: R,
+    /// The buffer, of which `buf[pos..filled]` hasn't been read yet.
+    buf: Box<[u8]>,
+    /// The start of the unread data in `buf`.
+    pos: usize,
+    /// The end of the unread data in `buf`.
+    filled: usize,
+}
+impl<R: Read> BufReader<R> {
+    /// Wrap `inner`, with the default capacity.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Wrap `inner`, with a buffer of `capacity` bytes.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+}
+impl<R> BufReader<R> {
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    ///
+    /// Reading from it directly will skip over whatever is buffered.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get the data which has been buffered but not read yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// Get the capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Unwrap the underlying reader, discarding anything buffered.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Big reads would only be copied through our buffer for nothing, so skip it when it's
+        // empty.
+        if self.pos == self.filled && buf.len() >= self.capacity() {
+            return self.inner.read(buf);
+        }
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+impl<R: Read> BufRead for BufReader<R> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        if self.pos == self.filled {
+            self.filled = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(self.buffer())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+/// Wraps a writer, collecting small writes together before passing them on.
+///
+/// Anything still buffered is written when this is dropped, but any error from doing so is lost.
+/// Call [`Write::flush`] first to see errors.
+pub struct BufWriter<W: Write> {
+    /// The writer we're buffering.
+    inner: W,
+    /// The data which hasn't been written to `inner` yet.
+    buf: Vec<u8>,
+}
+impl<W: Write> BufWriter<W> {
+    /// Wrap `inner`, with the default capacity.
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Wrap `inner`, with a buffer of `capacity` bytes.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    ///
+    /// Writing to it directly will put that data ahead of whatever is buffered.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Get the data which has been buffered but not written yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Get the capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Write everything buffered to the underlying writer, without flushing it.
+    ///
+    /// On failure, whatever wasn't written stays buffered.
+    fn flush_buf(&mut self) -> Result<()> {
+        let mut written = 0;
+        let result = loop {
+            if written == self.buf.len() {
+                break Ok(());
+            }
+            match self.inner.write(&self.buf[written..]) {
+                Ok(0) => break Err(Error::new(ErrorKind::Io, "failed to write whole buffer")),
+                Ok(len) => written += len,
+                Err(e) => break Err(e),
+            }
+        };
+        self.buf.drain(..written);
+        result
+    }
+}
+impl<W: Write> Write for BufWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.buf.len() + buf.len() > self.capacity() {
+            self.flush_buf()?;
+        }
+        // Big writes would only be copied through our buffer for nothing.
+        if buf.len() >= self.capacity() {
+            return self.inner.write(buf);
+        }
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+impl<W: Write> fmt::Write for BufWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        _ = self.flush_buf();
+    }
+}
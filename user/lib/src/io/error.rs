@@ -0,0 +1,65 @@
+//! The error type for I/O and the other operations userlib does through the kernel.
+
+use core::fmt;
+
+use super::ErrorKind;
+
+/// A result with [`Error`] as its default error type.
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+/// An error from I/O or another operation done through the kernel.
+///
+/// This is the [`ErrorKind`] the kernel (or userlib) reported, along with what was being done
+/// when it happened, if that's known.
+#[derive(Debug, Clone, Copy)]
+pub struct Error {
+    /// What kind of error this is.
+    kind: ErrorKind,
+    /// What was being done when the error happened.
+    context: Option<&'static str>,
+}
+impl Error {
+    /// Construct an error of the given kind, which happened while doing `context`.
+    #[must_use]
+    pub const fn new(kind: ErrorKind, context: &'static str) -> Self {
+        Self {
+            kind,
+            context: Some(context),
+        }
+    }
+
+    /// Get what kind of error this is.
+    #[must_use]
+    pub const fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Get what was being done when the error happened, if that's known.
+    #[must_use]
+    pub const fn context(&self) -> Option<&'static str> {
+        self.context
+    }
+
+    /// Replace what was being done when the error happened.
+    #[must_use]
+    pub const fn with_context(self, context: &'static str) -> Self {
+        Self::new(self.kind, context)
+    }
+}
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            context: None,
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(context) = self.context {
+            write!(f, "{context}: ")?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+impl core::error::Error for Error {}
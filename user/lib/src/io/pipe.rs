@@ -0,0 +1,79 @@
+//! Pipes, for sending bytes from one process (or thread) to another.
+
+use super::{Read, Result, Write};
+use crate::rd::{BorrowedResourceDescriptor, OwnedResourceDescriptor};
+
+/// Create a pipe, returning its read and write ends.
+///
+/// Whatever is written to the [`PipeWriter`] can be read from the [`PipeReader`], in order. The
+/// pipe only holds a few kilobytes, so writes wait for reads to catch up once it's full. Reads
+/// give 0 once the pipe is empty and every copy of the write end is closed, and writes fail with
+/// [`ErrorKind::BrokenPipe`](super::ErrorKind::BrokenPipe) once every copy of the read end is.
+///
+/// Either end can be given to a [`Command`](crate::process::Command) as one of its standard
+/// streams, to connect programs together.
+pub fn pipe() -> Result<(PipeReader, PipeWriter)> {
+    let (reader, writer) = crate::sys::pipe()?;
+    // SAFETY: The kernel just gave us these descriptors, so nothing else owns them.
+    let (reader, writer) = unsafe {
+        (
+            OwnedResourceDescriptor::from_raw(reader),
+            OwnedResourceDescriptor::from_raw(writer),
+        )
+    };
+    Ok((PipeReader(reader), PipeWriter(writer)))
+}
+
+/// The read end of a pipe, from [`pipe`].
+pub struct PipeReader(OwnedResourceDescriptor);
+impl PipeReader {
+    /// Borrow the pipe's resource descriptor.
+    #[must_use]
+    pub fn as_descriptor(&self) -> BorrowedResourceDescriptor<'_> {
+        self.0.borrow()
+    }
+
+    /// Create another handle to the read end of the pipe.
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self(self.0.try_clone()?))
+    }
+}
+impl From<PipeReader> for OwnedResourceDescriptor {
+    fn from(reader: PipeReader) -> Self {
+        reader.0
+    }
+}
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+/// The write end of a pipe, from [`pipe`].
+pub struct PipeWriter(OwnedResourceDescriptor);
+impl PipeWriter {
+    /// Borrow the pipe's resource descriptor.
+    #[must_use]
+    pub fn as_descriptor(&self) -> BorrowedResourceDescriptor<'_> {
+        self.0.borrow()
+    }
+
+    /// Create another handle to the write end of the pipe.
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self(self.0.try_clone()?))
+    }
+}
+impl From<PipeWriter> for OwnedResourceDescriptor {
+    fn from(writer: PipeWriter) -> Self {
+        writer.0
+    }
+}
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+}
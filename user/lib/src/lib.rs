@@ -2,11 +2,27 @@
 
 #![no_std]
 
+// Our own `alloc` module takes the usual name.
+extern crate alloc as rust_alloc;
+
 pub mod alloc;
+pub mod args;
+pub mod collections;
+pub mod env;
+pub mod fb;
 pub mod fs;
 mod init;
+pub mod input;
 pub mod io;
+pub mod net;
+pub mod panic;
+pub mod path;
 pub mod prelude;
+pub mod process;
 pub mod rd;
+pub mod readline;
 pub mod sync;
 pub mod sys;
+pub mod test;
+pub mod thread;
+pub mod time;
@@ -0,0 +1,92 @@
+//! Networking.
+
+use core::time::Duration;
+
+pub use shared::{ErrorKind, Ipv4Addr, SocketAddrV4};
+
+use crate::{io::Result, rd::OwnedResourceDescriptor};
+
+/// A UDP socket.
+pub struct UdpSocket {
+    /// The underlying resource descriptor.
+    descriptor: OwnedResourceDescriptor,
+}
+
+impl UdpSocket {
+    /// Open a socket bound to the given local address.
+    ///
+    /// A port of `0` binds to an unused port.
+    pub fn bind(addr: SocketAddrV4) -> Result<Self> {
+        let descriptor = crate::sys::socket(shared::SocketKind::Udp, 0)?;
+        // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+        let descriptor = unsafe { OwnedResourceDescriptor::from_raw(descriptor) };
+        crate::sys::bind(descriptor.raw(), &addr)?;
+        Ok(Self { descriptor })
+    }
+
+    /// Send a datagram to the given address.
+    ///
+    /// Returns the number of bytes sent.
+    pub fn send_to(&self, buf: &[u8], addr: SocketAddrV4) -> Result<usize> {
+        Ok(crate::sys::send_to(self.descriptor.raw(), buf, &addr)?)
+    }
+
+    /// Wait to receive a datagram.
+    ///
+    /// Returns the received data, which will be at the start of `buf`, and the address it came
+    /// from. Datagrams too long for `buf` are truncated.
+    pub fn recv_from<'a>(&self, buf: &'a mut [u8]) -> Result<(&'a mut [u8], SocketAddrV4)> {
+        let (len, from) = crate::sys::recv_from(self.descriptor.raw(), buf)?;
+        Ok((&mut buf[..len], from))
+    }
+}
+
+/// A socket for sending ICMP echo requests (pings) and receiving their replies.
+pub struct IcmpEchoSocket {
+    /// The underlying resource descriptor.
+    descriptor: OwnedResourceDescriptor,
+}
+
+impl IcmpEchoSocket {
+    /// The length of the header of an echo message.
+    pub const HEADER_LEN: usize = 8;
+    /// The type of an echo request message.
+    pub const TYPE_ECHO_REQUEST: u8 = 8;
+
+    /// Open a new socket.
+    ///
+    /// If `read_timeout` is given, receiving gives up with [`ErrorKind::TimedOut`] after
+    /// waiting that long.
+    pub fn new(read_timeout: Option<Duration>) -> Result<Self> {
+        let read_timeout_ms = read_timeout.map_or(0, |timeout| {
+            u32::try_from(timeout.as_millis())
+                .unwrap_or(u32::MAX)
+                .max(1)
+        });
+        let descriptor = crate::sys::socket(shared::SocketKind::IcmpEcho, read_timeout_ms)?;
+        // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+        let descriptor = unsafe { OwnedResourceDescriptor::from_raw(descriptor) };
+        Ok(Self { descriptor })
+    }
+
+    /// Send an echo request to the given address.
+    ///
+    /// `message` is the whole ICMP message, starting with the header. The kernel fills in the
+    /// identifier and checksum.
+    pub fn send_to(&self, message: &[u8], addr: Ipv4Addr) -> Result<usize> {
+        Ok(crate::sys::send_to(
+            self.descriptor.raw(),
+            message,
+            &SocketAddrV4 { ip: addr, port: 0 },
+        )?)
+    }
+
+    /// Wait to receive an echo reply to one of this socket's requests.
+    ///
+    /// Returns the received message including its header, which will be at the start of `buf`,
+    /// and the address it came from.
+    pub fn recv_from<'a>(&self, buf: &'a mut [u8]) -> Result<(&'a mut [u8], Ipv4Addr)> {
+        let (len, from) = crate::sys::recv_from(self.descriptor.raw(), buf)?;
+        Ok((&mut buf[..len], from.ip))
+    }
+}
@@ -0,0 +1,146 @@
+//! Customizing what happens when a program panics.
+//!
+//! When a program panics, userlib runs the panic hook, then exits the process with a status of 1.
+//! The default hook prints the panic message to standard error, followed by a backtrace if
+//! [`set_backtrace`] turned those on.
+
+use core::{
+    fmt::Write as _,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub use core::panic::PanicInfo;
+
+use crate::{io::Stderr, rust_alloc::boxed::Box, sync::RwLock};
+
+/// The type of a panic hook.
+pub type Hook = Box<dyn Fn(&PanicInfo<'_>) + Send + Sync + 'static>;
+
+/// The most frames [`print_backtrace`] prints, in case the chain of frame pointers is garbage.
+const MAX_FRAMES: usize = 64;
+/// The largest distance [`print_backtrace`] follows a frame pointer, in bytes.
+///
+/// This is a guard against garbage frame pointers, so it doesn't need to be exact, only larger
+/// than any real stack.
+const MAX_STACK_SIZE: usize = 1024 * 1024;
+
+/// The registered panic hook, or `None` to use [`default_hook`].
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+/// Whether a panic is currently being handled.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+/// Whether [`default_hook`] prints a backtrace.
+static BACKTRACE: AtomicBool = AtomicBool::new(false);
+
+/// Register a panic hook, which runs when the program panics, right before it exits.
+///
+/// This replaces any previously registered hook, including the default one.
+///
+/// # Panics
+/// Panics if called from inside a panic hook.
+pub fn set_hook(hook: Hook) {
+    assert!(
+        !PANICKING.load(Ordering::Relaxed),
+        "Can't set the panic hook while panicking"
+    );
+    *HOOK.write() = Some(hook);
+}
+
+/// Unregister the current panic hook, returning it.
+///
+/// The default hook is used again after this. If no hook was registered, then this returns the
+/// default hook.
+///
+/// # Panics
+/// Panics if called from inside a panic hook.
+#[must_use]
+pub fn take_hook() -> Hook {
+    assert!(
+        !PANICKING.load(Ordering::Relaxed),
+        "Can't take the panic hook while panicking"
+    );
+    HOOK.write()
+        .take()
+        .unwrap_or_else(|| Box::new(default_hook))
+}
+
+/// Set whether the default panic hook prints a backtrace.
+///
+/// See [`print_backtrace`] for what this needs to work.
+pub fn set_backtrace(enabled: bool) {
+    BACKTRACE.store(enabled, Ordering::Relaxed);
+}
+
+/// Print the return addresses of the functions on the call stack to standard error, innermost
+/// first.
+///
+/// This walks the chain of frame pointers, so the program needs to be built with
+/// `-C force-frame-pointers=yes` for the addresses to mean anything. Look them up with
+/// `addr2line` on the program's binary to get source locations.
+#[inline(never)]
+pub fn print_backtrace() {
+    let Some(mut stderr) = Stderr::try_lock() else {
+        return;
+    };
+    let mut sp: usize;
+    let mut fp: usize;
+    // SAFETY: This only copies registers.
+    unsafe {
+        core::arch::asm!(
+            "mv {sp}, sp",
+            "mv {fp}, s0",
+            sp = out(reg) sp,
+            fp = out(reg) fp,
+            options(nomem, nostack, preserves_flags),
+        );
+    }
+    _ = writeln!(stderr, "stack backtrace:");
+    for depth in 0..MAX_FRAMES {
+        // Each frame saves the return address and the caller's frame pointer in the two words
+        // below its frame pointer. Stop at anything that can't be a frame further up this stack,
+        // which includes the null frame pointer `start` and new threads begin with.
+        if !fp.is_multiple_of(4) || fp < sp + 8 || fp - sp > MAX_STACK_SIZE {
+            break;
+        }
+        // SAFETY:
+        // `fp` is between the stack pointer and the top of the stack, so the words below it are
+        // part of the stack.
+        let (return_addr, caller_fp) = unsafe {
+            let fp = core::ptr::with_exposed_provenance::<usize>(fp);
+            (fp.sub(1).read(), fp.sub(2).read())
+        };
+        _ = writeln!(stderr, "  {depth:>2}: {return_addr:#010x}");
+        sp = fp;
+        fp = caller_fp;
+    }
+}
+
+/// The panic hook used when no other one is registered.
+///
+/// This prints the panic message to standard error, followed by a backtrace if [`set_backtrace`]
+/// turned those on.
+fn default_hook(info: &PanicInfo<'_>) {
+    crate::eprintln!("\n{info}");
+    if BACKTRACE.load(Ordering::Relaxed) {
+        print_backtrace();
+    }
+}
+
+/// Handle a panic, by running the panic hook and then exiting.
+///
+/// If another panic is being handled (such as when the panic hook itself panics), then this only
+/// prints the message before exiting.
+pub(crate) fn handle(info: &PanicInfo<'_>) -> ! {
+    // SAFETY:
+    // We never return to whatever code might have stderr locked, so it's safe to take it over.
+    // Dropping it right away unlocks it for the hook to use.
+    drop(unsafe { Stderr::force_lock() });
+    if PANICKING.swap(true, Ordering::Relaxed) {
+        crate::eprintln!("\npanicked while handling a panic: {info}");
+    } else {
+        match &*HOOK.read() {
+            Some(hook) => hook(info),
+            None => default_hook(info),
+        }
+    }
+    crate::sys::exit(1);
+}
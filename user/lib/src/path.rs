@@ -0,0 +1,246 @@
+//! Filesystem paths.
+//!
+//! Paths are always UTF-8 and use `/` to separate their components. These types only look at the
+//! text of a path; nothing here checks the filesystem.
+
+use core::{borrow::Borrow, fmt, ops::Deref};
+
+use crate::rust_alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+/// A borrowed path, which is to [`PathBuf`] as [`str`] is to [`String`].
+#[repr(transparent)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Path(str);
+impl Path {
+    /// The root directory.
+    pub const ROOT: &Self = Self::new("/");
+
+    /// Treat a string as a path.
+    #[must_use]
+    pub const fn new(path: &str) -> &Self {
+        // SAFETY: `Path` is a transparent wrapper around `str`, so they have the same layout.
+        unsafe { &*(core::ptr::from_ref::<str>(path) as *const Self) }
+    }
+
+    /// Get the path as a string.
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Get whether the path starts from the root directory.
+    #[must_use]
+    pub fn is_absolute(&self) -> bool {
+        self.0.starts_with('/')
+    }
+
+    /// Get whether the path is relative to some other directory.
+    #[must_use]
+    pub fn is_relative(&self) -> bool {
+        !self.is_absolute()
+    }
+
+    /// Get the path without its final component, or `None` if it has no components.
+    ///
+    /// The parent of a single relative component is the empty path.
+    #[must_use]
+    pub fn parent(&self) -> Option<&Self> {
+        let trimmed = self.trim_trailing_slashes();
+        if trimmed.is_empty() || trimmed == "/" {
+            return None;
+        }
+        Some(match trimmed.rfind('/') {
+            None => Self::new(""),
+            Some(0) => Self::ROOT,
+            Some(idx) => Self::new(&trimmed[..idx]).trimmed(),
+        })
+    }
+
+    /// Get the final component of the path, or `None` if there isn't one or it's `..`.
+    #[must_use]
+    pub fn file_name(&self) -> Option<&str> {
+        let trimmed = self.trim_trailing_slashes();
+        let name = trimmed.rsplit('/').next()?;
+        (!name.is_empty() && name != "..").then_some(name)
+    }
+
+    /// Make a new path by appending `path` to this one.
+    ///
+    /// If `path` is absolute, it replaces this one.
+    #[must_use]
+    pub fn join(&self, path: impl AsRef<Self>) -> PathBuf {
+        let mut joined = self.to_path_buf();
+        joined.push(path);
+        joined
+    }
+
+    /// Iterate over the components of the path, skipping empty and `.` components.
+    #[must_use]
+    pub fn components(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.0
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+    }
+
+    /// Resolve `.` and `..` components and repeated separators, without looking at the
+    /// filesystem.
+    ///
+    /// `..` at the start of a relative path is kept, but `..` at the root directory is dropped.
+    #[must_use]
+    pub fn normalize(&self) -> PathBuf {
+        let mut components = Vec::new();
+        for component in self.components() {
+            match component {
+                ".." if components.last().is_some_and(|&last| last != "..") => {
+                    components.pop();
+                }
+                ".." if self.is_absolute() => {}
+                component => components.push(component),
+            }
+        }
+        let mut normalized = String::new();
+        if self.is_absolute() {
+            normalized.push('/');
+        }
+        normalized.push_str(&components.join("/"));
+        PathBuf(normalized)
+    }
+
+    /// Copy the path into a new [`PathBuf`].
+    #[must_use]
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf(self.0.into())
+    }
+
+    /// Get the path without any trailing separators, unless it's the root directory.
+    fn trim_trailing_slashes(&self) -> &str {
+        match self.0.trim_end_matches('/') {
+            "" if self.is_absolute() => "/",
+            trimmed => trimmed,
+        }
+    }
+
+    /// Get the path without any trailing separators, unless it's the root directory.
+    fn trimmed(&self) -> &Self {
+        Self::new(self.trim_trailing_slashes())
+    }
+}
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl AsRef<Path> for Path {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+impl AsRef<Path> for str {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+impl AsRef<Path> for String {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+impl AsRef<str> for Path {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+impl ToOwned for Path {
+    type Owned = PathBuf;
+
+    fn to_owned(&self) -> PathBuf {
+        self.to_path_buf()
+    }
+}
+
+/// An owned path, which is to [`Path`] as [`String`] is to [`str`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PathBuf(String);
+impl PathBuf {
+    /// Make a new, empty path.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(String::new())
+    }
+
+    /// Borrow the path.
+    #[must_use]
+    pub fn as_path(&self) -> &Path {
+        self
+    }
+
+    /// Append `path` to this path.
+    ///
+    /// If `path` is absolute, it replaces this one.
+    pub fn push(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            self.0.clear();
+        } else if !self.0.is_empty() && !self.0.ends_with('/') {
+            self.0.push('/');
+        }
+        self.0.push_str(path.as_str());
+    }
+
+    /// Remove the final component of this path, returning whether there was one to remove.
+    pub fn pop(&mut self) -> bool {
+        let Some(parent_len) = self.parent().map(|parent| parent.as_str().len()) else {
+            return false;
+        };
+        self.0.truncate(parent_len);
+        true
+    }
+
+    /// Get the path as a string.
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+impl Deref for PathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+impl Borrow<Path> for PathBuf {
+    fn borrow(&self) -> &Path {
+        self
+    }
+}
+impl AsRef<Path> for PathBuf {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+impl fmt::Display for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl From<String> for PathBuf {
+    fn from(path: String) -> Self {
+        Self(path)
+    }
+}
+impl From<&str> for PathBuf {
+    fn from(path: &str) -> Self {
+        Self(path.into())
+    }
+}
+impl From<&Path> for PathBuf {
+    fn from(path: &Path) -> Self {
+        path.to_path_buf()
+    }
+}
+impl From<PathBuf> for String {
+    fn from(path: PathBuf) -> Self {
+        path.0
+    }
+}
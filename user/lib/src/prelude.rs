@@ -1,3 +1,3 @@
 //! Re-exports for commonly-used things userspace programs will want to import.
 
-pub use crate::{print, println};
+pub use crate::{eprint, eprintln, print, println};
@@ -0,0 +1,282 @@
+//! Starting other programs and waiting for them to finish.
+
+use core::fmt;
+
+use crate::{
+    fs::File,
+    io::{Error, ErrorKind, PipeReader, PipeWriter, Result},
+    rd::OwnedResourceDescriptor,
+    rust_alloc::{collections::BTreeMap, string::String, vec::Vec},
+    sync::Mutex,
+};
+
+/// The functions registered with [`at_exit`], in the order they were registered.
+static EXIT_HOOKS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+/// Register `hook` to run when the process exits through [`exit`], which includes returning from
+/// `main`.
+///
+/// Hooks run in the reverse of the order they were registered, and hooks registered by other
+/// hooks run too. They don't run if the process panics or calls [`crate::sys::exit`] directly.
+pub fn at_exit(hook: fn()) {
+    EXIT_HOOKS.lock().push(hook);
+}
+
+/// Run the hooks registered with [`at_exit`], then exit the process with `status`.
+pub fn exit(status: i32) -> ! {
+    loop {
+        // Don't hold the lock while running the hook, in case it registers another one.
+        let hook = EXIT_HOOKS.lock().pop();
+        let Some(hook) = hook else {
+            break;
+        };
+        hook();
+    }
+    crate::sys::exit(status)
+}
+
+/// Builds up how to run a program, then starts it.
+///
+/// By default, the program inherits this process's environment variables and standard streams.
+pub struct Command {
+    /// The path to the program.
+    program: String,
+    /// The arguments to pass, not including the program name.
+    args: Vec<String>,
+    /// Environment variables to set (or unset, if `None`) on top of the inherited ones.
+    env_changes: BTreeMap<String, Option<String>>,
+    /// Whether to start from an empty environment, rather than inheriting this process's.
+    clear_env: bool,
+    /// What to give the program as its stdin, stdout, and stderr.
+    stdio: [Stdio; 3],
+}
+impl Command {
+    /// Start building a command to run the program at `program`, which may be relative to the
+    /// current directory.
+    #[must_use]
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            env_changes: BTreeMap::new(),
+            clear_env: false,
+            stdio: [Stdio::inherit(), Stdio::inherit(), Stdio::inherit()],
+        }
+    }
+
+    /// Add an argument to pass to the program.
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add several arguments to pass to the program.
+    pub fn args<'a>(&mut self, args: impl IntoIterator<Item = &'a str>) -> &mut Self {
+        self.args.extend(args.into_iter().map(String::from));
+        self
+    }
+
+    /// Set an environment variable for the program.
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.env_changes.insert(key.into(), Some(value.into()));
+        self
+    }
+
+    /// Don't pass the environment variable `key` on to the program.
+    pub fn env_remove(&mut self, key: &str) -> &mut Self {
+        self.env_changes.insert(key.into(), None);
+        self
+    }
+
+    /// Don't pass on any of this process's environment variables, only those set with
+    /// [`Self::env`].
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.clear_env = true;
+        self.env_changes.retain(|_, value| value.is_some());
+        self
+    }
+
+    /// Set what the program gets as its stdin.
+    pub fn stdin(&mut self, stdin: impl Into<Stdio>) -> &mut Self {
+        self.stdio[0] = stdin.into();
+        self
+    }
+
+    /// Set what the program gets as its stdout.
+    pub fn stdout(&mut self, stdout: impl Into<Stdio>) -> &mut Self {
+        self.stdio[1] = stdout.into();
+        self
+    }
+
+    /// Set what the program gets as its stderr.
+    pub fn stderr(&mut self, stderr: impl Into<Stdio>) -> &mut Self {
+        self.stdio[2] = stderr.into();
+        self
+    }
+
+    /// Start the program, without waiting for it to finish.
+    pub fn spawn(&mut self) -> Result<Child> {
+        let mut env = if self.clear_env {
+            BTreeMap::new()
+        } else {
+            crate::env::vars().into_iter().collect()
+        };
+        for (key, value) in &self.env_changes {
+            match value {
+                Some(value) => env.insert(key.clone(), value.clone()),
+                None => env.remove(key),
+            };
+        }
+        let args_block = encode_args_block(
+            core::iter::once(self.program.as_str()).chain(self.args.iter().map(String::as_str)),
+            &env,
+        )?;
+        let stdio = [0, 1, 2].map(|num| match &self.stdio[num] {
+            Stdio(StdioInner::Inherit) => num as i32,
+            Stdio(StdioInner::Descriptor(descriptor)) => descriptor.raw(),
+        });
+        let pid = crate::sys::spawn(
+            crate::env::absolute(&self.program).as_str(),
+            &args_block,
+            &stdio,
+        )?;
+        Ok(Child { pid, status: None })
+    }
+
+    /// Run the program and wait for it to finish, returning its exit status.
+    pub fn status(&mut self) -> Result<ExitStatus> {
+        self.spawn()?.wait()
+    }
+}
+
+/// What to give a [`Command`] as one of its standard streams.
+pub struct Stdio(StdioInner);
+impl Stdio {
+    /// Give the program the same stream this process has.
+    #[must_use]
+    pub fn inherit() -> Self {
+        Self(StdioInner::Inherit)
+    }
+}
+impl From<OwnedResourceDescriptor> for Stdio {
+    fn from(descriptor: OwnedResourceDescriptor) -> Self {
+        Self(StdioInner::Descriptor(descriptor))
+    }
+}
+impl From<File> for Stdio {
+    fn from(file: File) -> Self {
+        OwnedResourceDescriptor::from(file).into()
+    }
+}
+impl From<PipeReader> for Stdio {
+    fn from(reader: PipeReader) -> Self {
+        OwnedResourceDescriptor::from(reader).into()
+    }
+}
+impl From<PipeWriter> for Stdio {
+    fn from(writer: PipeWriter) -> Self {
+        OwnedResourceDescriptor::from(writer).into()
+    }
+}
+
+/// The options for [`Stdio`].
+enum StdioInner {
+    /// Use the same stream as this process.
+    Inherit,
+    /// Use this resource descriptor.
+    Descriptor(OwnedResourceDescriptor),
+}
+
+/// A process started by [`Command::spawn`].
+pub struct Child {
+    /// The PID of the process.
+    pid: u32,
+    /// How the process exited, if we've already waited for it.
+    status: Option<ExitStatus>,
+}
+impl Child {
+    /// Get the PID of the process.
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        self.pid
+    }
+
+    /// Wait for the process to exit, returning its exit status.
+    ///
+    /// This can be called again after the process has exited, giving the same status.
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        if let Some(status) = self.status {
+            return Ok(status);
+        }
+        let status = ExitStatus(crate::sys::wait(self.pid)?);
+        self.status = Some(status);
+        Ok(status)
+    }
+}
+
+/// How a process exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(i32);
+impl ExitStatus {
+    /// Get the code the process passed when it exited.
+    #[must_use]
+    pub fn code(self) -> i32 {
+        self.0
+    }
+
+    /// Get whether the process exited successfully, with code 0.
+    #[must_use]
+    pub fn success(self) -> bool {
+        self.0 == 0
+    }
+}
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit code: {}", self.0)
+    }
+}
+
+/// Lay out arguments and environment variables as described at [`shared::PROCESS_ARGS_ADDR`].
+///
+/// Fails with [`ErrorKind::InvalidFormat`] if any of them can't be represented, such as if they
+/// contain NUL bytes.
+fn encode_args_block<'a>(
+    args: impl Iterator<Item = &'a str>,
+    env: &BTreeMap<String, String>,
+) -> Result<Vec<u8>> {
+    let mut num_args = 0_u32;
+    let mut strings = Vec::new();
+    for arg in args {
+        if arg.contains('\0') {
+            return Err(Error::new(
+                ErrorKind::InvalidFormat,
+                "argument contains a NUL byte",
+            ));
+        }
+        strings.extend_from_slice(arg.as_bytes());
+        strings.push(0);
+        num_args += 1;
+    }
+    for (key, value) in env {
+        if key.is_empty() || key.contains(['=', '\0']) || value.contains('\0') {
+            return Err(Error::new(
+                ErrorKind::InvalidFormat,
+                "invalid environment variable",
+            ));
+        }
+        strings.extend_from_slice(key.as_bytes());
+        strings.push(b'=');
+        strings.extend_from_slice(value.as_bytes());
+        strings.push(0);
+    }
+    let mut block = Vec::with_capacity(3 * size_of::<u32>() + strings.len());
+    for field in [
+        num_args,
+        u32::try_from(env.len()).map_err(|_| ErrorKind::LimitReached)?,
+        u32::try_from(strings.len()).map_err(|_| ErrorKind::LimitReached)?,
+    ] {
+        block.extend_from_slice(&field.to_le_bytes());
+    }
+    block.extend_from_slice(&strings);
+    Ok(block)
+}
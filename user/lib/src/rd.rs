@@ -2,6 +2,8 @@
 
 use core::marker::PhantomData;
 
+use crate::io::{ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
 /// An RAII resource representing ownership over a resource descriptor.
 ///
 /// Ownership means that this object has exclusive access (up to the borrow checker) and gets
@@ -12,18 +14,37 @@ pub struct OwnedResourceDescriptor {
 }
 
 impl OwnedResourceDescriptor {
-    /// Construct for a given raw resource descriptor.
+    /// Take ownership of a raw resource descriptor, which gets closed when the result is dropped.
+    ///
+    /// # Safety
+    /// `raw` must be an open resource descriptor which nothing else owns, such as one returned by
+    /// [`Self::into_raw`] or freshly returned by a syscall.
     #[must_use]
-    pub(crate) fn from_raw(raw: i32) -> Self {
+    pub unsafe fn from_raw(raw: i32) -> Self {
         Self { raw }
     }
 
-    /// Get the raw resource descriptor.
+    /// Get the raw resource descriptor, without giving up ownership.
     #[must_use]
-    pub(crate) fn raw(&self) -> i32 {
+    pub fn raw(&self) -> i32 {
         self.raw
     }
 
+    /// Give up ownership of the resource descriptor, returning the raw descriptor without closing
+    /// it.
+    #[must_use]
+    pub fn into_raw(self) -> i32 {
+        core::mem::ManuallyDrop::new(self).raw
+    }
+
+    /// Create a new resource descriptor pointing at the same resource, which shares its offset
+    /// and other state with this one.
+    pub fn try_clone(&self) -> Result<Self> {
+        let raw = crate::sys::dup(self.raw)?;
+        // SAFETY: The kernel just gave us this descriptor, so nothing else owns it.
+        Ok(unsafe { Self::from_raw(raw) })
+    }
+
     /// Borrow this resource descriptor.
     #[must_use]
     pub fn borrow(&self) -> BorrowedResourceDescriptor<'_> {
@@ -45,6 +66,7 @@ impl Drop for OwnedResourceDescriptor {
 /// This type can be easily constructed from an `&OwnedResourceDescriptor`, but also might exist in
 /// other contexts where the resource descriptor exists but doesn't have an
 /// [`OwnedResourceDescriptor`] for the borrow checker to look at.
+#[derive(Clone, Copy)]
 pub struct BorrowedResourceDescriptor<'a> {
     /// The number of this resource descriptor.
     raw: i32,
@@ -61,7 +83,7 @@ impl BorrowedResourceDescriptor<'_> {
     }
 
     /// Get the raw resource descriptor.
-    pub(crate) fn raw(&self) -> i32 {
+    pub(crate) fn raw(self) -> i32 {
         self.raw
     }
 }
@@ -70,3 +92,52 @@ impl<'a, 'b: 'a> From<&'b OwnedResourceDescriptor> for BorrowedResourceDescripto
         rd.borrow()
     }
 }
+
+impl Read for BorrowedResourceDescriptor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(crate::sys::read(self.raw(), buf)?)
+    }
+}
+impl Write for BorrowedResourceDescriptor<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(crate::sys::write(self.raw(), buf)?)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // Writes go straight to the kernel, so there's nothing buffered here.
+        Ok(())
+    }
+}
+impl Seek for BorrowedResourceDescriptor<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (whence, offset) = match pos {
+            SeekFrom::Start(offset) => (
+                shared::SeekWhence::Start,
+                i64::try_from(offset).map_err(|_| ErrorKind::InvalidFormat)?,
+            ),
+            SeekFrom::End(offset) => (shared::SeekWhence::End, offset),
+            SeekFrom::Current(offset) => (shared::SeekWhence::Current, offset),
+        };
+        Ok(crate::sys::seek(self.raw(), whence, offset)?)
+    }
+}
+
+impl Read for OwnedResourceDescriptor {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.borrow().read(buf)
+    }
+}
+impl Write for OwnedResourceDescriptor {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.borrow().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.borrow().flush()
+    }
+}
+impl Seek for OwnedResourceDescriptor {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.borrow().seek(pos)
+    }
+}
@@ -0,0 +1,388 @@
+//! Line editing for interactive programs.
+//!
+//! The console hands us raw keypresses, without echoing them or handling any editing. An
+//! [`Editor`] reads a line from the console while letting the user move around it and edit it:
+//!
+//! - Left/right arrows (or ctrl+B/ctrl+F) move the cursor.
+//! - Home/end (or ctrl+A/ctrl+E) move to the start or end of the line.
+//! - Backspace and delete remove the character before or under the cursor.
+//! - Ctrl+K and ctrl+U delete everything after or before the cursor.
+//! - Ctrl+C abandons the line, and starts again on a fresh one.
+//! - Tab asks the [`Completer`], if there is one, to finish the word before the cursor.
+//! - Up/down arrows (or ctrl+P/ctrl+N) go back and forth through the lines added to the history
+//!   with [`Editor::add_history`].
+//!
+//! The whole line is redrawn after each edit, so lines longer than the terminal is wide won't
+//! display properly.
+
+use core::fmt::Write as _;
+
+use crate::{
+    io::{Result, Stdin},
+    rust_alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec},
+};
+
+/// The number of lines an [`Editor`] remembers by default.
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// Something which suggests ways to finish what the user has typed.
+pub trait Completer {
+    /// Suggest completions for `line`, whose cursor is at byte offset `cursor`.
+    fn complete(&mut self, line: &str, cursor: usize) -> Completion;
+}
+impl<F: FnMut(&str, usize) -> Completion> Completer for F {
+    fn complete(&mut self, line: &str, cursor: usize) -> Completion {
+        self(line, cursor)
+    }
+}
+
+/// Suggested completions for a line.
+#[derive(Debug, Clone, Default)]
+pub struct Completion {
+    /// The byte offset in the line where the text being completed starts.
+    ///
+    /// Each candidate replaces everything from here up to the cursor.
+    pub start: usize,
+    /// The candidates to replace the text with.
+    pub candidates: Vec<String>,
+}
+
+/// Reads lines from the console, with editing.
+pub struct Editor {
+    /// What gets asked for completions when tab is pressed.
+    completer: Option<Box<dyn Completer>>,
+    /// Previous lines which can be recalled, oldest first.
+    history: VecDeque<String>,
+    /// The most lines to keep in `history`, after which the oldest are forgotten.
+    history_limit: usize,
+}
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Editor {
+    /// Create an editor, without any completion or history.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            completer: None,
+            history: VecDeque::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+
+    /// Use `completer` to complete words when tab is pressed.
+    pub fn set_completer(&mut self, completer: impl Completer + 'static) {
+        self.completer = Some(Box::new(completer));
+    }
+
+    /// Add `line` to the history, so it can be recalled with the up arrow.
+    ///
+    /// Blank lines, and lines which are the same as the last one added, aren't added. Once the
+    /// history is full, adding a line forgets the oldest one.
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.back().is_some_and(|last| last == line) {
+            return;
+        }
+        if self.history.len() == self.history_limit {
+            self.history.pop_front();
+        }
+        if self.history_limit > 0 {
+            self.history.push_back(line.into());
+        }
+    }
+
+    /// Get the lines in the history, oldest first.
+    pub fn history(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.history.iter().map(String::as_str)
+    }
+
+    /// Set the most lines to keep in the history, forgetting the oldest ones if there are more.
+    ///
+    /// This is 100 by default.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    /// Show `prompt`, then read a line from the console, with editing.
+    ///
+    /// The returned line doesn't include the newline. This locks [`Stdin`] while it runs, so
+    /// panics if it's already locked.
+    pub fn read_line(&mut self, prompt: &str) -> Result<String> {
+        let mut stdin = Stdin::lock();
+        let mut chars = stdin.chars();
+        let mut state = LineState {
+            prompt,
+            line: String::new(),
+            cursor: 0,
+        };
+        // How many lines back in the history we are, and the line being typed before we started
+        // going back (since it isn't in the history).
+        let mut history_pos = 0;
+        let mut typed_line = String::new();
+        state.redraw();
+        // If there's nothing more to read, we return as much of the line as we got.
+        while let Some(c) = chars.next().transpose()? {
+            match Key::decode(c, &mut chars)? {
+                Key::Enter => break,
+                Key::Char(c) => state.insert(c),
+                Key::Backspace => {
+                    if state.move_left() {
+                        state.delete();
+                    }
+                }
+                Key::Delete => state.delete(),
+                Key::Left => _ = state.move_left(),
+                Key::Right => _ = state.move_right(),
+                Key::Home => state.cursor = 0,
+                Key::End => state.cursor = state.line.len(),
+                Key::KillToEnd => state.line.truncate(state.cursor),
+                Key::KillToStart => {
+                    state.line.drain(..state.cursor);
+                    state.cursor = 0;
+                }
+                Key::Tab => {
+                    if let Some(completer) = &mut self.completer {
+                        let completion = completer.complete(&state.line, state.cursor);
+                        state.complete(completion);
+                    }
+                }
+                Key::Up if history_pos < self.history.len() => {
+                    if history_pos == 0 {
+                        typed_line = core::mem::take(&mut state.line);
+                    }
+                    history_pos += 1;
+                    state
+                        .line
+                        .clone_from(&self.history[self.history.len() - history_pos]);
+                    state.cursor = state.line.len();
+                }
+                Key::Down if history_pos > 0 => {
+                    history_pos -= 1;
+                    if history_pos == 0 {
+                        state.line = core::mem::take(&mut typed_line);
+                    } else {
+                        state
+                            .line
+                            .clone_from(&self.history[self.history.len() - history_pos]);
+                    }
+                    state.cursor = state.line.len();
+                }
+                // Moving past either end of the history does nothing, like keys we ignore.
+                Key::Up | Key::Down | Key::Other => continue,
+                Key::Interrupt => {
+                    // Leave what was typed on the screen, as other shells do, but forget it.
+                    state.cursor = state.line.len();
+                    state.redraw();
+                    crate::println!("^C");
+                    state.line.clear();
+                    state.cursor = 0;
+                    history_pos = 0;
+                    typed_line.clear();
+                }
+            }
+            state.redraw();
+        }
+        crate::println!();
+        Ok(state.line)
+    }
+}
+
+/// A line being edited.
+struct LineState<'a> {
+    /// The prompt shown before the line.
+    prompt: &'a str,
+    /// What's been typed.
+    line: String,
+    /// The byte offset of the cursor in `line`, which is always on a character boundary.
+    cursor: usize,
+}
+impl LineState<'_> {
+    /// Insert a character at the cursor.
+    fn insert(&mut self, c: char) {
+        self.line.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Delete the character under the cursor, if there is one.
+    fn delete(&mut self) {
+        if self.cursor < self.line.len() {
+            self.line.remove(self.cursor);
+        }
+    }
+
+    /// Move the cursor back a character, returning whether it moved.
+    fn move_left(&mut self) -> bool {
+        let Some(c) = self.line[..self.cursor].chars().next_back() else {
+            return false;
+        };
+        self.cursor -= c.len_utf8();
+        true
+    }
+
+    /// Move the cursor forward a character, returning whether it moved.
+    fn move_right(&mut self) -> bool {
+        let Some(c) = self.line[self.cursor..].chars().next() else {
+            return false;
+        };
+        self.cursor += c.len_utf8();
+        true
+    }
+
+    /// Apply suggested completions.
+    ///
+    /// With one candidate, we use it. With several, we fill in as much as they have in common, and
+    /// list them if that doesn't add anything.
+    fn complete(&mut self, completion: Completion) {
+        let Completion { start, candidates } = completion;
+        if start > self.cursor || !self.line.is_char_boundary(start) {
+            return;
+        }
+        let Some((first, rest)) = candidates.split_first() else {
+            return;
+        };
+        let common_len = rest.iter().fold(first.len(), |len, candidate| {
+            common_prefix_len(&first[..len], candidate)
+        });
+        let typed = &self.line[start..self.cursor];
+        if rest.is_empty() || common_len > typed.len() {
+            self.line
+                .replace_range(start..self.cursor, &first[..common_len]);
+            self.cursor = start + common_len;
+        } else {
+            crate::println!();
+            for candidate in &candidates {
+                crate::print!("{candidate}  ");
+            }
+            crate::println!();
+        }
+    }
+
+    /// Draw the prompt and the line, with the terminal's cursor where ours is.
+    fn redraw(&self) {
+        let mut out = String::new();
+        // Go back to the start of the line, and clear whatever was drawn after it.
+        _ = write!(out, "\r{}{}\x1b[K", self.prompt, self.line);
+        let after_cursor = self.line[self.cursor..].chars().count();
+        if after_cursor > 0 {
+            _ = write!(out, "\x1b[{after_cursor}D");
+        }
+        crate::print!("{out}");
+    }
+}
+
+/// Get the length in bytes of the longest common prefix of `a` and `b`.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|&((_, a), b)| a != b)
+        .map_or(a.len().min(b.len()), |((idx, _), _)| idx)
+}
+
+/// What a keypress (or sequence of characters for one) does.
+enum Key {
+    /// Insert a character.
+    Char(char),
+    /// Finish the line.
+    Enter,
+    /// Delete the character before the cursor.
+    Backspace,
+    /// Delete the character under the cursor.
+    Delete,
+    /// Move the cursor back a character.
+    Left,
+    /// Move the cursor forward a character.
+    Right,
+    /// Go back to the previous line in the history.
+    Up,
+    /// Go forward to the next line in the history.
+    Down,
+    /// Move the cursor to the start of the line.
+    Home,
+    /// Move the cursor to the end of the line.
+    End,
+    /// Delete everything from the cursor to the end of the line.
+    KillToEnd,
+    /// Delete everything from the start of the line to the cursor.
+    KillToStart,
+    /// Ask for completions.
+    Tab,
+    /// Abandon the line and start a new one.
+    Interrupt,
+    /// A key we don't do anything with.
+    Other,
+}
+impl Key {
+    /// Decode the keypress starting with `c`, reading the rest of an escape sequence from `chars`
+    /// if it starts one.
+    fn decode(c: char, chars: &mut impl Iterator<Item = Result<char>>) -> Result<Self> {
+        Ok(match c {
+            '\r' | '\n' => Self::Enter,
+            '\x7f' | '\x08' => Self::Backspace,
+            '\t' => Self::Tab,
+            '\x01' => Self::Home,
+            '\x03' => Self::Interrupt,
+            '\x02' => Self::Left,
+            '\x05' => Self::End,
+            '\x06' => Self::Right,
+            '\x0b' => Self::KillToEnd,
+            '\x0e' => Self::Down,
+            '\x10' => Self::Up,
+            '\x15' => Self::KillToStart,
+            '\x1b' => Self::decode_escape(chars)?,
+            c if c.is_control() => Self::Other,
+            c => Self::Char(c),
+        })
+    }
+
+    /// Decode the rest of an escape sequence, after the escape character.
+    fn decode_escape(chars: &mut impl Iterator<Item = Result<char>>) -> Result<Self> {
+        let mut next = || chars.next().transpose().map(|c| c.unwrap_or('\0'));
+        match next()? {
+            // Some terminals send arrows, home, and end as "ESC O" followed by a letter.
+            'O' => Ok(match next()? {
+                'A' => Self::Up,
+                'B' => Self::Down,
+                'C' => Self::Right,
+                'D' => Self::Left,
+                'H' => Self::Home,
+                'F' => Self::End,
+                _ => Self::Other,
+            }),
+            '[' => {
+                // Control sequences are some numeric parameters, then a final letter or `~`.
+                let mut param = 0_u32;
+                loop {
+                    match next()? {
+                        c @ '0'..='9' => {
+                            param = param
+                                .saturating_mul(10)
+                                .saturating_add(c as u32 - '0' as u32);
+                        }
+                        ';' => {}
+                        'A' => return Ok(Self::Up),
+                        'B' => return Ok(Self::Down),
+                        'C' => return Ok(Self::Right),
+                        'D' => return Ok(Self::Left),
+                        'H' => return Ok(Self::Home),
+                        'F' => return Ok(Self::End),
+                        '~' => {
+                            return Ok(match param {
+                                1 | 7 => Self::Home,
+                                3 => Self::Delete,
+                                4 | 8 => Self::End,
+                                _ => Self::Other,
+                            });
+                        }
+                        _ => return Ok(Self::Other),
+                    }
+                }
+            }
+            _ => Ok(Self::Other),
+        }
+    }
+}
@@ -1,5 +1,15 @@
 //! Synchronization primitives
 
+mod condvar;
+mod lazy_lock;
+mod mutex;
+mod once_lock;
+mod rw_lock;
 mod spin_lock;
 
+pub use condvar::*;
+pub use lazy_lock::*;
+pub use mutex::*;
+pub use once_lock::*;
+pub use rw_lock::*;
 pub use spin_lock::*;
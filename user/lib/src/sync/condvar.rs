@@ -0,0 +1,69 @@
+//! A condition variable, for waiting until another task changes the value in a [`Mutex`].
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::{Mutex, MutexGuard};
+
+/// A condition variable, which lets tasks sleep until they're notified by another task.
+///
+/// As with the standard library's condition variable, waiting tasks can wake up without being
+/// notified, so they should check the condition they're waiting on again after they wake.
+/// [`Condvar::wait_while`] does this for you.
+pub struct Condvar {
+    /// A counter which is bumped on every notification.
+    ///
+    /// Waiters sleep on this value, so that a notification between unlocking the mutex and
+    /// sleeping stops them from sleeping.
+    seq: AtomicU32,
+}
+impl Condvar {
+    /// Construct a new condition variable.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// Unlock the mutex and sleep until notified, then lock it again.
+    #[must_use]
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let seq = self.seq.load(Ordering::Relaxed);
+        let mutex: &'a Mutex<T> = guard.mutex();
+        drop(guard);
+        if crate::sys::futex_wait(&self.seq, seq).is_err() {
+            crate::sys::sched_yield();
+        }
+        mutex.lock()
+    }
+
+    /// Sleep until notified, for as long as `condition` returns `true` on the value in the mutex.
+    #[must_use]
+    pub fn wait_while<'a, T: ?Sized>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Wake one task waiting on this condition variable, if there are any.
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        _ = crate::sys::futex_wake(&self.seq, 1);
+    }
+
+    /// Wake every task waiting on this condition variable.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        _ = crate::sys::futex_wake(&self.seq, u32::MAX);
+    }
+}
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,59 @@
+//! A value which is initialized the first time it's used, from any thread.
+
+use core::{cell::UnsafeCell, ops::Deref};
+
+use super::OnceLock;
+
+/// A value which is initialized the first time it's accessed.
+///
+/// If several tasks access the value at once, one of them runs the initialization function and
+/// the rest sleep until it's done.
+///
+/// Note that the default value for `F` is a function pointer, which requires the function to not
+/// be a closure that captures values. Most other meaningful types aren't nameable, so they can't
+/// be used in a `static` variable.
+pub struct LazyLock<T, F = fn() -> T> {
+    /// The value, once it's been initialized.
+    value: OnceLock<T>,
+    /// The function to initialize the value, until it's been called.
+    init_func: UnsafeCell<Option<F>>,
+}
+impl<T, F: FnOnce() -> T> LazyLock<T, F> {
+    /// Construct a new [`LazyLock`] that will call the given function to initialize.
+    pub const fn new(f: F) -> Self {
+        Self {
+            value: OnceLock::new(),
+            init_func: UnsafeCell::new(Some(f)),
+        }
+    }
+
+    /// Force the value to be initialized, and get a reference to it.
+    ///
+    /// Forcing the value again from inside the initialization function will never return.
+    pub fn force(this: &Self) -> &T {
+        this.value.get_or_init(|| {
+            // SAFETY:
+            // `OnceLock` only runs one initializer at a time, and only this one touches
+            // `init_func`.
+            let init_func = unsafe { &mut *this.init_func.get() }.take();
+            init_func.expect("A failed initializer would have aborted the process")()
+        })
+    }
+}
+impl<T, F: FnOnce() -> T> Deref for LazyLock<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        Self::force(self)
+    }
+}
+impl<T: Default> Default for LazyLock<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+// SAFETY:
+// The initialization function is only ever run by one thread, which amounts to sending it there,
+// and then the value is shared as with a `OnceLock<T>`.
+unsafe impl<T: Send + Sync, F: Send> Sync for LazyLock<T, F> {}
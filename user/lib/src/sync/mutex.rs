@@ -0,0 +1,143 @@
+//! A mutex which sleeps on contention.
+//!
+//! Unlike [`SpinLock`](super::SpinLock), a contended [`Mutex`] blocks in the kernel with
+//! [`futex_wait`](crate::sys::futex_wait) until the holder unlocks it, instead of repeatedly
+//! yielding.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The lock isn't held.
+const UNLOCKED: u32 = 0;
+/// The lock is held, and nobody is waiting for it.
+const LOCKED: u32 = 1;
+/// The lock is held, and someone might be waiting for it.
+const CONTENDED: u32 = 2;
+
+/// A mutual exclusion lock, which sleeps when contended.
+///
+/// Panics abort the process, so unlike the standard library's mutex, this one is never poisoned.
+pub struct Mutex<T: ?Sized> {
+    /// The lock state, which is one of [`UNLOCKED`], [`LOCKED`], or [`CONTENDED`].
+    state: AtomicU32,
+    /// The value stored in the lock.
+    value: UnsafeCell<T>,
+}
+impl<T> Mutex<T> {
+    /// Construct a [`Mutex`] to wrap the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Destruct the mutex and return the inner value.
+    ///
+    /// This function does not have to lock because consuming the value means it cannot be in use
+    /// anywhere else.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// Get an exclusive reference to the inner value from an exclusive reference to the outer
+    /// value.
+    ///
+    /// This function does not have to lock because the exclusive reference to the value means it
+    /// cannot be in use anywhere else.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Lock the mutex, returning an RAII guard.
+    ///
+    /// If the mutex is already locked, then this method will sleep until the task holding the lock
+    /// releases it.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+        MutexGuard { mutex: self }
+    }
+
+    /// Attempt to lock the mutex without blocking.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { mutex: self })
+    }
+
+    /// Wait for the lock to be released, then take it.
+    #[cold]
+    fn lock_contended(&self) {
+        // We can't tell whether anyone else is waiting, so we have to mark the lock as contended
+        // to make sure whoever holds it next wakes someone up.
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            if crate::sys::futex_wait(&self.state, CONTENDED).is_err() {
+                crate::sys::sched_yield();
+            }
+        }
+    }
+
+    /// Release the lock, waking a waiter if there might be one.
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            // If this fails, the waiter will find the lock free whenever it next runs anyways.
+            _ = crate::sys::futex_wake(&self.state, 1);
+        }
+    }
+}
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+// UnsafeCell implements `Send` as appropriate, so we only need `Sync`.
+
+// SAFETY:
+// Sharing the mutex between threads corresponds to sending the value to whichever thread locks
+// the mutex.
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+/// An RAII guard for a [`Mutex`].
+///
+/// This value is constructed by calling [`Mutex::lock`] and related methods.
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Get the mutex that this guard locks.
+    pub(super) fn mutex(&self) -> &'a Mutex<T> {
+        self.mutex
+    }
+}
+impl<T: ?Sized> core::ops::Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // We hold the lock, so we have exclusive access.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+impl<T: ?Sized> core::ops::DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // We hold the lock, so we have exclusive access.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
@@ -0,0 +1,170 @@
+//! A cell which can be initialized once, from any thread.
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The value hasn't been initialized, and nobody is initializing it.
+const UNINIT: u32 = 0;
+/// Someone is initializing the value, and nobody is waiting for them.
+const RUNNING: u32 = 1;
+/// Someone is initializing the value, and others might be waiting for them.
+const RUNNING_WAITING: u32 = 2;
+/// The value has been initialized.
+const COMPLETE: u32 = 3;
+
+/// A locked value which can only be written to once.
+///
+/// Unlike `util::cell::OnceLock`, tasks which try to initialize the value while someone else is
+/// initializing it sleep until they're done, instead of failing.
+pub struct OnceLock<T> {
+    /// The state of initialization, which is one of [`UNINIT`], [`RUNNING`], [`RUNNING_WAITING`],
+    /// or [`COMPLETE`].
+    state: AtomicU32,
+    /// The inner value, which is initialized once `state` is [`COMPLETE`].
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+impl<T> OnceLock<T> {
+    /// Construct a new lock, without a written value.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(UNINIT),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Get the value, if it has already been initialized.
+    pub fn get(&self) -> Option<&T> {
+        (self.state.load(Ordering::Acquire) == COMPLETE).then(|| {
+            // SAFETY:
+            // The value is initialized, and nobody has exclusive access to it anymore.
+            unsafe { (*self.value.get()).assume_init_ref() }
+        })
+    }
+
+    /// Get an exclusive reference to the value, if it has already been initialized.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        (*self.state.get_mut() == COMPLETE).then(|| {
+            // SAFETY:
+            // The value is initialized, and we have exclusive access to it.
+            unsafe { self.value.get_mut().assume_init_mut() }
+        })
+    }
+
+    /// Attempt to set the value.
+    ///
+    /// If the value has already been set, then the given value is returned in an `Err`. If
+    /// someone else is initializing the value, then this waits for them to finish.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().expect("Only called if the value is unset"));
+        value.map_or(Ok(()), Err)
+    }
+
+    /// Get the value, initializing it with `f` if it hasn't been yet.
+    ///
+    /// If someone else is initializing the value, then this waits for them to finish. Calling this
+    /// again from inside `f` will never return.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        match self.get_or_try_init(|| Ok::<T, core::convert::Infallible>(f())) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Get the value, initializing it with `f` if it hasn't been yet.
+    ///
+    /// If `f` fails, then the error is returned and the value is left uninitialized, so a later
+    /// call can try again. If someone else is initializing the value, then this waits for them to
+    /// finish. Calling this again from inside `f` will never return.
+    pub fn get_or_try_init<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+        let mut f = Some(f);
+        loop {
+            match self
+                .state
+                .compare_exchange(UNINIT, RUNNING, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    let Some(f) = f.take() else {
+                        unreachable!("We only take the lock to initialize once")
+                    };
+                    return self.initialize(f);
+                }
+                Err(COMPLETE) => {
+                    // SAFETY:
+                    // The value is initialized, and nobody has exclusive access to it anymore.
+                    return Ok(unsafe { (*self.value.get()).assume_init_ref() });
+                }
+                Err(state) => {
+                    // Make sure whoever is initializing wakes us when they're done.
+                    let marked_waiting = state == RUNNING_WAITING
+                        || self
+                            .state
+                            .compare_exchange(
+                                RUNNING,
+                                RUNNING_WAITING,
+                                Ordering::Relaxed,
+                                Ordering::Relaxed,
+                            )
+                            .is_ok();
+                    if marked_waiting
+                        && crate::sys::futex_wait(&self.state, RUNNING_WAITING).is_err()
+                    {
+                        crate::sys::sched_yield();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Initialize the value with `f`, after having set the state to [`RUNNING`].
+    fn initialize<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+        let (result, new_state) = match f() {
+            Ok(value) => {
+                // SAFETY:
+                // We're the one running initialization, so we have exclusive access.
+                let value = unsafe { &mut *self.value.get() }.write(value);
+                (Ok(&*value), COMPLETE)
+            }
+            Err(e) => (Err(e), UNINIT),
+        };
+        if self.state.swap(new_state, Ordering::Release) == RUNNING_WAITING {
+            // If this fails, the waiters will see the new state whenever they next run anyways.
+            _ = crate::sys::futex_wake(&self.state, u32::MAX);
+        }
+        result
+    }
+}
+impl<T> Default for OnceLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+/// Construct a [`OnceLock`] with the value already inside.
+impl<T> From<T> for OnceLock<T> {
+    fn from(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(COMPLETE),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+}
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            // SAFETY:
+            // The value is initialized, and we're never going to use it again.
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+// SAFETY:
+// A `OnceLock<T>` is equivalent to a `T`.
+unsafe impl<T: Send> Send for OnceLock<T> {}
+// SAFETY:
+// Any thread can initialize the value, which amounts to sending it to every other thread, and then
+// every thread can share it.
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
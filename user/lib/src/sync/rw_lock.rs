@@ -0,0 +1,207 @@
+//! A readers-writer lock which sleeps on contention.
+//!
+//! Writers take priority: once a writer is waiting, new readers wait behind it, so a steady stream
+//! of readers can't keep a writer out forever.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The value of `state` while a writer holds the lock.
+const WRITE_LOCKED: u32 = u32::MAX;
+/// The most readers that can hold the lock at once.
+const MAX_READERS: u32 = WRITE_LOCKED - 1;
+
+/// A lock which allows either many readers or one writer at a time.
+///
+/// Panics abort the process, so unlike the standard library's lock, this one is never poisoned.
+pub struct RwLock<T: ?Sized> {
+    /// The number of readers holding the lock, or [`WRITE_LOCKED`] if a writer holds it.
+    ///
+    /// Blocked tasks sleep on this value.
+    state: AtomicU32,
+    /// The number of writers waiting for the lock, which new readers wait behind.
+    writers_waiting: AtomicU32,
+    /// The number of tasks sleeping on `state`, so unlocking can skip waking nobody.
+    sleepers: AtomicU32,
+    /// The value stored in the lock.
+    value: UnsafeCell<T>,
+}
+impl<T> RwLock<T> {
+    /// Construct a [`RwLock`] to wrap the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            writers_waiting: AtomicU32::new(0),
+            sleepers: AtomicU32::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Destruct the lock and return the inner value.
+    ///
+    /// This function does not have to lock because consuming the value means it cannot be in use
+    /// anywhere else.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// Get an exclusive reference to the inner value from an exclusive reference to the outer
+    /// value.
+    ///
+    /// This function does not have to lock because the exclusive reference to the value means it
+    /// cannot be in use anywhere else.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Lock the value for shared reading, returning an RAII guard.
+    ///
+    /// If a writer holds the lock or is waiting for it, then this method will sleep until the
+    /// writer is done.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            let state = self.state.load(Ordering::Relaxed);
+            if self.readable(state) {
+                continue;
+            }
+            self.sleep(state);
+        }
+    }
+
+    /// Attempt to lock the value for shared reading without blocking.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while self.readable(state) {
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(RwLockReadGuard { lock: self }),
+                Err(new_state) => state = new_state,
+            }
+        }
+        None
+    }
+
+    /// Lock the value for exclusive writing, returning an RAII guard.
+    ///
+    /// If anyone else holds the lock, then this method will sleep until they release it.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        if let Some(guard) = self.try_write() {
+            return guard;
+        }
+        self.writers_waiting.fetch_add(1, Ordering::Relaxed);
+        while let Err(state) =
+            self.state
+                .compare_exchange(0, WRITE_LOCKED, Ordering::Acquire, Ordering::Relaxed)
+        {
+            self.sleep(state);
+        }
+        self.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Attempt to lock the value for exclusive writing without blocking.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITE_LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockWriteGuard { lock: self })
+    }
+
+    /// Check whether a new reader may take the lock while it's in the given state.
+    fn readable(&self, state: u32) -> bool {
+        state < MAX_READERS && self.writers_waiting.load(Ordering::Relaxed) == 0
+    }
+
+    /// Sleep until `state` changes from the given value.
+    #[cold]
+    fn sleep(&self, state: u32) {
+        // Unlocking changes `state` before checking `sleepers`, so either it sees us here and
+        // wakes us, or the kernel sees the new state and doesn't put us to sleep.
+        self.sleepers.fetch_add(1, Ordering::SeqCst);
+        if crate::sys::futex_wait(&self.state, state).is_err() {
+            crate::sys::sched_yield();
+        }
+        self.sleepers.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Wake everyone sleeping on the lock, if there's anyone.
+    fn wake_sleepers(&self) {
+        if self.sleepers.load(Ordering::SeqCst) > 0 {
+            // If this fails, the sleepers will find the lock free whenever they next run anyways.
+            _ = crate::sys::futex_wake(&self.state, u32::MAX);
+        }
+    }
+}
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+// UnsafeCell implements `Send` as appropriate, so we only need `Sync`.
+
+// SAFETY:
+// Readers on different threads share the value, so it must be `Sync`, and writers correspond to
+// sending the value to whichever thread writes, so it must be `Send`.
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// An RAII guard for shared read access to an [`RwLock`].
+///
+/// This value is constructed by calling [`RwLock::read`] and related methods.
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+impl<T: ?Sized> core::ops::Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // We hold a read lock, so nobody has exclusive access.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.lock.state.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.lock.wake_sleepers();
+        }
+    }
+}
+
+/// An RAII guard for exclusive write access to an [`RwLock`].
+///
+/// This value is constructed by calling [`RwLock::write`] and related methods.
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+impl<T: ?Sized> core::ops::Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // We hold the write lock, so we have exclusive access.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+impl<T: ?Sized> core::ops::DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // We hold the write lock, so we have exclusive access.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::SeqCst);
+        self.lock.wake_sleepers();
+    }
+}
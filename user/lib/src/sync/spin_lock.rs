@@ -1,8 +1,8 @@
 //! A spin-lock implementation.
 //!
 //! This lock supports only the most basic spinning, with yielding the thread to the kernel on
-//! contention. TODO Add support for a smarter lock with futex-like functionality once the kernel
-//! implements an appropriate set of syscalls.
+//! contention. Prefer [`Mutex`](super::Mutex), which sleeps until the lock is released, for locks
+//! that might be held for a while.
 
 use core::{
     cell::UnsafeCell,
@@ -5,7 +5,7 @@
 
 use core::ptr::NonNull;
 
-pub use shared::Syscall;
+pub use shared::{ShutdownKind, Syscall};
 
 /// Read a character from the console.
 pub fn getchar() -> Result<char, shared::ErrorKind> {
@@ -21,33 +21,65 @@ pub fn getchar() -> Result<char, shared::ErrorKind> {
     Ok(buf.into())
 }
 
+/// Read a character from the console, if one has already been typed.
+///
+/// Returns `None` instead of waiting if nothing has been typed.
+pub fn getchar_nonblocking() -> Result<Option<char>, shared::ErrorKind> {
+    let mut buf = 0_u8;
+    match read_timeout(
+        0,
+        core::slice::from_mut(&mut buf),
+        core::time::Duration::ZERO,
+    ) {
+        Ok(0) | Err(shared::ErrorKind::TimedOut) => Ok(None),
+        Ok(_) => Ok(Some(buf.into())),
+        Err(e) => Err(e),
+    }
+}
+
 /// Get the PID of the currently-active process.
 #[must_use]
 pub fn get_pid() -> u32 {
     // SAFETY: This matches the definition of this syscall.
-    unsafe { syscall(Syscall::GetPid as u32, [0; 3]) }.0
+    unsafe { syscall(Syscall::GetPid as u32, [0; 5]) }.0
 }
 
 /// Yield the current time slice.
 pub fn sched_yield() {
     // SAFETY: This matches the definition of this syscall.
-    _ = unsafe { syscall(Syscall::SchedYield as u32, [0; 3]) };
+    _ = unsafe { syscall(Syscall::SchedYield as u32, [0; 5]) };
 }
 
-/// Exit the current process.
+/// Exit the current process right away.
+///
+/// This skips the hooks registered with [`crate::process::at_exit`], which
+/// [`crate::process::exit`] runs first.
 pub fn exit(status: i32) -> ! {
     // SAFETY: This matches the definition of this syscall.
-    _ = unsafe { syscall(Syscall::Exit as u32, [status as u32, 0, 0]) };
+    _ = unsafe { syscall(Syscall::Exit as u32, [status as u32, 0, 0, 0, 0]) };
     unreachable!("exit syscall should never return")
 }
 
+/// Reset every device, then power off or reboot the machine.
+pub fn shutdown(kind: ShutdownKind) -> ! {
+    // SAFETY: This matches the definition of this syscall.
+    _ = unsafe { syscall(Syscall::Shutdown as u32, [kind as u32, 0, 0, 0, 0]) };
+    unreachable!("shutdown syscall should never return")
+}
+
 /// Fill a buffer with random bytes.
 pub fn get_random(buf: &mut [u8]) -> Result<(), shared::ErrorKind> {
     // SAFETY: This matches the definition of this syscall.
     let (ok, err) = unsafe {
         syscall(
             Syscall::GetRandom as u32,
-            [core::ptr::from_mut(buf).addr() as u32, buf.len() as u32, 0],
+            [
+                core::ptr::from_mut(buf).addr() as u32,
+                buf.len() as u32,
+                0,
+                0,
+                0,
+            ],
         )
     };
     match (ok, err) {
@@ -66,6 +98,8 @@ pub(crate) fn open(path: &str, flags: shared::FileOpenFlags) -> Result<i32, shar
                 core::ptr::from_ref(path).addr() as u32,
                 path.len() as u32,
                 flags.into(),
+                0,
+                0,
             ],
         )
     };
@@ -78,7 +112,61 @@ pub(crate) fn open(path: &str, flags: shared::FileOpenFlags) -> Result<i32, shar
 
 pub(crate) fn close(descriptor_num: i32) {
     // SAFETY: This matches the definition of this syscall.
-    _ = unsafe { syscall(Syscall::Close as u32, [descriptor_num as u32, 0, 0]) };
+    _ = unsafe { syscall(Syscall::Close as u32, [descriptor_num as u32, 0, 0, 0, 0]) };
+}
+
+/// Create a new resource descriptor pointing at the same resource description as
+/// `descriptor_num`.
+pub(crate) fn dup(descriptor_num: i32) -> Result<i32, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (new_descriptor_num, err) =
+        unsafe { syscall(Syscall::Dup as u32, [descriptor_num as u32, 0, 0, 0, 0]) };
+    if new_descriptor_num == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(new_descriptor_num as i32)
+}
+
+/// Make `new_descriptor_num` point at the same resource description as `descriptor_num`, closing
+/// whatever it pointed at before.
+pub(crate) fn dup2(descriptor_num: i32, new_descriptor_num: i32) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (result, err) = unsafe {
+        syscall(
+            Syscall::Dup2 as u32,
+            [descriptor_num as u32, new_descriptor_num as u32, 0, 0, 0],
+        )
+    };
+    if result == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(())
+}
+
+/// Create a pipe, returning the descriptors for its read and write ends.
+pub(crate) fn pipe() -> Result<(i32, i32), shared::ErrorKind> {
+    let mut descriptors = [0_u32; 2];
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Pipe as u32,
+            [
+                core::ptr::from_mut(&mut descriptors).addr() as u32,
+                size_of::<[u32; 2]>() as u32,
+                0,
+                0,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok((
+            u32::from_le(descriptors[0]) as i32,
+            u32::from_le(descriptors[1]) as i32,
+        )),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
 }
 
 pub(crate) fn read(descriptor_num: i32, buf: &mut [u8]) -> Result<usize, shared::ErrorKind> {
@@ -90,6 +178,38 @@ pub(crate) fn read(descriptor_num: i32, buf: &mut [u8]) -> Result<usize, shared:
                 descriptor_num as u32,
                 core::ptr::from_ref(buf).addr() as u32,
                 buf.len() as u32,
+                0,
+                0,
+            ],
+        )
+    };
+    if read_len == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(read_len as usize)
+}
+
+/// Read from a resource, failing with [`shared::ErrorKind::TimedOut`] if nothing arrives within
+/// `timeout`.
+///
+/// The timeout has millisecond precision, and a timeout of zero only takes what has already
+/// arrived.
+pub(crate) fn read_timeout(
+    descriptor_num: i32,
+    buf: &mut [u8],
+    timeout: core::time::Duration,
+) -> Result<usize, shared::ErrorKind> {
+    let timeout_ms = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+    // SAFETY: This matches the definition of this syscall.
+    let (read_len, err) = unsafe {
+        syscall(
+            Syscall::ReadTimeout as u32,
+            [
+                descriptor_num as u32,
+                core::ptr::from_ref(buf).addr() as u32,
+                buf.len() as u32,
+                timeout_ms,
+                0,
             ],
         )
     };
@@ -108,6 +228,8 @@ pub(crate) fn write(descriptor_num: i32, buf: &[u8]) -> Result<usize, shared::Er
                 descriptor_num as u32,
                 core::ptr::from_ref(buf).addr() as u32,
                 buf.len() as u32,
+                0,
+                0,
             ],
         )
     };
@@ -117,22 +239,486 @@ pub(crate) fn write(descriptor_num: i32, buf: &[u8]) -> Result<usize, shared::Er
     Ok(write_len as usize)
 }
 
+/// Create a new hard link at `new_path` to the file at `existing_path`.
+pub(crate) fn link(existing_path: &str, new_path: &str) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Link as u32,
+            [
+                core::ptr::from_ref(existing_path).addr() as u32,
+                existing_path.len() as u32,
+                core::ptr::from_ref(new_path).addr() as u32,
+                new_path.len() as u32,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok(()),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Remove the link to a file at `path`.
+pub(crate) fn unlink(path: &str) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Unlink as u32,
+            [
+                core::ptr::from_ref(path).addr() as u32,
+                path.len() as u32,
+                0,
+                0,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok(()),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Get usage information about the filesystem containing `path`.
+pub(crate) fn statfs(path: &str) -> Result<shared::FilesystemStats, shared::ErrorKind> {
+    let mut stats = shared::FilesystemStats::default();
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Statfs as u32,
+            [
+                core::ptr::from_ref(path).addr() as u32,
+                path.len() as u32,
+                core::ptr::from_mut(&mut stats).addr() as u32,
+                size_of::<shared::FilesystemStats>() as u32,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok(stats),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Get information about the file at `path`.
+pub(crate) fn stat(path: &str) -> Result<shared::FileMetadata, shared::ErrorKind> {
+    let mut metadata = shared::FileMetadata::default();
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Stat as u32,
+            [
+                core::ptr::from_ref(path).addr() as u32,
+                path.len() as u32,
+                core::ptr::from_mut(&mut metadata).addr() as u32,
+                size_of::<shared::FileMetadata>() as u32,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok(metadata),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Copy the name of the entry at `index` in the directory at `path` into `name_buf`.
+///
+/// Returns the length of the name, which is 0 if the directory doesn't have that many entries.
+pub(crate) fn read_dir(
+    path: &str,
+    index: usize,
+    name_buf: &mut [u8],
+) -> Result<usize, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (name_len, err) = unsafe {
+        syscall(
+            Syscall::ReadDir as u32,
+            [
+                core::ptr::from_ref(path).addr() as u32,
+                path.len() as u32,
+                index as u32,
+                name_buf.as_mut_ptr().addr() as u32,
+                name_buf.len() as u32,
+            ],
+        )
+    };
+    if name_len == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(name_len as usize)
+}
+
+/// Remove the empty directory at `path`.
+pub(crate) fn remove_dir(path: &str) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::RemoveDir as u32,
+            [
+                core::ptr::from_ref(path).addr() as u32,
+                path.len() as u32,
+                0,
+                0,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok(()),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Create a new, empty directory at `path`.
+pub(crate) fn create_dir(path: &str) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::CreateDir as u32,
+            [
+                core::ptr::from_ref(path).addr() as u32,
+                path.len() as u32,
+                0,
+                0,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok(()),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Open a new socket of the given kind.
+///
+/// A `read_timeout_ms` of zero means receiving waits forever.
+pub(crate) fn socket(
+    kind: shared::SocketKind,
+    read_timeout_ms: u32,
+) -> Result<i32, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ret, err) = unsafe {
+        syscall(
+            Syscall::Socket as u32,
+            [kind as u32, read_timeout_ms, 0, 0, 0],
+        )
+    };
+    let ret = ret as i32;
+    if ret == -1 {
+        return Err(err.unwrap());
+    }
+    Ok(ret)
+}
+
+/// Bind a socket to a local address.
+pub(crate) fn bind(
+    descriptor_num: i32,
+    addr: &shared::SocketAddrV4,
+) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Bind as u32,
+            [
+                descriptor_num as u32,
+                core::ptr::from_ref(addr).addr() as u32,
+                size_of::<shared::SocketAddrV4>() as u32,
+                0,
+                0,
+            ],
+        )
+    };
+    match (ok, err) {
+        (0, _) => Ok(()),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Send a datagram from a socket to the given address.
+pub(crate) fn send_to(
+    descriptor_num: i32,
+    buf: &[u8],
+    addr: &shared::SocketAddrV4,
+) -> Result<usize, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (send_len, err) = unsafe {
+        syscall(
+            Syscall::SendTo as u32,
+            [
+                descriptor_num as u32,
+                core::ptr::from_ref(buf).addr() as u32,
+                buf.len() as u32,
+                core::ptr::from_ref(addr).addr() as u32,
+                size_of::<shared::SocketAddrV4>() as u32,
+            ],
+        )
+    };
+    if send_len == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(send_len as usize)
+}
+
+/// Receive a datagram on a socket, returning its length and the address it came from.
+pub(crate) fn recv_from(
+    descriptor_num: i32,
+    buf: &mut [u8],
+) -> Result<(usize, shared::SocketAddrV4), shared::ErrorKind> {
+    let mut addr = shared::SocketAddrV4::default();
+    // SAFETY: This matches the definition of this syscall.
+    let (recv_len, err) = unsafe {
+        syscall(
+            Syscall::RecvFrom as u32,
+            [
+                descriptor_num as u32,
+                core::ptr::from_mut(buf).addr() as u32,
+                buf.len() as u32,
+                core::ptr::from_mut(&mut addr).addr() as u32,
+                size_of::<shared::SocketAddrV4>() as u32,
+            ],
+        )
+    };
+    if recv_len == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok((recv_len as usize, addr))
+}
+
 /// Request the kernel map more pages for us.
 ///
 /// `size` is the minimum requested size, in bytes. The kernel might give more memory than that,
 /// but presently it has no way to signal that it did so.
 pub(crate) fn mmap(size: usize) -> Result<NonNull<()>, shared::ErrorKind> {
     // SAFETY: This matches the definition of this syscall.
-    let (addr, err) = unsafe { syscall(Syscall::Mmap as u32, [size as u32, 0, 0]) };
-    NonNull::new(core::ptr::without_provenance_mut(addr as usize)).ok_or_else(|| err.unwrap())
+    let (addr, err) = unsafe { syscall(Syscall::Mmap as u32, [size as u32, 0, 0, 0, 0]) };
+    if addr == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(NonNull::new(core::ptr::without_provenance_mut(addr as usize)).unwrap())
+}
+
+/// Map the memory backing a resource into this process.
+pub(crate) fn map_resource(descriptor_num: i32) -> Result<NonNull<()>, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (addr, err) = unsafe {
+        syscall(
+            Syscall::MapResource as u32,
+            [descriptor_num as u32, 0, 0, 0, 0],
+        )
+    };
+    if addr == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(NonNull::new(core::ptr::without_provenance_mut(addr as usize)).unwrap())
+}
+
+/// Move the offset in a resource descriptor that reads and writes start from.
+///
+/// Returns the new offset.
+pub(crate) fn seek(
+    descriptor_num: i32,
+    whence: shared::SeekWhence,
+    offset: i64,
+) -> Result<u64, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (new_offset, err) = unsafe {
+        syscall(
+            Syscall::Seek as u32,
+            [
+                descriptor_num as u32,
+                whence as u32,
+                offset as u32,
+                (offset >> 32) as u32,
+                0,
+            ],
+        )
+    };
+    if new_offset == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(new_offset.into())
+}
+
+/// Start a new process running the program at `path`, as a child of this one.
+///
+/// `args_block` holds its arguments and environment variables, laid out as described at
+/// [`shared::PROCESS_ARGS_ADDR`], and `stdio` are the descriptors to give it as its stdin, stdout,
+/// and stderr. Returns the PID of the new process.
+pub(crate) fn spawn(
+    path: &str,
+    args_block: &[u8],
+    stdio: &[i32; 3],
+) -> Result<u32, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (pid, err) = unsafe {
+        syscall(
+            Syscall::Spawn as u32,
+            [
+                core::ptr::from_ref(path).addr() as u32,
+                path.len() as u32,
+                core::ptr::from_ref(args_block).addr() as u32,
+                args_block.len() as u32,
+                core::ptr::from_ref(stdio).addr() as u32,
+            ],
+        )
+    };
+    if pid == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(pid)
+}
+
+/// Wait for the child process `pid` to exit, returning its exit status.
+pub(crate) fn wait(pid: u32) -> Result<i32, shared::ErrorKind> {
+    let mut status = 0_i32;
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Wait as u32,
+            [pid, core::ptr::from_mut(&mut status).addr() as u32, 0, 0, 0],
+        )
+    };
+    if ok == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(status)
+}
+
+/// Get how long it's been since the machine started.
+pub(crate) fn clock_get_time() -> core::time::Duration {
+    // SAFETY: This matches the definition of this syscall.
+    let (secs, nanos) = unsafe { syscall_raw(Syscall::ClockGetTime as u32, [0; 5]) };
+    core::time::Duration::new(secs.into(), nanos)
+}
+
+/// Block this process for at least `duration`.
+pub(crate) fn sleep(duration: core::time::Duration) -> Result<(), shared::ErrorKind> {
+    // The kernel takes whole seconds as a `u32`, so longer sleeps get cut short.
+    let secs = u32::try_from(duration.as_secs()).unwrap_or(u32::MAX);
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::Sleep as u32,
+            [secs, duration.subsec_nanos(), 0, 0, 0],
+        )
+    };
+    if ok == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(())
 }
 
-/// Unmap pages that were allocated via [`mmap`].
+/// Block until woken by [`futex_wake`] on the same word, unless `word` no longer holds
+/// `expected`.
+///
+/// This can return without being woken, so callers should check what they're waiting for again.
+pub(crate) fn futex_wait(
+    word: &core::sync::atomic::AtomicU32,
+    expected: u32,
+) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe {
+        syscall(
+            Syscall::FutexWait as u32,
+            [core::ptr::from_ref(word).addr() as u32, expected, 0, 0, 0],
+        )
+    };
+    if ok == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(())
+}
+
+/// Wake up to `max` processes blocked in [`futex_wait`] on `word`, returning how many woke.
+pub(crate) fn futex_wake(
+    word: &core::sync::atomic::AtomicU32,
+    max: u32,
+) -> Result<u32, shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (woken, err) = unsafe {
+        syscall(
+            Syscall::FutexWake as u32,
+            [core::ptr::from_ref(word).addr() as u32, max, 0, 0, 0],
+        )
+    };
+    if woken == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(woken)
+}
+
+/// Start a new thread running `entry(arg)` on the stack ending at `stack_top`, returning its ID.
+pub(crate) fn spawn_thread(
+    entry: extern "C" fn(usize) -> !,
+    stack_top: NonNull<()>,
+    arg: usize,
+) -> Result<u32, shared::ErrorKind> {
+    #[allow(
+        clippy::fn_to_numeric_cast_any,
+        reason = "The kernel needs the function address"
+    )]
+    let entry = entry as usize;
+    // SAFETY: This matches the definition of this syscall.
+    let (tid, err) = unsafe {
+        syscall(
+            Syscall::SpawnThread as u32,
+            [
+                entry as u32,
+                stack_top.addr().get() as u32,
+                arg as u32,
+                0,
+                0,
+            ],
+        )
+    };
+    if tid == -1_i32 as u32 {
+        return Err(err.unwrap());
+    }
+    Ok(tid)
+}
+
+/// Exit the current thread.
+///
+/// Once the thread is done with its stack, the kernel sets `exited` (if given) to 0 and wakes
+/// anyone blocked in [`futex_wait`] on it.
+pub(crate) fn exit_thread(exited: Option<&core::sync::atomic::AtomicU32>) -> ! {
+    let exited_addr = exited.map_or(0, |exited| core::ptr::from_ref(exited).addr() as u32);
+    // SAFETY: This matches the definition of this syscall.
+    _ = unsafe { syscall(Syscall::ExitThread as u32, [exited_addr, 0, 0, 0, 0]) };
+    unreachable!("exit_thread syscall should never return")
+}
+
+/// Push any pending changes to a resource out to the underlying device.
+pub(crate) fn sync(descriptor_num: i32) -> Result<(), shared::ErrorKind> {
+    // SAFETY: This matches the definition of this syscall.
+    let (ok, err) = unsafe { syscall(Syscall::Sync as u32, [descriptor_num as u32, 0, 0, 0, 0]) };
+    match (ok, err) {
+        (0, _) => Ok(()),
+        (0xFFFF_FFFF_u32, Some(err)) => Err(err),
+        _ => unreachable!(),
+    }
+}
+
+/// Unmap pages that were allocated via [`mmap`], returning them to the kernel.
+///
+/// Part of an allocation can be unmapped, as long as `addr` is page-aligned. `size` is rounded up
+/// to a whole number of pages.
 ///
 /// # Safety
-/// `addr` must exactly match an address returned by `mmap`, and `size` must exactly match the
-/// `size` value from that call to `mmap`. Additionally, there must be no remaining references to
-/// that memory.
+/// The pages from `addr` for `size` bytes must all have been allocated via `mmap`, and not
+/// unmapped already. Additionally, there must be no remaining references to that memory.
 pub(crate) unsafe fn munmap(addr: NonNull<()>, size: usize) -> Result<(), shared::ErrorKind> {
     // SAFETY:
     // Because this memory region was `mmap`ed (see preconditions on this function), and nothing in
@@ -140,7 +726,7 @@ pub(crate) unsafe fn munmap(addr: NonNull<()>, size: usize) -> Result<(), shared
     let (ok, err) = unsafe {
         syscall(
             Syscall::Munmap as u32,
-            [addr.addr().get() as u32, size as u32, 0],
+            [addr.addr().get() as u32, size as u32, 0, 0, 0],
         )
     };
     match (ok, err) {
@@ -158,12 +744,21 @@ pub(crate) unsafe fn munmap(addr: NonNull<()>, size: usize) -> Result<(), shared
 /// This can be wildly unsafe, depending on the call done and the arguments. Prefer using the safe
 /// helper functions where possible.
 #[must_use]
-pub unsafe fn syscall(
-    syscall_number: u32,
-    [arg0, arg1, arg2]: [u32; 3],
-) -> (u32, Option<shared::ErrorKind>) {
+pub unsafe fn syscall(syscall_number: u32, args: [u32; 5]) -> (u32, Option<shared::ErrorKind>) {
+    // SAFETY: The caller of this method is responsible for ensuring that the results are sound.
+    let (ret_val, ret_err) = unsafe { syscall_raw(syscall_number, args) };
+    (ret_val, shared::ErrorKind::from_num(ret_err))
+}
+
+/// Perform an arbitrary syscall, returning both result registers as they are.
+///
+/// This is for the few syscalls which return a second value instead of an error.
+///
+/// # Safety
+/// See [`syscall`].
+unsafe fn syscall_raw(syscall_number: u32, [arg0, arg1, arg2, arg3, arg4]: [u32; 5]) -> (u32, u32) {
     let ret_val;
-    let ret_err;
+    let ret_second;
     // SAFETY:
     // This makes the given syscall. The caller of this method is responsible for ensuring that the
     // results are sound.
@@ -174,9 +769,11 @@ pub unsafe fn syscall(
             in("a1")  arg0,
             in("a2")  arg1,
             in("a3")  arg2,
+            in("a4")  arg3,
+            in("a5")  arg4,
             lateout("a1") ret_val,
-            lateout("a2") ret_err,
+            lateout("a2") ret_second,
         );
     }
-    (ret_val, shared::ErrorKind::from_num(ret_err))
+    (ret_val, ret_second)
 }
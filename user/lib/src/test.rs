@@ -0,0 +1,117 @@
+//! A small framework for tests which run as user programs.
+//!
+//! Register tests with [`user_test!`](crate::user_test), then call [`run`] from the test
+//! program's `main`. Each test runs in its own process, so a test fails by panicking (or by
+//! exiting with a nonzero status) without taking the rest down with it.
+//!
+//! Booting the kernel with `init=/bin/tests` runs the tests as the first process, so the machine
+//! powers off once they're done, with QEMU exiting with a nonzero status if any failed.
+
+use crate::{eprintln, print, println, process::Command, rust_alloc::vec::Vec};
+
+unsafe extern "C" {
+    /// The start of the tests registered with [`user_test!`](crate::user_test).
+    static __user_tests_start: u8;
+    /// The end of the tests registered with [`user_test!`](crate::user_test).
+    static __user_tests_end: u8;
+}
+
+/// A test registered with [`user_test!`](crate::user_test).
+#[derive(Debug)]
+pub struct Test {
+    /// The name of the test, including the module it's in.
+    pub name: &'static str,
+    /// The function to run, which panics if the test fails.
+    pub func: fn(),
+}
+
+/// Register functions as tests, to be run by [`run`].
+///
+/// ```ignore
+/// userlib::user_test! {
+///     fn addition() {
+///         assert_eq!(1 + 1, 2);
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! user_test {
+    ($( $(#[$attr:meta])* fn $name:ident() $body:block )*) => {$(
+        $(#[$attr])*
+        fn $name() $body
+
+        const _: () = {
+            #[used]
+            #[unsafe(link_section = ".user_tests")]
+            static TEST: $crate::test::Test = $crate::test::Test {
+                name: ::core::concat!(::core::module_path!(), "::", ::core::stringify!($name)),
+                func: $name,
+            };
+        };
+    )*};
+}
+
+/// Get every registered test.
+#[must_use]
+pub fn tests() -> &'static [Test] {
+    #[expect(
+        clippy::cast_ptr_alignment,
+        reason = "The linker script aligns the tests"
+    )]
+    let (start, end) = (
+        (&raw const __user_tests_start).cast::<Test>(),
+        (&raw const __user_tests_end).cast::<Test>(),
+    );
+    // SAFETY:
+    // The linker script puts every registered test between these symbols, and nothing else.
+    unsafe { core::slice::from_raw_parts(start, end.offset_from_unsigned(start)) }
+}
+
+/// Run the registered tests, then exit.
+///
+/// With no arguments, this runs each test by spawning this program again with the test's name as
+/// its argument, which needs the program's first argument to be the path it was started from.
+/// It exits with status 1 if any test failed.
+pub fn run() -> ! {
+    let mut args = crate::env::args();
+    let program = args.next().unwrap_or_default();
+    if let Some(name) = args.next() {
+        let Some(test) = tests().iter().find(|test| test.name == name) else {
+            eprintln!("No test named {name}");
+            crate::process::exit(2);
+        };
+        (test.func)();
+        crate::process::exit(0);
+    }
+
+    println!("running {} tests", tests().len());
+    let mut failed = Vec::new();
+    for test in tests() {
+        print!("test {} ... ", test.name);
+        match Command::new(program).arg(test.name).status() {
+            Ok(status) if status.success() => println!("ok"),
+            Ok(status) => {
+                println!("FAILED ({status})");
+                failed.push(test.name);
+            }
+            Err(e) => {
+                println!("FAILED to start: {e}");
+                failed.push(test.name);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        println!("\nfailures:");
+        for name in &failed {
+            println!("    {name}");
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        tests().len() - failed.len(),
+        failed.len(),
+    );
+    crate::process::exit(i32::from(!failed.is_empty()));
+}
@@ -0,0 +1,192 @@
+//! Running code concurrently, in threads which share the rest of the process.
+//!
+//! Each thread runs on its own stack, mapped with `mmap`. When a thread exits, the kernel clears
+//! a word in its [`JoinHandle`]'s shared state and wakes anyone waiting on it, so joining can tell
+//! that the stack is free to unmap.
+
+use core::{
+    cell::UnsafeCell,
+    ptr::NonNull,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{io::Result, rust_alloc::boxed::Box};
+
+/// The size of the stack new threads get, unless [`Builder::stack_size`] says otherwise.
+const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// Spawn a new thread running `f`, returning a handle to join it.
+///
+/// # Panics
+/// Panics if the thread can't be created. Use [`Builder::spawn`] to handle that case instead.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new().spawn(f).expect("Failed to spawn thread")
+}
+
+/// Yield the rest of this thread's time slice to whatever else is ready to run.
+pub fn yield_now() {
+    crate::sys::sched_yield();
+}
+
+/// Configures a new thread, then spawns it.
+#[derive(Debug)]
+pub struct Builder {
+    /// The size of the new thread's stack, in bytes.
+    stack_size: usize,
+}
+impl Builder {
+    /// Start configuring a new thread, with the default settings.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            stack_size: DEFAULT_STACK_SIZE,
+        }
+    }
+
+    /// Set the size of the new thread's stack, in bytes.
+    #[must_use]
+    pub const fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// Spawn a new thread running `f`, returning a handle to join it.
+    pub fn spawn<F, T>(self, f: F) -> Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        // Keep the stack pointer 16-byte aligned, as the calling convention requires.
+        let stack_size = self.stack_size.next_multiple_of(16);
+        let stack = crate::sys::mmap(stack_size)?;
+        // SAFETY: We just mapped `stack_size` bytes starting at `stack`.
+        let stack_top = unsafe { stack.byte_add(stack_size) };
+        let packet = NonNull::from(Box::leak(Box::new(Packet {
+            running: AtomicU32::new(1),
+            result: UnsafeCell::new(None),
+        })));
+        let start = Box::into_raw(Box::new(Start { main: f, packet }));
+        match crate::sys::spawn_thread(thread_start::<F, T>, stack_top, start.expose_provenance()) {
+            Ok(id) => Ok(JoinHandle {
+                id,
+                packet,
+                stack,
+                stack_size,
+            }),
+            Err(e) => {
+                // SAFETY: The thread never started, so we still own all of these.
+                unsafe {
+                    drop(Box::from_raw(start));
+                    drop(Box::from_raw(packet.as_ptr()));
+                    _ = crate::sys::munmap(stack, stack_size);
+                }
+                Err(e.into())
+            }
+        }
+    }
+}
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A handle to a running thread, which can wait for it to finish.
+///
+/// Dropping the handle detaches the thread, which keeps running. A detached thread's stack is
+/// never unmapped.
+pub struct JoinHandle<T> {
+    /// The thread's ID.
+    id: u32,
+    /// State shared with the thread, which we own once the thread has exited.
+    packet: NonNull<Packet<T>>,
+    /// The thread's stack, which we unmap once the thread has exited.
+    stack: NonNull<()>,
+    /// The size of the thread's stack.
+    stack_size: usize,
+}
+impl<T> JoinHandle<T> {
+    /// Get the ID of the thread.
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Check whether the thread has finished running, without blocking.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.packet().running.load(Ordering::Acquire) == 0
+    }
+
+    /// Wait for the thread to finish, and get the value its closure returned.
+    ///
+    /// Panics abort the whole process, so unlike the standard library, this never has a panic to
+    /// report.
+    #[must_use]
+    pub fn join(self) -> T {
+        let running = &self.packet().running;
+        while running.load(Ordering::Acquire) != 0 {
+            if crate::sys::futex_wait(running, 1).is_err() {
+                crate::sys::sched_yield();
+            }
+        }
+        // SAFETY:
+        // The thread has exited, so it's done with its stack and the packet, and we own them now.
+        let packet = unsafe {
+            _ = crate::sys::munmap(self.stack, self.stack_size);
+            Box::from_raw(self.packet.as_ptr())
+        };
+        packet
+            .result
+            .into_inner()
+            .expect("Threads set their result before exiting")
+    }
+
+    /// Get the state shared with the thread.
+    fn packet(&self) -> &Packet<T> {
+        // SAFETY:
+        // The packet lives until we free it in `join`, and the thread only writes `result`, which
+        // this reference isn't used to read.
+        unsafe { self.packet.as_ref() }
+    }
+}
+
+// SAFETY: The handle only gives out the thread's result, which is `Send`, when joining.
+unsafe impl<T: Send> Send for JoinHandle<T> {}
+// SAFETY: Shared references to the handle can't get at the thread's result.
+unsafe impl<T> Sync for JoinHandle<T> {}
+
+/// State shared between a thread and its [`JoinHandle`].
+struct Packet<T> {
+    /// 1 while the thread is running, which the kernel clears once it has exited.
+    running: AtomicU32,
+    /// The value returned by the thread's closure, which is set before it exits.
+    result: UnsafeCell<Option<T>>,
+}
+
+/// What a new thread needs to start running.
+struct Start<F, T> {
+    /// The closure to run.
+    main: F,
+    /// Where to put the result.
+    packet: NonNull<Packet<T>>,
+}
+
+/// The entry point for new threads, which takes a pointer to a leaked [`Start`].
+extern "C" fn thread_start<F: FnOnce() -> T, T>(start: usize) -> ! {
+    // SAFETY: `Builder::spawn` leaked this box for us to take ownership of.
+    let start =
+        unsafe { Box::from_raw(core::ptr::with_exposed_provenance_mut::<Start<F, T>>(start)) };
+    let Start { main, packet } = *start;
+    let result = crate::alloc::with_thread_cache(main);
+    // SAFETY:
+    // The packet lives until we exit, and the `JoinHandle` doesn't read `result` until then.
+    let packet = unsafe { packet.as_ref() };
+    // SAFETY: As above, nothing else accesses `result` until we exit.
+    unsafe { *packet.result.get() = Some(result) };
+    crate::sys::exit_thread(Some(&packet.running))
+}
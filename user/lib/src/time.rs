@@ -0,0 +1,90 @@
+//! Measuring and waiting for time.
+
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+pub use core::time::Duration;
+
+use crate::io::Result;
+
+/// A point in time, for measuring how long things take.
+///
+/// These only go forwards, and only make sense compared to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(Duration);
+impl Instant {
+    /// Get the current time.
+    #[must_use]
+    pub fn now() -> Self {
+        Self(crate::sys::clock_get_time())
+    }
+
+    /// Get how long it's been since this time.
+    #[must_use]
+    pub fn elapsed(self) -> Duration {
+        Self::now().duration_since(self)
+    }
+
+    /// Get how long passed from `earlier` to this time, or zero if `earlier` is later.
+    #[must_use]
+    pub fn duration_since(self, earlier: Self) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// Get the time `duration` after this one, if it can be represented.
+    #[must_use]
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        self.0.checked_add(duration).map(Self)
+    }
+
+    /// Get the time `duration` before this one, if it can be represented.
+    #[must_use]
+    pub fn checked_sub(self, duration: Duration) -> Option<Self> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+impl Add<Duration> for Instant {
+    type Output = Self;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.checked_add(rhs)
+            .expect("Overflow when adding duration to instant")
+    }
+}
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+impl Sub<Duration> for Instant {
+    type Output = Self;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        self.checked_sub(rhs)
+            .expect("Overflow when subtracting duration from instant")
+    }
+}
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.duration_since(rhs)
+    }
+}
+
+/// Block this process for at least `duration`.
+pub fn sleep(duration: Duration) -> Result<()> {
+    Ok(crate::sys::sleep(duration)?)
+}
+
+/// Block this process until at least `deadline`.
+pub fn sleep_until(deadline: Instant) -> Result<()> {
+    let remaining = deadline.duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Ok(());
+    }
+    sleep(remaining)
+}
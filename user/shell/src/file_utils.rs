@@ -0,0 +1,235 @@
+//! Builtin commands for managing files: `cp`, `mv`, `rm`, `mkdir` and `touch`.
+//!
+//! Each command takes its arguments after the command name, and returns a message to show the
+//! user if it fails.
+
+use alloc::{
+    format,
+    string::{String, ToString as _},
+    vec::Vec,
+};
+
+use userlib::{
+    args::{Arg, Parser},
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+/// Copy files, and directories along with everything in them with `-r`.
+///
+/// The last argument is where to copy to. If it's a directory, the other arguments are copied into
+/// it with the same names; otherwise there must be only one other argument, which is copied to it.
+pub fn cp<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut recursive = false;
+    let mut paths = Vec::new();
+    let mut parser = Parser::new(args);
+    while let Some(arg) = parser.next().map_err(|e| e.to_string())? {
+        match arg {
+            Arg::Short('r' | 'R') | Arg::Long("recursive") => recursive = true,
+            Arg::Value(path) => paths.push(path),
+            _ => return Err(arg.unexpected().to_string()),
+        }
+    }
+    let (sources, target) = split_target(&paths)?;
+    for &source in sources {
+        let dest = destination(source, target)?;
+        check_not_inside(source, &dest)?;
+        copy(Path::new(source), &dest, recursive).map_err(|e| format!("{source}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Move files and directories, which are given in the same way as for [`cp`].
+///
+/// Files are moved by linking them at their new path and then unlinking the old one. Directories
+/// can't be linked, so they're copied and then removed.
+pub fn mv<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut paths = Vec::new();
+    let mut parser = Parser::new(args);
+    while let Some(arg) = parser.next().map_err(|e| e.to_string())? {
+        match arg {
+            Arg::Value(path) => paths.push(path),
+            _ => return Err(arg.unexpected().to_string()),
+        }
+    }
+    let (sources, target) = split_target(&paths)?;
+    for &source in sources {
+        let dest = destination(source, target)?;
+        check_not_inside(source, &dest)?;
+        if absolute(Path::new(source)) == absolute(&dest) {
+            return Err(format!("{source} and {dest} are the same file"));
+        }
+        move_path(source, &dest).map_err(|e| format!("{source}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Remove files, and directories along with everything in them with `-r`.
+///
+/// With `-f`, files which don't exist are ignored.
+pub fn rm<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut recursive = false;
+    let mut force = false;
+    let mut paths = Vec::new();
+    let mut parser = Parser::new(args);
+    while let Some(arg) = parser.next().map_err(|e| e.to_string())? {
+        match arg {
+            Arg::Short('r' | 'R') | Arg::Long("recursive") => recursive = true,
+            Arg::Short('f') | Arg::Long("force") => force = true,
+            Arg::Value(path) => paths.push(path),
+            _ => return Err(arg.unexpected().to_string()),
+        }
+    }
+    if paths.is_empty() && !force {
+        return Err(String::from("missing file to remove"));
+    }
+    for path in paths {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if force && matches!(e.kind(), ErrorKind::NotFound) => continue,
+            Err(e) => return Err(format!("{path}: {e}")),
+        };
+        let result = if !metadata.is_dir() {
+            fs::remove_file(path)
+        } else if recursive {
+            fs::remove_dir_all(path)
+        } else {
+            return Err(format!("{path} is a directory (use -r to remove it)"));
+        };
+        result.map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Create directories, along with any parents which don't exist yet with `-p`.
+pub fn mkdir<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut parents = false;
+    let mut paths = Vec::new();
+    let mut parser = Parser::new(args);
+    while let Some(arg) = parser.next().map_err(|e| e.to_string())? {
+        match arg {
+            Arg::Short('p') | Arg::Long("parents") => parents = true,
+            Arg::Value(path) => paths.push(path),
+            _ => return Err(arg.unexpected().to_string()),
+        }
+    }
+    if paths.is_empty() {
+        return Err(String::from("missing directory to create"));
+    }
+    for path in paths {
+        let result = if parents {
+            fs::create_dir_all(path)
+        } else {
+            fs::create_dir(path)
+        };
+        result.map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Create empty files, leaving any which already exist as they are.
+pub fn touch<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut paths = Vec::new();
+    let mut parser = Parser::new(args);
+    while let Some(arg) = parser.next().map_err(|e| e.to_string())? {
+        match arg {
+            Arg::Value(path) => paths.push(path),
+            _ => return Err(arg.unexpected().to_string()),
+        }
+    }
+    if paths.is_empty() {
+        return Err(String::from("missing file to create"));
+    }
+    for path in paths {
+        // Opening to append creates the file without touching what's already in it.
+        fs::File::append(path).map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Split the arguments of `cp` or `mv` into the paths to copy and where to copy them to.
+fn split_target<'p, 'a>(paths: &'p [&'a str]) -> Result<(&'p [&'a str], &'a str), String> {
+    let (&target, sources) = paths
+        .split_last()
+        .ok_or_else(|| String::from("missing file operand"))?;
+    if sources.is_empty() {
+        return Err(format!("missing destination after {target}"));
+    }
+    if sources.len() > 1 && !is_dir(target) {
+        return Err(format!("{target} is not a directory"));
+    }
+    Ok((sources, target))
+}
+
+/// Get the path to copy or move `source` to, given the last argument of `cp` or `mv`.
+fn destination(source: &str, target: &str) -> Result<PathBuf, String> {
+    if !is_dir(target) {
+        return Ok(PathBuf::from(target));
+    }
+    let name = Path::new(source)
+        .file_name()
+        .ok_or_else(|| format!("{source} has no file name"))?;
+    Ok(Path::new(target).join(name))
+}
+
+/// Check that `dest` isn't inside `source`, which would never finish copying a directory.
+fn check_not_inside(source: &str, dest: &Path) -> Result<(), String> {
+    let source_path = absolute(Path::new(source));
+    let dest_path = absolute(dest);
+    let inside = dest_path
+        .as_str()
+        .strip_prefix(source_path.as_str())
+        .is_some_and(|rest| rest.starts_with('/'));
+    if inside {
+        return Err(format!("can't copy {source} into itself"));
+    }
+    Ok(())
+}
+
+/// Copy the file at `source` to `dest`, replacing any file there.
+///
+/// Directories are copied with everything in them if `recursive` is set, and are an error if not.
+fn copy(source: &Path, dest: &Path, recursive: bool) -> io::Result<()> {
+    if !fs::metadata(source)?.is_dir() {
+        let mut from = fs::File::open(source)?;
+        let mut to = fs::File::create(dest)?;
+        io::copy(&mut from, &mut to)?;
+        return Ok(());
+    }
+    if !recursive {
+        return Err(io::Error::new(
+            ErrorKind::InvalidFormat,
+            "is a directory (use -r to copy it)",
+        ));
+    }
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        copy(entry.path(), &dest.join(entry.file_name()), true)?;
+    }
+    Ok(())
+}
+
+/// Move the file or directory at `source` to `dest`, replacing any file there.
+fn move_path(source: &str, dest: &Path) -> io::Result<()> {
+    if fs::metadata(source)?.is_dir() {
+        copy(Path::new(source), dest, true)?;
+        return fs::remove_dir_all(source);
+    }
+    if fs::metadata(dest).is_ok() {
+        fs::remove_file(dest)?;
+    }
+    fs::hard_link(source, dest)?;
+    fs::remove_file(source)
+}
+
+/// Check whether there's a directory at `path`.
+fn is_dir(path: &str) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Get the normalized absolute path of `path`, to compare it with others.
+fn absolute(path: &Path) -> PathBuf {
+    userlib::env::current_dir().join(path).normalize()
+}
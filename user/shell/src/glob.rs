@@ -0,0 +1,117 @@
+//! Expanding wildcards to the names of the files they match.
+//!
+//! In a pattern, `*` matches any number of characters and `?` matches any one character, and a
+//! backslash makes the character after it stand for itself. Wildcards never match a `/`, and don't
+//! match a `.` at the start of a name unless the pattern starts with one too, so that hidden files
+//! have to be asked for.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use userlib::fs;
+
+/// Get the paths of the files which match `pattern`, in sorted order.
+///
+/// The paths are relative if the pattern is. This returns an empty list if nothing matches.
+pub fn expand(pattern: &str) -> Vec<String> {
+    let (mut paths, components) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec![String::from("/")], rest),
+        None => (vec![String::new()], pattern),
+    };
+    for component in components
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        let mut matched = Vec::new();
+        for path in &paths {
+            if !has_wildcards(component) {
+                matched.push(join(path, &unescape(component)));
+                continue;
+            }
+            let dir = if path.is_empty() { "." } else { path };
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let name = entry.file_name();
+                if name.starts_with('.') && !component.starts_with('.') {
+                    continue;
+                }
+                if matches(component, name) {
+                    matched.push(join(path, name));
+                }
+            }
+        }
+        paths = matched;
+    }
+    // Components without wildcards weren't checked against the filesystem as we went.
+    paths.retain(|path| fs::metadata(path).is_ok());
+    paths.sort_unstable();
+    paths
+}
+
+/// Check whether `name` matches `pattern`, which is a single path component.
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut pattern_chars = pattern.chars();
+    let mut name_chars = name.chars();
+    match pattern_chars.next() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = pattern_chars.as_str();
+            name.char_indices()
+                .map(|(i, _)| i)
+                .chain([name.len()])
+                .any(|i| matches(rest, &name[i..]))
+        }
+        Some('?') => {
+            name_chars.next().is_some() && matches(pattern_chars.as_str(), name_chars.as_str())
+        }
+        Some(c) => {
+            let c = if c == '\\' {
+                pattern_chars.next().unwrap_or('\\')
+            } else {
+                c
+            };
+            name_chars.next() == Some(c) && matches(pattern_chars.as_str(), name_chars.as_str())
+        }
+    }
+}
+
+/// Check whether `pattern` has any wildcards in it which aren't escaped.
+fn has_wildcards(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '?' => return true,
+            '\\' => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Remove the escaping backslashes from a pattern without wildcards.
+fn unescape(pattern: &str) -> String {
+    let mut unescaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' {
+            chars.next().unwrap_or('\\')
+        } else {
+            c
+        });
+    }
+    unescaped
+}
+
+/// Add `name` to the end of the directory path `dir`, which may be empty.
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        String::from(name)
+    } else if dir.ends_with('/') {
+        format!("{dir}{name}")
+    } else {
+        format!("{dir}/{name}")
+    }
+}
@@ -5,110 +5,1077 @@
 
 extern crate alloc;
 
-use userlib::{fs::File, prelude::*};
+mod file_utils;
+mod glob;
+mod tokenize;
+mod vars;
+
+use alloc::{
+    format,
+    string::{String, ToString as _},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+
+use tokenize::{Redirect, Token, Word, tokenize};
+use userlib::{
+    args::Arg,
+    fs,
+    io::{Read as _, Seek as _},
+    path::{Path, PathBuf},
+    prelude::*,
+    rd::OwnedResourceDescriptor,
+    readline::{Completion, Editor},
+    time::{Duration, Instant},
+};
+
+/// A builtin command, which takes its arguments and the editor the line was read with.
+///
+/// It returns its exit status, or why it failed.
+type Builtin = fn(&mut dyn Iterator<Item = &str>, &Editor) -> Result<i32, String>;
+
+/// The shell's builtin commands, by name.
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("cat", |args, _| cat(args).map(|()| 0)),
+    ("cd", |args, _| cd(args.next()).map(|()| 0)),
+    ("cp", |args, _| file_utils::cp(args).map(|()| 0)),
+    ("df", |args, _| df(args).map(|()| 0)),
+    ("exit", |args, _| Ok(exit(args))),
+    ("export", |args, _| export(args).map(|()| 0)),
+    ("fbdemo", |_, _| fbdemo().map(|()| 0)),
+    ("getpid", |_, _| {
+        println!("{}", userlib::sys::get_pid());
+        Ok(0)
+    }),
+    ("getrandom", |args, _| getrandom(args).map(|()| 0)),
+    ("getrandomtest", |_, _| getrandomtest().map(|()| 0)),
+    ("hello", |_, _| {
+        println!("Hello from user shell!");
+        Ok(0)
+    }),
+    ("hexdump", |args, _| hexdump(args).map(|()| 0)),
+    ("history", |_, editor| {
+        history(editor);
+        Ok(0)
+    }),
+    ("keytest", |_, _| keytest().map(|()| 0)),
+    ("ls", |args, _| ls(args).map(|()| 0)),
+    ("mkdir", |args, _| file_utils::mkdir(args).map(|()| 0)),
+    ("mv", |args, _| file_utils::mv(args).map(|()| 0)),
+    ("ping", |args, _| ping(args).map(|()| 0)),
+    ("poweroff", |_, _| {
+        userlib::sys::shutdown(userlib::sys::ShutdownKind::PowerOff)
+    }),
+    ("prepend", |args, _| prepend(args).map(|()| 0)),
+    ("pwd", |_, _| {
+        println!("{}", userlib::env::current_dir());
+        Ok(0)
+    }),
+    ("reboot", |_, _| {
+        userlib::sys::shutdown(userlib::sys::ShutdownKind::Reboot)
+    }),
+    ("rm", |args, _| file_utils::rm(args).map(|()| 0)),
+    ("source", |args, editor| {
+        let path = args.next().ok_or("missing filename")?;
+        Ok(run_script(path, editor))
+    }),
+    ("stat", |args, _| stat(args).map(|()| 0)),
+    ("touch", |args, _| file_utils::touch(args).map(|()| 0)),
+    ("udpecho", |args, _| udpecho(args).map(|()| 0)),
+    ("udpsend", |args, _| udpsend(args).map(|()| 0)),
+];
+
+/// Where to look for programs if the `PATH` environment variable isn't set.
+const DEFAULT_PATH: &str = "/bin";
+
+/// The script the shell runs at boot (when it's the first process), if it exists.
+const RC_PATH: &str = "/etc/rc";
 
 #[unsafe(no_mangle)]
 extern "Rust" fn main() {
-    let mut line_buf = alloc::vec::Vec::<u8>::new();
-    print!("> ");
+    userlib::panic::set_backtrace(true);
+    if userlib::env::var("PATH").is_err() {
+        userlib::env::set_var("PATH", DEFAULT_PATH);
+    }
+    let mut editor = Editor::new();
+    if let Some(script) = userlib::env::args().nth(1) {
+        let status = run_script(script, &editor);
+        userlib::process::exit(status);
+    }
+    if userlib::sys::get_pid() == 1 && fs::metadata(RC_PATH).is_ok() {
+        run_script(RC_PATH, &editor);
+    }
+    editor.set_completer(complete);
+    repl(&mut editor);
+}
+
+/// Read and run lines from the terminal, forever.
+fn repl(editor: &mut Editor) -> ! {
     loop {
-        match userlib::sys::getchar().unwrap() {
-            // On newline, run the queued command
-            '\r' | '\n' => {
-                let cmd = str::from_utf8(&line_buf).expect("Invalid utf-8");
-                println!();
+        let prompt = format!("{}> ", userlib::env::current_dir());
+        let line = match editor.read_line(&prompt) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Failed to read line: {e}");
+                continue;
+            }
+        };
+        editor.add_history(&line);
+        run_line(&line, editor);
+    }
+}
 
-                let mut cmd_parts = cmd.split_whitespace(); // TODO Support complex escaping
+/// Run the commands in the script at `path`, one line at a time.
+///
+/// Returns the exit status of the last command run, or 1 if the script couldn't be read.
+fn run_script(path: &str, editor: &Editor) -> i32 {
+    let script = match fs::read_to_string(path) {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return 1;
+        }
+    };
+    let mut status = 0;
+    for line in script.lines() {
+        if let Some(line_status) = run_line(line, editor) {
+            status = line_status;
+        }
+    }
+    status
+}
 
-                let Some(cmd_name) = cmd_parts.next() else {
-                    print!("> ");
-                    continue;
+/// Run one line of commands.
+///
+/// Returns the exit status of the last pipeline run, or `None` if the line had nothing to run.
+/// Lines which can't be parsed have a status of 2.
+fn run_line(line: &str, editor: &Editor) -> Option<i32> {
+    let pipelines = match tokenize(line).and_then(parse_line) {
+        Ok(pipelines) => pipelines,
+        Err(message) => {
+            eprintln!("{message}");
+            return Some(2);
+        }
+    };
+    let mut status = None;
+    for (condition, stages) in pipelines {
+        let run = match condition {
+            Condition::Always => true,
+            Condition::IfSuccess => status == Some(0),
+            Condition::IfFailure => status != Some(0),
+        };
+        if !run {
+            continue;
+        }
+        status = match stages.as_slice() {
+            [stage] if stage.words.is_empty() => {
+                for (name, value) in &stage.assignments {
+                    vars::set(name, value);
+                }
+                // A line with nothing on it doesn't count as running anything.
+                (!stage.is_empty()).then_some(0)
+            }
+            stages if stages.iter().any(|stage| stage.words.is_empty()) => {
+                eprintln!("Missing command in pipeline");
+                Some(2)
+            }
+            stages => Some(run_pipeline(stages, editor)),
+        };
+    }
+    status
+}
+
+/// When a pipeline on a line runs, depending on the one before it.
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    /// The pipeline always runs, since it's the first one on the line.
+    Always,
+    /// The pipeline runs if the one before it succeeded, as after `&&`.
+    IfSuccess,
+    /// The pipeline runs if the one before it failed, as after `||`.
+    IfFailure,
+}
+
+/// Parse the tokens of a command line into pipelines, along with when to run each one.
+///
+/// A redirection operator takes the word after it as its file name. If a stream is redirected
+/// more than once, the last one wins. Wildcards in other words are expanded with [`expand_word`].
+fn parse_line(tokens: Vec<Token>) -> Result<Vec<(Condition, Vec<Stage>)>, &'static str> {
+    let mut pipelines = Vec::new();
+    let mut condition = Condition::Always;
+    let mut stages = Vec::new();
+    let mut stage = Stage::default();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => match vars::parse_assignment(&word.text) {
+                Some((name, value)) if stage.words.is_empty() => {
+                    stage.assignments.push((name.into(), value.into()));
+                }
+                _ => stage.words.extend(expand_word(word)),
+            },
+            Token::Pipe => stages.push(core::mem::take(&mut stage)),
+            Token::And | Token::Or => {
+                stages.push(core::mem::take(&mut stage));
+                if stages.iter().all(Stage::is_empty) {
+                    return Err("Missing command before && or ||");
+                }
+                pipelines.push((condition, core::mem::take(&mut stages)));
+                condition = if matches!(token, Token::And) {
+                    Condition::IfSuccess
+                } else {
+                    Condition::IfFailure
                 };
+            }
+            Token::Redirect(redirect) => {
+                let Some(Token::Word(Word { text: file, .. })) = tokens.next() else {
+                    return Err("Missing filename for redirection");
+                };
+                match redirect {
+                    Redirect::Stdin => stage.redirections.stdin = Some(file),
+                    Redirect::Stdout => stage.redirections.stdout = Some((file, false)),
+                    Redirect::Append => stage.redirections.stdout = Some((file, true)),
+                }
+            }
+        }
+    }
+    stages.push(stage);
+    if !matches!(condition, Condition::Always) && stages.iter().all(Stage::is_empty) {
+        return Err("Missing command after && or ||");
+    }
+    pipelines.push((condition, stages));
+    Ok(pipelines)
+}
+
+/// Expand the wildcards in a word, into the paths of the files they match.
+///
+/// If nothing matches, the word is left as it is.
+fn expand_word(word: Word) -> Vec<String> {
+    let Some(pattern) = word.pattern else {
+        return alloc::vec![word.text];
+    };
+    let paths = glob::expand(&pattern);
+    if paths.is_empty() {
+        alloc::vec![word.text]
+    } else {
+        paths
+    }
+}
+
+/// One command in a pipeline, with its arguments, redirections and variable assignments.
+#[derive(Default)]
+struct Stage {
+    /// The variables to set for the command, given as `NAME=value` before the command name.
+    ///
+    /// With no command, these set the shell's variables instead.
+    assignments: Vec<(String, String)>,
+    /// The command name followed by its arguments.
+    words: Vec<String>,
+    /// Where the command's standard streams go, if not to the rest of the pipeline.
+    redirections: Redirections,
+}
+impl Stage {
+    /// Check whether there's nothing at all in this part of the command line.
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.assignments.is_empty()
+    }
+
+    /// Check whether this command is built into the shell, rather than a program to run.
+    fn is_builtin(&self) -> bool {
+        BUILTINS.iter().any(|&(name, _)| name == self.words[0])
+    }
+}
 
-                match cmd_name {
-                    "hello" => println!("Hello from user shell!"),
-                    "getpid" => {
-                        let pid = userlib::sys::get_pid();
-                        println!("{pid}");
-                    }
-                    "exit" => userlib::sys::exit(0),
-                    "getrandomtest" => {
-                        // Test that `getrandom` enforces valid addresses
-                        // SAFETY:
-                        // We ask the OS to write 1kB random data at memory address 0. This address
-                        // isn't mapped, so it should report an error.
-                        let (ok, err) = unsafe {
-                            userlib::sys::syscall(
-                                userlib::sys::Syscall::GetRandom as u32,
-                                [0, 1024, 0],
-                            )
-                        };
-                        assert_eq!(ok as i32, -1);
-                        assert_eq!(err.unwrap() as u32, 7);
-                        println!("Memory validation rejected successfully!");
-                    }
-                    "getrandom" => {
-                        let len = cmd_parts
-                            .next()
-                            .map_or(16, |s| s.parse().expect("Invalid number"));
-                        let mut buf = alloc::vec![0_u8; len];
-                        userlib::sys::get_random(&mut buf).expect("Failed to get random data");
-                        for byte in buf {
-                            print!("{byte:02X}");
-                        }
-                        println!();
-                    }
-                    "cat" => {
-                        let Some(filename) = cmd_parts.next() else {
-                            print!("Missing filename for cat command\n> ");
-                            line_buf.clear();
-                            continue;
-                        };
-                        let file = File::open(filename).expect("Failed to open file");
-                        let read_buf = &mut [0; 2048];
-                        let contents =
-                            str::from_utf8(file.read(read_buf).expect("Failed to read file"))
-                                .expect("File was invalid utf-8");
-                        print!("{contents}");
-                    }
-                    "prepend" => {
-                        let Some(filename) = cmd_parts.next() else {
-                            print!("Missing filename for prepend command\n> ");
-                            line_buf.clear();
-                            continue;
-                        };
-                        let file = File::open(filename).expect("Failed to open file");
-                        let read_buf = &mut [0; 2048];
-                        let contents =
-                            str::from_utf8(file.read(read_buf).expect("Failed to read file"))
-                                .expect("File was invalid utf-8");
-                        let file = File::overwrite(filename).expect("Failed to open file");
-                        let prepend_buf = &cmd.as_bytes()[9 + filename.len()..];
-                        file.write_all(prepend_buf)
-                            .expect("Error writing to buffer");
-                        file.write_all(contents.as_bytes())
-                            .expect("Error writing to buffer");
-                    }
-                    _ => {
-                        println!("Unrecognized command: {cmd}");
-                    }
+/// Run the commands in a pipeline, each with its stdout connected to the next one's stdin.
+///
+/// Commands that aren't built into the shell are run as programs, found with [`find_program`].
+/// Builtin commands run in the shell itself, with its standard streams pointed at the pipes while
+/// they run. Only one command can be a builtin, since the pipes could fill up if the shell had to
+/// run two of them one after the other.
+///
+/// Returns the exit status of the last command in the pipeline, which is 127 if it couldn't be
+/// found.
+fn run_pipeline(stages: &[Stage], editor: &Editor) -> i32 {
+    if stages.iter().filter(|stage| stage.is_builtin()).count() > 1 {
+        eprintln!("Only one command in a pipeline can be a shell builtin");
+        return 2;
+    }
+    let mut status = 0;
+    let mut children = Vec::new();
+    let mut builtin = None;
+    let mut next_stdin = None;
+    for (i, stage) in stages.iter().enumerate() {
+        let is_last = i + 1 == stages.len();
+        let mut streams = match stage.redirections.open() {
+            Ok(streams) => streams,
+            Err(e) => {
+                eprintln!("Failed to redirect: {e}");
+                status = 1;
+                break;
+            }
+        };
+        let stdin = next_stdin.take();
+        streams.stdin = streams.stdin.or(stdin);
+        if !is_last {
+            let (reader, writer) = match userlib::io::pipe() {
+                Ok(pipe) => pipe,
+                Err(e) => {
+                    eprintln!("Failed to create pipe: {e}");
+                    status = 1;
+                    break;
                 }
-                line_buf.clear();
-                print!("> ");
+            };
+            next_stdin = Some(reader.into());
+            streams.stdout = streams.stdout.or(Some(writer.into()));
+        }
+        if stage.is_builtin() {
+            builtin = Some((stage, streams, is_last));
+            continue;
+        }
+        let Some(program) = find_program(&stage.words[0]) else {
+            eprintln!("Unrecognized command: {}", stage.words[0]);
+            if is_last {
+                status = 127;
             }
-            // Handle backspace to allow command editing.
-            '\x7f' => {
-                if line_buf.pop().is_some() {
-                    print!("\x08 \x08");
+            continue;
+        };
+        let mut command = userlib::process::Command::new(program.as_str());
+        command.args(stage.words[1..].iter().map(String::as_str));
+        for (name, value) in &stage.assignments {
+            command.env(name, value);
+        }
+        if let Some(stdin) = streams.stdin {
+            command.stdin(stdin);
+        }
+        if let Some(stdout) = streams.stdout {
+            command.stdout(stdout);
+        }
+        match command.spawn() {
+            Ok(child) => children.push((&stage.words[0], child, is_last)),
+            Err(e) => {
+                eprintln!("Failed to run {}: {e}", stage.words[0]);
+                if is_last {
+                    status = 126;
                 }
             }
-            // Otherwise, assume normal character
-            //
-            // TODO Handle other special characters
-            c => {
-                print!("{c}");
-                line_buf.push(u8::try_from(c).expect("Non-u8 character"));
+        }
+    }
+    // Close our copy of the last pipe, in case a failure stopped us before giving it to anyone.
+    drop(next_stdin);
+
+    if let Some((stage, streams, is_last)) = builtin {
+        let builtin_status = match streams.redirect_shell() {
+            Ok(redirected) => {
+                let builtin_status = run_builtin(stage, editor);
+                drop(redirected);
+                builtin_status
+            }
+            Err(e) => {
+                eprintln!("Failed to redirect: {e}");
+                1
             }
+        };
+        if is_last {
+            status = builtin_status;
+        }
+        // Dropping the streams closes the pipes, so the commands on either side see them end.
+        drop(streams);
+    }
+    for (name, mut child, is_last) in children {
+        let child_status = match child.wait() {
+            Ok(exit_status) if exit_status.success() => 0,
+            Ok(exit_status) => {
+                eprintln!("{name} failed ({exit_status})");
+                exit_status.code()
+            }
+            Err(e) => {
+                eprintln!("Failed to wait for {name}: {e}");
+                1
+            }
+        };
+        if is_last {
+            status = child_status;
+        }
+    }
+    status
+}
+
+/// Run a builtin command, with the variables assigned for it set only while it runs.
+///
+/// Returns the command's exit status.
+fn run_builtin(stage: &Stage, editor: &Editor) -> i32 {
+    // The assignments only last for the command, so remember what to put back.
+    let saved: Vec<_> = stage
+        .assignments
+        .iter()
+        .map(|(name, value)| {
+            let old = userlib::env::var(name).ok();
+            userlib::env::set_var(name, value);
+            (name, old)
+        })
+        .collect();
+    let status = run_command(
+        &stage.words[0],
+        stage.words[1..].iter().map(String::as_str),
+        editor,
+    );
+    for (name, old) in saved.into_iter().rev() {
+        match old {
+            Some(old) => userlib::env::set_var(name, &old),
+            None => userlib::env::remove_var(name),
+        }
+    }
+    status
+}
+
+/// Find the program to run for the command `name`.
+///
+/// Names with a `/` in them are paths to the program. Otherwise, we look for a file with that name
+/// in each of the directories listed in the `PATH` environment variable (separated by `:`), in
+/// order.
+fn find_program(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(PathBuf::from(name));
+    }
+    let path = userlib::env::var("PATH").unwrap_or_default();
+    path.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(name))
+        .find(|program| fs::metadata(program).is_ok_and(|metadata| metadata.is_file()))
+}
+
+/// Run the command named `cmd_name`, with the given arguments.
+///
+/// `editor` is what the line was read with, for commands which look at its history. Returns the
+/// command's exit status. If the command fails, its error is printed after its name.
+fn run_command<'a>(
+    cmd_name: &str,
+    cmd_parts: impl Iterator<Item = &'a str>,
+    editor: &Editor,
+) -> i32 {
+    match builtin(cmd_name, cmd_parts, editor) {
+        Ok(status) => status,
+        Err(message) => {
+            eprintln!("{cmd_name}: {message}");
+            1
+        }
+    }
+}
+
+/// Run the builtin command named `cmd_name`, returning its exit status or why it failed.
+fn builtin<'a>(
+    cmd_name: &str,
+    mut cmd_parts: impl Iterator<Item = &'a str>,
+    editor: &Editor,
+) -> Result<i32, String> {
+    let Some(&(_, run)) = BUILTINS.iter().find(|&&(name, _)| name == cmd_name) else {
+        eprintln!("Unrecognized command: {cmd_name}");
+        return Ok(127);
+    };
+    run(&mut cmd_parts, editor)
+}
+
+/// Print the lines in the editor's history, numbered from 1.
+fn history(editor: &Editor) {
+    for (i, line) in editor.history().enumerate() {
+        println!("{:>5}  {line}", i + 1);
+    }
+}
+
+/// Export the given variables (or `NAME=value` assignments) to the environment.
+///
+/// With no arguments, this prints every environment variable.
+fn export<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut args = args.peekable();
+    if args.peek().is_none() {
+        for (name, value) in userlib::env::vars() {
+            println!("export {name}={value}");
         }
     }
+    for arg in args {
+        match vars::parse_assignment(arg) {
+            Some((name, value)) => vars::export(name, Some(value)),
+            None if vars::is_name(arg) => vars::export(arg, None),
+            None => return Err(format!("invalid variable name {arg:?}")),
+        }
+    }
+    Ok(())
+}
+
+/// Exit the shell, with the given code (or 0).
+///
+/// This only returns if the code is invalid, with the exit status for that.
+fn exit<'a>(mut args: impl Iterator<Item = &'a str>) -> i32 {
+    let code = match args
+        .next()
+        .map_or(Ok(0), |code| parse_arg(code, "exit code"))
+    {
+        Ok(code) => code,
+        Err(message) => {
+            // Like other shells, treat this as a usage error rather than a failure.
+            eprintln!("exit: {message}");
+            return 2;
+        }
+    };
+    userlib::process::exit(code);
+}
+
+/// Check that `getrandom` rejects addresses the process can't write to.
+fn getrandomtest() -> Result<(), String> {
+    // SAFETY:
+    // We ask the OS to write 1kB random data at memory address 0. This address
+    // isn't mapped, so it should report an error.
+    let (ok, err) = unsafe {
+        userlib::sys::syscall(userlib::sys::Syscall::GetRandom as u32, [0, 1024, 0, 0, 0])
+    };
+    if ok as i32 != -1 || !matches!(err, Some(userlib::io::ErrorKind::NotPermitted)) {
+        return Err(String::from("memory validation wasn't enforced"));
+    }
+    println!("Memory validation rejected successfully!");
+    Ok(())
+}
+
+/// Print the given number of random bytes (or 16), in hex.
+fn getrandom<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let len = args.next().map_or(Ok(16), |len| parse_arg(len, "length"))?;
+    let mut buf = alloc::vec![0_u8; len];
+    userlib::sys::get_random(&mut buf).map_err(|e| e.to_string())?;
+    for byte in buf {
+        print!("{byte:02X}");
+    }
+    println!();
+    Ok(())
+}
+
+/// Print the contents of a file.
+fn cat<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let filename = args.next().ok_or("missing filename")?;
+    let mut file = fs::File::open(filename).map_err(|e| format!("{filename}: {e}"))?;
+    userlib::io::copy(&mut file, &mut userlib::io::Stdout::lock())
+        .map_err(|e| format!("{filename}: {e}"))?;
+    Ok(())
+}
+
+/// Add the rest of the arguments, joined with spaces, to the start of a file.
+fn prepend<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let filename = args.next().ok_or("missing filename")?;
+    let contents = fs::read(filename).map_err(|e| format!("{filename}: {e}"))?;
+    let mut new_contents = args.collect::<Vec<_>>().join(" ").into_bytes();
+    new_contents.extend_from_slice(&contents);
+    fs::write(filename, new_contents).map_err(|e| format!("{filename}: {e}"))?;
+    Ok(())
+}
+
+/// Print how much space is used and free on the filesystem holding the given path (or `/`).
+fn df<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let path = args.next().unwrap_or("/");
+    let stats = fs::filesystem_stats(path).map_err(|e| format!("{path}: {e}"))?;
+    let fs_type = match stats.fs_type {
+        fs::FilesystemStats::EXT2_FS_TYPE => "ext2",
+        _ => "unknown",
+    };
+    let block_kb = u64::from(stats.block_size) / 1024;
+    let used_blocks = stats.total_blocks - stats.free_blocks;
+    println!("Type     1K-blocks       Used  Available Use%     Inodes      IFree");
+    println!(
+        "{fs_type:<8} {:>9} {:>10} {:>10} {:>3}% {:>10} {:>10}",
+        stats.total_blocks * block_kb,
+        used_blocks * block_kb,
+        stats.free_blocks * block_kb,
+        (used_blocks * 100).div_ceil(stats.total_blocks.max(1)),
+        stats.total_inodes,
+        stats.free_inodes,
+    );
+    Ok(())
+}
+
+/// Send ICMP echo requests to an address (4 of them, unless a count is given), and report the
+/// replies.
+fn ping<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let addr: userlib::net::Ipv4Addr = parse_arg(args.next().ok_or("missing address")?, "address")?;
+    let count: u16 = args
+        .next()
+        .map_or(Ok(4), |count| parse_arg(count, "count"))?;
+    let socket = userlib::net::IcmpEchoSocket::new(Some(Duration::from_secs(1)))
+        .map_err(|e| format!("failed to open socket: {e}"))?;
+    let mut received = 0;
+    for seq in 0..count {
+        let mut request = [0_u8; 64];
+        request[0] = userlib::net::IcmpEchoSocket::TYPE_ECHO_REQUEST;
+        request[6..8].copy_from_slice(&seq.to_be_bytes());
+        for (i, byte) in request
+            .iter_mut()
+            .enumerate()
+            .skip(userlib::net::IcmpEchoSocket::HEADER_LEN)
+        {
+            *byte = i as u8;
+        }
+        let sent_at = Instant::now();
+        socket
+            .send_to(&request, addr)
+            .map_err(|e| format!("failed to send echo request: {e}"))?;
+        let reply_buf = &mut [0; 128];
+        match socket.recv_from(reply_buf) {
+            Ok((reply, from)) => {
+                let rtt = sent_at.elapsed();
+                let reply_seq = u16::from_be_bytes([reply[6], reply[7]]);
+                println!(
+                    "{} bytes from {from}: icmp_seq={reply_seq} time={:.3} ms",
+                    reply.len(),
+                    rtt.as_secs_f64() * 1000.0,
+                );
+                received += 1;
+            }
+            Err(e) if matches!(e.kind(), userlib::net::ErrorKind::TimedOut) => {
+                println!("Request timeout for icmp_seq={seq}");
+            }
+            Err(e) => return Err(format!("failed to receive echo reply: {e}")),
+        }
+    }
+    println!(
+        "{count} packets transmitted, {received} packets received, {}% packet loss",
+        (u32::from(count - received) * 100) / u32::from(count.max(1)),
+    );
+    Ok(())
+}
+
+/// Send UDP datagrams received on a port (7, unless one is given) back where they came from,
+/// forever or until the given number have been echoed.
+fn udpecho<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let port = args.next().map_or(Ok(7), |port| parse_arg(port, "port"))?;
+    let count: Option<usize> = args
+        .next()
+        .map(|count| parse_arg(count, "count"))
+        .transpose()?;
+    let socket = userlib::net::UdpSocket::bind(userlib::net::SocketAddrV4::new(
+        userlib::net::Ipv4Addr::UNSPECIFIED,
+        port,
+    ))
+    .map_err(|e| format!("failed to bind socket: {e}"))?;
+    println!("Echoing UDP datagrams on port {port}");
+    let mut echoed = 0;
+    while count.is_none_or(|count| echoed < count) {
+        let buf = &mut [0; 1472];
+        let (datagram, from) = socket
+            .recv_from(buf)
+            .map_err(|e| format!("failed to receive datagram: {e}"))?;
+        println!("{} bytes from {from}", datagram.len());
+        socket
+            .send_to(datagram, from)
+            .map_err(|e| format!("failed to send datagram: {e}"))?;
+        echoed += 1;
+    }
+    Ok(())
+}
+
+/// Send the rest of the arguments, joined with spaces, as a UDP datagram to an address.
+fn udpsend<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let addr: userlib::net::SocketAddrV4 =
+        parse_arg(args.next().ok_or("missing address")?, "address")?;
+    let message = args.collect::<Vec<_>>().join(" ");
+    let socket = userlib::net::UdpSocket::bind(userlib::net::SocketAddrV4::default())
+        .map_err(|e| format!("failed to bind socket: {e}"))?;
+    let sent = socket
+        .send_to(message.as_bytes(), addr)
+        .map_err(|e| format!("failed to send datagram: {e}"))?;
+    println!("Sent {sent} bytes to {addr}");
+    Ok(())
+}
+
+/// Draw a gradient on the framebuffer.
+fn fbdemo() -> Result<(), String> {
+    let mut fb =
+        userlib::fb::Framebuffer::open().map_err(|e| format!("failed to open framebuffer: {e}"))?;
+    let info = fb.info();
+    // Draw a gradient, red across and green down.
+    for y in 0..info.height {
+        for x in 0..info.width {
+            let red = x * 0xFF / info.width.max(1);
+            let green = y * 0xFF / info.height.max(1);
+            fb.set_pixel(x, y, (red << 16) | (green << 8) | 0x80);
+        }
+    }
+    fb.flush()
+        .map_err(|e| format!("failed to flush framebuffer: {e}"))?;
+    println!("Drew to {}x{} framebuffer", info.width, info.height);
+    Ok(())
+}
+
+/// Print the keyboard's key events until escape is pressed.
+fn keytest() -> Result<(), String> {
+    use userlib::input::InputEvent;
+
+    let keyboard =
+        userlib::input::Keyboard::open().map_err(|e| format!("failed to open keyboard: {e}"))?;
+    println!("Press keys to see their events, or escape (here or there) to stop");
+    let mut ctrl_held = false;
+    loop {
+        let Some(event) = keyboard
+            .next_event_timeout(Duration::from_millis(50))
+            .map_err(|e| format!("failed to read event: {e}"))?
+        else {
+            // The console might be what's being typed on, so let escape stop us there.
+            if let Ok(Some('\x1b')) = userlib::sys::getchar_nonblocking() {
+                break;
+            }
+            continue;
+        };
+        if event.ty != InputEvent::TYPE_KEY {
+            continue;
+        }
+        let pressed = event.value != InputEvent::KEY_RELEASED;
+        match event.code {
+            InputEvent::KEY_ESC => break,
+            InputEvent::KEY_LEFT_CTRL | InputEvent::KEY_RIGHT_CTRL => {
+                ctrl_held = pressed;
+                continue;
+            }
+            _ => {}
+        }
+        if !pressed {
+            continue;
+        }
+        let name = match event.code {
+            InputEvent::KEY_UP => "up",
+            InputEvent::KEY_DOWN => "down",
+            InputEvent::KEY_LEFT => "left",
+            InputEvent::KEY_RIGHT => "right",
+            _ => "",
+        };
+        let ctrl = if ctrl_held { "ctrl+" } else { "" };
+        println!("{ctrl}key {} {name}", event.code);
+    }
+    Ok(())
+}
+
+/// Parse an argument, describing it as `what` in the error if it's invalid.
+fn parse_arg<T: core::str::FromStr>(arg: &str, what: &str) -> Result<T, String> {
+    arg.parse().map_err(|_| format!("invalid {what} {arg:?}"))
+}
+
+/// Change the shell's current directory to `dir`.
+///
+/// With no directory, this goes to the one in `HOME` (or the root directory, if that isn't set).
+/// `cd -` goes back to the previous directory, which is kept in `OLDPWD`, and prints it.
+fn cd(dir: Option<&str>) -> Result<(), String> {
+    let target = match dir {
+        Some("-") => userlib::env::var("OLDPWD").map_err(|_| "OLDPWD not set")?,
+        Some(dir) => String::from(dir),
+        None => userlib::env::var("HOME").unwrap_or_else(|_| String::from("/")),
+    };
+    let previous = userlib::env::current_dir();
+    userlib::env::set_current_dir(&target).map_err(|e| format!("{target}: {e}"))?;
+    userlib::env::set_var("OLDPWD", previous.as_str());
+    if dir == Some("-") {
+        println!("{}", userlib::env::current_dir());
+    }
+    Ok(())
+}
+
+/// List the files in a directory (or just the one file, if given a file).
+///
+/// `-a` includes files whose names start with `.`, and `-l` shows each file's type, permissions,
+/// number of links, and size alongside its name.
+fn ls<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut all = false;
+    let mut long = false;
+    let mut path = None;
+    let mut parser = userlib::args::Parser::new(args);
+    while let Some(arg) = parser.next().map_err(|e| e.to_string())? {
+        match arg {
+            Arg::Short('a') => all = true,
+            Arg::Short('l') => long = true,
+            Arg::Value(value) if path.is_none() => path = Some(value),
+            _ => return Err(arg.unexpected().to_string()),
+        }
+    }
+    let path = path.unwrap_or(".");
+
+    let metadata = fs::metadata(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut entries = if metadata.is_dir() {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path).map_err(|e| format!("{path}: {e}"))? {
+            let entry = entry.map_err(|e| format!("{path}: {e}"))?;
+            if !all && entry.file_name().starts_with('.') {
+                continue;
+            }
+            let metadata = entry
+                .metadata()
+                .map_err(|e| format!("{}: {e}", entry.path()))?;
+            entries.push((String::from(entry.file_name()), metadata));
+        }
+        entries
+    } else {
+        alloc::vec![(String::from(path), metadata)]
+    };
+    entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (name, metadata) in entries {
+        if long {
+            println!(
+                "{} {:>3} {:>10} {name}",
+                mode_string(metadata),
+                metadata.hard_links(),
+                metadata.len(),
+            );
+        } else {
+            println!("{name}");
+        }
+    }
+    Ok(())
+}
+
+/// Describe a file's type and permissions in the style of `ls -l`, like `drwxr-xr-x`.
+fn mode_string(metadata: fs::Metadata) -> String {
+    let kind = match metadata.kind() {
+        fs::FileKind::Directory => 'd',
+        fs::FileKind::RegularFile => '-',
+        fs::FileKind::Other => '?',
+    };
+    let permissions = metadata.permissions();
+    let mode = "rwxrwxrwx".chars().enumerate().map(|(i, c)| {
+        if permissions & (0o400 >> i) == 0 {
+            '-'
+        } else {
+            c
+        }
+    });
+    core::iter::once(kind).chain(mode).collect()
+}
+
+/// Show everything we know about the files at the given paths.
+fn stat<'a>(paths: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let mut paths = paths.peekable();
+    if paths.peek().is_none() {
+        return Err(String::from("missing file"));
+    }
+    for path in paths {
+        let metadata = fs::metadata(path).map_err(|e| format!("{path}: {e}"))?;
+        let kind = match metadata.kind() {
+            fs::FileKind::Directory => "directory",
+            fs::FileKind::RegularFile => "regular file",
+            fs::FileKind::Other => "other",
+        };
+        println!("  File: {path}");
+        println!("  Type: {kind}");
+        println!("  Size: {}", metadata.len());
+        println!(" Links: {}", metadata.hard_links());
+        println!(
+            "Access: {:04o} ({})",
+            metadata.permissions(),
+            mode_string(metadata),
+        );
+    }
+    Ok(())
+}
+
+/// Show the contents of a file in hex and ASCII, 16 bytes to a line.
+///
+/// `-s OFFSET` starts that many bytes into the file, and `-n LENGTH` stops after that many bytes.
+fn hexdump<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    /// The number of bytes shown on each line.
+    const LINE_LEN: usize = 16;
+
+    let mut offset = 0;
+    let mut length = None;
+    let mut path = None;
+    let mut parser = userlib::args::Parser::new(args);
+    while let Some(arg) = parser.next().map_err(|e| e.to_string())? {
+        match arg {
+            Arg::Short('s') | Arg::Long("skip") => {
+                offset = parser.parsed_value().map_err(|e| e.to_string())?;
+            }
+            Arg::Short('n') | Arg::Long("length") => {
+                length = Some(parser.parsed_value().map_err(|e| e.to_string())?);
+            }
+            Arg::Value(value) if path.is_none() => path = Some(value),
+            _ => return Err(arg.unexpected().to_string()),
+        }
+    }
+    let path = path.ok_or("missing file")?;
+
+    let mut file = fs::File::open(path).map_err(|e| format!("{path}: {e}"))?;
+    file.seek(userlib::io::SeekFrom::Start(offset))
+        .map_err(|e| format!("{path}: {e}"))?;
+    let mut remaining: u64 = length.unwrap_or(u64::MAX);
+    let mut buf = [0_u8; LINE_LEN];
+    while remaining > 0 {
+        let want = usize::try_from(remaining).map_or(LINE_LEN, |remaining| remaining.min(LINE_LEN));
+        // Fill the whole line if we can, even if the file gives us less at a time.
+        let mut len = 0;
+        while len < want {
+            match file.read(&mut buf[len..want]) {
+                Ok(0) => break,
+                Ok(read_len) => len += read_len,
+                Err(e) => return Err(format!("{path}: {e}")),
+            }
+        }
+        if len == 0 {
+            break;
+        }
+
+        let mut line = format!("{offset:08x} ");
+        for i in 0..LINE_LEN {
+            if i == LINE_LEN / 2 {
+                line.push(' ');
+            }
+            match buf[..len].get(i) {
+                // Writing to a `String` can't fail.
+                Some(byte) => _ = write!(line, " {byte:02x}"),
+                None => line.push_str("   "),
+            }
+        }
+        let ascii: String = buf[..len]
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    char::from(byte)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!("{line}  |{ascii}|");
+        offset += len as u64;
+        remaining -= len as u64;
+    }
+    println!("{offset:08x}");
+    Ok(())
+}
+
+/// Where to send a command's standard streams, as given by redirections on its command line.
+#[derive(Default)]
+struct Redirections {
+    /// The file to read standard input from, given with `< file`.
+    stdin: Option<String>,
+    /// The file to write standard output to, given with `> file` (or `>> file` to append, in which
+    /// case the flag is set).
+    stdout: Option<(String, bool)>,
+}
+impl Redirections {
+    /// Open the files that the standard streams are redirected to.
+    fn open(&self) -> userlib::io::Result<Streams> {
+        let stdin = self.stdin.as_deref().map(fs::File::open).transpose()?;
+        let stdout = self
+            .stdout
+            .as_ref()
+            .map(|(file, append)| {
+                if *append {
+                    fs::File::append(file)
+                } else {
+                    fs::File::create(file)
+                }
+            })
+            .transpose()?;
+        Ok(Streams {
+            stdin: stdin.map(Into::into),
+            stdout: stdout.map(Into::into),
+        })
+    }
+}
+
+/// What to give a command as its standard streams, or `None` to leave a stream as the shell's.
+struct Streams {
+    /// What the command reads its standard input from.
+    stdin: Option<OwnedResourceDescriptor>,
+    /// What the command writes its standard output to.
+    stdout: Option<OwnedResourceDescriptor>,
+}
+impl Streams {
+    /// Point the shell's own standard streams at these, for running a builtin command.
+    ///
+    /// The streams are put back when the result is dropped.
+    fn redirect_shell(&self) -> userlib::io::Result<Redirected> {
+        let mut redirected = Redirected::default();
+        if let Some(stdin) = &self.stdin {
+            redirected.stdin = Some(userlib::io::replace_stdin(stdin.borrow())?);
+        }
+        if let Some(stdout) = &self.stdout {
+            redirected.stdout = Some(userlib::io::replace_stdout(stdout.borrow())?);
+        }
+        Ok(redirected)
+    }
+}
+
+/// The standard streams the shell had before [`Streams::redirect_shell`], which get put back when
+/// this is dropped.
+#[derive(Default)]
+struct Redirected {
+    /// What standard input was, if it was redirected.
+    stdin: Option<OwnedResourceDescriptor>,
+    /// What standard output was, if it was redirected.
+    stdout: Option<OwnedResourceDescriptor>,
+}
+impl Drop for Redirected {
+    fn drop(&mut self) {
+        if let Some(stdin) = self.stdin.take() {
+            userlib::io::replace_stdin(stdin.borrow()).expect("Failed to restore stdin");
+        }
+        if let Some(stdout) = self.stdout.take() {
+            userlib::io::replace_stdout(stdout.borrow()).expect("Failed to restore stdout");
+        }
+    }
+}
+
+/// Complete the word before the cursor.
+///
+/// The first word of a command is completed as a builtin or a program in `PATH`, unless it looks
+/// like a path. Other words are completed as paths, from the entries of the directory they're in.
+fn complete(line: &str, cursor: usize) -> Completion {
+    let typed = &line[..cursor];
+    let start = typed
+        .rfind(|c: char| c.is_whitespace() || matches!(c, '|' | '<' | '>'))
+        .map_or(0, |idx| idx + 1);
+    let word = &typed[start..];
+    let before = typed[..start].trim_end();
+    let is_command = before.is_empty() || before.ends_with('|');
+    let mut candidates = if is_command && !word.contains('/') {
+        let path = userlib::env::var("PATH").unwrap_or_default();
+        let programs = path
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.metadata().is_ok_and(|metadata| metadata.is_file()))
+            .map(|entry| String::from(entry.file_name()));
+        let mut candidates: Vec<_> = BUILTINS
+            .iter()
+            .map(|&(cmd, _)| String::from(cmd))
+            .chain(programs)
+            .filter(|cmd| cmd.starts_with(word))
+            .map(|cmd| format!("{cmd} "))
+            .collect();
+        // A program might have the same name as a builtin, or be in more than one directory.
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    } else {
+        complete_path(word)
+    };
+    candidates.sort_unstable();
+    Completion { start, candidates }
+}
+
+/// Get the paths that `word` could be the start of.
+///
+/// Directories end with `/`, so completing can carry on into them, and everything else ends with a
+/// space, so the next word can be typed right away.
+fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(idx) => word.split_at(idx + 1),
+        None => ("", word),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().starts_with(prefix))
+        .map(|entry| {
+            let is_dir = entry.metadata().is_ok_and(|metadata| metadata.is_dir());
+            let suffix = if is_dir { "/" } else { " " };
+            format!("{dir}{}{suffix}", entry.file_name())
+        })
+        .collect()
 }
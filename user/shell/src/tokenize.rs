@@ -0,0 +1,207 @@
+//! Splitting command lines into words and operators.
+//!
+//! This follows the usual shell rules for quoting: inside single quotes every character is taken
+//! as it is, inside double quotes a backslash escapes `"`, `\` and `$`, and elsewhere a backslash
+//! escapes whatever comes after it. Unquoted whitespace separates words, and unquoted `|`, `||`,
+//! `&&`, `<`, `>` and `>>` are operators even without whitespace around them. A `#` at the start
+//! of a word starts a comment, which runs to the end of the line.
+//!
+//! Variables are expanded as the line is tokenized, from `$NAME` or `${NAME}` outside of single
+//! quotes. The value becomes part of the word the reference is in, without being split at
+//! whitespace.
+//!
+//! Words with an unquoted `*` or `?` in them are kept as patterns as well, to be matched against
+//! file names by [`glob`](crate::glob). Wildcards which are quoted or come from a variable are
+//! taken literally.
+
+use alloc::{string::String, vec::Vec};
+use core::{iter::Peekable, str::Chars};
+
+use crate::vars;
+
+/// A piece of a command line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Token {
+    /// A word.
+    Word(Word),
+    /// `|`, between the commands of a pipeline.
+    Pipe,
+    /// `&&`, to run the next pipeline only if the one before it succeeds.
+    And,
+    /// `||`, to run the next pipeline only if the one before it fails.
+    Or,
+    /// An operator redirecting one of a command's standard streams to the file named next.
+    Redirect(Redirect),
+}
+
+/// A word of a command line.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Word {
+    /// The text of the word, with its quotes and escapes removed.
+    pub text: String,
+    /// The word as a pattern for [`glob::expand`](crate::glob::expand), if it has any unquoted
+    /// wildcards in it.
+    pub pattern: Option<String>,
+}
+
+/// Which redirection a [`Token::Redirect`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redirect {
+    /// `<`, to read standard input from a file.
+    Stdin,
+    /// `>`, to write standard output to a file, replacing its contents.
+    Stdout,
+    /// `>>`, to write standard output to the end of a file.
+    Append,
+}
+
+/// Split `line` into tokens.
+///
+/// Fails if a quote isn't closed, if the line ends with a backslash, or if a `${` isn't followed
+/// by a variable name and a `}`.
+pub fn tokenize(line: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    // The word being built, or `None` between words. An empty word is still a word (from `''`).
+    let mut word: Option<WordBuilder> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' | '<' | '>' => {
+                tokens.extend(word.take().map(WordBuilder::finish));
+                tokens.push(match c {
+                    '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+                    '|' => Token::Pipe,
+                    '<' => Token::Redirect(Redirect::Stdin),
+                    _ if chars.next_if_eq(&'>').is_some() => Token::Redirect(Redirect::Append),
+                    _ => Token::Redirect(Redirect::Stdout),
+                });
+            }
+            '&' if chars.next_if_eq(&'&').is_some() => {
+                tokens.extend(word.take().map(WordBuilder::finish));
+                tokens.push(Token::And);
+            }
+            // A comment runs to the end of the line, but only if it starts a word.
+            '#' if word.is_none() => break,
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next().ok_or("Unterminated single quote")? {
+                        '\'' => break,
+                        c => word.push_literal(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next().ok_or("Unterminated double quote")? {
+                        '"' => break,
+                        '$' => match expand_variable(&mut chars)? {
+                            Some(value) => word.push_str_literal(&value),
+                            None => word.push_literal('$'),
+                        },
+                        '\\' => match chars.next().ok_or("Unterminated double quote")? {
+                            c @ ('"' | '\\' | '$') => word.push_literal(c),
+                            c => {
+                                word.push_literal('\\');
+                                word.push_literal(c);
+                            }
+                        },
+                        c => word.push_literal(c),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or("Nothing to escape at the end of the line")?;
+                word.get_or_insert_default().push_literal(escaped);
+            }
+            '$' => match expand_variable(&mut chars)? {
+                // As in other shells, an empty variable outside of quotes isn't a word by itself.
+                Some(value) if value.is_empty() => {}
+                Some(value) => word.get_or_insert_default().push_str_literal(&value),
+                None => word.get_or_insert_default().push_literal('$'),
+            },
+            c if c.is_whitespace() => tokens.extend(word.take().map(WordBuilder::finish)),
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    tokens.extend(word.map(WordBuilder::finish));
+    Ok(tokens)
+}
+
+/// A word which is being tokenized.
+#[derive(Default)]
+struct WordBuilder {
+    /// The text of the word so far.
+    text: String,
+    /// The text of the word as a pattern, with the wildcards and backslashes which should be taken
+    /// literally escaped with a backslash.
+    pattern: String,
+    /// Whether there are any unquoted wildcards in the word.
+    has_wildcards: bool,
+}
+impl WordBuilder {
+    /// Add an unquoted character to the word.
+    fn push(&mut self, c: char) {
+        if matches!(c, '*' | '?') {
+            self.text.push(c);
+            self.pattern.push(c);
+            self.has_wildcards = true;
+        } else {
+            self.push_literal(c);
+        }
+    }
+
+    /// Add a character to the word which is never a wildcard, such as one that was quoted.
+    fn push_literal(&mut self, c: char) {
+        self.text.push(c);
+        if matches!(c, '*' | '?' | '\\') {
+            self.pattern.push('\\');
+        }
+        self.pattern.push(c);
+    }
+
+    /// Add characters to the word which are never wildcards.
+    fn push_str_literal(&mut self, s: &str) {
+        for c in s.chars() {
+            self.push_literal(c);
+        }
+    }
+
+    /// Make the finished word into a token.
+    fn finish(self) -> Token {
+        Token::Word(Word {
+            text: self.text,
+            pattern: self.has_wildcards.then_some(self.pattern),
+        })
+    }
+}
+
+/// Expand the variable reference after a `$`, which is either `NAME` or `{NAME}`.
+///
+/// Unset variables expand to nothing. Returns `None` if there's no variable name after the `$`, in
+/// which case it isn't a reference, only a `$`.
+fn expand_variable(chars: &mut Peekable<Chars<'_>>) -> Result<Option<String>, &'static str> {
+    let mut name = String::new();
+    if chars.next_if_eq(&'{').is_some() {
+        loop {
+            match chars.next().ok_or("Unterminated ${")? {
+                '}' => break,
+                c => name.push(c),
+            }
+        }
+        if !vars::is_name(&name) {
+            return Err("Invalid variable name in ${}");
+        }
+    } else {
+        while let Some(c) = chars.next_if(|&c| vars::is_name_char(c, name.is_empty())) {
+            name.push(c);
+        }
+        if name.is_empty() {
+            return Ok(None);
+        }
+    }
+    Ok(Some(vars::get(&name).unwrap_or_default()))
+}
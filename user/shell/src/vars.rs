@@ -0,0 +1,57 @@
+//! Shell variables.
+//!
+//! A variable is either exported, in which case it's one of the shell's environment variables and
+//! is passed on to the programs it runs, or known only to the shell itself. A variable set with
+//! `NAME=value` stays on whichever side it's already on, and starts out only in the shell.
+
+use alloc::{collections::BTreeMap, string::String};
+
+use userlib::sync::Mutex;
+
+/// The variables which haven't been exported.
+static SHELL_VARS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Get the value of the variable `name`, whether or not it's exported.
+pub fn get(name: &str) -> Option<String> {
+    let value = SHELL_VARS.lock().get(name).cloned();
+    value.or_else(|| userlib::env::var(name).ok())
+}
+
+/// Set the variable `name` to `value`.
+pub fn set(name: &str, value: &str) {
+    if userlib::env::var(name).is_ok() {
+        userlib::env::set_var(name, value);
+    } else {
+        SHELL_VARS.lock().insert(name.into(), value.into());
+    }
+}
+
+/// Export the variable `name`, so the programs the shell runs see it.
+///
+/// If a value is given, the variable is set to it first. Exporting a variable which isn't set and
+/// isn't given a value does nothing.
+pub fn export(name: &str, value: Option<&str>) {
+    let shell_value = SHELL_VARS.lock().remove(name);
+    if let Some(value) = value.or(shell_value.as_deref()) {
+        userlib::env::set_var(name, value);
+    }
+}
+
+/// Split a word like `NAME=value` into the name and value, if it's an assignment.
+pub fn parse_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, value) = word.split_once('=')?;
+    is_name(name).then_some((name, value))
+}
+
+/// Check whether `name` can be the name of a variable.
+///
+/// Names are made of ASCII letters, digits and underscores, and don't start with a digit.
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| is_name_char(c, true)) && chars.all(|c| is_name_char(c, false))
+}
+
+/// Check whether `c` can be part of a variable name, at the start of the name if `first` is set.
+pub fn is_name_char(c: char, first: bool) -> bool {
+    c == '_' || c.is_ascii_alphabetic() || (!first && c.is_ascii_digit())
+}
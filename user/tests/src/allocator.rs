@@ -0,0 +1,82 @@
+//! Tests for `userlib::alloc`.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use userlib::{
+    alloc::{ALLOC_POISON, ALLOCATOR, FREE_POISON},
+    thread,
+};
+
+userlib::user_test! {
+    fn stats_track_allocations() {
+        let before = ALLOCATOR.stats();
+        let boxed = Box::new([0_u8; 100]);
+        let during = ALLOCATOR.stats();
+        let (_, class_before) = before.size_classes().find(|&(size, _)| size == 128).unwrap();
+        let (_, class_during) = during.size_classes().find(|&(size, _)| size == 128).unwrap();
+        assert_eq!(class_during.allocations, class_before.allocations + 1);
+        assert_eq!(class_during.bytes_live, class_before.bytes_live + 128);
+        assert!(class_during.pages_mapped > 0);
+
+        drop(boxed);
+        let after = ALLOCATOR.stats();
+        let (_, class_after) = after.size_classes().find(|&(size, _)| size == 128).unwrap();
+        assert_eq!(class_after.frees, class_before.frees + 1);
+        assert_eq!(class_after.live(), class_before.live());
+        assert_eq!(class_after.bytes_live, class_before.bytes_live);
+    }
+
+    fn thread_caches_are_flushed_on_exit() {
+        let class_stats = || {
+            let stats = ALLOCATOR.stats();
+            *stats.size_classes().find(|&(size, _)| size == 64).unwrap().1
+        };
+        let before = class_stats();
+        let allocated = thread::spawn(|| {
+            // Enough to refill and flush the thread's magazine several times over.
+            let boxes = (0..100_u8).map(|idx| Box::new([idx; 40])).collect::<Vec<_>>();
+            boxes.len()
+        })
+        .join();
+        assert_eq!(allocated, 100);
+        let after = class_stats();
+        assert_eq!(after.allocations, before.allocations + 100);
+        assert_eq!(after.frees, before.frees + 100);
+        assert_eq!(after.bytes_live, before.bytes_live);
+    }
+
+    fn stats_track_large_allocations() {
+        let before = ALLOCATOR.stats().large;
+        let big = Vec::<u8>::with_capacity(3 * 4096);
+        let during = ALLOCATOR.stats().large;
+        assert_eq!(during.live(), before.live() + 1);
+        assert_eq!(during.pages_mapped, before.pages_mapped + 3);
+
+        drop(big);
+        let after = ALLOCATOR.stats().large;
+        assert_eq!(after.live(), before.live());
+        assert_eq!(after.pages_mapped, before.pages_mapped);
+    }
+
+    fn poisoning_fills_memory() {
+        ALLOCATOR.set_poisoning(true);
+        let layout = core::alloc::Layout::new::<[u8; 64]>();
+        // SAFETY: The layout isn't zero-sized.
+        let (ptr, other) = unsafe { (alloc::alloc::alloc(layout), alloc::alloc::alloc(layout)) };
+        assert!(!ptr.is_null() && !other.is_null());
+        // SAFETY: The poisoning initialized the whole allocation.
+        let bytes = unsafe { core::slice::from_raw_parts(ptr, 64) };
+        assert!(bytes.iter().all(|&byte| byte == ALLOC_POISON));
+        // SAFETY: We allocated this with the same layout.
+        unsafe { alloc::alloc::dealloc(ptr, layout) };
+        // SAFETY:
+        // Each test runs in a fresh process, which hasn't used enough blocks of this size to need a
+        // second slab, so `ptr` shares a slab with `other` and it stays mapped. The allocator only
+        // writes to the first word of freed blocks.
+        let freed = unsafe { core::slice::from_raw_parts(ptr.add(8), 56) };
+        assert!(freed.iter().all(|&byte| byte == FREE_POISON));
+        // SAFETY: We allocated this with the same layout.
+        unsafe { alloc::alloc::dealloc(other, layout) };
+        ALLOCATOR.set_poisoning(false);
+    }
+}
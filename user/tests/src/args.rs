@@ -0,0 +1,76 @@
+//! Tests for `userlib::args`.
+
+use alloc::vec::Vec;
+
+use userlib::args::{Arg, Error, Parser};
+
+/// Parse `args` into every argument, taking a value for `-n` and `--count`.
+fn parse<'a>(args: &[&'a str]) -> Result<Vec<(Arg<'a>, Option<&'a str>)>, Error<'a>> {
+    let mut parser = Parser::new(args.iter().copied());
+    let mut parsed = Vec::new();
+    while let Some(arg) = parser.next()? {
+        let value = match arg {
+            Arg::Short('n') | Arg::Long("count") => Some(parser.value()?),
+            _ => None,
+        };
+        parsed.push((arg, value));
+    }
+    Ok(parsed)
+}
+
+userlib::user_test! {
+    fn flags_and_positionals() {
+        assert_eq!(
+            parse(&["-la", "file", "--verbose", "-", "other"]),
+            Ok(alloc::vec![
+                (Arg::Short('l'), None),
+                (Arg::Short('a'), None),
+                (Arg::Value("file"), None),
+                (Arg::Long("verbose"), None),
+                (Arg::Value("-"), None),
+                (Arg::Value("other"), None),
+            ]),
+        );
+    }
+
+    fn option_values() {
+        assert_eq!(
+            parse(&["-n5", "-n", "-6", "-vn7", "--count=8", "--count", "9"]),
+            Ok(alloc::vec![
+                (Arg::Short('n'), Some("5")),
+                (Arg::Short('n'), Some("-6")),
+                (Arg::Short('v'), None),
+                (Arg::Short('n'), Some("7")),
+                (Arg::Long("count"), Some("8")),
+                (Arg::Long("count"), Some("9")),
+            ]),
+        );
+    }
+
+    fn double_dash_ends_options() {
+        assert_eq!(
+            parse(&["-v", "--", "-n", "--count"]),
+            Ok(alloc::vec![
+                (Arg::Short('v'), None),
+                (Arg::Value("-n"), None),
+                (Arg::Value("--count"), None),
+            ]),
+        );
+    }
+
+    fn errors() {
+        assert_eq!(parse(&["-n"]), Err(Error::MissingValue(Arg::Short('n'))));
+        assert_eq!(
+            parse(&["--verbose=yes"]),
+            Err(Error::UnexpectedValue { option: Arg::Long("verbose"), value: "yes" }),
+        );
+        let mut parser = Parser::new(["-n", "five"]);
+        assert_eq!(parser.next(), Ok(Some(Arg::Short('n'))));
+        assert_eq!(
+            parser.parsed_value::<u32>(),
+            Err(Error::InvalidValue { option: Arg::Short('n'), value: "five" }),
+        );
+        assert_eq!(Arg::Short('x').unexpected(), Error::UnexpectedOption(Arg::Short('x')));
+        assert_eq!(Arg::Value("x").unexpected(), Error::UnexpectedArgument("x"));
+    }
+}
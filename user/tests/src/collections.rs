@@ -0,0 +1,24 @@
+//! Tests for `userlib::collections`.
+
+use userlib::collections::{HashMap, HashSet};
+
+userlib::user_test! {
+    fn hash_map_insert_and_get() {
+        let mut map: HashMap<_, _> = HashMap::default();
+        for i in 0..100 {
+            assert_eq!(map.insert(i, i * 2), None);
+        }
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get(&42), Some(&84));
+        assert_eq!(map.insert(42, 0), Some(84));
+        assert_eq!(map.remove(&42), Some(0));
+        assert_eq!(map.get(&42), None);
+    }
+
+    fn hash_set_dedups() {
+        let set: HashSet<_> = [3, 1, 3, 2, 1].into_iter().collect();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&2));
+        assert!(!set.contains(&4));
+    }
+}
@@ -0,0 +1,84 @@
+//! Tests for `userlib::fs` and the I/O helpers built on it.
+
+use userlib::{fs, io::ErrorKind};
+
+userlib::user_test! {
+    fn write_then_read() {
+        fs::write("/test-write-then-read.txt", "Hello, world!").unwrap();
+        let contents = fs::read_to_string("/test-write-then-read.txt").unwrap();
+        fs::remove_file("/test-write-then-read.txt").unwrap();
+        assert_eq!(contents, "Hello, world!");
+    }
+
+    fn copy_between_files() {
+        let contents: alloc::vec::Vec<u8> = (0..2000).map(|i| i as u8).collect();
+        fs::write("/test-copy-from.bin", &contents).unwrap();
+        let copied = {
+            let mut from = fs::File::open("/test-copy-from.bin").unwrap();
+            let mut to = fs::File::create("/test-copy-to.bin").unwrap();
+            userlib::io::copy(&mut from, &mut to).unwrap()
+        };
+        let read_back = fs::read("/test-copy-to.bin").unwrap();
+        fs::remove_file("/test-copy-from.bin").unwrap();
+        fs::remove_file("/test-copy-to.bin").unwrap();
+        assert_eq!(copied, 2000);
+        assert_eq!(read_back, contents);
+    }
+
+    fn redirect_stdout_to_file() {
+        {
+            let file = fs::File::create("/test-redirect.txt").unwrap();
+            let old = userlib::io::replace_stdout(file.as_descriptor()).unwrap();
+            userlib::println!("first");
+            userlib::io::replace_stdout(old.borrow()).unwrap();
+        }
+        {
+            let file = fs::File::append("/test-redirect.txt").unwrap();
+            let old = userlib::io::replace_stdout(file.as_descriptor()).unwrap();
+            userlib::println!("second");
+            userlib::io::replace_stdout(old.borrow()).unwrap();
+        }
+        let contents = fs::read_to_string("/test-redirect.txt").unwrap();
+        fs::remove_file("/test-redirect.txt").unwrap();
+        assert_eq!(contents, "first\nsecond\n");
+    }
+
+    fn new_file_metadata() {
+        fs::write("/test-metadata.txt", "12345").unwrap();
+        let metadata = fs::metadata("/test-metadata.txt").unwrap();
+        fs::remove_file("/test-metadata.txt").unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(metadata.len(), 5);
+        assert_eq!(metadata.hard_links(), 1);
+        assert_eq!(metadata.permissions(), 0o644);
+    }
+
+    fn current_dir_must_be_directory() {
+        fs::write("/test-not-a-dir.txt", "").unwrap();
+        let result = userlib::env::set_current_dir("/test-not-a-dir.txt");
+        fs::remove_file("/test-not-a-dir.txt").unwrap();
+        let Err(e) = result else {
+            panic!("Changed into a file");
+        };
+        assert!(matches!(e.kind(), ErrorKind::InvalidFormat), "{e}");
+        assert_eq!(userlib::env::current_dir().as_str(), "/");
+    }
+
+    fn remove_directories() {
+        fs::create_dir_all("/test-remove/nested").unwrap();
+        fs::write("/test-remove/nested/file.txt", "contents").unwrap();
+        let Err(e) = fs::remove_dir("/test-remove/nested") else {
+            panic!("Removed a directory with a file in it");
+        };
+        assert!(matches!(e.kind(), ErrorKind::DirectoryNotEmpty), "{e}");
+        fs::remove_dir_all("/test-remove").unwrap();
+        assert!(fs::metadata("/test-remove").is_err());
+    }
+
+    fn open_missing_file() {
+        let Err(e) = fs::File::open("/this-file-does-not-exist") else {
+            panic!("Opened a file which doesn't exist");
+        };
+        assert!(matches!(e.kind(), ErrorKind::NotFound), "{e}");
+    }
+}
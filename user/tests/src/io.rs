@@ -0,0 +1,38 @@
+//! Tests for `userlib::io`.
+
+use alloc::{string::String, vec::Vec};
+
+use userlib::io::{ErrorKind, Read as _, Write as _};
+
+userlib::user_test! {
+    fn pipe_carries_bytes() {
+        let (mut reader, mut writer) = userlib::io::pipe().unwrap();
+        writer.write_all(b"through the pipe").unwrap();
+        drop(writer);
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "through the pipe");
+    }
+
+    fn pipe_waits_for_reader() {
+        let (mut reader, mut writer) = userlib::io::pipe().unwrap();
+        let contents: Vec<u8> = (0..20_000).map(|i| i as u8).collect();
+        let handle = {
+            let contents = contents.clone();
+            userlib::thread::spawn(move || writer.write_all(&contents).unwrap())
+        };
+        let mut read_back = Vec::new();
+        reader.read_to_end(&mut read_back).unwrap();
+        let () = handle.join();
+        assert_eq!(read_back, contents);
+    }
+
+    fn pipe_without_reader_is_broken() {
+        let (reader, mut writer) = userlib::io::pipe().unwrap();
+        drop(reader);
+        let Err(e) = writer.write(b"nobody's listening") else {
+            panic!("Wrote to a pipe with no reader");
+        };
+        assert!(matches!(e.kind(), ErrorKind::BrokenPipe), "{e}");
+    }
+}
@@ -0,0 +1,22 @@
+//! Integration tests for userlib, which run as a user program.
+//!
+//! Build this like the shell, put it on the filesystem at `/bin/tests`, and boot with
+//! `init=/bin/tests` to run them (`run.sh` does all of this when `TESTS=1` is set).
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+mod allocator;
+mod args;
+mod collections;
+mod fs;
+mod io;
+mod path;
+mod sync;
+
+#[unsafe(no_mangle)]
+extern "Rust" fn main() {
+    userlib::test::run();
+}
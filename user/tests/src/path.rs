@@ -0,0 +1,17 @@
+//! Tests for `userlib::path`.
+
+use userlib::path::Path;
+
+userlib::user_test! {
+    fn join_and_normalize() {
+        let path = Path::new("/usr/lib").join("../bin/./tests");
+        assert_eq!(path.normalize().as_str(), "/usr/bin/tests");
+    }
+
+    fn parent_and_file_name() {
+        let path = Path::new("/bin/tests");
+        assert_eq!(path.parent().map(Path::as_str), Some("/bin"));
+        assert_eq!(path.file_name(), Some("tests"));
+        assert!(path.is_absolute());
+    }
+}
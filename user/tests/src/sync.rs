@@ -0,0 +1,68 @@
+//! Tests for `userlib::sync` and `userlib::thread`.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use userlib::{
+    sync::{Condvar, Mutex, OnceLock, RwLock},
+    thread,
+};
+
+userlib::user_test! {
+    fn mutex_across_threads() {
+        let counter = Arc::new(Mutex::new(0));
+        let handles: Vec<_> = core::iter::repeat_with(|| {
+            let counter = Arc::clone(&counter);
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    *counter.lock() += 1;
+                }
+            })
+        })
+        .take(4)
+        .collect();
+        for handle in handles {
+            let () = handle.join();
+        }
+        assert_eq!(*counter.lock(), 4000);
+    }
+
+    fn condvar_wakes_waiter() {
+        let state = Arc::new((Mutex::new(false), Condvar::new()));
+        let handle = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                let (ready, condvar) = &*state;
+                *ready.lock() = true;
+                condvar.notify_all();
+            })
+        };
+        let (ready, condvar) = &*state;
+        let guard = condvar.wait_while(ready.lock(), |ready| !*ready);
+        assert!(*guard);
+        drop(guard);
+        let () = handle.join();
+    }
+
+    fn rw_lock_readers_and_writer() {
+        let lock = RwLock::new(1);
+        {
+            let a = lock.read();
+            let b = lock.read();
+            assert_eq!(*a + *b, 2);
+        }
+        *lock.write() = 5;
+        assert_eq!(*lock.read(), 5);
+    }
+
+    fn once_lock_initializes_once() {
+        static CELL: OnceLock<u32> = OnceLock::new();
+        assert_eq!(*CELL.get_or_init(|| 1), 1);
+        assert_eq!(*CELL.get_or_init(|| 2), 1);
+        assert_eq!(CELL.set(3), Err(3));
+    }
+
+    fn join_returns_value() {
+        let handle = thread::spawn(|| 6 * 7);
+        assert_eq!(handle.join(), 42);
+    }
+}
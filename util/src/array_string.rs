@@ -0,0 +1,177 @@
+//! A string with a fixed capacity, which doesn't need an allocator.
+
+use core::{fmt, ops::Deref};
+
+/// A UTF-8 string which stores up to `N` bytes inline.
+///
+/// Pushing more than fits keeps as much as it can (without splitting a character) and reports an
+/// error, so it's easy to build strings which are allowed to be cut short, like log lines.
+///
+/// ```
+/// # use core::fmt::Write;
+/// # use util::array_string::ArrayString;
+/// let mut name = ArrayString::<8>::new();
+/// write!(name, "proc{}", 12).unwrap();
+/// assert_eq!(name.as_str(), "proc12");
+/// assert!(name.push_str("→x").is_err());
+/// assert_eq!(name.as_str(), "proc12", "The arrow doesn't fit, so nothing after it is pushed");
+/// ```
+#[derive(Clone, Copy)]
+pub struct ArrayString<const N: usize> {
+    /// The number of bytes in the string, which are valid UTF-8 at the start of `buf`.
+    len: usize,
+    /// The storage for the string.
+    buf: [u8; N],
+}
+impl<const N: usize> ArrayString<N> {
+    /// Construct a new, empty string.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            len: 0,
+            buf: [0; N],
+        }
+    }
+
+    /// Get the number of bytes the string can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Check whether the string is full.
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Get the string.
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        let (bytes, _) = self.buf.split_at(self.len);
+        // SAFETY:
+        // The first `self.len` bytes are always valid UTF-8.
+        unsafe { core::str::from_utf8_unchecked(bytes) }
+    }
+
+    /// Append a string, truncating it to fit if needed.
+    ///
+    /// If the whole string didn't fit, then as much of it as fits without splitting a character is
+    /// appended, and an error is returned.
+    pub fn push_str(&mut self, s: &str) -> Result<(), CapacityError> {
+        let (s, result) = if s.len() <= N - self.len {
+            (s, Ok(()))
+        } else {
+            (
+                &s[..s.floor_char_boundary(N - self.len)],
+                Err(CapacityError),
+            )
+        };
+        self.buf[self.len..][..s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        result
+    }
+
+    /// Append a character, failing if it doesn't fit.
+    pub fn push(&mut self, c: char) -> Result<(), CapacityError> {
+        if c.len_utf8() > N - self.len {
+            return Err(CapacityError);
+        }
+        self.len += c.encode_utf8(&mut self.buf[self.len..]).len();
+        Ok(())
+    }
+
+    /// Remove the last character of the string, if any, and return it.
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.as_str().chars().next_back()?;
+        self.len -= c.len_utf8();
+        Some(c)
+    }
+
+    /// Shorten the string to `len` bytes.
+    ///
+    /// If the string is at most `len` bytes long, this does nothing.
+    ///
+    /// # Panics
+    /// Panics if `len` doesn't lie on a character boundary.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            assert!(
+                self.as_str().is_char_boundary(len),
+                "Truncating ArrayString in the middle of a character"
+            );
+            self.len = len;
+        }
+    }
+
+    /// Make the string empty.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+impl<const N: usize> Deref for ArrayString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+impl<const N: usize> Default for ArrayString<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<const N: usize> fmt::Write for ArrayString<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s).map_err(|CapacityError| fmt::Error)
+    }
+}
+impl<const N: usize> fmt::Display for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+impl<const N: usize> fmt::Debug for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+impl<const N: usize> PartialEq for ArrayString<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+impl<const N: usize> Eq for ArrayString<N> {}
+impl<const N: usize> PartialEq<str> for ArrayString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+impl<const N: usize> PartialEq<&str> for ArrayString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+/// Copy a string, failing if it doesn't fit.
+impl<const N: usize> TryFrom<&str> for ArrayString<N> {
+    type Error = CapacityError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if value.len() > N {
+            return Err(CapacityError);
+        }
+        let mut this = Self::new();
+        this.push_str(value)?;
+        Ok(this)
+    }
+}
+
+/// An error from an [`ArrayString`] not having room for everything pushed to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError;
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ArrayString capacity exceeded")
+    }
+}
+impl core::error::Error for CapacityError {}
@@ -0,0 +1,267 @@
+//! A vector with a fixed capacity, which doesn't need an allocator.
+
+use core::{
+    mem::MaybeUninit,
+    ops::{Bound, Deref, DerefMut, RangeBounds},
+};
+
+/// A vector which stores up to `N` values of type `T` inline.
+///
+/// ```
+/// # use util::array_vec::ArrayVec;
+/// let mut vec = ArrayVec::<u32, 4>::new();
+/// vec.push(1);
+/// vec.push(2);
+/// vec.push(3);
+/// assert_eq!(vec.try_push(4), Ok(()));
+/// assert_eq!(vec.try_push(5), Err(5));
+/// assert_eq!(vec.pop(), Some(4));
+/// assert_eq!(vec.drain(..2).collect::<Vec<_>>(), [1, 2]);
+/// assert_eq!(*vec, [3]);
+/// ```
+pub struct ArrayVec<T, const N: usize> {
+    /// The number of values in the vector, which are initialized at the start of `buf`.
+    len: usize,
+    /// The storage for the values.
+    buf: [MaybeUninit<T>; N],
+}
+impl<T, const N: usize> ArrayVec<T, N> {
+    /// Construct a new, empty vector.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            len: 0,
+            buf: [const { MaybeUninit::uninit() }; N],
+        }
+    }
+
+    /// Get the number of values the vector can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Check whether the vector is full.
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Add a value to the end of the vector.
+    ///
+    /// # Panics
+    /// Panics if the vector is full. See [`Self::try_push`] for a version which doesn't panic.
+    #[track_caller]
+    pub fn push(&mut self, value: T) {
+        assert!(
+            self.try_push(value).is_ok(),
+            "ArrayVec capacity of {N} exceeded"
+        );
+    }
+
+    /// Add a value to the end of the vector, or give it back if the vector is full.
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        self.buf[self.len].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove the last value of the vector, if any, and return it.
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        // SAFETY:
+        // The value was initialized, and it isn't anymore as far as `self.len` is concerned.
+        Some(unsafe { self.buf[self.len].assume_init_read() })
+    }
+
+    /// Drop every value past the first `len`.
+    ///
+    /// If the vector has at most `len` values, this does nothing.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail: *mut [T] = &raw mut self[len..];
+        self.len = len;
+        // SAFETY:
+        // The values were initialized, and they aren't anymore as far as `self.len` is concerned.
+        unsafe { tail.drop_in_place() };
+    }
+
+    /// Drop every value.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Get a slice of the values.
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: The first `self.len` values are initialized.
+        unsafe { self.buf[..self.len].assume_init_ref() }
+    }
+
+    /// Get a mutable slice of the values.
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: The first `self.len` values are initialized.
+        unsafe { self.buf[..self.len].assume_init_mut() }
+    }
+
+    /// Remove the values in `range` from the vector, returning an iterator over them.
+    ///
+    /// The values are removed even if the iterator isn't fully consumed. If the iterator is
+    /// leaked, then the vector may lose more values than the range.
+    ///
+    /// # Panics
+    /// Panics if `range` is out of bounds.
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Drain<'_, T, N> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "Range {start}..{end} out of bounds for ArrayVec of length {}",
+            self.len
+        );
+        let tail_len = self.len - end;
+        // Forget the drained values and the tail until the `Drain` puts the tail back.
+        self.len = start;
+        Drain {
+            vec: self,
+            next: start,
+            end,
+            tail_start: end,
+            tail_len,
+        }
+    }
+}
+impl<T, const N: usize> Deref for ArrayVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+impl<T, const N: usize> DerefMut for ArrayVec<T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+impl<T, const N: usize> Default for ArrayVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: Clone, const N: usize> Clone for ArrayVec<T, N> {
+    fn clone(&self) -> Self {
+        let mut new = Self::new();
+        for value in self {
+            new.push(value.clone());
+        }
+        new
+    }
+}
+impl<T: core::fmt::Debug, const N: usize> core::fmt::Debug for ArrayVec<T, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
+impl<T: PartialEq, const N: usize> PartialEq for ArrayVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+impl<T: Eq, const N: usize> Eq for ArrayVec<T, N> {}
+impl<T, const N: usize> Drop for ArrayVec<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+impl<'a, T, const N: usize> IntoIterator for &'a ArrayVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+impl<'a, T, const N: usize> IntoIterator for &'a mut ArrayVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = core::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator which removes values from an [`ArrayVec`].
+///
+/// This value is constructed by calling [`ArrayVec::drain`].
+pub struct Drain<'a, T, const N: usize> {
+    /// The vector being drained, whose length is the start of the drained range.
+    vec: &'a mut ArrayVec<T, N>,
+    /// The index of the next value to yield from the front.
+    next: usize,
+    /// The index after the next value to yield from the back.
+    end: usize,
+    /// The index of the first value after the drained range.
+    tail_start: usize,
+    /// The number of values after the drained range, which are moved back on drop.
+    tail_len: usize,
+}
+impl<T, const N: usize> Iterator for Drain<'_, T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.end {
+            return None;
+        }
+        self.next += 1;
+        // SAFETY:
+        // Values in `next..end` are initialized, and we only read each one once.
+        Some(unsafe { self.vec.buf[self.next - 1].assume_init_read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.next;
+        (len, Some(len))
+    }
+}
+impl<T, const N: usize> DoubleEndedIterator for Drain<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.next == self.end {
+            return None;
+        }
+        self.end -= 1;
+        // SAFETY:
+        // Values in `next..end` are initialized, and we only read each one once.
+        Some(unsafe { self.vec.buf[self.end].assume_init_read() })
+    }
+}
+impl<T, const N: usize> ExactSizeIterator for Drain<'_, T, N> {}
+impl<T, const N: usize> Drop for Drain<'_, T, N> {
+    fn drop(&mut self) {
+        let rest: *mut [MaybeUninit<T>] = &raw mut self.vec.buf[self.next..self.end];
+        // SAFETY:
+        // Values in `next..end` are initialized, and haven't been yielded.
+        unsafe { (rest as *mut [T]).drop_in_place() };
+        let start = self.vec.len;
+        let buf = self.vec.buf.as_mut_ptr();
+        // SAFETY:
+        // Both ranges are in bounds, and the tail values are initialized. After the copy, the
+        // values at the start of the tail are initialized, and only the moved copies are counted
+        // by `len`.
+        unsafe { core::ptr::copy(buf.add(self.tail_start), buf.add(start), self.tail_len) };
+        self.vec.len = start + self.tail_len;
+    }
+}
@@ -0,0 +1,201 @@
+//! Bitmaps for tracking which of a set of resources are in use.
+//!
+//! Bit `i` of a bitmap is bit `i % W::BITS` (counting from the least-significant bit) of word
+//! `i / W::BITS`, so a `Bitmap<u8>` matches the on-disk layout of ext2's block and inode bitmaps.
+//!
+//! ```
+//! # use util::bitmap::Bitmap;
+//! let mut words = [0_u32; 2];
+//! let bitmap = Bitmap::from_mut(&mut words);
+//! bitmap.set_range(0..35);
+//! assert_eq!(bitmap.find_first_zero(), Some(35));
+//! bitmap.clear_range(3..5);
+//! assert_eq!(bitmap.find_first_zero(), Some(3));
+//! assert_eq!(bitmap.count_ones(), 33);
+//! assert_eq!(words, [!0b11000, 0b111]);
+//! ```
+
+use core::ops::{BitAnd, BitOr, Not, Range, Shl, Shr};
+
+/// A word type which a [`Bitmap`] can be made of.
+pub trait BitmapWord:
+    Copy
+    + Eq
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    /// The number of bits in the word.
+    const BITS: u32;
+    /// The word with no bits set.
+    const ZERO: Self;
+    /// The word with only the least-significant bit set.
+    const ONE: Self;
+
+    /// Count the number of set bits.
+    fn count_ones(self) -> u32;
+
+    /// Count the number of set bits before the first clear one, starting from the
+    /// least-significant bit.
+    fn trailing_ones(self) -> u32;
+
+    /// Count the number of clear bits before the first set one, starting from the
+    /// least-significant bit.
+    fn trailing_zeros(self) -> u32;
+}
+
+/// Implement [`BitmapWord`] for the given primitive integer types.
+macro_rules! impl_bitmap_word {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl BitmapWord for $ty {
+                const BITS: u32 = <$ty>::BITS;
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+
+                fn count_ones(self) -> u32 {
+                    <$ty>::count_ones(self)
+                }
+
+                fn trailing_ones(self) -> u32 {
+                    <$ty>::trailing_ones(self)
+                }
+
+                fn trailing_zeros(self) -> u32 {
+                    <$ty>::trailing_zeros(self)
+                }
+            }
+        )*
+    };
+}
+impl_bitmap_word!(u8, u16, u32, u64, usize);
+
+/// A bitmap backed by a slice of words.
+///
+/// This is an unsized type, like [`str`], which is used behind a reference made by
+/// [`Self::from_slice`] or [`Self::from_mut`].
+#[repr(transparent)]
+pub struct Bitmap<W>([W]);
+impl<W: BitmapWord> Bitmap<W> {
+    /// View a slice of words as a bitmap.
+    pub const fn from_slice(words: &[W]) -> &Self {
+        // SAFETY: We're `repr(transparent)` so converting reference types is sound.
+        unsafe { &*(core::ptr::from_ref(words) as *const Self) }
+    }
+
+    /// View a mutable slice of words as a mutable bitmap.
+    pub const fn from_mut(words: &mut [W]) -> &mut Self {
+        // SAFETY: We're `repr(transparent)` so converting reference types is sound.
+        unsafe { &mut *(core::ptr::from_mut(words) as *mut Self) }
+    }
+
+    /// Get the backing words.
+    pub const fn as_words(&self) -> &[W] {
+        &self.0
+    }
+
+    /// Get the number of bits in the bitmap.
+    pub const fn len(&self) -> usize {
+        self.0.len() * W::BITS as usize
+    }
+
+    /// Check whether the bitmap has no bits at all.
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the value of the bit at `idx`.
+    ///
+    /// # Panics
+    /// Panics if `idx` is out of bounds.
+    pub fn get(&self, idx: usize) -> bool {
+        let (word, mask) = Self::locate(idx);
+        self.0[word] & mask != W::ZERO
+    }
+
+    /// Set the bit at `idx` to `value`, returning the previous value.
+    ///
+    /// # Panics
+    /// Panics if `idx` is out of bounds.
+    pub fn set(&mut self, idx: usize, value: bool) -> bool {
+        let (word, mask) = Self::locate(idx);
+        let word = &mut self.0[word];
+        let old_value = *word & mask != W::ZERO;
+        *word = if value { *word | mask } else { *word & !mask };
+        old_value
+    }
+
+    /// Find the index of the first clear bit, if any.
+    pub fn find_first_zero(&self) -> Option<usize> {
+        let word_idx = self.0.iter().position(|&word| word != !W::ZERO)?;
+        Some(word_idx * W::BITS as usize + self.0[word_idx].trailing_ones() as usize)
+    }
+
+    /// Find the index of the first set bit, if any.
+    pub fn find_first_one(&self) -> Option<usize> {
+        let word_idx = self.0.iter().position(|&word| word != W::ZERO)?;
+        Some(word_idx * W::BITS as usize + self.0[word_idx].trailing_zeros() as usize)
+    }
+
+    /// Set every bit in `range`.
+    ///
+    /// # Panics
+    /// Panics if `range` is out of bounds.
+    pub fn set_range(&mut self, range: Range<usize>) {
+        self.update_range(range, |word, mask| word | mask);
+    }
+
+    /// Clear every bit in `range`.
+    ///
+    /// # Panics
+    /// Panics if `range` is out of bounds.
+    pub fn clear_range(&mut self, range: Range<usize>) {
+        self.update_range(range, |word, mask| word & !mask);
+    }
+
+    /// Count the number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.0.iter().map(|&word| word.count_ones() as usize).sum()
+    }
+
+    /// Get the index of the word containing bit `idx`, and the mask for the bit in that word.
+    fn locate(idx: usize) -> (usize, W) {
+        let bits = W::BITS as usize;
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "The remainder is less than `W::BITS`"
+        )]
+        let bit = (idx % bits) as u32;
+        (idx / bits, W::ONE << bit)
+    }
+
+    /// Apply `f` to each word overlapping `range`, along with a mask of the bits in the range.
+    fn update_range(&mut self, range: Range<usize>, f: impl Fn(W, W) -> W) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "Range {range:?} out of bounds for bitmap of length {}",
+            self.len()
+        );
+        let bits = W::BITS as usize;
+        let mut start = range.start;
+        while start < range.end {
+            let word = &mut self.0[start / bits];
+            let low = start % bits;
+            let high = bits.min(low + (range.end - start));
+            #[expect(
+                clippy::cast_possible_truncation,
+                reason = "Both bounds are at most `W::BITS`"
+            )]
+            let mask = (!W::ZERO << low as u32) & (!W::ZERO >> (bits - high) as u32);
+            *word = f(*word, mask);
+            start += high - low;
+        }
+    }
+}
+impl<W: core::fmt::Debug> core::fmt::Debug for Bitmap<W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Bitmap").field(&&self.0).finish()
+    }
+}
@@ -1,7 +1,7 @@
 //! Cell types.
 
 use crate::sync::atomic::Atomic;
-use core::{cell::UnsafeCell, mem::MaybeUninit};
+use core::{cell::UnsafeCell, mem::MaybeUninit, sync::atomic::AtomicU32};
 
 /// A wrapper for [`UnsafeCell`] which is also [`Sync`].
 ///
@@ -119,6 +119,212 @@ unsafe impl<T: ?Sized> Sync for SyncUnsafeCell<T> {}
 // SAFETY: Safe construction only permits `Send` values.
 unsafe impl<T: ?Sized> Send for SyncUnsafeCell<T> {}
 
+/// The value of a [`CheckedCell`]'s borrow count while it's mutably borrowed.
+const MUT_BORROWED: u32 = u32::MAX;
+
+/// A cell which checks Rust's borrowing rules at runtime, and which is also [`Sync`].
+///
+/// This is like [`core::cell::RefCell`], but the borrow count is atomic, so it can be put in
+/// static memory and shared between harts. It's meant for values which the surrounding code
+/// already arranges to have exclusive access to (where a [`SyncUnsafeCell`] would otherwise be
+/// used), so that a mistake in that arrangement causes a panic instead of undefined behavior. It
+/// isn't a lock: conflicting borrows fail instead of waiting.
+///
+/// ```
+/// # use util::cell::CheckedCell;
+/// let cell = CheckedCell::new(5);
+/// {
+///     let first = cell.borrow();
+///     let second = cell.borrow();
+///     assert_eq!(*first + *second, 10);
+///     assert!(cell.try_borrow_mut().is_err());
+/// }
+/// *cell.borrow_mut() += 1;
+/// assert_eq!(*cell.borrow(), 6);
+/// ```
+pub struct CheckedCell<T: ?Sized> {
+    /// The number of shared borrows, or [`MUT_BORROWED`] if it's mutably borrowed.
+    borrows: AtomicU32,
+    /// The inner value.
+    value: UnsafeCell<T>,
+}
+impl<T> CheckedCell<T> {
+    /// Construct a new cell containing the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            borrows: AtomicU32::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Convert back into the original value.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+impl<T: ?Sized> CheckedCell<T> {
+    /// Borrow the value immutably.
+    ///
+    /// # Panics
+    /// Panics if the value is currently mutably borrowed. See [`Self::try_borrow`] for a version
+    /// which doesn't panic.
+    #[track_caller]
+    pub fn borrow(&self) -> CheckedRef<'_, T> {
+        match self.try_borrow() {
+            Ok(borrow) => borrow,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Attempt to borrow the value immutably, failing if it's currently mutably borrowed.
+    pub fn try_borrow(&self) -> Result<CheckedRef<'_, T>, BorrowError> {
+        let mut borrows = self.borrows.load(core::sync::atomic::Ordering::Relaxed);
+        loop {
+            if borrows >= MUT_BORROWED - 1 {
+                return Err(BorrowError);
+            }
+            match self.borrows.compare_exchange_weak(
+                borrows,
+                borrows + 1,
+                core::sync::atomic::Ordering::Acquire,
+                core::sync::atomic::Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(CheckedRef { cell: self }),
+                Err(new_borrows) => borrows = new_borrows,
+            }
+        }
+    }
+
+    /// Borrow the value mutably.
+    ///
+    /// # Panics
+    /// Panics if the value is currently borrowed. See [`Self::try_borrow_mut`] for a version
+    /// which doesn't panic.
+    #[track_caller]
+    pub fn borrow_mut(&self) -> CheckedRefMut<'_, T> {
+        match self.try_borrow_mut() {
+            Ok(borrow) => borrow,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Attempt to borrow the value mutably, failing if it's currently borrowed.
+    pub fn try_borrow_mut(&self) -> Result<CheckedRefMut<'_, T>, BorrowMutError> {
+        self.borrows
+            .compare_exchange(
+                0,
+                MUT_BORROWED,
+                core::sync::atomic::Ordering::Acquire,
+                core::sync::atomic::Ordering::Relaxed,
+            )
+            .map(|_| CheckedRefMut { cell: self })
+            .map_err(|_| BorrowMutError)
+    }
+
+    /// Get an exclusive reference to the inner value from an exclusive reference to the cell.
+    ///
+    /// This function does not have to check because the exclusive reference to the cell means it
+    /// cannot be borrowed anywhere else.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+impl<T: Default> Default for CheckedCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<T: ?Sized + core::fmt::Debug> core::fmt::Debug for CheckedCell<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut f = f.debug_struct("CheckedCell");
+        match self.try_borrow() {
+            Ok(value) => f.field("value", &&*value),
+            Err(_) => f.field("value", &format_args!("<borrowed>")),
+        };
+        f.finish()
+    }
+}
+
+// UnsafeCell implements `Send` as appropriate, so we only need `Sync`.
+
+// SAFETY:
+// Shared borrows on different threads share the value, so it must be `Sync`, and mutable borrows
+// correspond to sending the value to whichever thread borrows, so it must be `Send`.
+unsafe impl<T: ?Sized + Send + Sync> Sync for CheckedCell<T> {}
+
+/// A shared borrow of the value in a [`CheckedCell`].
+///
+/// This value is constructed by calling [`CheckedCell::borrow`] and related methods.
+pub struct CheckedRef<'a, T: ?Sized> {
+    /// The cell which this borrows.
+    cell: &'a CheckedCell<T>,
+}
+impl<T: ?Sized> core::ops::Deref for CheckedRef<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // We hold a shared borrow, so nobody has exclusive access.
+        unsafe { &*self.cell.value.get() }
+    }
+}
+impl<T: ?Sized> Drop for CheckedRef<'_, T> {
+    fn drop(&mut self) {
+        self.cell
+            .borrows
+            .fetch_sub(1, core::sync::atomic::Ordering::Release);
+    }
+}
+
+/// An exclusive borrow of the value in a [`CheckedCell`].
+///
+/// This value is constructed by calling [`CheckedCell::borrow_mut`] and related methods.
+pub struct CheckedRefMut<'a, T: ?Sized> {
+    /// The cell which this borrows.
+    cell: &'a CheckedCell<T>,
+}
+impl<T: ?Sized> core::ops::Deref for CheckedRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // We hold the exclusive borrow, so we have exclusive access.
+        unsafe { &*self.cell.value.get() }
+    }
+}
+impl<T: ?Sized> core::ops::DerefMut for CheckedRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // We hold the exclusive borrow, so we have exclusive access.
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+impl<T: ?Sized> Drop for CheckedRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.cell
+            .borrows
+            .store(0, core::sync::atomic::Ordering::Release);
+    }
+}
+
+/// An error from [`CheckedCell::try_borrow`], because the value is already mutably borrowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowError;
+impl core::fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("value is already mutably borrowed")
+    }
+}
+impl core::error::Error for BorrowError {}
+
+/// An error from [`CheckedCell::try_borrow_mut`], because the value is already borrowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowMutError;
+impl core::fmt::Display for BorrowMutError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("value is already borrowed")
+    }
+}
+impl core::error::Error for BorrowMutError {}
+
 /// A locked value which can only be written to once.
 pub struct OnceLock<T> {
     /// Flags indicating the inner state.
@@ -140,7 +346,7 @@ impl<T> OnceLock<T> {
     pub fn get(&self) -> Option<&T> {
         self.flags
             .load(core::sync::atomic::Ordering::Acquire)
-            .locked()
+            .initialized()
             .then(|| {
                 // SAFETY:
                 // Because `self.initialized` is set, no more exclusive access can exist.
@@ -171,6 +377,59 @@ impl<T> OnceLock<T> {
         );
         Ok(())
     }
+
+    /// Get the value, initializing it with `f` if it hasn't been yet.
+    ///
+    /// If someone else is initializing the value, then this spins until they finish. Calling this
+    /// again from inside `f` will never return.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        match self.get_or_try_init(|| Ok::<T, core::convert::Infallible>(f())) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Get the value, initializing it with `f` if it hasn't been yet.
+    ///
+    /// If `f` fails, then the error is returned and the value is left uninitialized, so a later
+    /// call can try again, which is also the case if `f` panics. If someone else is initializing
+    /// the value, then this spins until they finish. Calling this again from inside `f` will never
+    /// return.
+    pub fn get_or_try_init<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+        loop {
+            let flags = self
+                .flags
+                .fetch_or(OnceLockFlags::LOCKED, core::sync::atomic::Ordering::Acquire);
+            if !flags.locked() {
+                break;
+            }
+            // Someone else has the lock, so wait until they either finish or give up.
+            loop {
+                let flags = self.flags.load(core::sync::atomic::Ordering::Acquire);
+                if flags.initialized() {
+                    // SAFETY:
+                    // Because `self.initialized` is set, no more exclusive access can exist, and
+                    // the value must be initialized.
+                    return Ok(unsafe { (*self.value.get()).assume_init_ref() });
+                }
+                if !flags.locked() {
+                    break;
+                }
+                core::hint::spin_loop();
+            }
+        }
+        let guard = OnceLockInitGuard { flags: &self.flags };
+        let value = f()?;
+        core::mem::forget(guard);
+        // SAFETY:
+        // Because we set `self.locked`, we have exclusive access until we mark `self.initialized`.
+        let value = unsafe { &mut *self.value.get() }.write(value);
+        self.flags.fetch_or(
+            OnceLockFlags::INITIALIZED,
+            core::sync::atomic::Ordering::Release,
+        );
+        Ok(value)
+    }
 }
 impl<T> Default for OnceLock<T> {
     fn default() -> Self {
@@ -186,12 +445,97 @@ impl<T> From<T> for OnceLock<T> {
         }
     }
 }
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if self.flags.get_mut().initialized() {
+            // SAFETY:
+            // The value is initialized, and we're never going to use it again.
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
 // SAFETY:
 // A `OnceLock<T>` is equivalent to a `T`.
 unsafe impl<T: Send> Send for OnceLock<T> {}
 // SAFETY:
-// A `OnceLock<T>` is equivalent to a `T`.
-unsafe impl<T: Sync> Sync for OnceLock<T> {}
+// Any thread can initialize the value, which amounts to sending it to every other thread, and then
+// every thread can share it.
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+
+/// Unlocks a [`OnceLock`] when dropped, so that an initializer which fails or panics lets a later
+/// one try again.
+///
+/// This is forgotten once the initializer succeeds.
+struct OnceLockInitGuard<'a> {
+    /// The flags of the lock being initialized.
+    flags: &'a Atomic<OnceLockFlags>,
+}
+impl Drop for OnceLockInitGuard<'_> {
+    fn drop(&mut self) {
+        self.flags.store(
+            OnceLockFlags::empty(),
+            core::sync::atomic::Ordering::Release,
+        );
+    }
+}
+
+/// A value which is initialized the first time it's accessed.
+///
+/// If several threads access the value at once, one of them runs the initialization function and
+/// the rest spin until it's done.
+///
+/// Note that the default value for `F` is a function pointer, which requires the function to not
+/// be a closure that captures values. Most other meaningful types aren't nameable, so they can't
+/// be used in a `static` variable.
+pub struct LazyLock<T, F = fn() -> T> {
+    /// The value, once it's been initialized.
+    value: OnceLock<T>,
+    /// The function to initialize the value, until it's been called.
+    init_func: UnsafeCell<Option<F>>,
+}
+impl<T, F: FnOnce() -> T> LazyLock<T, F> {
+    /// Construct a new [`LazyLock`] that will call the given function to initialize.
+    pub const fn new(f: F) -> Self {
+        Self {
+            value: OnceLock::new(),
+            init_func: UnsafeCell::new(Some(f)),
+        }
+    }
+
+    /// Force the value to be initialized, and get a reference to it.
+    ///
+    /// Forcing the value again from inside the initialization function (including from a trap
+    /// taken while it's running) will never return. If the initialization function panics, then
+    /// forcing the value again panics too.
+    pub fn force(this: &Self) -> &T {
+        this.value.get_or_init(|| {
+            // SAFETY:
+            // `OnceLock` only runs one initializer at a time, and only this one touches
+            // `init_func`.
+            let init_func = unsafe { &mut *this.init_func.get() }.take();
+            init_func.expect("The initialization function panicked")()
+        })
+    }
+}
+impl<T, F: FnOnce() -> T> core::ops::Deref for LazyLock<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        Self::force(self)
+    }
+}
+impl<T: Default> Default for LazyLock<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+// UnsafeCell implements `Send` as appropriate, so we only need `Sync`.
+
+// SAFETY:
+// The initialization function is only ever run by one thread, which amounts to sending it there,
+// and then the value is shared as with a `OnceLock<T>`.
+unsafe impl<T: Send + Sync, F: Send> Sync for LazyLock<T, F> {}
 
 bitset::bitset!(
     /// Flags for the state of a `OnceLock`.
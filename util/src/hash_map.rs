@@ -0,0 +1,232 @@
+//! A fixed-capacity hash map, which doesn't need an allocator.
+//!
+//! The map uses open addressing with linear probing, and removals shift later entries back
+//! instead of leaving tombstones, so lookups never slow down as entries come and go.
+//!
+//! There's no default hasher, since there's no random number generator to key one with, so the
+//! caller has to choose one. If the keys might be chosen by someone hostile, that should be a
+//! keyed hasher.
+
+use core::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+/// A hash map which stores up to `N` entries inline, hashing keys with `S`.
+///
+/// ```
+/// # use std::hash::RandomState;
+/// # use util::hash_map::HashMap;
+/// let mut map = HashMap::<&str, u32, _, 4>::with_hasher(RandomState::new());
+/// assert_eq!(map.insert("one", 1), Ok(None));
+/// assert_eq!(map.insert("two", 2), Ok(None));
+/// assert_eq!(map.insert("one", 3), Ok(Some(1)));
+/// assert_eq!(map.get("one"), Some(&3));
+/// assert_eq!(map.remove("two"), Some(2));
+/// assert_eq!(map.len(), 1);
+/// ```
+pub struct HashMap<K, V, S, const N: usize> {
+    /// The slots for the entries.
+    ///
+    /// Each entry is in the first empty slot at or after the slot its hash picks (wrapping
+    /// around), so there are no empty slots between those two.
+    slots: [Option<(K, V)>; N],
+    /// The number of entries in the map.
+    len: usize,
+    /// The hasher for the keys.
+    hasher: S,
+}
+impl<K, V, S, const N: usize> HashMap<K, V, S, N> {
+    /// Check that the capacity isn't zero, which would leave nowhere for keys to hash to.
+    const NONZERO_CAPACITY: () = assert!(N > 0, "A `HashMap` must have a nonzero capacity");
+
+    /// Construct a new, empty map which hashes keys with `hasher`.
+    pub const fn with_hasher(hasher: S) -> Self {
+        let () = Self::NONZERO_CAPACITY;
+        Self {
+            slots: [const { None }; N],
+            len: 0,
+            hasher,
+        }
+    }
+
+    /// Get the number of entries the map can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Get the number of entries in the map.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the map is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check whether the map is full.
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Get the map's hasher.
+    #[must_use]
+    pub const fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Iterate over the entries of the map, in an unspecified order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|(key, value)| (key, value)))
+    }
+
+    /// Iterate over the entries of the map with mutable access to the values, in an unspecified
+    /// order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.as_mut().map(|(key, value)| (&*key, value)))
+    }
+
+    /// Iterate over the keys of the map, in an unspecified order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Iterate over the values of the map, in an unspecified order.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Remove every entry.
+    pub fn clear(&mut self) {
+        self.slots.fill_with(|| None);
+        self.len = 0;
+    }
+}
+impl<K: Hash + Eq, V, S: BuildHasher, const N: usize> HashMap<K, V, S, N> {
+    /// Get the value for `key`, if it's in the map.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find(key).ok()?;
+        self.slots[idx].as_ref().map(|(_, value)| value)
+    }
+
+    /// Get mutable access to the value for `key`, if it's in the map.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find(key).ok()?;
+        self.slots[idx].as_mut().map(|(_, value)| value)
+    }
+
+    /// Check whether `key` is in the map.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_ok()
+    }
+
+    /// Insert a value for `key`, returning the value it replaced, if any.
+    ///
+    /// If `key` isn't in the map and the map is full, the key and value are given back.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        match self.find(&key) {
+            Ok(idx) => {
+                let (_, old_value) = self.slots[idx]
+                    .as_mut()
+                    .expect("`find` returns occupied slots");
+                Ok(Some(core::mem::replace(old_value, value)))
+            }
+            Err(Some(idx)) => {
+                self.slots[idx] = Some((key, value));
+                self.len += 1;
+                Ok(None)
+            }
+            Err(None) => Err((key, value)),
+        }
+    }
+
+    /// Remove `key` from the map, returning its value if it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut hole = self.find(key).ok()?;
+        let (_, value) = self.slots[hole].take()?;
+        self.len -= 1;
+        // Shift back any later entries which would have gone in the hole, so there's still no gap
+        // between any entry and the slot its hash picks.
+        let mut idx = hole;
+        loop {
+            idx = (idx + 1) % N;
+            let Some((key, _)) = &self.slots[idx] else {
+                break;
+            };
+            let key_slot = self.home(key);
+            if (idx + N - key_slot) % N >= (idx + N - hole) % N {
+                self.slots[hole] = self.slots[idx].take();
+                hole = idx;
+            }
+        }
+        Some(value)
+    }
+
+    /// Get the slot which `key` hashes to.
+    fn home<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "The remainder is less than `N`, which is a `usize`"
+        )]
+        let idx = (self.hasher.hash_one(key) % N as u64) as usize;
+        idx
+    }
+
+    /// Find the slot holding `key`.
+    ///
+    /// If `key` isn't in the map, then this returns the empty slot it would go in, or `None` if
+    /// the map is full.
+    fn find<Q>(&self, key: &Q) -> Result<usize, Option<usize>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let home = self.home(key);
+        for offset in 0..N {
+            let idx = (home + offset) % N;
+            match &self.slots[idx] {
+                Some((slot_key, _)) if slot_key.borrow() == key => return Ok(idx),
+                Some(_) => {}
+                None => return Err(Some(idx)),
+            }
+        }
+        Err(None)
+    }
+}
+impl<K, V, S: Default, const N: usize> Default for HashMap<K, V, S, N> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+impl<K: core::fmt::Debug, V: core::fmt::Debug, S, const N: usize> core::fmt::Debug
+    for HashMap<K, V, S, N>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -1,6 +1,16 @@
 //! Some utilities which are generally useful.
 
 #![no_std]
+#![cfg_attr(
+    all(feature = "atomic128", target_has_atomic = "128"),
+    feature(integer_atomics)
+)]
 
+pub mod array_string;
+pub mod array_vec;
+pub mod bitmap;
 pub mod cell;
+pub mod hash_map;
+pub mod list;
+pub mod mmio;
 pub mod sync;
@@ -0,0 +1,409 @@
+//! An intrusive doubly-linked list.
+//!
+//! The links live inside the elements (in a [`Link`] field), so putting an element in a list
+//! doesn't need any allocation, and an element can be removed in constant time given just a
+//! pointer to it. The list doesn't own its elements: whoever puts an element in a list has to
+//! keep it alive and in place until it's taken back out, which is why insertion is `unsafe`.
+//!
+//! ```
+//! # use core::{mem::offset_of, ptr::NonNull};
+//! # use util::list::{Link, Linked, List};
+//! struct Task {
+//!     id: u32,
+//!     link: Link,
+//! }
+//! // SAFETY: `link` is a `Link` field of `Task`.
+//! unsafe impl Linked for Task {
+//!     const LINK_OFFSET: usize = offset_of!(Task, link);
+//! }
+//!
+//! let mut tasks = [1, 2, 3].map(|id| Task { id, link: Link::new() });
+//! let mut list = List::<Task>::new();
+//! for task in &mut tasks {
+//!     // SAFETY: `tasks` outlives `list`, and isn't otherwise used while `list` is.
+//!     unsafe { list.push_back(NonNull::from(task)) };
+//! }
+//! assert_eq!(list.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 2, 3]);
+//!
+//! let mut cursor = list.cursor_front_mut();
+//! cursor.move_next();
+//! assert_eq!(cursor.current().map(|task| task.id), Some(2));
+//! cursor.remove_current();
+//! assert_eq!(list.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 3]);
+//! ```
+
+use core::{cell::Cell, marker::PhantomData, ptr::NonNull};
+
+/// The links which an element of a [`List`] embeds.
+///
+/// This must be a field of the element type, and the type must implement [`Linked`] to say where
+/// it is.
+#[derive(Default)]
+pub struct Link {
+    /// The next element's link, or `None` if this is the last element.
+    next: Cell<Option<NonNull<Link>>>,
+    /// The previous element's link, or `None` if this is the first element.
+    prev: Cell<Option<NonNull<Link>>>,
+}
+impl Link {
+    /// Construct a new link, for an element which isn't in a list.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            next: Cell::new(None),
+            prev: Cell::new(None),
+        }
+    }
+}
+impl core::fmt::Debug for Link {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // The neighbours might be changing under us, so don't try to show them.
+        f.debug_struct("Link").finish_non_exhaustive()
+    }
+}
+
+// SAFETY:
+// The fields are only ever accessed by a `List` (through `&mut List`) which contains the element,
+// so whoever can access them has exclusive access to them, and they carry no data of their own.
+unsafe impl Send for Link {}
+// SAFETY: See above.
+unsafe impl Sync for Link {}
+
+/// A type which can be put in a [`List`].
+///
+/// # Safety
+/// [`Self::LINK_OFFSET`] must be the offset of a [`Link`] field in `Self`, as given by
+/// [`core::mem::offset_of!`].
+pub unsafe trait Linked {
+    /// The offset of the [`Link`] field within `Self`.
+    const LINK_OFFSET: usize;
+}
+
+/// Get the link from a pointer to an element.
+fn link_of<T: Linked>(node: NonNull<T>) -> NonNull<Link> {
+    // SAFETY:
+    // `Linked` promises that there's a `Link` at this offset, so it's in bounds.
+    unsafe { node.byte_add(T::LINK_OFFSET).cast() }
+}
+
+/// Get the element from a pointer to its link.
+///
+/// # Safety
+/// `link` must have come from [`link_of`].
+unsafe fn node_of<T: Linked>(link: NonNull<Link>) -> NonNull<T> {
+    // SAFETY:
+    // The caller ensures that this link is `LINK_OFFSET` bytes into a `T`.
+    unsafe { link.byte_sub(T::LINK_OFFSET).cast() }
+}
+
+/// An intrusive doubly-linked list of `T`s.
+///
+/// See the [module documentation](self) for more details. Dropping the list doesn't do anything to
+/// its elements.
+pub struct List<T: Linked> {
+    /// The first element's link.
+    head: Option<NonNull<Link>>,
+    /// The last element's link.
+    tail: Option<NonNull<Link>>,
+    /// The number of elements in the list.
+    len: usize,
+    /// The list acts like it holds references to its elements.
+    _marker: PhantomData<*const T>,
+}
+impl<T: Linked> List<T> {
+    /// Construct a new, empty list.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            head: None,
+            tail: None,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Check whether the list is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Get the number of elements in the list.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the first element, if any.
+    #[must_use]
+    pub fn front(&self) -> Option<&T> {
+        // SAFETY:
+        // Elements stay valid to share while they're in the list.
+        self.head.map(|link| unsafe { node_of(link).as_ref() })
+    }
+
+    /// Get the last element, if any.
+    #[must_use]
+    pub fn back(&self) -> Option<&T> {
+        // SAFETY:
+        // Elements stay valid to share while they're in the list.
+        self.tail.map(|link| unsafe { node_of(link).as_ref() })
+    }
+
+    /// Add an element to the front of the list.
+    ///
+    /// # Safety
+    /// `node` must not already be in a list, and it must stay valid (not moved or dropped) and
+    /// valid to share until it's removed from this list.
+    pub unsafe fn push_front(&mut self, node: NonNull<T>) {
+        // SAFETY: The caller upholds the requirements.
+        unsafe { self.insert_after(None, node) };
+    }
+
+    /// Add an element to the back of the list.
+    ///
+    /// # Safety
+    /// `node` must not already be in a list, and it must stay valid (not moved or dropped) and
+    /// valid to share until it's removed from this list.
+    pub unsafe fn push_back(&mut self, node: NonNull<T>) {
+        // SAFETY: The caller upholds the requirements.
+        unsafe { self.insert_after(self.tail, node) };
+    }
+
+    /// Remove the first element of the list, if any, and return it.
+    pub fn pop_front(&mut self) -> Option<NonNull<T>> {
+        let head = self.head?;
+        // SAFETY: `head` is in this list.
+        unsafe { self.unlink(head) };
+        // SAFETY: `head` is an element's link.
+        Some(unsafe { node_of(head) })
+    }
+
+    /// Remove the last element of the list, if any, and return it.
+    pub fn pop_back(&mut self) -> Option<NonNull<T>> {
+        let tail = self.tail?;
+        // SAFETY: `tail` is in this list.
+        unsafe { self.unlink(tail) };
+        // SAFETY: `tail` is an element's link.
+        Some(unsafe { node_of(tail) })
+    }
+
+    /// Remove an element from the list.
+    ///
+    /// # Safety
+    /// `node` must be in this list.
+    pub unsafe fn remove(&mut self, node: NonNull<T>) {
+        // SAFETY: The caller ensures that `node` is in this list.
+        unsafe { self.unlink(link_of(node)) };
+    }
+
+    /// Iterate over the elements of the list, from front to back.
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get a cursor which starts at the first element (or the "ghost" position, if the list is
+    /// empty).
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.head,
+            list: self,
+        }
+    }
+
+    /// Get a cursor which starts at the last element (or the "ghost" position, if the list is
+    /// empty).
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.tail,
+            list: self,
+        }
+    }
+
+    /// Insert `node` after `prev`, or at the front if `prev` is `None`.
+    ///
+    /// # Safety
+    /// `prev` must be in this list, and `node` must satisfy the requirements of
+    /// [`Self::push_back`].
+    unsafe fn insert_after(&mut self, prev: Option<NonNull<Link>>, node: NonNull<T>) {
+        let link = link_of(node);
+        // SAFETY:
+        // The caller ensures that `prev` is in this list, so its link is valid and we have
+        // exclusive access to its fields.
+        let next = prev.map_or(self.head, |prev| unsafe { prev.as_ref().next.get() });
+        // SAFETY:
+        // The caller ensures that `node` is valid and not in a list, so we have exclusive access
+        // to its link, and `next` is in this list.
+        unsafe {
+            link.as_ref().prev.set(prev);
+            link.as_ref().next.set(next);
+            match prev {
+                Some(prev) => prev.as_ref().next.set(Some(link)),
+                None => self.head = Some(link),
+            }
+            match next {
+                Some(next) => next.as_ref().prev.set(Some(link)),
+                None => self.tail = Some(link),
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Remove the element with the given link from the list.
+    ///
+    /// # Safety
+    /// `link` must be in this list.
+    unsafe fn unlink(&mut self, link: NonNull<Link>) {
+        // SAFETY:
+        // The caller ensures that `link` is in this list, so it and its neighbours are valid and
+        // we have exclusive access to their fields.
+        unsafe {
+            let prev = link.as_ref().prev.replace(None);
+            let next = link.as_ref().next.replace(None);
+            match prev {
+                Some(prev) => prev.as_ref().next.set(next),
+                None => self.head = next,
+            }
+            match next {
+                Some(next) => next.as_ref().prev.set(prev),
+                None => self.tail = prev,
+            }
+        }
+        self.len -= 1;
+    }
+}
+impl<T: Linked> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: Linked + core::fmt::Debug> core::fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+impl<'a, T: Linked> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// SAFETY:
+// Sending the list sends access to the elements.
+unsafe impl<T: Linked + Send> Send for List<T> {}
+// SAFETY:
+// Sharing the list only gives out shared references to the elements.
+unsafe impl<T: Linked + Sync> Sync for List<T> {}
+
+/// An iterator over the elements of a [`List`].
+///
+/// This value is constructed by calling [`List::iter`].
+pub struct Iter<'a, T: Linked> {
+    /// The link of the next element to yield.
+    next: Option<NonNull<Link>>,
+    /// The iterator borrows the list.
+    _marker: PhantomData<&'a List<T>>,
+}
+impl<'a, T: Linked> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let link = self.next?;
+        // SAFETY:
+        // The list is borrowed, so `link` is still in it, and elements stay valid to share while
+        // they're in the list.
+        unsafe {
+            self.next = link.as_ref().next.get();
+            Some(node_of(link).as_ref())
+        }
+    }
+}
+
+/// A cursor over a [`List`], which can move back and forth and edit the list as it goes.
+///
+/// The cursor is either at an element, or at a "ghost" position past both ends of the list. Moving
+/// forward from the last element or backward from the first goes to the ghost position, and moving
+/// from the ghost position goes to the first or last element respectively.
+///
+/// This value is constructed by calling [`List::cursor_front_mut`] and related methods.
+pub struct CursorMut<'a, T: Linked> {
+    /// The current element's link, or `None` at the ghost position.
+    current: Option<NonNull<Link>>,
+    /// The list being edited.
+    list: &'a mut List<T>,
+}
+impl<T: Linked> CursorMut<'_, T> {
+    /// Get the element the cursor is at, or `None` at the ghost position.
+    #[must_use]
+    pub fn current(&self) -> Option<&T> {
+        // SAFETY:
+        // `current` is in the list, and elements stay valid to share while they're in the list.
+        self.current.map(|link| unsafe { node_of(link).as_ref() })
+    }
+
+    /// Move to the next element.
+    pub fn move_next(&mut self) {
+        self.current = match self.current {
+            // SAFETY: `current` is in the list.
+            Some(link) => unsafe { link.as_ref().next.get() },
+            None => self.list.head,
+        };
+    }
+
+    /// Move to the previous element.
+    pub fn move_prev(&mut self) {
+        self.current = match self.current {
+            // SAFETY: `current` is in the list.
+            Some(link) => unsafe { link.as_ref().prev.get() },
+            None => self.list.tail,
+        };
+    }
+
+    /// Remove the current element from the list and return it, moving the cursor to the next
+    /// element.
+    ///
+    /// At the ghost position, this does nothing and returns `None`.
+    pub fn remove_current(&mut self) -> Option<NonNull<T>> {
+        let link = self.current?;
+        // SAFETY: `current` is in the list.
+        unsafe {
+            self.current = link.as_ref().next.get();
+            self.list.unlink(link);
+            Some(node_of(link))
+        }
+    }
+
+    /// Insert an element before the current one, or at the back of the list at the ghost
+    /// position.
+    ///
+    /// # Safety
+    /// `node` must satisfy the requirements of [`List::push_back`].
+    pub unsafe fn insert_before(&mut self, node: NonNull<T>) {
+        let prev = match self.current {
+            // SAFETY: `current` is in the list.
+            Some(link) => unsafe { link.as_ref().prev.get() },
+            None => self.list.tail,
+        };
+        // SAFETY:
+        // `prev` is in the list, and the caller upholds the requirements for `node`.
+        unsafe { self.list.insert_after(prev, node) };
+    }
+
+    /// Insert an element after the current one, or at the front of the list at the ghost
+    /// position.
+    ///
+    /// # Safety
+    /// `node` must satisfy the requirements of [`List::push_back`].
+    pub unsafe fn insert_after(&mut self, node: NonNull<T>) {
+        // SAFETY:
+        // `current` is in the list, and the caller upholds the requirements for `node`.
+        unsafe { self.list.insert_after(self.current, node) };
+    }
+}
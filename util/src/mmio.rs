@@ -0,0 +1,124 @@
+//! Access to memory-mapped device registers.
+//!
+//! Device registers have to be accessed with volatile reads and writes, since reading or writing
+//! them has effects the compiler can't see, and many of them can only be read or only be written.
+//! [`Register`] wraps a pointer to a register and only allows the accesses its access marker
+//! permits, so using a register the wrong way is a compile error.
+//!
+//! Registers are handled through pointers rather than references, because a reference would let
+//! the compiler insert reads of its own, which devices might react to.
+//!
+//! ```
+//! # use util::mmio::{ReadOnly, ReadWrite, Register};
+//! let mut memory = [0_u32; 2];
+//! // SAFETY: `memory` is valid for reads and writes and outlives the registers.
+//! let (status, control) = unsafe {
+//!     (
+//!         Register::<u32, ReadOnly>::new(&raw mut memory[0]),
+//!         Register::<u32, ReadWrite>::new(&raw mut memory[1]),
+//!     )
+//! };
+//! control.write(5);
+//! control.modify(|value| value | 0b10);
+//! assert_eq!(control.read(), 7);
+//! assert_eq!(status.read(), 0);
+//! ```
+
+use core::{marker::PhantomData, ptr::NonNull};
+
+/// A marker for which accesses a [`Register`] allows.
+pub trait Access {}
+/// A marker for [`Register`]s which can be read.
+pub trait Readable: Access {}
+/// A marker for [`Register`]s which can be written.
+pub trait Writable: Access {}
+
+/// A marker for registers which can only be read.
+#[derive(Debug)]
+pub enum ReadOnly {}
+impl Access for ReadOnly {}
+impl Readable for ReadOnly {}
+
+/// A marker for registers which can only be written.
+#[derive(Debug)]
+pub enum WriteOnly {}
+impl Access for WriteOnly {}
+impl Writable for WriteOnly {}
+
+/// A marker for registers which can be both read and written.
+#[derive(Debug)]
+pub enum ReadWrite {}
+impl Access for ReadWrite {}
+impl Readable for ReadWrite {}
+impl Writable for ReadWrite {}
+
+/// A memory-mapped register holding a `T`, which allows the accesses given by `A`.
+pub struct Register<T, A: Access> {
+    /// The address of the register.
+    ptr: NonNull<T>,
+    /// The register acts like it's accessed as `A` allows.
+    _access: PhantomData<A>,
+}
+impl<T: Copy, A: Access> Register<T, A> {
+    /// Construct a register at the given address.
+    ///
+    /// # Safety
+    /// For as long as the register is used, `ptr` must be aligned and valid for volatile reads (if
+    /// `A` is [`Readable`]) and volatile writes (if `A` is [`Writable`]) of a `T`, and doing those
+    /// accesses mustn't interfere with anything else using the device.
+    ///
+    /// # Panics
+    /// Panics if `ptr` is null.
+    pub const unsafe fn new(ptr: *mut T) -> Self {
+        Self {
+            ptr: NonNull::new(ptr).expect("Register must not be null"),
+            _access: PhantomData,
+        }
+    }
+
+    /// Get the address of the register.
+    #[must_use]
+    pub const fn as_ptr(self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    /// Read the register.
+    #[must_use]
+    pub fn read(self) -> T
+    where
+        A: Readable,
+    {
+        // SAFETY: The constructor ensures that we can read.
+        unsafe { self.ptr.read_volatile() }
+    }
+
+    /// Write the register.
+    pub fn write(self, value: T)
+    where
+        A: Writable,
+    {
+        // SAFETY: The constructor ensures that we can write.
+        unsafe { self.ptr.write_volatile(value) };
+    }
+
+    /// Read the register, and then write back the result of calling `f` on its value.
+    ///
+    /// The read and write are separate accesses, so this isn't atomic with respect to the device.
+    pub fn modify(self, f: impl FnOnce(T) -> T)
+    where
+        A: Readable + Writable,
+    {
+        self.write(f(self.read()));
+    }
+}
+impl<T, A: Access> Clone for Register<T, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T, A: Access> Copy for Register<T, A> {}
+impl<T, A: Access> core::fmt::Debug for Register<T, A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Register").field(&self.ptr).finish()
+    }
+}
@@ -1,6 +1,9 @@
 //! An implementation of an atomic type.
 
+mod tagged_ptr;
+
 pub use core::sync::atomic::Ordering;
+pub use tagged_ptr::AtomicTaggedPtr;
 
 use core::{
     mem::{align_of, size_of},
@@ -9,6 +12,8 @@ use core::{
 
 use core::cell::UnsafeCell;
 
+use bitset::BitSet;
+
 /// An atomic wrapper around custom types.
 ///
 /// ```
@@ -30,6 +35,10 @@ use core::cell::UnsafeCell;
 /// that the target machine have atomic operations for the given size and alignment (which can only
 /// be checked post-monomorphization for the specific type and target).
 ///
+/// Values of up to 8 bytes are supported where the target has atomics that size. With the
+/// `atomic128` feature, 16-byte values are supported too on targets which have 16-byte atomics,
+/// though those targets then need a nightly compiler.
+///
 /// ```compile_fail
 /// # use util::sync::atomic::{Atomic, Ordering};
 /// let atomic = Atomic::new([0_u8; 256]);
@@ -194,6 +203,31 @@ impl<T: bytemuck::NoUninit> Atomic<T> {
         )
     }
 
+    /// Update the value according to a function which may decline to change it, returning the
+    /// old value.
+    ///
+    /// This works like [`core::sync::atomic::AtomicUsize::fetch_update`]: `f` is called with the
+    /// current value, and its result is stored if the value hasn't changed in the meantime. If it
+    /// has, `f` is called again with the new value. Returns `Ok` with the value `f` was last given
+    /// once a store succeeds, or `Err` with it if `f` returns `None`.
+    ///
+    /// `set_order` is the ordering for a successful store, and `fetch_order` for the loads.
+    pub fn fetch_update(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: impl FnMut(T) -> Option<T>,
+    ) -> Result<T, T> {
+        let mut prev = self.load(fetch_order);
+        while let Some(next) = f(prev) {
+            match self.compare_exchange_weak(prev, next, set_order, fetch_order) {
+                Ok(old_value) => return Ok(old_value),
+                Err(current) => prev = current,
+            }
+        }
+        Err(prev)
+    }
+
     /// Attempt to update the value according to a function, if no other thread changes it in the
     /// meantime.
     ///
@@ -269,6 +303,26 @@ impl<T: bytemuck::Pod> Atomic<T> {
     );
 }
 
+/// Methods involving arithmetic.
+///
+/// These treat the bytes of the value as an unsigned integer of the same size, in the target's
+/// byte order, so they only make sense for types which are integers underneath (like a
+/// `#[repr(transparent)]` wrapper around one). Like the primitive atomics, they wrap around on
+/// overflow.
+impl<T: bytemuck::Pod> Atomic<T> {
+    defer_to_inner!(
+        /// Add to the current value.
+        ///
+        /// The stored value is set to the result, and the old value is returned.
+        pub fn fetch_add(&self; value: T, ordering: Ordering) -> T;
+
+        /// Subtract from the current value.
+        ///
+        /// The stored value is set to the result, and the old value is returned.
+        pub fn fetch_sub(&self; value: T, ordering: Ordering) -> T;
+    );
+}
+
 /// Defer an up-toone-input and up-to-one-output function to the inner atomic.
 ///
 /// This macro abstracts over a common pattern used by many, but not all, methods, and is meant to
@@ -310,6 +364,90 @@ impl<T> From<T> for Atomic<T> {
     }
 }
 
+/// An atomic wrapper around a type from [`bitset::bitset!`], with operations on individual bits.
+///
+/// ```
+/// # use util::sync::atomic::{AtomicBitSet, Ordering};
+/// bitset::bitset!(
+///     /// Some flags.
+///     Flags(u32) {
+///         /// A flag.
+///         A,
+///         /// Another flag.
+///         B,
+///     }
+/// );
+/// let atomic = AtomicBitSet::new(Flags::A);
+/// assert!(!atomic.test_and_set(Flags::B, Ordering::Relaxed));
+/// assert!(atomic.test_and_set(Flags::B, Ordering::Relaxed));
+/// assert_eq!(atomic.fetch_remove(Flags::A, Ordering::Relaxed), Flags::A | Flags::B);
+/// assert_eq!(atomic.load(Ordering::Relaxed), Flags::B);
+/// ```
+pub struct AtomicBitSet<T>(Atomic<T>);
+
+impl<T> AtomicBitSet<T> {
+    /// Construct a new value starting with the given bits set.
+    pub const fn new(value: T) -> Self {
+        Self(Atomic::new(value))
+    }
+
+    /// Deconstruct the value.
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+
+    /// Convert to an exclusive reference to the inner value.
+    ///
+    /// Calling this method requires the borrow checker prove exclusive access, so this method
+    /// doesn't involve atomicity operations.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+}
+
+impl<T: BitSet + bytemuck::Pod> AtomicBitSet<T> {
+    /// Load the bits from the atomic.
+    ///
+    /// # Panics
+    /// Panics if `order` is `Release` or `AcqRel`.
+    pub fn load(&self, ordering: Ordering) -> T {
+        self.0.load(ordering)
+    }
+
+    /// Store the bits into the atomic.
+    ///
+    /// # Panics
+    /// Panics if `order` is `Aquire` or `AcqRel`.
+    pub fn store(&self, value: T, ordering: Ordering) {
+        self.0.store(value, ordering);
+    }
+
+    /// Set every bit set in `other`, returning the old bits.
+    pub fn fetch_insert(&self, other: T, ordering: Ordering) -> T {
+        self.0.fetch_or(other, ordering)
+    }
+
+    /// Clear every bit set in `other`, returning the old bits.
+    pub fn fetch_remove(&self, other: T, ordering: Ordering) -> T {
+        // Build the mask from the raw bits, since the bit set's own complement would also clear
+        // any unknown bits.
+        let mut mask = other;
+        *mask.as_inner_mut() = !*other.as_inner();
+        self.0.fetch_and(mask, ordering)
+    }
+
+    /// Set the bits in `flag`, returning whether they were all already set.
+    pub fn test_and_set(&self, flag: T, ordering: Ordering) -> bool {
+        self.fetch_insert(flag, ordering).contains(flag)
+    }
+}
+
+impl<T> From<T> for AtomicBitSet<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
 /// A helper macro for dispatching to an atomic.
 ///
 /// The body of `$impl` must compile and type check for all possible atomic values, but will only
@@ -339,6 +477,11 @@ macro_rules! dispatch_atomic {
                 type $atomic = std_atomic::AtomicU64;
                 $impl
             }
+            #[cfg(all(target_has_atomic = "128", feature = "atomic128"))]
+            16 if align_of::<$user_ty>() >= align_of::<std_atomic::AtomicU128>() => {
+                type $atomic = std_atomic::AtomicU128;
+                $impl
+            }
             _ => unreachable!("Atomic operations for type not available, should have been caught at compile time"),
 
         }
@@ -355,6 +498,8 @@ macro_rules! dispatch_atomic {
             4 if align_of::<$user_ty>() >= align_of::<std_atomic::AtomicU32>() => {}
             #[cfg(target_has_atomic = "64")]
             8 if align_of::<$user_ty>() >= align_of::<std_atomic::AtomicU64>() => {}
+            #[cfg(all(target_has_atomic = "128", feature = "atomic128"))]
+            16 if align_of::<$user_ty>() >= align_of::<std_atomic::AtomicU128>() => {}
             _ => panic!("Atomic operations for type not available on current target"),
 
         }
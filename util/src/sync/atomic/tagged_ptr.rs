@@ -0,0 +1,169 @@
+//! An atomic pointer with a tag packed into it.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// An atomic pointer to a `T`, with a small tag packed into the low bits which its alignment
+/// leaves unused.
+///
+/// Lock-free structures can use the tag as a version counter which changes on every update, so a
+/// compare-exchange fails if the pointer was changed and then changed back in the meantime (the
+/// ABA problem). There are only as many tag bits as the alignment of `T` gives (see
+/// [`Self::TAG_BITS`]), so tags wrap around quickly; this makes ABA unlikely, not impossible.
+///
+/// ```
+/// # use util::sync::atomic::{AtomicTaggedPtr, Ordering};
+/// let mut value = 5_u32;
+/// let atomic = AtomicTaggedPtr::new(&raw mut value, 1);
+/// let (ptr, tag) = atomic.load(Ordering::Relaxed);
+/// assert_eq!((ptr, tag), (&raw mut value, 1));
+/// assert!(atomic
+///     .compare_exchange((ptr, tag), (ptr, tag + 1), Ordering::Relaxed, Ordering::Relaxed)
+///     .is_ok());
+/// assert_eq!(atomic.load(Ordering::Relaxed), (&raw mut value, 2));
+/// ```
+pub struct AtomicTaggedPtr<T> {
+    /// The pointer, with the tag in its low bits.
+    inner: AtomicPtr<T>,
+}
+
+impl<T> AtomicTaggedPtr<T> {
+    /// The number of bits available for the tag.
+    pub const TAG_BITS: u32 = align_of::<T>().trailing_zeros();
+
+    /// The bits of the pointer which hold the tag, which is also the largest tag.
+    pub const TAG_MASK: usize = align_of::<T>() - 1;
+
+    /// Construct a new value with the given pointer and tag.
+    ///
+    /// Only the low [`Self::TAG_BITS`] bits of `tag` are kept.
+    ///
+    /// # Panics
+    /// Panics if `ptr` isn't aligned for `T`.
+    pub fn new(ptr: *mut T, tag: usize) -> Self {
+        Self {
+            inner: AtomicPtr::new(Self::pack(ptr, tag)),
+        }
+    }
+
+    /// Construct a new value with a null pointer and a tag of 0.
+    #[must_use]
+    pub const fn null() -> Self {
+        Self {
+            inner: AtomicPtr::new(core::ptr::null_mut()),
+        }
+    }
+
+    /// Load the pointer and tag.
+    ///
+    /// # Panics
+    /// Panics if `order` is `Release` or `AcqRel`.
+    pub fn load(&self, ordering: Ordering) -> (*mut T, usize) {
+        Self::unpack(self.inner.load(ordering))
+    }
+
+    /// Store a pointer and tag.
+    ///
+    /// # Panics
+    /// Panics if `ptr` isn't aligned for `T`, or if `order` is `Acquire` or `AcqRel`.
+    pub fn store(&self, ptr: *mut T, tag: usize, ordering: Ordering) {
+        self.inner.store(Self::pack(ptr, tag), ordering);
+    }
+
+    /// Store a pointer and tag, returning the old ones.
+    ///
+    /// # Panics
+    /// Panics if `ptr` isn't aligned for `T`.
+    pub fn swap(&self, ptr: *mut T, tag: usize, ordering: Ordering) -> (*mut T, usize) {
+        Self::unpack(self.inner.swap(Self::pack(ptr, tag), ordering))
+    }
+
+    /// Store `new` if the current pointer and tag are both the same as `current`.
+    ///
+    /// The return value is a result indicating whether the new value was written and containing
+    /// the previous pointer and tag. The orderings work the same as for
+    /// [`Atomic::compare_exchange`](super::Atomic::compare_exchange).
+    ///
+    /// # Panics
+    /// Panics if either pointer isn't aligned for `T`.
+    pub fn compare_exchange(
+        &self,
+        current: (*mut T, usize),
+        new: (*mut T, usize),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        self.inner
+            .compare_exchange(
+                Self::pack(current.0, current.1),
+                Self::pack(new.0, new.1),
+                success,
+                failure,
+            )
+            .map(Self::unpack)
+            .map_err(Self::unpack)
+    }
+
+    /// Store `new` if the current pointer and tag are both the same as `current`, possibly
+    /// failing spuriously.
+    ///
+    /// This is the same as [`Self::compare_exchange`], except that it's allowed to fail even if
+    /// the comparison succeeds, which can result in more efficient code on some platforms.
+    ///
+    /// # Panics
+    /// Panics if either pointer isn't aligned for `T`.
+    pub fn compare_exchange_weak(
+        &self,
+        current: (*mut T, usize),
+        new: (*mut T, usize),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        self.inner
+            .compare_exchange_weak(
+                Self::pack(current.0, current.1),
+                Self::pack(new.0, new.1),
+                success,
+                failure,
+            )
+            .map(Self::unpack)
+            .map_err(Self::unpack)
+    }
+
+    /// Deconstruct the value into its pointer and tag.
+    pub fn into_inner(self) -> (*mut T, usize) {
+        Self::unpack(self.inner.into_inner())
+    }
+
+    /// Pack a pointer and tag into a single pointer.
+    fn pack(ptr: *mut T, tag: usize) -> *mut T {
+        assert!(
+            ptr.addr() & Self::TAG_MASK == 0,
+            "Pointer in `AtomicTaggedPtr` must be aligned"
+        );
+        ptr.map_addr(|addr| addr | (tag & Self::TAG_MASK))
+    }
+
+    /// Split a pointer from [`Self::pack`] into the pointer and tag.
+    fn unpack(packed: *mut T) -> (*mut T, usize) {
+        (
+            packed.map_addr(|addr| addr & !Self::TAG_MASK),
+            packed.addr() & Self::TAG_MASK,
+        )
+    }
+}
+
+impl<T> Default for AtomicTaggedPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> core::fmt::Debug for AtomicTaggedPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (ptr, tag) = self.load(Ordering::Relaxed);
+        f.debug_struct("AtomicTaggedPtr")
+            .field("ptr", &ptr)
+            .field("tag", &tag)
+            .finish()
+    }
+}
@@ -0,0 +1,129 @@
+//! Values which only one hart may access.
+
+/// Get the ID of the hart we're running on.
+///
+/// On RISC-V, this reads `tp`, which the kernel keeps set to the hart ID (supervisor mode can't
+/// read `mhartid` itself). User code is free to use `tp` for something else, so this is only
+/// meaningful in the kernel. On other targets, such as when running tests on the host, this is
+/// always 0, so [`HartLocal`] isn't [`Sync`] there.
+#[must_use]
+pub fn current_hart_id() -> usize {
+    #[cfg(target_arch = "riscv32")]
+    {
+        let hart_id: usize;
+        // SAFETY: Reading `tp` has no side effects.
+        unsafe {
+            core::arch::asm!(
+                "mv {}, tp",
+                out(reg) hart_id,
+                options(nomem, nostack, preserves_flags),
+            );
+        }
+        hart_id
+    }
+    #[cfg(not(target_arch = "riscv32"))]
+    {
+        0
+    }
+}
+
+/// A value which can only be accessed from the hart which owns it.
+///
+/// This can be put in a `static` to hold state for one hart, like interior-mutable types which
+/// aren't [`Sync`], without promising that other harts can use it safely. Accessing it from any
+/// other hart is checked against [`current_hart_id`] and refused.
+///
+/// This is only [`Sync`] on RISC-V, where [`current_hart_id`] tells harts apart. Elsewhere every
+/// thread looks like hart 0, so sharing one between threads would let them all access it:
+///
+/// ```compile_fail
+/// # use core::cell::Cell;
+/// # use util::sync::HartLocal;
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<HartLocal<Cell<u32>>>();
+/// ```
+///
+/// ```
+/// # use core::cell::Cell;
+/// # use util::sync::{HartLocal, current_hart_id};
+/// let counter = HartLocal::new(Cell::new(0));
+/// assert_eq!(counter.owner(), current_hart_id());
+///
+/// counter.local().set(counter.local().get() + 1);
+/// assert_eq!(counter.get().map(Cell::get), Some(1));
+/// ```
+#[derive(Debug)]
+pub struct HartLocal<T> {
+    /// The ID of the hart which may access the value.
+    owner: usize,
+    /// The value.
+    value: T,
+}
+impl<T> HartLocal<T> {
+    /// Construct a new value owned by the hart we're running on.
+    pub fn new(value: T) -> Self {
+        Self::with_owner(current_hart_id(), value)
+    }
+
+    /// Construct a new value owned by the hart with the given ID.
+    ///
+    /// This is for values which are made before the hart which will use them is running, like
+    /// `static`s.
+    pub const fn with_owner(owner: usize, value: T) -> Self {
+        Self { owner, value }
+    }
+
+    /// Get the ID of the hart which owns this value.
+    #[must_use]
+    pub const fn owner(&self) -> usize {
+        self.owner
+    }
+
+    /// Get the value, if we're on the hart which owns it.
+    #[must_use]
+    pub fn get(&self) -> Option<&T> {
+        (current_hart_id() == self.owner).then_some(&self.value)
+    }
+
+    /// Get the value.
+    ///
+    /// # Panics
+    /// Panics if we aren't on the hart which owns the value. See [`Self::get`] for a version which
+    /// doesn't panic.
+    #[must_use]
+    #[track_caller]
+    pub fn local(&self) -> &T {
+        let current = current_hart_id();
+        assert_eq!(
+            current, self.owner,
+            "HartLocal owned by hart {} accessed from hart {current}",
+            self.owner
+        );
+        &self.value
+    }
+
+    /// Get mutable access to the value.
+    ///
+    /// Since this requires a mutable reference, we know that no other hart is accessing the
+    /// value, so this works from any hart.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Retrieve the value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+/// Make a value owned by the hart we're running on.
+impl<T: Default> Default for HartLocal<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+// SAFETY:
+// Shared access to the value is only given out on the owning hart, so it's never accessed from
+// more than one hart at once. The value may still be moved to and dropped on any hart, so it must
+// be `Send`.
+#[cfg(target_arch = "riscv32")]
+unsafe impl<T: Send> Sync for HartLocal<T> {}
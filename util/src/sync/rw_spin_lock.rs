@@ -0,0 +1,200 @@
+//! A readers-writer lock which spins on contention.
+//!
+//! This lock doesn't depend on the kernel, so it works both in the kernel and in userspace. It's
+//! meant for read-mostly structures whose critical sections are short; anything which might hold
+//! the lock for a while should use a lock which sleeps instead.
+//!
+//! Writers take priority: once a writer is waiting, new readers wait behind it, so a steady stream
+//! of readers can't keep a writer out forever.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The value of `state` while a writer holds the lock.
+const WRITE_LOCKED: u32 = u32::MAX;
+/// The most readers that can hold the lock at once.
+const MAX_READERS: u32 = WRITE_LOCKED - 1;
+
+/// A lock which allows either many readers or one writer at a time, spinning when contended.
+///
+/// ```
+/// # use util::sync::RwSpinLock;
+/// let lock = RwSpinLock::new(5);
+/// {
+///     let first = lock.read();
+///     let second = lock.read();
+///     assert_eq!(*first + *second, 10);
+///     assert!(lock.try_write().is_none());
+/// }
+/// *lock.write() += 1;
+/// assert_eq!(*lock.read(), 6);
+/// ```
+pub struct RwSpinLock<T: ?Sized> {
+    /// The number of readers holding the lock, or [`WRITE_LOCKED`] if a writer holds it.
+    state: AtomicU32,
+    /// The number of writers waiting for the lock, which new readers wait behind.
+    writers_waiting: AtomicU32,
+    /// The value stored in the lock.
+    value: UnsafeCell<T>,
+}
+impl<T> RwSpinLock<T> {
+    /// Construct a [`RwSpinLock`] to wrap the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            writers_waiting: AtomicU32::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Destruct the lock and return the inner value.
+    ///
+    /// This function does not have to lock because consuming the value means it cannot be in use
+    /// anywhere else.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// Get an exclusive reference to the inner value from an exclusive reference to the outer
+    /// value.
+    ///
+    /// This function does not have to lock because the exclusive reference to the value means it
+    /// cannot be in use anywhere else.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: ?Sized> RwSpinLock<T> {
+    /// Lock the value for shared reading, returning an RAII guard.
+    ///
+    /// If a writer holds the lock or is waiting for it, then this method will spin until the
+    /// writer is done.
+    pub fn read(&self) -> RwSpinLockReadGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Attempt to lock the value for shared reading without blocking.
+    pub fn try_read(&self) -> Option<RwSpinLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while self.readable(state) {
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(RwSpinLockReadGuard { lock: self }),
+                Err(new_state) => state = new_state,
+            }
+        }
+        None
+    }
+
+    /// Lock the value for exclusive writing, returning an RAII guard.
+    ///
+    /// If anyone else holds the lock, then this method will spin until they release it.
+    pub fn write(&self) -> RwSpinLockWriteGuard<'_, T> {
+        if let Some(guard) = self.try_write() {
+            return guard;
+        }
+        self.writers_waiting.fetch_add(1, Ordering::Relaxed);
+        loop {
+            // Wait for the lock to look free before trying to take it, so we aren't constantly
+            // taking the cache line away from the holders.
+            while self.state.load(Ordering::Relaxed) != 0 {
+                core::hint::spin_loop();
+            }
+            if self
+                .state
+                .compare_exchange_weak(0, WRITE_LOCKED, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                break;
+            }
+        }
+        self.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+        RwSpinLockWriteGuard { lock: self }
+    }
+
+    /// Attempt to lock the value for exclusive writing without blocking.
+    pub fn try_write(&self) -> Option<RwSpinLockWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITE_LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwSpinLockWriteGuard { lock: self })
+    }
+
+    /// Check whether a new reader may take the lock while it's in the given state.
+    fn readable(&self, state: u32) -> bool {
+        state < MAX_READERS && self.writers_waiting.load(Ordering::Relaxed) == 0
+    }
+}
+impl<T: Default> Default for RwSpinLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+// UnsafeCell implements `Send` as appropriate, so we only need `Sync`.
+
+// SAFETY:
+// Readers on different threads share the value, so it must be `Sync`, and writers correspond to
+// sending the value to whichever thread writes, so it must be `Send`.
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwSpinLock<T> {}
+
+/// An RAII guard for shared read access to an [`RwSpinLock`].
+///
+/// This value is constructed by calling [`RwSpinLock::read`] and related methods.
+pub struct RwSpinLockReadGuard<'a, T: ?Sized> {
+    /// The lock which this guard holds for reading.
+    lock: &'a RwSpinLock<T>,
+}
+impl<T: ?Sized> core::ops::Deref for RwSpinLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // We hold a read lock, so nobody has exclusive access.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+impl<T: ?Sized> Drop for RwSpinLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// An RAII guard for exclusive write access to an [`RwSpinLock`].
+///
+/// This value is constructed by calling [`RwSpinLock::write`] and related methods.
+pub struct RwSpinLockWriteGuard<'a, T: ?Sized> {
+    /// The lock which this guard holds for writing.
+    lock: &'a RwSpinLock<T>,
+}
+impl<T: ?Sized> core::ops::Deref for RwSpinLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // We hold the write lock, so we have exclusive access.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+impl<T: ?Sized> core::ops::DerefMut for RwSpinLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // We hold the write lock, so we have exclusive access.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+impl<T: ?Sized> Drop for RwSpinLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
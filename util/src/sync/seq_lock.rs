@@ -0,0 +1,215 @@
+//! A sequence lock, for values which are read often and written rarely.
+//!
+//! Readers never block writers and never write to shared memory: they copy the value out, and
+//! then check a sequence counter to see whether a writer was active during the copy, retrying if
+//! so. Writers make the counter odd while writing, and even again once they're done.
+//!
+//! Because readers can run concurrently with a writer, the value is only ever copied with
+//! (relaxed) atomic accesses, and a copy is only treated as a `T` once the counter shows it
+//! wasn't torn.
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{self, AtomicU8, AtomicU32, Ordering},
+};
+
+use bytemuck::NoUninit;
+
+/// A lock around a small [`Copy`] value whose readers never block writers, with writers spinning
+/// on each other.
+///
+/// ```
+/// # use util::sync::SeqLock;
+/// let lock = SeqLock::new([1_u32, 2]);
+/// lock.write([3, 4]);
+/// lock.update(|[a, b]| [a + 1, b + 1]);
+/// assert_eq!(lock.read(), [4, 5]);
+/// ```
+pub struct SeqLock<T> {
+    /// The sequence counter, which is odd while a writer is writing.
+    seq: AtomicU32,
+    /// The value stored in the lock.
+    ///
+    /// This is only accessed through [`load_racy`] and [`store_racy`] while shared.
+    value: UnsafeCell<T>,
+}
+impl<T> SeqLock<T> {
+    /// Construct a [`SeqLock`] to wrap the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Destruct the lock and return the inner value.
+    ///
+    /// This function does not have to lock because consuming the value means it cannot be in use
+    /// anywhere else.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// Get an exclusive reference to the inner value from an exclusive reference to the outer
+    /// value.
+    ///
+    /// This function does not have to lock because the exclusive reference to the value means it
+    /// cannot be in use anywhere else.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: NoUninit> SeqLock<T> {
+    /// Read the value.
+    ///
+    /// If a writer is active, then this method will spin until it's done.
+    pub fn read(&self) -> T {
+        loop {
+            if let Some(value) = self.try_read() {
+                return value;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Attempt to read the value, failing if a writer was active during the read.
+    pub fn try_read(&self) -> Option<T> {
+        let seq = self.seq.load(Ordering::Acquire);
+        if !seq.is_multiple_of(2) {
+            return None;
+        }
+        // SAFETY:
+        // While shared, `value` is only accessed through `load_racy` and `store_racy`.
+        let value = unsafe { load_racy(self.value.get()) };
+        // Keep the copy from being reordered after the second load of `seq`.
+        atomic::fence(Ordering::Acquire);
+        (self.seq.load(Ordering::Relaxed) == seq).then(|| {
+            // SAFETY:
+            // No writer ran during the copy, so it's an intact copy of the last written value.
+            unsafe { value.assume_init() }
+        })
+    }
+
+    /// Replace the value.
+    ///
+    /// If another writer is active, then this method will spin until it's done.
+    pub fn write(&self, value: T) {
+        self.update(|_| value);
+    }
+
+    /// Replace the value with the result of calling `f` on the current value.
+    ///
+    /// Other writers are excluded for the duration of the call, so this is a consistent
+    /// read-modify-write. Readers spin until it's done, so `f` should be quick.
+    pub fn update(&self, f: impl FnOnce(T) -> T) {
+        let seq = self.lock_write();
+        // SAFETY:
+        // While shared, `value` is only accessed through `load_racy` and `store_racy`, and we
+        // have excluded other writers, so the copy can't be torn.
+        let value = unsafe { load_racy(self.value.get()).assume_init() };
+        // SAFETY:
+        // While shared, `value` is only accessed through `load_racy` and `store_racy`.
+        unsafe { store_racy(self.value.get(), &f(value)) };
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Wait until no writer is active and then make the counter odd, returning its old value.
+    fn lock_write(&self) -> u32 {
+        loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq.is_multiple_of(2)
+                && self
+                    .seq
+                    .compare_exchange_weak(
+                        seq,
+                        seq.wrapping_add(1),
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                // Keep the writes to the value from being reordered before the counter update.
+                atomic::fence(Ordering::Release);
+                return seq;
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+impl<T: Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+// UnsafeCell implements `Send` as appropriate, so we only need `Sync`.
+
+// SAFETY:
+// Readers get their own copy of the value, and writers move the value in, both of which
+// correspond to sending the value between threads.
+unsafe impl<T: Send> Sync for SeqLock<T> {}
+
+/// Copy a value using relaxed atomic loads.
+///
+/// The copy may be torn by a concurrent [`store_racy`], so it isn't necessarily a valid `T`.
+///
+/// # Safety
+/// `src` must be valid for reads and aligned, and may only be accessed concurrently by this
+/// function and [`store_racy`].
+unsafe fn load_racy<T: NoUninit>(src: *const T) -> MaybeUninit<T> {
+    let mut out = MaybeUninit::<T>::uninit();
+    if size_of::<T>().is_multiple_of(4) && align_of::<T>() >= align_of::<AtomicU32>() {
+        let src = src.cast::<u32>();
+        let dst = out.as_mut_ptr().cast::<u32>();
+        for i in 0..size_of::<T>() / 4 {
+            // SAFETY:
+            // `T` has no padding and is aligned for `u32`, so every word is in bounds, aligned,
+            // and initialized. The caller ensures all concurrent accesses are atomic.
+            unsafe {
+                let value = AtomicU32::from_ptr(src.add(i).cast_mut()).load(Ordering::Relaxed);
+                dst.add(i).write(value);
+            }
+        }
+    } else {
+        let src = src.cast::<u8>();
+        let dst = out.as_mut_ptr().cast::<u8>();
+        for i in 0..size_of::<T>() {
+            // SAFETY:
+            // `T` has no padding, so every byte is in bounds and initialized. The caller ensures
+            // all concurrent accesses are atomic.
+            unsafe {
+                let value = AtomicU8::from_ptr(src.add(i).cast_mut()).load(Ordering::Relaxed);
+                dst.add(i).write(value);
+            }
+        }
+    }
+    out
+}
+
+/// Copy a value into `dst` using relaxed atomic stores.
+///
+/// # Safety
+/// `dst` must be valid for writes and aligned, and may only be accessed concurrently by this
+/// function and [`load_racy`].
+unsafe fn store_racy<T: NoUninit>(dst: *mut T, value: &T) {
+    let bytes = bytemuck::bytes_of(value);
+    if size_of::<T>().is_multiple_of(4) && align_of::<T>() >= align_of::<AtomicU32>() {
+        let dst = dst.cast::<u32>();
+        for (i, word) in bytes.chunks_exact(4).enumerate() {
+            let word = u32::from_ne_bytes([word[0], word[1], word[2], word[3]]);
+            // SAFETY:
+            // `T` is aligned for `u32`, so every word is in bounds and aligned. The caller
+            // ensures all concurrent accesses are atomic.
+            unsafe { AtomicU32::from_ptr(dst.add(i)).store(word, Ordering::Relaxed) };
+        }
+    } else {
+        let dst = dst.cast::<u8>();
+        for (i, &byte) in bytes.iter().enumerate() {
+            // SAFETY:
+            // Every byte is in bounds. The caller ensures all concurrent accesses are atomic.
+            unsafe { AtomicU8::from_ptr(dst.add(i)).store(byte, Ordering::Relaxed) };
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! A fixed-capacity single-producer single-consumer ring buffer.
+//!
+//! The producer and consumer each own one of the two indices into the buffer, so neither ever has
+//! to wait for the other: pushing fails if the buffer is full, and popping fails if it's empty.
+//!
+//! ```
+//! # use util::sync::spsc::Ring;
+//! let mut ring = Ring::<u8, 4>::new();
+//! let (mut producer, mut consumer) = ring.split();
+//! assert_eq!(producer.push_slice(b"hello"), 4);
+//! assert_eq!(producer.push(b'!'), Err(b'!'));
+//! assert_eq!(consumer.pop(), Some(b'h'));
+//! let mut buf = [0; 8];
+//! assert_eq!(consumer.pop_slice(&mut buf), 3);
+//! assert_eq!(&buf[..3], b"ell");
+//! ```
+//!
+//! A ring in a `static` can be split once with [`Ring::try_split`]:
+//!
+//! ```
+//! # use util::sync::spsc::{Consumer, Producer, Ring};
+//! static RING: Ring<u8, 4> = Ring::new();
+//! let (producer, consumer): (Producer<'static, _, 4>, Consumer<'static, _, 4>) =
+//!     RING.try_split().unwrap();
+//! assert!(RING.try_split().is_none());
+//! ```
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// A ring buffer which holds up to `N` values of type `T`.
+///
+/// Values go in through a [`Producer`] and come out through a [`Consumer`], which are obtained from
+/// [`Ring::split`] (or [`Ring::try_split`]) and can be used from different threads.
+pub struct Ring<T, const N: usize> {
+    /// The number of values ever popped, modulo [`Self::INDEX_LIMIT`], which the consumer advances.
+    ///
+    /// The oldest value is at index `head % N`.
+    head: AtomicUsize,
+    /// The number of values ever pushed, modulo [`Self::INDEX_LIMIT`], which the producer advances.
+    ///
+    /// The next value goes at index `tail % N`.
+    tail: AtomicUsize,
+    /// Whether [`Ring::try_split`] has handed out the halves.
+    split: AtomicBool,
+    /// The buffer, in which the values from `head` to `tail` are initialized.
+    buf: [UnsafeCell<MaybeUninit<T>>; N],
+}
+impl<T, const N: usize> Ring<T, N> {
+    /// The limit which `head` and `tail` wrap around at.
+    ///
+    /// This is twice the capacity, so that a full buffer and an empty one have different indices.
+    /// It's also a multiple of `N`, so each slot keeps the same index when they wrap around.
+    const INDEX_LIMIT: usize = {
+        assert!(N > 0, "A `Ring` must have a nonzero capacity");
+        assert!(
+            N <= usize::MAX / 4,
+            "A `Ring`'s capacity must leave room for its indices"
+        );
+        N * 2
+    };
+
+    /// Construct a new, empty ring buffer.
+    #[must_use]
+    pub const fn new() -> Self {
+        let _ = Self::INDEX_LIMIT;
+        Self {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            split: AtomicBool::new(false),
+            buf: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+        }
+    }
+
+    /// Get the number of values the buffer can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Get the number of values currently in the buffer.
+    ///
+    /// If the buffer is in use, then this may be out of date by the time it returns.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        Self::distance(head, self.tail.load(Ordering::Acquire))
+    }
+
+    /// Check whether the buffer is empty.
+    ///
+    /// If the buffer is in use, then this may be out of date by the time it returns.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Split the buffer into its producing and consuming halves.
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { ring: self }, Consumer { ring: self })
+    }
+
+    /// Split the buffer into its producing and consuming halves through a shared reference, such
+    /// as to a `static`.
+    ///
+    /// Nothing tracks when the halves are dropped, so this only returns them the first time it's
+    /// called.
+    pub fn try_split(&self) -> Option<(Producer<'_, T, N>, Consumer<'_, T, N>)> {
+        if self.split.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        Some((Producer { ring: self }, Consumer { ring: self }))
+    }
+
+    /// Get a pointer to the slot for the given index.
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.buf[index % N].get()
+    }
+
+    /// Move an index forward by `count` values.
+    const fn advance(index: usize, count: usize) -> usize {
+        (index + count) % Self::INDEX_LIMIT
+    }
+
+    /// Get the number of values from index `from` to index `to`.
+    const fn distance(from: usize, to: usize) -> usize {
+        (to + Self::INDEX_LIMIT - from) % Self::INDEX_LIMIT
+    }
+}
+impl<T, const N: usize> Default for Ring<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T, const N: usize> Drop for Ring<T, N> {
+    fn drop(&mut self) {
+        Consumer { ring: self }.clear();
+    }
+}
+
+// UnsafeCell implements `Send` as appropriate, so we only need `Sync`.
+
+// SAFETY:
+// Values only ever move from the producer to the consumer, which corresponds to sending them
+// between threads, and nobody ever gets a shared reference to them.
+unsafe impl<T: Send, const N: usize> Sync for Ring<T, N> {}
+
+/// The half of a [`Ring`] which pushes values in.
+///
+/// This value is constructed by calling [`Ring::split`] or [`Ring::try_split`].
+pub struct Producer<'a, T, const N: usize> {
+    /// The buffer to push into.
+    ring: &'a Ring<T, N>,
+}
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Push a value into the buffer, or give it back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        if Ring::<T, N>::distance(self.ring.head.load(Ordering::Acquire), tail) == N {
+            return Err(value);
+        }
+        // SAFETY:
+        // The slot is outside `head..tail`, so the consumer won't touch it until we move `tail`
+        // past it, and we're the only producer.
+        unsafe { (*self.ring.slot(tail)).write(value) };
+        self.ring
+            .tail
+            .store(Ring::<T, N>::advance(tail, 1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Get the number of values which can currently be pushed.
+    #[must_use]
+    pub fn free_space(&self) -> usize {
+        N - self.ring.len()
+    }
+
+    /// Check whether the buffer is full.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.free_space() == 0
+    }
+}
+impl<T: Copy, const N: usize> Producer<'_, T, N> {
+    /// Push as many values from the front of `values` as will fit, returning how many were pushed.
+    pub fn push_slice(&mut self, values: &[T]) -> usize {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let count = values.len().min(self.free_space());
+        for (i, &value) in values[..count].iter().enumerate() {
+            // SAFETY: As in `push`.
+            unsafe { (*self.ring.slot(Ring::<T, N>::advance(tail, i))).write(value) };
+        }
+        self.ring
+            .tail
+            .store(Ring::<T, N>::advance(tail, count), Ordering::Release);
+        count
+    }
+}
+
+/// The half of a [`Ring`] which pops values out.
+///
+/// This value is constructed by calling [`Ring::split`] or [`Ring::try_split`].
+pub struct Consumer<'a, T, const N: usize> {
+    /// The buffer to pop from.
+    ring: &'a Ring<T, N>,
+}
+impl<T, const N: usize> Consumer<'_, T, N> {
+    /// Pop the oldest value out of the buffer, if there is one.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        if head == self.ring.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY:
+        // The slot is inside `head..tail`, so the producer initialized it and won't touch it until
+        // we move `head` past it, and we're the only consumer.
+        let value = unsafe { (*self.ring.slot(head)).assume_init_read() };
+        self.ring
+            .head
+            .store(Ring::<T, N>::advance(head, 1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Get the number of values which can currently be popped.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Check whether the buffer is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Pop and drop every value currently in the buffer.
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+impl<T: Copy, const N: usize> Consumer<'_, T, N> {
+    /// Pop values into `buf` until it's full or the buffer is empty, returning how many were
+    /// popped.
+    pub fn pop_slice(&mut self, buf: &mut [T]) -> usize {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let count = buf.len().min(self.len());
+        for (i, slot) in buf[..count].iter_mut().enumerate() {
+            // SAFETY: As in `pop`.
+            *slot = unsafe { (*self.ring.slot(Ring::<T, N>::advance(head, i))).assume_init_read() };
+        }
+        self.ring
+            .head
+            .store(Ring::<T, N>::advance(head, count), Ordering::Release);
+        count
+    }
+}
+impl<T, const N: usize> Iterator for Consumer<'_, T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pop()
+    }
+}
@@ -0,0 +1,54 @@
+//! Testing of [`ArrayString`].
+
+use core::fmt::Write as _;
+
+use util::array_string::{ArrayString, CapacityError};
+
+#[test]
+fn test_array_string() {
+    let mut s = ArrayString::<6>::new();
+    assert!(s.is_empty());
+    assert_eq!(s.push_str("ab"), Ok(()));
+    assert_eq!(s.push('é'), Ok(()));
+    assert_eq!(s, "abé");
+    assert_eq!(s.len(), 4);
+    assert_eq!(s.pop(), Some('é'));
+    s.truncate(1);
+    assert_eq!(s.as_str(), "a");
+    s.clear();
+    assert_eq!(s.pop(), None);
+    assert_eq!(
+        ArrayString::<3>::try_from("abc").map(|s| s.to_string()),
+        Ok("abc".into())
+    );
+    assert_eq!(ArrayString::<3>::try_from("abcd"), Err(CapacityError));
+}
+
+#[test]
+fn test_array_string_truncation() {
+    let mut s = ArrayString::<5>::new();
+    // Only part of the multi-byte character would fit, so none of it is pushed.
+    assert_eq!(s.push_str("abcdé"), Err(CapacityError));
+    assert_eq!(s, "abcd");
+    assert_eq!(s.push('é'), Err(CapacityError));
+    assert_eq!(s.push_str("e!"), Err(CapacityError));
+    assert_eq!(s, "abcde");
+    assert!(s.is_full());
+}
+
+#[test]
+fn test_array_string_write() {
+    let mut s = ArrayString::<8>::new();
+    assert!(write!(s, "{}-{}", 12, 34).is_ok());
+    assert_eq!(s, "12-34");
+    assert!(write!(s, "{}", 5678).is_err());
+    assert_eq!(s, "12-34567");
+    assert_eq!(format!("{s} {s:?}"), "12-34567 \"12-34567\"");
+}
+
+#[test]
+#[should_panic = "middle of a character"]
+fn test_array_string_truncate_mid_char() {
+    let mut s = ArrayString::<4>::try_from("é").unwrap();
+    s.truncate(1);
+}
@@ -0,0 +1,71 @@
+//! Testing of [`ArrayVec`].
+
+use std::rc::Rc;
+
+use util::array_vec::ArrayVec;
+
+#[test]
+fn test_array_vec() {
+    let mut vec = ArrayVec::<u32, 3>::new();
+    assert!(vec.is_empty());
+    assert_eq!(vec.capacity(), 3);
+    vec.push(1);
+    vec.push(2);
+    assert_eq!(vec.try_push(3), Ok(()));
+    assert!(vec.is_full());
+    assert_eq!(vec.try_push(4), Err(4));
+    assert_eq!(*vec, [1, 2, 3]);
+    vec[0] = 5;
+    assert_eq!(vec.iter().sum::<u32>(), 10);
+    assert_eq!(vec.clone(), vec);
+    assert_eq!(vec.pop(), Some(3));
+    vec.truncate(1);
+    assert_eq!(*vec, [5]);
+    vec.clear();
+    assert_eq!(vec.pop(), None);
+}
+
+#[test]
+#[should_panic = "capacity of 1 exceeded"]
+fn test_array_vec_push_full() {
+    let mut vec = ArrayVec::<u32, 1>::new();
+    vec.push(1);
+    vec.push(2);
+}
+
+#[test]
+fn test_array_vec_drain() {
+    let mut vec = ArrayVec::<u32, 8>::new();
+    for i in 0..6 {
+        vec.push(i);
+    }
+    assert_eq!(vec.drain(1..3).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(*vec, [0, 3, 4, 5]);
+    let mut drain = vec.drain(..=2);
+    assert_eq!(drain.len(), 3);
+    assert_eq!(drain.next_back(), Some(4));
+    assert_eq!(drain.next(), Some(0));
+    // Dropping the iterator still removes the rest of the range.
+    drop(drain);
+    assert_eq!(*vec, [5]);
+    assert_eq!(vec.drain(..).collect::<Vec<_>>(), [5]);
+    assert!(vec.is_empty());
+}
+
+#[test]
+fn test_array_vec_drops_values() {
+    let value = Rc::new(());
+    let mut vec = ArrayVec::<_, 8>::new();
+    for _ in 0..6 {
+        vec.push(Rc::clone(&value));
+    }
+    vec.truncate(5);
+    assert_eq!(Rc::strong_count(&value), 6);
+    let mut drain = vec.drain(1..4);
+    drop(drain.next());
+    drop(drain);
+    assert_eq!(Rc::strong_count(&value), 3);
+    assert_eq!(vec.len(), 2);
+    drop(vec);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
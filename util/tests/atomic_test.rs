@@ -1,7 +1,7 @@
 //! Test coverage of the atomic type.
 
 use bytemuck::NoUninit;
-use util::sync::atomic::{Atomic, Ordering};
+use util::sync::atomic::{Atomic, AtomicBitSet, AtomicTaggedPtr, Ordering};
 
 #[repr(u8)]
 #[derive(NoUninit, Clone, Copy)]
@@ -124,3 +124,109 @@ fn test_bitwise_ops() {
     assert_eq!(atomic.fetch_nand(0x27, Ordering::Relaxed), 0x11);
     assert_eq!(atomic.load(Ordering::Relaxed), 0xfe);
 }
+
+bitset::bitset!(
+    /// Flags to test [`AtomicBitSet`] with.
+    TestFlags(u16) {
+        /// The first flag.
+        A,
+        /// The second flag.
+        B,
+        /// The third flag.
+        C = 8,
+    }
+);
+
+#[test]
+fn test_atomic_bitset() {
+    let atomic = AtomicBitSet::new(TestFlags::A);
+    assert_eq!(
+        atomic.fetch_insert(TestFlags::B | TestFlags::C, Ordering::Relaxed),
+        TestFlags::A
+    );
+    assert_eq!(atomic.load(Ordering::Relaxed), TestFlags::all());
+    assert_eq!(
+        atomic.fetch_remove(TestFlags::A | TestFlags::C, Ordering::Relaxed),
+        TestFlags::all()
+    );
+    assert!(atomic.test_and_set(TestFlags::B, Ordering::Relaxed));
+    assert!(!atomic.test_and_set(TestFlags::C, Ordering::Relaxed));
+    assert_eq!(atomic.into_inner(), TestFlags::B | TestFlags::C);
+}
+
+#[test]
+fn test_atomic_bitset_keeps_unknown_bits() {
+    let atomic = AtomicBitSet::new(TestFlags::from_repr_retain(0x8003));
+    atomic.fetch_remove(TestFlags::A, Ordering::Relaxed);
+    assert_eq!(u16::from(atomic.load(Ordering::Relaxed)), 0x8002);
+}
+
+/// A counter, to test arithmetic on types wrapping an integer.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+struct Counter(u32);
+
+#[test]
+fn test_arithmetic_ops() {
+    let atomic = Atomic::new(Counter(5));
+    assert_eq!(atomic.fetch_add(Counter(3), Ordering::Relaxed), Counter(5));
+    assert_eq!(atomic.fetch_sub(Counter(10), Ordering::Relaxed), Counter(8));
+    assert_eq!(atomic.load(Ordering::Relaxed), Counter(u32::MAX - 1));
+    assert_eq!(
+        atomic.fetch_add(Counter(2), Ordering::Relaxed),
+        Counter(u32::MAX - 1)
+    );
+    assert_eq!(atomic.load(Ordering::Relaxed), Counter(0));
+}
+
+#[test]
+fn test_fetch_update() {
+    let atomic = Atomic::new(254_u8);
+    let increment = |value: u8| value.checked_add(1);
+    assert_eq!(
+        atomic.fetch_update(Ordering::Relaxed, Ordering::Relaxed, increment),
+        Ok(254)
+    );
+    assert_eq!(
+        atomic.fetch_update(Ordering::Relaxed, Ordering::Relaxed, increment),
+        Err(255)
+    );
+    assert_eq!(atomic.load(Ordering::Relaxed), 255);
+}
+
+#[test]
+fn test_tagged_ptr() {
+    let mut values = [1_u32, 2];
+    let [first, second] = values.each_mut().map(core::ptr::from_mut);
+    assert_eq!(AtomicTaggedPtr::<u32>::TAG_BITS, 2);
+
+    let atomic = AtomicTaggedPtr::new(first, 3);
+    assert_eq!(atomic.load(Ordering::Relaxed), (first, 3));
+    assert_eq!(atomic.swap(second, 4, Ordering::Relaxed), (first, 3));
+    // Tags wrap around to fit in the available bits.
+    assert_eq!(atomic.load(Ordering::Relaxed), (second, 0));
+
+    // A matching pointer with a stale tag doesn't compare equal.
+    assert_eq!(
+        atomic.compare_exchange(
+            (second, 1),
+            (first, 2),
+            Ordering::Relaxed,
+            Ordering::Relaxed
+        ),
+        Err((second, 0))
+    );
+    assert_eq!(
+        atomic.compare_exchange(
+            (second, 0),
+            (first, 1),
+            Ordering::Relaxed,
+            Ordering::Relaxed
+        ),
+        Ok((second, 0))
+    );
+    assert_eq!(atomic.into_inner(), (first, 1));
+
+    let null = AtomicTaggedPtr::<u32>::default();
+    assert_eq!(null.load(Ordering::Relaxed), (core::ptr::null_mut(), 0));
+}
@@ -0,0 +1,71 @@
+//! Testing of [`Bitmap`].
+
+use util::bitmap::Bitmap;
+
+#[test]
+fn test_bitmap_bits() {
+    let mut words = [0_u8; 3];
+    let bitmap = Bitmap::from_mut(&mut words);
+    assert_eq!(bitmap.len(), 24);
+    assert!(!bitmap.set(9, true));
+    assert!(bitmap.set(9, true));
+    assert!(bitmap.get(9));
+    assert!(!bitmap.get(8));
+    assert!(bitmap.set(9, false));
+    assert!(!bitmap.get(9));
+    bitmap.set(0, true);
+    bitmap.set(23, true);
+    assert_eq!(words, [0b1, 0, 0b1000_0000]);
+}
+
+#[test]
+fn test_bitmap_find_first_zero() {
+    let mut words = [0_u16; 2];
+    let bitmap = Bitmap::from_mut(&mut words);
+    assert_eq!(bitmap.find_first_zero(), Some(0));
+    bitmap.set_range(0..20);
+    assert_eq!(bitmap.find_first_zero(), Some(20));
+    bitmap.set(25, true);
+    bitmap.set_range(20..25);
+    assert_eq!(bitmap.find_first_zero(), Some(26));
+    bitmap.set_range(0..32);
+    assert_eq!(bitmap.find_first_zero(), None);
+    assert!(Bitmap::<u64>::from_slice(&[]).find_first_zero().is_none());
+}
+
+#[test]
+fn test_bitmap_find_first_one() {
+    let mut words = [0_u16; 2];
+    let bitmap = Bitmap::from_mut(&mut words);
+    assert_eq!(bitmap.find_first_one(), None);
+    bitmap.set(20, true);
+    bitmap.set(27, true);
+    assert_eq!(bitmap.find_first_one(), Some(20));
+    bitmap.set(20, false);
+    assert_eq!(bitmap.find_first_one(), Some(27));
+    bitmap.set(3, true);
+    assert_eq!(bitmap.find_first_one(), Some(3));
+}
+
+#[test]
+fn test_bitmap_ranges() {
+    let mut words = [0_u8; 4];
+    let bitmap = Bitmap::from_mut(&mut words);
+    bitmap.set_range(3..29);
+    assert_eq!(bitmap.count_ones(), 26);
+    bitmap.clear_range(8..16);
+    bitmap.clear_range(5..5);
+    bitmap.clear_range(4..6);
+    assert_eq!(bitmap.count_ones(), 16);
+    assert_eq!(words, [0b1100_1000, 0, 0xFF, 0b1_1111]);
+
+    let bitmap = Bitmap::from_mut(&mut words);
+    bitmap.set_range(0..32);
+    assert_eq!(words, [0xFF; 4]);
+}
+
+#[test]
+#[should_panic = "out of bounds"]
+fn test_bitmap_range_out_of_bounds() {
+    Bitmap::from_mut(&mut [0_u32]).set_range(30..33);
+}
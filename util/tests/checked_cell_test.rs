@@ -0,0 +1,31 @@
+//! Testing of [`CheckedCell`].
+
+use util::cell::{BorrowError, BorrowMutError, CheckedCell};
+
+#[test]
+fn test_checked_cell() {
+    let mut cell = CheckedCell::new(5_u32);
+    {
+        let first = cell.borrow();
+        let second = cell.try_borrow().expect("Shared borrows should coexist");
+        assert_eq!(*first + *second, 10);
+        assert_eq!(cell.try_borrow_mut().err(), Some(BorrowMutError));
+    }
+    {
+        let mut borrow = cell.borrow_mut();
+        *borrow += 1;
+        assert_eq!(cell.try_borrow().err(), Some(BorrowError));
+        assert_eq!(cell.try_borrow_mut().err(), Some(BorrowMutError));
+    }
+    assert_eq!(*cell.borrow(), 6);
+    *cell.get_mut() += 1;
+    assert_eq!(cell.into_inner(), 7);
+}
+
+#[test]
+#[should_panic = "value is already mutably borrowed"]
+fn test_checked_cell_conflict_panics() {
+    let cell = CheckedCell::new(5_u32);
+    let _borrow = cell.borrow_mut();
+    let _ = cell.borrow();
+}
@@ -0,0 +1,36 @@
+//! Testing of [`HartLocal`].
+
+use core::cell::Cell;
+
+use util::sync::{HartLocal, current_hart_id};
+
+#[test]
+fn test_hart_local() {
+    assert_eq!(current_hart_id(), 0, "The host counts as hart 0");
+    let mut local = HartLocal::new(Cell::new(1));
+    assert_eq!(local.owner(), current_hart_id());
+    local.local().set(2);
+    assert_eq!(local.get().map(Cell::get), Some(2));
+    local.get_mut().set(3);
+    assert_eq!(local.into_inner().get(), 3);
+    assert_eq!(HartLocal::<u32>::default().owner(), current_hart_id());
+}
+
+#[test]
+fn test_hart_local_other_hart() {
+    let mut local = HartLocal::with_owner(1, Cell::new(1));
+    assert!(local.get().is_none());
+    local.get_mut().set(2);
+    assert_eq!(
+        local.into_inner().get(),
+        2,
+        "Exclusive access works from any hart"
+    );
+}
+
+#[test]
+#[should_panic = "owned by hart 1 accessed from hart 0"]
+fn test_hart_local_panics_on_other_hart() {
+    let local = HartLocal::with_owner(1, 0_u32);
+    _ = local.local();
+}
@@ -0,0 +1,92 @@
+//! Testing of [`HashMap`].
+
+use std::hash::{BuildHasher as _, BuildHasherDefault, Hasher, RandomState};
+
+use util::hash_map::HashMap;
+
+/// A hasher which hashes every key to the same value, to test collisions.
+#[derive(Default)]
+struct CollidingHasher;
+impl Hasher for CollidingHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {}
+}
+
+#[test]
+fn test_hash_map() {
+    let mut map = HashMap::<u32, u32, _, 4>::with_hasher(RandomState::new());
+    assert!(map.is_empty());
+    for i in 0..4 {
+        assert_eq!(map.insert(i, i * 10), Ok(None));
+    }
+    assert!(map.is_full());
+    assert_eq!(map.insert(4, 40), Err((4, 40)));
+    assert_eq!(
+        map.insert(2, 25),
+        Ok(Some(20)),
+        "Replacing should work when full"
+    );
+    *map.get_mut(&3).expect("3 was inserted") += 5;
+    assert_eq!(map.get(&3), Some(&35));
+    assert!(!map.contains_key(&4));
+
+    let mut entries = map.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>();
+    entries.sort_unstable();
+    assert_eq!(entries, [(0, 0), (1, 10), (2, 25), (3, 35)]);
+
+    assert_eq!(map.remove(&1), Some(10));
+    assert_eq!(map.remove(&1), None);
+    assert_eq!(map.insert(4, 40), Ok(None));
+    assert_eq!(map.len(), 4);
+    map.clear();
+    assert!(map.is_empty());
+    assert_eq!(map.get(&4), None);
+}
+
+#[test]
+fn test_hash_map_collisions() {
+    let mut map = HashMap::<u32, u32, BuildHasherDefault<CollidingHasher>, 5>::default();
+    for i in 0..5 {
+        assert_eq!(map.insert(i, i), Ok(None));
+    }
+    // Removing from the middle of a probe sequence shouldn't hide the keys after it.
+    assert_eq!(map.remove(&1), Some(1));
+    assert_eq!(map.remove(&3), Some(3));
+    for i in [0, 2, 4] {
+        assert_eq!(map.get(&i), Some(&i));
+    }
+    assert_eq!(map.get(&1), None);
+    assert_eq!(map.insert(1, 11), Ok(None));
+    assert_eq!(map.get(&1), Some(&11));
+}
+
+#[test]
+fn test_hash_map_wrapping_removal() {
+    // Fill every slot with keys which all hash near the end, so probing wraps around, and then
+    // remove each key in turn to check that the shifting keeps everything findable.
+    let hasher = RandomState::new();
+    let mut keys = (0..).filter(|key| hasher.hash_one(key) % 8 >= 6);
+    let keys = [(); 8].map(|()| keys.next().expect("Infinitely many keys"));
+    for removed in keys {
+        let mut map = HashMap::<u32, (), _, 8>::with_hasher(hasher.clone());
+        for key in keys {
+            assert_eq!(map.insert(key, ()), Ok(None));
+        }
+        assert_eq!(map.remove(&removed), Some(()));
+        for key in keys {
+            assert_eq!(map.contains_key(&key), key != removed);
+        }
+    }
+}
+
+#[test]
+fn test_hash_map_borrowed_keys() {
+    let mut map = HashMap::<String, u32, RandomState, 2>::default();
+    assert_eq!(map.insert("one".into(), 1), Ok(None));
+    assert_eq!(map.get("one"), Some(&1));
+    assert_eq!(map.remove("one"), Some(1));
+    assert_eq!(format!("{map:?}"), "{}");
+}
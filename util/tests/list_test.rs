@@ -0,0 +1,108 @@
+//! Testing of [`List`].
+
+use core::{mem::offset_of, ptr::NonNull};
+
+use util::list::{Link, Linked, List};
+
+struct Node {
+    value: u32,
+    link: Link,
+}
+// SAFETY: `link` is a `Link` field of `Node`.
+unsafe impl Linked for Node {
+    const LINK_OFFSET: usize = offset_of!(Node, link);
+}
+
+fn values(list: &List<Node>) -> Vec<u32> {
+    list.iter().map(|node| node.value).collect()
+}
+
+#[test]
+fn test_list_push_pop() {
+    let mut nodes = [1, 2, 3, 4].map(|value| Node {
+        value,
+        link: Link::new(),
+    });
+    let [one, two, three, four] = nodes.each_mut().map(NonNull::from);
+    let mut list = List::new();
+    assert!(list.is_empty());
+    // SAFETY: `nodes` outlives `list`, and isn't otherwise used while `list` is.
+    unsafe {
+        list.push_back(two);
+        list.push_back(three);
+        list.push_front(one);
+        list.push_back(four);
+    }
+    assert_eq!(values(&list), [1, 2, 3, 4]);
+    assert_eq!(list.len(), 4);
+    assert_eq!(list.front().map(|node| node.value), Some(1));
+    assert_eq!(list.back().map(|node| node.value), Some(4));
+
+    // SAFETY: `three` is in `list`.
+    unsafe { list.remove(three) };
+    assert_eq!(values(&list), [1, 2, 4]);
+    assert_eq!(list.pop_front(), Some(one));
+    assert_eq!(list.pop_back(), Some(four));
+    assert_eq!(values(&list), [2]);
+
+    // Removed nodes can go back in.
+    // SAFETY: As above.
+    unsafe { list.push_front(three) };
+    assert_eq!(values(&list), [3, 2]);
+    assert_eq!(list.pop_back(), Some(two));
+    assert_eq!(list.pop_back(), Some(three));
+    assert_eq!(list.pop_back(), None);
+    assert!(list.is_empty());
+}
+
+#[test]
+fn test_list_cursor() {
+    let mut nodes = [1, 2, 3, 4, 5].map(|value| Node {
+        value,
+        link: Link::new(),
+    });
+    let [one, two, three, four, five] = nodes.each_mut().map(NonNull::from);
+    let mut list = List::new();
+    // SAFETY: `nodes` outlives `list`, and isn't otherwise used while `list` is.
+    unsafe {
+        list.push_back(two);
+        list.push_back(four);
+    }
+
+    let mut cursor = list.cursor_front_mut();
+    assert_eq!(cursor.current().map(|node| node.value), Some(2));
+    // SAFETY: As above.
+    unsafe {
+        cursor.insert_before(one);
+        cursor.insert_after(three);
+    }
+    cursor.move_next();
+    cursor.move_next();
+    assert_eq!(cursor.current().map(|node| node.value), Some(4));
+    cursor.move_next();
+    assert!(
+        cursor.current().is_none(),
+        "Should be at the ghost position"
+    );
+    // SAFETY: As above.
+    unsafe { cursor.insert_before(five) };
+    cursor.move_prev();
+    assert_eq!(cursor.current().map(|node| node.value), Some(5));
+    assert_eq!(values(&list), [1, 2, 3, 4, 5]);
+
+    // Remove the even values.
+    let mut cursor = list.cursor_back_mut();
+    cursor.move_next();
+    cursor.move_next();
+    let mut removed = Vec::new();
+    while let Some(node) = cursor.current() {
+        if node.value % 2 == 0 {
+            removed.extend(cursor.remove_current());
+        } else {
+            cursor.move_next();
+        }
+    }
+    assert_eq!(removed, [two, four]);
+    assert_eq!(values(&list), [1, 3, 5]);
+    assert_eq!(list.len(), 3);
+}
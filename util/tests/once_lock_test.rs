@@ -1,6 +1,13 @@
-//! Testing of [`OnceLock`].
+//! Testing of [`OnceLock`] and [`LazyLock`].
 
-use util::cell::OnceLock;
+use std::{
+    panic,
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::Duration,
+};
+
+use util::cell::{LazyLock, OnceLock};
 
 #[test]
 fn test_once_lock() {
@@ -14,3 +21,90 @@ fn test_once_lock() {
     assert_eq!(*lock.get().expect("Should now have a value"), 7);
     assert!(lock.set(8).is_err(), "Should no longer allow setting");
 }
+
+#[test]
+fn test_once_lock_get_or_init() {
+    let lock = OnceLock::<u32>::new();
+    assert_eq!(lock.get_or_try_init(|| Err("failed")), Err("failed"));
+    assert!(
+        lock.get().is_none(),
+        "A failed initializer should leave the value unset"
+    );
+    assert_eq!(*lock.get_or_init(|| 5), 5);
+    assert_eq!(*lock.get_or_init(|| 6), 5);
+    assert_eq!(lock.get_or_try_init(|| Err("failed")), Ok(&5));
+    assert!(lock.set(7).is_err(), "Should no longer allow setting");
+}
+
+#[test]
+fn test_once_lock_init_panics() {
+    let lock = OnceLock::<u32>::new();
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        *lock.get_or_init(|| panic!("Initializer panicked"))
+    }));
+    assert!(result.is_err());
+    assert!(
+        lock.get().is_none(),
+        "A panicking initializer should leave the value unset"
+    );
+    assert_eq!(
+        *lock.get_or_init(|| 5),
+        5,
+        "A panicking initializer shouldn't leave the lock held"
+    );
+}
+
+#[test]
+fn test_lazy_lock_init_panics() {
+    static LAZY: LazyLock<u32> = LazyLock::new(|| panic!("Initializer panicked"));
+    assert!(panic::catch_unwind(|| *LAZY).is_err());
+    assert!(
+        panic::catch_unwind(|| *LAZY).is_err(),
+        "Forcing again should panic rather than hang"
+    );
+}
+
+#[test]
+fn test_once_lock_concurrent_init() {
+    let lock = OnceLock::<u32>::new();
+    let calls = AtomicU32::new(0);
+    thread::scope(|s| {
+        for i in 0..8 {
+            let (lock, calls) = (&lock, &calls);
+            s.spawn(move || {
+                let value = lock.get_or_init(|| {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(Duration::from_millis(10));
+                    i
+                });
+                assert_eq!(lock.get(), Some(value));
+            });
+        }
+    });
+    assert_eq!(
+        calls.load(Ordering::Relaxed),
+        1,
+        "Only one initializer should run"
+    );
+}
+
+#[test]
+fn test_lazy_lock() {
+    static CALLS: AtomicU32 = AtomicU32::new(0);
+    static LAZY: LazyLock<u32> = LazyLock::new(|| {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(10));
+        5
+    });
+    thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| assert_eq!(*LAZY, 5));
+        }
+    });
+    assert_eq!(*LazyLock::force(&LAZY), 5);
+    assert_eq!(
+        CALLS.load(Ordering::Relaxed),
+        1,
+        "Only one initializer should run"
+    );
+}
@@ -0,0 +1,73 @@
+//! Testing of [`RwSpinLock`].
+
+use std::{sync::Barrier, thread};
+
+use util::sync::RwSpinLock;
+
+#[test]
+fn test_rw_spin_lock() {
+    let mut lock = RwSpinLock::new(5_u32);
+    {
+        let first = lock.read();
+        let second = lock.try_read().expect("Readers should share the lock");
+        assert_eq!(*first + *second, 10);
+        assert!(
+            lock.try_write().is_none(),
+            "Writers should wait for readers"
+        );
+    }
+    {
+        let mut guard = lock.write();
+        *guard += 1;
+        assert!(lock.try_read().is_none(), "Readers should wait for writers");
+        assert!(
+            lock.try_write().is_none(),
+            "Writers should wait for writers"
+        );
+    }
+    assert_eq!(*lock.read(), 6);
+    *lock.get_mut() += 1;
+    assert_eq!(lock.into_inner(), 7);
+}
+
+#[test]
+fn test_rw_spin_lock_waiting_writer_blocks_readers() {
+    let lock = RwSpinLock::new(0_u32);
+    let barrier = Barrier::new(2);
+    thread::scope(|s| {
+        let reader = lock.read();
+        s.spawn(|| {
+            barrier.wait();
+            *lock.write() += 1;
+        });
+        barrier.wait();
+        // Once the writer is waiting, new readers have to wait behind it.
+        while lock.try_read().is_some() {
+            thread::yield_now();
+        }
+        drop(reader);
+        assert_eq!(*lock.read(), 1);
+    });
+}
+
+#[test]
+fn test_rw_spin_lock_contention() {
+    const THREADS: u32 = 4;
+    const ITERS: u32 = 1000;
+    let lock = RwSpinLock::new((0_u32, 0_u32));
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    let mut guard = lock.write();
+                    guard.0 += 1;
+                    guard.1 += 1;
+                    drop(guard);
+                    let guard = lock.read();
+                    assert_eq!(guard.0, guard.1, "Readers should never see a partial write");
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), (THREADS * ITERS, THREADS * ITERS));
+}
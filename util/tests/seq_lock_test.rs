@@ -0,0 +1,44 @@
+//! Testing of [`SeqLock`].
+
+use std::thread;
+
+use util::sync::SeqLock;
+
+#[test]
+fn test_seq_lock() {
+    let mut lock = SeqLock::new(5_u32);
+    assert_eq!(lock.read(), 5);
+    assert_eq!(lock.try_read(), Some(5));
+    lock.write(6);
+    lock.update(|value| value + 1);
+    assert_eq!(lock.read(), 7);
+    *lock.get_mut() += 1;
+    assert_eq!(lock.into_inner(), 8);
+
+    // A value which isn't a whole number of words is copied bytewise.
+    let lock = SeqLock::new([1_u8, 2, 3]);
+    lock.update(|[a, b, c]| [c, b, a]);
+    assert_eq!(lock.read(), [3, 2, 1]);
+}
+
+#[test]
+fn test_seq_lock_reads_are_consistent() {
+    const THREADS: u64 = 4;
+    const ITERS: u64 = 1000;
+    let lock = SeqLock::new([0_u64; 4]);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    lock.update(|value| value.map(|x| x + 1));
+                    let value = lock.read();
+                    assert!(
+                        value.iter().all(|&x| x == value[0]),
+                        "Readers should never see a partial write"
+                    );
+                }
+            });
+        }
+    });
+    assert_eq!(lock.read(), [THREADS * ITERS; 4]);
+}
@@ -0,0 +1,94 @@
+//! Testing of [`Ring`].
+
+use std::{rc::Rc, thread};
+
+use util::sync::spsc::Ring;
+
+#[test]
+fn test_ring() {
+    let mut ring = Ring::<u32, 3>::new();
+    assert_eq!(ring.capacity(), 3);
+    let (mut producer, mut consumer) = ring.split();
+    assert_eq!(consumer.pop(), None);
+    // Go around the buffer a few times.
+    for i in 0..10 {
+        assert_eq!(producer.push(i), Ok(()));
+        assert_eq!(producer.push(i + 100), Ok(()));
+        assert_eq!(consumer.pop(), Some(i));
+        assert_eq!(consumer.pop(), Some(i + 100));
+    }
+    assert_eq!(producer.push_slice(&[1, 2, 3, 4]), 3);
+    assert!(producer.is_full());
+    assert_eq!(producer.push(5), Err(5));
+    let mut buf = [0; 2];
+    assert_eq!(consumer.pop_slice(&mut buf), 2);
+    assert_eq!(buf, [1, 2]);
+    assert_eq!(consumer.len(), 1);
+    assert_eq!(consumer.collect::<Vec<_>>(), [3]);
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn test_ring_try_split() {
+    static RING: Ring<u32, 3> = Ring::new();
+    let (mut producer, mut consumer) = RING.try_split().unwrap();
+    assert!(
+        RING.try_split().is_none(),
+        "The halves should only be handed out once"
+    );
+    thread::spawn(move || {
+        for i in 0..10 {
+            while producer.push(i).is_err() {
+                thread::yield_now();
+            }
+        }
+    });
+    let mut values = Vec::new();
+    while values.len() < 10 {
+        match consumer.pop() {
+            Some(value) => values.push(value),
+            None => thread::yield_now(),
+        }
+    }
+    assert_eq!(values, (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_ring_drops_values() {
+    let value = Rc::new(());
+    let mut ring = Ring::<_, 4>::new();
+    let (mut producer, mut consumer) = ring.split();
+    for _ in 0..3 {
+        assert!(producer.push(Rc::clone(&value)).is_ok());
+    }
+    drop(consumer.pop());
+    assert_eq!(Rc::strong_count(&value), 3);
+    drop(ring);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
+
+#[test]
+fn test_ring_threads() {
+    const COUNT: u32 = 10_000;
+    let mut ring = Ring::<u32, 16>::new();
+    let (mut producer, mut consumer) = ring.split();
+    thread::scope(|s| {
+        s.spawn(move || {
+            for i in 0..COUNT {
+                while producer.push(i).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < COUNT {
+            match consumer.pop() {
+                Some(value) => {
+                    assert_eq!(value, expected, "Values should come out in order");
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+    });
+}